@@ -1 +1,366 @@
-fn main() {}
+use std::{fmt::Write, path::PathBuf, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use dhcp::{
+    send_control_request, types::HardwareAddr, ControlError, ControlRequest, ControlResponse,
+    InterfaceCounters, PoolHistory, DEFAULT_CONTROL_SOCKET, DEFAULT_STATUS_SOCKET,
+};
+use tokio::net::UnixStream;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    daemon: Daemon,
+}
+
+#[derive(Subcommand)]
+enum Daemon {
+    /// Controls the DHCP server through its control socket
+    Server {
+        /// Path of the control socket
+        #[arg(long, default_value = DEFAULT_CONTROL_SOCKET)]
+        socket: PathBuf,
+
+        #[command(subcommand)]
+        command: Command,
+    },
+
+    /// Controls the DHCP client through its status socket
+    Client {
+        /// Path of the status socket
+        #[arg(long, default_value = DEFAULT_STATUS_SOCKET)]
+        socket: PathBuf,
+
+        #[command(subcommand)]
+        command: Command,
+    },
+
+    /// Manages the leases of the DHCP server, same as `server leases`
+    Leases {
+        /// Path of the control socket
+        #[arg(long, default_value = DEFAULT_CONTROL_SOCKET)]
+        socket: PathBuf,
+
+        #[command(subcommand)]
+        command: LeasesCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the status of the daemon
+    Status,
+
+    /// Changes the log filter, like "debug" or "info,dhcp::server=trace"
+    LogLevel {
+        filter: String,
+
+        /// Reverts to the default filter after this time, like "90s", "10m"
+        /// or "1h"
+        #[arg(long, value_parser = parse_duration)]
+        revert_after: Option<Duration>,
+    },
+
+    /// Inspects the address pools of the server
+    Pool {
+        #[command(subcommand)]
+        command: PoolCommand,
+    },
+
+    /// Manages the leases of the server
+    Leases {
+        #[command(subcommand)]
+        command: LeasesCommand,
+    },
+
+    /// Reloads the reservations from the config file of the server
+    Reload,
+
+    /// Prints the effective configuration of the server as JSON
+    Config,
+
+    /// Prints all counters of the server in the Prometheus text format
+    Metrics,
+
+    /// Prints the message counters of each interface of the server
+    Interfaces,
+}
+
+#[derive(Subcommand)]
+enum PoolCommand {
+    /// Prints the utilization history and lease churn of a pool
+    History { name: String },
+}
+
+#[derive(Subcommand)]
+enum LeasesCommand {
+    /// Releases the lease of a client right away, e.g. to hand its address
+    /// over to a pending reservation
+    Revoke {
+        /// Hardware address of the client, like "02:00:00:00:00:01"
+        #[arg(value_parser = parse_hardware_addr)]
+        hardware_addr: HardwareAddr,
+    },
+}
+
+/// Characters of the utilization sparkline, from empty to full.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Maximum width of the utilization sparkline. Longer histories are
+/// downsampled by averaging.
+const SPARKLINE_WIDTH: usize = 60;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let (socket, command) = match cli.daemon {
+        Daemon::Server { socket, command } => (socket, command),
+        Daemon::Client { socket, command } => (socket, command),
+        Daemon::Leases { socket, command } => (socket, Command::Leases { command }),
+    };
+
+    let request = match command {
+        Command::Status => ControlRequest::Status,
+        Command::LogLevel {
+            filter,
+            revert_after,
+        } => ControlRequest::SetLogFilter {
+            filter,
+            revert_after: revert_after.map(|d| d.as_secs().try_into().unwrap_or(u32::MAX)),
+        },
+        Command::Pool {
+            command: PoolCommand::History { name },
+        } => ControlRequest::PoolHistory(name),
+        Command::Leases {
+            command: LeasesCommand::Revoke { hardware_addr },
+        } => ControlRequest::ReleaseLease(hardware_addr),
+        Command::Reload => ControlRequest::ReloadConfig,
+        Command::Config => ControlRequest::EffectiveConfig,
+        Command::Metrics => ControlRequest::Metrics,
+        Command::Interfaces => ControlRequest::InterfaceStats,
+    };
+
+    let mut stream = UnixStream::connect(&socket)
+        .await
+        .with_context(|| format!("failed to connect to {}", socket.display()))?;
+
+    let response = match send_control_request(&mut stream, &request).await {
+        Err(ControlError::UnsupportedVersion { local, remote }) if remote > local => {
+            bail!("the daemon speaks control protocol version {remote}, upgrade vulcan-ctl")
+        }
+        Err(ControlError::UnsupportedVersion { remote, .. }) => {
+            bail!("the daemon speaks the outdated control protocol version {remote}, upgrade it")
+        }
+        // Daemons predating the frame header drop the connection
+        Err(ControlError::Closed) => {
+            bail!("the daemon closed the connection, it may need an upgrade")
+        }
+        response => response?,
+    };
+
+    match response {
+        ControlResponse::Status(entries) => {
+            for (key, value) in entries {
+                println!("{key}: {value}");
+            }
+        }
+        ControlResponse::LogFilter { filter, revert_in } => match revert_in {
+            Some(secs) => println!("log filter: {filter} (reverts in {secs}s)"),
+            None => println!("log filter: {filter}"),
+        },
+        ControlResponse::PoolHistory(history) => print!("{}", render_history(&history)),
+        ControlResponse::Config(json) => println!("{json}"),
+        ControlResponse::Metrics(metrics) => print!("{metrics}"),
+        ControlResponse::InterfaceStats(interfaces) => print!("{}", render_interfaces(&interfaces)),
+        ControlResponse::Released(true) => println!("lease revoked"),
+        ControlResponse::Released(false) => bail!("no lease to revoke"),
+        ControlResponse::Error(message) => bail!(message),
+        response => bail!("unexpected response {response:?}"),
+    }
+
+    Ok(())
+}
+
+/// Renders the utilization of `history` as sparkline with a summary,
+/// followed by a table of the lease churn per hour.
+fn render_history(history: &PoolHistory) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "pool {}: {} addresses, one sample every {}s",
+        history.name, history.size, history.resolution
+    );
+
+    let ratios: Vec<f64> = history
+        .samples
+        .iter()
+        .map(|s| s.allocated as f64 / history.size.max(1) as f64)
+        .collect();
+
+    match ratios.last() {
+        Some(now) => {
+            let min = ratios.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = ratios.iter().copied().fold(0.0, f64::max);
+            let avg = ratios.iter().sum::<f64>() / ratios.len() as f64;
+
+            let _ = writeln!(out, "\n{}", sparkline(&ratios));
+            let _ = writeln!(
+                out,
+                "min {:.0}%  avg {:.0}%  max {:.0}%  now {:.0}%",
+                min * 100.0,
+                avg * 100.0,
+                max * 100.0,
+                now * 100.0
+            );
+        }
+        None => {
+            let _ = writeln!(out, "\nno samples yet");
+        }
+    }
+
+    if let Some(last) = history.churn.last() {
+        let _ = writeln!(
+            out,
+            "\n{:>6} {:>8} {:>8} {:>8}",
+            "hour", "granted", "expired", "released"
+        );
+
+        for bucket in &history.churn {
+            let hour = match (last.hour - bucket.hour) / 3600 {
+                0 => String::from("now"),
+                ago => format!("-{ago}h"),
+            };
+            let _ = writeln!(
+                out,
+                "{:>6} {:>8} {:>8} {:>8}",
+                hour, bucket.granted, bucket.expired, bucket.released
+            );
+        }
+    }
+
+    out
+}
+
+/// Renders the message counters of each interface as a table.
+fn render_interfaces(interfaces: &[(String, InterfaceCounters)]) -> String {
+    let mut out = format!(
+        "{:<16} {:>12} {:>12} {:>12}\n",
+        "interface", "received", "dropped", "replies"
+    );
+
+    for (name, counters) in interfaces {
+        let _ = writeln!(
+            out,
+            "{:<16} {:>12} {:>12} {:>12}",
+            name, counters.received, counters.dropped, counters.replies
+        );
+    }
+
+    out
+}
+
+/// Renders `ratios` between zero and one as a line of block characters, at
+/// most [`SPARKLINE_WIDTH`] wide.
+fn sparkline(ratios: &[f64]) -> String {
+    let chunk = ratios.len().div_ceil(SPARKLINE_WIDTH).max(1);
+
+    ratios
+        .chunks(chunk)
+        .map(|chunk| {
+            let avg = chunk.iter().sum::<f64>() / chunk.len() as f64;
+            let level = (avg * SPARKS.len() as f64) as usize;
+            SPARKS[level.min(SPARKS.len() - 1)]
+        })
+        .collect()
+}
+
+/// Parses a duration made of a number and an optional unit, which is one of
+/// `s` (the default), `m` or `h`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (value, multiplier) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 3600),
+        _ => (s, 1),
+    };
+
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration '{s}'"))?;
+
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{s}' is too long"))
+}
+
+fn parse_hardware_addr(s: &str) -> Result<HardwareAddr, String> {
+    HardwareAddr::try_from(s.to_string())
+        .map_err(|err| format!("invalid hardware address '{s}': {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+
+        for invalid in ["", "m", "-1m", "10d", "1.5h"] {
+            assert!(parse_duration(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_leases_shortcut() {
+        let cli =
+            Cli::try_parse_from(["vulcan-ctl", "leases", "revoke", "02:00:00:00:00:01"]).unwrap();
+        assert!(matches!(
+            cli.daemon,
+            Daemon::Leases {
+                socket,
+                command: LeasesCommand::Revoke { .. },
+            } if socket == Path::new(DEFAULT_CONTROL_SOCKET)
+        ));
+
+        assert!(Cli::try_parse_from(["vulcan-ctl", "leases", "status"]).is_err());
+    }
+
+    #[test]
+    fn test_render_interfaces() {
+        let counters = InterfaceCounters {
+            received: 12,
+            dropped: 1,
+            replies: 11,
+        };
+        let table = render_interfaces(&[(String::from("eth0"), counters)]);
+
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("interface"));
+        assert_eq!(
+            lines[1].split_whitespace().collect::<Vec<_>>(),
+            ["eth0", "12", "1", "11"]
+        );
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0.0, 0.25, 0.5, 0.75, 1.0]), "▁▃▅▇█");
+        assert_eq!(sparkline(&[]), "");
+
+        // Long histories are averaged down to the maximum width
+        let ratios: Vec<f64> = (0..1440).map(|i| if i < 720 { 0.0 } else { 1.0 }).collect();
+        let line = sparkline(&ratios);
+        assert_eq!(line.chars().count(), SPARKLINE_WIDTH);
+        assert!(line.starts_with('▁') && line.ends_with('█'));
+    }
+}
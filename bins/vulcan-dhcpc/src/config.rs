@@ -1,9 +1,16 @@
 use std::{
     fs,
+    net::{AddrParseError, Ipv4Addr, SocketAddr},
+    ops::Range,
     path::PathBuf,
+    str::FromStr,
     time::{self, Duration},
 };
 
+use dhcp::{
+    InterfaceGonePolicy, LogToggles, CLIENT_PORT, DEFAULT_INITIAL_DELAY_MAX_SECS,
+    DEFAULT_INITIAL_DELAY_MIN_SECS, DEFAULT_MAX_INIT_BACKOFF_SECS, DEFAULT_ROUTE_METRIC,
+};
 use serde::Deserialize;
 use thiserror::Error;
 use toml;
@@ -15,6 +22,9 @@ pub enum ConfigError {
 
     #[error("Error while reading TOML config file: {0}")]
     Read(#[from] std::io::Error),
+
+    #[error("Invalid bind address '{0}': {1}")]
+    InvalidBindAddress(String, AddrParseError),
 }
 
 #[derive(Deserialize, Default)]
@@ -24,6 +34,24 @@ pub struct RawConfig {
     write_timeout: u64,
     bind_timeout: u64,
     read_timeout: u64,
+    packet_socket: bool,
+    route_metric: Option<u32>,
+    max_discover_retries: Option<u32>,
+    min_initial_delay_ms: Option<u64>,
+    max_initial_delay_ms: Option<u64>,
+    max_init_backoff_secs: Option<u64>,
+    strict: bool,
+    mtu_check: Option<bool>,
+    socket_filter: Option<bool>,
+    wait_for_interface: bool,
+    bind_address: Option<String>,
+    port: Option<u16>,
+    state_dir: Option<PathBuf>,
+    status_socket: Option<PathBuf>,
+    log_filter: Option<String>,
+    log_packets: Option<bool>,
+    log_leases: Option<bool>,
+    log_fsm: Option<bool>,
 }
 
 pub struct Config {
@@ -31,17 +59,70 @@ pub struct Config {
     pub write_timeout: time::Duration,
     pub bind_timeout: time::Duration,
     pub read_timeout: time::Duration,
+    pub packet_socket: bool,
+    pub route_metric: u32,
+    pub max_discover_retries: u32,
+    pub initial_delay: Range<Duration>,
+    pub max_init_backoff: Duration,
+    pub strict: bool,
+    pub mtu_check: bool,
+    pub socket_filter: bool,
+    pub interface_gone_policy: InterfaceGonePolicy,
+    pub bind_address: SocketAddr,
+    pub state_dir: Option<PathBuf>,
+    pub status_socket: Option<PathBuf>,
+    pub log_filter: Option<String>,
+    pub log_toggles: LogToggles,
 }
 
 impl TryFrom<RawConfig> for Config {
     type Error = ConfigError;
 
     fn try_from(value: RawConfig) -> Result<Self, Self::Error> {
+        let bind_address = parse_bind_address(
+            value.bind_address.as_deref(),
+            value.port.unwrap_or(CLIENT_PORT),
+        )?;
+
         Ok(Self {
+            bind_address,
             write_timeout: Duration::from_secs(value.write_timeout),
             bind_timeout: Duration::from_secs(value.bind_timeout),
             read_timeout: Duration::from_secs(value.read_timeout),
+            packet_socket: value.packet_socket,
+            route_metric: value.route_metric.unwrap_or(DEFAULT_ROUTE_METRIC),
+            max_discover_retries: value.max_discover_retries.unwrap_or(1),
+            initial_delay: Duration::from_millis(
+                value
+                    .min_initial_delay_ms
+                    .unwrap_or(DEFAULT_INITIAL_DELAY_MIN_SECS * 1000),
+            )
+                ..Duration::from_millis(
+                    value
+                        .max_initial_delay_ms
+                        .unwrap_or(DEFAULT_INITIAL_DELAY_MAX_SECS * 1000),
+                ),
+            max_init_backoff: Duration::from_secs(
+                value
+                    .max_init_backoff_secs
+                    .unwrap_or(DEFAULT_MAX_INIT_BACKOFF_SECS),
+            ),
+            strict: value.strict,
+            mtu_check: value.mtu_check.unwrap_or(true),
+            socket_filter: value.socket_filter.unwrap_or(true),
+            interface_gone_policy: match value.wait_for_interface {
+                true => InterfaceGonePolicy::Wait,
+                false => InterfaceGonePolicy::Exit,
+            },
             interface: value.interface,
+            state_dir: value.state_dir,
+            status_socket: value.status_socket,
+            log_filter: value.log_filter,
+            log_toggles: LogToggles {
+                packets: value.log_packets,
+                leases: value.log_leases,
+                fsm: value.log_fsm,
+            },
         })
     }
 }
@@ -49,8 +130,67 @@ impl TryFrom<RawConfig> for Config {
 impl Config {
     pub fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
         let b = fs::read_to_string(path)?;
-        let c: RawConfig = toml::from_str(&b)?;
+        b.parse()
+    }
+}
 
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let c: RawConfig = toml::from_str(s)?;
         Self::try_from(c)
     }
 }
+
+/// Combines the configured bind address (all addresses if unset) and port
+/// into a socket address.
+fn parse_bind_address(addr: Option<&str>, port: u16) -> Result<SocketAddr, ConfigError> {
+    let addr = match addr {
+        Some(addr) => addr
+            .parse::<Ipv4Addr>()
+            .map_err(|err| ConfigError::InvalidBindAddress(addr.to_string(), err))?,
+        None => Ipv4Addr::UNSPECIFIED,
+    };
+
+    Ok(SocketAddr::from((addr, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bind_address() {
+        let config: Config = "interface = \"eth0\"".parse().unwrap();
+        assert_eq!(
+            config.bind_address,
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, CLIENT_PORT))
+        );
+    }
+
+    #[test]
+    fn test_init_delay() {
+        let config: Config = "interface = \"eth0\"".parse().unwrap();
+        assert_eq!(
+            config.initial_delay,
+            Duration::from_secs(1)..Duration::from_secs(10)
+        );
+        assert_eq!(config.max_init_backoff, Duration::from_secs(64));
+
+        let config: Config =
+            "min_initial_delay_ms = 0\nmax_initial_delay_ms = 0\nmax_init_backoff_secs = 8"
+                .parse()
+                .unwrap();
+        assert_eq!(config.initial_delay, Duration::ZERO..Duration::ZERO);
+        assert_eq!(config.max_init_backoff, Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_invalid_bind_address() {
+        assert!(matches!(
+            "bind_address = \"::1\"".parse::<Config>(),
+            Err(ConfigError::InvalidBindAddress(addr, _)) if addr == "::1"
+        ));
+    }
+}
@@ -2,9 +2,7 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::Parser;
-use dhcp::Client;
-use tracing::Level;
-use tracing_subscriber::FmtSubscriber;
+use dhcp::prelude::{Client, ClientBuilder};
 
 use crate::config::Config;
 
@@ -28,22 +26,67 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     let config = Config::from_file(cli.config)?;
 
-    // Build stdout subscriber
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .finish();
-
-    // Set the above subscriber as the default one
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+    // Log to stdout, the filter can be changed through the status socket
+    let log_filter = config.log_filter.as_deref().unwrap_or("debug");
+    let log_control = dhcp::init_logging(&config.log_toggles.apply(log_filter))?;
 
     // Build and run client
-    let mut client = Client::builder()
+    let mut client = client_builder(config)
+        .with_log_control(log_control)
+        .build()?;
+
+    client.run().await?;
+    Ok(())
+}
+
+/// Applies the settings of the config file to a new client builder.
+fn client_builder(config: Config) -> ClientBuilder {
+    let builder = Client::builder()
         .with_write_timeout(config.write_timeout)
         .with_bind_timeout(config.bind_timeout)
         .with_read_timeout(config.read_timeout)
         .with_interface_name(config.interface)
-        .build()?;
+        .with_packet_socket(config.packet_socket)
+        .with_route_metric(config.route_metric)
+        .with_max_discover_retries(config.max_discover_retries)
+        .with_initial_delay(config.initial_delay)
+        .with_max_init_backoff(config.max_init_backoff)
+        .with_strict(config.strict)
+        .with_mtu_check(config.mtu_check)
+        .with_socket_filter(config.socket_filter)
+        .with_interface_gone_policy(config.interface_gone_policy)
+        .with_bind_address(config.bind_address);
 
-    client.run().await?;
-    Ok(())
+    let builder = match config.state_dir {
+        Some(path) => builder.with_state_dir(path),
+        None => builder,
+    };
+
+    match config.status_socket {
+        Some(path) => builder.with_status_socket(path),
+        None => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use super::*;
+
+    #[test]
+    fn test_bind_address_reaches_builder() {
+        let config: Config = r#"
+interface = "eth0"
+bind_address = "10.0.0.2"
+port = 1068
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            client_builder(config).bind_address(),
+            SocketAddr::from((Ipv4Addr::new(10, 0, 0, 2), 1068))
+        );
+    }
 }
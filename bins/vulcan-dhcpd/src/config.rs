@@ -1,5 +1,15 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    net::{AddrParseError, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
 
+use dhcp::{
+    types::{HardwareAddr, ParseHardwareAddrError},
+    CacheLimits, HostnameConflictPolicy, HostnameHandling, LogToggles, Reservation, VendorProfile,
+    DEFAULT_RELAY_MAX_MESSAGE_SIZE, SERVER_PORT,
+};
 use serde::Deserialize;
 use thiserror::Error;
 
@@ -10,6 +20,12 @@ pub enum ConfigError {
 
     #[error("Error while deserializing TOML: {0}")]
     Deserialize(#[from] toml::de::Error),
+
+    #[error("Invalid bind address '{0}': {1}")]
+    InvalidBindAddress(String, AddrParseError),
+
+    #[error("Invalid hardware address '{0}' in reservation: {1}")]
+    InvalidReservation(String, ParseHardwareAddrError),
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,6 +34,32 @@ pub struct RawConfig {
     pub server: RawServerOptions,
     pub rebind_time: u32,
     pub renew_time: u32,
+    pub domain_name: Option<String>,
+    pub domain_search: Option<Vec<String>>,
+    pub synthesize_domain_search: Option<bool>,
+    pub allow_foreign_pools: Option<bool>,
+    pub next_server: Option<Ipv4Addr>,
+    pub boot_file: Option<String>,
+    pub defensive_mode: Option<bool>,
+    pub strict: Option<bool>,
+    pub trusted_relays: Option<Vec<Ipv4Addr>>,
+    pub subnet_mask: Option<Ipv4Addr>,
+    pub inform_unknown_subnets: Option<bool>,
+    pub relay_max_message_size: Option<u16>,
+    pub socket_filter: Option<bool>,
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+    pub control_socket: Option<PathBuf>,
+    pub log_filter: Option<String>,
+    pub log_packets: Option<bool>,
+    pub log_leases: Option<bool>,
+    pub hostname_handling: Option<HostnameHandling>,
+    pub hostname_conflict_policy: Option<HostnameConflictPolicy>,
+    pub cache_limits: Option<CacheLimits>,
+    pub audit: Option<AuditConfig>,
+    pub pools: Option<Vec<PoolConfig>>,
+    pub reservations: Option<Vec<ReservationConfig>>,
+    pub vendor_profile: Option<Vec<VendorProfile>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,12 +67,16 @@ pub struct RawStorageOptions {
     #[serde(rename = "type")]
     ty: StorageType,
     path: PathBuf,
+    require_persistence: Option<bool>,
 }
 
 #[derive(Debug)]
 pub struct StorageOptions {
     ty: StorageType,
-    path: PathBuf,
+    pub path: PathBuf,
+
+    /// Refuse new leases while the leases can't be written.
+    pub require_persistence: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,17 +85,54 @@ pub enum StorageType {
     File,
 }
 
+/// An address pool, e.g. `range = "192.168.1.100-192.168.1.200"`.
+#[derive(Debug, Deserialize)]
+pub struct PoolConfig {
+    pub name: String,
+    pub range: String,
+    pub lease_time: Option<u32>,
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+}
+
+/// Startup address-conflict audit, enabled by the presence of the `[audit]`
+/// table. Free addresses are probed with ICMP echo requests, which requires
+/// `CAP_NET_RAW`.
+#[derive(Debug, Deserialize)]
+pub struct AuditConfig {
+    /// Probes per second, defaults to [`dhcp::DEFAULT_AUDIT_RATE`].
+    pub rate: Option<u32>,
+
+    /// Number of free addresses probed per pool, all if unset.
+    pub sample_size: Option<usize>,
+
+    /// Time to wait for an echo reply in milliseconds, defaults to
+    /// [`dhcp::DEFAULT_PROBE_TIMEOUT`].
+    pub probe_timeout_ms: Option<u64>,
+}
+
+/// A reserved address, e.g. `hardware_addr = "02:00:00:00:00:01"` and
+/// `ip_addr = "192.168.1.150"`. The address has to be part of a pool.
+#[derive(Debug, Deserialize)]
+pub struct ReservationConfig {
+    pub hardware_addr: String,
+    pub ip_addr: Ipv4Addr,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RawServerOptions {
     interface: String,
     write_timeout: u64,
     bind_timeout: u64,
     read_timeout: u64,
+    bind_address: Option<String>,
+    port: Option<u16>,
 }
 
 #[derive(Debug)]
 pub struct ServerOptions {
-    interface: String,
+    pub interface: String,
+    pub bind_address: SocketAddr,
     write_timeout: u64,
     bind_timeout: u64,
     read_timeout: u64,
@@ -61,25 +144,95 @@ pub struct Config {
     pub server: ServerOptions,
     pub rebind_time: u32,
     pub renew_time: u32,
+    pub domain_name: Option<String>,
+    pub domain_search: Option<Vec<String>>,
+    pub synthesize_domain_search: bool,
+    pub allow_foreign_pools: bool,
+    pub next_server: Option<Ipv4Addr>,
+    pub boot_file: Option<String>,
+    pub defensive_mode: bool,
+    pub strict: bool,
+    pub trusted_relays: Vec<Ipv4Addr>,
+    pub subnet_mask: Option<Ipv4Addr>,
+    pub inform_unknown_subnets: bool,
+    pub relay_max_message_size: u16,
+    pub socket_filter: bool,
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+    pub control_socket: Option<PathBuf>,
+    pub log_filter: Option<String>,
+    pub log_toggles: LogToggles,
+    pub hostname_handling: HostnameHandling,
+    pub hostname_conflict_policy: HostnameConflictPolicy,
+    pub cache_limits: CacheLimits,
+    pub audit: Option<AuditConfig>,
+    pub pools: Vec<PoolConfig>,
+    pub reservations: Vec<Reservation>,
+    pub vendor_profiles: Vec<VendorProfile>,
 }
 
 impl TryFrom<RawConfig> for Config {
     type Error = ConfigError;
 
     fn try_from(value: RawConfig) -> Result<Self, Self::Error> {
+        let bind_address = parse_bind_address(
+            value.server.bind_address.as_deref(),
+            value.server.port.unwrap_or(SERVER_PORT),
+        )?;
+
+        let reservations = value
+            .reservations
+            .unwrap_or_default()
+            .into_iter()
+            .map(parse_reservation)
+            .collect::<Result<_, _>>()?;
+
         Ok(Self {
             storage: StorageOptions {
                 ty: value.storage.ty,
                 path: value.storage.path,
+                require_persistence: value.storage.require_persistence.unwrap_or(false),
             },
             server: ServerOptions {
                 interface: value.server.interface,
+                bind_address,
                 write_timeout: value.server.write_timeout,
                 bind_timeout: value.server.bind_timeout,
                 read_timeout: value.server.read_timeout,
             },
             rebind_time: value.rebind_time,
             renew_time: value.renew_time,
+            domain_name: value.domain_name,
+            domain_search: value.domain_search,
+            synthesize_domain_search: value.synthesize_domain_search.unwrap_or(true),
+            allow_foreign_pools: value.allow_foreign_pools.unwrap_or(false),
+            next_server: value.next_server,
+            boot_file: value.boot_file,
+            defensive_mode: value.defensive_mode.unwrap_or(false),
+            strict: value.strict.unwrap_or(false),
+            trusted_relays: value.trusted_relays.unwrap_or_default(),
+            subnet_mask: value.subnet_mask,
+            inform_unknown_subnets: value.inform_unknown_subnets.unwrap_or(false),
+            socket_filter: value.socket_filter.unwrap_or(true),
+            relay_max_message_size: value
+                .relay_max_message_size
+                .unwrap_or(DEFAULT_RELAY_MAX_MESSAGE_SIZE),
+            time_servers: value.time_servers,
+            ntp_servers: value.ntp_servers,
+            control_socket: value.control_socket,
+            log_filter: value.log_filter,
+            log_toggles: LogToggles {
+                packets: value.log_packets,
+                leases: value.log_leases,
+                fsm: None,
+            },
+            hostname_handling: value.hostname_handling.unwrap_or_default(),
+            hostname_conflict_policy: value.hostname_conflict_policy.unwrap_or_default(),
+            cache_limits: value.cache_limits.unwrap_or_default(),
+            audit: value.audit,
+            pools: value.pools.unwrap_or_default(),
+            reservations,
+            vendor_profiles: value.vendor_profile.unwrap_or_default(),
         })
     }
 }
@@ -87,8 +240,152 @@ impl TryFrom<RawConfig> for Config {
 impl Config {
     pub fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
         let b = fs::read_to_string(path)?;
-        let c: RawConfig = toml::from_str(&b)?;
+        b.parse()
+    }
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
 
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let c: RawConfig = toml::from_str(s)?;
         Self::try_from(c)
     }
 }
+
+/// Combines the configured bind address (all addresses if unset) and port
+/// into a socket address.
+fn parse_bind_address(addr: Option<&str>, port: u16) -> Result<SocketAddr, ConfigError> {
+    let addr = match addr {
+        Some(addr) => addr
+            .parse::<Ipv4Addr>()
+            .map_err(|err| ConfigError::InvalidBindAddress(addr.to_string(), err))?,
+        None => Ipv4Addr::UNSPECIFIED,
+    };
+
+    Ok(SocketAddr::from((addr, port)))
+}
+
+fn parse_reservation(config: ReservationConfig) -> Result<Reservation, ConfigError> {
+    let hardware_addr = HardwareAddr::try_from(config.hardware_addr.clone())
+        .map_err(|err| ConfigError::InvalidReservation(config.hardware_addr, err))?;
+
+    Ok(Reservation {
+        hardware_addr,
+        ip_addr: config.ip_addr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use dhcp::VendorPrecedence;
+
+    use super::*;
+
+    fn config(server: &str) -> Result<Config, ConfigError> {
+        format!(
+            r#"
+rebind_time = 2000
+renew_time = 1000
+
+[server]
+interface = "eth0"
+write_timeout = 2
+bind_timeout = 2
+read_timeout = 2
+{server}
+
+[storage]
+type = "file"
+path = "/var/lib/vulcan/dhcpd.leases"
+"#
+        )
+        .parse()
+    }
+
+    #[test]
+    fn test_default_bind_address() {
+        let config = config("").unwrap();
+        assert_eq!(
+            config.server.bind_address,
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, SERVER_PORT))
+        );
+    }
+
+    #[test]
+    fn test_custom_port() {
+        let config = config("port = 1067").unwrap();
+        assert_eq!(
+            config.server.bind_address,
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 1067))
+        );
+    }
+
+    #[test]
+    fn test_reservations() {
+        let reserved = config(
+            r#"
+[[reservations]]
+hardware_addr = "02:00:00:00:00:01"
+ip_addr = "192.168.1.150"
+"#,
+        )
+        .unwrap();
+        assert_eq!(reserved.reservations.len(), 1);
+        assert_eq!(
+            reserved.reservations[0].ip_addr,
+            Ipv4Addr::new(192, 168, 1, 150)
+        );
+
+        assert!(matches!(
+            config("[[reservations]]\nhardware_addr = \"02:zz\"\nip_addr = \"192.168.1.150\""),
+            Err(ConfigError::InvalidReservation(addr, _)) if addr == "02:zz"
+        ));
+    }
+
+    #[test]
+    fn test_vendor_profiles() {
+        let config = config(
+            r#"
+[[vendor_profile]]
+enterprise_number = 3561
+class = "acme-phone"
+precedence = "both"
+
+[[vendor_profile.sub_options]]
+code = 1
+data = [104, 116, 116, 112]
+"#,
+        )
+        .unwrap();
+
+        let expected = VendorProfile::new(3561)
+            .with_class(String::from("acme-phone"))
+            .with_sub_option(1, b"http".to_vec())
+            .with_precedence(VendorPrecedence::Both);
+        assert_eq!(config.vendor_profiles, vec![expected]);
+    }
+
+    #[test]
+    fn test_cache_limits() {
+        assert_eq!(config("").unwrap().cache_limits, CacheLimits::default());
+
+        // Limits missing from the table keep their default
+        let limited = config("[cache_limits]\noffers = 1000").unwrap();
+        assert_eq!(
+            limited.cache_limits,
+            CacheLimits {
+                offers: 1000,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_bind_address() {
+        assert!(matches!(
+            config("bind_address = \"192.168.1\""),
+            Err(ConfigError::InvalidBindAddress(addr, _)) if addr == "192.168.1"
+        ));
+    }
+}
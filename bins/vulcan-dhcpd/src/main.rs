@@ -1,8 +1,12 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use anyhow::Result;
-use clap::Parser;
-use dhcp::Server;
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use dhcp::{
+    prelude::{Server, ServerBuilder},
+    EffectiveConfig, IcmpProber, DEFAULT_AUDIT_RATE, DEFAULT_LOG_FILTER, DEFAULT_PROBE_TIMEOUT,
+};
+use tokio::signal::unix::{signal, SignalKind};
 
 use crate::config::Config;
 
@@ -23,17 +27,401 @@ struct Cli {
     /// Enables verbose output on STDOUT
     #[arg(short, long)]
     verbose: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Inspects the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Prints the fully resolved configuration the server would run with
+    Dump {
+        #[arg(long, value_enum, default_value_t = DumpFormat::Toml)]
+        format: DumpFormat,
+    },
+
+    /// Validates the configuration and lists reservations which don't take
+    /// effect yet because of running leases
+    Check,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DumpFormat {
+    Toml,
+    Json,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let cfg = Config::from_file(cli.config)?;
+    let cfg = Config::from_file(cli.config.clone())?;
+
+    // Servers without pools only answer DHCPINFORMs, which is almost never
+    // what a config file without pools means
+    if cfg.pools.is_empty() {
+        bail!("at least one pool is required");
+    }
 
-    let mut srv = Server::builder()
+    match cli.command {
+        Some(Command::Config(ConfigCommand::Dump { format })) => {
+            let srv = server_builder(cfg).build()?;
+            print!("{}", dump_config(&srv.effective_config(), format)?);
+            return Ok(());
+        }
+        Some(Command::Config(ConfigCommand::Check)) => {
+            let srv = server_builder(cfg).build()?;
+            println!("configuration ok");
+            for conflict in srv.reservation_conflicts() {
+                println!("pending reservation: {conflict}");
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // The filter can be changed at runtime through the control socket, it
+    // falls back to this one
+    let log_filter = match (cli.verbose, cfg.log_filter.as_deref()) {
+        (true, _) => "debug",
+        (false, Some(filter)) => filter,
+        (false, None) => DEFAULT_LOG_FILTER,
+    };
+    let log_control = dhcp::init_logging(&cfg.log_toggles.apply(log_filter))?;
+
+    // Reservations are re-read from the config file on reload requests
+    let path = cli.config;
+    let mut srv = server_builder(cfg)
+        .with_log_control(log_control)
+        .with_reservation_reload(move || {
+            Config::from_file(path.clone())
+                .map(|cfg| cfg.reservations)
+                .map_err(|err| err.to_string())
+        })
+        .build()?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async {
+        // Shut down gracefully on SIGINT and SIGTERM, which persists the
+        // leases before exiting
+        let mut terminate = signal(SignalKind::terminate())?;
+        let shutdown = srv.shutdown_handle();
+
+        tokio::spawn(async move {
+            tokio::select! {
+                Ok(()) = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            shutdown.shutdown();
+        });
+
+        Ok(srv.run_async().await?)
+    })
+}
+
+/// Serializes the effective configuration in `format`.
+fn dump_config(config: &EffectiveConfig, format: DumpFormat) -> Result<String> {
+    let dump = match format {
+        DumpFormat::Toml => toml::to_string_pretty(config)?,
+        DumpFormat::Json => serde_json::to_string_pretty(config)? + "\n",
+    };
+
+    Ok(dump)
+}
+
+/// Applies the settings of the config file to a new server builder.
+fn server_builder(cfg: Config) -> ServerBuilder {
+    let mut builder = Server::builder()
         .with_rebind_time(cfg.rebind_time)
         .with_renew_time(cfg.renew_time)
-        .build()?;
+        .with_synthesize_domain_search(cfg.synthesize_domain_search)
+        .with_allow_foreign_pools(cfg.allow_foreign_pools)
+        .with_defensive_mode(cfg.defensive_mode)
+        .with_strict(cfg.strict)
+        .with_trusted_relays(cfg.trusted_relays)
+        .with_inform_unknown_subnets(cfg.inform_unknown_subnets)
+        .with_relay_max_message_size(cfg.relay_max_message_size)
+        .with_socket_filter(cfg.socket_filter)
+        .with_hostname_handling(cfg.hostname_handling)
+        .with_hostname_conflict_policy(cfg.hostname_conflict_policy)
+        .with_cache_limits(cfg.cache_limits)
+        .with_require_persistence(cfg.storage.require_persistence)
+        .with_state_file(cfg.storage.path)
+        .with_bind_address(cfg.server.bind_address)
+        .with_interface(cfg.server.interface);
+
+    if let Some(domain_name) = cfg.domain_name {
+        builder = builder.with_domain_name(domain_name);
+    }
+
+    if let Some(domain_search) = cfg.domain_search {
+        builder = builder.with_domain_search(domain_search);
+    }
+
+    if let Some(next_server) = cfg.next_server {
+        builder = builder.with_next_server(next_server);
+    }
+
+    if let Some(subnet_mask) = cfg.subnet_mask {
+        builder = builder.with_subnet_mask(subnet_mask);
+    }
+
+    if let Some(boot_file) = cfg.boot_file {
+        builder = builder.with_boot_file(boot_file);
+    }
+
+    if let Some(time_servers) = cfg.time_servers {
+        builder = builder.with_time_servers(time_servers);
+    }
+
+    if let Some(ntp_servers) = cfg.ntp_servers {
+        builder = builder.with_ntp_servers(ntp_servers);
+    }
+
+    for profile in cfg.vendor_profiles {
+        builder = builder.with_vendor_profile(profile);
+    }
+
+    if let Some(audit) = cfg.audit {
+        let timeout = audit
+            .probe_timeout_ms
+            .map_or(DEFAULT_PROBE_TIMEOUT, Duration::from_millis);
+
+        builder = builder
+            .with_startup_audit(Arc::new(IcmpProber::new(timeout)))
+            .with_audit_rate(audit.rate.unwrap_or(DEFAULT_AUDIT_RATE));
+
+        if let Some(size) = audit.sample_size {
+            builder = builder.with_audit_sample_size(size);
+        }
+    }
+
+    if let Some(control_socket) = cfg.control_socket {
+        builder = builder.with_control_socket(control_socket);
+    }
+
+    for reservation in cfg.reservations {
+        builder = builder.with_reservation(reservation.hardware_addr, reservation.ip_addr);
+    }
+
+    for pool in cfg.pools {
+        if let Some(lease_time) = pool.lease_time {
+            builder = builder.with_pool_lease_time(pool.name.clone(), lease_time);
+        }
+
+        if let Some(time_servers) = pool.time_servers {
+            builder = builder.with_pool_time_servers(pool.name.clone(), time_servers);
+        }
+
+        if let Some(ntp_servers) = pool.ntp_servers {
+            builder = builder.with_pool_ntp_servers(pool.name.clone(), ntp_servers);
+        }
+
+        builder = builder.with_pool(pool.name, pool.range);
+    }
+
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use super::*;
+
+    #[test]
+    fn test_bind_address_reaches_builder() {
+        let cfg: Config = r#"
+rebind_time = 2000
+renew_time = 1000
+
+[server]
+interface = "eth0"
+write_timeout = 2
+bind_timeout = 2
+read_timeout = 2
+bind_address = "192.168.1.1"
+port = 1067
+
+[storage]
+type = "file"
+path = "/var/lib/vulcan/dhcpd.leases"
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            server_builder(cfg).bind_address(),
+            SocketAddr::from((Ipv4Addr::new(192, 168, 1, 1), 1067))
+        );
+    }
+
+    #[test]
+    fn test_reservation_outside_pools() {
+        let cfg: Config = r#"
+rebind_time = 2000
+renew_time = 1000
+
+[server]
+interface = "eth0"
+write_timeout = 2
+bind_timeout = 2
+read_timeout = 2
+
+[storage]
+type = "file"
+path = "/nonexistent/vulcan/dhcpd.leases"
+
+[[pools]]
+name = "lan"
+range = "192.168.1.100-192.168.1.200"
+
+[[reservations]]
+hardware_addr = "02:00:00:00:00:01"
+ip_addr = "192.168.2.150"
+"#
+        .parse()
+        .unwrap();
+
+        assert!(server_builder(cfg).build().is_err());
+    }
+
+    #[test]
+    fn test_audit_reaches_builder() {
+        let cfg: Config = r#"
+rebind_time = 2000
+renew_time = 1000
+allow_foreign_pools = true
+
+[server]
+interface = "eth0"
+write_timeout = 2
+bind_timeout = 2
+read_timeout = 2
+
+[storage]
+type = "file"
+path = "/nonexistent/vulcan/dhcpd.leases"
+
+[[pools]]
+name = "lan"
+range = "192.168.1.100-192.168.1.200"
+
+[audit]
+rate = 100
+sample_size = 20
+"#
+        .parse()
+        .unwrap();
+
+        let config = server_builder(cfg).build().unwrap().effective_config();
+        assert_eq!(config.audit_rate, 100);
+        assert_eq!(config.audit_sample_size, Some(20));
+    }
+
+    #[test]
+    fn test_config_dump() {
+        let cfg: Config = r#"
+rebind_time = 2000
+renew_time = 1000
+ntp_servers = ["192.168.1.1"]
+allow_foreign_pools = true
+
+[server]
+interface = "eth0"
+write_timeout = 2
+bind_timeout = 2
+read_timeout = 2
+
+[storage]
+type = "file"
+path = "/nonexistent/vulcan/dhcpd.leases"
+
+[[pools]]
+name = "lan"
+range = "192.168.1.100-192.168.1.200"
+
+[[pools]]
+name = "guest"
+range = "192.168.1.210-192.168.1.250"
+lease_time = 600
+"#
+        .parse()
+        .unwrap();
+
+        let srv = server_builder(cfg).build().unwrap();
+        let dump = dump_config(&srv.effective_config(), DumpFormat::Toml).unwrap();
+
+        assert_eq!(
+            dump,
+            r#"server_identifier = '0.0.0.0'
+bind_address = '0.0.0.0:67'
+interfaces = ['eth0']
+lease_time = 3600
+renew_time = 1000
+rebind_time = 2000
+send_times = true
+synthesize_domain_search = true
+ntp_servers = ['192.168.1.1']
+hostname_handling = 'sanitize'
+hostname_conflict_policy = 'suffix'
+inform_unknown_subnets = false
+nak_unknown_renewals = true
+check_renewal_source = false
+option_overload = false
+relay_max_message_size = 1500
+socket_filter = true
+strict = false
+trusted_relays = []
+offer_timeout = 60
+defensive_mode = false
+defensive_offer_timeout = 5
+handler_timeout = 5
+max_concurrent = 1024
+audit_rate = 10
+reclaim_grace = 60
+reclaim_probation = 300
+lease_retention = 86400
+
+[cache_limits]
+discover_cache = 16384
+offers = 16384
+known_clients = 16384
+replay_counters = 16384
+transactions = 16384
+
+[storage]
+backend = 'file'
+path = '/nonexistent/vulcan/dhcpd.leases'
+require_persistence = false
+
+[[pools]]
+name = 'lan'
+start = '192.168.1.100'
+end = '192.168.1.200'
+lease_time = 3600
+renew_time = 1000
+rebind_time = 2000
+ntp_servers = ['192.168.1.1']
 
-    Ok(srv.run()?)
+[[pools]]
+name = 'guest'
+start = '192.168.1.210'
+end = '192.168.1.250'
+lease_time = 600
+renew_time = 300
+rebind_time = 525
+ntp_servers = ['192.168.1.1']
+"#
+        );
+    }
 }
@@ -0,0 +1,130 @@
+//! Measures the effect of the socket filter, see
+//! [`ServerBuilder::with_socket_filter`](dhcp::ServerBuilder::with_socket_filter).
+//!
+//! A server listening on the loopback interface is flooded with datagrams
+//! which can't be DHCP messages, followed by a single DHCPDISCOVER. For each
+//! run, the number of datagrams which reached the server (each of them a
+//! wakeup of the listener) and the time until the DISCOVER was handled are
+//! reported:
+//!
+//! ```shell
+//! cargo bench -p dhcp --bench socket_filter -- 100000
+//! ```
+use std::{
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use dhcp::{types::HardwareAddr, MessageBuilder, Server, ServerStats};
+
+/// Number of junk datagrams sent per run, unless passed as an argument.
+const DEFAULT_FLOOD: usize = 50_000;
+
+/// Time to wait for the server to handle the DHCPDISCOVER.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval between retransmissions of the DHCPDISCOVER.
+const RESEND_INTERVAL: Duration = Duration::from_millis(1);
+
+struct Run {
+    received: u64,
+    elapsed: Duration,
+}
+
+/// Returns datagrams which are dropped by the filter: too short, a bad 'op'
+/// field, and no magic cookie.
+fn junk() -> [Vec<u8>; 3] {
+    let mut bad_op = vec![0; 300];
+    bad_op[0] = 7;
+
+    let mut no_cookie = vec![0; 300];
+    no_cookie[0] = 1;
+
+    [vec![1; 100], bad_op, no_cookie]
+}
+
+fn discover() -> Vec<u8> {
+    let hardware_addr = HardwareAddr::try_from(String::from("02:00:00:00:00:01")).unwrap();
+    let mut builder = MessageBuilder::new(hardware_addr, None, 1500);
+
+    builder
+        .make_discover_message(1, None, None)
+        .unwrap()
+        .to_bytes()
+        .unwrap()
+}
+
+async fn run(socket_filter: bool, flood: usize) -> Run {
+    // Let the kernel pick a free port for the server
+    let port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+    let mut server = Server::builder()
+        .with_pool("default".into(), "127.0.0.100-127.0.0.200".into())
+        .with_server_identifier(Ipv4Addr::LOCALHOST)
+        .with_subnet_mask(Ipv4Addr::new(255, 0, 0, 0))
+        .with_bind_address(addr)
+        .with_allow_foreign_pools(true)
+        .with_socket_filter(socket_filter)
+        .build()
+        .unwrap();
+
+    let service = server.service();
+    let shutdown = server.shutdown_handle();
+    let task = tokio::spawn(async move { server.run_async().await });
+
+    // Give the server time to bind its socket
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let junk = junk();
+    let start = Instant::now();
+
+    for i in 0..flood {
+        sender.send_to(&junk[i % junk.len()], addr).unwrap();
+    }
+
+    // Without the filter, the flood can overflow the receive buffer of the
+    // server and the DHCPDISCOVER is lost, so it's resent until handled
+    let discover = discover();
+    let stats = service.stats();
+    while ServerStats::get(&stats.discover_cache_misses) == 0 {
+        assert!(
+            start.elapsed() < TIMEOUT,
+            "the DHCPDISCOVER was not handled"
+        );
+        sender.send_to(&discover, addr).unwrap();
+        tokio::time::sleep(RESEND_INTERVAL).await;
+    }
+    let elapsed = start.elapsed();
+
+    shutdown.shutdown();
+    task.await.unwrap().unwrap();
+
+    Run {
+        received: stats.total().received,
+        elapsed,
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let flood = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_FLOOD);
+
+    println!("{flood} junk datagrams and a DHCPDISCOVER on the loopback interface");
+    println!("{:<8} {:>10} {:>12}", "filter", "received", "elapsed");
+
+    for socket_filter in [false, true] {
+        let run = run(socket_filter, flood).await;
+        let filter = if socket_filter { "on" } else { "off" };
+
+        println!("{:<8} {:>10} {:>10.2?}", filter, run.received, run.elapsed);
+    }
+}
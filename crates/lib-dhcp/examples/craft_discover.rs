@@ -0,0 +1,23 @@
+//! Builds a DHCPDISCOVER for a hardware address and prints its hex dump.
+//! The message is what the client sends first, with the default parameter
+//! request list:
+//!
+//! ```shell
+//! cargo run --example craft_discover -- 02:00:00:00:00:01
+//! ```
+use dhcp::{types::HardwareAddr, MessageBuilder};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mac = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("de:ad:be:ef:12:34"));
+    let hardware_addr = HardwareAddr::try_from(mac)?;
+
+    // Without an explicit client identifier, the hardware address is sent
+    // as one
+    let mut builder = MessageBuilder::new(hardware_addr, None, 1500);
+    let discover = builder.make_discover_message(rand::random(), None, None)?;
+
+    println!("{}", discover.to_hex()?);
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! Decodes a DHCP message from a hex dump read from stdin and prints it.
+//! Whitespace between the octets is ignored, so the output of the
+//! `craft_discover` example or a copy from Wireshark's "Copy as Hex
+//! Stream" both work:
+//!
+//! ```shell
+//! cargo run --example craft_discover | cargo run --example decode_packet
+//! ```
+use std::io::Read;
+
+use dhcp::types::Message;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut hex = String::new();
+    std::io::stdin().read_to_string(&mut hex)?;
+
+    let message = Message::from_hex(&hex)?;
+    println!("{message}");
+
+    // Structural problems don't prevent parsing, but are worth a note
+    if let Err(err) = message.validate() {
+        eprintln!("warning: {err}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! Embeds the DHCP server into an application which owns the socket.
+//!
+//! Instead of running the server with [`Server::run_async`], the request
+//! handling core is taken from it and fed datagrams from a socket bound by
+//! the application. Binding to the server port usually requires elevated
+//! privileges:
+//!
+//! ```shell
+//! sudo cargo run --example embedded_server
+//! ```
+use std::{net::Ipv4Addr, sync::Arc};
+
+use dhcp::{RequestMeta, Server, MINIMUM_LEGAL_MAX_MESSAGE_SIZE, SERVER_PORT};
+use tokio::net::UdpSocket;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::builder()
+        .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+        .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+        .with_subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+        .build()?;
+
+    // Clones of the service share leases, pools and stats, so they can be
+    // handed to any number of tasks or front-ends
+    let service = server.service();
+
+    let socket = Arc::new(UdpSocket::bind((Ipv4Addr::UNSPECIFIED, SERVER_PORT)).await?);
+    socket.set_broadcast(true)?;
+
+    loop {
+        let mut buf = [0u8; MINIMUM_LEGAL_MAX_MESSAGE_SIZE as usize];
+        let (len, source) = socket.recv_from(&mut buf).await?;
+
+        let service = service.clone();
+        let socket = socket.clone();
+
+        tokio::spawn(async move {
+            let Some(reply) = service.call(&buf[..len], RequestMeta::new(source)).await else {
+                return;
+            };
+
+            if let Err(err) = socket.send_to(&reply.bytes, reply.destination).await {
+                eprintln!("failed to send reply to {}: {}", reply.destination, err);
+            }
+        });
+    }
+}
@@ -0,0 +1,33 @@
+//! Runs the client on an interface and prints every change of its lease as
+//! a line of JSON, like the lease being bound, renewed or lost. Configuring
+//! interfaces requires elevated privileges:
+//!
+//! ```shell
+//! sudo cargo run --example lease_watcher -- eth0
+//! ```
+use dhcp::Client;
+use tokio::sync::broadcast::error::RecvError;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let interface = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("eth0"));
+
+    let mut client = Client::builder().with_interface_name(interface).build()?;
+
+    // Subscribe before running the client, so that no event is missed
+    let mut events = client.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => println!("{}", serde_json::to_string(&event).unwrap()),
+                Err(RecvError::Lagged(missed)) => eprintln!("missed {missed} events"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    client.run().await?;
+    Ok(())
+}
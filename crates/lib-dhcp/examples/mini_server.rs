@@ -0,0 +1,30 @@
+//! Runs a single-pool server on a high port, which doesn't require elevated
+//! privileges. Leases are kept in memory only and are lost when the server
+//! stops. Clients have to be pointed at the port, or a relay agent
+//! forwards to it:
+//!
+//! ```shell
+//! cargo run --example mini_server
+//! ```
+use std::net::{Ipv4Addr, SocketAddr};
+
+use dhcp::Server;
+
+/// Port the server listens on, instead of the privileged [`dhcp::SERVER_PORT`].
+const PORT: u16 = 6767;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut server = Server::builder()
+        .with_pool("default".into(), "10.99.0.100-10.99.0.200".into())
+        .with_server_identifier(Ipv4Addr::new(10, 99, 0, 1))
+        .with_subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+        .with_bind_address(SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT)))
+        // The pool doesn't need to match an address of this host
+        .with_allow_foreign_pools(true)
+        .build()?;
+
+    println!("listening on port {PORT}");
+    server.run_async().await?;
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! Memory-bounded map for per-client state. Everything the server keeps
+//! per client outside of leases is created by unauthenticated messages, so
+//! a flood of random hardware addresses must not grow it without limit.
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    ops::Add,
+    time::{Duration, Instant},
+};
+
+/// Map with a maximum number of entries, each of which expires after a
+/// TTL. When inserting a new key into a full map, the least recently used
+/// entry is evicted. Expired entries are not returned by [`get`], but only
+/// removed by [`purge_expired`], so that callers can clean up after them.
+///
+/// Entries expire at a point in time `T`, which is an [`Instant`] by
+/// default. Maps whose expiry is persisted use
+/// [`SystemTime`](std::time::SystemTime) instead.
+///
+/// [`get`]: BoundedTtlMap::get
+/// [`purge_expired`]: BoundedTtlMap::purge_expired
+#[derive(Debug)]
+pub(crate) struct BoundedTtlMap<K, V, T = Instant> {
+    entries: HashMap<K, Entry<V, T>>,
+
+    /// Keys by the tick they were last used at, least recently used first.
+    recency: BTreeMap<u64, K>,
+
+    /// Keys by expiry, the tick of their insert breaks ties.
+    deadlines: BTreeMap<(T, u64), K>,
+
+    capacity: usize,
+    ttl: Duration,
+    tick: u64,
+    evictions: u64,
+}
+
+#[derive(Debug)]
+struct Entry<V, T> {
+    value: V,
+    expires: T,
+    inserted: u64,
+    used: u64,
+}
+
+impl<K, V, T> BoundedTtlMap<K, V, T>
+where
+    K: Clone + Eq + Hash,
+    T: Copy + Ord + Add<Duration, Output = T>,
+{
+    /// Creates a map holding at most `capacity` entries, which expire `ttl`
+    /// after they were inserted. A capacity of zero is treated as one.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            deadlines: BTreeMap::new(),
+            capacity: capacity.max(1),
+            ttl,
+            tick: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.capacity
+    }
+
+    /// Returns the number of entries evicted to make room for new ones.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Returns the value of `key` if it didn't expire at `now`, and marks
+    /// it as recently used.
+    pub fn get<Q>(&mut self, key: &Q, now: T) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.tick += 1;
+        let tick = self.tick;
+
+        let entry = self.entries.get_mut(key).filter(|e| e.expires > now)?;
+        let key = self.recency.remove(&entry.used)?;
+
+        self.recency.insert(tick, key);
+        entry.used = tick;
+        Some(&entry.value)
+    }
+
+    /// Returns the value of `key`, expired or not, without marking it as
+    /// used.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns all values, including expired ones.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|entry| &entry.value)
+    }
+
+    /// Inserts `value` for `key`, expiring after the TTL of the map. See
+    /// [`BoundedTtlMap::insert_until`].
+    pub fn insert(&mut self, key: K, value: V, now: T) -> Option<(K, V)> {
+        let expires = now + self.ttl;
+        self.insert_until(key, value, expires)
+    }
+
+    /// Inserts `value` for `key`, expiring at `expires`. A previous value of
+    /// `key` is replaced. Returns the least recently used entry if it was
+    /// evicted to make room.
+    pub fn insert_until(&mut self, key: K, value: V, expires: T) -> Option<(K, V)> {
+        self.remove(&key);
+
+        let evicted = if self.is_full() { self.evict() } else { None };
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.deadlines.insert((expires, self.tick), key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                expires,
+                inserted: self.tick,
+                used: self.tick,
+            },
+        );
+
+        evicted
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.entries.remove(key)?;
+
+        self.recency.remove(&entry.used);
+        self.deadlines.remove(&(entry.expires, entry.inserted));
+        Some(entry.value)
+    }
+
+    /// Removes and returns all entries which expired at `now`.
+    pub fn purge_expired(&mut self, now: T) -> Vec<(K, V)> {
+        let mut expired = Vec::new();
+
+        while let Some(entry) = self.deadlines.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            let key = entry.remove();
+            if let Some(value) = self.remove(&key) {
+                expired.push((key, value));
+            }
+        }
+
+        expired
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.deadlines.clear();
+    }
+
+    fn evict(&mut self) -> Option<(K, V)> {
+        let (_, key) = self.recency.pop_first()?;
+        let entry = self.entries.remove(&key)?;
+
+        self.deadlines.remove(&(entry.expires, entry.inserted));
+        self.evictions += 1;
+        Some((key, entry.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry_and_lru_eviction() {
+        let now = Instant::now();
+        let mut map = BoundedTtlMap::new(2, Duration::from_secs(10));
+
+        assert_eq!(map.insert("a", 1, now), None);
+        assert_eq!(map.insert("b", 2, now + Duration::from_secs(5)), None);
+
+        // Using "a" makes "b" the least recently used entry
+        assert_eq!(map.get("a", now), Some(&1));
+        assert_eq!(map.insert("c", 3, now), Some(("b", 2)));
+        assert_eq!(map.evictions(), 1);
+
+        // Replacing a value doesn't evict
+        assert_eq!(map.insert("c", 4, now), None);
+        assert!(map.is_full());
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(map.get("a", later), None);
+        assert_eq!(map.peek("a"), Some(&1));
+        assert_eq!(map.purge_expired(later), vec![("a", 1), ("c", 4)]);
+        assert!(map.entries.is_empty());
+    }
+
+    #[test]
+    fn test_bounded_under_random_keys() {
+        const CAPACITY: usize = 1024;
+
+        let start = Instant::now();
+        let mut map = BoundedTtlMap::new(CAPACITY, Duration::from_secs(600));
+        let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+        map.insert(0, 0, start);
+
+        for i in 1..1_000_000u64 {
+            // xorshift64
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+
+            let now = start + Duration::from_micros(i);
+            map.insert(rng, i, now);
+
+            // A client which keeps talking is never evicted
+            if i % 100 == 0 {
+                assert!(map.get(&0, now).is_some());
+            }
+
+            assert!(map.entries.len() <= CAPACITY);
+        }
+
+        assert_eq!(map.entries.len(), CAPACITY);
+        assert_eq!(map.recency.len(), CAPACITY);
+        assert_eq!(map.deadlines.len(), CAPACITY);
+        assert!(map.peek(&0).is_some());
+        assert!(map.evictions() >= 1_000_000 - CAPACITY as u64);
+    }
+}
@@ -2,7 +2,7 @@ use std::net::Ipv4Addr;
 
 use crate::types::{
     options::{ClientIdentifier, DhcpMessageType, ParameterRequestList},
-    DhcpOption, HardwareAddr, Message, MessageError, OptionData, OptionTag,
+    DhcpOption, HardwareAddr, Message, MessageError, OpCode, OptionData, OptionTag,
 };
 
 #[derive(Debug)]
@@ -10,6 +10,20 @@ pub struct MessageBuilder {
     client_hardware_addr: HardwareAddr,
     client_identifier: Option<Vec<u8>>,
     max_dhcp_message_size: u16,
+
+    /// Order in which options (and the tags in the parameter request list)
+    /// are emitted. Tags not listed keep their default order and are placed
+    /// after the listed ones.
+    option_order: Vec<OptionTag>,
+
+    /// Server to load the boot file from ('siaddr'), used by replies.
+    next_server: Option<Ipv4Addr>,
+
+    /// Boot file name ('file'), used by replies.
+    boot_file: Option<String>,
+
+    /// Hop count ('hops') of client messages.
+    hops: u8,
 }
 
 impl MessageBuilder {
@@ -22,22 +36,65 @@ impl MessageBuilder {
             max_dhcp_message_size,
             client_hardware_addr,
             client_identifier,
+            option_order: Vec::new(),
+            next_server: None,
+            boot_file: None,
+            hops: 0,
         }
     }
 
+    /// Returns the hardware address of the client.
+    pub fn client_hardware_addr(&self) -> &HardwareAddr {
+        &self.client_hardware_addr
+    }
+
+    /// Sets the order in which options are emitted. This allows the client
+    /// to mimic the DHCP fingerprint of a specific operating system. The
+    /// order is applied to the options of the message as well as the tags in
+    /// the parameter request list (option 55). The End option is always
+    /// emitted last.
+    pub fn with_option_order(mut self, order: Vec<OptionTag>) -> Self {
+        self.option_order = order;
+        self
+    }
+
+    /// Sets the next server ('siaddr') in DHCPOFFER and DHCPACK messages.
+    /// PXE clients load the boot file from this server via TFTP.
+    pub fn with_next_server(mut self, addr: Ipv4Addr) -> Self {
+        self.next_server = Some(addr);
+        self
+    }
+
+    /// Sets the boot file name ('file') in DHCPOFFER and DHCPACK messages.
+    /// The field is 128 octets long and null terminated, longer names are
+    /// truncated to 127 octets.
+    pub fn with_boot_file(mut self, file: String) -> Self {
+        self.boot_file = Some(file);
+        self
+    }
+
+    /// Sets the hop count ('hops') of client messages. Clients set it to
+    /// zero, which is the default. A non-zero count makes the message look
+    /// like it was forwarded by relay agents, which is useful to test relay
+    /// handling.
+    pub fn with_hops(mut self, hops: u8) -> Self {
+        self.hops = hops;
+        self
+    }
+
     /// This creates a new DHCPDISCOVER message with the values described in
-    /// RFC 2131 Section 4.
+    /// RFC 2131 Section 4. The message never carries a server identifier,
+    /// even if it is unicast (Table 5).
     pub fn make_discover_message(
         &mut self,
         xid: u32,
-        destination_addr: Ipv4Addr,
         requested_client_addr: Option<Ipv4Addr>,
         requested_lease_time: Option<u32>,
     ) -> Result<Message, MessageError> {
         // The client sets 'ciaddr' to 0x00000000. This is already done in
         // Message::new() (Default value).
-        let mut message = Message::new_with_xid(xid);
-        self.add_default_options(&mut message)?;
+        let mut message = self.new_message(xid);
+        self.add_default_options(&mut message, DhcpMessageType::Discover)?;
 
         // Set DHCP message type option
         message.add_option_parts(
@@ -45,13 +102,6 @@ impl MessageBuilder {
             OptionData::DhcpMessageType(DhcpMessageType::Discover),
         )?;
 
-        if destination_addr != Ipv4Addr::BROADCAST {
-            message.add_option_parts(
-                OptionTag::ServerIdentifier,
-                OptionData::ServerIdentifier(destination_addr),
-            )?;
-        }
-
         // The client MAY suggest a network address and/or lease time by
         // including the 'requested IP address' and 'IP address lease time'
         // options.
@@ -71,21 +121,14 @@ impl MessageBuilder {
 
         // The client MAY include a different unique identifier in the 'client
         // identifier' option, as discussed in section 4.2.
-        let client_identifier = match &self.client_identifier {
-            Some(ident) => ident.clone(),
-            None => self.client_hardware_addr.as_bytes(),
-        };
-
-        message.add_option_parts(
-            OptionTag::ClientIdentifier,
-            OptionData::ClientIdentifier(ClientIdentifier::from(client_identifier)),
-        )?;
+        self.add_client_identifier(&mut message)?;
 
         // NOTE (Techassi): Maybe add hostname option
 
         // The client MAY request specific parameters by including the
         // 'parameter request list' option.
-        message.add_option(Self::default_request_parameter_list())?;
+        message.add_option(self.default_request_parameter_list())?;
+        self.apply_option_order(&mut message);
         message.end()?;
 
         message.set_hardware_address(self.client_hardware_addr.clone());
@@ -101,8 +144,8 @@ impl MessageBuilder {
         offered_client_addr: Ipv4Addr,
         offered_lease_time: u32,
     ) -> Result<Message, MessageError> {
-        let mut message = Message::new_with_xid(xid);
-        self.add_default_options(&mut message)?;
+        let mut message = self.new_message(xid);
+        self.add_default_options(&mut message, DhcpMessageType::Request)?;
 
         // Set DHCP message type option
         message.add_option_parts(
@@ -127,7 +170,8 @@ impl MessageBuilder {
 
         // NOTE (Techassi): Maybe add hostname option
 
-        message.add_option(Self::default_request_parameter_list())?;
+        message.add_option(self.default_request_parameter_list())?;
+        self.apply_option_order(&mut message);
         message.end()?;
 
         message.set_hardware_address(self.client_hardware_addr.clone());
@@ -135,14 +179,16 @@ impl MessageBuilder {
     }
 
     /// Creates a new DHCPREQUEST message in BOUND, RENEWING or REBINDING state.
+    /// The client is identified by 'ciaddr', the 'requested IP address'
+    /// option MUST NOT be included (Table 4).
     pub fn make_renewing_message(
         &self,
         xid: u32,
         client_addr: Ipv4Addr,
         lease_time: u32,
     ) -> Result<Message, MessageError> {
-        let mut message = Message::new_with_xid(xid);
-        self.add_default_options(&mut message)?;
+        let mut message = self.new_message(xid);
+        self.add_default_options(&mut message, DhcpMessageType::Request)?;
 
         // Set ciaddr
         message.ciaddr = client_addr;
@@ -153,9 +199,125 @@ impl MessageBuilder {
             OptionData::DhcpMessageType(DhcpMessageType::Request),
         )?;
 
+        message.add_option_parts(
+            OptionTag::IpAddrLeaseTime,
+            OptionData::IpAddrLeaseTime(lease_time),
+        )?;
+
+        // NOTE (Techassi): Maybe add hostname option
+
+        message.add_option(self.default_request_parameter_list())?;
+        self.apply_option_order(&mut message);
+        message.end()?;
+
+        message.set_hardware_address(self.client_hardware_addr.clone());
+        Ok(message)
+    }
+
+    /// Creates a new DHCPRELEASE message which relinquishes the lease of
+    /// `client_addr`. The message is unicast to the server with
+    /// `server_identifier`, no reply is expected.
+    ///
+    /// ### See
+    ///
+    /// RFC 2131 - Section 4.4.6: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4.6
+    pub fn make_release_message(
+        &self,
+        xid: u32,
+        client_addr: Ipv4Addr,
+        server_identifier: Ipv4Addr,
+    ) -> Result<Message, MessageError> {
+        let mut message = self.new_message(xid);
+        self.add_default_options(&mut message, DhcpMessageType::Release)?;
+
+        message.ciaddr = client_addr;
+
+        message.add_option_parts(
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Release),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
+        )?;
+
+        self.add_client_identifier(&mut message)?;
+        self.apply_option_order(&mut message);
+        message.end()?;
+
+        message.set_hardware_address(self.client_hardware_addr.clone());
+        Ok(message)
+    }
+
+    /// Creates a new DHCPDECLINE message which tells the server with
+    /// `server_identifier` that `declined_addr` is already in use. 'ciaddr'
+    /// stays zero, the declined address is sent in the 'requested IP
+    /// address' option.
+    ///
+    /// ### See
+    ///
+    /// RFC 2131 - Section 4.4.1 - Table 5: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4.1
+    pub fn make_decline_message(
+        &self,
+        xid: u32,
+        declined_addr: Ipv4Addr,
+        server_identifier: Ipv4Addr,
+    ) -> Result<Message, MessageError> {
+        let mut message = self.new_message(xid);
+        self.add_default_options(&mut message, DhcpMessageType::Decline)?;
+
+        message.add_option_parts(
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Decline),
+        )?;
+
         message.add_option_parts(
             OptionTag::RequestedIpAddr,
-            OptionData::RequestedIpAddr(client_addr),
+            OptionData::RequestedIpAddr(declined_addr),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::Message,
+            OptionData::Message(format!("address {} is already in use", declined_addr)),
+        )?;
+
+        self.add_client_identifier(&mut message)?;
+        self.apply_option_order(&mut message);
+        message.end()?;
+
+        message.set_hardware_address(self.client_hardware_addr.clone());
+        Ok(message)
+    }
+
+    /// Creates a new DHCPOFFER message in response to the DHCPDISCOVER
+    /// `request` with the values described in RFC 2131 Section 4.3.1
+    /// (Table 3).
+    pub fn make_offer_message(
+        &self,
+        request: &Message,
+        offered_client_addr: Ipv4Addr,
+        server_identifier: Ipv4Addr,
+        lease_time: u32,
+        options: Vec<DhcpOption>,
+    ) -> Result<Message, MessageError> {
+        let mut message = Self::make_reply_message(request);
+        message.yiaddr = offered_client_addr;
+        self.set_boot_fields(&mut message);
+
+        message.add_option_parts(
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Offer),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
         )?;
 
         message.add_option_parts(
@@ -163,31 +325,525 @@ impl MessageBuilder {
             OptionData::IpAddrLeaseTime(lease_time),
         )?;
 
-        // NOTE (Techassi): Maybe add hostname option
+        for option in options {
+            message.add_option(option)?;
+        }
 
-        message.add_option(Self::default_request_parameter_list())?;
         message.end()?;
+        Ok(message)
+    }
 
-        message.set_hardware_address(self.client_hardware_addr.clone());
+    /// Creates a DHCPACK message, which confirms the address
+    /// `client_addr` requested via the client's `request`.
+    pub fn make_ack_message(
+        &self,
+        request: &Message,
+        client_addr: Ipv4Addr,
+        server_identifier: Ipv4Addr,
+        lease_time: u32,
+        options: Vec<DhcpOption>,
+    ) -> Result<Message, MessageError> {
+        let mut message = Self::make_reply_message(request);
+        message.ciaddr = request.ciaddr;
+        message.yiaddr = client_addr;
+        self.set_boot_fields(&mut message);
+
+        message.add_option_parts(
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Ack),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::IpAddrLeaseTime,
+            OptionData::IpAddrLeaseTime(lease_time),
+        )?;
+
+        for option in options {
+            message.add_option(option)?;
+        }
+
+        message.end()?;
         Ok(message)
     }
 
-    fn add_default_options(&self, message: &mut Message) -> Result<(), MessageError> {
+    /// Creates a DHCPACK message in response to a DHCPINFORM. The client
+    /// already has an address, so 'yiaddr' stays zero and no lease time is
+    /// included, only the configuration parameters in `options`.
+    ///
+    /// ### See
+    ///
+    /// RFC 2131 - Section 4.3.5 - DHCPINFORM message: https://datatracker.ietf.org/doc/html/rfc2131#section-4.3.5
+    pub fn make_inform_ack_message(
+        &self,
+        request: &Message,
+        server_identifier: Ipv4Addr,
+        options: Vec<DhcpOption>,
+    ) -> Result<Message, MessageError> {
+        let mut message = Self::make_reply_message(request);
+        message.ciaddr = request.ciaddr;
+
         message.add_option_parts(
-            OptionTag::MaxDhcpMessageSize,
-            OptionData::MaxDhcpMessageSize(self.max_dhcp_message_size),
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Ack),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
+        )?;
+
+        for option in options {
+            message.add_option(option)?;
+        }
+
+        message.end()?;
+        Ok(message)
+    }
+
+    /// Creates the reply of type `ty` to the DHCPLEASEQUERY `request`. For a
+    /// DHCPLEASEACTIVE, 'ciaddr' and 'chaddr' carry the address and hardware
+    /// address of the queried binding, given via `binding`.
+    ///
+    /// ### See
+    ///
+    /// RFC 4388 - Section 6.1 - Sending DHCPLEASEQUERY responses: https://datatracker.ietf.org/doc/html/rfc4388#section-6.1
+    pub fn make_lease_query_reply(
+        &self,
+        request: &Message,
+        ty: DhcpMessageType,
+        server_identifier: Ipv4Addr,
+        binding: Option<(Ipv4Addr, HardwareAddr)>,
+        options: Vec<DhcpOption>,
+    ) -> Result<Message, MessageError> {
+        let mut message = Self::make_reply_message(request);
+        message.ciaddr = request.ciaddr;
+
+        if let Some((addr, hardware_addr)) = binding {
+            message.ciaddr = addr;
+            message.set_hardware_address(hardware_addr);
+        }
+
+        message.add_option_parts(OptionTag::DhcpMessageType, OptionData::DhcpMessageType(ty))?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
+        )?;
+
+        for option in options {
+            message.add_option(option)?;
+        }
+
+        message.end()?;
+        Ok(message)
+    }
+
+    /// Creates a DHCPNAK message, which rejects the client's `request`.
+    pub fn make_nak_message(
+        &self,
+        request: &Message,
+        server_identifier: Ipv4Addr,
+    ) -> Result<Message, MessageError> {
+        let mut message = Self::make_reply_message(request);
+
+        message.add_option_parts(
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Nak),
+        )?;
+
+        message.add_option_parts(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(server_identifier),
+        )?;
+
+        message.end()?;
+        Ok(message)
+    }
+
+    /// Creates a client message with the configured hop count.
+    fn new_message(&self, xid: u32) -> Message {
+        let mut message = Message::new_with_xid(xid);
+        message.header.hops = self.hops;
+        message
+    }
+
+    /// Creates a BOOTREPLY message which copies 'xid', 'htype', 'flags',
+    /// 'giaddr' and 'chaddr' from the client's `request`. 'hops' is left at
+    /// zero (Table 3).
+    fn make_reply_message(request: &Message) -> Message {
+        let mut message = Message::new_with_xid(request.header.xid);
+        message.header.opcode = OpCode::BootReply;
+        message.header.htype = request.header.htype.clone();
+        message.header.flags = request.header.flags;
+        message.giaddr = request.giaddr;
+
+        message.set_hardware_address(request.chaddr.clone());
+        message
+    }
+
+    /// Sets 'siaddr' and 'file' if a next server and boot file are
+    /// configured.
+    fn set_boot_fields(&self, message: &mut Message) {
+        if let Some(next_server) = self.next_server {
+            message.siaddr = next_server;
+        }
+
+        if let Some(boot_file) = &self.boot_file {
+            let bytes = boot_file.as_bytes();
+            let len = bytes.len().min(127);
+
+            let mut file = vec![0; 128];
+            file[..len].copy_from_slice(&bytes[..len]);
+            message.file = file;
+        }
+    }
+
+    /// Adds the options every client message of type `ty` carries. The
+    /// maximum DHCP message size is only sent in messages which expect a
+    /// reply, DHCPDECLINE and DHCPRELEASE MUST NOT include it.
+    ///
+    /// ### See
+    ///
+    /// RFC 2131 - Section 4.4.1 - Table 5: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4.1
+    fn add_default_options(
+        &self,
+        message: &mut Message,
+        ty: DhcpMessageType,
+    ) -> Result<(), MessageError> {
+        match ty {
+            DhcpMessageType::Discover | DhcpMessageType::Request | DhcpMessageType::Inform => {
+                message.add_option_parts(
+                    OptionTag::MaxDhcpMessageSize,
+                    OptionData::MaxDhcpMessageSize(self.max_dhcp_message_size),
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds the 'client identifier' option. Without a configured identifier,
+    /// the hardware address is used.
+    fn add_client_identifier(&self, message: &mut Message) -> Result<(), MessageError> {
+        let client_identifier = match &self.client_identifier {
+            Some(ident) => ident.clone(),
+            None => self.client_hardware_addr.as_bytes(),
+        };
+
+        message.add_option_parts(
+            OptionTag::ClientIdentifier,
+            OptionData::ClientIdentifier(ClientIdentifier::from(client_identifier)),
         )
     }
 
-    fn default_request_parameter_list() -> DhcpOption {
+    fn default_request_parameter_list(&self) -> DhcpOption {
+        let mut tags = vec![
+            OptionTag::Router,
+            OptionTag::DomainNameServer,
+            OptionTag::DomainName,
+            OptionTag::DomainSearch,
+            OptionTag::NetworkTimeProtocolServers,
+            OptionTag::RenewalT1Time,
+            OptionTag::RebindingT2Time,
+        ];
+        tags.sort_by_key(|tag| self.option_position(tag));
+
         DhcpOption::new(
             OptionTag::ParameterRequestList,
-            OptionData::ParameterRequestList(ParameterRequestList::new(vec![
-                OptionTag::Router,
-                OptionTag::DomainNameServer,
-                OptionTag::RenewalT1Time,
-                OptionTag::RebindingT2Time,
-            ])),
+            OptionData::ParameterRequestList(ParameterRequestList::new(tags)),
         )
     }
+
+    /// Reorders the options of `message` according to the configured option
+    /// order. The sort is stable, so unlisted options keep their relative
+    /// order.
+    fn apply_option_order(&self, message: &mut Message) {
+        if self.option_order.is_empty() {
+            return;
+        }
+
+        message
+            .options
+            .sort_by_key(|option| self.option_position(&option.header().tag));
+    }
+
+    fn option_position(&self, tag: &OptionTag) -> usize {
+        self.option_order
+            .iter()
+            .position(|t| t == tag)
+            .unwrap_or(self.option_order.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use binbuf::prelude::*;
+
+    use super::*;
+
+    fn hardware_addr() -> HardwareAddr {
+        HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap()
+    }
+
+    fn options_bytes(message: &Message) -> Vec<u8> {
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+
+        // Skip the fixed header fields (236 octets) and the magic cookie
+        buf.bytes()[240..].to_vec()
+    }
+
+    #[test]
+    fn test_default_option_order() {
+        let builder = MessageBuilder::new(hardware_addr(), None, 1500);
+        let message = builder
+            .make_request_message(
+                1,
+                Ipv4Addr::new(192, 168, 1, 1),
+                Ipv4Addr::new(192, 168, 1, 100),
+                3600,
+            )
+            .unwrap();
+
+        let tags: Vec<OptionTag> = message
+            .options
+            .iter()
+            .map(|o| o.header().tag.clone())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                OptionTag::MaxDhcpMessageSize,
+                OptionTag::DhcpMessageType,
+                OptionTag::ServerIdentifier,
+                OptionTag::RequestedIpAddr,
+                OptionTag::IpAddrLeaseTime,
+                OptionTag::ParameterRequestList,
+                OptionTag::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_configured_option_order() {
+        let mut builder = MessageBuilder::new(hardware_addr(), None, 1500).with_option_order(vec![
+            OptionTag::DhcpMessageType,
+            OptionTag::ClientIdentifier,
+            OptionTag::MaxDhcpMessageSize,
+            OptionTag::ParameterRequestList,
+            OptionTag::RebindingT2Time,
+            OptionTag::RenewalT1Time,
+            OptionTag::DomainNameServer,
+            OptionTag::Router,
+        ]);
+
+        let message = builder.make_discover_message(1, None, None).unwrap();
+
+        #[rustfmt::skip]
+        let expected = vec![
+            53, 1, 1,
+            61, 7, 1, 0xde, 0xad, 0xbe, 0xef, 0x12, 0x34,
+            57, 2, 0x05, 0xdc,
+            55, 7, 59, 58, 6, 3, 15, 119, 42,
+            255,
+        ];
+
+        assert_eq!(options_bytes(&message), expected);
+    }
+
+    #[test]
+    fn test_discover_options_map() {
+        let mut builder = MessageBuilder::new(hardware_addr(), None, 1500);
+        let discover = builder.make_discover_message(1, None, None).unwrap();
+
+        let options = discover.options_map();
+        let mut tags: Vec<_> = options.keys().cloned().collect();
+        tags.sort_by_key(|tag| u8::from(tag));
+        assert_eq!(
+            tags,
+            [
+                OptionTag::DhcpMessageType,
+                OptionTag::ParameterRequestList,
+                OptionTag::MaxDhcpMessageSize,
+                OptionTag::ClientIdentifier,
+            ]
+        );
+        assert_eq!(
+            options[&OptionTag::MaxDhcpMessageSize],
+            &OptionData::MaxDhcpMessageSize(1500)
+        );
+
+        // The map of the parsed message is the same
+        let mut buf = WriteBuffer::new();
+        discover.write::<BigEndian>(&mut buf).unwrap();
+        let parsed = Message::from_bytes(buf.bytes()).unwrap();
+        assert_eq!(parsed.options_map(), options);
+    }
+
+    #[test]
+    fn test_ack_boot_fields() {
+        let builder = MessageBuilder::new(hardware_addr(), None, 1500)
+            .with_next_server(Ipv4Addr::new(192, 168, 1, 5))
+            .with_boot_file(String::from("pxelinux.0"));
+
+        let request = builder
+            .make_request_message(
+                1,
+                Ipv4Addr::new(192, 168, 1, 1),
+                Ipv4Addr::new(192, 168, 1, 100),
+                3600,
+            )
+            .unwrap();
+
+        let ack = builder
+            .make_ack_message(
+                &request,
+                Ipv4Addr::new(192, 168, 1, 100),
+                Ipv4Addr::new(192, 168, 1, 1),
+                3600,
+                Vec::new(),
+            )
+            .unwrap();
+
+        assert_eq!(ack.siaddr, Ipv4Addr::new(192, 168, 1, 5));
+        assert_eq!(ack.file.len(), 128);
+        assert_eq!(&ack.file[..10], b"pxelinux.0");
+        assert!(ack.file[10..].iter().all(|b| *b == 0));
+
+        // The fields are written at their fixed offsets
+        let mut buf = WriteBuffer::new();
+        ack.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(&buf.bytes()[20..24], &[192, 168, 1, 5]);
+        assert_eq!(&buf.bytes()[108..118], b"pxelinux.0");
+    }
+
+    fn default_tags(builder: &MessageBuilder, ty: DhcpMessageType) -> Vec<OptionTag> {
+        let mut message = Message::new_with_xid(1);
+        builder.add_default_options(&mut message, ty).unwrap();
+
+        message
+            .options
+            .iter()
+            .map(|o| o.header().tag.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_default_options_per_message_type() {
+        let builder = MessageBuilder::new(hardware_addr(), None, 1500);
+
+        let discover = default_tags(&builder, DhcpMessageType::Discover);
+        assert!(discover.contains(&OptionTag::MaxDhcpMessageSize));
+
+        let request = default_tags(&builder, DhcpMessageType::Request);
+        assert!(request.contains(&OptionTag::MaxDhcpMessageSize));
+
+        assert!(default_tags(&builder, DhcpMessageType::Release).is_empty());
+        assert!(default_tags(&builder, DhcpMessageType::Decline).is_empty());
+    }
+
+    fn tags(message: &Message) -> Vec<OptionTag> {
+        message
+            .options
+            .iter()
+            .map(|o| o.header().tag.clone())
+            .collect()
+    }
+
+    /// Options which MUST NOT appear in DHCPDECLINE and DHCPRELEASE messages.
+    const FORBIDDEN_TAGS: [OptionTag; 4] = [
+        OptionTag::IpAddrLeaseTime,
+        OptionTag::ParameterRequestList,
+        OptionTag::MaxDhcpMessageSize,
+        OptionTag::ClassIdentifier,
+    ];
+
+    #[test]
+    fn test_release_message() {
+        let builder = MessageBuilder::new(hardware_addr(), None, 1500);
+        let client_addr = Ipv4Addr::new(192, 168, 1, 100);
+        let server_id = Ipv4Addr::new(192, 168, 1, 1);
+
+        let release = builder
+            .make_release_message(0x1234, client_addr, server_id)
+            .unwrap();
+        let tags = tags(&release);
+
+        assert_eq!(release.header.xid, 0x1234);
+        assert_eq!(release.ciaddr, client_addr);
+        assert_eq!(release.chaddr, hardware_addr());
+        assert_eq!(release.get_message_type(), Some(&DhcpMessageType::Release));
+        assert_eq!(release.get_server_identifier(), Some(server_id));
+        assert!(tags.contains(&OptionTag::ClientIdentifier));
+        assert!(!tags.contains(&OptionTag::RequestedIpAddr));
+        assert!(FORBIDDEN_TAGS.iter().all(|tag| !tags.contains(tag)));
+    }
+
+    #[test]
+    fn test_decline_message() {
+        let builder = MessageBuilder::new(hardware_addr(), None, 1500);
+        let declined = Ipv4Addr::new(192, 168, 1, 100);
+        let server_id = Ipv4Addr::new(192, 168, 1, 1);
+
+        let decline = builder
+            .make_decline_message(0x1234, declined, server_id)
+            .unwrap();
+        let tags = tags(&decline);
+
+        assert_eq!(decline.ciaddr, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(decline.get_message_type(), Some(&DhcpMessageType::Decline));
+        assert_eq!(decline.get_requested_ip_addr(), Some(declined));
+        assert_eq!(decline.get_server_identifier(), Some(server_id));
+        assert!(decline
+            .get_message_text()
+            .unwrap()
+            .contains("192.168.1.100"));
+        assert!(FORBIDDEN_TAGS.iter().all(|tag| !tags.contains(tag)));
+    }
+
+    #[test]
+    fn test_discover_encoding_is_deterministic() {
+        let encode = || {
+            let mut builder = MessageBuilder::new(hardware_addr(), Some(vec![1, 2, 3]), 1500);
+            let message = builder
+                .make_discover_message(0x1234, Some(Ipv4Addr::new(192, 168, 1, 100)), Some(3600))
+                .unwrap();
+
+            let mut buf = WriteBuffer::new();
+            message.write::<BigEndian>(&mut buf).unwrap();
+            buf.bytes().to_vec()
+        };
+
+        assert_eq!(encode(), encode());
+    }
+
+    #[test]
+    fn test_hops_round_trip() {
+        let mut builder = MessageBuilder::new(hardware_addr(), None, 1500).with_hops(3);
+        let discover = builder.make_discover_message(1, None, None).unwrap();
+        assert_eq!(discover.header.hops, 3);
+
+        let mut buf = WriteBuffer::new();
+        discover.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes()[3], 3);
+
+        let read = Message::from_bytes(buf.bytes()).unwrap();
+        assert_eq!(read.header.hops, 3);
+
+        // Replies never carry the hop count of the request
+        let offer = builder
+            .make_offer_message(
+                &read,
+                Ipv4Addr::new(192, 168, 1, 100),
+                Ipv4Addr::new(192, 168, 1, 1),
+                3600,
+                Vec::new(),
+            )
+            .unwrap();
+        assert_eq!(offer.header.hops, 0);
+    }
 }
@@ -14,7 +14,7 @@ pub enum CmdError {
     IoError(#[from] std::io::Error),
 }
 
-pub fn set_interface_up(interface_name: &String) -> Result<(), CmdError> {
+pub fn set_interface_up(interface_name: &str) -> Result<(), CmdError> {
     let status = Command::new("ip")
         .args(["link", "set"])
         .args(["dev", interface_name, "up"])
@@ -28,7 +28,7 @@ pub fn set_interface_up(interface_name: &String) -> Result<(), CmdError> {
 }
 
 /// Flushes the IP address of the interface with `interface_name`.
-pub fn flush_ip_address(interface_name: &String) -> Result<(), CmdError> {
+pub fn flush_ip_address(interface_name: &str) -> Result<(), CmdError> {
     // ip -4 addr flush dev ${interface}
     let status = Command::new("ip")
         .arg("-4")
@@ -44,7 +44,7 @@ pub fn flush_ip_address(interface_name: &String) -> Result<(), CmdError> {
 }
 
 /// Adds an IP address to the interface with `interface_name`.
-pub fn add_ip_address(ip_addr: &Ipv4Addr, interface_name: &String) -> Result<(), CmdError> {
+pub fn add_ip_address(ip_addr: &Ipv4Addr, interface_name: &str) -> Result<(), CmdError> {
     let status = Command::new("ip")
         .arg("-4")
         .args(["addr", "add", &ip_addr.to_string()])
@@ -57,3 +57,93 @@ pub fn add_ip_address(ip_addr: &Ipv4Addr, interface_name: &String) -> Result<(),
 
     Ok(())
 }
+
+/// Removes an IP address from the interface with `interface_name`.
+pub fn del_ip_address(ip_addr: &Ipv4Addr, interface_name: &str) -> Result<(), CmdError> {
+    let status = Command::new("ip")
+        .arg("-4")
+        .args(["addr", "del", &ip_addr.to_string()])
+        .args(["dev", interface_name])
+        .status()?;
+
+    if !status.success() {
+        return Err(CmdError::UnexpectedStatus(status));
+    }
+
+    Ok(())
+}
+
+/// Sets the MTU of the interface with `interface_name`.
+pub fn set_mtu(mtu: u16, interface_name: &str) -> Result<(), CmdError> {
+    // ip link set dev ${interface} mtu ${mtu}
+    let status = Command::new("ip")
+        .args(["link", "set"])
+        .args(["dev", interface_name])
+        .args(["mtu", &mtu.to_string()])
+        .status()?;
+
+    if !status.success() {
+        return Err(CmdError::UnexpectedStatus(status));
+    }
+
+    Ok(())
+}
+
+/// Sends a gratuitous ARP for `ip_addr` on the interface with
+/// `interface_name`.
+pub fn announce_address(ip_addr: &Ipv4Addr, interface_name: &str) -> Result<(), CmdError> {
+    // arping -U -c 1 -I ${interface} ${ip_addr}
+    let status = Command::new("arping")
+        .args(["-U", "-c", "1"])
+        .args(["-I", interface_name])
+        .arg(ip_addr.to_string())
+        .status()?;
+
+    if !status.success() {
+        return Err(CmdError::UnexpectedStatus(status));
+    }
+
+    Ok(())
+}
+
+/// Adds a default route via `gateway` with `metric` on the interface with
+/// `interface_name`.
+pub fn add_default_route(
+    gateway: &Ipv4Addr,
+    metric: u32,
+    interface_name: &str,
+) -> Result<(), CmdError> {
+    route("add", gateway, metric, interface_name)
+}
+
+/// Removes the default route via `gateway` with `metric` from the interface
+/// with `interface_name`.
+pub fn del_default_route(
+    gateway: &Ipv4Addr,
+    metric: u32,
+    interface_name: &str,
+) -> Result<(), CmdError> {
+    route("del", gateway, metric, interface_name)
+}
+
+fn route(
+    command: &str,
+    gateway: &Ipv4Addr,
+    metric: u32,
+    interface_name: &str,
+) -> Result<(), CmdError> {
+    // ip -4 route ${command} default via ${gateway} dev ${interface} metric ${metric}
+    let status = Command::new("ip")
+        .arg("-4")
+        .args(["route", command, "default"])
+        .args(["via", &gateway.to_string()])
+        .args(["dev", interface_name])
+        .args(["metric", &metric.to_string()])
+        .status()?;
+
+    if !status.success() {
+        return Err(CmdError::UnexpectedStatus(status));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use std::{fmt, io, net::Ipv4Addr, path::Path};
+
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    client::{
+        cmd::{self, CmdError},
+        dns::RESOLV_CONF_PATH,
+        link, Action, DnsConfig, Route,
+    },
+    LEASE_TARGET,
+};
+
+#[derive(Debug, Error)]
+pub enum ConfigureError {
+    #[error("Command error: {0}")]
+    CmdError(#[from] CmdError),
+
+    /// The interface doesn't exist (anymore).
+    #[error("Interface '{0}' no longer exists")]
+    InterfaceGone(String),
+
+    #[error("Failed to write the resolver configuration: {0}")]
+    ResolvConf(#[from] io::Error),
+
+    /// Error of a configurator supplied by an embedder.
+    #[error("{0}")]
+    Other(String),
+}
+
+/// A [`NetConfigurator`] applies the network configuration received by the
+/// client to an interface. All changes the client makes to the system go
+/// through this trait, which allows embedders to forward them to their own
+/// network management (like NetworkManager) and tests to record them, see
+/// [`MockConfigurator`].
+pub trait NetConfigurator: fmt::Debug + Send + Sync {
+    /// Sets the interface named `interface` up before the client starts.
+    fn set_up(&self, interface: &str) -> Result<(), ConfigureError>;
+
+    /// Adds `addr` to the interface named `interface`.
+    fn add_address(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError>;
+
+    /// Removes `addr` from the interface named `interface`.
+    fn del_address(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError>;
+
+    /// Installs the default `route` on the interface named `interface`.
+    fn add_route(&self, interface: &str, route: &Route) -> Result<(), ConfigureError>;
+
+    /// Removes the default `route` from the interface named `interface`.
+    fn del_route(&self, interface: &str, route: &Route) -> Result<(), ConfigureError>;
+
+    /// Sets the MTU of the interface named `interface`.
+    fn set_mtu(&self, interface: &str, mtu: u16) -> Result<(), ConfigureError>;
+
+    /// Announces `addr` on the link of the interface named `interface` with
+    /// a gratuitous ARP, so neighbors update their caches.
+    fn announce(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError>;
+
+    /// Exports the resolver configuration received on the interface named
+    /// `interface` to the system resolver.
+    fn set_dns(&self, interface: &str, config: &DnsConfig) -> Result<(), ConfigureError>;
+}
+
+/// Configures Linux interfaces using the `ip` and `arping` commands. The
+/// resolver configuration is written to [`RESOLV_CONF_PATH`], the last
+/// interface to receive one wins.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinuxConfigurator;
+
+impl NetConfigurator for LinuxConfigurator {
+    fn set_up(&self, interface: &str) -> Result<(), ConfigureError> {
+        checked(interface, cmd::set_interface_up(interface))
+    }
+
+    fn add_address(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError> {
+        checked(interface, cmd::add_ip_address(&addr, interface))
+    }
+
+    fn del_address(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError> {
+        checked(interface, cmd::del_ip_address(&addr, interface))
+    }
+
+    fn add_route(&self, interface: &str, route: &Route) -> Result<(), ConfigureError> {
+        checked(
+            interface,
+            cmd::add_default_route(&route.gateway, route.metric, interface),
+        )
+    }
+
+    fn del_route(&self, interface: &str, route: &Route) -> Result<(), ConfigureError> {
+        checked(
+            interface,
+            cmd::del_default_route(&route.gateway, route.metric, interface),
+        )
+    }
+
+    fn set_mtu(&self, interface: &str, mtu: u16) -> Result<(), ConfigureError> {
+        checked(interface, cmd::set_mtu(mtu, interface))
+    }
+
+    fn announce(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError> {
+        checked(interface, cmd::announce_address(&addr, interface))
+    }
+
+    fn set_dns(&self, _interface: &str, config: &DnsConfig) -> Result<(), ConfigureError> {
+        Ok(config.write_resolv_conf(Path::new(RESOLV_CONF_PATH))?)
+    }
+}
+
+/// Maps the failure of a command on `interface` to
+/// [`ConfigureError::InterfaceGone`] if the interface was removed. The `ip`
+/// command only reports this with its exit status.
+fn checked(interface: &str, result: Result<(), CmdError>) -> Result<(), ConfigureError> {
+    match result {
+        Err(CmdError::IoError(err)) if link::is_interface_gone(&err) => {
+            Err(ConfigureError::InterfaceGone(interface.to_string()))
+        }
+        Err(CmdError::UnexpectedStatus(_)) if !link::interface_exists(interface) => {
+            Err(ConfigureError::InterfaceGone(interface.to_string()))
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Executes the interface configuring `action` with `configurator`.
+/// Returns `false` without doing anything if `action` doesn't touch the
+/// interface, like sending a message.
+pub(crate) fn apply(
+    configurator: &dyn NetConfigurator,
+    interface: &str,
+    action: &Action,
+) -> Result<bool, ConfigureError> {
+    match action {
+        Action::SetMtu(mtu) => {
+            info!(target: LEASE_TARGET, mtu, interface, "setting MTU");
+            configurator.set_mtu(interface, *mtu)?;
+        }
+        Action::ConfigureAddress(addr) => {
+            info!(target: LEASE_TARGET, %addr, interface, "adding address");
+            configurator.add_address(interface, *addr)?;
+        }
+        Action::RemoveAddress(addr) => {
+            info!(target: LEASE_TARGET, %addr, interface, "removing address");
+            configurator.del_address(interface, *addr)?;
+        }
+        Action::Announce(addr) => {
+            // Neighbors learn the address from the next packet anyway, for
+            // example if arping isn't installed
+            match configurator.announce(interface, *addr) {
+                Err(err @ ConfigureError::InterfaceGone(_)) => return Err(err),
+                Err(err) => warn!(target: LEASE_TARGET, %addr, %err, "failed to announce address"),
+                Ok(()) => {}
+            }
+        }
+        Action::AddRoute(route) => {
+            info!(
+                target: LEASE_TARGET,
+                gateway = %route.gateway,
+                metric = route.metric,
+                "adding default route"
+            );
+            configurator.add_route(interface, route)?;
+        }
+        Action::RemoveRoute(route) => {
+            info!(
+                target: LEASE_TARGET,
+                gateway = %route.gateway,
+                metric = route.metric,
+                "removing default route"
+            );
+            configurator.del_route(interface, route)?;
+        }
+        Action::ConfigureDns(config) => {
+            info!(
+                target: LEASE_TARGET,
+                nameservers = ?config.nameservers,
+                interface,
+                "configuring resolver"
+            );
+
+            // The address is usable without name resolution, so only a
+            // removed interface ends the client
+            match configurator.set_dns(interface, config) {
+                Err(err @ ConfigureError::InterfaceGone(_)) => return Err(err),
+                Err(err) => warn!(target: LEASE_TARGET, %err, "failed to configure resolver"),
+                Ok(()) => {}
+            }
+        }
+        Action::Send(..) | Action::SetTimer(_) | Action::Lease(_) => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+#[cfg(any(test, feature = "testing"))]
+pub use mock::{ConfiguratorCall, MockConfigurator};
+
+#[cfg(any(test, feature = "testing"))]
+mod mock {
+    use std::{
+        net::Ipv4Addr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use crate::client::{
+        configurator::{ConfigureError, NetConfigurator},
+        DnsConfig, Route,
+    };
+
+    /// A call to a [`NetConfigurator`], recorded by the [`MockConfigurator`].
+    /// The first field is the interface name.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ConfiguratorCall {
+        SetUp(String),
+        AddAddress(String, Ipv4Addr),
+        DelAddress(String, Ipv4Addr),
+        AddRoute(String, Route),
+        DelRoute(String, Route),
+        SetMtu(String, u16),
+        Announce(String, Ipv4Addr),
+        SetDns(String, DnsConfig),
+    }
+
+    /// Test-only [`NetConfigurator`] which doesn't touch any interface, but
+    /// records every call. Clones share the recorded calls, like the
+    /// [`RecordingTransport`](crate::RecordingTransport).
+    #[derive(Debug, Clone, Default)]
+    pub struct MockConfigurator {
+        calls: Arc<Mutex<Vec<ConfiguratorCall>>>,
+        interface_gone: Arc<AtomicBool>,
+    }
+
+    impl MockConfigurator {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Removes and returns all recorded calls in the order they were
+        /// made.
+        pub fn take(&self) -> Vec<ConfiguratorCall> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+
+        /// Simulates the removal of the interface while `gone` is set. Calls
+        /// fail with [`ConfigureError::InterfaceGone`] and aren't recorded.
+        pub fn set_interface_gone(&self, gone: bool) {
+            self.interface_gone.store(gone, Ordering::Relaxed);
+        }
+
+        fn record(&self, call: ConfiguratorCall) -> Result<(), ConfigureError> {
+            if self.interface_gone.load(Ordering::Relaxed) {
+                let (ConfiguratorCall::SetUp(interface)
+                | ConfiguratorCall::AddAddress(interface, _)
+                | ConfiguratorCall::DelAddress(interface, _)
+                | ConfiguratorCall::AddRoute(interface, _)
+                | ConfiguratorCall::DelRoute(interface, _)
+                | ConfiguratorCall::SetMtu(interface, _)
+                | ConfiguratorCall::Announce(interface, _)
+                | ConfiguratorCall::SetDns(interface, _)) = call;
+                return Err(ConfigureError::InterfaceGone(interface));
+            }
+
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+    }
+
+    impl NetConfigurator for MockConfigurator {
+        fn set_up(&self, interface: &str) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::SetUp(interface.into()))
+        }
+
+        fn add_address(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::AddAddress(interface.into(), addr))
+        }
+
+        fn del_address(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::DelAddress(interface.into(), addr))
+        }
+
+        fn add_route(&self, interface: &str, route: &Route) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::AddRoute(interface.into(), *route))
+        }
+
+        fn del_route(&self, interface: &str, route: &Route) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::DelRoute(interface.into(), *route))
+        }
+
+        fn set_mtu(&self, interface: &str, mtu: u16) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::SetMtu(interface.into(), mtu))
+        }
+
+        fn announce(&self, interface: &str, addr: Ipv4Addr) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::Announce(interface.into(), addr))
+        }
+
+        fn set_dns(&self, interface: &str, config: &DnsConfig) -> Result<(), ConfigureError> {
+            self.record(ConfiguratorCall::SetDns(interface.into(), config.clone()))
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+
+use crate::types::Message;
+
+/// Path of the system resolver configuration written by the
+/// [`LinuxConfigurator`](crate::client::LinuxConfigurator).
+pub const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// DNS resolver configuration derived from a DHCPACK. This is what the
+/// client exports to the system resolver (e.g. `/etc/resolv.conf`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DnsConfig {
+    pub nameservers: Vec<Ipv4Addr>,
+    pub domain: Option<String>,
+    pub search: Vec<String>,
+}
+
+impl Display for DnsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(domain) = &self.domain {
+            writeln!(f, "domain {}", domain)?;
+        }
+
+        if !self.search.is_empty() {
+            writeln!(f, "search {}", self.search.join(" "))?;
+        }
+
+        for nameserver in &self.nameservers {
+            writeln!(f, "nameserver {}", nameserver)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DnsConfig {
+    /// Derives the resolver configuration from the options of `message`.
+    ///
+    /// If the domain search option (119) is present, RFC 3397 states it
+    /// takes precedence over the domain name option (15) for search
+    /// purposes. The domain name is still kept as the plain domain. Without
+    /// option 119, the domain name is used as the single search domain.
+    pub fn from_message(message: &Message) -> Self {
+        let nameservers = message
+            .get_domain_name_servers()
+            .cloned()
+            .unwrap_or_default();
+
+        let domain = message.get_domain_name().cloned();
+
+        let search = match (message.get_domain_search(), &domain) {
+            (Some(search), _) => search.domains().to_vec(),
+            (None, Some(domain)) => vec![domain.clone()],
+            (None, None) => Vec::new(),
+        };
+
+        Self {
+            nameservers,
+            domain,
+            search,
+        }
+    }
+
+    /// Writes the configuration to `path` in the `resolv.conf(5)` format.
+    /// The file is replaced atomically, so resolvers never read a partial
+    /// file. If `path` is a symlink, like the stub file of a local resolver
+    /// daemon, its target is replaced.
+    pub fn write_resolv_conf(&self, path: &Path) -> io::Result<()> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        let mut tmp = PathBuf::from(&path).into_os_string();
+        tmp.push(".vulcan-tmp");
+
+        fs::write(&tmp, self.to_string())?;
+        fs::rename(&tmp, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{options::DomainSearch, OptionData, OptionTag};
+
+    fn message(domain_name: Option<&str>, domain_search: Option<Vec<&str>>) -> Message {
+        let mut message = Message::new_with_xid(1);
+
+        if let Some(name) = domain_name {
+            message
+                .add_option_parts(OptionTag::DomainName, OptionData::DomainName(name.into()))
+                .unwrap();
+        }
+
+        if let Some(domains) = domain_search {
+            let domains = domains.into_iter().map(String::from).collect();
+            message
+                .add_option_parts(
+                    OptionTag::DomainSearch,
+                    OptionData::DomainSearch(DomainSearch::new(domains)),
+                )
+                .unwrap();
+        }
+
+        message
+    }
+
+    #[test]
+    fn test_both_present_prefers_search_list() {
+        let config = DnsConfig::from_message(&message(Some("lan"), Some(vec!["corp.example"])));
+
+        assert_eq!(config.domain, Some("lan".into()));
+        assert_eq!(config.search, vec!["corp.example".to_string()]);
+    }
+
+    #[test]
+    fn test_domain_name_only() {
+        let config = DnsConfig::from_message(&message(Some("lan"), None));
+
+        assert_eq!(config.domain, Some("lan".into()));
+        assert_eq!(config.search, vec!["lan".to_string()]);
+    }
+
+    #[test]
+    fn test_search_list_only() {
+        let config = DnsConfig::from_message(&message(None, Some(vec!["a.example", "b.example"])));
+
+        assert_eq!(config.domain, None);
+        assert_eq!(
+            config.search,
+            vec!["a.example".to_string(), "b.example".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_neither_present() {
+        let config = DnsConfig::from_message(&message(None, None));
+        assert_eq!(config, DnsConfig::default());
+    }
+
+    #[test]
+    fn test_write_resolv_conf() {
+        let dir = std::env::temp_dir().join(format!("vulcan-resolv-{}", rand::random::<u64>()));
+        fs::create_dir(&dir).unwrap();
+
+        let target = dir.join("stub-resolv.conf");
+        let link = dir.join("resolv.conf");
+        fs::write(&target, "nameserver 127.0.0.53\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let config = DnsConfig {
+            nameservers: vec![Ipv4Addr::new(192, 168, 1, 1)],
+            domain: Some("lan".into()),
+            search: vec!["lan".into()],
+        };
+        config.write_resolv_conf(&link).unwrap();
+
+        // The symlink is kept and its target replaced
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "domain lan\nsearch lan\nnameserver 192.168.1.1\n"
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
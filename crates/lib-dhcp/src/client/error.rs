@@ -4,39 +4,101 @@ use network_interface::Error as InterfaceError;
 use thiserror::Error;
 
 use crate::{
-    client::{cmd::CmdError, state::DhcpStateError},
+    client::{
+        cmd::CmdError, configurator::ConfigureError, link, state::DhcpStateError,
+        state_dir::StateDirError,
+    },
     types::{MessageError, ParseHardwareAddrError},
+    TransportError,
 };
 
 #[derive(Debug, Error)]
 pub enum ClientError {
-    #[error("IO error: {0}")]
+    #[error("{code} IO error: {0}", code = self.code())]
     IO(#[from] std::io::Error),
 
-    #[error("Bind error: Failed to create and bind UDP socket after {0:?}")]
+    #[error("{code} Bind error: Failed to create and bind UDP socket after {0:?}", code = self.code())]
     BindTimeout(time::Duration),
 
-    #[error("Failed to retrieve interfaces: {0}")]
+    #[error("{code} Failed to retrieve interfaces: {0}", code = self.code())]
     InterfaceError(#[from] InterfaceError),
 
-    #[error("Failed to select a network interface: {0}")]
+    #[error("{code} Failed to select a network interface: {0}", code = self.code())]
     NoInterfaceFound(String),
 
-    #[error("Parse hardware address error: {0}")]
+    #[error("{code} Parse hardware address error: {0}", code = self.code())]
     ParseHardwareAddrError(#[from] ParseHardwareAddrError),
 
-    #[error("No hardware/mac address on interface '{0}' found")]
+    #[error("{code} No hardware/mac address on interface '{0}' found", code = self.code())]
     NoHardwareAddressError(String),
 
-    #[error("DHCP state error: {0}")]
+    #[error("{code} DHCP state error: {0}", code = self.code())]
     DhcpStateError(#[from] DhcpStateError),
 
-    #[error("Message error: {0}")]
+    #[error("{code} Message error: {0}", code = self.code())]
     MessageError(#[from] MessageError),
 
-    #[error("Command error: {0}")]
+    #[error("{code} Transport error: {0}", code = self.code())]
+    TransportError(#[from] TransportError),
+
+    #[error("{code} Command error: {0}", code = self.code())]
     CmdError(#[from] CmdError),
 
-    #[error("Invalid message format or length: {0}")]
+    #[error("{code} Invalid message format or length: {0}", code = self.code())]
     Invalid(String),
+
+    #[error("{code} Hardware address of interface '{0}' is {1} octets long, expected {2} for Ethernet", code = self.code())]
+    HardwareAddrLength(String, usize, usize),
+
+    #[error("{code} State directory error: {0}", code = self.code())]
+    StateDirError(#[from] StateDirError),
+
+    #[error("{code} Failed to configure the interface: {0}", code = self.code())]
+    ConfigureError(#[from] ConfigureError),
+
+    #[error("{code} Interface '{0}' disappeared", code = self.code())]
+    InterfaceGone(String),
+
+    #[error("{code} MTU of interface '{0}' is {1}, below the max DHCP message size of {2}", code = self.code())]
+    MtuTooSmall(String, u32, u16),
+
+    #[error("{code} Invalid client configuration: {0}", code = self.code())]
+    InvalidConfig(String),
 }
+
+impl ClientError {
+    /// Returns if the error was caused by the interface disappearing, like
+    /// a send failing with `ENODEV` on a socket bound to it.
+    pub fn is_interface_gone(&self) -> bool {
+        match self {
+            Self::InterfaceGone(_) | Self::ConfigureError(ConfigureError::InterfaceGone(_)) => true,
+            Self::IO(err)
+            | Self::TransportError(TransportError::Io(err))
+            | Self::CmdError(CmdError::IoError(err))
+            | Self::ConfigureError(ConfigureError::CmdError(CmdError::IoError(err))) => {
+                link::is_interface_gone(err)
+            }
+            _ => false,
+        }
+    }
+}
+
+error_codes!(ClientError {
+    BindTimeout => "VULCAN-C101",
+    IO => "VULCAN-C102",
+    InterfaceError => "VULCAN-C103",
+    NoInterfaceFound => "VULCAN-C104",
+    ParseHardwareAddrError => "VULCAN-C105",
+    NoHardwareAddressError => "VULCAN-C106",
+    DhcpStateError => "VULCAN-C107",
+    MessageError => "VULCAN-C108",
+    TransportError => "VULCAN-C109",
+    CmdError => "VULCAN-C110",
+    Invalid => "VULCAN-C111",
+    HardwareAddrLength => "VULCAN-C112",
+    StateDirError => "VULCAN-C113",
+    ConfigureError => "VULCAN-C114",
+    InterfaceGone => "VULCAN-C115",
+    MtuTooSmall => "VULCAN-C116",
+    InvalidConfig => "VULCAN-C117",
+});
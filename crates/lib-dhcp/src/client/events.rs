@@ -0,0 +1,42 @@
+use std::net::Ipv4Addr;
+
+use serde::Serialize;
+
+/// Number of lease events buffered for each subscriber. Subscribers falling
+/// further behind miss the oldest events.
+pub(crate) const LEASE_EVENT_CAPACITY: usize = 16;
+
+/// Change of the lease of a [`Client`](crate::Client), see
+/// [`Client::subscribe`](crate::Client::subscribe).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LeaseEvent {
+    /// A new lease was acknowledged and its address configured.
+    Bound(LeaseInfo),
+
+    /// The lease was extended in RENEWING or REBINDING.
+    Renewed(LeaseInfo),
+
+    /// The lease expired or was refused with a DHCPNAK. Its address was
+    /// removed from the interface.
+    Lost { addr: Ipv4Addr },
+}
+
+/// Lease as acknowledged by the server. Times are in seconds, starting when
+/// the DHCPACK was received.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LeaseInfo {
+    pub addr: Ipv4Addr,
+
+    /// Server identifier of the server which granted the lease.
+    pub server: Option<Ipv4Addr>,
+
+    /// Lease time, `u32::MAX` for infinite leases.
+    pub lease_time: u32,
+
+    /// Time until the client starts renewing the lease (T1).
+    pub renewal_time: u32,
+
+    /// Time until the client starts rebinding the lease (T2).
+    pub rebinding_time: u32,
+}
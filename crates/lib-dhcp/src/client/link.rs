@@ -0,0 +1,261 @@
+use std::{
+    fmt, io,
+    mem::size_of,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use async_trait::async_trait;
+use tokio::io::unix::AsyncFd;
+use tracing::warn;
+
+use crate::{client::ClientError, ListenInterface, DAEMON_TARGET};
+
+/// What the client does when its interface disappears while running, like
+/// a USB tethering or VPN interface which was removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InterfaceGonePolicy {
+    /// Stop and return [`ClientError::InterfaceGone`], so that a supervisor
+    /// can act on it.
+    ///
+    /// [`ClientError::InterfaceGone`]: crate::client::ClientError::InterfaceGone
+    #[default]
+    Exit,
+
+    /// Forget the binding, wait in WAITING-FOR-INTERFACE until an interface
+    /// with the same name appears and start over in INIT.
+    Wait,
+}
+
+/// Returns if `err` reports that the interface doesn't exist (anymore).
+/// Sockets bound to a removed interface fail with `ENODEV` or `ENXIO`.
+pub(crate) fn is_interface_gone(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::ENODEV | libc::ENXIO))
+}
+
+/// Returns if an interface named `name` currently exists.
+pub(crate) fn interface_exists(name: &str) -> bool {
+    ListenInterface::lookup(name).is_ok()
+}
+
+/// Checks that the MTU of the interface `name` fits messages of
+/// `max_message_size` octets. Broadcasts exceeding the MTU are fragmented or
+/// dropped, so a smaller MTU is logged as a warning, or refused in `strict`
+/// mode.
+pub(crate) fn check_mtu(
+    name: &str,
+    mtu: u32,
+    max_message_size: u16,
+    strict: bool,
+) -> Result<(), ClientError> {
+    if mtu >= u32::from(max_message_size) {
+        return Ok(());
+    }
+
+    if strict {
+        return Err(ClientError::MtuTooSmall(
+            name.to_string(),
+            mtu,
+            max_message_size,
+        ));
+    }
+
+    warn!(
+        target: DAEMON_TARGET,
+        interface = name,
+        mtu,
+        max_message_size,
+        "interface MTU is below the max DHCP message size"
+    );
+    Ok(())
+}
+
+/// An [`InterfaceWatcher`] waits for an interface to appear. The client
+/// uses it in WAITING-FOR-INTERFACE, tests can simulate interface events
+/// with the [`MockInterfaceWatcher`].
+#[async_trait]
+pub trait InterfaceWatcher: fmt::Debug + Send + Sync {
+    /// Returns once an interface named `name` exists, right away if it
+    /// already does.
+    async fn wait_for(&self, name: &str) -> io::Result<()>;
+}
+
+/// Waits for interfaces using link notifications of the kernel
+/// (`RTMGRP_LINK` on a rtnetlink socket).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetlinkWatcher;
+
+#[async_trait]
+impl InterfaceWatcher for NetlinkWatcher {
+    async fn wait_for(&self, name: &str) -> io::Result<()> {
+        // Subscribe before looking the interface up, so that it can't
+        // appear unnoticed in between
+        let socket = subscribe_link_events()?;
+        let mut buf = [0u8; 8192];
+
+        loop {
+            // Any link event is a reason to look again, there is no need
+            // to parse the notification
+            if interface_exists(name) {
+                return Ok(());
+            }
+
+            let mut guard = socket.readable().await?;
+
+            let result = guard.try_io(|fd| {
+                let res = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                        0,
+                    )
+                };
+
+                if res < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            });
+
+            match result {
+                Ok(Err(err)) if err.raw_os_error() != Some(libc::ENOBUFS) => return Err(err),
+                // Notifications were dropped, which is fine as the
+                // interface is looked up again anyway
+                Ok(_) => {}
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Opens a rtnetlink socket subscribed to link notifications.
+fn subscribe_link_events() -> io::Result<AsyncFd<OwnedFd>> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as u16;
+    addr.nl_groups = libc::RTMGRP_LINK as u32;
+
+    let res = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const _ as *const libc::sockaddr,
+            size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    AsyncFd::new(fd)
+}
+
+#[cfg(any(test, feature = "testing"))]
+pub use mock::MockInterfaceWatcher;
+
+#[cfg(any(test, feature = "testing"))]
+mod mock {
+    use std::{
+        collections::HashSet,
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use tokio::sync::Notify;
+
+    use crate::client::link::InterfaceWatcher;
+
+    #[derive(Debug, Default)]
+    struct Interfaces {
+        present: Mutex<HashSet<String>>,
+        changed: Notify,
+    }
+
+    /// Test-only [`InterfaceWatcher`] on simulated interfaces, which are
+    /// added and removed by the test. Clones share the interfaces.
+    #[derive(Debug, Clone, Default)]
+    pub struct MockInterfaceWatcher {
+        interfaces: Arc<Interfaces>,
+    }
+
+    impl MockInterfaceWatcher {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Simulates the interface `name` appearing, which wakes up waiting
+        /// clients.
+        pub fn add(&self, name: &str) {
+            self.interfaces
+                .present
+                .lock()
+                .unwrap()
+                .insert(name.to_string());
+            self.interfaces.changed.notify_waiters();
+        }
+
+        /// Simulates the removal of the interface `name`.
+        pub fn remove(&self, name: &str) {
+            self.interfaces.present.lock().unwrap().remove(name);
+            self.interfaces.changed.notify_waiters();
+        }
+    }
+
+    #[async_trait]
+    impl InterfaceWatcher for MockInterfaceWatcher {
+        async fn wait_for(&self, name: &str) -> io::Result<()> {
+            loop {
+                let changed = self.interfaces.changed.notified();
+
+                if self.interfaces.present.lock().unwrap().contains(name) {
+                    return Ok(());
+                }
+
+                changed.await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_mtu() {
+        let capture = crate::utils::LogCapture::default();
+
+        tracing::subscriber::with_default(capture.subscriber(tracing::Level::WARN), || {
+            check_mtu("eth0", 1500, 1500, true).unwrap();
+            assert!(capture.output().is_empty());
+
+            // An interface with a low MTU, like a tunnel or a misconfigured
+            // bridge
+            check_mtu("tun0", 576, 1500, false).unwrap();
+        });
+
+        let output = capture.output();
+        assert!(output.contains("interface MTU is below the max DHCP message size"));
+        assert!(output.contains("interface=\"tun0\" mtu=576 max_message_size=1500"));
+
+        assert!(matches!(
+            check_mtu("tun0", 576, 1500, true),
+            Err(ClientError::MtuTooSmall(name, 576, 1500)) if name == "tun0"
+        ));
+    }
+}
@@ -1,32 +1,68 @@
 use std::{
+    future,
     net::{Ipv4Addr, SocketAddr},
-    time::{self, Duration},
+    ops::Range,
+    path::PathBuf,
+    sync::Arc,
+    time,
 };
 
 use binbuf::prelude::*;
 use network_interface::NetworkInterface;
-use rand::{self, Rng};
+use rand::RngCore;
 use tokio::{
     net::{ToSocketAddrs, UdpSocket},
+    select,
+    sync::broadcast,
     time::{sleep, timeout},
 };
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, info, instrument, warn};
 
 use crate::{
     builder::MessageBuilder,
-    client::state::{ClientState, DhcpState, DhcpStateMachine},
-    types::{options::DhcpMessageType, HardwareAddr, Message, OptionData, OptionTag},
-    utils, TimeoutResult, MINIMAL_RETRANS_DURATION_SECS, MINIMUM_LEGAL_MAX_MESSAGE_SIZE,
-    SERVER_PORT,
+    client::status::StatusServer,
+    net,
+    server::control::{bind_control_socket, serve_control},
+    types::{HardwareAddr, HardwareType, Message, OpCode, OptionTag},
+    utils, Clock, LogControl, SystemClock, Transport, CLIENT_PORT, DAEMON_TARGET,
+    DEFAULT_INITIAL_DELAY_MAX_SECS, DEFAULT_INITIAL_DELAY_MIN_SECS, DEFAULT_MAX_INIT_BACKOFF_SECS,
+    DEFAULT_ROUTE_METRIC, FSM_TARGET, MINIMUM_LEGAL_MAX_MESSAGE_SIZE, SERVER_PORT, WIRE_TARGET,
 };
 
 mod cmd;
+mod configurator;
+mod dns;
 mod error;
+mod events;
+mod link;
+mod ntp;
+mod packet;
+mod protocol;
+mod routes;
 mod state;
+mod state_dir;
+mod status;
 mod storage;
 // mod timers;
 
+#[cfg(any(test, feature = "testing"))]
+pub use configurator::{ConfiguratorCall, MockConfigurator};
+pub use configurator::{ConfigureError, LinuxConfigurator, NetConfigurator};
+pub use dns::{DnsConfig, RESOLV_CONF_PATH};
 pub use error::ClientError;
+use events::LEASE_EVENT_CAPACITY;
+pub use events::{LeaseEvent, LeaseInfo};
+#[cfg(any(test, feature = "testing"))]
+pub use link::MockInterfaceWatcher;
+pub use link::{InterfaceGonePolicy, InterfaceWatcher, NetlinkWatcher};
+pub use ntp::NtpConfig;
+pub use packet::PacketSocket;
+pub use protocol::{Action, Dest, Protocol};
+pub use routes::Route;
+pub use state::DhcpState;
+use state_dir::LEASE_FILE;
+pub use state_dir::{StateDir, StateDirError, DEFAULT_STATE_ROOT};
+pub use status::DEFAULT_STATUS_SOCKET;
 
 pub struct ClientBuilder {
     /// Duration before the binding process of the socket times out.
@@ -50,6 +86,61 @@ pub struct ClientBuilder {
 
     /// Network interface name
     interface: String,
+
+    /// Address and port the UDP socket is bound to.
+    bind_address: SocketAddr,
+
+    /// Receive replies on a packet socket instead of the UDP socket.
+    packet_socket: bool,
+
+    /// Drop datagrams which can't be DHCP messages in the kernel.
+    socket_filter: bool,
+
+    /// Order in which options are emitted, empty for the default order.
+    option_order: Vec<OptionTag>,
+
+    /// Metric of the route via the first router.
+    route_metric: u32,
+
+    /// Number of DHCPDISCOVERs sent per cycle.
+    max_discover_retries: u32,
+
+    /// Range of the random delay before the first DHCPDISCOVER of a cycle.
+    initial_delay: Range<time::Duration>,
+
+    /// Upper bound of the additional wait in INIT after failed cycles.
+    max_init_backoff: time::Duration,
+
+    /// Refuse DHCPOFFERs and DHCPACKs failing [`Message::validate`].
+    strict: bool,
+
+    /// Check the interface MTU against the max DHCP message size.
+    mtu_check: bool,
+
+    /// Directory the client persists its state in, a directory per
+    /// interface below [`DEFAULT_STATE_ROOT`] if unset.
+    state_dir: Option<PathBuf>,
+
+    /// Random number generator, seeded from the OS if unset.
+    rng: Option<Box<dyn RngCore + Send>>,
+
+    /// Applies the received configuration to the interface.
+    configurator: Arc<dyn NetConfigurator>,
+
+    /// What to do when the interface disappears.
+    interface_gone_policy: InterfaceGonePolicy,
+
+    /// Waits for the interface to return under [`InterfaceGonePolicy::Wait`].
+    interface_watcher: Arc<dyn InterfaceWatcher>,
+
+    /// Source of the current time.
+    clock: Arc<dyn Clock>,
+
+    /// Path of the status socket, which is not served if unset.
+    status_socket: Option<PathBuf>,
+
+    /// Changes the log filter on request of the status socket.
+    log_control: Option<Arc<LogControl>>,
 }
 
 impl Default for ClientBuilder {
@@ -59,44 +150,117 @@ impl Default for ClientBuilder {
             read_timeout: time::Duration::from_secs(2),
             write_timeout: time::Duration::from_secs(2),
             interface: String::from("eth0"),
+            bind_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, CLIENT_PORT)),
             max_dhcp_message_size: 1500,
             interface_fallback: false,
             client_identifier: None,
+            option_order: Vec::new(),
+            packet_socket: false,
+            socket_filter: true,
+            route_metric: DEFAULT_ROUTE_METRIC,
+            max_discover_retries: 1,
+            initial_delay: time::Duration::from_secs(DEFAULT_INITIAL_DELAY_MIN_SECS)
+                ..time::Duration::from_secs(DEFAULT_INITIAL_DELAY_MAX_SECS),
+            max_init_backoff: time::Duration::from_secs(DEFAULT_MAX_INIT_BACKOFF_SECS),
+            strict: false,
+            mtu_check: true,
+            state_dir: None,
+            clock: Arc::new(SystemClock),
+            rng: None,
+            configurator: Arc::new(LinuxConfigurator),
+            interface_gone_policy: InterfaceGonePolicy::default(),
+            interface_watcher: Arc::new(NetlinkWatcher),
+            status_socket: None,
+            log_control: None,
         }
     }
 }
 
 impl ClientBuilder {
     pub fn build(self) -> Result<Client, ClientError> {
+        self.validate()?;
+
         let interface =
             match utils::select_network_interface(&self.interface, self.interface_fallback)? {
                 Some(ifa) => ifa,
                 None => return Err(ClientError::NoInterfaceFound(self.interface)),
             };
 
-        let hardware_address = match &interface.mac_addr {
-            Some(mac_addr) => HardwareAddr::try_from(mac_addr)?,
-            None => return Err(ClientError::NoHardwareAddressError(interface.name)),
-        };
+        self.build_on(interface)
+    }
+
+    /// Builds the client for the selected `interface`.
+    fn build_on(self, interface: NetworkInterface) -> Result<Client, ClientError> {
+        let hardware_address =
+            ethernet_hardware_address(&interface.name, interface.mac_addr.as_ref())?;
+
+        let state_path = self
+            .state_dir
+            .unwrap_or_else(|| StateDir::default_path(&interface.name));
 
         let builder = MessageBuilder::new(
             hardware_address.clone(),
             self.client_identifier,
             self.max_dhcp_message_size,
-        );
+        )
+        .with_option_order(self.option_order);
+
+        let mut protocol = Protocol::new(builder, self.read_timeout)
+            .with_route_metric(self.route_metric)
+            .with_max_discover_retries(self.max_discover_retries)
+            .with_initial_delay(self.initial_delay)
+            .with_max_init_backoff(self.max_init_backoff)
+            .with_strict(self.strict)
+            .with_interface_name(interface.name.clone());
+
+        if let Some(rng) = self.rng {
+            protocol = protocol.with_rng(rng);
+        }
 
         Ok(Client {
-            client_state: ClientState::default(),
+            status: Arc::new(StatusServer::new(interface.name.clone(), self.log_control)),
+            status_socket: self.status_socket,
+            bind_address: self.bind_address,
+            state_path,
+            state_dir: None,
+            lease_events: broadcast::channel(LEASE_EVENT_CAPACITY).0,
+            clock: self.clock,
+            configurator: self.configurator,
+            interface_gone_policy: self.interface_gone_policy,
+            interface_watcher: self.interface_watcher,
+            protocol,
             write_timeout: self.write_timeout,
-            dhcp_state: DhcpState::default(),
             bind_timeout: self.bind_timeout,
-            read_timeout: self.read_timeout,
+            max_dhcp_message_size: self.max_dhcp_message_size,
+            mtu_check: self.mtu_check,
+            strict: self.strict,
+            packet_socket: self.packet_socket,
+            socket_filter: self.socket_filter,
             hardware_address,
             interface,
-            builder,
         })
     }
 
+    /// Checks the settings for consistency, without looking at the system
+    /// like [`ClientBuilder::build`] does.
+    pub fn validate(&self) -> Result<(), ClientError> {
+        if self.max_dhcp_message_size < MINIMUM_LEGAL_MAX_MESSAGE_SIZE {
+            return Err(ClientError::InvalidConfig(format!(
+                "max DHCP message size {} is below the minimum of {}",
+                self.max_dhcp_message_size, MINIMUM_LEGAL_MAX_MESSAGE_SIZE
+            )));
+        }
+
+        if self.initial_delay.start > self.initial_delay.end {
+            return Err(ClientError::InvalidConfig(format!(
+                "initial delay range {:?} is empty",
+                self.initial_delay
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn with_bind_timeout(mut self, bind_timeout: time::Duration) -> Self {
         self.bind_timeout = bind_timeout;
         self
@@ -117,6 +281,18 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the address and port the client's UDP socket is bound to.
+    /// Replies are expected on this port. Defaults to 0.0.0.0:68.
+    pub fn with_bind_address(mut self, addr: SocketAddr) -> Self {
+        self.bind_address = addr;
+        self
+    }
+
+    /// Returns the address and port the client will bind to.
+    pub fn bind_address(&self) -> SocketAddr {
+        self.bind_address
+    }
+
     pub fn with_interface_fallback(mut self, fallback: bool) -> Self {
         self.interface_fallback = fallback;
         self
@@ -131,526 +307,502 @@ impl ClientBuilder {
         self.max_dhcp_message_size = size;
         self
     }
-}
 
-// TODO (Techassi): The T1 and T2 timers a implemented slightly wrong. See 4.4.5
-
-#[derive(Debug)]
-pub struct Client {
-    /// Duration before the binding process of the socket times out.
-    bind_timeout: time::Duration,
-
-    /// Duration before the read process of DHCP answers times out.
-    read_timeout: time::Duration,
-
-    /// Duration before the write process of DHCP requests times out.
-    write_timeout: time::Duration,
+    /// Receive replies on a packet (AF_PACKET) socket. This is required on
+    /// platforms which don't deliver broadcast replies to the UDP socket
+    /// before the interface has an address. Messages are still sent using
+    /// the UDP socket.
+    pub fn with_packet_socket(mut self, packet_socket: bool) -> Self {
+        self.packet_socket = packet_socket;
+        self
+    }
 
-    /// Selected network interface
-    interface: NetworkInterface,
+    /// Drops datagrams on the UDP socket which can't be DHCP messages with a
+    /// BPF filter in the kernel, see [`ServerBuilder::with_socket_filter`](crate::ServerBuilder::with_socket_filter).
+    /// Filtered datagrams never show up as parse errors. Defaults to true.
+    pub fn with_socket_filter(mut self, socket_filter: bool) -> Self {
+        self.socket_filter = socket_filter;
+        self
+    }
 
-    /// Hardware (MAC) address of the selected network interface
-    hardware_address: HardwareAddr,
+    /// Controls the order of emitted options and of the tags in the parameter
+    /// request list (option 55). Some captive portals fingerprint clients by
+    /// this order, which allows mimicking a specific OS. Tags not listed keep
+    /// the default order.
+    pub fn with_option_order(mut self, order: Vec<OptionTag>) -> Self {
+        self.option_order = order;
+        self
+    }
 
-    /// Client state
-    client_state: ClientState,
+    /// Sets how many DHCPDISCOVERs are sent per cycle, the first one
+    /// included, before the client waits and starts over in INIT. Each
+    /// retransmission waits twice as long for offers as the previous one.
+    /// Defaults to 1.
+    pub fn with_max_discover_retries(mut self, retries: u32) -> Self {
+        self.max_discover_retries = retries;
+        self
+    }
 
-    /// DHCP state
-    dhcp_state: DhcpState,
+    /// Sets the range the random delay before the first DHCPDISCOVER of a
+    /// cycle is drawn from, one to ten seconds by default. A zero range
+    /// sends the DHCPDISCOVER right away.
+    pub fn with_initial_delay(mut self, delay: Range<time::Duration>) -> Self {
+        self.initial_delay = delay;
+        self
+    }
 
-    /// Message builder
-    builder: MessageBuilder,
-}
+    /// Sets the upper bound of the additional wait in INIT, which doubles
+    /// with every cycle that ends without a lease. Defaults to 64 seconds,
+    /// zero disables the backoff.
+    pub fn with_max_init_backoff(mut self, max: time::Duration) -> Self {
+        self.max_init_backoff = max;
+        self
+    }
 
-impl Client {
-    /// Create a new DHCP [`Client`] with default values.
-    pub fn new() -> Result<Self, ClientError> {
-        Self::builder().build()
+    /// Enables strict mode, which refuses DHCPOFFERs and DHCPACKs that fail
+    /// [`Message::validate`] instead of accepting them. Defaults to false.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
     }
 
-    /// Create a new [`ClientBuilder`] to declaratively build a [`Client`].
-    pub fn builder() -> ClientBuilder {
-        ClientBuilder::default()
+    /// Checks the MTU of the interface before bringing it up. An MTU below
+    /// the max DHCP message size is logged as a warning, or fails in strict
+    /// mode, see [`ClientBuilder::with_strict`]. Defaults to true.
+    pub fn with_mtu_check(mut self, mtu_check: bool) -> Self {
+        self.mtu_check = mtu_check;
+        self
     }
 
-    /// Run the client as a daemon
-    #[instrument]
-    pub async fn run(&mut self) -> Result<(), ClientError> {
-        info!(interface = self.interface.name, "binding to udp socket");
+    /// Sets the metric of the default route via the first router received
+    /// in the Router option. Following routers get increasing metrics, so
+    /// backup routers are only used if the preferred one is gone.
+    pub fn with_route_metric(mut self, metric: u32) -> Self {
+        self.route_metric = metric;
+        self
+    }
 
-        // Create UDP socket with a bind timeout
-        let socket = create_sock_with_timeout("0.0.0.0:68", self.bind_timeout).await?;
-        socket.bind_device(Some(self.interface.name.as_bytes()))?;
-        socket.set_broadcast(true)?;
+    /// Sets the random number generator used for transaction IDs and the
+    /// random delay in INIT.
+    pub fn with_rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Some(Box::new(rng));
+        self
+    }
 
-        // Ensure the interface is UP
-        debug!("setting interface to up");
-        cmd::set_interface_up(&self.interface.name)?;
+    /// Sets the directory the client persists its state in. The directory is
+    /// locked while the client exists. Defaults to a directory named after
+    /// the interface below [`DEFAULT_STATE_ROOT`].
+    pub fn with_state_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(path.into());
+        self
+    }
 
-        // We use a state machine to keep track of the client state.
-        // This is described in 4.4: https://www.rfc-editor.org/rfc/rfc2131#section-4.4
+    /// Sets the clock the client reads the current time from. Defaults to
+    /// the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
 
-        // NOTE (Techassi): Maaaaan I really want to remove the multiple awaits and just
-        //                  single one at the end of the match expression, but this
-        //                  doesn't work for whatever reason...
-        debug!("entering state machine loop");
-        loop {
-            match self.dhcp_state {
-                DhcpState::Init => self.handle_init().await?,
-                DhcpState::InitReboot => self.handle_init_reboot().await?, // NOOP
-                DhcpState::Selecting => self.handle_selecting(&socket).await?,
-                DhcpState::SelectingSent => self.handle_selecting_sent(&socket).await?,
-                DhcpState::Rebooting => self.handle_rebooting().await?, // NOOP
-                DhcpState::Requesting => self.handle_requesting(&socket).await?,
-                DhcpState::RequestingSent => self.handle_requesting_sent(&socket).await?,
-                DhcpState::Rebinding => self.handle_rebinding(&socket).await?,
-                DhcpState::RebindingSent => self.handle_rebinding_sent(&socket).await?,
-                DhcpState::Bound => self.handle_bound().await?,
-                DhcpState::Renewing => self.handle_renewing(&socket).await?,
-                DhcpState::RenewingSent => self.handle_renewing_sent(&socket).await?,
-            }
-        }
+    /// Sets the configurator which applies addresses and routes to the
+    /// interface. Defaults to the [`LinuxConfigurator`], embedders can
+    /// forward the configuration to their own network management instead.
+    pub fn with_configurator(mut self, configurator: impl NetConfigurator + 'static) -> Self {
+        self.configurator = Arc::new(configurator);
+        self
     }
 
-    /// Handle the DHCP state INIT
-    #[instrument]
-    async fn handle_init(&mut self) -> Result<(), ClientError> {
-        debug!(state = "INIT", "entering dhcp state INIT");
+    /// Sets what the client does when its interface disappears while
+    /// running. Defaults to [`InterfaceGonePolicy::Exit`].
+    pub fn with_interface_gone_policy(mut self, policy: InterfaceGonePolicy) -> Self {
+        self.interface_gone_policy = policy;
+        self
+    }
 
-        // Wait a random amount between one and ten seconds
-        let wait_duration = Duration::from_secs(rand::thread_rng().gen_range(1..=10));
-        debug!(
-            "Waiting for {:?} to send DHCPDISCOVER message",
-            wait_duration
-        );
-        sleep(wait_duration).await;
+    /// Sets the watcher used to wait for the interface to return under
+    /// [`InterfaceGonePolicy::Wait`]. Defaults to the [`NetlinkWatcher`].
+    pub fn with_interface_watcher(mut self, watcher: impl InterfaceWatcher + 'static) -> Self {
+        self.interface_watcher = Arc::new(watcher);
+        self
+    }
 
-        // Transition to SELECTING
-        Ok(self.transition_to(DhcpState::Selecting)?)
+    /// Serves the status socket at `path` while the client is running. It
+    /// reports the state of the client and allows changing the log filter,
+    /// see [`ControlRequest`](crate::ControlRequest).
+    pub fn with_status_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.status_socket = Some(path.into());
+        self
     }
 
-    async fn handle_init_reboot(&mut self) -> Result<(), ClientError> {
-        Ok(())
+    /// Allows changing the log filter through the status socket.
+    pub fn with_log_control(mut self, log_control: Arc<LogControl>) -> Self {
+        self.log_control = Some(log_control);
+        self
     }
+}
 
-    /// Handle the DHCP state SELECTING
-    #[instrument]
-    async fn handle_selecting(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(state = "SELECTING", "entering dhcp state SELECTING");
+#[derive(Debug)]
+pub struct Client {
+    /// Duration before the binding process of the socket times out.
+    bind_timeout: time::Duration,
 
-        // Send DHCPDISCOVER message
-        debug!("sending DHCPDISCOVER message");
-        let discover_message = self.builder.make_discover_message(
-            self.get_xid(),
-            self.destination_addr(),
-            None,
-            None,
-        )?;
-        self.send_message(discover_message, &socket).await?;
+    /// Duration before the write process of DHCP requests times out.
+    write_timeout: time::Duration,
 
-        // Transition to REQUESTING
-        Ok(self.transition_to(DhcpState::SelectingSent)?)
-    }
+    /// Selected network interface
+    interface: NetworkInterface,
 
-    #[instrument]
-    async fn handle_selecting_sent(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(
-            state = "SELECTING-SENT",
-            "entering dhcp state SELECTING-SENT"
-        );
-
-        // Collect replies (DHCPOFFER)
-        // TODO (Techassi): Scale the timeout duration over time
-        let (message, _addr) =
-            match utils::timeout(self.read_timeout, self.recv_message(&socket)).await {
-                TimeoutResult::Timeout => {
-                    self.transition_to(DhcpState::Init)?;
-                    return Ok(());
-                }
-                TimeoutResult::Error(err) => return Err(err),
-                TimeoutResult::Ok(result) => match result {
-                    Some(result) => result,
-                    None => return Ok(()),
-                },
-            };
+    /// Hardware (MAC) address of the selected network interface
+    hardware_address: HardwareAddr,
 
-        // Check if the transaction ID matches
-        if !message.valid_xid(self.get_xid()) {
-            error!(
-                "received response with wrong transaction ID: {} (yours: {})",
-                message.header.xid,
-                self.get_xid()
-            );
-            return Ok(());
-        }
+    /// Receive replies on a packet socket instead of the UDP socket.
+    packet_socket: bool,
 
-        // Check if the DHCP message type is correct
-        if !message.valid_message_type(DhcpMessageType::Offer) {
-            error!("received response with no DHCP message type option set");
-            return Ok(());
-        }
+    /// Drop datagrams which can't be DHCP messages in the kernel.
+    socket_filter: bool,
 
-        // Select offer
-        // Set destination server IP address
-        if let Some(option) = message.get_option(OptionTag::ServerIdentifier) {
-            match option.data() {
-                OptionData::ServerIdentifier(ip) => self.client_state.server_identifier = Some(*ip),
-                _ => {}
-            }
-        }
+    /// Address and port the UDP socket is bound to.
+    bind_address: SocketAddr,
 
-        // Set offered IP address lease time
-        if let Some(option) = message.get_option(OptionTag::IpAddrLeaseTime) {
-            match option.data() {
-                OptionData::IpAddrLeaseTime(time) => {
-                    self.client_state.offered_lease_time = Some(*time)
-                }
-                _ => {}
-            }
-        }
+    /// Max DHCP message size the interface MTU is checked against
+    max_dhcp_message_size: u16,
 
-        // Set offered IP address
-        self.client_state.offered_ip_address = Some(message.yiaddr);
+    /// Check the interface MTU before bringing the interface up
+    mtu_check: bool,
 
-        Ok(self.transition_to(DhcpState::Requesting)?)
-    }
+    /// Fail instead of warning if the interface MTU is too small
+    strict: bool,
 
-    #[instrument]
-    async fn handle_rebooting(&mut self) -> Result<(), ClientError> {
-        Ok(())
-    }
+    /// Sans-IO protocol state machine
+    protocol: Protocol,
 
-    #[instrument]
-    async fn handle_requesting(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(state = "REQUESTING", "entering dhcp state REQUESTING");
+    /// Source of the current time
+    clock: Arc<dyn Clock>,
 
-        // Send DHCPREQUEST message
-        debug!("sending DHCPREQUEST message");
-        let request_message = self.builder.make_request_message(
-            self.get_xid(),
-            self.destination_addr(),
-            self.client_state.offered_ip_address.unwrap(),
-            self.client_state.offered_lease_time.unwrap(),
-        )?;
-        self.send_message(request_message, &socket).await?;
+    /// Applies the received configuration to the interface
+    configurator: Arc<dyn NetConfigurator>,
 
-        Ok(self.transition_to(DhcpState::RequestingSent)?)
-    }
+    /// What to do when the interface disappears
+    interface_gone_policy: InterfaceGonePolicy,
 
-    #[instrument]
-    async fn handle_requesting_sent(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(
-            state = "REQUESTING-SENT",
-            "entering dhcp state REQUESTING-SENT"
-        );
-        // Discard other DHCPOFFER
-
-        // We should get a DHCPACK or DHCPNAK message
-        // TODO (Techassi): Scale the timeout duration over time
-        let (message, _addr) =
-            match utils::timeout(self.read_timeout, self.recv_message(&socket)).await {
-                TimeoutResult::Timeout => {
-                    self.transition_to(DhcpState::Init)?;
-                    return Ok(());
-                }
-                TimeoutResult::Error(err) => return Err(err),
-                TimeoutResult::Ok(result) => match result {
-                    Some(result) => result,
-                    None => return Ok(()),
-                },
-            };
+    /// Waits for the interface to return
+    interface_watcher: Arc<dyn InterfaceWatcher>,
 
-        // Check if the transaction ID matches
-        if !message.valid_xid(self.get_xid()) {
-            error!(
-                "Received response with wrong transaction ID: {} (yours: {})",
-                message.header.xid,
-                self.get_xid()
-            );
-            return Ok(());
-        }
+    /// Path of the directory the client persists its state in
+    state_path: PathBuf,
 
-        // TODO (Techassi): We should introduce a timer which ticks everytime we encounter this code path to
-        // not get stuck in this state
-        match message.get_message_type() {
-            Some(ty) => match ty {
-                DhcpMessageType::Nak => {
-                    return Ok(self.transition_to(DhcpState::Init)?);
-                }
-                DhcpMessageType::Ack => {}
-                _ => return Ok(()),
-            },
-            None => return Ok(()),
-        }
+    /// Locked state directory, opened when the client runs
+    state_dir: Option<StateDir>,
 
-        // Set lease, T1 and T2 timers (DHCPACK)
-        self.client_state.renewal_time = Some(
-            message
-                .get_renewal_t1_time()
-                .unwrap_or((self.client_state.offered_lease_time.unwrap() as f64 * 0.5) as u32),
-        );
+    /// Path of the status socket, if it is served
+    status_socket: Option<PathBuf>,
 
-        self.client_state.rebinding_time = Some(
-            message
-                .get_rebinding_t2_time()
-                .unwrap_or((self.client_state.offered_lease_time.unwrap() as f64 * 0.875) as u32),
-        );
+    /// Answers requests on the status socket
+    status: Arc<StatusServer>,
 
-        info!(
-            "ip -4 addr add {} dev {}",
-            self.client_state.offered_ip_address.unwrap(),
-            self.interface.name
-        );
-        cmd::add_ip_address(
-            &self.client_state.offered_ip_address.unwrap(),
-            &self.interface.name,
-        )?;
+    /// Sends lease events to the subscribers
+    lease_events: broadcast::Sender<LeaseEvent>,
+}
 
-        // Transition to BOUND
-        Ok(self.transition_to(DhcpState::Bound)?)
+impl Client {
+    /// Create a new DHCP [`Client`] with default values.
+    pub fn new() -> Result<Self, ClientError> {
+        Self::builder().build()
     }
 
-    #[instrument]
-    async fn handle_rebinding(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(state = "REBINDING", "entering dhcp state REBINDING");
+    /// Create a new [`ClientBuilder`] to declaratively build a [`Client`].
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
 
-        // Reset the server identifier (IP address). The message will be
-        // send using the broadcast address.
-        self.client_state.server_identifier = None;
+    /// Returns the locked directory the client persists its state in, or
+    /// [`None`] if the client didn't run yet.
+    pub fn state_dir(&self) -> Option<&StateDir> {
+        self.state_dir.as_ref()
+    }
 
-        debug!("sending DHCPREQUEST message");
-        let request_message = self.builder.make_renewing_message(
-            self.get_xid(),
-            self.client_state.offered_ip_address.unwrap(),
-            self.client_state.offered_lease_time.unwrap(),
-        )?;
-        self.send_message(request_message, socket).await?;
+    /// Subscribes to the changes of the lease, like the lease being bound,
+    /// renewed or lost. Events are only delivered while the client runs,
+    /// subscribers lagging behind miss the oldest ones.
+    pub fn subscribe(&self) -> broadcast::Receiver<LeaseEvent> {
+        self.lease_events.subscribe()
+    }
 
-        Ok(self.transition_to(DhcpState::RebindingSent)?)
+    /// Unicasts the following messages to `server` and requests the lease
+    /// from it, see [`Protocol::request_from`].
+    pub fn request_from(&mut self, server: Ipv4Addr) {
+        self.protocol.request_from(server);
     }
 
+    /// Run the client as a daemon
     #[instrument]
-    async fn handle_rebinding_sent(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(
-            state = "REBINDING-SENT",
-            "entering dhcp state REBINDING-SENT"
-        );
-
-        let (message, _addr) = match self.recv_message(socket).await? {
-            Some(result) => result,
-            None => match &self.client_state.rebinding_time_left {
-                Some(time) => {
-                    // We dropped below the minimal retransmission timer,
-                    // transition to INIT.
-                    if *time < MINIMAL_RETRANS_DURATION_SECS * 2 {
-                        return Ok(self.transition_to(DhcpState::Init)?);
+    pub async fn run(&mut self) -> Result<(), ClientError> {
+        // Fail before touching the interface if another instance manages it
+        self.open_state_dir()?;
+
+        // Serve the status socket alongside the state machine
+        let status = match &self.status_socket {
+            Some(path) => {
+                let listener = bind_control_socket(path)?;
+                let server = self.status.clone();
+
+                Some(tokio::spawn(async move {
+                    if let Err(err) = serve_control(server, listener).await {
+                        warn!(target: DAEMON_TARGET, %err, "status socket failed");
                     }
+                }))
+            }
+            None => None,
+        };
 
-                    // We still have time left to receive a response.
-                    sleep(Duration::from_secs(*time as u64)).await;
-                    self.client_state.rebinding_time_left = Some((time / 2) as u32);
-
-                    return Ok(self.transition_to(DhcpState::Rebinding)?);
-                }
-                None => {
-                    return Err(ClientError::Invalid(String::from(
-                        "RENEWING: No renewal (T1) timer",
-                    )))
-                }
-            },
+        let result = loop {
+            match self.run_on_interface().await {
+                Err(err) if self.is_interface_gone(&err) => match self.interface_gone_policy {
+                    InterfaceGonePolicy::Exit => {
+                        break Err(ClientError::InterfaceGone(self.interface.name.clone()))
+                    }
+                    InterfaceGonePolicy::Wait => {
+                        if let Err(err) = self.wait_for_interface().await {
+                            break Err(err);
+                        }
+                    }
+                },
+                result => break result,
+            }
         };
 
-        // Check if the transaction ID matches
-        if !message.valid_xid(self.get_xid()) {
-            error!(
-                "Received response with wrong transaction ID: {} (yours: {})",
-                message.header.xid,
-                self.get_xid()
-            );
+        if let Some(status) = status {
+            status.abort();
+        }
+
+        result
+    }
+
+    /// Opens and locks the state directory, unless that already happened.
+    /// The address of the previous lease is suggested to the server again.
+    fn open_state_dir(&mut self) -> Result<(), ClientError> {
+        if self.state_dir.is_some() {
             return Ok(());
         }
 
-        match message.get_message_type() {
-            Some(ty) => match ty {
-                DhcpMessageType::Nak => {
-                    self.transition_to(DhcpState::Init)?;
-                    return Ok(());
-                }
-                DhcpMessageType::Ack => {}
-                _ => return Ok(()), // NOTE (Techassi): How should we handle other message types?
+        let state_dir = StateDir::open(&self.state_path)?;
+
+        match state_dir.read(LEASE_FILE) {
+            Ok(Some(contents)) => match String::from_utf8_lossy(&contents).trim().parse() {
+                Ok(addr) => self.protocol.set_previous_address(addr),
+                Err(err) => warn!(target: DAEMON_TARGET, %err, "ignoring invalid lease file"),
             },
-            None => return Ok(()),
+            Ok(None) => {}
+            Err(err) => warn!(target: DAEMON_TARGET, %err, "failed to read the lease file"),
         }
 
-        // Set lease, T1 and T2 timers (DHCPACK)
-        self.client_state.renewal_time = Some(
-            message
-                .get_renewal_t1_time()
-                .unwrap_or((self.client_state.offered_lease_time.unwrap() as f64 * 0.5) as u32),
-        );
-
-        self.client_state.rebinding_time = Some(
-            message
-                .get_rebinding_t2_time()
-                .unwrap_or((self.client_state.offered_lease_time.unwrap() as f64 * 0.875) as u32),
-        );
+        self.state_dir = Some(state_dir);
+        Ok(())
+    }
 
-        debug!(
-            "ip -4 addr add {} dev {}",
-            self.client_state.offered_ip_address.unwrap(),
-            self.interface.name
-        );
-        cmd::add_ip_address(
-            &self.client_state.offered_ip_address.unwrap(),
-            &self.interface.name,
-        )?;
+    /// Records `addr` as the address of the last lease. Failing to do so
+    /// only costs the suggestion after a restart.
+    fn persist_address(&self, addr: Ipv4Addr) {
+        let Some(state_dir) = &self.state_dir else {
+            return;
+        };
 
-        Ok(self.transition_to(DhcpState::Bound)?)
+        if let Err(err) = state_dir.write_atomic(LEASE_FILE, addr.to_string().as_bytes()) {
+            warn!(target: DAEMON_TARGET, %err, "failed to write the lease file");
+        }
     }
 
-    /// Handle the DHCP state BOUND.
-    #[instrument]
-    async fn handle_bound(&mut self) -> Result<(), ClientError> {
-        debug!(state = "BOUND", "entering dhcp state BOUND");
-        // Remain in this state. Discard incoming
-        // DHCPOFFER, DHCPACK and DHCPNAK
-
-        // T1 expires, send DHCPREQUEST to leasing server
-        debug!("Waiting for T1 to expire, then sending DHCPREQUEST");
-        match &self.client_state.renewal_time {
-            Some(time) => sleep(Duration::from_secs(*time as u64)).await,
-            None => {
-                return Err(ClientError::Invalid(String::from(
-                    "BOUND: No renewal (T1) time set, invalid state",
-                )))
-            }
-        }
+    /// Binds the sockets to the interface and runs the protocol on it.
+    async fn run_on_interface(&mut self) -> Result<(), ClientError> {
+        info!(target: DAEMON_TARGET, interface = self.interface.name, "binding to udp socket");
 
-        // Transition to RENEWING
-        Ok(self.transition_to(DhcpState::Renewing)?)
-    }
+        // Create UDP socket with a bind timeout
+        let socket = create_sock_with_timeout(self.bind_address, self.bind_timeout).await?;
+        socket.bind_device(Some(self.interface.name.as_bytes()))?;
+        socket.set_broadcast(true)?;
+        if self.socket_filter {
+            net::filter_dhcp_datagrams(&socket);
+        }
 
-    /// Handle the DHCP state RENEWING. This method sends out the DHCP message
-    /// and immediatly transitions to the intermediate state RENEWING-SENT.
-    /// This state is officially not part of the state machine described by
-    /// RFC 2131, but this implementation introduces this state to be able to
-    /// return back to here in case the T1 timer ticks which should trigger a
-    /// retransmission of the DHCPREQUEST message.
-    #[instrument]
-    async fn handle_renewing(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(state = "RENEWING", "entering dhcp state RENEWING");
-        debug!("renewing XID");
-        self.renew_xid();
+        if self.mtu_check {
+            self.check_mtu()?;
+        }
 
-        debug!("Sending DHCPREQUEST message");
-        let request_message = self.builder.make_renewing_message(
-            self.get_xid(),
-            self.client_state.offered_ip_address.unwrap(),
-            self.client_state.offered_lease_time.unwrap(),
-        )?;
-        self.send_message(request_message, socket).await?;
+        // Ensure the interface is UP
+        debug!(target: DAEMON_TARGET, "setting interface to up");
+        self.configurator.set_up(&self.interface.name)?;
+
+        let packet_socket = match self.packet_socket {
+            true => {
+                debug!(target: DAEMON_TARGET, "binding to packet socket");
+                Some(PacketSocket::bind(
+                    self.interface.index,
+                    self.bind_address.port(),
+                )?)
+            }
+            false => None,
+        };
 
-        Ok(self.transition_to(DhcpState::RenewingSent)?)
+        // The state machine described in 4.4 (https://www.rfc-editor.org/rfc/rfc2131#section-4.4)
+        // is implemented by the sans-IO protocol. The client only executes
+        // the returned actions and feeds back incoming messages and timer
+        // expirations.
+        self.run_protocol(&socket, packet_socket.as_ref()).await
     }
 
-    /// Handle the intermediate state RENEWINGSENT. This method listens for
-    /// incoming messages after sending out a DHCPREQUEST message to renew the
-    /// lease. If
-    #[instrument]
-    async fn handle_renewing_sent(&mut self, socket: &UdpSocket) -> Result<(), ClientError> {
-        debug!(state = "RENEWING-SENT", "entering dhcp state RENEWING-SENT");
-
-        let (message, _addr) = match self.recv_message(socket).await? {
-            Some(result) => result,
-            None => match &self.client_state.renewal_time_left {
-                Some(time) => {
-                    // We dropped below the minimal retransmission timer,
-                    // transition to REBINDING.
-                    if *time < MINIMAL_RETRANS_DURATION_SECS * 2 {
-                        return Ok(self.transition_to(DhcpState::Rebinding)?);
-                    }
+    /// Checks the interface MTU against the max DHCP message size, see
+    /// [`link::check_mtu`]. The check is skipped if the MTU can't be
+    /// retrieved.
+    fn check_mtu(&self) -> Result<(), ClientError> {
+        self.check_mtu_with(lib_ifs::if_mtu)
+    }
 
-                    // We still have time left to receive a response.
-                    sleep(Duration::from_secs(*time as u64)).await;
-                    self.client_state.renewal_time_left = Some((time / 2) as u32);
+    /// Same as [`Client::check_mtu`], with the MTU looked up by `if_mtu`.
+    fn check_mtu_with<F, E>(&self, if_mtu: F) -> Result<(), ClientError>
+    where
+        F: FnOnce(&str) -> Result<u32, E>,
+        E: std::fmt::Display,
+    {
+        match if_mtu(&self.interface.name) {
+            Ok(mtu) => link::check_mtu(
+                &self.interface.name,
+                mtu,
+                self.max_dhcp_message_size,
+                self.strict,
+            ),
+            Err(err) => {
+                warn!(
+                    target: DAEMON_TARGET,
+                    interface = self.interface.name,
+                    %err,
+                    "failed to retrieve interface MTU"
+                );
+                Ok(())
+            }
+        }
+    }
 
-                    return Ok(self.transition_to(DhcpState::Renewing)?);
-                }
-                None => {
-                    return Err(ClientError::Invalid(String::from(
-                        "RENEWING: No renewal (T1) timer",
-                    )))
-                }
-            },
-        };
+    /// Returns if `err` was caused by the interface disappearing. Commands
+    /// like `ip` only report a failure, in that case the interface is
+    /// looked up.
+    fn is_interface_gone(&self, err: &ClientError) -> bool {
+        err.is_interface_gone()
+            || matches!(err, ClientError::CmdError(_))
+                && !link::interface_exists(&self.interface.name)
+    }
 
-        // Check if the transaction ID matches
-        if !message.valid_xid(self.get_xid()) {
-            error!(
-                "Received response with wrong transaction ID: {} (yours: {})",
-                message.header.xid,
-                self.get_xid()
-            );
-            return Ok(());
+    /// Waits in WAITING-FOR-INTERFACE until the interface returns with the
+    /// same name. The protocol starts over in INIT afterwards.
+    async fn wait_for_interface(&mut self) -> Result<(), ClientError> {
+        let name = self.interface.name.clone();
+        park(
+            &mut self.protocol,
+            self.interface_watcher.as_ref(),
+            &name,
+            &self.status,
+        )
+        .await?;
+
+        // The index changes when the interface is created again
+        if let Some(interface) = utils::select_network_interface(&name, false)? {
+            self.interface = interface;
         }
 
-        // TODO (Techassi): All this stuff below can be extracted into a method
-        // Set lease, T1 and T2 timers (DHCPACK)
-        match message.get_message_type() {
-            Some(ty) => match ty {
-                DhcpMessageType::Nak => {
-                    self.transition_to(DhcpState::Init)?;
-                    return Ok(());
-                }
-                DhcpMessageType::Ack => {}
-                _ => return Ok(()), // NOTE (Techassi): How should we handle other message types?
-            },
-            None => return Ok(()),
-        }
+        Ok(())
+    }
 
-        // Set lease, T1 and T2 timers (DHCPACK)
-        self.client_state.renewal_time = Some(
-            message
-                .get_renewal_t1_time()
-                .unwrap_or((self.client_state.offered_lease_time.unwrap() as f64 * 0.5) as u32),
-        );
+    /// Drives the protocol state machine until an error occurs.
+    async fn run_protocol(
+        &mut self,
+        socket: &UdpSocket,
+        packet_socket: Option<&PacketSocket>,
+    ) -> Result<(), ClientError> {
+        debug!(target: FSM_TARGET, "entering state machine loop");
+        let mut deadline = None;
+        let mut actions = self.protocol.start(self.clock.now_instant());
 
-        self.client_state.rebinding_time = Some(
-            message
-                .get_rebinding_t2_time()
-                .unwrap_or((self.client_state.offered_lease_time.unwrap() as f64 * 0.875) as u32),
-        );
+        loop {
+            for action in actions {
+                match action {
+                    Action::Send(message, dest) => send_message(socket, message, dest).await?,
+                    Action::SetTimer(d) => deadline = Some(d),
+                    Action::Lease(event) => {
+                        // Nobody may be subscribed
+                        let _ = self.lease_events.send(event);
+                    }
+                    action => {
+                        configurator::apply(
+                            self.configurator.as_ref(),
+                            &self.interface.name,
+                            &action,
+                        )?;
+
+                        if let Action::ConfigureAddress(addr) = action {
+                            self.persist_address(addr);
+                        }
+                    }
+                }
+            }
+
+            self.status.set_state(self.protocol.state());
 
-        debug!(
-            "ip -4 addr add {} dev {}",
-            self.client_state.offered_ip_address.unwrap(),
-            self.interface.name
-        );
-        cmd::add_ip_address(
-            &self.client_state.offered_ip_address.unwrap(),
-            &self.interface.name,
-        )?;
+            let timer = async {
+                match deadline {
+                    Some(d) => sleep(d.saturating_duration_since(self.clock.now_instant())).await,
+                    None => future::pending().await,
+                }
+            };
 
-        Ok(self.transition_to(DhcpState::Bound)?)
+            actions = select! {
+                result = self.recv(socket, packet_socket) => match result? {
+                    Some(message) => self.protocol.handle_message(message, self.clock.now_instant())?,
+                    None => Vec::new(),
+                },
+                _ = timer => self.protocol.handle_timeout(self.clock.now_instant())?,
+            };
+        }
     }
 
-    /// Returns the current transaction ID.
-    fn get_xid(&self) -> u32 {
-        self.client_state.transaction_id
+    /// Returns the DNS configuration received with the last DHCPACK.
+    pub fn dns_config(&self) -> Option<&DnsConfig> {
+        self.protocol.dns_config()
     }
 
-    /// Renews the transaction ID by selecting a new, random one.
-    fn renew_xid(&mut self) {
-        self.client_state.transaction_id = rand::random()
+    /// Returns the NTP configuration received with the last DHCPACK.
+    pub fn ntp_config(&self) -> Option<&NtpConfig> {
+        self.protocol.ntp_config()
     }
 
-    /// Returns the destination address. This is either the IP address of the
-    /// current DHCP server or the IPv4 broadcast address.
-    fn destination_addr(&self) -> Ipv4Addr {
-        match self.client_state.server_identifier {
-            Some(ip) => ip,
-            None => Ipv4Addr::BROADCAST,
+    /// Receive a DHCP message, either from the packet socket (if enabled) or
+    /// from the UDP socket. Datagrams which are not a reply to this client
+    /// are skipped.
+    async fn recv(
+        &self,
+        socket: &UdpSocket,
+        packet_socket: Option<&PacketSocket>,
+    ) -> Result<Option<Message>, ClientError> {
+        let message = match packet_socket {
+            Some(packet_socket) => {
+                let payload = packet_socket.recv().await?;
+                let mut buf = ReadBuffer::new(&payload);
+                Message::read_be(&mut buf)?
+            }
+            None => match self.recv_message(socket).await? {
+                Some((message, _)) => message,
+                None => return Ok(None),
+            },
+        };
+
+        if !is_reply_for(&message, &self.hardware_address) {
+            debug!(
+                target: WIRE_TARGET,
+                chaddr = %message.chaddr,
+                "skipping datagram not addressed to us"
+            );
+            return Ok(None);
         }
+
+        Ok(Some(message))
     }
 
     /// Receive a DHCP message. This internally runs through the following
@@ -688,29 +840,59 @@ impl Client {
         };
 
         let mut buf = ReadBuffer::new(buf);
-        Ok(Some((Message::read_be(&mut buf)?, addr)))
+        let message = Message::read_be(&mut buf)?;
+        utils::trace_message(&message, addr);
+
+        Ok(Some((message, addr)))
     }
+}
 
-    /// Send a DHCP message / packet with the default timeouts to `dest_addr`
-    /// by binding to `bind_addr`. The bind address is usually `0.0.0.0:68`.
-    /// The default timeouts can be adjusted by using [`Client::builder`]
-    #[instrument]
-    async fn send_message(&self, message: Message, socket: &UdpSocket) -> Result<(), ClientError> {
-        // Choose a destion IP address. This is either the broadcast address
-        // or the DHCP server address.
-        let destination_addr = self.destination_addr();
+/// Parks `protocol` in WAITING-FOR-INTERFACE until `watcher` reports the
+/// interface with `name` again, then returns it to INIT.
+async fn park(
+    protocol: &mut Protocol,
+    watcher: &dyn InterfaceWatcher,
+    name: &str,
+    status: &StatusServer,
+) -> Result<(), ClientError> {
+    protocol.interface_gone()?;
+    status.set_state(protocol.state());
+
+    warn!(target: DAEMON_TARGET, interface = name, "interface disappeared, waiting for it to return");
+    watcher.wait_for(name).await?;
+    info!(target: DAEMON_TARGET, interface = name, "interface returned, starting over");
+
+    protocol.interface_returned()?;
+    status.set_state(protocol.state());
+    Ok(())
+}
 
-        // Create the write buffer
-        let mut buf = WriteBuffer::new();
-        message.write_be(&mut buf)?;
+/// Returns if `message` is a plausible reply to the client with
+/// `hardware_addr`. Replies are BOOTREPLY messages carrying the client's
+/// hardware address. Other DHCP traffic on the network, like requests of
+/// other clients or replies to them, is not meant for us.
+fn is_reply_for(message: &Message, hardware_addr: &HardwareAddr) -> bool {
+    if message.header.opcode != OpCode::BootReply {
+        return false;
+    }
 
-        // Off to the wire the bytes go
-        socket
-            .send_to(buf.bytes(), (destination_addr, SERVER_PORT))
-            .await?;
+    let chaddr = message.chaddr.as_bytes();
+    message.chaddr == *hardware_addr || (!chaddr.is_empty() && chaddr.iter().all(|b| *b == 0xff))
+}
 
-        Ok(())
-    }
+/// Send a DHCP message / packet to `dest`. This is either the broadcast
+/// address or the DHCP server address.
+#[instrument(skip(transport))]
+async fn send_message<T: Transport>(
+    transport: &T,
+    message: Message,
+    dest: Dest,
+) -> Result<(), ClientError> {
+    // Off to the wire the bytes go
+    let destination_addr = SocketAddr::from((dest.addr(), SERVER_PORT));
+    transport.send(message, destination_addr).await?;
+
+    Ok(())
 }
 
 // TODO (Techassi): Don't return a client error here, but instead a more
@@ -730,3 +912,313 @@ where
         Err(_) => return Err(ClientError::BindTimeout(bind_timeout)),
     }
 }
+
+/// Parses the hardware address `mac_addr` of the interface with `name`. The
+/// client advertises Ethernet as hardware type in all messages, so the
+/// address must be of matching length.
+fn ethernet_hardware_address(
+    name: &str,
+    mac_addr: Option<&String>,
+) -> Result<HardwareAddr, ClientError> {
+    let hardware_address = match mac_addr {
+        Some(mac_addr) => HardwareAddr::try_from(mac_addr)?,
+        None => return Err(ClientError::NoHardwareAddressError(name.to_string())),
+    };
+
+    let expected = HardwareType::Ethernet.addr_len();
+    if hardware_address.len() != expected {
+        return Err(ClientError::HardwareAddrLength(
+            name.to_string(),
+            hardware_address.len(),
+            expected,
+        ));
+    }
+
+    Ok(hardware_address)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::{
+        server::control::ControlHandler,
+        types::{options::DhcpMessageType, OptionData},
+        utils::LogCapture,
+        ControlRequest, ControlResponse, RecordingTransport, TransportError,
+    };
+
+    #[test]
+    fn test_unrelated_datagram_is_skipped() {
+        let ours = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let other = HardwareAddr::try_from(String::from("de:ad:be:ef:56:78")).unwrap();
+
+        let datagram = |opcode: OpCode, chaddr: &HardwareAddr| {
+            let mut message = Message::new_with_xid(1);
+            message.header.opcode = opcode;
+            message.set_hardware_address(chaddr.clone());
+            message
+        };
+
+        assert!(is_reply_for(&datagram(OpCode::BootReply, &ours), &ours));
+
+        // Request of another client and reply to another client
+        assert!(!is_reply_for(&datagram(OpCode::BootRequest, &other), &ours));
+        assert!(!is_reply_for(&datagram(OpCode::BootReply, &other), &ours));
+
+        // Our own request, echoed back
+        assert!(!is_reply_for(&datagram(OpCode::BootRequest, &ours), &ours));
+
+        let broadcast = HardwareAddr::try_from(String::from("ff:ff:ff:ff:ff:ff")).unwrap();
+        assert!(is_reply_for(
+            &datagram(OpCode::BootReply, &broadcast),
+            &ours
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_discover_is_recorded() {
+        let hardware_addr = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let builder = MessageBuilder::new(hardware_addr, None, 1500);
+        let mut protocol = Protocol::new(builder, time::Duration::from_secs(2));
+        let transport = RecordingTransport::new();
+
+        // Skip the initial delay
+        let deadline = match protocol.start(std::time::Instant::now()).as_slice() {
+            [Action::SetTimer(deadline)] => *deadline,
+            actions => panic!("unexpected actions {:?}", actions),
+        };
+
+        for action in protocol.handle_timeout(deadline).unwrap() {
+            if let Action::Send(message, dest) = action {
+                send_message(&transport, message, dest).await.unwrap();
+            }
+        }
+
+        let sent = transport.take();
+        assert_eq!(sent.len(), 1);
+
+        let (message, addr) = &sent[0];
+        assert_eq!(*addr, SocketAddr::from((Ipv4Addr::BROADCAST, SERVER_PORT)));
+        assert!(matches!(
+            message
+                .get_option(OptionTag::DhcpMessageType)
+                .map(|o| o.data()),
+            Some(OptionData::DhcpMessageType(DhcpMessageType::Discover))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_park_until_interface_returns() {
+        let hardware_addr = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let builder = MessageBuilder::new(hardware_addr, None, 1500);
+        let mut protocol = Protocol::new(builder, time::Duration::from_secs(2));
+        let status = StatusServer::new(String::from("usb0"), None);
+        let watcher = MockInterfaceWatcher::new();
+
+        let now = std::time::Instant::now();
+        protocol.start(now);
+
+        // Sending on a socket bound to a removed interface fails with ENODEV
+        let err = ClientError::from(TransportError::Io(std::io::Error::from_raw_os_error(
+            libc::ENODEV,
+        )));
+        assert!(err.is_interface_gone());
+        assert!(!ClientError::Invalid(String::from("short")).is_interface_gone());
+
+        watcher.add("usb0");
+        watcher.remove("usb0");
+
+        let mut parked = Box::pin(park(&mut protocol, &watcher, "usb0", &status));
+        assert!(timeout(time::Duration::from_millis(20), &mut parked)
+            .await
+            .is_err());
+        assert!(matches!(
+            status.dispatch(ControlRequest::Status),
+            ControlResponse::Status(entries) if entries[1].1 == "WAITING-FOR-INTERFACE"
+        ));
+
+        // Another interface appearing doesn't end the wait
+        watcher.add("usb1");
+        assert!(timeout(time::Duration::from_millis(20), &mut parked)
+            .await
+            .is_err());
+
+        watcher.add("usb0");
+        timeout(time::Duration::from_secs(1), parked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(protocol.state(), DhcpState::Init));
+    }
+
+    #[test]
+    fn test_ethernet_hardware_address() {
+        let cases = [
+            (Some("de:ad:be:ef:12:34"), true),
+            (Some("de:ad:be:ef"), false),
+            (
+                Some("00:00:00:00:00:00:00:00:de:ad:be:ef:12:34:56:78"),
+                false,
+            ),
+            (None, false),
+        ];
+
+        for (mac_addr, valid) in cases {
+            let mac_addr = mac_addr.map(String::from);
+            let result = ethernet_hardware_address("eth0", mac_addr.as_ref());
+            assert_eq!(result.is_ok(), valid, "{mac_addr:?}");
+        }
+    }
+
+    #[test]
+    fn test_default_builder_is_valid() {
+        ClientBuilder::default().validate().unwrap();
+
+        // Whether the default interface exists depends on the host, so the
+        // build only fails if it's missing
+        let state_dir = std::env::temp_dir()
+            .join(format!("vulcan-builder-{}", std::process::id()))
+            .join("eth0");
+        match ClientBuilder::default().with_state_dir(&state_dir).build() {
+            Ok(_) | Err(ClientError::NoInterfaceFound(_)) => {}
+            Err(err) => panic!("default builder failed: {err}"),
+        }
+
+        let interface = NetworkInterface {
+            name: String::from("eth0"),
+            addr: None,
+            mac_addr: Some(String::from("de:ad:be:ef:12:34")),
+            index: 2,
+        };
+        let client = ClientBuilder::default()
+            .with_state_dir(&state_dir)
+            .build_on(interface)
+            .unwrap();
+        assert_eq!(client.interface.name, "eth0");
+
+        // Building doesn't touch the state directory
+        assert!(!state_dir.exists());
+
+        let builder = Client::builder().with_max_dhcp_message_size(575);
+        assert!(matches!(
+            builder.validate(),
+            Err(ClientError::InvalidConfig(_))
+        ));
+
+        let builder = Client::builder()
+            .with_initial_delay(time::Duration::from_secs(2)..time::Duration::from_secs(1));
+        assert!(matches!(
+            builder.validate(),
+            Err(ClientError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_interface_mtu_check() {
+        let client = |strict: bool| {
+            let interface = NetworkInterface {
+                name: String::from("eth0"),
+                addr: None,
+                mac_addr: Some(String::from("de:ad:be:ef:12:34")),
+                index: 2,
+            };
+
+            ClientBuilder::default()
+                .with_max_dhcp_message_size(1500)
+                .with_strict(strict)
+                .build_on(interface)
+                .unwrap()
+        };
+
+        let capture = LogCapture::default();
+        let _guard = tracing::subscriber::set_default(capture.subscriber(tracing::Level::WARN));
+
+        // The MTU of the mocked interface is looked up by its name
+        let lookup = |mtu: u32| {
+            move |name: &str| -> Result<u32, String> {
+                assert_eq!(name, "eth0");
+                Ok(mtu)
+            }
+        };
+        client(true).check_mtu_with(lookup(1500)).unwrap();
+        assert!(capture.output().is_empty());
+
+        assert!(matches!(
+            client(true).check_mtu_with(lookup(1280)),
+            Err(ClientError::MtuTooSmall(name, 1280, 1500)) if name == "eth0"
+        ));
+
+        client(false).check_mtu_with(lookup(1280)).unwrap();
+        assert!(capture
+            .output()
+            .contains("interface MTU is below the max DHCP message size"));
+
+        // The check is skipped if the MTU can't be retrieved, even in strict
+        // mode
+        client(true)
+            .check_mtu_with(|_: &str| Err(String::from("no such device")))
+            .unwrap();
+        let output = capture.output();
+        let line = output
+            .lines()
+            .find(|line| line.contains("failed to retrieve interface MTU"))
+            .expect("no warning");
+        assert!(line.contains("err=no such device"));
+    }
+
+    #[test]
+    fn test_state_dir_contention() {
+        let state_dir = std::env::temp_dir()
+            .join(format!("vulcan-contention-{}", std::process::id()))
+            .join("eth0");
+        let _ = std::fs::remove_dir_all(&state_dir);
+
+        let client = || {
+            let interface = NetworkInterface {
+                name: String::from("eth0"),
+                addr: None,
+                mac_addr: Some(String::from("de:ad:be:ef:12:34")),
+                index: 2,
+            };
+
+            ClientBuilder::default()
+                .with_state_dir(&state_dir)
+                .build_on(interface)
+                .unwrap()
+        };
+
+        // The second client on the interface fails until the first is gone
+        let mut first = client();
+        let mut second = client();
+        first.open_state_dir().unwrap();
+        assert!(matches!(
+            second.open_state_dir(),
+            Err(ClientError::StateDirError(StateDirError::Locked(path))) if path == state_dir
+        ));
+
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+        first.persist_address(addr);
+        drop(first);
+        second.open_state_dir().unwrap();
+        assert!(second.state_dir().is_some());
+
+        // The address of the previous lease is requested again
+        let deadline = match second.protocol.start(std::time::Instant::now()).as_slice() {
+            [Action::SetTimer(deadline)] => *deadline,
+            actions => panic!("unexpected actions {:?}", actions),
+        };
+        let actions = second.protocol.handle_timeout(deadline).unwrap();
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::Send(message, _), ..] if matches!(
+                message.get_option(OptionTag::RequestedIpAddr).map(|o| o.data()),
+                Some(OptionData::RequestedIpAddr(requested)) if *requested == addr
+            )
+        ));
+
+        drop(second);
+        std::fs::remove_dir_all(state_dir.parent().unwrap()).unwrap();
+    }
+}
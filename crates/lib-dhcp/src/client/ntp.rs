@@ -0,0 +1,62 @@
+use std::{fmt::Display, net::Ipv4Addr};
+
+use crate::types::Message;
+
+/// NTP configuration derived from a DHCPACK. Displays as a sources fragment
+/// understood by both chrony and ntpd, e.g. for `/etc/chrony/sources.d`.
+#[derive(Debug, Default, PartialEq)]
+pub struct NtpConfig {
+    pub servers: Vec<Ipv4Addr>,
+}
+
+impl Display for NtpConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for server in &self.servers {
+            writeln!(f, "server {} iburst", server)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NtpConfig {
+    /// Derives the NTP configuration from the network time protocol servers
+    /// option (42) of `message`.
+    pub fn from_message(message: &Message) -> Self {
+        Self {
+            servers: message.get_ntp_servers().cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{OptionData, OptionTag};
+
+    #[test]
+    fn test_sources_fragment() {
+        let mut message = Message::new_with_xid(1);
+        message
+            .add_option_parts(
+                OptionTag::NetworkTimeProtocolServers,
+                OptionData::NetworkTimeProtocolServers(vec![
+                    Ipv4Addr::new(10, 0, 0, 1),
+                    Ipv4Addr::new(10, 0, 0, 2),
+                ]),
+            )
+            .unwrap();
+
+        let config = NtpConfig::from_message(&message);
+        assert_eq!(
+            config.to_string(),
+            "server 10.0.0.1 iburst\nserver 10.0.0.2 iburst\n"
+        );
+
+        assert!(NtpConfig::from_message(&Message::new_with_xid(1)).is_empty());
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    io,
+    mem::size_of,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use thiserror::Error;
+use tokio::io::unix::AsyncFd;
+
+use crate::net::bpf::{
+    self, jump, stmt, BPF_JMP_JEQ_K, BPF_JMP_JSET_K, BPF_LDX_B_MSH, BPF_LD_B_ABS, BPF_LD_H_ABS,
+    BPF_LD_H_IND, BPF_RET_K,
+};
+
+/// Length of the Ethernet header (without VLAN tags).
+const ETHERNET_HEADER_LEN: usize = 14;
+
+/// EtherType of IPv4.
+const ETHERTYPE_IPV4: u16 = 0x0800;
+
+/// Protocol number of UDP in the IPv4 header.
+const IP_PROTO_UDP: u8 = 17;
+
+/// Mask of the 'fragment offset' field and the 'more fragments' flag.
+const IP_FRAGMENT_MASK: u16 = 0x3fff;
+
+const UDP_HEADER_LEN: usize = 8;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PacketError {
+    #[error("frame too short")]
+    TooShort,
+
+    #[error("not an IPv4 packet")]
+    NotIpv4,
+
+    #[error("not a UDP datagram")]
+    NotUdp,
+
+    #[error("fragmented IPv4 packets are not supported")]
+    Fragmented,
+
+    #[error("unexpected UDP destination port {0}")]
+    WrongPort(u16),
+
+    #[error("invalid length field")]
+    InvalidLength,
+}
+
+/// Returns a classic BPF program which only accepts unfragmented IPv4 UDP
+/// datagrams with destination `port` in Ethernet frames. Everything else is
+/// dropped in the kernel before it reaches the socket.
+pub fn udp_port_filter(port: u16) -> Vec<libc::sock_filter> {
+    vec![
+        // Check the EtherType
+        stmt(BPF_LD_H_ABS, 12),
+        jump(BPF_JMP_JEQ_K, ETHERTYPE_IPV4 as u32, 0, 8),
+        // Check the IP protocol
+        stmt(BPF_LD_B_ABS, 23),
+        jump(BPF_JMP_JEQ_K, IP_PROTO_UDP as u32, 0, 6),
+        // Drop fragments
+        stmt(BPF_LD_H_ABS, 20),
+        jump(BPF_JMP_JSET_K, IP_FRAGMENT_MASK as u32, 4, 0),
+        // Load the IP header length into X and check the UDP destination port
+        stmt(BPF_LDX_B_MSH, ETHERNET_HEADER_LEN as u32),
+        stmt(BPF_LD_H_IND, ETHERNET_HEADER_LEN as u32 + 2),
+        jump(BPF_JMP_JEQ_K, port as u32, 0, 1),
+        // Accept the complete frame
+        stmt(BPF_RET_K, u32::MAX),
+        // Drop
+        stmt(BPF_RET_K, 0),
+    ]
+}
+
+/// Extracts the DHCP payload from a complete Ethernet frame. This validates
+/// the Ethernet, IPv4 and UDP headers and returns the UDP payload if the
+/// destination port matches `port`.
+pub fn extract_udp_payload(frame: &[u8], port: u16) -> Result<&[u8], PacketError> {
+    if frame.len() < ETHERNET_HEADER_LEN {
+        return Err(PacketError::TooShort);
+    }
+
+    if u16::from_be_bytes([frame[12], frame[13]]) != ETHERTYPE_IPV4 {
+        return Err(PacketError::NotIpv4);
+    }
+
+    let ip = &frame[ETHERNET_HEADER_LEN..];
+    if ip.len() < 20 {
+        return Err(PacketError::TooShort);
+    }
+
+    if ip[0] >> 4 != 4 {
+        return Err(PacketError::NotIpv4);
+    }
+
+    let header_len = ((ip[0] & 0x0f) as usize) * 4;
+    let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+
+    if header_len < 20 || total_len < header_len || total_len > ip.len() {
+        return Err(PacketError::InvalidLength);
+    }
+
+    if u16::from_be_bytes([ip[6], ip[7]]) & IP_FRAGMENT_MASK != 0 {
+        return Err(PacketError::Fragmented);
+    }
+
+    if ip[9] != IP_PROTO_UDP {
+        return Err(PacketError::NotUdp);
+    }
+
+    // Ethernet frames can contain trailing padding, only use the length
+    // provided by the IP header
+    let udp = &ip[header_len..total_len];
+    if udp.len() < UDP_HEADER_LEN {
+        return Err(PacketError::TooShort);
+    }
+
+    let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+    if dst_port != port {
+        return Err(PacketError::WrongPort(dst_port));
+    }
+
+    let udp_len = u16::from_be_bytes([udp[4], udp[5]]) as usize;
+    if udp_len < UDP_HEADER_LEN || udp_len > udp.len() {
+        return Err(PacketError::InvalidLength);
+    }
+
+    Ok(&udp[UDP_HEADER_LEN..udp_len])
+}
+
+/// Packet (AF_PACKET) socket which receives DHCP replies before the
+/// interface has an address. Some platforms don't deliver broadcast replies
+/// to a UDP socket in that case. Only IPv4 UDP datagrams to the client port
+/// pass the attached BPF filter.
+#[derive(Debug)]
+pub struct PacketSocket {
+    fd: AsyncFd<OwnedFd>,
+    port: u16,
+}
+
+impl PacketSocket {
+    /// Opens a packet socket on the interface with `interface_index` which
+    /// receives UDP datagrams to `port`.
+    pub fn bind(interface_index: u32, port: u16) -> io::Result<Self> {
+        let protocol = (libc::ETH_P_IP as u16).to_be();
+
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                protocol as i32,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // Attach the filter before binding, so that no unfiltered packets
+        // get queued
+        bpf::attach(&fd, &mut udp_port_filter(port))?;
+
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = protocol;
+        addr.sll_ifindex = interface_index as i32;
+
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const _ as *const libc::sockaddr,
+                size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+            port,
+        })
+    }
+
+    /// Receives the next DHCP payload. Frames which pass the filter but are
+    /// malformed are skipped.
+    pub async fn recv(&self) -> io::Result<Vec<u8>> {
+        let mut frame = [0u8; 1514];
+
+        loop {
+            let mut guard = self.fd.readable().await?;
+
+            let len = match guard.try_io(|fd| {
+                let res = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        frame.as_mut_ptr() as *mut libc::c_void,
+                        frame.len(),
+                        0,
+                    )
+                };
+
+                if res < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(res as usize)
+                }
+            }) {
+                Ok(result) => result?,
+                Err(_would_block) => continue,
+            };
+
+            if let Ok(payload) = extract_udp_payload(&frame[..len], self.port) {
+                return Ok(payload.to_vec());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal classic BPF interpreter for the instructions used by the
+    /// filter.
+    fn run_filter(filter: &[libc::sock_filter], packet: &[u8]) -> u32 {
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0usize);
+
+        let load_h = |offset: usize| {
+            packet
+                .get(offset..offset + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+        };
+
+        loop {
+            let ins = &filter[pc];
+            pc += 1;
+
+            match ins.code {
+                BPF_LD_H_ABS => match load_h(ins.k as usize) {
+                    Some(v) => a = v,
+                    None => return 0,
+                },
+                BPF_LD_H_IND => match load_h((x + ins.k) as usize) {
+                    Some(v) => a = v,
+                    None => return 0,
+                },
+                BPF_LD_B_ABS => match packet.get(ins.k as usize) {
+                    Some(v) => a = *v as u32,
+                    None => return 0,
+                },
+                BPF_LDX_B_MSH => match packet.get(ins.k as usize) {
+                    Some(v) => x = ((*v & 0x0f) as u32) * 4,
+                    None => return 0,
+                },
+                BPF_JMP_JEQ_K | BPF_JMP_JSET_K => {
+                    let cond = if ins.code == BPF_JMP_JEQ_K {
+                        a == ins.k
+                    } else {
+                        a & ins.k != 0
+                    };
+
+                    pc += if cond { ins.jt } else { ins.jf } as usize;
+                }
+                BPF_RET_K => return ins.k,
+                code => panic!("unsupported instruction {code:#x}"),
+            }
+        }
+    }
+
+    fn frame(protocol: u8, flags_fragment: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0x12, 0x34]);
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+        let total_len = (20 + UDP_HEADER_LEN + payload.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0x00]);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        frame.extend_from_slice(&flags_fragment.to_be_bytes());
+        frame.extend_from_slice(&[64, protocol, 0x00, 0x00]);
+        frame.extend_from_slice(&[192, 168, 1, 1, 255, 255, 255, 255]);
+
+        let udp_len = (UDP_HEADER_LEN + payload.len()) as u16;
+        frame.extend_from_slice(&67u16.to_be_bytes());
+        frame.extend_from_slice(&dst_port.to_be_bytes());
+        frame.extend_from_slice(&udp_len.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        frame.extend_from_slice(payload);
+
+        frame
+    }
+
+    #[test]
+    fn test_filter_accepts_dhcp_reply() {
+        let filter = udp_port_filter(68);
+        assert_eq!(run_filter(&filter, &frame(17, 0, 68, &[1, 2, 3])), u32::MAX);
+
+        // The don't fragment flag is fine
+        assert_eq!(run_filter(&filter, &frame(17, 0x4000, 68, &[1])), u32::MAX);
+    }
+
+    #[test]
+    fn test_filter_drops_other_packets() {
+        let filter = udp_port_filter(68);
+
+        assert_eq!(run_filter(&filter, &frame(17, 0, 67, &[1])), 0);
+        assert_eq!(run_filter(&filter, &frame(6, 0, 68, &[1])), 0);
+        assert_eq!(run_filter(&filter, &frame(17, 0x2000, 68, &[1])), 0);
+        assert_eq!(run_filter(&filter, &frame(17, 0x0001, 68, &[1])), 0);
+
+        let mut arp = frame(17, 0, 68, &[1]);
+        arp[12..14].copy_from_slice(&0x0806u16.to_be_bytes());
+        assert_eq!(run_filter(&filter, &arp), 0);
+    }
+
+    #[test]
+    fn test_extract_udp_payload() {
+        let payload = [0x02, 0x01, 0x06, 0x00, 0xde, 0xad];
+        let mut frame = frame(17, 0, 68, &payload);
+
+        // Add Ethernet padding, which must not end up in the payload
+        frame.extend_from_slice(&[0; 8]);
+
+        assert_eq!(extract_udp_payload(&frame, 68), Ok(&payload[..]));
+    }
+
+    #[test]
+    fn test_extract_udp_payload_errors() {
+        let payload = [1, 2, 3];
+
+        assert_eq!(
+            extract_udp_payload(&frame(17, 0, 67, &payload), 68),
+            Err(PacketError::WrongPort(67))
+        );
+        assert_eq!(
+            extract_udp_payload(&frame(6, 0, 68, &payload), 68),
+            Err(PacketError::NotUdp)
+        );
+        assert_eq!(
+            extract_udp_payload(&frame(17, 0x2000, 68, &payload), 68),
+            Err(PacketError::Fragmented)
+        );
+        assert_eq!(
+            extract_udp_payload(&frame(17, 0, 68, &payload)[..40], 68),
+            Err(PacketError::InvalidLength)
+        );
+        assert_eq!(
+            extract_udp_payload(&[0; 10], 68),
+            Err(PacketError::TooShort)
+        );
+    }
+}
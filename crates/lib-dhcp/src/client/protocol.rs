@@ -0,0 +1,1694 @@
+use std::{
+    fmt,
+    net::Ipv4Addr,
+    ops::Range,
+    time::{Duration, Instant, SystemTime},
+};
+
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use tracing::{debug, error, warn};
+
+use crate::{
+    builder::MessageBuilder,
+    client::{
+        dns::DnsConfig,
+        events::{LeaseEvent, LeaseInfo},
+        ntp::NtpConfig,
+        routes::{diff_routes, routes_for, Route},
+        state::{ClientState, DhcpState, DhcpStateMachine},
+        ClientError,
+    },
+    transaction::{TransactionRecord, TransactionStep},
+    types::{options::DhcpMessageType, LeaseTime, Message, OpCode, OptionData, OptionTag},
+    DEFAULT_INITIAL_DELAY_MAX_SECS, DEFAULT_INITIAL_DELAY_MIN_SECS, DEFAULT_MAX_INIT_BACKOFF_SECS,
+    DEFAULT_ROUTE_METRIC, FSM_TARGET, LEASE_TARGET, MAX_DISCOVER_BACKOFF_SECS,
+    MINIMAL_RETRANS_DURATION_SECS, WIRE_TARGET,
+};
+
+/// Destination of an outgoing DHCP message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dest {
+    /// Send to the IPv4 broadcast address.
+    Broadcast,
+
+    /// Send directly to the DHCP server with this address.
+    Unicast(Ipv4Addr),
+}
+
+impl Dest {
+    pub fn addr(&self) -> Ipv4Addr {
+        match self {
+            Dest::Broadcast => Ipv4Addr::BROADCAST,
+            Dest::Unicast(addr) => *addr,
+        }
+    }
+}
+
+/// Actions the IO driver has to execute on behalf of the [`Protocol`].
+#[derive(Debug)]
+pub enum Action {
+    /// Send the message to the destination (on the DHCP server port).
+    Send(Message, Dest),
+
+    /// (Re)arm the single protocol timer. When the deadline is reached, the
+    /// driver calls [`Protocol::handle_timeout`]. A new deadline replaces the
+    /// previous one.
+    SetTimer(Instant),
+
+    /// Set the MTU of the network interface.
+    SetMtu(u16),
+
+    /// Configure the leased address on the network interface.
+    ConfigureAddress(Ipv4Addr),
+
+    /// Announce the configured address to the neighbors on the link.
+    Announce(Ipv4Addr),
+
+    /// Remove a previously configured address from the network interface.
+    RemoveAddress(Ipv4Addr),
+
+    /// Install a default route via a router.
+    AddRoute(Route),
+
+    /// Remove a previously installed default route.
+    RemoveRoute(Route),
+
+    /// Export the resolver configuration of the lease to the system.
+    ConfigureDns(DnsConfig),
+
+    /// Report a change of the lease to subscribers.
+    Lease(LeaseEvent),
+}
+
+/// Sans-IO core of the DHCP client. The protocol doesn't perform any IO
+/// itself, it only consumes incoming messages and timer expirations and
+/// returns the [`Action`]s to execute. All time is provided by the caller,
+/// which makes every transition testable with a fake clock. Transaction IDs
+/// and the INIT delay are drawn from an exchangeable random number
+/// generator.
+///
+/// ### See
+///
+/// RFC 2131 - Section 4.4 - DHCP client behavior: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4
+pub struct Protocol {
+    /// Duration to wait for replies after sending DHCPDISCOVER and
+    /// DHCPREQUEST messages in SELECTING and REQUESTING.
+    read_timeout: Duration,
+
+    /// Metric of the route via the first router.
+    route_metric: u32,
+
+    /// Number of DHCPDISCOVERs sent per cycle before returning to INIT.
+    max_discover_retries: u32,
+
+    /// Range the random delay in INIT is drawn from.
+    initial_delay: Range<Duration>,
+
+    /// Upper bound of the additional wait in INIT after failed cycles.
+    max_init_backoff: Duration,
+
+    /// Refuse replies failing [`Message::validate`].
+    strict: bool,
+
+    /// Number of times INIT was entered since the client was last BOUND.
+    init_cycles: u32,
+
+    /// Deadline of the currently armed timer.
+    deadline: Option<Instant>,
+
+    /// Name of the interface, included in the transaction records.
+    interface: Option<String>,
+
+    /// Server all requests are sent to, regardless of which server offered
+    /// the lease.
+    requested_server: Option<Ipv4Addr>,
+
+    /// Address of the previous lease, suggested in DHCPDISCOVER.
+    previous_address: Option<Ipv4Addr>,
+
+    /// Record of the current DORA exchange or renewal, emitted once it
+    /// completed.
+    transaction: Option<TransactionRecord>,
+
+    /// Client state
+    client_state: ClientState,
+
+    /// DHCP state
+    pub(crate) dhcp_state: DhcpState,
+
+    /// Message builder
+    builder: MessageBuilder,
+
+    /// Source of transaction IDs and the INIT delay
+    rng: Box<dyn RngCore + Send>,
+}
+
+impl fmt::Debug for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Protocol")
+            .field("read_timeout", &self.read_timeout)
+            .field("route_metric", &self.route_metric)
+            .field("max_discover_retries", &self.max_discover_retries)
+            .field("initial_delay", &self.initial_delay)
+            .field("max_init_backoff", &self.max_init_backoff)
+            .field("strict", &self.strict)
+            .field("init_cycles", &self.init_cycles)
+            .field("deadline", &self.deadline)
+            .field("interface", &self.interface)
+            .field("requested_server", &self.requested_server)
+            .field("previous_address", &self.previous_address)
+            .field("client_state", &self.client_state)
+            .field("dhcp_state", &self.dhcp_state)
+            .field("builder", &self.builder)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Protocol {
+    pub fn new(builder: MessageBuilder, read_timeout: Duration) -> Self {
+        Self {
+            client_state: ClientState::default(),
+            dhcp_state: DhcpState::default(),
+            deadline: None,
+            interface: None,
+            requested_server: None,
+            previous_address: None,
+            transaction: None,
+            route_metric: DEFAULT_ROUTE_METRIC,
+            max_discover_retries: 1,
+            initial_delay: Duration::from_secs(DEFAULT_INITIAL_DELAY_MIN_SECS)
+                ..Duration::from_secs(DEFAULT_INITIAL_DELAY_MAX_SECS),
+            max_init_backoff: Duration::from_secs(DEFAULT_MAX_INIT_BACKOFF_SECS),
+            strict: false,
+            init_cycles: 0,
+            rng: Box::new(StdRng::from_entropy()),
+            read_timeout,
+            builder,
+        }
+    }
+
+    /// Sets the random number generator used for transaction IDs and the
+    /// INIT delay. A seeded generator makes runs reproducible.
+    pub fn with_rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Sets the metric of the route via the first router. Following
+    /// routers get increasing metrics.
+    pub fn with_route_metric(mut self, metric: u32) -> Self {
+        self.route_metric = metric;
+        self
+    }
+
+    /// Sets how many DHCPDISCOVERs are sent per cycle, the first one
+    /// included, before the client returns to INIT. Retransmissions wait
+    /// twice as long as the previous attempt, up to 64 seconds. Defaults to
+    /// 1, which restarts the cycle after the first unanswered DHCPDISCOVER.
+    pub fn with_max_discover_retries(mut self, retries: u32) -> Self {
+        self.max_discover_retries = retries.max(1);
+        self
+    }
+
+    /// Sets the range the random delay before the first DHCPDISCOVER of a
+    /// cycle is drawn from. Defaults to one to ten seconds, an empty range
+    /// like `Duration::ZERO..Duration::ZERO` waits exactly its start.
+    pub fn with_initial_delay(mut self, delay: Range<Duration>) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the upper bound of the additional wait in INIT. Every cycle
+    /// that ends without a lease doubles it, starting at one second, so a
+    /// dead network doesn't cause a tight DHCPDISCOVER loop. Defaults to 64
+    /// seconds, zero disables the backoff.
+    pub fn with_max_init_backoff(mut self, max: Duration) -> Self {
+        self.max_init_backoff = max;
+        self
+    }
+
+    /// Refuses DHCPOFFERs and DHCPACKs failing [`Message::validate`], like
+    /// an OFFER with 'ciaddr' set or a client identifier. Lenient by
+    /// default, which accepts them.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the name of the interface the client runs on. It is only used
+    /// in the transaction records.
+    pub fn with_interface_name(mut self, interface: impl Into<String>) -> Self {
+        self.interface = Some(interface.into());
+        self
+    }
+
+    /// Unicasts the following messages to `server` and requests the lease
+    /// from it. Offers of other servers are ignored. Requests in REBINDING
+    /// are still broadcast.
+    pub fn request_from(&mut self, server: Ipv4Addr) {
+        self.requested_server = Some(server);
+        self.client_state.server_identifier = Some(server);
+    }
+
+    /// Suggests `addr`, the address of a previous lease, in the following
+    /// DHCPDISCOVER messages (RFC 2131 Section 3.1).
+    pub fn set_previous_address(&mut self, addr: Ipv4Addr) {
+        self.previous_address = Some(addr);
+    }
+
+    /// Returns the current DHCP state.
+    pub fn state(&self) -> &DhcpState {
+        &self.dhcp_state
+    }
+
+    /// Returns the DNS configuration received with the last DHCPACK.
+    pub fn dns_config(&self) -> Option<&DnsConfig> {
+        self.client_state.dns_config.as_ref()
+    }
+
+    /// Returns the NTP configuration received with the last DHCPACK.
+    pub fn ntp_config(&self) -> Option<&NtpConfig> {
+        self.client_state.ntp_config.as_ref()
+    }
+
+    /// Starts the protocol in INIT. The client waits a random amount from
+    /// the initial delay range, one to ten seconds by default, before
+    /// sending the first DHCPDISCOVER.
+    pub fn start(&mut self, now: Instant) -> Vec<Action> {
+        self.init_cycles = 0;
+        self.init_delay(now)
+    }
+
+    /// Enters WAITING-FOR-INTERFACE after the interface disappeared. The
+    /// binding is forgotten without removing the address and routes, which
+    /// vanished with the interface. Timers and messages are ignored until
+    /// [`Protocol::interface_returned`].
+    pub fn interface_gone(&mut self) -> Result<(), ClientError> {
+        warn!(
+            target: LEASE_TARGET,
+            address = ?self.client_state.offered_ip_address,
+            "interface disappeared, dropping the binding"
+        );
+
+        self.transition_to(DhcpState::WaitingForInterface)?;
+        self.client_state = ClientState::default();
+        self.transaction = None;
+        self.deadline = None;
+
+        Ok(())
+    }
+
+    /// Returns to INIT once the interface is back. Call [`Protocol::start`]
+    /// afterwards to begin a new cycle.
+    pub fn interface_returned(&mut self) -> Result<(), ClientError> {
+        self.transition_to(DhcpState::Init)?;
+        Ok(())
+    }
+
+    /// Handles the expiration of the protocol timer. Calls before the
+    /// current deadline are ignored.
+    pub fn handle_timeout(&mut self, now: Instant) -> Result<Vec<Action>, ClientError> {
+        match self.deadline {
+            Some(deadline) if now >= deadline => self.deadline = None,
+            _ => return Ok(Vec::new()),
+        }
+
+        match self.dhcp_state {
+            DhcpState::Init => self.select(now),
+            DhcpState::SelectingSent
+                if self.client_state.discover_attempts < self.max_discover_retries =>
+            {
+                // No offer received yet, retransmit with backoff
+                self.transition_to(DhcpState::Selecting)?;
+                self.discover(now)
+            }
+            DhcpState::SelectingSent | DhcpState::RequestingSent => {
+                // No (usable) reply received, start over
+                self.transition_to(DhcpState::Init)?;
+                Ok(self.init_delay(now))
+            }
+            DhcpState::Bound => {
+                // T1 expired, send DHCPREQUEST to the leasing server
+                self.transition_to(DhcpState::Renewing)?;
+                self.client_state.renewal_time_left = Some(self.t2().saturating_sub(self.t1()));
+                self.start_transaction(TransactionStep::Request, now);
+                self.renew(now)
+            }
+            DhcpState::RenewingSent => match self.client_state.renewal_time_left {
+                // T2 expired, broadcast DHCPREQUEST to any server
+                Some(0) | None => {
+                    self.transition_to(DhcpState::Rebinding)?;
+                    let lease_time = self.client_state.offered_lease_time.unwrap_or_default();
+                    self.client_state.rebinding_time_left =
+                        Some(lease_time.saturating_sub(self.t2()));
+                    self.rebind(now)
+                }
+                Some(_) => {
+                    self.transition_to(DhcpState::Renewing)?;
+                    self.renew(now)
+                }
+            },
+            DhcpState::RebindingSent => match self.client_state.rebinding_time_left {
+                // The lease expired, start over
+                Some(0) | None => {
+                    self.complete_transaction(TransactionStep::Expired, now);
+                    self.transition_to(DhcpState::Init)?;
+                    let mut actions = self.drop_binding();
+                    actions.extend(self.init_delay(now));
+                    Ok(actions)
+                }
+                Some(_) => {
+                    self.transition_to(DhcpState::Rebinding)?;
+                    self.rebind(now)
+                }
+            },
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Handles an incoming DHCP message. Messages with a wrong transaction
+    /// ID or an unexpected message type are discarded, in strict mode also
+    /// messages failing validation.
+    pub fn handle_message(
+        &mut self,
+        message: Message,
+        now: Instant,
+    ) -> Result<Vec<Action>, ClientError> {
+        let expects_reply = matches!(
+            self.dhcp_state,
+            DhcpState::SelectingSent
+                | DhcpState::RequestingSent
+                | DhcpState::RenewingSent
+                | DhcpState::RebindingSent
+        );
+
+        // Remain in the current state, discard the message. This includes
+        // DHCPOFFER, DHCPACK and DHCPNAK messages while BOUND.
+        if !expects_reply {
+            return Ok(Vec::new());
+        }
+
+        // Some systems echo our own broadcasts back to the socket. These
+        // carry our transaction ID, but are requests, not replies.
+        if message.header.opcode == OpCode::BootRequest
+            && message.chaddr == *self.builder.client_hardware_addr()
+        {
+            debug!(target: WIRE_TARGET, "discarding echo of our own broadcast");
+            return Ok(Vec::new());
+        }
+
+        // Check if the transaction ID matches
+        if !message.valid_xid(self.xid()) {
+            error!(
+                target: WIRE_TARGET,
+                "received response with wrong transaction ID: {} (yours: {})",
+                message.header.xid,
+                self.xid()
+            );
+            return Ok(Vec::new());
+        }
+
+        if self.strict {
+            if let Err(err) = message.validate() {
+                warn!(target: WIRE_TARGET, "discarding invalid response in strict mode: {err}");
+                return Ok(Vec::new());
+            }
+        }
+
+        if let DhcpState::SelectingSent = self.dhcp_state {
+            return self.handle_offer(message, now);
+        }
+
+        match message.get_message_type() {
+            Some(DhcpMessageType::Nak) => self.handle_nak(message, now),
+            Some(DhcpMessageType::Ack) => self.handle_ack(message, now),
+            // NOTE (Techassi): How should we handle other message types?
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn handle_offer(&mut self, message: Message, now: Instant) -> Result<Vec<Action>, ClientError> {
+        // Check if the DHCP message type is correct
+        if !message.valid_message_type(DhcpMessageType::Offer) {
+            error!(
+                target: WIRE_TARGET,
+                "received response with no DHCPOFFER message type option set"
+            );
+            return Ok(Vec::new());
+        }
+
+        // Requesting an address another server offered from the given one
+        // only gets a DHCPNAK
+        if let Some(server) = self.requested_server {
+            if message.get_server_identifier() != Some(server) {
+                debug!(
+                    target: WIRE_TARGET,
+                    server = ?message.get_server_identifier(),
+                    requested = %server,
+                    "ignoring DHCPOFFER of another server"
+                );
+                return Ok(Vec::new());
+            }
+        }
+
+        // Select offer
+        // Set destination server IP address
+        if let Some(OptionData::ServerIdentifier(ip)) = message
+            .get_option(OptionTag::ServerIdentifier)
+            .map(|o| o.data())
+        {
+            self.client_state.server_identifier = Some(*ip)
+        }
+
+        // Set offered IP address lease time
+        if let Some(OptionData::IpAddrLeaseTime(time)) = message
+            .get_option(OptionTag::IpAddrLeaseTime)
+            .map(|o| o.data())
+        {
+            self.client_state.offered_lease_time = Some(*time)
+        }
+
+        // Set offered IP address
+        self.client_state.offered_ip_address = Some(message.yiaddr);
+
+        if let Some(record) = &mut self.transaction {
+            record.push(TransactionStep::Offer);
+        }
+
+        self.transition_to(DhcpState::Requesting)?;
+        self.request(now)
+    }
+
+    fn handle_ack(&mut self, message: Message, now: Instant) -> Result<Vec<Action>, ClientError> {
+        // The lease time of the DHCPACK is binding, the one of the DHCPOFFER
+        // only applies if the server didn't repeat it
+        let lease_time = match message
+            .get_option(OptionTag::IpAddrLeaseTime)
+            .map(|o| o.data())
+        {
+            Some(OptionData::IpAddrLeaseTime(time)) => *time,
+            _ => self.client_state.offered_lease_time.unwrap_or_default(),
+        };
+        self.client_state.offered_lease_time = Some(lease_time);
+
+        // Set lease, T1 and T2 timers (DHCPACK)
+        self.client_state.renewal_time = Some(
+            message
+                .get_renewal_t1_time()
+                .unwrap_or_else(|| default_renewal_time(lease_time)),
+        );
+
+        self.client_state.rebinding_time = Some(
+            message
+                .get_rebinding_t2_time()
+                .unwrap_or_else(|| default_rebinding_time(lease_time)),
+        );
+
+        if let Some(record) = &mut self.transaction {
+            record.addr = self.client_state.offered_ip_address;
+            record.lease_time = Some(lease_time);
+        }
+        self.complete_transaction(TransactionStep::Ack, now);
+
+        self.client_state.ntp_config = Some(NtpConfig::from_message(&message));
+
+        let mut actions = Vec::new();
+
+        // The MTU is set before the address, so that the route of the
+        // subnet is created with it
+        if let Some(mtu) = message.get_interface_mtu() {
+            if self.client_state.configured_mtu != Some(mtu) {
+                self.client_state.configured_mtu = Some(mtu);
+                actions.push(Action::SetMtu(mtu));
+            }
+        }
+
+        // The address only needs to be configured once, renewing and
+        // rebinding extend the lease of the already configured address.
+        if let DhcpState::RequestingSent = self.dhcp_state {
+            if let Some(addr) = self.client_state.offered_ip_address {
+                self.client_state.configured_address = Some(addr);
+                self.previous_address = Some(addr);
+                actions.push(Action::ConfigureAddress(addr));
+                actions.push(Action::Announce(addr));
+            }
+        }
+
+        // Install routes via all routers, replacing the previous ones if the
+        // set changed on renewal
+        let routers = message
+            .get_option(OptionTag::Router)
+            .and_then(|o| o.data().as_ipv4_list())
+            .unwrap_or_default();
+        let routes = routes_for(routers, self.route_metric);
+        let changes = diff_routes(&self.client_state.installed_routes, &routes);
+
+        // Stale routes go first, a new route can take the metric of a
+        // removed one and the kernel refuses duplicate default routes
+        actions.extend(changes.remove.into_iter().map(Action::RemoveRoute));
+        actions.extend(changes.add.into_iter().map(Action::AddRoute));
+        self.client_state.installed_routes = routes;
+
+        // Export the resolver configuration when it changed. Leases without
+        // name servers leave the system resolver untouched.
+        let dns_config = DnsConfig::from_message(&message);
+        if !dns_config.nameservers.is_empty()
+            && self.client_state.dns_config.as_ref() != Some(&dns_config)
+        {
+            actions.push(Action::ConfigureDns(dns_config.clone()));
+        }
+        self.client_state.dns_config = Some(dns_config);
+
+        if let Some(addr) = self.client_state.offered_ip_address {
+            let lease = LeaseInfo {
+                addr,
+                server: self.client_state.server_identifier,
+                lease_time,
+                renewal_time: self.t1(),
+                rebinding_time: self.t2(),
+            };
+
+            actions.push(Action::Lease(match self.dhcp_state {
+                DhcpState::RequestingSent => LeaseEvent::Bound(lease),
+                _ => LeaseEvent::Renewed(lease),
+            }));
+        }
+
+        // Transition to BOUND and wait for T1 to expire. Infinite leases are
+        // never renewed.
+        self.transition_to(DhcpState::Bound)?;
+        self.init_cycles = 0;
+
+        match LeaseTime::from(lease_time) {
+            LeaseTime::Infinite => {
+                debug!(target: LEASE_TARGET, "lease is infinite, not scheduling renewal");
+                self.deadline = None;
+            }
+            LeaseTime::Finite(_) => {
+                actions.push(self.set_timer(now + Duration::from_secs(self.t1() as u64)))
+            }
+        }
+
+        Ok(actions)
+    }
+
+    /// Handles a DHCPNAK in REQUESTING, RENEWING or REBINDING. The server
+    /// rejected the address, so the client stops using it and starts over
+    /// in INIT without any knowledge of the previous binding.
+    fn handle_nak(&mut self, message: Message, now: Instant) -> Result<Vec<Action>, ClientError> {
+        warn!(
+            target: LEASE_TARGET,
+            server = ?message.get_server_identifier(),
+            address = ?self.client_state.offered_ip_address,
+            reason = message.get_message_text().map(String::as_str).unwrap_or("none given"),
+            "received DHCPNAK, dropping the binding"
+        );
+        self.complete_transaction(TransactionStep::Nak, now);
+
+        self.transition_to(DhcpState::Init)?;
+        let mut actions = self.drop_binding();
+        actions.extend(self.init_delay(now));
+
+        Ok(actions)
+    }
+
+    /// Forgets the current binding and returns the actions which remove the
+    /// configured address and the installed routes from the interface.
+    fn drop_binding(&mut self) -> Vec<Action> {
+        let state = std::mem::take(&mut self.client_state);
+
+        let mut actions: Vec<Action> = state
+            .installed_routes
+            .into_iter()
+            .map(Action::RemoveRoute)
+            .collect();
+        if let Some(addr) = state.configured_address {
+            actions.push(Action::RemoveAddress(addr));
+            actions.push(Action::Lease(LeaseEvent::Lost { addr }));
+        }
+
+        actions
+    }
+
+    /// Waits a random amount from the initial delay range in INIT, plus the
+    /// backoff if previous cycles ended without a lease.
+    fn init_delay(&mut self, now: Instant) -> Vec<Action> {
+        let delay = if self.initial_delay.is_empty() {
+            self.initial_delay.start
+        } else {
+            self.rng.gen_range(self.initial_delay.clone())
+        };
+
+        let wait_duration = delay + self.init_backoff();
+        self.init_cycles = self.init_cycles.saturating_add(1);
+        debug!(
+            target: FSM_TARGET,
+            "Waiting for {:?} to send DHCPDISCOVER message",
+            wait_duration
+        );
+
+        vec![self.set_timer(now + wait_duration)]
+    }
+
+    /// Returns the additional wait in INIT, which doubles with every
+    /// consecutive cycle without a lease up to the configured maximum.
+    fn init_backoff(&self) -> Duration {
+        match self.init_cycles {
+            0 => Duration::ZERO,
+            n => (Duration::from_secs(1) * 2u32.pow((n - 1).min(16))).min(self.max_init_backoff),
+        }
+    }
+
+    /// Enter SELECTING and send a DHCPDISCOVER message.
+    fn select(&mut self, now: Instant) -> Result<Vec<Action>, ClientError> {
+        self.transition_to(DhcpState::Selecting)?;
+        self.renew_xid();
+        self.client_state.discover_attempts = 0;
+        self.start_transaction(TransactionStep::Discover, now);
+
+        self.discover(now)
+    }
+
+    /// Send a DHCPDISCOVER message in SELECTING. Every retransmission in
+    /// the same cycle waits twice as long for offers as the previous one.
+    fn discover(&mut self, now: Instant) -> Result<Vec<Action>, ClientError> {
+        debug!(target: FSM_TARGET, "sending DHCPDISCOVER message");
+        let message =
+            self.builder
+                .make_discover_message(self.xid(), self.previous_address, None)?;
+
+        let wait = (self.read_timeout * 2u32.pow(self.client_state.discover_attempts.min(16)))
+            .min(Duration::from_secs(MAX_DISCOVER_BACKOFF_SECS))
+            .max(self.read_timeout);
+        self.client_state.discover_attempts += 1;
+
+        self.transition_to(DhcpState::SelectingSent)?;
+        Ok(vec![
+            Action::Send(message, self.destination()),
+            self.set_timer(now + wait),
+        ])
+    }
+
+    /// Send a DHCPREQUEST message in REQUESTING.
+    fn request(&mut self, now: Instant) -> Result<Vec<Action>, ClientError> {
+        debug!(target: FSM_TARGET, "sending DHCPREQUEST message");
+        let message = self.builder.make_request_message(
+            self.xid(),
+            self.destination_addr(),
+            self.client_state
+                .offered_ip_address
+                .unwrap_or(Ipv4Addr::UNSPECIFIED),
+            self.client_state.offered_lease_time.unwrap_or_default(),
+        )?;
+
+        if let Some(record) = &mut self.transaction {
+            record.push(TransactionStep::Request);
+        }
+
+        self.transition_to(DhcpState::RequestingSent)?;
+        Ok(vec![
+            Action::Send(message, self.destination()),
+            self.set_timer(now + self.read_timeout),
+        ])
+    }
+
+    /// Send a DHCPREQUEST message to the leasing server in RENEWING.
+    fn renew(&mut self, now: Instant) -> Result<Vec<Action>, ClientError> {
+        debug!(target: FSM_TARGET, "renewing XID");
+        self.renew_xid();
+
+        let message = self.make_renewing_message()?;
+        let wait = retransmission_wait(&mut self.client_state.renewal_time_left);
+
+        self.transition_to(DhcpState::RenewingSent)?;
+        Ok(vec![
+            Action::Send(message, self.destination()),
+            self.set_timer(now + wait),
+        ])
+    }
+
+    /// Broadcast a DHCPREQUEST message in REBINDING.
+    fn rebind(&mut self, now: Instant) -> Result<Vec<Action>, ClientError> {
+        // Reset the server identifier (IP address). The message will be
+        // send using the broadcast address.
+        self.client_state.server_identifier = None;
+
+        let message = self.make_renewing_message()?;
+        let wait = retransmission_wait(&mut self.client_state.rebinding_time_left);
+
+        self.transition_to(DhcpState::RebindingSent)?;
+        Ok(vec![
+            Action::Send(message, self.destination()),
+            self.set_timer(now + wait),
+        ])
+    }
+
+    fn make_renewing_message(&self) -> Result<Message, ClientError> {
+        debug!(target: FSM_TARGET, "sending DHCPREQUEST message");
+        Ok(self.builder.make_renewing_message(
+            self.xid(),
+            self.client_state
+                .offered_ip_address
+                .unwrap_or(Ipv4Addr::UNSPECIFIED),
+            self.client_state.offered_lease_time.unwrap_or_default(),
+        )?)
+    }
+
+    /// Starts the record of a new transaction with `step` at `now`,
+    /// replacing an unfinished one.
+    fn start_transaction(&mut self, step: TransactionStep, now: Instant) {
+        let mut record =
+            TransactionRecord::new(self.builder.client_hardware_addr().to_string(), now);
+        record.interface = self.interface.clone();
+        record.push(step);
+        self.transaction = Some(record);
+    }
+
+    /// Completes the current transaction with `step` at `now` and emits its
+    /// record. The record carries the wall-clock time, the duration is
+    /// measured with the instants passed to the protocol.
+    fn complete_transaction(&mut self, step: TransactionStep, now: Instant) {
+        let Some(mut record) = self.transaction.take() else {
+            return;
+        };
+
+        record.push(step);
+        record.complete(SystemTime::now(), now);
+        record.emit();
+    }
+
+    fn set_timer(&mut self, deadline: Instant) -> Action {
+        self.deadline = Some(deadline);
+        Action::SetTimer(deadline)
+    }
+
+    fn t1(&self) -> u32 {
+        self.client_state.renewal_time.unwrap_or_default()
+    }
+
+    fn t2(&self) -> u32 {
+        self.client_state
+            .rebinding_time
+            .unwrap_or_default()
+            .max(self.t1())
+    }
+
+    /// Returns the current transaction ID.
+    fn xid(&self) -> u32 {
+        self.client_state.transaction_id
+    }
+
+    /// Renews the transaction ID by selecting a new, random one.
+    fn renew_xid(&mut self) {
+        self.client_state.transaction_id = self.rng.gen()
+    }
+
+    /// Returns the destination address. This is either the IP address of the
+    /// current DHCP server or the IPv4 broadcast address.
+    fn destination_addr(&self) -> Ipv4Addr {
+        self.destination().addr()
+    }
+
+    fn destination(&self) -> Dest {
+        match self.client_state.server_identifier {
+            Some(ip) => Dest::Unicast(ip),
+            None => Dest::Broadcast,
+        }
+    }
+}
+
+/// Returns how long to wait for a reply before retransmitting a DHCPREQUEST
+/// in RENEWING or REBINDING and subtracts it from the time left. The client
+/// waits one-half of the remaining time, down to a minimum of 60 seconds.
+/// Below that, it waits for the remaining time.
+///
+/// ### See
+///
+/// RFC 2131 - Section 4.4.5 - Reacquisition and expiration: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4.5
+fn retransmission_wait(time_left: &mut Option<u32>) -> Duration {
+    let left = time_left.unwrap_or_default();
+
+    let wait = if left < MINIMAL_RETRANS_DURATION_SECS * 2 {
+        left
+    } else {
+        left / 2
+    };
+
+    *time_left = Some(left - wait);
+    Duration::from_secs(wait as u64)
+}
+
+/// Returns the renewal time (T1) used if the server didn't send one,
+/// one-half of `lease_time` (RFC 2131 Section 4.4.5).
+fn default_renewal_time(lease_time: u32) -> u32 {
+    lease_time / 2
+}
+
+/// Returns the rebinding time (T2) used if the server didn't send one,
+/// seven-eighths of `lease_time` (RFC 2131 Section 4.4.5). The product is
+/// computed in 64 bits, so large lease times neither overflow nor lose
+/// precision.
+fn default_rebinding_time(lease_time: u32) -> u32 {
+    u32::try_from(lease_time as u64 * 7 / 8).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use binbuf::prelude::*;
+
+    use super::*;
+    use crate::{
+        client::{configurator, ConfiguratorCall, MockConfigurator},
+        types::{HardwareAddr, OpCode},
+        Clock, SeededRng, StepClock,
+    };
+
+    const SERVER: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 100);
+
+    fn protocol() -> Protocol {
+        let hardware_addr = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let builder = MessageBuilder::new(hardware_addr, None, 1500);
+
+        Protocol::new(builder, Duration::from_secs(2))
+    }
+
+    fn reply(xid: u32, ty: DhcpMessageType, lease_time: u32) -> Message {
+        reply_from(SERVER, xid, ty, lease_time)
+    }
+
+    fn reply_from(server: Ipv4Addr, xid: u32, ty: DhcpMessageType, lease_time: u32) -> Message {
+        let mut message = Message::new_with_xid(xid);
+        message.header.opcode = OpCode::BootReply;
+        message.yiaddr = CLIENT;
+
+        message
+            .add_option_parts(OptionTag::DhcpMessageType, OptionData::DhcpMessageType(ty))
+            .unwrap();
+        message
+            .add_option_parts(
+                OptionTag::ServerIdentifier,
+                OptionData::ServerIdentifier(server),
+            )
+            .unwrap();
+        message
+            .add_option_parts(
+                OptionTag::IpAddrLeaseTime,
+                OptionData::IpAddrLeaseTime(lease_time),
+            )
+            .unwrap();
+        message.end().unwrap();
+        message
+    }
+
+    fn deadline(actions: &[Action]) -> Instant {
+        actions
+            .iter()
+            .find_map(|a| match a {
+                Action::SetTimer(deadline) => Some(*deadline),
+                _ => None,
+            })
+            .expect("no timer set")
+    }
+
+    fn sent(actions: &[Action]) -> (&Message, Dest) {
+        actions
+            .iter()
+            .find_map(|a| match a {
+                Action::Send(message, dest) => Some((message, *dest)),
+                _ => None,
+            })
+            .expect("no message sent")
+    }
+
+    /// Executes the interface configuring `actions` on a mock configurator
+    /// for "eth0" and returns the recorded calls.
+    fn configure(actions: &[Action]) -> Vec<ConfiguratorCall> {
+        let configurator = MockConfigurator::new();
+        for action in actions {
+            configurator::apply(&configurator, "eth0", action).unwrap();
+        }
+
+        configurator.take()
+    }
+
+    /// Runs through DHCPDISCOVER, DHCPOFFER, DHCPREQUEST and DHCPACK and
+    /// returns the time the client entered BOUND.
+    fn dora(protocol: &mut Protocol, lease_time: u32) -> (Vec<Action>, Instant) {
+        let clock = StepClock::default();
+        let actions = protocol.start(clock.now_instant());
+
+        // The first DHCPDISCOVER is delayed between one and ten seconds
+        clock.advance_to(deadline(&actions));
+        let now = clock.now_instant();
+        assert!(protocol
+            .handle_timeout(now - Duration::from_millis(1))
+            .unwrap()
+            .is_empty());
+
+        let actions = protocol.handle_timeout(now).unwrap();
+        let (discover, dest) = sent(&actions);
+        assert_eq!(
+            discover.get_message_type(),
+            Some(&DhcpMessageType::Discover)
+        );
+        assert_eq!(dest, Dest::Broadcast);
+        let xid = discover.header.xid;
+
+        let offer = reply(xid, DhcpMessageType::Offer, lease_time);
+        let actions = protocol.handle_message(offer, now).unwrap();
+        let (request, dest) = sent(&actions);
+        assert_eq!(request.get_message_type(), Some(&DhcpMessageType::Request));
+        assert_eq!(request.header.xid, xid);
+        assert_eq!(dest, Dest::Unicast(SERVER));
+
+        let ack = reply(xid, DhcpMessageType::Ack, lease_time);
+        (protocol.handle_message(ack, now).unwrap(), now)
+    }
+
+    #[test]
+    fn test_dora() {
+        let mut protocol = protocol();
+        let (actions, now) = dora(&mut protocol, 3600);
+
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+        assert_eq!(
+            configure(&actions),
+            [
+                ConfiguratorCall::AddAddress("eth0".into(), CLIENT),
+                ConfiguratorCall::Announce("eth0".into(), CLIENT),
+            ]
+        );
+        assert_eq!(deadline(&actions), now + Duration::from_secs(1800));
+    }
+
+    #[test]
+    fn test_wrong_xid_is_discarded() {
+        let mut protocol = protocol();
+        let actions = protocol.start(Instant::now());
+        let now = deadline(&actions);
+
+        let actions = protocol.handle_timeout(now).unwrap();
+        let xid = sent(&actions).0.header.xid;
+
+        let offer = reply(xid.wrapping_add(1), DhcpMessageType::Offer, 3600);
+        assert!(protocol.handle_message(offer, now).unwrap().is_empty());
+        assert!(matches!(protocol.state(), DhcpState::SelectingSent));
+    }
+
+    #[test]
+    fn test_strict_mode_refuses_invalid_offer() {
+        for strict in [false, true] {
+            let mut protocol = protocol().with_strict(strict);
+            let actions = protocol.start(Instant::now());
+            let now = deadline(&actions);
+
+            let actions = protocol.handle_timeout(now).unwrap();
+            let xid = sent(&actions).0.header.xid;
+
+            // An OFFER must not carry the 'ciaddr' of the client
+            let mut offer = reply(xid, DhcpMessageType::Offer, 3600);
+            offer.ciaddr = CLIENT;
+            assert!(offer.validate().is_err());
+
+            let actions = protocol.handle_message(offer, now).unwrap();
+            assert_eq!(actions.is_empty(), strict);
+
+            if strict {
+                assert!(matches!(protocol.state(), DhcpState::SelectingSent));
+            } else {
+                assert!(matches!(protocol.state(), DhcpState::RequestingSent));
+            }
+        }
+    }
+
+    #[test]
+    fn test_own_broadcast_is_discarded() {
+        let mut protocol = protocol();
+        let actions = protocol.start(Instant::now());
+        let now = deadline(&actions);
+
+        let actions = protocol.handle_timeout(now).unwrap();
+        let discover = sent(&actions).0.clone();
+
+        // A request with our hardware address and transaction ID is dropped,
+        // even if it would otherwise be taken as an offer
+        let mut echo = reply(discover.header.xid, DhcpMessageType::Offer, 3600);
+        echo.header.opcode = OpCode::BootRequest;
+        echo.set_hardware_address(discover.chaddr.clone());
+
+        assert!(protocol.handle_message(discover, now).unwrap().is_empty());
+        assert!(protocol.handle_message(echo, now).unwrap().is_empty());
+        assert!(matches!(protocol.state(), DhcpState::SelectingSent));
+    }
+
+    #[test]
+    fn test_selecting_timeout_restarts() {
+        let mut protocol = protocol();
+        let actions = protocol.start(Instant::now());
+
+        let actions = protocol.handle_timeout(deadline(&actions)).unwrap();
+        let actions = protocol.handle_timeout(deadline(&actions)).unwrap();
+
+        assert!(matches!(protocol.state(), DhcpState::Init));
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[test]
+    fn test_state_machine_log_target() {
+        let capture = crate::utils::LogCapture::default();
+        let mut protocol = protocol();
+
+        tracing::subscriber::with_default(capture.subscriber(tracing::Level::TRACE), || {
+            let actions = protocol.start(Instant::now());
+            protocol.handle_timeout(deadline(&actions)).unwrap();
+        });
+
+        let output = capture.output();
+        let transitions: Vec<_> = output
+            .lines()
+            .filter(|line| line.contains("state transition"))
+            .collect();
+
+        assert!(!transitions.is_empty());
+        assert!(transitions
+            .iter()
+            .all(|line| line.contains(" vulcan::fsm: ")));
+        assert!(output
+            .lines()
+            .any(|line| line.contains(" vulcan::fsm: ") && line.contains("sending DHCPDISCOVER")));
+    }
+
+    /// Runs the client for `duration` without any server answering and
+    /// returns the encoded messages it sent, with the time they were sent.
+    fn trace(seed: u64, duration: Duration) -> Vec<(Duration, Vec<u8>)> {
+        let clock = StepClock::default();
+        let mut protocol = protocol()
+            .with_rng(SeededRng::new(seed))
+            .with_max_discover_retries(3);
+
+        let mut actions = protocol.start(clock.now_instant());
+        let mut trace = Vec::new();
+
+        while clock.elapsed() < duration {
+            for action in &actions {
+                if let Action::Send(message, _) = action {
+                    let mut buf = WriteBuffer::new();
+                    message.write::<BigEndian>(&mut buf).unwrap();
+                    trace.push((clock.elapsed(), buf.bytes().to_vec()));
+                }
+            }
+
+            clock.advance_to(deadline(&actions));
+            actions = protocol.handle_timeout(clock.now_instant()).unwrap();
+        }
+
+        trace
+    }
+
+    #[test]
+    fn test_discover_retries() {
+        let clock = StepClock::default();
+        let mut protocol = protocol()
+            .with_rng(SeededRng::new(1))
+            .with_max_discover_retries(3);
+        let actions = protocol.start(clock.now_instant());
+
+        clock.advance_to(deadline(&actions));
+        let mut actions = protocol.handle_timeout(clock.now_instant()).unwrap();
+        let mut discovers = Vec::new();
+        let mut waits = Vec::new();
+
+        // No server answers, retransmit until the client starts over
+        while matches!(protocol.state(), DhcpState::SelectingSent) {
+            discovers.push(sent(&actions).0.header.xid);
+            waits.push(deadline(&actions) - clock.now_instant());
+
+            clock.advance_to(deadline(&actions));
+            actions = protocol.handle_timeout(clock.now_instant()).unwrap();
+        }
+
+        assert!(matches!(protocol.state(), DhcpState::Init));
+        assert_eq!(discovers.len(), 3);
+
+        // Retransmissions reuse the transaction ID and back off
+        assert!(discovers.iter().all(|xid| *xid == discovers[0]));
+        assert_eq!(
+            waits,
+            vec![
+                Duration::from_secs(2),
+                Duration::from_secs(4),
+                Duration::from_secs(8)
+            ]
+        );
+    }
+
+    /// Returns how long the client waited in INIT before each of the first
+    /// `cycles` cycles, with no server answering.
+    fn init_waits(mut protocol: Protocol, cycles: usize) -> Vec<Duration> {
+        let clock = StepClock::default();
+        let mut actions = protocol.start(clock.now_instant());
+        let mut waits = Vec::new();
+
+        while waits.len() < cycles {
+            if matches!(protocol.state(), DhcpState::Init) {
+                waits.push(deadline(&actions) - clock.now_instant());
+            }
+
+            clock.advance_to(deadline(&actions));
+            actions = protocol.handle_timeout(clock.now_instant()).unwrap();
+        }
+
+        waits
+    }
+
+    #[test]
+    fn test_init_backoff() {
+        let zero_delay = protocol()
+            .with_rng(SeededRng::new(1))
+            .with_initial_delay(Duration::ZERO..Duration::ZERO)
+            .with_max_init_backoff(Duration::from_secs(8));
+
+        // The first DHCPDISCOVER is sent right away, then every failed cycle
+        // doubles the wait up to the maximum
+        assert_eq!(
+            init_waits(zero_delay, 6),
+            [0, 1, 2, 4, 8, 8].map(Duration::from_secs)
+        );
+
+        let no_backoff = protocol()
+            .with_rng(SeededRng::new(1))
+            .with_initial_delay(Duration::from_millis(100)..Duration::from_millis(200))
+            .with_max_init_backoff(Duration::ZERO);
+
+        assert!(init_waits(no_backoff, 10).iter().all(|wait| {
+            *wait >= Duration::from_millis(100) && *wait < Duration::from_millis(200)
+        }));
+    }
+
+    #[test]
+    fn test_bound_resets_init_backoff() {
+        let mut protocol = protocol()
+            .with_rng(SeededRng::new(1))
+            .with_initial_delay(Duration::ZERO..Duration::ZERO);
+
+        // Fail a few cycles before a server answers
+        let now = Instant::now();
+        let mut actions = protocol.start(now);
+        for _ in 0..3 {
+            actions = protocol.handle_timeout(deadline(&actions)).unwrap();
+            actions = protocol.handle_timeout(deadline(&actions)).unwrap();
+        }
+        assert_eq!(protocol.init_backoff(), Duration::from_secs(8));
+
+        let now = deadline(&actions);
+        let actions = protocol.handle_timeout(now).unwrap();
+        let xid = sent(&actions).0.header.xid;
+        protocol
+            .handle_message(reply(xid, DhcpMessageType::Offer, 3600), now)
+            .unwrap();
+        protocol
+            .handle_message(reply(xid, DhcpMessageType::Ack, 3600), now)
+            .unwrap();
+
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+        assert_eq!(protocol.init_backoff(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_seeded_runs_are_identical() {
+        let first = trace(7, Duration::from_secs(300));
+
+        // Several cycles of three DHCPDISCOVERs each
+        assert!(first.len() > 3);
+        assert_eq!(first, trace(7, Duration::from_secs(300)));
+        assert_ne!(first, trace(8, Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn test_nak_restarts() {
+        let mut protocol = protocol();
+        let actions = protocol.start(Instant::now());
+        let now = deadline(&actions);
+
+        let actions = protocol.handle_timeout(now).unwrap();
+        let xid = sent(&actions).0.header.xid;
+
+        let offer = reply(xid, DhcpMessageType::Offer, 3600);
+        protocol.handle_message(offer, now).unwrap();
+
+        let nak = reply(xid, DhcpMessageType::Nak, 3600);
+        protocol.handle_message(nak, now).unwrap();
+        assert!(matches!(protocol.state(), DhcpState::Init));
+    }
+
+    #[test]
+    fn test_nak_drops_binding() {
+        let mut protocol = protocol();
+        let (actions, _) = dora(&mut protocol, 3600);
+
+        let t1 = deadline(&actions);
+        let actions = protocol.handle_timeout(t1).unwrap();
+        let xid = sent(&actions).0.header.xid;
+
+        // The ACK of the renewal installs a route
+        let mut ack = reply(xid, DhcpMessageType::Ack, 3600);
+        ack.options.pop();
+        ack.add_option_parts(OptionTag::Router, OptionData::Router(vec![SERVER]))
+            .unwrap();
+        ack.end().unwrap();
+        let actions = protocol.handle_message(ack, t1).unwrap();
+        assert_eq!(
+            configure(&actions),
+            [ConfiguratorCall::AddRoute(
+                "eth0".into(),
+                Route {
+                    gateway: SERVER,
+                    metric: DEFAULT_ROUTE_METRIC
+                }
+            )]
+        );
+
+        // The server rejects the next renewal
+        let actions = protocol.handle_timeout(deadline(&actions)).unwrap();
+        let xid = sent(&actions).0.header.xid;
+
+        let mut nak = reply(xid, DhcpMessageType::Nak, 3600);
+        nak.options.pop();
+        nak.add_option_parts(
+            OptionTag::Message,
+            OptionData::Message(String::from("wrong network")),
+        )
+        .unwrap();
+        nak.end().unwrap();
+
+        let actions = protocol.handle_message(nak, t1).unwrap();
+        assert!(matches!(protocol.state(), DhcpState::Init));
+        assert!(matches!(actions.last(), Some(Action::SetTimer(_))));
+        assert_eq!(
+            configure(&actions),
+            [
+                ConfiguratorCall::DelRoute(
+                    "eth0".into(),
+                    Route {
+                        gateway: SERVER,
+                        metric: DEFAULT_ROUTE_METRIC
+                    }
+                ),
+                ConfiguratorCall::DelAddress("eth0".into(), CLIENT),
+            ]
+        );
+
+        // Nothing of the previous binding is left
+        assert!(protocol.dns_config().is_none());
+        assert!(protocol.ntp_config().is_none());
+        assert!(protocol.client_state.offered_ip_address.is_none());
+        assert!(protocol.client_state.server_identifier.is_none());
+        assert!(protocol.client_state.installed_routes.is_empty());
+        assert!(protocol.client_state.configured_address.is_none());
+    }
+
+    #[test]
+    fn test_changed_routers_remove_before_add() {
+        const A: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+        const B: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+        const C: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 3);
+
+        let mut protocol = protocol();
+        let (mut actions, _) = dora(&mut protocol, 3600);
+
+        let mut renew = |actions: &[Action], routers: Vec<Ipv4Addr>| {
+            let t1 = deadline(actions);
+            let actions = protocol.handle_timeout(t1).unwrap();
+
+            let mut ack = reply(sent(&actions).0.header.xid, DhcpMessageType::Ack, 3600);
+            ack.options.pop();
+            ack.add_option_parts(OptionTag::Router, OptionData::Router(routers))
+                .unwrap();
+            ack.end().unwrap();
+            protocol.handle_message(ack, t1).unwrap()
+        };
+
+        actions = renew(&actions, vec![A, B]);
+        assert_eq!(configure(&actions).len(), 2);
+
+        // C takes over the metric of B, which has to be removed first
+        let route = |gateway, metric| Route { gateway, metric };
+        assert_eq!(
+            configure(&renew(&actions, vec![A, C])),
+            [
+                ConfiguratorCall::DelRoute("eth0".into(), route(B, DEFAULT_ROUTE_METRIC + 1)),
+                ConfiguratorCall::AddRoute("eth0".into(), route(C, DEFAULT_ROUTE_METRIC + 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interface_mtu_is_set() {
+        let mut protocol = protocol();
+        let (mut actions, _) = dora(&mut protocol, 3600);
+
+        let mut renew = |actions: &[Action], mtu: u16| {
+            let t1 = deadline(actions);
+            let actions = protocol.handle_timeout(t1).unwrap();
+
+            let mut ack = reply(sent(&actions).0.header.xid, DhcpMessageType::Ack, 3600);
+            ack.options.pop();
+            ack.add_option_parts(OptionTag::InterfaceMtu, OptionData::InterfaceMtu(mtu))
+                .unwrap();
+            ack.end().unwrap();
+            protocol.handle_message(ack, t1).unwrap()
+        };
+
+        actions = renew(&actions, 1400);
+        assert_eq!(
+            configure(&actions),
+            [ConfiguratorCall::SetMtu("eth0".into(), 1400)]
+        );
+
+        // An unchanged MTU isn't set again
+        actions = renew(&actions, 1400);
+        assert!(configure(&actions).is_empty());
+    }
+
+    #[test]
+    fn test_changed_dns_is_configured() {
+        const NS: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 53);
+
+        let mut protocol = protocol();
+        let (mut actions, _) = dora(&mut protocol, 3600);
+
+        let mut renew = |actions: &[Action], nameservers: Vec<Ipv4Addr>| {
+            let t1 = deadline(actions);
+            let actions = protocol.handle_timeout(t1).unwrap();
+
+            let mut ack = reply(sent(&actions).0.header.xid, DhcpMessageType::Ack, 3600);
+            ack.options.pop();
+            ack.add_option_parts(
+                OptionTag::DomainNameServer,
+                OptionData::DomainNameServer(nameservers),
+            )
+            .unwrap();
+            ack.end().unwrap();
+            protocol.handle_message(ack, t1).unwrap()
+        };
+
+        actions = renew(&actions, vec![NS]);
+        let expected = DnsConfig {
+            nameservers: vec![NS],
+            ..Default::default()
+        };
+        assert_eq!(
+            configure(&actions),
+            [ConfiguratorCall::SetDns("eth0".into(), expected)]
+        );
+
+        // An unchanged configuration isn't written again
+        actions = renew(&actions, vec![NS]);
+        assert!(configure(&actions).is_empty());
+    }
+
+    #[test]
+    fn test_renewal() {
+        let mut protocol = protocol();
+        let (actions, _) = dora(&mut protocol, 3600);
+
+        // T1 expires, the DHCPREQUEST is sent to the leasing server
+        let t1 = deadline(&actions);
+        let actions = protocol.handle_timeout(t1).unwrap();
+        let (request, dest) = sent(&actions);
+
+        assert!(matches!(protocol.state(), DhcpState::RenewingSent));
+        assert_eq!(request.ciaddr, CLIENT);
+        assert_eq!(dest, Dest::Unicast(SERVER));
+
+        // T2 - T1 = 1350 seconds left, wait one-half of that
+        assert_eq!(deadline(&actions), t1 + Duration::from_secs(675));
+
+        let ack = reply(request.header.xid, DhcpMessageType::Ack, 3600);
+        let actions = protocol.handle_message(ack, t1).unwrap();
+
+        // The address is already configured, only the T1 timer is re-armed
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+        assert_eq!(actions.len(), 2);
+        assert!(configure(&actions).is_empty());
+        assert_eq!(deadline(&actions), t1 + Duration::from_secs(1800));
+    }
+
+    #[test]
+    fn test_lease_events() {
+        let events = |actions: &[Action]| -> Vec<LeaseEvent> {
+            actions
+                .iter()
+                .filter_map(|a| match a {
+                    Action::Lease(event) => Some(event.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let lease = LeaseInfo {
+            addr: CLIENT,
+            server: Some(SERVER),
+            lease_time: 3600,
+            renewal_time: 1800,
+            rebinding_time: 3150,
+        };
+
+        let mut protocol = protocol();
+        let (actions, _) = dora(&mut protocol, 3600);
+        assert_eq!(events(&actions), [LeaseEvent::Bound(lease.clone())]);
+        assert_eq!(
+            serde_json::to_string(&events(&actions)[0]).unwrap(),
+            "{\"event\":\"bound\",\"addr\":\"192.168.1.100\",\"server\":\"192.168.1.1\",\
+             \"lease_time\":3600,\"renewal_time\":1800,\"rebinding_time\":3150}"
+        );
+
+        // Renewals report the extended lease
+        let t1 = deadline(&actions);
+        let actions = protocol.handle_timeout(t1).unwrap();
+        let xid = sent(&actions).0.header.xid;
+        let ack = reply(xid, DhcpMessageType::Ack, 3600);
+        let actions = protocol.handle_message(ack, t1).unwrap();
+        assert_eq!(events(&actions), [LeaseEvent::Renewed(lease)]);
+
+        // A DHCPNAK loses the lease after the address was removed
+        let t1 = deadline(&actions);
+        let actions = protocol.handle_timeout(t1).unwrap();
+        let xid = sent(&actions).0.header.xid;
+        let nak = reply(xid, DhcpMessageType::Nak, 3600);
+        let actions = protocol.handle_message(nak, t1).unwrap();
+        assert_eq!(events(&actions), [LeaseEvent::Lost { addr: CLIENT }]);
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::RemoveAddress(CLIENT), Action::Lease(_), ..]
+        ));
+    }
+
+    #[test]
+    fn test_ack_lease_time_and_late_t2() {
+        let mut protocol = protocol();
+        let (actions, _) = dora(&mut protocol, 3600);
+
+        // The renewal DHCPACK extends the lease beyond the offered one and
+        // sets T2 past the offered lease time
+        let t1 = deadline(&actions);
+        let actions = protocol.handle_timeout(t1).unwrap();
+        let mut ack = reply(sent(&actions).0.header.xid, DhcpMessageType::Ack, 7200);
+        ack.options.pop();
+        ack.add_option_parts(OptionTag::RenewalT1Time, OptionData::RenewalT1Time(4000))
+            .unwrap();
+        ack.add_option_parts(
+            OptionTag::RebindingT2Time,
+            OptionData::RebindingT2Time(5000),
+        )
+        .unwrap();
+        ack.end().unwrap();
+
+        let actions = protocol.handle_message(ack, t1).unwrap();
+        assert_eq!(protocol.client_state.offered_lease_time, Some(7200));
+        assert_eq!(deadline(&actions), t1 + Duration::from_secs(4000));
+
+        // Renew without a reply until T2, which leaves the rest of the
+        // acknowledged lease for rebinding
+        let mut now = deadline(&actions);
+        let mut actions = protocol.handle_timeout(now).unwrap();
+        while matches!(protocol.state(), DhcpState::RenewingSent) {
+            now = deadline(&actions);
+            actions = protocol.handle_timeout(now).unwrap();
+        }
+
+        assert!(matches!(protocol.state(), DhcpState::RebindingSent));
+        assert_eq!(deadline(&actions), now + Duration::from_secs(1100));
+    }
+
+    #[test]
+    fn test_request_from() {
+        let other = Ipv4Addr::new(192, 168, 1, 2);
+        let mut protocol = protocol();
+        protocol.request_from(other);
+
+        let clock = StepClock::default();
+        let actions = protocol.start(clock.now_instant());
+        let now = deadline(&actions);
+        let actions = protocol.handle_timeout(now).unwrap();
+        let (discover, dest) = sent(&actions);
+        assert_eq!(dest, Dest::Unicast(other));
+        assert_eq!(discover.get_server_identifier(), None);
+
+        // Offers of other servers are ignored
+        let xid = discover.header.xid;
+        let offer = reply(xid, DhcpMessageType::Offer, 3600);
+        assert!(protocol.handle_message(offer, now).unwrap().is_empty());
+        assert!(matches!(protocol.state(), DhcpState::SelectingSent));
+
+        let offer = reply_from(other, xid, DhcpMessageType::Offer, 3600);
+        let actions = protocol.handle_message(offer, now).unwrap();
+        let (request, dest) = sent(&actions);
+        assert_eq!(dest, Dest::Unicast(other));
+        assert_eq!(request.get_server_identifier(), Some(other));
+
+        let ack = reply_from(other, xid, DhcpMessageType::Ack, 3600);
+        let actions = protocol.handle_message(ack, now).unwrap();
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+
+        // Renewals go to the given server as well
+        let t1 = deadline(&actions);
+        let actions = protocol.handle_timeout(t1).unwrap();
+        assert_eq!(sent(&actions).1, Dest::Unicast(other));
+    }
+
+    #[test]
+    fn test_transaction_records() {
+        let capture = crate::utils::LogCapture::default();
+        let mut protocol = protocol().with_interface_name("eth0");
+
+        tracing::subscriber::with_default(capture.subscriber(tracing::Level::INFO), || {
+            let (actions, _) = dora(&mut protocol, 3600);
+
+            let t1 = deadline(&actions);
+            let actions = protocol.handle_timeout(t1).unwrap();
+            let xid = sent(&actions).0.header.xid;
+
+            let nak = reply(xid, DhcpMessageType::Nak, 3600);
+            protocol
+                .handle_message(nak, t1 + Duration::from_millis(250))
+                .unwrap();
+        });
+
+        let output = capture.output();
+        let records: Vec<_> = output
+            .lines()
+            .filter_map(|line| line.split_once(" interface="))
+            .map(|(_, record)| record)
+            .collect();
+
+        assert_eq!(
+            records,
+            [
+                "eth0 flow=discover>offer>request>ack client=de:ad:be:ef:12:34 hostname=- \
+                 addr=192.168.1.100 lease=3600 pool=- relay=- duration_ms=0",
+                "eth0 flow=request>nak client=de:ad:be:ef:12:34 hostname=- addr=- lease=- \
+                 pool=- relay=- duration_ms=250",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_renewal_without_reply_rebinds_and_expires() {
+        let mut protocol = protocol();
+        let (actions, _) = dora(&mut protocol, 3600);
+
+        let mut now = deadline(&actions);
+        let mut actions = protocol.handle_timeout(now).unwrap();
+
+        // Retransmit in RENEWING until T2 expires
+        while matches!(protocol.state(), DhcpState::RenewingSent) {
+            now = deadline(&actions);
+            actions = protocol.handle_timeout(now).unwrap();
+        }
+
+        let (_, dest) = sent(&actions);
+        assert!(matches!(protocol.state(), DhcpState::RebindingSent));
+        assert_eq!(dest, Dest::Broadcast);
+
+        // Retransmit in REBINDING until the lease expires
+        while matches!(protocol.state(), DhcpState::RebindingSent) {
+            now = deadline(&actions);
+            actions = protocol.handle_timeout(now).unwrap();
+        }
+
+        assert!(matches!(protocol.state(), DhcpState::Init));
+    }
+
+    #[test]
+    fn test_interface_gone_while_bound() {
+        let mut protocol = protocol();
+        let (actions, _) = dora(&mut protocol, 3600);
+
+        // The interface is removed before the address is configured
+        let configurator = MockConfigurator::new();
+        configurator.set_interface_gone(true);
+        let err =
+            ClientError::from(configurator::apply(&configurator, "eth0", &actions[0]).unwrap_err());
+        assert!(err.is_interface_gone());
+
+        protocol.interface_gone().unwrap();
+        assert_eq!(protocol.state().to_string(), "WAITING-FOR-INTERFACE");
+        assert!(protocol.client_state.configured_address.is_none());
+
+        // Neither T1 nor late replies wake the client up
+        let t1 = deadline(&actions);
+        assert!(protocol.handle_timeout(t1).unwrap().is_empty());
+        let ack = reply(protocol.xid(), DhcpMessageType::Ack, 3600);
+        assert!(protocol.handle_message(ack, t1).unwrap().is_empty());
+        assert!(protocol.transition_to(DhcpState::Selecting).is_err());
+
+        // Once the interface is back, the client starts over
+        protocol.interface_returned().unwrap();
+        assert!(matches!(protocol.state(), DhcpState::Init));
+
+        let (actions, _) = dora(&mut protocol, 3600);
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+        assert_eq!(
+            configure(&actions),
+            [
+                ConfiguratorCall::AddAddress("eth0".into(), CLIENT),
+                ConfiguratorCall::Announce("eth0".into(), CLIENT),
+            ]
+        );
+        assert!(configurator.take().is_empty());
+    }
+
+    #[test]
+    fn test_default_times() {
+        assert_eq!(default_renewal_time(3600), 1800);
+        assert_eq!(default_rebinding_time(3600), 3150);
+        assert_eq!(default_rebinding_time(9), 7);
+
+        let lease_time = u32::MAX - 1;
+        assert_eq!(default_renewal_time(lease_time), 2_147_483_647);
+        assert_eq!(default_rebinding_time(lease_time), 3_758_096_382);
+        assert!(default_rebinding_time(u32::MAX) < u32::MAX);
+    }
+
+    #[test]
+    fn test_infinite_lease_is_never_renewed() {
+        let mut protocol = protocol();
+        let (actions, now) = dora(&mut protocol, LeaseTime::INFINITE_SECS);
+
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+        assert_eq!(
+            configure(&actions),
+            [
+                ConfiguratorCall::AddAddress("eth0".into(), CLIENT),
+                ConfiguratorCall::Announce("eth0".into(), CLIENT),
+            ]
+        );
+        assert!(!actions.iter().any(|a| matches!(a, Action::SetTimer(_))));
+
+        // Even after the "regular" T1, the client stays bound silently
+        let later = now + Duration::from_secs(LeaseTime::INFINITE_SECS as u64);
+        assert!(protocol.handle_timeout(later).unwrap().is_empty());
+        assert!(matches!(protocol.state(), DhcpState::Bound));
+    }
+}
@@ -0,0 +1,108 @@
+use std::net::Ipv4Addr;
+
+/// A default route via a router received in the Router option (3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub gateway: Ipv4Addr,
+    pub metric: u32,
+}
+
+/// Routes to add and remove to get from the installed set of routes to the
+/// wanted one.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct RouteChanges {
+    pub add: Vec<Route>,
+    pub remove: Vec<Route>,
+}
+
+/// Returns the routes for the list of routers, in order of preference. The
+/// Router option lists routers in order of preference, so the first router
+/// gets the base metric and every following one a metric one higher than
+/// its predecessor.
+pub(crate) fn routes_for(routers: &[Ipv4Addr], base_metric: u32) -> Vec<Route> {
+    routers
+        .iter()
+        .zip(base_metric..)
+        .map(|(gateway, metric)| Route {
+            gateway: *gateway,
+            metric,
+        })
+        .collect()
+}
+
+/// Computes the changes to get from `installed` to `wanted`. Routes present
+/// in both (with the same metric) are left alone.
+pub(crate) fn diff_routes(installed: &[Route], wanted: &[Route]) -> RouteChanges {
+    RouteChanges {
+        add: wanted
+            .iter()
+            .filter(|route| !installed.contains(route))
+            .copied()
+            .collect(),
+        remove: installed
+            .iter()
+            .filter(|route| !wanted.contains(route))
+            .copied()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+    const B: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+    const C: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 3);
+
+    fn route(gateway: Ipv4Addr, metric: u32) -> Route {
+        Route { gateway, metric }
+    }
+
+    #[test]
+    fn test_routes_for() {
+        assert_eq!(
+            routes_for(&[A, B, C], 100),
+            vec![route(A, 100), route(B, 101), route(C, 102)]
+        );
+        assert!(routes_for(&[], 100).is_empty());
+    }
+
+    #[test]
+    fn test_diff_initial() {
+        let changes = diff_routes(&[], &routes_for(&[A, B], 100));
+
+        assert_eq!(changes.add, vec![route(A, 100), route(B, 101)]);
+        assert!(changes.remove.is_empty());
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let routes = routes_for(&[A, B], 100);
+        assert_eq!(diff_routes(&routes, &routes), RouteChanges::default());
+    }
+
+    #[test]
+    fn test_diff_router_replaced() {
+        let changes = diff_routes(&routes_for(&[A, B], 100), &routes_for(&[A, C], 100));
+
+        assert_eq!(changes.add, vec![route(C, 101)]);
+        assert_eq!(changes.remove, vec![route(B, 101)]);
+    }
+
+    #[test]
+    fn test_diff_order_changed() {
+        let changes = diff_routes(&routes_for(&[A, B], 100), &routes_for(&[B, A], 100));
+
+        assert_eq!(changes.add, vec![route(B, 100), route(A, 101)]);
+        assert_eq!(changes.remove, vec![route(A, 100), route(B, 101)]);
+    }
+
+    #[test]
+    fn test_diff_cleanup() {
+        let changes = diff_routes(&routes_for(&[A, B], 100), &[]);
+
+        assert!(changes.add.is_empty());
+        assert_eq!(changes.remove, vec![route(A, 100), route(B, 101)]);
+    }
+}
@@ -1,5 +1,7 @@
 use std::net::Ipv4Addr;
 
+use crate::client::{dns::DnsConfig, ntp::NtpConfig, routes::Route};
+
 #[derive(Debug, Default)]
 pub struct ClientState {
     pub server_identifier: Option<Ipv4Addr>,
@@ -9,6 +11,24 @@ pub struct ClientState {
     pub renewal_time: Option<u32>,
     pub transaction_id: u32,
 
+    /// Number of DHCPDISCOVERs sent in the current cycle
+    pub discover_attempts: u32,
+
+    /// DNS configuration received with the last DHCPACK
+    pub dns_config: Option<DnsConfig>,
+
+    /// NTP configuration received with the last DHCPACK
+    pub ntp_config: Option<NtpConfig>,
+
+    /// Address configured on the interface
+    pub configured_address: Option<Ipv4Addr>,
+
+    /// Routes installed for the routers received with the last DHCPACK
+    pub installed_routes: Vec<Route>,
+
+    /// MTU set from the interface MTU option of the last DHCPACK
+    pub configured_mtu: Option<u16>,
+
     // Timers
     pub rebinding_time_left: Option<u32>,
     pub renewal_time_left: Option<u32>,
@@ -1,9 +1,12 @@
 use std::{error::Error, fmt::Display};
 
-use crate::Client;
+use tracing::debug;
 
-#[derive(Debug, Clone)]
+use crate::{client::Protocol, FSM_TARGET};
+
+#[derive(Debug, Clone, Default)]
 pub enum DhcpState {
+    #[default]
     Init,
     InitReboot,
     Selecting,
@@ -16,12 +19,10 @@ pub enum DhcpState {
     Bound,
     Renewing,
     RenewingSent,
-}
 
-impl Default for DhcpState {
-    fn default() -> Self {
-        Self::Init
-    }
+    /// The interface disappeared, the client waits for it to return. This
+    /// is not a state of RFC 2131.
+    WaitingForInterface,
 }
 
 impl Display for DhcpState {
@@ -39,6 +40,7 @@ impl Display for DhcpState {
             DhcpState::Bound => write!(f, "BOUND"),
             DhcpState::Renewing => write!(f, "RENEWING"),
             DhcpState::RenewingSent => write!(f, "RENEWING-SENT"),
+            DhcpState::WaitingForInterface => write!(f, "WAITING-FOR-INTERFACE"),
         }
     }
 }
@@ -71,9 +73,16 @@ pub trait DhcpStateMachine {
     fn transition_to(&mut self, state: DhcpState) -> Result<(), DhcpStateError>;
 }
 
-impl DhcpStateMachine for Client {
+impl DhcpStateMachine for Protocol {
     fn transition_to(&mut self, state: DhcpState) -> Result<(), DhcpStateError> {
-        match self.dhcp_state {
+        let from = self.dhcp_state.clone();
+
+        let result = match self.dhcp_state {
+            // The interface can disappear in any state
+            _ if matches!(state, DhcpState::WaitingForInterface) => {
+                self.dhcp_state = state;
+                Ok(())
+            }
             DhcpState::Init => match state {
                 next @ DhcpState::Selecting => {
                     self.dhcp_state = next;
@@ -90,6 +99,10 @@ impl DhcpStateMachine for Client {
                 _ => Err(DhcpStateError::new(self.dhcp_state.clone(), state)),
             },
             DhcpState::SelectingSent => match state {
+                next @ DhcpState::Init => {
+                    self.dhcp_state = next;
+                    Ok(())
+                }
                 next @ DhcpState::Selecting => {
                     self.dhcp_state = next;
                     Ok(())
@@ -145,6 +158,10 @@ impl DhcpStateMachine for Client {
                 _ => Err(DhcpStateError::new(self.dhcp_state.clone(), state)),
             },
             DhcpState::RebindingSent => match state {
+                next @ DhcpState::Rebinding => {
+                    self.dhcp_state = next;
+                    Ok(())
+                }
                 next @ DhcpState::Init => {
                     self.dhcp_state = next;
                     Ok(())
@@ -192,6 +209,19 @@ impl DhcpStateMachine for Client {
                 }
                 _ => Err(DhcpStateError::new(self.dhcp_state.clone(), state)),
             },
+            DhcpState::WaitingForInterface => match state {
+                next @ DhcpState::Init => {
+                    self.dhcp_state = next;
+                    Ok(())
+                }
+                _ => Err(DhcpStateError::new(self.dhcp_state.clone(), state)),
+            },
+        };
+
+        if result.is_ok() {
+            debug!(target: FSM_TARGET, %from, to = %self.dhcp_state, "state transition");
         }
+
+        result
     }
 }
@@ -0,0 +1,167 @@
+use std::{
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    os::unix::{fs::DirBuilderExt, io::AsRawFd},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/// Root of the per-interface state directories.
+pub const DEFAULT_STATE_ROOT: &str = "/var/lib/vulcan/dhcpc";
+
+/// Name of the lock file inside the state directory.
+const LOCK_FILE: &str = "lock";
+
+/// Name of the file holding the address of the last lease.
+pub(crate) const LEASE_FILE: &str = "lease";
+
+#[derive(Debug, Error)]
+pub enum StateDirError {
+    #[error("io error in state directory: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("state directory {0} is locked by another instance")]
+    Locked(PathBuf),
+}
+
+/// Directory holding the persisted state of a client, like its lease. The
+/// directory is locked for as long as the [`StateDir`] exists, so that two
+/// clients on the same interface don't corrupt each other's state.
+#[derive(Debug)]
+pub struct StateDir {
+    root: PathBuf,
+
+    /// Open lock file. The lock is released when the file is closed.
+    _lock: File,
+}
+
+impl StateDir {
+    /// Returns the default state directory of the client on `interface`.
+    pub fn default_path(interface: &str) -> PathBuf {
+        Path::new(DEFAULT_STATE_ROOT).join(interface)
+    }
+
+    /// Opens the state directory at `root` and locks it. Missing directories
+    /// are created, accessible only by the current user. Fails with
+    /// [`StateDirError::Locked`] if another instance holds the lock.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, StateDirError> {
+        let root = root.into();
+
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&root)?;
+
+        let lock = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(root.join(LOCK_FILE))?;
+
+        // Advisory lock, released by the kernel if the process dies
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+
+            return match err.kind() {
+                ErrorKind::WouldBlock => Err(StateDirError::Locked(root)),
+                _ => Err(err.into()),
+            };
+        }
+
+        Ok(Self { root, _lock: lock })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path of the file with `name` inside the directory.
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Reads the file with `name`. Returns [`None`] if it doesn't exist.
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>, StateDirError> {
+        match fs::read(self.file(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Replaces the file with `name` by `contents`. The contents are written
+    /// to a temporary file first, which then replaces the previous file. A
+    /// crash while writing leaves the previous file intact.
+    pub fn write_atomic(&self, name: &str, contents: &[u8]) -> Result<(), StateDirError> {
+        let tmp_path = self.temp_file(name);
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        fs::rename(tmp_path, self.file(name))?;
+        Ok(())
+    }
+
+    fn temp_file(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{name}.tmp"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join(format!("vulcan-statedir-{}-{}", name, std::process::id()))
+            .join("eth0");
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn test_lock_contention() {
+        let root = root("lock");
+
+        let first = StateDir::open(&root).unwrap();
+        let mode = fs::metadata(first.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // A second instance on the same interface fails fast
+        assert!(matches!(
+            StateDir::open(&root),
+            Err(StateDirError::Locked(path)) if path == root
+        ));
+
+        // The lock is released with the first instance
+        drop(first);
+        assert!(StateDir::open(&root).is_ok());
+
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_previous_file() {
+        let root = root("atomic");
+        let dir = StateDir::open(&root).unwrap();
+
+        dir.write_atomic("lease.json", b"old").unwrap();
+
+        // Crash after the temporary file was (partially) written, but before
+        // it replaced the previous file
+        fs::write(dir.temp_file("lease.json"), b"ne").unwrap();
+        assert_eq!(dir.read("lease.json").unwrap().unwrap(), b"old");
+
+        // The next write replaces the leftover temporary file
+        dir.write_atomic("lease.json", b"new").unwrap();
+        assert_eq!(dir.read("lease.json").unwrap().unwrap(), b"new");
+        assert!(!dir.temp_file("lease.json").exists());
+        assert!(dir.read("missing").unwrap().is_none());
+
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    client::DhcpState,
+    server::control::{log_filter_status, set_log_filter, ControlHandler},
+    ControlRequest, ControlRequestError, ControlResponse, LogControl,
+};
+
+/// Default path of the status socket of the client.
+pub const DEFAULT_STATUS_SOCKET: &str = "/run/vulcan/dhcpc.sock";
+
+/// Answers requests on the status socket of the client. The socket speaks
+/// the control protocol of the server, but only supports
+/// [`ControlRequest::Status`] and [`ControlRequest::SetLogFilter`].
+#[derive(Debug)]
+pub(crate) struct StatusServer {
+    interface: String,
+    state: Mutex<DhcpState>,
+    log_control: Option<Arc<LogControl>>,
+}
+
+impl StatusServer {
+    pub fn new(interface: String, log_control: Option<Arc<LogControl>>) -> Self {
+        Self {
+            state: Mutex::new(DhcpState::default()),
+            interface,
+            log_control,
+        }
+    }
+
+    /// Updates the DHCP state reported by the socket.
+    pub fn set_state(&self, state: &DhcpState) {
+        *self.state.lock().unwrap() = state.clone();
+    }
+}
+
+impl ControlHandler for StatusServer {
+    fn dispatch(&self, request: ControlRequest) -> ControlResponse {
+        match request {
+            ControlRequest::Status => {
+                let mut entries = vec![
+                    (String::from("interface"), self.interface.clone()),
+                    (
+                        String::from("state"),
+                        self.state.lock().unwrap().to_string(),
+                    ),
+                ];
+
+                entries.extend(log_filter_status(self.log_control.as_ref()));
+                ControlResponse::Status(entries)
+            }
+            ControlRequest::SetLogFilter {
+                filter,
+                revert_after,
+            } => set_log_filter(self.log_control.as_ref(), &filter, revert_after),
+            request => ControlRequestError::NotSupportedByClient(format!("{request:?}")).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UnixStream;
+
+    use super::*;
+    use crate::{
+        parse_log_filter, send_control_request, server::control::handle_control_connection,
+    };
+
+    #[tokio::test]
+    async fn test_status_socket() {
+        let log_control = Arc::new(LogControl::new(parse_log_filter("info").unwrap(), |_| {
+            Ok(())
+        }));
+
+        let server = StatusServer::new(String::from("eth0"), Some(log_control));
+        server.set_state(&DhcpState::Bound);
+
+        let (mut client, peer) = UnixStream::pair().unwrap();
+        let handle = tokio::spawn(async move { handle_control_connection(&server, peer).await });
+
+        let request = ControlRequest::SetLogFilter {
+            filter: String::from("dhcp::client=trace"),
+            revert_after: None,
+        };
+        let response = send_control_request(&mut client, &request).await.unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::LogFilter {
+                revert_in: None,
+                ..
+            }
+        ));
+
+        let response = send_control_request(&mut client, &ControlRequest::Status)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            ControlResponse::Status(vec![
+                (String::from("interface"), String::from("eth0")),
+                (String::from("state"), String::from("BOUND")),
+                (
+                    String::from("log_filter"),
+                    String::from("dhcp::client=trace")
+                ),
+            ])
+        );
+
+        // Server requests are rejected
+        let response = send_control_request(&mut client, &ControlRequest::ListLeases)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::Error(message) if message.starts_with("VULCAN-K111 ")
+        ));
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt::Debug,
+    time::{Instant, SystemTime},
+};
+
+#[cfg(any(test, feature = "testing"))]
+pub use testing::{SeededRng, StepClock};
+
+/// Source of the current time. The client and server read the time only
+/// through a clock, which allows tests and embedders to control it.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current monotonic time, used for timers and timeouts.
+    fn now_instant(&self) -> Instant;
+
+    /// Returns the current wall-clock time, used for lease expiry.
+    fn now_system(&self) -> SystemTime;
+}
+
+/// Clock which returns the time of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn now_system(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+mod testing {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant, SystemTime},
+    };
+
+    use super::Clock;
+
+    /// Clock which only moves forward when advanced explicitly. Clones share
+    /// the same time.
+    #[derive(Debug, Clone)]
+    pub struct StepClock {
+        start_instant: Instant,
+        start_system: SystemTime,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl StepClock {
+        /// Creates a clock whose wall-clock time starts at `start`.
+        pub fn new(start: SystemTime) -> Self {
+            Self {
+                start_instant: Instant::now(),
+                start_system: start,
+                elapsed: Arc::default(),
+            }
+        }
+
+        /// Returns the monotonic time the clock started at.
+        pub fn start(&self) -> Instant {
+            self.start_instant
+        }
+
+        /// Returns the time elapsed since the clock started.
+        pub fn elapsed(&self) -> Duration {
+            *self.elapsed.lock().unwrap()
+        }
+
+        /// Moves the clock forward by `duration`.
+        pub fn advance(&self, duration: Duration) {
+            *self.elapsed.lock().unwrap() += duration;
+        }
+
+        /// Moves the clock forward to `instant`. Instants in the past are
+        /// ignored.
+        pub fn advance_to(&self, instant: Instant) {
+            let mut elapsed = self.elapsed.lock().unwrap();
+            *elapsed = (*elapsed).max(instant.saturating_duration_since(self.start_instant));
+        }
+    }
+
+    impl Default for StepClock {
+        fn default() -> Self {
+            Self::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+        }
+    }
+
+    impl Clock for StepClock {
+        fn now_instant(&self) -> Instant {
+            self.start_instant + self.elapsed()
+        }
+
+        fn now_system(&self) -> SystemTime {
+            self.start_system + self.elapsed()
+        }
+    }
+
+    /// Random number generator which produces the same sequence for the same
+    /// seed.
+    #[derive(Debug, Clone)]
+    pub struct SeededRng(rand::rngs::StdRng);
+
+    impl SeededRng {
+        pub fn new(seed: u64) -> Self {
+            use rand::SeedableRng;
+            Self(rand::rngs::StdRng::seed_from_u64(seed))
+        }
+    }
+
+    impl rand::RngCore for SeededRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0.next_u32()
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0.next_u64()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.0.fill_bytes(dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.0.try_fill_bytes(dest)
+        }
+    }
+}
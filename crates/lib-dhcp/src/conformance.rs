@@ -0,0 +1,972 @@
+//! Conformance of messages with RFC 2131 Tables 3 to 5, which list the
+//! fields and options each message type MUST, MUST NOT or MAY contain. The
+//! tables are encoded as data below. Every message the [`MessageBuilder`]
+//! produces is checked against them, and [`Message::validate`] is checked
+//! to accept and reject exactly the same messages as the tables, so the two
+//! can't drift apart.
+//!
+//! Where a later RFC changed a requirement, like RFC 6842 did for the
+//! client identifier in server messages, the tables follow it. SHOULD and
+//! SHOULD NOT are treated like MAY. 'hops' and 'giaddr' of client
+//! messages are updated by relay agents and not covered.
+//!
+//! ### See
+//!
+//! RFC 2131 - Section 4.3.1 - Table 3: https://datatracker.ietf.org/doc/html/rfc2131#section-4.3.1
+//! RFC 2131 - Section 4.3.2 - Table 4: https://datatracker.ietf.org/doc/html/rfc2131#section-4.3.2
+//! RFC 2131 - Section 4.4.1 - Table 5: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4.1
+use std::{fmt, net::Ipv4Addr};
+
+use binbuf::prelude::*;
+
+use crate::{
+    builder::MessageBuilder,
+    types::{
+        options::{
+            ClassIdentifier, ClientIdentifier, DhcpMessageType, OptionOverload,
+            ParameterRequestList, RelayAgentInformation,
+        },
+        DhcpOption, HardwareAddr, Message, OpCode, OptionData, OptionTag,
+    },
+};
+
+use Column::*;
+use Expect::*;
+use Req::*;
+
+/// A column of the tables. DHCPACK and DHCPREQUEST are split into the
+/// columns the tables distinguish, see [`Column::of`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Offer,
+    AckRequest,
+    AckInform,
+    Nak,
+    Discover,
+    RequestSelecting,
+    RequestRenewing,
+    Decline,
+    Release,
+    Inform,
+}
+
+impl Column {
+    const ALL: [Column; 10] = [
+        Offer,
+        AckRequest,
+        AckInform,
+        Nak,
+        Discover,
+        RequestSelecting,
+        RequestRenewing,
+        Decline,
+        Release,
+        Inform,
+    ];
+
+    /// Returns the column of `message`, [`None`] for BOOTP messages and
+    /// message types the tables don't cover.
+    fn of(message: &Message) -> Option<Self> {
+        let zero = Ipv4Addr::UNSPECIFIED;
+
+        let column = match message.get_message_type()? {
+            DhcpMessageType::Offer => Offer,
+            DhcpMessageType::Ack if message.yiaddr != zero => AckRequest,
+            DhcpMessageType::Ack => AckInform,
+            DhcpMessageType::Nak => Nak,
+            DhcpMessageType::Discover => Discover,
+            DhcpMessageType::Request if message.ciaddr == zero => RequestSelecting,
+            DhcpMessageType::Request => RequestRenewing,
+            DhcpMessageType::Decline => Decline,
+            DhcpMessageType::Release => Release,
+            DhcpMessageType::Inform => Inform,
+            _ => return None,
+        };
+
+        Some(column)
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Offer => "DHCPOFFER",
+            AckRequest => "DHCPACK to DHCPREQUEST",
+            AckInform => "DHCPACK to DHCPINFORM",
+            Nak => "DHCPNAK",
+            Discover => "DHCPDISCOVER",
+            RequestSelecting => "DHCPREQUEST in SELECTING/INIT-REBOOT",
+            RequestRenewing => "DHCPREQUEST in RENEWING/REBINDING",
+            Decline => "DHCPDECLINE",
+            Release => "DHCPRELEASE",
+            Inform => "DHCPINFORM",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Req {
+    Must,
+    MustNot,
+    May,
+}
+
+/// An option row. A row without tag covers all options not listed in
+/// another row of the same table.
+struct OptionRow {
+    table: &'static str,
+    name: &'static str,
+    tag: Option<OptionTag>,
+    reqs: &'static [(Column, Req)],
+}
+
+/// Expected value of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Zero,
+    NonZero,
+    Is(u32),
+}
+
+struct FieldRow {
+    table: &'static str,
+    name: &'static str,
+    reqs: &'static [(Column, Expect)],
+}
+
+const TABLE_3: &str = "Table 3";
+const TABLE_4: &str = "Table 4";
+const TABLE_5: &str = "Table 5";
+
+/// Relay agents add option 82 to client messages and servers echo it, this
+/// postdates RFC 2131.
+const RFC_3046: &str = "RFC 3046";
+
+const BOOTREQUEST: u32 = 1;
+const BOOTREPLY: u32 = 2;
+
+const OPTION_ROWS: &[OptionRow] = &[
+    // Table 3: Options used by DHCP servers
+    OptionRow {
+        table: TABLE_3,
+        name: "Requested IP address",
+        tag: Some(OptionTag::RequestedIpAddr),
+        reqs: &[
+            (Offer, MustNot),
+            (AckRequest, MustNot),
+            (AckInform, MustNot),
+            (Nak, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "IP address lease time",
+        tag: Some(OptionTag::IpAddrLeaseTime),
+        reqs: &[
+            (Offer, Must),
+            (AckRequest, Must),
+            (AckInform, MustNot),
+            (Nak, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Use 'file'/'sname' fields",
+        tag: Some(OptionTag::OptionOverload),
+        reqs: &[
+            (Offer, May),
+            (AckRequest, May),
+            (AckInform, May),
+            (Nak, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "DHCP message type",
+        tag: Some(OptionTag::DhcpMessageType),
+        reqs: &[
+            (Offer, Must),
+            (AckRequest, Must),
+            (AckInform, Must),
+            (Nak, Must),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Parameter request list",
+        tag: Some(OptionTag::ParameterRequestList),
+        reqs: &[
+            (Offer, MustNot),
+            (AckRequest, MustNot),
+            (AckInform, MustNot),
+            (Nak, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Message",
+        tag: Some(OptionTag::Message),
+        reqs: &[
+            (Offer, May),
+            (AckRequest, May),
+            (AckInform, May),
+            (Nak, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Client identifier",
+        tag: Some(OptionTag::ClientIdentifier),
+        // RFC 6842 replaces the MUST NOT of RFC 2131, servers echo it
+        reqs: &[
+            (Offer, May),
+            (AckRequest, May),
+            (AckInform, May),
+            (Nak, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Vendor class identifier",
+        tag: Some(OptionTag::ClassIdentifier),
+        reqs: &[
+            (Offer, May),
+            (AckRequest, May),
+            (AckInform, May),
+            (Nak, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Server identifier",
+        tag: Some(OptionTag::ServerIdentifier),
+        reqs: &[
+            (Offer, Must),
+            (AckRequest, Must),
+            (AckInform, Must),
+            (Nak, Must),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "Maximum message size",
+        tag: Some(OptionTag::MaxDhcpMessageSize),
+        reqs: &[
+            (Offer, MustNot),
+            (AckRequest, MustNot),
+            (AckInform, MustNot),
+            (Nak, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_3,
+        name: "All others",
+        tag: None,
+        reqs: &[
+            (Offer, May),
+            (AckRequest, May),
+            (AckInform, May),
+            (Nak, MustNot),
+        ],
+    },
+    // Table 4: Client messages from different states
+    OptionRow {
+        table: TABLE_4,
+        name: "requested-ip",
+        tag: Some(OptionTag::RequestedIpAddr),
+        reqs: &[(RequestSelecting, Must), (RequestRenewing, MustNot)],
+    },
+    OptionRow {
+        table: TABLE_4,
+        name: "server-ip",
+        // MUST in SELECTING, MUST NOT in INIT-REBOOT
+        tag: Some(OptionTag::ServerIdentifier),
+        reqs: &[(RequestSelecting, May), (RequestRenewing, MustNot)],
+    },
+    // Table 5: Options used by DHCP clients
+    OptionRow {
+        table: TABLE_5,
+        name: "Requested IP address",
+        tag: Some(OptionTag::RequestedIpAddr),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, Must),
+            (RequestRenewing, MustNot),
+            (Decline, Must),
+            (Release, MustNot),
+            (Inform, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "IP address lease time",
+        tag: Some(OptionTag::IpAddrLeaseTime),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, MustNot),
+            (Release, MustNot),
+            (Inform, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Use 'file'/'sname' fields",
+        tag: Some(OptionTag::OptionOverload),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, MustNot),
+            (Release, MustNot),
+            (Inform, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "DHCP message type",
+        tag: Some(OptionTag::DhcpMessageType),
+        reqs: &[
+            (Discover, Must),
+            (RequestSelecting, Must),
+            (RequestRenewing, Must),
+            (Decline, Must),
+            (Release, Must),
+            (Inform, Must),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Client identifier",
+        tag: Some(OptionTag::ClientIdentifier),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, May),
+            (Release, May),
+            (Inform, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Vendor class identifier",
+        tag: Some(OptionTag::ClassIdentifier),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, MustNot),
+            (Release, MustNot),
+            (Inform, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Server identifier",
+        tag: Some(OptionTag::ServerIdentifier),
+        reqs: &[
+            (Discover, MustNot),
+            // MUST after SELECTING, MUST NOT after INIT-REBOOT
+            (RequestSelecting, May),
+            (RequestRenewing, MustNot),
+            (Decline, Must),
+            (Release, Must),
+            (Inform, MustNot),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Parameter request list",
+        tag: Some(OptionTag::ParameterRequestList),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, MustNot),
+            (Release, MustNot),
+            (Inform, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Maximum message size",
+        tag: Some(OptionTag::MaxDhcpMessageSize),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, MustNot),
+            (Release, MustNot),
+            (Inform, May),
+        ],
+    },
+    OptionRow {
+        table: TABLE_5,
+        name: "Message",
+        tag: Some(OptionTag::Message),
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, May),
+            (Release, May),
+            (Inform, May),
+        ],
+    },
+    // Site-specific options follow the same rules as all others
+    OptionRow {
+        table: TABLE_5,
+        name: "All others",
+        tag: None,
+        reqs: &[
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, MustNot),
+            (Release, MustNot),
+            (Inform, May),
+        ],
+    },
+    OptionRow {
+        table: RFC_3046,
+        name: "Relay agent information",
+        tag: Some(OptionTag::RelayAgentInformation),
+        reqs: &[
+            (Offer, May),
+            (AckRequest, May),
+            (AckInform, May),
+            (Nak, May),
+            (Discover, May),
+            (RequestSelecting, May),
+            (RequestRenewing, May),
+            (Decline, May),
+            (Release, May),
+            (Inform, May),
+        ],
+    },
+];
+
+/// Field rows. 'yiaddr' of DHCPACK and 'ciaddr' of DHCPREQUEST select the
+/// column and have no row.
+const FIELD_ROWS: &[FieldRow] = &[
+    // Table 3: Fields used by DHCP servers
+    FieldRow {
+        table: TABLE_3,
+        name: "op",
+        reqs: &[
+            (Offer, Is(BOOTREPLY)),
+            (AckRequest, Is(BOOTREPLY)),
+            (AckInform, Is(BOOTREPLY)),
+            (Nak, Is(BOOTREPLY)),
+        ],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "hops",
+        reqs: &[
+            (Offer, Zero),
+            (AckRequest, Zero),
+            (AckInform, Zero),
+            (Nak, Zero),
+        ],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "secs",
+        reqs: &[
+            (Offer, Zero),
+            (AckRequest, Zero),
+            (AckInform, Zero),
+            (Nak, Zero),
+        ],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "ciaddr",
+        reqs: &[(Offer, Zero), (Nak, Zero)],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "yiaddr",
+        reqs: &[(Offer, NonZero), (Nak, Zero)],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "siaddr",
+        reqs: &[(Nak, Zero)],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "sname",
+        reqs: &[(Nak, Zero)],
+    },
+    FieldRow {
+        table: TABLE_3,
+        name: "file",
+        reqs: &[(Nak, Zero)],
+    },
+    // Table 5: Fields used by DHCP clients
+    FieldRow {
+        table: TABLE_5,
+        name: "op",
+        reqs: &[
+            (Discover, Is(BOOTREQUEST)),
+            (RequestSelecting, Is(BOOTREQUEST)),
+            (RequestRenewing, Is(BOOTREQUEST)),
+            (Decline, Is(BOOTREQUEST)),
+            (Release, Is(BOOTREQUEST)),
+            (Inform, Is(BOOTREQUEST)),
+        ],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "secs",
+        reqs: &[(Decline, Zero), (Release, Zero)],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "flags",
+        reqs: &[(Decline, Zero), (Release, Zero)],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "ciaddr",
+        reqs: &[
+            (Discover, Zero),
+            (Decline, Zero),
+            (Release, NonZero),
+            (Inform, NonZero),
+        ],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "yiaddr",
+        reqs: &[
+            (Discover, Zero),
+            (RequestSelecting, Zero),
+            (RequestRenewing, Zero),
+            (Decline, Zero),
+            (Release, Zero),
+            (Inform, Zero),
+        ],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "siaddr",
+        reqs: &[
+            (Discover, Zero),
+            (RequestSelecting, Zero),
+            (RequestRenewing, Zero),
+            (Decline, Zero),
+            (Release, Zero),
+            (Inform, Zero),
+        ],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "sname",
+        reqs: &[(Decline, Zero), (Release, Zero)],
+    },
+    FieldRow {
+        table: TABLE_5,
+        name: "file",
+        reqs: &[(Decline, Zero), (Release, Zero)],
+    },
+];
+
+/// Returns the value of the field `name`. 'sname' and 'file' are counted as
+/// the number of non-zero octets.
+fn field_value(message: &Message, name: &str) -> u32 {
+    let used = |field: &[u8]| field.iter().filter(|b| **b != 0).count() as u32;
+
+    match name {
+        "op" => match message.header.opcode {
+            OpCode::BootRequest => BOOTREQUEST,
+            OpCode::BootReply => BOOTREPLY,
+        },
+        "hops" => message.header.hops as u32,
+        "secs" => message.header.secs as u32,
+        "flags" => message.header.flags as u32,
+        "ciaddr" => u32::from(message.ciaddr),
+        "yiaddr" => u32::from(message.yiaddr),
+        "siaddr" => u32::from(message.siaddr),
+        "sname" => used(&message.sname),
+        "file" => used(&message.file),
+        _ => unreachable!("unknown field {}", name),
+    }
+}
+
+fn is_listed(table: &str, tag: &OptionTag) -> bool {
+    OPTION_ROWS
+        .iter()
+        .any(|row| row.table == table && row.tag.as_ref() == Some(tag))
+}
+
+/// Returns the requirement of `row` for `column`, [`None`] if the row
+/// doesn't cover the column.
+fn req<T: Copy>(reqs: &[(Column, T)], column: Column) -> Option<T> {
+    reqs.iter().find(|(c, _)| *c == column).map(|(_, req)| *req)
+}
+
+/// Checks `message` against the tables and returns a description of every
+/// violated row.
+fn violations(message: &Message) -> Vec<String> {
+    let column = match Column::of(message) {
+        Some(column) => column,
+        None => return Vec::new(),
+    };
+
+    let mut violations = Vec::new();
+
+    for row in OPTION_ROWS {
+        let req = match req(row.reqs, column) {
+            Some(req) => req,
+            None => continue,
+        };
+
+        let present = match &row.tag {
+            Some(tag) => message.get_option(tag.clone()).is_some(),
+            None => message.options.iter().any(|option| {
+                let tag = &option.header().tag;
+                !matches!(tag, OptionTag::Pad | OptionTag::End)
+                    && !is_listed(row.table, tag)
+                    && !is_listed(RFC_3046, tag)
+            }),
+        };
+
+        let violated = match req {
+            Must => !present,
+            MustNot => present,
+            May => false,
+        };
+
+        if violated {
+            violations.push(format!(
+                "{} '{}': {:?} in {}",
+                row.table, row.name, req, column
+            ));
+        }
+    }
+
+    for row in FIELD_ROWS {
+        let expect = match req(row.reqs, column) {
+            Some(expect) => expect,
+            None => continue,
+        };
+
+        let value = field_value(message, row.name);
+        let violated = match expect {
+            Zero => value != 0,
+            NonZero => value == 0,
+            Is(expected) => value != expected,
+        };
+
+        if violated {
+            violations.push(format!(
+                "{} '{}': expected {:?} in {}, got {}",
+                row.table, row.name, expect, column, value
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Panics with the violated rows if `message` doesn't conform to the tables.
+pub(crate) fn assert_conforms(message: &Message) {
+    let violations = violations(message);
+    assert!(
+        violations.is_empty(),
+        "{:?} violates RFC 2131:\n{}",
+        message.get_message_type(),
+        violations.join("\n")
+    );
+}
+
+fn hardware_addr() -> HardwareAddr {
+    HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap()
+}
+
+/// Returns an option with tag `tag` and some valid data.
+fn sample_option(tag: &OptionTag) -> DhcpOption {
+    let data = match tag {
+        OptionTag::RequestedIpAddr => OptionData::RequestedIpAddr(Ipv4Addr::new(192, 168, 1, 100)),
+        OptionTag::IpAddrLeaseTime => OptionData::IpAddrLeaseTime(3600),
+        OptionTag::OptionOverload => OptionData::OptionOverload(OptionOverload::File),
+        OptionTag::ServerIdentifier => OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)),
+        OptionTag::ParameterRequestList => {
+            OptionData::ParameterRequestList(ParameterRequestList::new(vec![OptionTag::Router]))
+        }
+        OptionTag::Message => OptionData::Message(String::from("conformance")),
+        OptionTag::MaxDhcpMessageSize => OptionData::MaxDhcpMessageSize(1500),
+        OptionTag::ClassIdentifier => {
+            let mut buf = ReadBuffer::new(b"vendor");
+            OptionData::ClassIdentifier(ClassIdentifier::read::<BigEndian>(&mut buf, 6).unwrap())
+        }
+        OptionTag::ClientIdentifier => {
+            OptionData::ClientIdentifier(ClientIdentifier::new(0, b"client".to_vec()))
+        }
+        OptionTag::RelayAgentInformation => OptionData::RelayAgentInformation(
+            RelayAgentInformation::new(vec![(1, b"port1".to_vec())]),
+        ),
+        OptionTag::HostName => OptionData::HostName(String::from("host")),
+        _ => unreachable!("no sample for option {}", tag),
+    };
+
+    DhcpOption::new(tag.clone(), data)
+}
+
+/// Returns `message` with `option` inserted before the End option.
+fn with_option(message: &Message, option: DhcpOption) -> Message {
+    let mut message = message.clone();
+    let end = message
+        .options
+        .iter()
+        .position(|o| o.header().tag == OptionTag::End)
+        .unwrap_or(message.options.len());
+
+    message.options.insert(end, option);
+    message
+}
+
+fn without_option(message: &Message, tag: &OptionTag) -> Message {
+    let mut message = message.clone();
+    message.options.retain(|o| &o.header().tag != tag);
+    message
+}
+
+fn inform() -> Message {
+    let mut message = Message::new_with_xid(0xcafe);
+    message.ciaddr = Ipv4Addr::new(192, 168, 1, 50);
+    message.set_hardware_address(hardware_addr());
+    message
+        .add_option_parts(
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Inform),
+        )
+        .unwrap();
+    message.end().unwrap();
+    message
+}
+
+/// Returns every message the builder produces, including replies to its
+/// own requests.
+fn builder_messages() -> Vec<Message> {
+    let addr = Ipv4Addr::new(192, 168, 1, 100);
+    let server_id = Ipv4Addr::new(192, 168, 1, 1);
+    let options = || {
+        vec![
+            DhcpOption::new(OptionTag::Router, OptionData::Router(vec![server_id])),
+            DhcpOption::new(OptionTag::RenewalT1Time, OptionData::RenewalT1Time(1800)),
+        ]
+    };
+
+    let mut client = MessageBuilder::new(hardware_addr(), None, 1500).with_option_order(vec![
+        OptionTag::ClientIdentifier,
+        OptionTag::DhcpMessageType,
+    ]);
+    let server = MessageBuilder::new(hardware_addr(), None, 1500)
+        .with_next_server(server_id)
+        .with_boot_file(String::from("pxelinux.0"));
+
+    let discover = client.make_discover_message(1, None, None).unwrap();
+    let request = client
+        .make_request_message(2, server_id, addr, 3600)
+        .unwrap();
+    let renewing = client.make_renewing_message(3, addr, 3600).unwrap();
+    let inform = inform();
+
+    let mut messages = vec![
+        client
+            .make_discover_message(4, Some(addr), Some(3600))
+            .unwrap(),
+        client.make_release_message(5, addr, server_id).unwrap(),
+        client.make_decline_message(6, addr, server_id).unwrap(),
+        server
+            .make_offer_message(&discover, addr, server_id, 3600, options())
+            .unwrap(),
+        server
+            .make_ack_message(&request, addr, server_id, 3600, options())
+            .unwrap(),
+        server
+            .make_ack_message(&renewing, addr, server_id, 3600, options())
+            .unwrap(),
+        server
+            .make_inform_ack_message(&inform, server_id, options())
+            .unwrap(),
+        server.make_nak_message(&request, server_id).unwrap(),
+        server.make_nak_message(&renewing, server_id).unwrap(),
+    ];
+
+    // RFC 4388 messages aren't covered by the tables, but have to validate
+    for ty in [
+        DhcpMessageType::LeaseUnassigned,
+        DhcpMessageType::LeaseUnknown,
+        DhcpMessageType::LeaseActive,
+    ] {
+        let binding = (ty == DhcpMessageType::LeaseActive).then(|| (addr, hardware_addr()));
+        messages.push(
+            server
+                .make_lease_query_reply(&request, ty, server_id, binding, Vec::new())
+                .unwrap(),
+        );
+    }
+
+    messages.extend([discover, request, renewing, inform]);
+    messages
+}
+
+/// Returns a message conforming to the tables for every column.
+fn base_messages() -> Vec<(Column, Message)> {
+    let messages: Vec<_> = builder_messages()
+        .into_iter()
+        .filter_map(|message| Column::of(&message).map(|column| (column, message)))
+        .collect();
+
+    for column in Column::ALL {
+        assert!(
+            messages.iter().any(|(c, _)| *c == column),
+            "no message for column {}",
+            column
+        );
+    }
+
+    messages
+}
+
+/// Returns copies of `message` changed so they violate or flip a single
+/// row each, with the name of the row.
+fn mutations(column: Column, message: &Message) -> Vec<(String, Message)> {
+    let mut mutations = Vec::new();
+
+    for row in OPTION_ROWS {
+        let req = match req(row.reqs, column) {
+            Some(req) => req,
+            None => continue,
+        };
+
+        // Without message type, the message is a BOOTP message
+        let tag = match &row.tag {
+            Some(OptionTag::DhcpMessageType) => continue,
+            Some(tag) => tag.clone(),
+            None => OptionTag::HostName,
+        };
+
+        let mutated = match (req, message.get_option(tag.clone()).is_some()) {
+            (Must, _) | (May, true) => without_option(message, &tag),
+            (MustNot, _) | (May, false) => with_option(message, sample_option(&tag)),
+        };
+
+        mutations.push((format!("{} '{}'", row.table, row.name), mutated));
+    }
+
+    for row in FIELD_ROWS {
+        let expect = match req(row.reqs, column) {
+            Some(expect) => expect,
+            None => continue,
+        };
+
+        let mut mutated = message.clone();
+        let violating = Ipv4Addr::new(10, 0, 0, 1);
+
+        match (row.name, expect) {
+            ("op", _) => {
+                mutated.header.opcode = match mutated.header.opcode {
+                    OpCode::BootRequest => OpCode::BootReply,
+                    OpCode::BootReply => OpCode::BootRequest,
+                }
+            }
+            ("hops", _) => mutated.header.hops = 1,
+            ("secs", _) => mutated.header.secs = 1,
+            ("flags", _) => mutated.set_is_broadcast(true),
+            ("ciaddr", Zero) => mutated.ciaddr = violating,
+            ("ciaddr", _) => mutated.ciaddr = Ipv4Addr::UNSPECIFIED,
+            ("yiaddr", Zero) => mutated.yiaddr = violating,
+            ("yiaddr", _) => mutated.yiaddr = Ipv4Addr::UNSPECIFIED,
+            ("siaddr", _) => mutated.siaddr = violating,
+            ("sname", _) => mutated.sname[0] = b'x',
+            ("file", _) => mutated.file[0] = b'x',
+            (name, _) => unreachable!("unknown field {}", name),
+        }
+
+        mutations.push((format!("{} '{}'", row.table, row.name), mutated));
+    }
+
+    mutations
+}
+
+#[test]
+fn test_builder_messages_conform() {
+    for message in builder_messages() {
+        assert_conforms(&message);
+
+        if let Err(err) = message.validate() {
+            panic!("{:?} fails validation: {}", message.get_message_type(), err);
+        }
+
+        // Conformance has to survive encoding
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        let decoded = Message::read::<BigEndian>(&mut ReadBuffer::new(buf.bytes())).unwrap();
+        assert_conforms(&decoded);
+    }
+}
+
+#[test]
+fn test_validate_agrees_with_tables() {
+    for (column, message) in base_messages() {
+        for (row, mutated) in mutations(column, &message) {
+            let violations = violations(&mutated);
+            let validated = mutated.validate();
+
+            assert_eq!(
+                validated.is_ok(),
+                violations.is_empty(),
+                "{} mutated for {}: validate returned {:?}, tables found {:?}",
+                column,
+                row,
+                validated,
+                violations
+            );
+
+            // Every MUST and MUST NOT row is reported by name
+            if !violations.is_empty() {
+                assert!(
+                    violations.iter().any(|v| v.starts_with(&row)),
+                    "{} mutated for {}: violations {:?} don't name the row",
+                    column,
+                    row,
+                    violations
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_violations_name_row() {
+    let server_id = Ipv4Addr::new(192, 168, 1, 1);
+    let request = MessageBuilder::new(hardware_addr(), None, 1500)
+        .make_request_message(1, server_id, Ipv4Addr::new(192, 168, 1, 100), 3600)
+        .unwrap();
+    let nak = MessageBuilder::new(hardware_addr(), None, 1500)
+        .make_nak_message(&request, server_id)
+        .unwrap();
+
+    let nak = without_option(&nak, &OptionTag::ServerIdentifier);
+    assert_eq!(
+        violations(&nak),
+        vec!["Table 3 'Server identifier': Must in DHCPNAK"]
+    );
+    assert!(matches!(
+        nak.validate(),
+        Err(crate::types::MessageError::MissingOption(
+            DhcpMessageType::Nak,
+            OptionTag::ServerIdentifier
+        ))
+    ));
+}
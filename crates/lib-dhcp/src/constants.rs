@@ -4,11 +4,33 @@ pub const MIN_MSG_SIZE: usize = 300;
 pub const SERVER_PORT: u16 = 67;
 pub const CLIENT_PORT: u16 = 68;
 
+/// Lengths of the 'sname' and 'file' fields in octets.
+pub const SNAME_LEN: usize = 64;
+pub const FILE_LEN: usize = 128;
+
 pub const MAGIC_COOKIE_ARR: [u8; 4] = [99, 130, 83, 99];
 pub const MAGIC_COOKIE: u32 = 1_669_485_411;
 
 pub const MINIMAL_RETRANS_DURATION_SECS: u32 = 60;
 
+/// Upper bound of the DHCPDISCOVER retransmission backoff (RFC 2131 Section
+/// 4.1).
+pub const MAX_DISCOVER_BACKOFF_SECS: u64 = 64;
+
+/// Bounds of the random delay before the first DHCPDISCOVER (RFC 2131
+/// Section 4.4.1).
+pub const DEFAULT_INITIAL_DELAY_MIN_SECS: u64 = 1;
+pub const DEFAULT_INITIAL_DELAY_MAX_SECS: u64 = 10;
+
+/// Upper bound of the additional wait in INIT after consecutive cycles
+/// without a lease.
+pub const DEFAULT_MAX_INIT_BACKOFF_SECS: u64 = 64;
+
+/// Largest 'hops' count of a relayed message. Relay agents discard
+/// messages exceeding it, the server does the same (RFC 1542 Section
+/// 4.1.1).
+pub const MAX_HOPS: u8 = 16;
+
 pub const HARDWARE_ADDR_TYPE_ETHERNET: u8 = 1;
 pub const HARDWARE_ADDR_LEN_ETHERNET: u8 = 6;
 
@@ -16,3 +38,7 @@ pub const ONE_HOUR_SECS: u32 = 3600;
 
 pub const DEFAULT_REBIND_PERCENT: f64 = 0.875;
 pub const DEFAULT_RENEW_PERCENT: f64 = 0.5;
+
+/// Metric of the route via the first router, following routers get
+/// increasing metrics.
+pub const DEFAULT_ROUTE_METRIC: u32 = 100;
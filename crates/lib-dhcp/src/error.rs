@@ -5,3 +5,213 @@ pub enum ProtocolError {
     #[error("Invalid opcode ({0})")]
     InvalidOpCode(u8),
 }
+
+/// Assigns stable error codes to the variants of an error enum. Generates a
+/// `code()` method and a `CODES` table listing every variant with its code.
+/// This table is the only place the codes are written down. The Display
+/// output of each variant starts with the code, so the `#[error]` attribute
+/// of each variant has the form `#[error("{code} ...", code = self.code())]`.
+///
+/// Codes are of the form `VULCAN-<area><number>`, where the area is `C` for
+/// the client, `S` for the server, `P` for message parsing, `D` for lease
+/// storage and `K` for the control socket. Codes must never be reused or changed once released.
+macro_rules! error_codes {
+    ($ty:ident { $($variant:ident => $code:literal,)+ }) => {
+        impl $ty {
+            /// All variants of this error with their error code.
+            pub const CODES: &'static [(&'static str, &'static str)] =
+                &[$((stringify!($variant), $code),)+];
+
+            /// Returns the stable error code of this error, which support
+            /// tooling can grep for in logs.
+            pub fn code(&self) -> &'static str {
+                match self {
+                    $(Self::$variant { .. } => $code,)+
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        types::MessageError, types::OptionError, ClientError, ControlRequestError, ServerError,
+        StorageError,
+    };
+
+    fn table() -> Vec<(&'static str, &'static str, &'static str)> {
+        let tables = [
+            ("ClientError", ClientError::CODES),
+            ("ServerError", ServerError::CODES),
+            ("MessageError", MessageError::CODES),
+            ("OptionError", OptionError::CODES),
+            ("StorageError", StorageError::CODES),
+            ("ControlRequestError", ControlRequestError::CODES),
+        ];
+
+        tables
+            .into_iter()
+            .flat_map(|(ty, codes)| {
+                codes
+                    .iter()
+                    .map(move |(variant, code)| (ty, *variant, *code))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_error_codes_unique() {
+        let mut seen = HashSet::new();
+
+        for (ty, variant, code) in table() {
+            assert!(seen.insert(code), "{code} of {ty}::{variant} is used twice");
+        }
+    }
+
+    #[test]
+    fn test_error_codes_snapshot() {
+        let snapshot: Vec<String> = table()
+            .into_iter()
+            .map(|(ty, variant, code)| format!("{code} {ty}::{variant}"))
+            .collect();
+
+        // Codes are part of the public interface, never change existing
+        // entries. New variants get a new code appended here.
+        assert_eq!(
+            snapshot,
+            vec![
+                "VULCAN-C101 ClientError::BindTimeout",
+                "VULCAN-C102 ClientError::IO",
+                "VULCAN-C103 ClientError::InterfaceError",
+                "VULCAN-C104 ClientError::NoInterfaceFound",
+                "VULCAN-C105 ClientError::ParseHardwareAddrError",
+                "VULCAN-C106 ClientError::NoHardwareAddressError",
+                "VULCAN-C107 ClientError::DhcpStateError",
+                "VULCAN-C108 ClientError::MessageError",
+                "VULCAN-C109 ClientError::TransportError",
+                "VULCAN-C110 ClientError::CmdError",
+                "VULCAN-C111 ClientError::Invalid",
+                "VULCAN-C112 ClientError::HardwareAddrLength",
+                "VULCAN-C113 ClientError::StateDirError",
+                "VULCAN-C114 ClientError::ConfigureError",
+                "VULCAN-C115 ClientError::InterfaceGone",
+                "VULCAN-C116 ClientError::MtuTooSmall",
+                "VULCAN-C117 ClientError::InvalidConfig",
+                "VULCAN-S101 ServerError::AlreadyRunning",
+                "VULCAN-S102 ServerError::ServerBuilderError",
+                "VULCAN-S103 ServerError::Io",
+                "VULCAN-S104 ServerError::StorageError",
+                "VULCAN-P101 MessageError::HeaderError",
+                "VULCAN-P102 MessageError::OptionError",
+                "VULCAN-P103 MessageError::BufferError",
+                "VULCAN-P104 MessageError::DuplicateOptionError",
+                "VULCAN-P106 MessageError::HardwareAddrLength",
+                "VULCAN-P107 MessageError::InconsistentClientIdentifier",
+                "VULCAN-P108 MessageError::MissingOption",
+                "VULCAN-P109 MessageError::ForbiddenOption",
+                "VULCAN-P110 MessageError::InvalidField",
+                "VULCAN-P111 MessageError::OptionLength",
+                "VULCAN-P112 MessageError::TruncatedOption",
+                "VULCAN-P113 MessageError::OptionsTooLarge",
+                "VULCAN-P114 MessageError::InvalidHex",
+                "VULCAN-P201 OptionError::OptionHeaderError",
+                "VULCAN-P202 OptionError::OptionDataError",
+                "VULCAN-P203 OptionError::BufferError",
+                "VULCAN-D101 StorageError::RetrieveError",
+                "VULCAN-D102 StorageError::StoreError",
+                "VULCAN-D103 StorageError::Unknown",
+                "VULCAN-K101 ControlRequestError::Protocol",
+                "VULCAN-K102 ControlRequestError::OutdatedPeer",
+                "VULCAN-K103 ControlRequestError::LogFilterNotTunable",
+                "VULCAN-K104 ControlRequestError::LogFilter",
+                "VULCAN-K105 ControlRequestError::ReloadNotSupported",
+                "VULCAN-K106 ControlRequestError::Reload",
+                "VULCAN-K107 ControlRequestError::Reservation",
+                "VULCAN-K108 ControlRequestError::Persist",
+                "VULCAN-K109 ControlRequestError::Config",
+                "VULCAN-K110 ControlRequestError::UnknownPool",
+                "VULCAN-K111 ControlRequestError::NotSupportedByClient",
+            ]
+        );
+    }
+
+    /// Sources of all enums with error codes, by enum name.
+    const SOURCES: [(&str, &str); 8] = [
+        ("ClientError", include_str!("client/error.rs")),
+        ("ServerError", include_str!("server/mod.rs")),
+        ("MessageError", include_str!("types/message.rs")),
+        ("OptionError", include_str!("types/option/mod.rs")),
+        ("StorageError", include_str!("storage/mod.rs")),
+        ("ClientError", include_str!("v6/client.rs")),
+        ("MessageError", include_str!("v6/message.rs")),
+        ("ControlRequestError", include_str!("server/control.rs")),
+    ];
+
+    /// Returns the lines of the block which starts with the line `start`.
+    fn block<'a>(source: &'a str, start: &str) -> Vec<&'a str> {
+        source
+            .lines()
+            .skip_while(|line| *line != start)
+            .skip(1)
+            .take_while(|line| !line.starts_with('}'))
+            .collect()
+    }
+
+    #[test]
+    fn test_display_starts_with_code() {
+        for (ty, source) in SOURCES {
+            let mut codes: Vec<_> = block(source, &format!("error_codes!({ty} {{"))
+                .iter()
+                .map(|line| line.trim().split_once(" => ").unwrap().0)
+                .collect();
+
+            // Each variant has an error attribute, which is followed by the
+            // variant after optional doc comments
+            let mut attribute = None;
+            let mut variants = Vec::new();
+
+            for line in block(source, &format!("pub enum {ty} {{")) {
+                let line = line.trim();
+
+                if line.starts_with("#[error(") {
+                    attribute = Some(line);
+                } else if line.starts_with(char::is_uppercase) {
+                    let variant = line
+                        .split(|c: char| !c.is_alphanumeric() && c != '_')
+                        .next()
+                        .unwrap();
+                    let attribute = attribute.take().unwrap_or_default();
+
+                    assert!(
+                        attribute.starts_with("#[error(\"{code} ")
+                            && attribute.ends_with(", code = self.code())]"),
+                        "{ty}::{variant} doesn't start with its code: {attribute}"
+                    );
+                    variants.push(variant);
+                }
+            }
+
+            variants.sort_unstable();
+            codes.sort_unstable();
+            assert_eq!(variants, codes, "variants of {ty}");
+        }
+
+        // The code is rendered from the table
+        let err = ClientError::BindTimeout(std::time::Duration::from_secs(1));
+        assert_eq!(
+            err.to_string(),
+            "VULCAN-C101 Bind error: Failed to create and bind UDP socket after 1s"
+        );
+        assert_eq!(
+            ServerError::AlreadyRunning
+                .to_string()
+                .split_once(' ')
+                .unwrap()
+                .0,
+            "VULCAN-S101"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Interoperability corpus of client messages, see `tests/interop/`. Each
+//! file holds a single frame as hex dump, preceded by `key: value` lines
+//! listing what the typed accessors must return for it. Lines starting with
+//! `#` are comments. Adding a capture only requires adding a file.
+//!
+//! The frames reproduce the options, their order and the padding of the
+//! clients named in the comments. MAC addresses are replaced by locally
+//! administered ones.
+//!
+//! Every capture has to parse, pass validation and match its expectations.
+//! The server tests additionally check the replies to them against the
+//! conformance tables.
+
+use std::fs;
+
+use binbuf::prelude::*;
+
+use crate::types::{options::DhcpMessageType, Message};
+
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/interop");
+
+/// A captured client message together with its expectations.
+pub(crate) struct Capture {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    expectations: Vec<(String, String)>,
+}
+
+impl Capture {
+    /// Returns the client which sent the capture, the file name up to the
+    /// last dash. Captures of the same client belong to one exchange.
+    pub fn client(&self) -> &str {
+        self.name
+            .rsplit_once('-')
+            .map_or(&self.name, |(client, _)| client)
+    }
+
+    pub fn message(&self) -> Message {
+        Message::read::<BigEndian>(&mut ReadBuffer::new(&self.bytes))
+            .unwrap_or_else(|err| panic!("{}: failed to parse: {}", self.name, err))
+    }
+
+    fn parse(name: String, content: &str) -> Self {
+        let mut bytes = Vec::new();
+        let mut expectations = Vec::new();
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once(':') {
+                expectations.push((key.trim().to_string(), value.trim().to_string()));
+                continue;
+            }
+
+            for byte in line.split_whitespace() {
+                let byte = u8::from_str_radix(byte, 16)
+                    .unwrap_or_else(|_| panic!("{name}: invalid hex '{byte}'"));
+                bytes.push(byte);
+            }
+        }
+
+        Self {
+            name,
+            bytes,
+            expectations,
+        }
+    }
+}
+
+/// Loads all captures of the corpus, ordered by file name.
+pub(crate) fn captures() -> Vec<Capture> {
+    let mut paths: Vec<_> = fs::read_dir(CORPUS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy();
+            Capture::parse(name.into_owned(), &fs::read_to_string(path).unwrap())
+        })
+        .collect()
+}
+
+/// Returns the value of `key` in `message` in the notation of the corpus
+/// files.
+fn actual(message: &Message, key: &str) -> String {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(":")
+    };
+    let none = || String::from("none");
+
+    match key {
+        "type" => message.get_message_type().map_or_else(none, |ty| {
+            let name = match ty {
+                DhcpMessageType::Discover => "discover",
+                DhcpMessageType::Request => "request",
+                DhcpMessageType::Decline => "decline",
+                DhcpMessageType::Release => "release",
+                DhcpMessageType::Inform => "inform",
+                _ => "other",
+            };
+            name.to_string()
+        }),
+        "hostname" => message.get_host_name().cloned().unwrap_or_else(none),
+        "client-id" => message
+            .get_client_identifier()
+            .map_or_else(none, |id| hex(&[&[id.ty()], id.identifier()].concat())),
+        "class-id" => message
+            .get_class_identifier()
+            .map_or_else(none, |class| class.as_str().to_string()),
+        "requested-ip" => message
+            .get_requested_ip_addr()
+            .map_or_else(none, |ip| ip.to_string()),
+        "server-id" => message
+            .get_server_identifier()
+            .map_or_else(none, |ip| ip.to_string()),
+        "max-size" => message
+            .get_max_message_size()
+            .map_or_else(none, |size| size.to_string()),
+        "fqdn" => message
+            .get_client_fqdn()
+            .map_or_else(none, |fqdn| fqdn.name().to_string()),
+        "prl" => message
+            .get_parameter_request_list()
+            .map_or_else(none, |list| {
+                let codes: Vec<_> = list
+                    .tags()
+                    .iter()
+                    .map(|t| u8::from(t).to_string())
+                    .collect();
+                codes.join(" ")
+            }),
+        _ => panic!("unknown expectation '{key}'"),
+    }
+}
+
+#[test]
+fn test_interop_captures() {
+    let captures = captures();
+    assert!(!captures.is_empty());
+
+    for capture in &captures {
+        let message = capture.message();
+
+        if let Err(err) = message.validate() {
+            panic!("{}: failed validation: {}", capture.name, err);
+        }
+
+        for (key, expected) in &capture.expectations {
+            assert_eq!(
+                &actual(&message, key),
+                expected,
+                "{}: unexpected {}",
+                capture.name,
+                key
+            );
+        }
+    }
+}
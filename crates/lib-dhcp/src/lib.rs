@@ -1,16 +1,96 @@
+#[macro_use]
+mod error;
+#[macro_use]
+mod macros;
+
 pub mod types;
 
+mod bounded;
 mod builder;
 mod client;
+mod clock;
+#[cfg(test)]
+mod conformance;
 mod constants;
-mod error;
+#[cfg(test)]
+mod interop;
+mod logging;
+mod net;
 mod server;
 mod storage;
+mod transaction;
+mod transport;
 mod utils;
 
-pub use client::*;
-pub use constants::*;
-pub use error::*;
-pub use server::*;
-pub use storage::*;
-pub use utils::*;
+#[cfg(feature = "dhcpv6")]
+pub mod v6;
+
+/// The types most users of the crate need, `use dhcp::prelude::*`.
+pub mod prelude {
+    pub use crate::client::{Client, ClientBuilder};
+    pub use crate::server::{Server, ServerBuilder};
+    pub use crate::types::{DhcpOption, Lease, Message, OptionData, OptionTag};
+}
+
+// The public API of the crate. Changes to these lists are checked against
+// the snapshot in tests/public-api.txt, so they have to be deliberate.
+
+pub use builder::MessageBuilder;
+pub use client::{
+    Client, ClientBuilder, ClientError, ConfigureError, DhcpState, DnsConfig, InterfaceGonePolicy,
+    InterfaceWatcher, LeaseEvent, LeaseInfo, LinuxConfigurator, NetConfigurator, NetlinkWatcher,
+    NtpConfig, Route, StateDir, StateDirError, DEFAULT_STATE_ROOT, DEFAULT_STATUS_SOCKET,
+    RESOLV_CONF_PATH,
+};
+pub use clock::{Clock, SystemClock};
+pub use constants::{
+    CLIENT_PORT, DEFAULT_INITIAL_DELAY_MAX_SECS, DEFAULT_INITIAL_DELAY_MIN_SECS,
+    DEFAULT_MAX_INIT_BACKOFF_SECS, DEFAULT_REBIND_PERCENT, DEFAULT_RENEW_PERCENT,
+    DEFAULT_ROUTE_METRIC, HARDWARE_ADDR_TYPE_ETHERNET, MAGIC_COOKIE, MAX_HOPS,
+    MINIMUM_LEGAL_MAX_MESSAGE_SIZE, MIN_MSG_SIZE, SERVER_PORT,
+};
+pub use error::ProtocolError;
+pub use logging::{
+    init_logging, parse_log_filter, LogControl, LogFilter, LogFilterError, LogFilterStatus,
+    LogToggles, DAEMON_TARGET, DEFAULT_LOG_FILTER, FSM_TARGET, LEASE_TARGET, TRANSACTION_TARGET,
+    WIRE_TARGET,
+};
+pub use server::{
+    decode_frame, encode_frame, sanitize_hostname, send_control_request, AuditProgress,
+    AuditSummary, CacheEvictionCounters, CacheLimits, ConflictProber, ControlError, ControlRequest,
+    ControlRequestError, ControlResponse, DhcpService, EffectiveConfig, EffectivePool,
+    EffectiveStorage, FrameHeader, HandlerStats, HistorySample, HostnameConflictPolicy,
+    HostnameHandling, IcmpProber, InterfaceCounters, InterfaceStats, LatencyHistogram, LeaseChurn,
+    LeaseEntry, ListenInterface, ParseErrorCounters, PendingOffer, PersistedState, PoolHistory,
+    PoolStat, ReclaimedAddr, Reply, RequestMeta, Reservation, ReservationConflict,
+    ReservationError, ReservationReload, Server, ServerBuilder, ServerBuilderError, ServerError,
+    ServerStats, ShutdownHandle, StateStore, StrictDropCounters, StrictViolation, TransientCache,
+    TransitionError, UtilizationHistory, VendorPrecedence, VendorProfile, VendorSubOption,
+    CONTROL_MAGIC, CONTROL_PROTOCOL_VERSION, DEFAULT_AUDIT_RATE, DEFAULT_CACHE_LIMIT,
+    DEFAULT_CONTROL_SOCKET, DEFAULT_HANDLER_TIMEOUT, DEFAULT_HISTORY_RESOLUTION,
+    DEFAULT_HISTORY_WINDOW, DEFAULT_LEASE_RETENTION, DEFAULT_MAX_IN_FLIGHT_HANDLERS,
+    DEFAULT_PROBE_TIMEOUT, DEFAULT_RECLAIM_GRACE, DEFAULT_RECLAIM_PROBATION,
+    DEFAULT_RELAY_MAX_MESSAGE_SIZE, LATENCY_BUCKETS, MAX_AUDIT_RATE, MAX_FRAME_SIZE,
+    MAX_HOSTNAME_LEN, PERSIST_RETRY_MAX, PERSIST_RETRY_MIN, SHUTDOWN_DEADLINE, STRICT_MAX_SECS,
+};
+pub use storage::{IntoLease, Storage, StorageError};
+pub use transport::{Transport, TransportError};
+
+/// Test doubles for the clock, transport and network configuration.
+#[cfg(any(test, feature = "testing"))]
+pub use client::{ConfiguratorCall, MockConfigurator, MockInterfaceWatcher};
+#[cfg(any(test, feature = "testing"))]
+pub use clock::{SeededRng, StepClock};
+#[cfg(any(test, feature = "testing"))]
+pub use transport::RecordingTransport;
+
+// Internals, like the sans-IO client state machine, which the modules of the
+// crate refer to by their root path
+#[allow(unused_imports)]
+pub(crate) use client::*;
+#[allow(unused_imports)]
+pub(crate) use constants::*;
+#[allow(unused_imports)]
+pub(crate) use server::*;
+#[allow(unused_imports)]
+pub(crate) use utils::*;
@@ -0,0 +1,400 @@
+//! Runtime control of the log filter of the daemons. Both daemons install
+//! their subscriber with [`init_logging`], which puts the filter behind a
+//! reload layer. The returned [`LogControl`] swaps the filter while the
+//! daemon is running, optionally only for a limited time.
+//!
+//! Events of the library are emitted under these targets, so they can be
+//! enabled independently, like `info,vulcan::wire=trace`:
+//!
+//! - [`WIRE_TARGET`] (`vulcan::wire`): packet-level events, like decoded
+//!   messages and datagrams which are dropped before they are handled.
+//! - [`LEASE_TARGET`] (`vulcan::lease`): the lease lifecycle, like offers,
+//!   ACKs, NAKs, reclamation and the addresses configured by the client.
+//! - [`FSM_TARGET`] (`vulcan::fsm`): transitions of the client state
+//!   machine and the messages it decides to send.
+//! - [`TRANSACTION_TARGET`] (`vulcan::transaction`): one line per
+//!   completed DHCP transaction, summarizing the exchanged messages.
+//! - [`DAEMON_TARGET`] (`vulcan::daemon`): everything else, like sockets,
+//!   persistence and the control sockets.
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+use tracing::{info, warn};
+use tracing_subscriber::{
+    filter::{EnvFilter, LevelFilter},
+    fmt as tracing_fmt,
+    prelude::*,
+    reload,
+};
+
+/// Log filter of the daemons if none is configured.
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+pub const WIRE_TARGET: &str = "vulcan::wire";
+pub const LEASE_TARGET: &str = "vulcan::lease";
+pub const FSM_TARGET: &str = "vulcan::fsm";
+pub const TRANSACTION_TARGET: &str = "vulcan::transaction";
+pub const DAEMON_TARGET: &str = "vulcan::daemon";
+
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("invalid log filter '{0}': {1}")]
+    Invalid(String, String),
+
+    #[error("failed to apply log filter: {0}")]
+    Reload(String),
+
+    #[error("failed to install the subscriber: {0}")]
+    Init(String),
+}
+
+/// A validated log filter in the directive syntax of [`EnvFilter`], see
+/// [`parse_log_filter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter(String);
+
+impl LogFilter {
+    /// Builds the [`EnvFilter`] of the directives.
+    pub fn to_env_filter(&self) -> Result<EnvFilter, LogFilterError> {
+        EnvFilter::builder()
+            .parse(&self.0)
+            .map_err(|err| LogFilterError::Invalid(self.0.clone(), err.to_string()))
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Parses a log filter made of comma separated [`EnvFilter`] directives,
+/// which are either a level (`debug`), applying to all targets, or a target
+/// and level (`dhcp::server=trace`). Targets can be narrowed to spans, like
+/// `dhcp::server[transaction]=debug`. Levels are `off`, `error`, `warn`,
+/// `info`, `debug` and `trace`.
+pub fn parse_log_filter(filter: &str) -> Result<LogFilter, LogFilterError> {
+    let invalid = |reason: String| LogFilterError::Invalid(filter.to_string(), reason);
+
+    if filter.trim().is_empty() {
+        return Err(invalid(String::from("empty filter")));
+    }
+
+    let directives = filter.split(',').map(str::trim).collect::<Vec<_>>();
+
+    // EnvFilter treats a bare word it can't parse as a level as a target
+    // enabled at all levels, which turns typos like "inof" into "trace".
+    for directive in &directives {
+        if directive.is_empty() {
+            return Err(invalid(String::from("empty directive")));
+        }
+
+        let level = match directive.rsplit_once('=') {
+            Some((target, level)) if !target.trim().is_empty() => level.trim(),
+            Some(_) => return Err(invalid(format!("missing target in '{directive}'"))),
+            None => directive,
+        };
+
+        level
+            .parse::<LevelFilter>()
+            .map_err(|_| invalid(format!("invalid level '{level}'")))?;
+    }
+
+    let filter = LogFilter(directives.join(","));
+    filter.to_env_filter()?;
+
+    Ok(filter)
+}
+
+/// Switches for the log targets of the library, as set in the
+/// configuration of the daemons. A target is logged at all levels if its
+/// switch is on and not at all if it is off. Targets without a switch are
+/// left to the filter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogToggles {
+    pub packets: Option<bool>,
+    pub leases: Option<bool>,
+    pub fsm: Option<bool>,
+}
+
+impl LogToggles {
+    /// Returns `filter` with the directives of the switches appended, which
+    /// take precedence over the directives of `filter`.
+    pub fn apply(&self, filter: &str) -> String {
+        let toggles = [
+            (WIRE_TARGET, self.packets),
+            (LEASE_TARGET, self.leases),
+            (FSM_TARGET, self.fsm),
+        ];
+
+        let mut directives: Vec<String> = filter
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| {
+                !toggles.iter().any(|(target, toggle)| {
+                    toggle.is_some()
+                        && directive.split_once('=').map(|(t, _)| t.trim()) == Some(target)
+                })
+            })
+            .map(String::from)
+            .collect();
+
+        for (target, toggle) in toggles {
+            match toggle {
+                Some(true) => directives.push(format!("{target}=trace")),
+                Some(false) => directives.push(format!("{target}=off")),
+                None => {}
+            }
+        }
+
+        directives.join(",")
+    }
+}
+
+/// Installs the global subscriber, which writes to stdout and filters events
+/// with the reloadable `filter`, see [`parse_log_filter`].
+pub fn init_logging(filter: &str) -> Result<Arc<LogControl>, LogFilterError> {
+    let filter = parse_log_filter(filter)?;
+    let (layer, handle) = reload::Layer::new(filter.to_env_filter()?);
+
+    tracing_subscriber::registry()
+        .with(layer)
+        .with(tracing_fmt::layer())
+        .try_init()
+        .map_err(|err| LogFilterError::Init(err.to_string()))?;
+
+    Ok(Arc::new(LogControl::new(filter, move |filter| {
+        handle
+            .reload(filter.to_env_filter()?)
+            .map_err(|err| LogFilterError::Reload(err.to_string()))
+    })))
+}
+
+/// The current log filter, as reported by the control sockets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilterStatus {
+    pub filter: String,
+
+    /// Time until the filter reverts to the default, [`None`] if it doesn't.
+    pub revert_in: Option<Duration>,
+}
+
+type ApplyFn = dyn Fn(&LogFilter) -> Result<(), LogFilterError> + Send + Sync;
+
+/// Swaps the log filter at runtime. A filter set with a revert timeout is
+/// replaced by the default filter once the timeout elapsed, unless another
+/// filter was set in the meantime.
+pub struct LogControl {
+    default: LogFilter,
+    apply: Box<ApplyFn>,
+    current: Mutex<CurrentFilter>,
+}
+
+struct CurrentFilter {
+    filter: LogFilter,
+    revert_at: Option<Instant>,
+
+    /// Incremented on every change, so a pending revert can tell whether
+    /// its filter is still active.
+    generation: u64,
+}
+
+impl fmt::Debug for LogControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogControl")
+            .field("default", &self.default.to_string())
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl LogControl {
+    /// Creates a control which starts with (and reverts to) the `default`
+    /// filter. `apply` installs a filter in the subscriber.
+    pub fn new<F>(default: LogFilter, apply: F) -> Self
+    where
+        F: Fn(&LogFilter) -> Result<(), LogFilterError> + Send + Sync + 'static,
+    {
+        Self {
+            current: Mutex::new(CurrentFilter {
+                filter: default.clone(),
+                revert_at: None,
+                generation: 0,
+            }),
+            apply: Box::new(apply),
+            default,
+        }
+    }
+
+    /// Sets the log filter to `filter`. With `revert_after`, the default
+    /// filter is restored after that duration. This spawns a task and must
+    /// be called from within a Tokio runtime.
+    pub fn set(
+        self: &Arc<Self>,
+        filter: &str,
+        revert_after: Option<Duration>,
+    ) -> Result<LogFilterStatus, LogFilterError> {
+        let parsed = parse_log_filter(filter)?;
+        let now = Instant::now();
+
+        let generation = {
+            let mut current = self.current.lock().unwrap();
+            (self.apply)(&parsed)?;
+
+            current.filter = parsed;
+            current.revert_at = revert_after.map(|after| now + after);
+            current.generation += 1;
+            current.generation
+        };
+
+        info!(target: DAEMON_TARGET, filter, revert_after = ?revert_after, "changed log filter");
+
+        if let Some(after) = revert_after {
+            let control = self.clone();
+
+            tokio::spawn(async move {
+                tokio::time::sleep(after).await;
+                control.revert(generation);
+            });
+        }
+
+        Ok(self.status_at(now))
+    }
+
+    /// Returns the current filter.
+    pub fn status(&self) -> LogFilterStatus {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> LogFilterStatus {
+        let current = self.current.lock().unwrap();
+
+        LogFilterStatus {
+            filter: current.filter.to_string(),
+            revert_in: current
+                .revert_at
+                .map(|at| at.saturating_duration_since(now)),
+        }
+    }
+
+    /// Restores the default filter if the filter of `generation` is still
+    /// active.
+    fn revert(&self, generation: u64) {
+        let mut current = self.current.lock().unwrap();
+        if current.generation != generation {
+            return;
+        }
+
+        match (self.apply)(&self.default) {
+            Ok(()) => {
+                current.filter = self.default.clone();
+                current.revert_at = None;
+                current.generation += 1;
+                drop(current);
+
+                info!(target: DAEMON_TARGET, filter = %self.default, "reverted log filter");
+            }
+            Err(err) => warn!(target: DAEMON_TARGET, %err, "failed to revert log filter"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a control with the `info` default which records the applied
+    /// filters.
+    fn control() -> (Arc<LogControl>, Arc<Mutex<Vec<String>>>) {
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let recorder = applied.clone();
+
+        let control = LogControl::new(parse_log_filter("info").unwrap(), move |filter| {
+            recorder.lock().unwrap().push(filter.to_string());
+            Ok(())
+        });
+
+        (Arc::new(control), applied)
+    }
+
+    #[test]
+    fn test_parse_log_filter() {
+        assert_eq!(parse_log_filter("debug").unwrap().to_string(), "debug");
+        assert_eq!(
+            parse_log_filter("info, dhcp::server=trace")
+                .unwrap()
+                .to_string(),
+            "info,dhcp::server=trace"
+        );
+        assert!(parse_log_filter("dhcp::server[transaction{xid=1}]=debug").is_ok());
+
+        for invalid in ["", " ", "loud", "dhcp=loud", "info,,debug", "=debug"] {
+            assert!(
+                matches!(parse_log_filter(invalid), Err(LogFilterError::Invalid(..))),
+                "{invalid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_log_toggles() {
+        let toggles = LogToggles {
+            packets: Some(true),
+            leases: Some(false),
+            fsm: None,
+        };
+
+        assert_eq!(
+            toggles.apply("info,vulcan::wire=warn,vulcan::fsm=debug"),
+            "info,vulcan::fsm=debug,vulcan::wire=trace,vulcan::lease=off"
+        );
+        assert_eq!(LogToggles::default().apply("debug"), "debug");
+        assert!(parse_log_filter(&toggles.apply(DEFAULT_LOG_FILTER)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_revert_after_timeout() {
+        let (control, applied) = control();
+
+        let status = control
+            .set("debug", Some(Duration::from_millis(50)))
+            .unwrap();
+        assert_eq!(status.filter, "debug");
+        assert_eq!(status.revert_in, Some(Duration::from_millis(50)));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert_eq!(
+            control.status(),
+            LogFilterStatus {
+                filter: String::from("info"),
+                revert_in: None,
+            }
+        );
+        assert_eq!(*applied.lock().unwrap(), ["debug", "info"]);
+    }
+
+    #[tokio::test]
+    async fn test_newer_filter_cancels_revert() {
+        let (control, applied) = control();
+
+        control
+            .set("debug", Some(Duration::from_millis(50)))
+            .unwrap();
+        control.set("trace", None).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert_eq!(control.status().filter, "trace");
+        assert_eq!(control.status().revert_in, None);
+        assert_eq!(*applied.lock().unwrap(), ["debug", "trace"]);
+
+        // Invalid filters leave the current one in place
+        assert!(control.set("loud", None).is_err());
+        assert_eq!(control.status().filter, "trace");
+    }
+}
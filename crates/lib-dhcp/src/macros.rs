@@ -0,0 +1,94 @@
+/// Reads fixed-layout fields from a `ReadBuffer` in the listed order and
+/// binds each to a local variable of the same name. Errors are propagated
+/// with `?`, so the error types of the fields have to convert into the error
+/// of the surrounding function.
+///
+/// ```ignore
+/// read_fields!(buf, E => xid: u32, secs: u16);
+/// Ok(Self { xid, secs })
+/// ```
+macro_rules! read_fields {
+    ($buf:expr, $e:ty => $($field:ident: $ty:ty),+ $(,)?) => {
+        $(let $field = <$ty>::read::<$e>($buf)?;)+
+    };
+}
+
+/// Writes the listed values to a `WriteBuffer` in order and evaluates to the
+/// total number of bytes written. Like [`read_fields!`], errors are
+/// propagated with `?`.
+///
+/// ```ignore
+/// let n = write_fields!(buf, E => self.xid, self.secs);
+/// ```
+macro_rules! write_fields {
+    ($buf:expr, $e:ty => $($value:expr),+ $(,)?) => {{
+        let mut n = 0;
+        $(n += $value.write::<$e>($buf)?;)+
+        n
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use binbuf::prelude::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Fixed {
+        kind: u8,
+        id: u32,
+        port: u16,
+        addr: Ipv4Addr,
+    }
+
+    impl Readable for Fixed {
+        type Error = BufferError;
+
+        fn read<E: Endianness>(buf: &mut ReadBuffer) -> Result<Self, Self::Error> {
+            read_fields!(buf, E => kind: u8, id: u32, port: u16, addr: Ipv4Addr);
+            Ok(Self {
+                kind,
+                id,
+                port,
+                addr,
+            })
+        }
+    }
+
+    impl Writeable for Fixed {
+        type Error = BufferError;
+
+        fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+            Ok(write_fields!(buf, E => self.kind, self.id, self.port, self.addr))
+        }
+    }
+
+    #[test]
+    fn test_fields_round_trip() {
+        let fixed = Fixed {
+            kind: 1,
+            id: 0xdeadbeef,
+            port: 67,
+            addr: Ipv4Addr::new(192, 168, 1, 1),
+        };
+
+        let mut buf = WriteBuffer::new();
+        let n = fixed.write::<BigEndian>(&mut buf).unwrap();
+
+        assert_eq!(n, 11);
+        assert_eq!(buf.len(), n);
+        assert_eq!(
+            buf.bytes(),
+            [1, 0xde, 0xad, 0xbe, 0xef, 0, 67, 192, 168, 1, 1]
+        );
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(Fixed::read::<BigEndian>(&mut buf).unwrap(), fixed);
+        assert!(buf.is_empty());
+
+        // Missing fields are reported, not defaulted
+        let mut buf = ReadBuffer::new(&[1, 0xde, 0xad]);
+        assert!(Fixed::read::<BigEndian>(&mut buf).is_err());
+    }
+}
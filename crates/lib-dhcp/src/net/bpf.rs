@@ -0,0 +1,160 @@
+//! Classic BPF programs attached to sockets with `SO_ATTACH_FILTER`.
+use std::{io, mem::size_of, os::fd::AsRawFd};
+
+use crate::constants::MAGIC_COOKIE;
+
+// Classic BPF instruction classes and modes. See linux/bpf_common.h
+pub(crate) const BPF_LD_W_ABS: u16 = 0x20;
+pub(crate) const BPF_LD_H_ABS: u16 = 0x28;
+pub(crate) const BPF_LD_B_ABS: u16 = 0x30;
+pub(crate) const BPF_LD_H_IND: u16 = 0x48;
+pub(crate) const BPF_LD_W_LEN: u16 = 0x80;
+pub(crate) const BPF_LDX_B_MSH: u16 = 0xb1;
+pub(crate) const BPF_JMP_JEQ_K: u16 = 0x15;
+pub(crate) const BPF_JMP_JGE_K: u16 = 0x35;
+pub(crate) const BPF_JMP_JSET_K: u16 = 0x45;
+pub(crate) const BPF_RET_K: u16 = 0x06;
+
+/// Filters attached to UDP sockets see the datagram starting with the UDP
+/// header.
+const UDP_HEADER_LEN: u32 = 8;
+
+/// Offset of the 'op' field and the magic cookie in the DHCP message.
+const OP_OFFSET: u32 = 0;
+const MAGIC_COOKIE_OFFSET: u32 = 236;
+
+const BOOTREQUEST: u32 = 1;
+const BOOTREPLY: u32 = 2;
+
+pub(crate) fn stmt(code: u16, k: u32) -> libc::sock_filter {
+    jump(code, k, 0, 0)
+}
+
+pub(crate) fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Returns a classic BPF program for UDP sockets which only accepts
+/// datagrams long enough to hold the fixed length fields and the magic
+/// cookie, with a valid 'op' field and the magic cookie in place.
+pub(crate) fn dhcp_datagram_filter() -> Vec<libc::sock_filter> {
+    let cookie = UDP_HEADER_LEN + MAGIC_COOKIE_OFFSET;
+
+    vec![
+        // Check the length of the UDP header and payload
+        stmt(BPF_LD_W_LEN, 0),
+        jump(BPF_JMP_JGE_K, cookie + 4, 0, 6),
+        // Check the opcode
+        stmt(BPF_LD_B_ABS, UDP_HEADER_LEN + OP_OFFSET),
+        jump(BPF_JMP_JEQ_K, BOOTREQUEST, 1, 0),
+        jump(BPF_JMP_JEQ_K, BOOTREPLY, 0, 3),
+        // Check the magic cookie
+        stmt(BPF_LD_W_ABS, cookie),
+        jump(BPF_JMP_JEQ_K, MAGIC_COOKIE, 0, 1),
+        // Accept the complete datagram
+        stmt(BPF_RET_K, u32::MAX),
+        // Drop
+        stmt(BPF_RET_K, 0),
+    ]
+}
+
+/// Attaches the `filter` program to `socket`. Datagrams already queued on
+/// the socket are not filtered.
+pub(crate) fn attach(socket: &impl AsRawFd, filter: &mut [libc::sock_filter]) -> io::Result<()> {
+    let prog = libc::sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_ATTACH_FILTER,
+            &prog as *const _ as *const libc::c_void,
+            size_of::<libc::sock_fprog>() as libc::socklen_t,
+        )
+    };
+
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, time::Duration};
+
+    use super::*;
+    use crate::constants::MAGIC_COOKIE_ARR;
+
+    fn datagram(op: u8, len: usize, cookie: [u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        bytes[0] = op;
+
+        if len >= 240 {
+            bytes[236..240].copy_from_slice(&cookie);
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test_dhcp_datagram_filter_bytecode() {
+        // Compiled by hand, in the format of 'tcpdump -dd'
+        let expected: [(u16, u8, u8, u32); 9] = [
+            (0x80, 0, 0, 0x00000000),
+            (0x35, 0, 6, 0x000000f8),
+            (0x30, 0, 0, 0x00000008),
+            (0x15, 1, 0, 0x00000001),
+            (0x15, 0, 3, 0x00000002),
+            (0x20, 0, 0, 0x000000f4),
+            (0x15, 0, 1, 0x63825363),
+            (0x06, 0, 0, 0xffffffff),
+            (0x06, 0, 0, 0x00000000),
+        ];
+
+        let filter: Vec<_> = dhcp_datagram_filter()
+            .iter()
+            .map(|ins| (ins.code, ins.jt, ins.jf, ins.k))
+            .collect();
+
+        assert_eq!(filter, expected);
+    }
+
+    #[test]
+    fn test_filter_drops_flood_on_loopback() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        attach(&receiver, &mut dhcp_datagram_filter()).unwrap();
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = receiver.local_addr().unwrap();
+
+        // Flood the socket with datagrams which can't be DHCP messages. Each
+        // of them would wake up the receiver without the filter.
+        let junk = [
+            datagram(1, 100, MAGIC_COOKIE_ARR),
+            datagram(7, 300, MAGIC_COOKIE_ARR),
+            datagram(1, 300, [0, 0, 0, 0]),
+        ];
+
+        for i in 0..300 {
+            sender.send_to(&junk[i % junk.len()], addr).unwrap();
+        }
+
+        let message = datagram(2, 300, MAGIC_COOKIE_ARR);
+        sender.send_to(&message, addr).unwrap();
+
+        let mut buf = [0; 1500];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], message.as_slice());
+
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! Socket helpers shared by the client and the server.
+use std::os::fd::AsRawFd;
+
+use tracing::debug;
+
+use crate::DAEMON_TARGET;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod bpf;
+
+/// Attaches a BPF filter to the UDP `socket` which drops datagrams that
+/// can't be DHCP messages in the kernel, before they wake up the receiving
+/// task. Dropped datagrams don't show up in the receive and parse error
+/// counters. Failing to attach the filter is not an error, the messages are
+/// rejected when parsing them instead.
+pub(crate) fn filter_dhcp_datagrams(
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))] socket: &impl AsRawFd,
+) {
+    #[cfg(target_os = "linux")]
+    if let Err(err) = bpf::attach(socket, &mut bpf::dhcp_datagram_filter()) {
+        debug!(target: DAEMON_TARGET, %err, "failed to attach socket filter");
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    debug!(target: DAEMON_TARGET, "socket filters are not supported on this platform");
+}
@@ -0,0 +1,122 @@
+use std::{
+    net::Ipv4Addr,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{server::state::ServerState, types::HardwareAddr};
+
+/// An address allocated for an offer which isn't recorded yet. If the
+/// allocation is dropped before [`OfferAllocation::record`] is called, for
+/// example because the handler timed out while probing the address, the
+/// address is handed back to the pool.
+///
+/// Addresses the client already held before the allocation are left alone.
+pub(crate) struct OfferAllocation<'a> {
+    state: &'a ServerState,
+    key: String,
+    addr: Ipv4Addr,
+    fresh: bool,
+    recorded: bool,
+}
+
+impl<'a> OfferAllocation<'a> {
+    /// Allocates the address offered to the client with `key`, see
+    /// [`ServerState::offer_address`].
+    pub fn new(state: &'a ServerState, key: &str, now: Instant) -> Option<Self> {
+        let held = state
+            .pools
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|pool| pool.address_of(key).is_some());
+        let addr = state.offer_address(key, now)?;
+
+        Some(Self {
+            state,
+            key: key.to_string(),
+            addr,
+            fresh: !held,
+            recorded: false,
+        })
+    }
+
+    pub fn addr(&self) -> Ipv4Addr {
+        self.addr
+    }
+
+    /// Returns whether the address was newly allocated to the client, as
+    /// opposed to one it already held.
+    pub fn is_fresh(&self) -> bool {
+        self.fresh
+    }
+
+    /// Records the allocated address as offered to the client, see
+    /// [`ServerState::record_offer`].
+    pub fn record(
+        mut self,
+        hardware_addr: HardwareAddr,
+        xid: u32,
+        timeout: Duration,
+        now: SystemTime,
+    ) {
+        self.state
+            .record_offer(&self.key, hardware_addr, xid, self.addr, timeout, now);
+        self.recorded = true;
+    }
+}
+
+impl Drop for OfferAllocation<'_> {
+    fn drop(&mut self) {
+        if self.fresh && !self.recorded {
+            self.state.roll_back_allocation(&self.key, self.addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Clock, Server, StepClock};
+
+    #[test]
+    fn test_dropped_allocation_is_rolled_back() {
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("default".into(), "10.0.0.10-10.0.0.20".into())
+            .with_allow_foreign_pools(true)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let key = "02:00:00:00:00:01";
+        let addr = Ipv4Addr::new(10, 0, 0, 10);
+
+        let allocation = OfferAllocation::new(state, key, clock.now_instant()).unwrap();
+        assert_eq!(allocation.addr(), addr);
+        assert!(allocation.is_fresh());
+        drop(allocation);
+
+        let pools = state.pools.lock().unwrap();
+        assert_eq!(pools[0].holder_of(&addr), None);
+        drop(pools);
+
+        // The coalescing cache doesn't hand out the address either
+        let other = OfferAllocation::new(state, "02:00:00:00:00:02", clock.now_instant()).unwrap();
+        assert_eq!(other.addr(), addr);
+        other.record(
+            "02:00:00:00:00:02".to_string().try_into().unwrap(),
+            0,
+            Duration::from_secs(60),
+            clock.now_system(),
+        );
+
+        // Recorded offers and addresses held before are kept
+        let again = OfferAllocation::new(state, "02:00:00:00:00:02", clock.now_instant()).unwrap();
+        assert!(!again.is_fresh());
+        drop(again);
+        assert_eq!(
+            state.pools.lock().unwrap()[0].holder_of(&addr),
+            Some("02:00:00:00:00:02")
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    net::Ipv4Addr,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use rand::seq::SliceRandom;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::{
+    server::{probe::ConflictProber, state::ServerState},
+    LEASE_TARGET,
+};
+
+/// Default number of probes per second sent by the startup audit.
+pub const DEFAULT_AUDIT_RATE: u32 = 10;
+
+/// Maximum number of probes per second sent by the startup audit, one probe
+/// per nanosecond.
+pub const MAX_AUDIT_RATE: u32 = 1_000_000_000;
+
+/// Progress of the startup address-conflict audit, see
+/// [`Server::audit_progress`](crate::Server::audit_progress).
+#[derive(Debug, Default)]
+pub struct AuditProgress {
+    /// Number of addresses selected for probing.
+    pub total: AtomicU64,
+
+    /// Number of addresses probed so far.
+    pub probed: AtomicU64,
+
+    /// Number of probed addresses which were found in use and put on
+    /// probation.
+    pub occupied: AtomicU64,
+
+    /// Set once the sweep completed.
+    pub finished: AtomicBool,
+}
+
+impl AuditProgress {
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+/// Result of a completed startup audit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditSummary {
+    pub probed: usize,
+    pub occupied: Vec<Ipv4Addr>,
+    pub errors: usize,
+}
+
+/// Probes the free addresses of all pools at a bounded rate and puts the
+/// addresses of responding hosts on probation. Addresses allocated while
+/// the sweep is running are skipped.
+pub(crate) async fn run_audit(state: &ServerState, prober: &dyn ConflictProber) -> AuditSummary {
+    let candidates = select_candidates(state);
+    let progress = &state.audit;
+    progress
+        .total
+        .store(candidates.len() as u64, Ordering::Relaxed);
+
+    let rate = state.config.audit_rate.clamp(1, MAX_AUDIT_RATE);
+    let mut interval = time::interval(Duration::from_secs(1) / rate);
+
+    let mut summary = AuditSummary {
+        probed: 0,
+        occupied: Vec::new(),
+        errors: 0,
+    };
+
+    for addr in candidates {
+        interval.tick().await;
+
+        match prober.probe(addr).await {
+            Ok(true) => {
+                let mut pools = state.pools.lock().unwrap();
+                if pools.iter_mut().any(|pool| pool.put_on_probation(addr)) {
+                    warn!(
+                        target: LEASE_TARGET,
+                        %addr,
+                        "address is in use by an unknown host, putting it on probation"
+                    );
+                    progress.occupied.fetch_add(1, Ordering::Relaxed);
+                    summary.occupied.push(addr);
+                }
+            }
+            Ok(false) => {}
+            Err(err) => {
+                warn!(target: LEASE_TARGET, %addr, %err, "failed to probe address");
+                summary.errors += 1;
+            }
+        }
+
+        summary.probed += 1;
+        progress.probed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    progress.finished.store(true, Ordering::Relaxed);
+    info!(
+        target: LEASE_TARGET,
+        probed = summary.probed,
+        occupied = summary.occupied.len(),
+        errors = summary.errors,
+        "startup address audit finished"
+    );
+
+    summary
+}
+
+/// Selects the addresses to probe. If a sample size is configured, a random
+/// sample of that size is taken from the free addresses of each pool.
+fn select_candidates(state: &ServerState) -> Vec<Ipv4Addr> {
+    let pools = state.pools.lock().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut candidates = Vec::new();
+
+    for pool in pools.iter() {
+        let free = pool.free_addresses();
+
+        match state.config.audit_sample_size {
+            Some(size) => candidates.extend(free.choose_multiple(&mut rng, size).copied()),
+            None => candidates.extend(free),
+        }
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::Server;
+
+    /// Prober which reports a fixed set of addresses as occupied.
+    struct FakeProber {
+        occupied: HashSet<Ipv4Addr>,
+    }
+
+    #[async_trait]
+    impl ConflictProber for FakeProber {
+        async fn probe(&self, addr: Ipv4Addr) -> std::io::Result<bool> {
+            Ok(self.occupied.contains(&addr))
+        }
+    }
+
+    fn prober() -> Arc<FakeProber> {
+        Arc::new(FakeProber {
+            occupied: HashSet::from([Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 4)]),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_audit_puts_responders_on_probation() {
+        let server = Server::builder()
+            .with_pool("small".into(), "10.0.0.1-10.0.0.5".into())
+            .with_allow_foreign_pools(true)
+            .with_startup_audit(prober())
+            .with_audit_rate(1000)
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+
+        // The client holding 10.0.0.1 is known, it must not be probed
+        state.offer_address("a", std::time::Instant::now());
+
+        let summary = run_audit(state, prober().as_ref()).await;
+
+        assert_eq!(summary.probed, 4);
+        assert_eq!(
+            summary.occupied,
+            vec![Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 4)]
+        );
+        assert_eq!(summary.errors, 0);
+
+        assert!(state.audit.is_finished());
+        assert_eq!(state.audit.total.load(Ordering::Relaxed), 4);
+        assert_eq!(state.audit.occupied.load(Ordering::Relaxed), 2);
+
+        // Addresses on probation are skipped by the allocator
+        let addrs: Vec<_> = ["b", "c"]
+            .iter()
+            .map(|key| state.offer_address(key, std::time::Instant::now()))
+            .collect();
+        assert_eq!(
+            addrs,
+            vec![
+                Some(Ipv4Addr::new(10, 0, 0, 3)),
+                Some(Ipv4Addr::new(10, 0, 0, 5))
+            ]
+        );
+        assert_eq!(state.offer_address("d", std::time::Instant::now()), None);
+    }
+
+    #[tokio::test]
+    async fn test_audit_sample() {
+        let server = Server::builder()
+            .with_pool("small".into(), "10.0.0.1-10.0.0.5".into())
+            .with_allow_foreign_pools(true)
+            .with_startup_audit(prober())
+            .with_audit_sample_size(2)
+            .with_audit_rate(1000)
+            .build()
+            .unwrap();
+
+        let summary = run_audit(&server.service.state, prober().as_ref()).await;
+        assert_eq!(summary.probed, 2);
+    }
+}
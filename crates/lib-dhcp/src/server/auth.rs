@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use thiserror::Error;
+
+use crate::types::{
+    options::{ALGORITHM_HMAC_MD5, PROTOCOL_DELAYED},
+    MessageView, OptionTag,
+};
+
+type HmacMd5 = Hmac<Md5>;
+
+/// Offset of the 'hops' field, which relay agents change in transit.
+const HOPS_OFFSET: usize = 3;
+
+/// Offset of the 'giaddr' field, which relay agents change in transit.
+const GIADDR_OFFSET: usize = 24;
+
+/// Offset of the authentication information within the option data.
+const INFORMATION_OFFSET: usize = 11;
+
+/// Length of the secret ID preceding the HMAC.
+const SECRET_ID_LEN: usize = 4;
+
+/// Length of an HMAC-MD5.
+const MAC_LEN: usize = 16;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(crate) enum AuthError {
+    #[error("No authentication information")]
+    Missing,
+
+    #[error("Unsupported authentication protocol {0} with algorithm {1}")]
+    Unsupported(u8, u8),
+
+    #[error("Malformed authentication information")]
+    Malformed,
+
+    #[error("Unknown secret ID {0}")]
+    UnknownSecret(u32),
+
+    #[error("HMAC doesn't match the message")]
+    BadMac,
+}
+
+/// Verifies the Authentication option of the message in `view` with the
+/// delayed authentication protocol of RFC 3118 section 5, using HMAC-MD5.
+/// The HMAC covers the whole message with 'hops', 'giaddr' and the HMAC
+/// itself set to zero. `keys` maps secret IDs to the shared secrets.
+pub(crate) fn verify(
+    view: &MessageView<'_>,
+    keys: &HashMap<u32, Vec<u8>>,
+) -> Result<(), AuthError> {
+    let buf = view.bytes();
+    let range = view
+        .option_range(OptionTag::Authentication)
+        .ok_or(AuthError::Missing)?;
+
+    let option = &buf[range.clone()];
+    if option.len() < INFORMATION_OFFSET {
+        return Err(AuthError::Malformed);
+    }
+
+    if (option[0], option[1]) != (PROTOCOL_DELAYED, ALGORITHM_HMAC_MD5) {
+        return Err(AuthError::Unsupported(option[0], option[1]));
+    }
+
+    let information = &option[INFORMATION_OFFSET..];
+    if information.len() != SECRET_ID_LEN + MAC_LEN {
+        return Err(AuthError::Malformed);
+    }
+
+    let mut secret_id = [0; SECRET_ID_LEN];
+    secret_id.copy_from_slice(&information[..SECRET_ID_LEN]);
+    let secret_id = u32::from_be_bytes(secret_id);
+
+    let key = keys
+        .get(&secret_id)
+        .ok_or(AuthError::UnknownSecret(secret_id))?;
+
+    let mac_start = range.start + INFORMATION_OFFSET + SECRET_ID_LEN;
+    let mut mac = HmacMd5::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&zeroed(buf, mac_start));
+
+    mac.verify_slice(&buf[mac_start..mac_start + MAC_LEN])
+        .map_err(|_| AuthError::BadMac)
+}
+
+/// Returns a copy of `buf` with the fields excluded from the HMAC set to
+/// zero, the HMAC starts at `mac_start`.
+fn zeroed(buf: &[u8], mac_start: usize) -> Vec<u8> {
+    let mut buf = buf.to_vec();
+    buf[HOPS_OFFSET] = 0;
+    buf[GIADDR_OFFSET..GIADDR_OFFSET + 4].fill(0);
+    buf[mac_start..mac_start + MAC_LEN].fill(0);
+    buf
+}
+
+/// Fills in the HMAC of the Authentication option of `buf`, which has to
+/// carry a secret ID and a zeroed HMAC, using `key`.
+#[cfg(test)]
+pub(crate) fn sign(buf: &mut [u8], key: &[u8]) {
+    let range = MessageView::new(buf)
+        .unwrap()
+        .option_range(OptionTag::Authentication)
+        .unwrap();
+    let mac_start = range.start + INFORMATION_OFFSET + SECRET_ID_LEN;
+
+    let mut mac = HmacMd5::new_from_slice(key).unwrap();
+    mac.update(&zeroed(buf, mac_start));
+    buf[mac_start..mac_start + MAC_LEN].copy_from_slice(&mac.finalize().into_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use binbuf::prelude::*;
+
+    use super::*;
+    use crate::types::{
+        options::{Authentication, DhcpMessageType, RDM_MONOTONIC_COUNTER},
+        Message, OptionData,
+    };
+
+    fn request(secret_id: u32) -> Vec<u8> {
+        let mut information = secret_id.to_be_bytes().to_vec();
+        information.extend([0; MAC_LEN]);
+
+        let mut message = Message::new_with_xid(0xdeadbeef);
+        message
+            .add_option_parts(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Request),
+            )
+            .unwrap();
+        message
+            .add_option_parts(
+                OptionTag::Authentication,
+                OptionData::Authentication(Authentication::new(
+                    PROTOCOL_DELAYED,
+                    ALGORITHM_HMAC_MD5,
+                    RDM_MONOTONIC_COUNTER,
+                    1,
+                    information,
+                )),
+            )
+            .unwrap();
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    #[test]
+    fn test_verify() {
+        let keys = HashMap::from([(7, b"secret".to_vec())]);
+        let verify = |buf: &[u8]| verify(&MessageView::new(buf).unwrap(), &keys);
+
+        let mut buf = request(7);
+        assert_eq!(verify(&buf), Err(AuthError::BadMac));
+
+        sign(&mut buf, b"secret");
+        assert_eq!(verify(&buf), Ok(()));
+
+        // Relay agents may change 'hops' and 'giaddr'
+        buf[HOPS_OFFSET] = 1;
+        buf[GIADDR_OFFSET..GIADDR_OFFSET + 4].copy_from_slice(&[10, 0, 0, 1]);
+        assert_eq!(verify(&buf), Ok(()));
+
+        // But nothing else
+        buf[4] ^= 0xff;
+        assert_eq!(verify(&buf), Err(AuthError::BadMac));
+
+        let mut buf = request(8);
+        sign(&mut buf, b"secret");
+        assert_eq!(verify(&buf), Err(AuthError::UnknownSecret(8)));
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::Ipv4Addr,
+};
+
+use crate::types::Lease;
+
+/// Reverse index from client keys to the addresses bound to them. A client
+/// can hold addresses in several pools at the same time, while the lease
+/// table only records the most recent binding of each client. The index
+/// keeps a copy of the lease of every binding instead.
+#[derive(Debug, Default)]
+pub(crate) struct BindingIndex {
+    by_key: HashMap<String, BTreeMap<Ipv4Addr, Lease>>,
+}
+
+impl BindingIndex {
+    /// Records that the address of `lease` is bound to the client with
+    /// `key`, replacing the previous record of this binding.
+    pub fn insert(&mut self, key: &str, lease: &Lease) {
+        self.by_key
+            .entry(key.to_string())
+            .or_default()
+            .insert(lease.ip_addr(), lease.clone());
+    }
+
+    /// Updates the record of the binding of the address of `lease`, if it
+    /// is bound to the client with `key`.
+    pub fn update(&mut self, key: &str, lease: &Lease) {
+        if let Some(bound) = self
+            .by_key
+            .get_mut(key)
+            .and_then(|leases| leases.get_mut(&lease.ip_addr()))
+        {
+            *bound = lease.clone();
+        }
+    }
+
+    /// Removes the binding of `addr` to the client with `key`.
+    pub fn remove(&mut self, key: &str, addr: &Ipv4Addr) {
+        if let Some(leases) = self.by_key.get_mut(key) {
+            leases.remove(addr);
+
+            if leases.is_empty() {
+                self.by_key.remove(key);
+            }
+        }
+    }
+
+    pub fn is_bound(&self, key: &str, addr: &Ipv4Addr) -> bool {
+        self.by_key
+            .get(key)
+            .is_some_and(|leases| leases.contains_key(addr))
+    }
+
+    /// Returns all addresses bound to the client with `key`, in ascending
+    /// order.
+    pub fn addresses(&self, key: &str) -> Vec<Ipv4Addr> {
+        self.by_key
+            .get(key)
+            .map(|leases| leases.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the key of the client `addr` is bound to.
+    pub fn key_of(&self, addr: &Ipv4Addr) -> Option<&str> {
+        self.by_key
+            .iter()
+            .find(|(_, leases)| leases.contains_key(addr))
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// Returns the lease of the binding of `addr` to the client with `key`.
+    pub fn lease(&self, key: &str, addr: &Ipv4Addr) -> Option<&Lease> {
+        self.by_key.get(key).and_then(|leases| leases.get(addr))
+    }
+}
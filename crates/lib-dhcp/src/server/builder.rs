@@ -1,8 +1,50 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use lib_ifs::InterfacesError;
 use thiserror::Error;
+use tokio::sync::Notify;
+use tracing::warn;
 
 use crate::{
-    server::config::ServerConfig, Server, DEFAULT_REBIND_PERCENT, DEFAULT_RENEW_PERCENT,
-    ONE_HOUR_SECS,
+    bounded::BoundedTtlMap,
+    builder::MessageBuilder,
+    server::{
+        audit::{AuditProgress, DEFAULT_AUDIT_RATE, MAX_AUDIT_RATE},
+        bindings::BindingIndex,
+        coalesce::{DiscoverCache, DEFAULT_COALESCE_WINDOW},
+        config::{LeaseTimes, PoolOptions, ServerConfig},
+        history::{UtilizationHistory, DEFAULT_HISTORY_RESOLUTION, DEFAULT_HISTORY_WINDOW},
+        hostname::{HostnameConflictPolicy, HostnameHandling},
+        limits::CacheLimits,
+        offers::{OfferCache, DEFAULT_OFFER_TIMEOUT},
+        ownership::foreign_pools,
+        persistence::PersistenceHealth,
+        pool::{Pool, PoolParseError},
+        probe::ConflictProber,
+        reclaim::{DEFAULT_LEASE_RETENTION, DEFAULT_RECLAIM_GRACE, DEFAULT_RECLAIM_PROBATION},
+        replay::ReplayDetector,
+        reservations::{self, Reservation, ReservationError, ReservationReload},
+        service::DhcpService,
+        size::DEFAULT_RELAY_MAX_MESSAGE_SIZE,
+        starvation::{
+            StarvationDetector, DEFAULT_DEFENSIVE_OFFER_TIMEOUT, DEFAULT_STARVATION_THRESHOLD,
+            DEFAULT_STARVATION_WINDOW,
+        },
+        state::ServerState,
+        stats::ServerStats,
+        storage::{FileStore, PersistedState, ServerStorageError, StateStore},
+        tasks::{DEFAULT_HANDLER_TIMEOUT, DEFAULT_MAX_IN_FLIGHT_HANDLERS},
+        vendor::VendorProfile,
+    },
+    types::{HardwareAddr, Ipv4Net, Ipv4NetError},
+    Clock, LogControl, Server, SystemClock, DAEMON_TARGET, DEFAULT_REBIND_PERCENT,
+    DEFAULT_RENEW_PERCENT, MINIMUM_LEGAL_MAX_MESSAGE_SIZE, ONE_HOUR_SECS, SERVER_PORT,
 };
 
 #[derive(Debug, Error)]
@@ -13,8 +55,39 @@ pub enum ServerBuilderError {
     #[error("renew time (T1) must be smaller than rebind time (T2)")]
     InvalidPercent,
 
+    #[deprecated(note = "a server without pools is valid, this is never returned")]
     #[error("at least one pool configuration is required")]
     InvalidPoolCount,
+
+    #[error("lease times configured for unknown pool {0}")]
+    UnknownPool(String),
+
+    #[error("renew time (T1) of pool {0} must be smaller than its rebind time (T2)")]
+    InvalidPoolTimes(String),
+
+    #[error("pool parse error: {0}")]
+    PoolParseError(#[from] PoolParseError),
+
+    #[error("invalid subnet mask: {0}")]
+    InvalidSubnetMask(#[from] Ipv4NetError),
+
+    #[error("pool {pool} does not fit within a single subnet ({subnet})")]
+    PoolCrossesSubnet { pool: String, subnet: Ipv4Net },
+
+    #[error("no interface owns an address in the subnet of pool(s) {0:?}")]
+    ForeignPools(Vec<String>),
+
+    #[error("interface error: {0}")]
+    InterfacesError(#[from] InterfacesError),
+
+    #[error("failed to load persisted state: {0}")]
+    StateError(#[from] ServerStorageError),
+
+    #[error("reservation error: {0}")]
+    ReservationError(#[from] ReservationError),
+
+    #[error("sub-options of vendor profile {0} exceed the maximum option length")]
+    VendorProfileTooLong(u32),
 }
 
 pub struct ServerBuilder {
@@ -28,6 +101,67 @@ pub struct ServerBuilder {
     lease_time: u32,
 
     pools: Vec<(String, String)>,
+    pool_lease_times: HashMap<String, u32>,
+    pool_renew_rebind_times: HashMap<String, (u32, u32)>,
+    pool_options: HashMap<String, PoolOptions>,
+    reservations: Vec<Reservation>,
+    reservation_reload: Option<ReservationReload>,
+    server_identifier: Ipv4Addr,
+    interfaces: Vec<String>,
+    bind_address: SocketAddr,
+    subnet_mask: Option<Ipv4Addr>,
+    inform_unknown_subnets: bool,
+    nak_unknown_renewals: bool,
+    check_renewal_source: bool,
+    option_overload: bool,
+    relay_max_message_size: u16,
+    socket_filter: bool,
+    strict: bool,
+    trusted_relays: Vec<Ipv4Addr>,
+    auth_keys: HashMap<u32, Vec<u8>>,
+    discover_coalesce_window: Duration,
+    allow_foreign_pools: bool,
+    offer_timeout: Duration,
+    state_file: Option<PathBuf>,
+    state_store: Option<Arc<dyn StateStore>>,
+    require_persistence: bool,
+
+    starvation_window: Duration,
+    starvation_threshold: usize,
+    defensive_mode: bool,
+    defensive_offer_timeout: Duration,
+
+    next_server: Option<Ipv4Addr>,
+    boot_file: Option<String>,
+
+    audit_prober: Option<Arc<dyn ConflictProber>>,
+    offer_prober: Option<Arc<dyn ConflictProber>>,
+    handler_timeout: Duration,
+    max_concurrent: usize,
+    cache_limits: CacheLimits,
+    log_control: Option<Arc<LogControl>>,
+
+    #[cfg(feature = "dhcpv6")]
+    v6_responder: Option<Arc<crate::v6::Responder>>,
+    audit_sample_size: Option<usize>,
+    audit_rate: u32,
+    reclaim_grace: Duration,
+    reclaim_probation: Duration,
+    lease_retention: Duration,
+    history_resolution: Duration,
+    history_window: Duration,
+
+    domain_name: Option<String>,
+    domain_search: Option<Vec<String>>,
+    synthesize_domain_search: bool,
+    time_servers: Option<Vec<Ipv4Addr>>,
+    ntp_servers: Option<Vec<Ipv4Addr>>,
+    vendor_profiles: Vec<VendorProfile>,
+    control_socket: Option<PathBuf>,
+    hostname_handling: HostnameHandling,
+    hostname_conflict_policy: HostnameConflictPolicy,
+
+    clock: Arc<dyn Clock>,
 }
 
 impl Default for ServerBuilder {
@@ -36,10 +170,65 @@ impl Default for ServerBuilder {
             rebind_percent: DEFAULT_REBIND_PERCENT,
             renew_percent: DEFAULT_RENEW_PERCENT,
             lease_time: ONE_HOUR_SECS,
+            discover_coalesce_window: DEFAULT_COALESCE_WINDOW,
+            offer_timeout: DEFAULT_OFFER_TIMEOUT,
+            starvation_window: DEFAULT_STARVATION_WINDOW,
+            starvation_threshold: DEFAULT_STARVATION_THRESHOLD,
+            defensive_offer_timeout: DEFAULT_DEFENSIVE_OFFER_TIMEOUT,
+            defensive_mode: false,
+            server_identifier: Ipv4Addr::UNSPECIFIED,
+            interfaces: Vec::new(),
+            bind_address: SocketAddr::from((Ipv4Addr::UNSPECIFIED, SERVER_PORT)),
+            subnet_mask: None,
+            inform_unknown_subnets: false,
+            nak_unknown_renewals: true,
+            check_renewal_source: false,
+            option_overload: false,
+            relay_max_message_size: DEFAULT_RELAY_MAX_MESSAGE_SIZE,
+            socket_filter: true,
+            strict: false,
+            trusted_relays: Vec::new(),
+            auth_keys: HashMap::new(),
             calculates_times: false,
+            allow_foreign_pools: false,
             rebind_time: None,
+            synthesize_domain_search: true,
+            domain_search: None,
+            domain_name: None,
+            time_servers: None,
+            ntp_servers: None,
+            vendor_profiles: Vec::new(),
+            control_socket: None,
+            hostname_handling: HostnameHandling::default(),
+            hostname_conflict_policy: HostnameConflictPolicy::default(),
             pools: Vec::new(),
+            pool_lease_times: HashMap::new(),
+            pool_renew_rebind_times: HashMap::new(),
+            pool_options: HashMap::new(),
+            reservations: Vec::new(),
+            reservation_reload: None,
             renew_time: None,
+            state_file: None,
+            state_store: None,
+            require_persistence: false,
+            next_server: None,
+            boot_file: None,
+            audit_prober: None,
+            offer_prober: None,
+            handler_timeout: DEFAULT_HANDLER_TIMEOUT,
+            max_concurrent: DEFAULT_MAX_IN_FLIGHT_HANDLERS,
+            cache_limits: CacheLimits::default(),
+            log_control: None,
+            #[cfg(feature = "dhcpv6")]
+            v6_responder: None,
+            audit_sample_size: None,
+            audit_rate: DEFAULT_AUDIT_RATE,
+            reclaim_grace: DEFAULT_RECLAIM_GRACE,
+            reclaim_probation: DEFAULT_RECLAIM_PROBATION,
+            lease_retention: DEFAULT_LEASE_RETENTION,
+            history_resolution: DEFAULT_HISTORY_RESOLUTION,
+            history_window: DEFAULT_HISTORY_WINDOW,
+            clock: Arc::new(SystemClock),
         }
     }
 }
@@ -79,11 +268,455 @@ impl ServerBuilder {
         self
     }
 
+    /// Adds the pool with `name` handing out the addresses in `range`, like
+    /// "192.168.1.100-192.168.1.200". Without any pool, the server makes no
+    /// offers and only answers DHCPINFORMs and lease queries.
     pub fn with_pool(mut self, name: String, range: String) -> Self {
         self.pools.push((name, range));
         self
     }
 
+    /// Overrides the lease time of addresses from the pool with `name`. The
+    /// renewal (T1) and rebinding (T2) times of the pool are derived from it
+    /// using the configured percentages, unless set explicitly via
+    /// [`ServerBuilder::with_pool_times`].
+    pub fn with_pool_lease_time(mut self, name: String, time: u32) -> Self {
+        self.pool_lease_times.insert(name, time);
+        self
+    }
+
+    /// Overrides the renewal (T1) and rebinding (T2) times of addresses from
+    /// the pool with `name`.
+    pub fn with_pool_times(mut self, name: String, renew_time: u32, rebind_time: u32) -> Self {
+        self.pool_renew_rebind_times
+            .insert(name, (renew_time, rebind_time));
+        self
+    }
+
+    /// Overrides the RFC 868 time servers (option 4) handed out to clients of
+    /// the pool with `name`.
+    pub fn with_pool_time_servers(mut self, name: String, servers: Vec<Ipv4Addr>) -> Self {
+        self.pool_options.entry(name).or_default().time_servers = Some(servers);
+        self
+    }
+
+    /// Overrides the network time protocol servers (option 42) handed out to
+    /// clients of the pool with `name`.
+    pub fn with_pool_ntp_servers(mut self, name: String, servers: Vec<Ipv4Addr>) -> Self {
+        self.pool_options.entry(name).or_default().ntp_servers = Some(servers);
+        self
+    }
+
+    /// Reserves `ip_addr` for the client with `hardware_addr`. The address
+    /// must be part of a pool. Only this client is allocated the address,
+    /// and it is always allocated this address.
+    pub fn with_reservation(mut self, hardware_addr: HardwareAddr, ip_addr: Ipv4Addr) -> Self {
+        self.reservations.push(Reservation {
+            hardware_addr,
+            ip_addr,
+        });
+        self
+    }
+
+    /// Reloads the reservations with `reload` on
+    /// [`ControlRequest::ReloadConfig`](crate::ControlRequest::ReloadConfig),
+    /// which is refused without it. Leases conflicting with the new
+    /// reservations run to their expiry, see
+    /// [`ReservationConflict`](crate::ReservationConflict).
+    pub fn with_reservation_reload(
+        mut self,
+        reload: impl Fn() -> Result<Vec<Reservation>, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.reservation_reload = Some(Arc::new(reload));
+        self
+    }
+
+    pub fn with_server_identifier(mut self, addr: Ipv4Addr) -> Self {
+        self.server_identifier = addr;
+        self
+    }
+
+    /// Listens on the interface with `name`. Can be called multiple times to
+    /// listen on several interfaces, with messages, logs and stats
+    /// attributed to the interface they arrived on. Without any interface,
+    /// the server listens on all interfaces using a single socket.
+    pub fn with_interface(mut self, name: String) -> Self {
+        self.interfaces.push(name);
+        self
+    }
+
+    /// Sets the address and port the server listens on. Defaults to
+    /// 0.0.0.0:67.
+    pub fn with_bind_address(mut self, addr: SocketAddr) -> Self {
+        self.bind_address = addr;
+        self
+    }
+
+    /// Returns the address and port the server will listen on.
+    pub fn bind_address(&self) -> SocketAddr {
+        self.bind_address
+    }
+
+    /// Sets the subnet mask (option 1) of the managed subnet. Every pool
+    /// must lie within a single subnet with this mask.
+    pub fn with_subnet_mask(mut self, mask: Ipv4Addr) -> Self {
+        self.subnet_mask = Some(mask);
+        self
+    }
+
+    /// Controls if DHCPINFORMs from subnets no pool is part of are answered
+    /// with the global options. Such messages are ignored by default.
+    pub fn with_inform_unknown_subnets(mut self, answer: bool) -> Self {
+        self.inform_unknown_subnets = answer;
+        self
+    }
+
+    /// Controls if renewals ('ciaddr' set, no server identifier) of
+    /// addresses on a subnet the server serves, but for which the client
+    /// holds no lease, are answered with a DHCPNAK. The client then starts
+    /// over instead of retrying until its lease runs out. Renewals of
+    /// addresses on other subnets are always ignored. Enabled by default.
+    pub fn with_nak_unknown_renewals(mut self, nak: bool) -> Self {
+        self.nak_unknown_renewals = nak;
+        self
+    }
+
+    /// Controls if renewals which weren't relayed are only accepted when
+    /// sent from 'ciaddr'. A client in the RENEWING or REBINDING state
+    /// already uses its leased address, so other sources are likely
+    /// spoofed. Disabled by default.
+    pub fn with_renewal_source_check(mut self, check: bool) -> Self {
+        self.check_renewal_source = check;
+        self
+    }
+
+    /// Controls if options of replies exceeding the maximum message size
+    /// are moved into the unused 'file' and 'sname' fields (option
+    /// overload, option 52) before options are dropped. Disabled by
+    /// default, as some clients don't look for options in these fields.
+    pub fn with_option_overload(mut self, overload: bool) -> Self {
+        self.option_overload = overload;
+        self
+    }
+
+    /// Sets the maximum reply size for relayed requests which don't carry a
+    /// maximum DHCP message size (option 57). Defaults to 1500 octets.
+    /// Requests received directly without the option get replies of at most
+    /// 576 octets.
+    pub fn with_relay_max_message_size(mut self, size: u16) -> Self {
+        self.relay_max_message_size = size;
+        self
+    }
+
+    /// Enables strict mode, which drops messages deviating from clean
+    /// RFC 2131 DHCP instead of tolerating them: BOOTP messages, messages
+    /// without client identifier, hardware types other than Ethernet,
+    /// relayed messages not received from a trusted relay and 'secs' values
+    /// above an hour. Drops are counted per
+    /// [`StrictViolation`](crate::server::StrictViolation). Disabled by
+    /// default.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Drops datagrams which can't be DHCP messages (too short, invalid
+    /// 'op' or no magic cookie) with a BPF filter in the kernel, so floods
+    /// of junk don't wake up the listeners. Defaults to true, only has an
+    /// effect on Linux.
+    ///
+    /// Filtered datagrams never reach the server, so they aren't counted as
+    /// received, and the `truncated` and `bad_cookie` parse errors stay at
+    /// zero for them. Disable the filter to see these counters.
+    pub fn with_socket_filter(mut self, socket_filter: bool) -> Self {
+        self.socket_filter = socket_filter;
+        self
+    }
+
+    /// Sets the relay agents relayed messages are accepted from in strict
+    /// mode, see [`ServerBuilder::with_strict`]. DHCPLEASEQUERYs are only
+    /// answered for these relay agents, regardless of strict mode.
+    pub fn with_trusted_relays(mut self, relays: Vec<Ipv4Addr>) -> Self {
+        self.trusted_relays = relays;
+        self
+    }
+
+    /// Adds the shared secret `key` with `secret_id` for RFC 3118 delayed
+    /// authentication. Once a key is set, messages carrying authentication
+    /// information are only handled if their HMAC-MD5 verifies, and only
+    /// then does their replay detection value count.
+    pub fn with_auth_key(mut self, secret_id: u32, key: Vec<u8>) -> Self {
+        self.auth_keys.insert(secret_id, key);
+        self
+    }
+
+    /// Sets the window in which repeated DHCPDISCOVERs of the same client
+    /// reuse the already computed offer. Defaults to 2 seconds.
+    pub fn with_discover_coalesce_window(mut self, window: Duration) -> Self {
+        self.discover_coalesce_window = window;
+        self
+    }
+
+    /// Allows serving pools for which no interface owns an address in the
+    /// pool's subnet. This is required for relay-only deployments. When
+    /// disabled (the default), such pools are rejected when building the
+    /// server, otherwise a warning is emitted.
+    pub fn with_allow_foreign_pools(mut self, allow: bool) -> Self {
+        self.allow_foreign_pools = allow;
+        self
+    }
+
+    /// Sets how long an offered address stays reserved for the client
+    /// without a matching DHCPREQUEST. Defaults to 60 seconds.
+    pub fn with_offer_timeout(mut self, timeout: Duration) -> Self {
+        self.offer_timeout = timeout;
+        self
+    }
+
+    /// Sets the file leases and pending offers are persisted to on shutdown.
+    /// If the file exists, the state is restored when building the server.
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(path);
+        self
+    }
+
+    /// Persists the state to `store` instead of the state file. The state
+    /// is still restored from the state file, if one is set.
+    pub fn with_state_store(mut self, store: Arc<dyn StateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// Sets whether new leases are refused while the state can't be
+    /// persisted. Clients are then only offered the addresses already bound
+    /// to them. Otherwise, leases are granted from memory and persisted once
+    /// writing the state works again. Defaults to `false`.
+    pub fn with_require_persistence(mut self, require: bool) -> Self {
+        self.require_persistence = require;
+        self
+    }
+
+    /// Sets the sliding window in which new (never-seen) clients are counted
+    /// to detect starvation attacks. Defaults to 10 seconds.
+    pub fn with_starvation_window(mut self, window: Duration) -> Self {
+        self.starvation_window = window;
+        self
+    }
+
+    /// Sets the number of new clients within the starvation window above
+    /// which a starvation attack is assumed. Defaults to 50.
+    pub fn with_starvation_threshold(mut self, threshold: usize) -> Self {
+        self.starvation_threshold = threshold;
+        self
+    }
+
+    /// Enables the defensive mode while a starvation attack is assumed. In
+    /// defensive mode, offers expire after the (short) defensive offer
+    /// timeout, so that addresses are only consumed by clients which follow
+    /// up with a DHCPREQUEST. Disabled by default.
+    pub fn with_defensive_mode(mut self, enabled: bool) -> Self {
+        self.defensive_mode = enabled;
+        self
+    }
+
+    /// Sets how hostnames sent by clients are handled. By default, they are
+    /// sanitized into a valid DNS label before they are stored.
+    pub fn with_hostname_handling(mut self, handling: HostnameHandling) -> Self {
+        self.hostname_handling = handling;
+        self
+    }
+
+    /// Sets what happens if a client claims a hostname another client
+    /// already holds. By default, the name gets a numeric suffix.
+    pub fn with_hostname_conflict_policy(mut self, policy: HostnameConflictPolicy) -> Self {
+        self.hostname_conflict_policy = policy;
+        self
+    }
+
+    /// Sets the offer timeout used in defensive mode. Defaults to 5 seconds.
+    pub fn with_defensive_offer_timeout(mut self, timeout: Duration) -> Self {
+        self.defensive_offer_timeout = timeout;
+        self
+    }
+
+    /// Enables the startup address-conflict audit. When the server starts,
+    /// the free addresses of all pools are probed in the background using
+    /// `prober`. Addresses of responding hosts are put on probation and are
+    /// not handed out. The server serves requests during the sweep.
+    pub fn with_startup_audit(mut self, prober: Arc<dyn ConflictProber>) -> Self {
+        self.audit_prober = Some(prober);
+        self
+    }
+
+    /// Probes newly allocated addresses with `prober` before offering them
+    /// (RFC 2131 Section 2.2). Addresses of responding hosts are put on
+    /// probation and the client isn't answered, it retries with a new
+    /// DHCPDISCOVER. By default, addresses are offered without probing.
+    pub fn with_offer_probe(mut self, prober: Arc<dyn ConflictProber>) -> Self {
+        self.offer_prober = Some(prober);
+        self
+    }
+
+    /// Sets the time a message handler may take. Handlers taking longer,
+    /// for example because of a hanging probe, are aborted and addresses
+    /// they allocated are handed back. Defaults to 5 seconds.
+    pub fn with_handler_timeout(mut self, timeout: Duration) -> Self {
+        self.handler_timeout = timeout;
+        self
+    }
+
+    /// Sets how many message handlers may run at the same time, at least
+    /// one. Further messages wait in the socket buffer until a handler
+    /// finishes and are dropped by the kernel once it is full. Defaults to
+    /// [`DEFAULT_MAX_IN_FLIGHT_HANDLERS`].
+    pub fn with_max_concurrent(mut self, max: usize) -> Self {
+        self.max_concurrent = max.max(1);
+        self
+    }
+
+    /// Sets the maximum number of entries of the per-client caches, which
+    /// are filled by unauthenticated messages. Once a cache is full, its
+    /// least recently used entry is evicted. Each cache holds up to
+    /// [`DEFAULT_CACHE_LIMIT`](crate::server::DEFAULT_CACHE_LIMIT) entries
+    /// by default.
+    pub fn with_cache_limits(mut self, limits: CacheLimits) -> Self {
+        self.cache_limits = limits;
+        self
+    }
+
+    /// Only probe a random sample of `size` free addresses per pool during
+    /// the startup audit. By default, all free addresses are probed.
+    pub fn with_audit_sample_size(mut self, size: usize) -> Self {
+        self.audit_sample_size = Some(size);
+        self
+    }
+
+    /// Sets the number of probes per second sent by the startup audit,
+    /// between one and [`MAX_AUDIT_RATE`](crate::server::MAX_AUDIT_RATE).
+    /// Defaults to 10.
+    pub fn with_audit_rate(mut self, rate: u32) -> Self {
+        self.audit_rate = rate.clamp(1, MAX_AUDIT_RATE);
+        self
+    }
+
+    /// Sets the time after the end of a lease before its address is
+    /// reclaimed, unless the client was seen in the meantime. Defaults to
+    /// 60 seconds.
+    pub fn with_reclaim_grace(mut self, grace: Duration) -> Self {
+        self.reclaim_grace = grace;
+        self
+    }
+
+    /// Sets the time a reclaimed address is only handed out to the client
+    /// it was reclaimed from. Defaults to 5 minutes.
+    pub fn with_reclaim_probation(mut self, probation: Duration) -> Self {
+        self.reclaim_probation = probation;
+        self
+    }
+
+    /// Sets the time released and reclaimed leases are kept as records
+    /// after they ended, for example to answer when a client was last seen.
+    /// Defaults to one day.
+    pub fn with_lease_retention(mut self, retention: Duration) -> Self {
+        self.lease_retention = retention;
+        self
+    }
+
+    /// Sets the interval between two utilization samples of the pools.
+    /// Defaults to one minute.
+    pub fn with_history_resolution(mut self, resolution: Duration) -> Self {
+        self.history_resolution = resolution;
+        self
+    }
+
+    /// Sets the time span covered by the utilization history of the pools.
+    /// Defaults to 24 hours.
+    pub fn with_history_window(mut self, window: Duration) -> Self {
+        self.history_window = window;
+        self
+    }
+
+    /// Sets the clock used for offer and lease expiry and the starvation
+    /// detector. Defaults to the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets the server PXE clients load the boot file from ('siaddr').
+    pub fn with_next_server(mut self, addr: Ipv4Addr) -> Self {
+        self.next_server = Some(addr);
+        self
+    }
+
+    /// Sets the boot file name ('file') handed out to PXE clients.
+    pub fn with_boot_file(mut self, file: String) -> Self {
+        self.boot_file = Some(file);
+        self
+    }
+
+    pub fn with_domain_name(mut self, domain_name: String) -> Self {
+        self.domain_name = Some(domain_name);
+        self
+    }
+
+    pub fn with_domain_search(mut self, domains: Vec<String>) -> Self {
+        self.domain_search = Some(domains);
+        self
+    }
+
+    /// Controls if the server synthesizes a domain search list (option 119)
+    /// from the configured domain name (option 15) when the client requests
+    /// it, but no explicit list is configured. Enabled by default.
+    pub fn with_synthesize_domain_search(mut self, synthesize: bool) -> Self {
+        self.synthesize_domain_search = synthesize;
+        self
+    }
+
+    /// Sets the RFC 868 time servers (option 4) handed out to clients
+    /// requesting them.
+    pub fn with_time_servers(mut self, servers: Vec<Ipv4Addr>) -> Self {
+        self.time_servers = Some(servers);
+        self
+    }
+
+    /// Sets the network time protocol servers (option 42) handed out to
+    /// clients requesting them.
+    pub fn with_ntp_servers(mut self, servers: Vec<Ipv4Addr>) -> Self {
+        self.ntp_servers = Some(servers);
+        self
+    }
+
+    /// Adds a vendor profile, which hands out vendor options (43 or 125) to
+    /// matching clients. The first matching profile is used, see
+    /// [`VendorProfile`].
+    pub fn with_vendor_profile(mut self, profile: VendorProfile) -> Self {
+        self.vendor_profiles.push(profile);
+        self
+    }
+
+    /// Serves the control socket at `path` while the server is running, see
+    /// [`ControlRequest`](crate::ControlRequest).
+    pub fn with_control_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.control_socket = Some(path.into());
+        self
+    }
+
+    /// Allows changing the log filter through the control socket, see
+    /// [`ControlRequest::SetLogFilter`](crate::ControlRequest::SetLogFilter).
+    pub fn with_log_control(mut self, log_control: Arc<LogControl>) -> Self {
+        self.log_control = Some(log_control);
+        self
+    }
+
+    /// Answers stateless DHCPv6 INFORMATION-REQUESTs with `responder` on the
+    /// interfaces the server listens on.
+    #[cfg(feature = "dhcpv6")]
+    pub fn with_v6_responder(mut self, responder: crate::v6::Responder) -> Self {
+        self.v6_responder = Some(Arc::new(responder));
+        self
+    }
+
     pub fn build(self) -> Result<Server, ServerBuilderError> {
         // Determine if the server should send the T1 and T2 time
         let send_times =
@@ -98,7 +731,7 @@ impl ServerBuilder {
         }
 
         // Make sure that T1 < T2
-        if self.rebind_percent >= self.renew_percent {
+        if self.renew_percent >= self.rebind_percent {
             return Err(ServerBuilderError::InvalidPercent);
         }
 
@@ -111,21 +744,311 @@ impl ServerBuilder {
             .renew_time
             .unwrap_or((self.lease_time as f64 * self.renew_percent) as u32);
 
-        // Check that there is at least one pool configured
-        if self.pools.is_empty() {
-            return Err(ServerBuilderError::InvalidPoolCount);
+        if let Some(profile) = self.vendor_profiles.iter().find(|p| !p.fits()) {
+            return Err(ServerBuilderError::VendorProfileTooLong(
+                profile.enterprise_number,
+            ));
         }
 
         // Parse the pools
-        // let pools = Vec::new();
+        let mut pools = Vec::new();
+        for pool in self.pools {
+            pools.push(Pool::try_from(pool)?);
+        }
+
+        // Apply the lease times of pools with overrides
+        for name in self
+            .pool_lease_times
+            .keys()
+            .chain(self.pool_renew_rebind_times.keys())
+        {
+            let pool = match pools.iter_mut().find(|pool| pool.name() == name) {
+                Some(pool) => pool,
+                None => return Err(ServerBuilderError::UnknownPool(name.clone())),
+            };
+
+            let lease_time = self
+                .pool_lease_times
+                .get(name)
+                .copied()
+                .unwrap_or(self.lease_time);
+
+            let (renew_time, rebind_time) = match self.pool_renew_rebind_times.get(name) {
+                Some(times) => *times,
+                None => (
+                    (lease_time as f64 * self.renew_percent) as u32,
+                    (lease_time as f64 * self.rebind_percent) as u32,
+                ),
+            };
+
+            if renew_time >= rebind_time {
+                return Err(ServerBuilderError::InvalidPoolTimes(name.clone()));
+            }
+
+            pool.set_times(LeaseTimes {
+                lease_time,
+                renew_time,
+                rebind_time,
+            });
+        }
+
+        // Apply the options of pools with overrides
+        for (name, options) in self.pool_options {
+            match pools.iter_mut().find(|pool| pool.name() == name) {
+                Some(pool) => pool.set_options(options),
+                None => return Err(ServerBuilderError::UnknownPool(name)),
+            }
+        }
+
+        reservations::assign(&mut pools, &self.reservations)?;
+
+        // Make sure no pool crosses a subnet boundary
+        if let Some(mask) = self.subnet_mask {
+            for pool in &pools {
+                let subnet = Ipv4Net::with_netmask(pool.range().start(), mask)?;
+
+                if !subnet.contains(&pool.range().end()) {
+                    return Err(ServerBuilderError::PoolCrossesSubnet {
+                        pool: pool.name().to_string(),
+                        subnet,
+                    });
+                }
+            }
+        }
+
+        // Make sure the pools are served on a network this host is part of
+        let foreign = foreign_pools(&pools, &lib_ifs::if_addrs()?, &self.interfaces);
+        if !foreign.is_empty() {
+            if !self.allow_foreign_pools {
+                return Err(ServerBuilderError::ForeignPools(foreign));
+            }
+
+            warn!(
+                target: DAEMON_TARGET,
+                pools = ?foreign,
+                "no interface owns an address in the subnet of pools"
+            );
+        }
+
+        // Clients in the RENEWING state unicast to the server identifier and
+        // expect the reply from it
+        let bind_ip = self.bind_address.ip();
+        if !bind_ip.is_unspecified()
+            && !self.server_identifier.is_unspecified()
+            && bind_ip != self.server_identifier
+        {
+            warn!(
+                target: DAEMON_TARGET,
+                bind_address = %bind_ip,
+                server_identifier = %self.server_identifier,
+                "bind address differs from the server identifier, unicast renewals won't be answered"
+            );
+        }
+
+        // Load the state persisted on the last shutdown
+        let persisted = match &self.state_file {
+            Some(path) => PersistedState::load(path)?,
+            None => None,
+        };
+
+        let store = self.state_store.or_else(|| {
+            let path = self.state_file.clone()?;
+            Some(Arc::new(FileStore(path)) as Arc<dyn StateStore>)
+        });
+
+        let config = ServerConfig {
+            server_identifier: self.server_identifier,
+            interfaces: self.interfaces,
+            bind_address: self.bind_address,
+            subnet_mask: self.subnet_mask,
+            inform_unknown_subnets: self.inform_unknown_subnets,
+            nak_unknown_renewals: self.nak_unknown_renewals,
+            check_renewal_source: self.check_renewal_source,
+            option_overload: self.option_overload,
+            relay_max_message_size: self.relay_max_message_size,
+            socket_filter: self.socket_filter,
+            strict: self.strict,
+            trusted_relays: self.trusted_relays,
+            auth_keys: self.auth_keys,
+            state_file: self.state_file,
+            require_persistence: self.require_persistence,
+            offer_timeout: self.offer_timeout,
+            handler_timeout: self.handler_timeout,
+            max_concurrent: self.max_concurrent,
+            cache_limits: self.cache_limits,
+            defensive_mode: self.defensive_mode,
+            defensive_offer_timeout: self.defensive_offer_timeout,
+            audit_sample_size: self.audit_sample_size,
+            audit_rate: self.audit_rate,
+            reclaim_grace: self.reclaim_grace,
+            reclaim_probation: self.reclaim_probation,
+            lease_retention: self.lease_retention,
+            lease_time: self.lease_time,
+            send_times,
+            rebind_time,
+            renew_time,
+            synthesize_domain_search: self.synthesize_domain_search,
+            domain_search: self.domain_search,
+            domain_name: self.domain_name,
+            time_servers: self.time_servers,
+            ntp_servers: self.ntp_servers,
+            vendor_profiles: self.vendor_profiles,
+            control_socket: self.control_socket,
+            hostname_handling: self.hostname_handling,
+            hostname_conflict_policy: self.hostname_conflict_policy,
+        };
+
+        let mut builder = MessageBuilder::new(
+            HardwareAddr::default(),
+            None,
+            MINIMUM_LEGAL_MAX_MESSAGE_SIZE,
+        );
+
+        if let Some(next_server) = self.next_server {
+            builder = builder.with_next_server(next_server);
+        }
+
+        if let Some(boot_file) = self.boot_file {
+            builder = builder.with_boot_file(boot_file);
+        }
+
+        // Transactions outlive their offer, so that expired offers still
+        // complete them
+        let limits = config.cache_limits;
+        let transaction_ttl = config.offer_timeout.saturating_mul(2);
+
+        let state = ServerState {
+            discover_cache: Mutex::new(DiscoverCache::new(
+                self.discover_coalesce_window,
+                limits.discover_cache,
+            )),
+            offers: Mutex::new(OfferCache::new(limits.offers)),
+            starvation: Mutex::new(StarvationDetector::new(
+                self.starvation_window,
+                self.starvation_threshold,
+                limits.known_clients,
+            )),
+            replay: Mutex::new(ReplayDetector::new(limits.replay_counters)),
+            reservations: Mutex::new(self.reservations),
+            transactions: Mutex::new(BoundedTtlMap::new(limits.transactions, transaction_ttl)),
+            store,
+            persistence: PersistenceHealth::default(),
+            leases: Mutex::new(HashMap::new()),
+            bindings: Mutex::new(BindingIndex::default()),
+            audit: AuditProgress::default(),
+            offer_prober: self.offer_prober,
+            stats: Arc::new(ServerStats::with_history(UtilizationHistory::new(
+                self.history_resolution,
+                self.history_window,
+            ))),
+            pools: Mutex::new(pools),
+            clock: self.clock,
+            builder,
+            config,
+        };
+
+        if let Some(persisted) = persisted {
+            state.restore(persisted, state.clock.now_system());
+        }
 
         Ok(Server {
+            audit_prober: self.audit_prober,
+            log_control: self.log_control,
+            reservation_reload: self.reservation_reload,
+            #[cfg(feature = "dhcpv6")]
+            v6_responder: self.v6_responder,
+            shutdown: Arc::new(Notify::new()),
+            service: DhcpService::new(Arc::new(state)),
             is_running: false,
-            config: ServerConfig {
-                send_times,
-                rebind_time,
-                renew_time,
-            },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(range: &str) -> ServerBuilder {
+        ServerBuilder::new()
+            .with_pool("lan".into(), range.into())
+            .with_subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+            .with_allow_foreign_pools(true)
+    }
+
+    #[test]
+    fn test_pool_within_subnet() {
+        assert!(builder("192.168.1.100-192.168.1.200").build().is_ok());
+    }
+
+    #[test]
+    fn test_pool_crossing_subnet() {
+        match builder("192.168.1.200-192.168.2.10").build() {
+            Err(ServerBuilderError::PoolCrossesSubnet { pool, subnet }) => {
+                assert_eq!(pool, "lan");
+                assert_eq!(subnet.network(), Ipv4Addr::new(192, 168, 1, 0));
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("pool crossing the subnet boundary was accepted"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_subnet_mask() {
+        let result = builder("192.168.1.100-192.168.1.200")
+            .with_subnet_mask(Ipv4Addr::new(255, 0, 255, 0))
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(ServerBuilderError::InvalidSubnetMask(_))
+        ));
+    }
+
+    #[test]
+    fn test_lease_times_of_unknown_pool() {
+        let result = builder("192.168.1.100-192.168.1.200")
+            .with_pool_lease_time("guest".into(), 600)
+            .build();
+
+        assert!(matches!(result, Err(ServerBuilderError::UnknownPool(name)) if name == "guest"));
+    }
+
+    #[test]
+    fn test_renew_percent_below_rebind_percent() {
+        let result = builder("192.168.1.100-192.168.1.200")
+            .with_renew_percent(0.9)
+            .with_rebind_percent(0.8)
+            .build();
+        assert!(matches!(result, Err(ServerBuilderError::InvalidPercent)));
+
+        let result = builder("192.168.1.100-192.168.1.200")
+            .with_renew_percent(0.8)
+            .with_rebind_percent(0.8)
+            .build();
+        assert!(matches!(result, Err(ServerBuilderError::InvalidPercent)));
+
+        // The defaults of 50% and 87.5% are valid
+        assert!(builder("192.168.1.100-192.168.1.200").build().is_ok());
+    }
+
+    #[test]
+    fn test_audit_rate_is_clamped() {
+        let server = builder("192.168.1.100-192.168.1.200")
+            .with_audit_rate(u32::MAX)
+            .build()
+            .unwrap();
+        assert_eq!(server.service().state.config.audit_rate, MAX_AUDIT_RATE);
+
+        let server = builder("192.168.1.100-192.168.1.200")
+            .with_audit_rate(0)
+            .build()
+            .unwrap();
+        assert_eq!(server.service().state.config.audit_rate, 1);
+    }
+
+    #[test]
+    fn test_default_builder_is_valid() {
+        let server = ServerBuilder::default().build().unwrap();
+        assert!(server.service().state.pools.lock().unwrap().is_empty());
+    }
+}
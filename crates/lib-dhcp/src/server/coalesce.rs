@@ -0,0 +1,58 @@
+use std::{
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+
+use crate::bounded::BoundedTtlMap;
+
+/// Default window in which repeated DHCPDISCOVERs of the same client are
+/// coalesced.
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Small TTL cache in front of the DHCPDISCOVER handling. Broken firmware
+/// sometimes sends bursts of DHCPDISCOVERs (with incrementing xids). Within
+/// the configured window, repeated DHCPDISCOVERs of the same client reuse
+/// the already computed offer.
+#[derive(Debug)]
+pub(crate) struct DiscoverCache {
+    entries: BoundedTtlMap<String, Ipv4Addr>,
+}
+
+impl DiscoverCache {
+    /// Creates a cache of at most `capacity` offers, each reused for
+    /// `window`.
+    pub fn new(window: Duration, capacity: usize) -> Self {
+        Self {
+            entries: BoundedTtlMap::new(capacity, window),
+        }
+    }
+
+    /// Returns the cached offered address for the client with `key` if the
+    /// offer is still within the coalescing window.
+    pub fn get(&mut self, key: &str, now: Instant) -> Option<Ipv4Addr> {
+        self.entries.get(key, now).copied()
+    }
+
+    /// Drops the cached offer of the client with `key`.
+    pub fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+
+    /// Drops all cached offers, for example because the reservations
+    /// changed.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Caches the offered address for the client with `key`. Expired entries
+    /// are purged on every insert to keep the cache small.
+    pub fn insert(&mut self, key: String, yiaddr: Ipv4Addr, now: Instant) {
+        self.entries.purge_expired(now);
+        self.entries.insert(key, yiaddr, now);
+    }
+
+    /// Returns the number of offers evicted because the cache was full.
+    pub fn evictions(&self) -> u64 {
+        self.entries.evictions()
+    }
+}
@@ -1,5 +1,200 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::server::{
+    hostname::{HostnameConflictPolicy, HostnameHandling},
+    limits::CacheLimits,
+    vendor::VendorProfile,
+};
+
+/// Lease time handed out together with the renewal (T1) and rebinding (T2)
+/// times, all in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LeaseTimes {
+    pub lease_time: u32,
+    pub renew_time: u32,
+    pub rebind_time: u32,
+}
+
+impl LeaseTimes {
+    /// Returns these times for a lease shortened to `lease_time`. T1 and T2
+    /// keep their ratio to the lease time.
+    pub fn truncated(self, lease_time: u32) -> LeaseTimes {
+        if lease_time >= self.lease_time {
+            return self;
+        }
+
+        let scale = |time: u32| (time as u64 * lease_time as u64 / self.lease_time as u64) as u32;
+        LeaseTimes {
+            lease_time,
+            renew_time: scale(self.renew_time),
+            rebind_time: scale(self.rebind_time),
+        }
+    }
+}
+
+/// Configuration options which can be set per pool. Unset options fall back
+/// to the server-wide ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PoolOptions {
+    /// RFC 868 time servers (option 4).
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+
+    /// Network time protocol servers (option 42).
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+}
+
+impl PoolOptions {
+    /// Returns these options with unset ones taken from `fallback`.
+    pub fn or(self, fallback: &PoolOptions) -> PoolOptions {
+        PoolOptions {
+            time_servers: self.time_servers.or_else(|| fallback.time_servers.clone()),
+            ntp_servers: self.ntp_servers.or_else(|| fallback.ntp_servers.clone()),
+        }
+    }
+}
+
 pub(crate) struct ServerConfig {
+    /// Address the server identifies itself with (option 54).
+    pub server_identifier: Ipv4Addr,
+
+    /// Interfaces to listen on, all interfaces if empty.
+    pub interfaces: Vec<String>,
+
+    /// Address and port the listeners are bound to.
+    pub bind_address: SocketAddr,
+
+    /// Subnet mask (option 1) of the managed subnet.
+    pub subnet_mask: Option<Ipv4Addr>,
+
+    /// Answer DHCPINFORMs from subnets we don't serve with the global
+    /// options instead of ignoring them.
+    pub inform_unknown_subnets: bool,
+
+    /// Answer renewals of addresses on our subnets without a lease with a
+    /// DHCPNAK instead of ignoring them.
+    pub nak_unknown_renewals: bool,
+
+    /// Drop renewals which weren't relayed and not sent from 'ciaddr'.
+    pub check_renewal_source: bool,
+
+    /// Move options which don't fit into the options field of a reply into
+    /// the 'file' and 'sname' fields.
+    pub option_overload: bool,
+
+    /// Drop datagrams which can't be DHCP messages in the kernel.
+    pub socket_filter: bool,
+
+    /// Maximum reply size for relayed requests without option 57.
+    pub relay_max_message_size: u16,
+
+    /// Drop messages deviating from clean RFC 2131 DHCP instead of
+    /// tolerating them, see [`StrictViolation`](crate::server::StrictViolation).
+    pub strict: bool,
+
+    /// Relay agents relayed messages are accepted from in strict mode. These
+    /// are also the only sources DHCPLEASEQUERYs are answered for.
+    pub trusted_relays: Vec<Ipv4Addr>,
+
+    /// Shared secrets by secret ID, used to verify authenticated messages
+    /// (RFC 3118 delayed authentication).
+    pub auth_keys: HashMap<u32, Vec<u8>>,
+
+    /// File the leases and pending offers are persisted to on shutdown.
+    pub state_file: Option<PathBuf>,
+
+    /// Refuse new leases while the state can't be persisted.
+    pub require_persistence: bool,
+
+    /// Time an offered address stays reserved without a DHCPREQUEST.
+    pub offer_timeout: Duration,
+
+    /// Time after which a message handler is aborted.
+    pub handler_timeout: Duration,
+
+    /// Number of message handlers which may run at the same time.
+    pub max_concurrent: usize,
+
+    /// Maximum number of entries of the per-client caches.
+    pub cache_limits: CacheLimits,
+
+    /// Switch to defensive mode when a starvation attack is detected.
+    pub defensive_mode: bool,
+
+    /// Offer timeout used while in defensive mode.
+    pub defensive_offer_timeout: Duration,
+
+    /// Maximum number of free addresses probed per pool by the startup
+    /// audit, all if [`None`].
+    pub audit_sample_size: Option<usize>,
+
+    /// Number of probes per second sent by the startup audit.
+    pub audit_rate: u32,
+
+    /// Time after the end of a lease before its address is reclaimed.
+    pub reclaim_grace: Duration,
+
+    /// Time a reclaimed address is only handed out to its previous holder.
+    pub reclaim_probation: Duration,
+
+    /// Time released and reclaimed leases are kept as records after they
+    /// ended.
+    pub lease_retention: Duration,
+
+    pub lease_time: u32,
     pub send_times: bool,
     pub rebind_time: u32,
     pub renew_time: u32,
+
+    /// Domain name (option 15) handed out to clients.
+    pub domain_name: Option<String>,
+
+    /// Explicit domain search list (option 119) handed out to clients.
+    pub domain_search: Option<Vec<String>>,
+
+    /// Synthesize a domain search list from the domain name when a client
+    /// requests option 119 but no explicit list is configured.
+    pub synthesize_domain_search: bool,
+
+    /// RFC 868 time servers (option 4) handed out to clients.
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+
+    /// Network time protocol servers (option 42) handed out to clients.
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+
+    /// Vendor options (43 and 125) handed out to matching clients.
+    pub vendor_profiles: Vec<VendorProfile>,
+
+    /// Path of the control socket, no control socket is served if [`None`].
+    pub control_socket: Option<PathBuf>,
+
+    /// Handling of the hostnames sent by clients.
+    pub hostname_handling: HostnameHandling,
+
+    /// Handling of hostnames claimed by more than one client.
+    pub hostname_conflict_policy: HostnameConflictPolicy,
+}
+
+impl ServerConfig {
+    /// Returns the server-wide lease times, used for pools without
+    /// overrides.
+    pub fn default_times(&self) -> LeaseTimes {
+        LeaseTimes {
+            lease_time: self.lease_time,
+            renew_time: self.renew_time,
+            rebind_time: self.rebind_time,
+        }
+    }
+
+    /// Returns the server-wide options, used for pools without overrides.
+    pub fn default_options(&self) -> PoolOptions {
+        PoolOptions {
+            time_servers: self.time_servers.clone(),
+            ntp_servers: self.ntp_servers.clone(),
+        }
+    }
 }
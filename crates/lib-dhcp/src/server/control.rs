@@ -0,0 +1,1303 @@
+use std::{
+    io,
+    net::Ipv4Addr,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
+};
+
+use binbuf::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    server::{
+        audit::AuditProgress,
+        effective::EffectiveConfig,
+        history::PoolHistory,
+        reservations::{ReservationError, ReservationReload},
+        state::ServerState,
+        stats::InterfaceCounters,
+        storage::ServerStorageError,
+    },
+    types::HardwareAddr,
+    LogControl, LogFilterError, DAEMON_TARGET,
+};
+
+/// Default path of the control socket of the server.
+pub const DEFAULT_CONTROL_SOCKET: &str = "/run/vulcan/dhcpd.sock";
+
+/// Maximum size of a single request or response frame.
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Magic starting every frame on a control socket.
+pub const CONTROL_MAGIC: [u8; 4] = *b"VCTL";
+
+/// Version of the control protocol spoken by this build. Peers only talk
+/// to each other if they speak the same version. Version 1 encoded the
+/// payloads with binbuf, version 2 encodes them as JSON.
+pub const CONTROL_PROTOCOL_VERSION: u8 = 2;
+
+/// Type of the error response of peers predating the frame header.
+const LEGACY_ERROR_RESPONSE: u8 = 4;
+
+/// Length of the frame header: magic, version and payload length.
+const FRAME_HEADER_LEN: usize = 9;
+
+#[derive(Debug, Error)]
+pub enum ControlError {
+    #[error("io error on control socket: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("buffer error: {0}")]
+    BufferError(#[from] BufferError),
+
+    #[error("invalid control message: {0}")]
+    Payload(#[from] serde_json::Error),
+
+    #[error("frame of {0} bytes exceeds the maximum frame size")]
+    FrameTooLarge(usize),
+
+    #[error("invalid frame magic, the peer doesn't speak the control protocol or is outdated")]
+    InvalidMagic,
+
+    #[error(
+        "peer speaks control protocol version {remote}, this build speaks version {local}, {}",
+        upgrade_hint(*.local, *.remote)
+    )]
+    UnsupportedVersion { local: u8, remote: u8 },
+
+    #[error("connection closed within a frame")]
+    Truncated,
+
+    #[error("connection closed before a response was received")]
+    Closed,
+}
+
+/// Reason a control request couldn't be served, sent to the peer as
+/// [`ControlResponse::Error`]. Unlike [`ControlError`] these carry error
+/// codes, as they end up in front of operators.
+#[derive(Debug, Error)]
+pub enum ControlRequestError {
+    #[error("{code} {0}", code = self.code())]
+    Protocol(#[from] ControlError),
+
+    #[error("{code} the daemon speaks a newer control protocol, upgrade vulcan-ctl", code = self.code())]
+    OutdatedPeer,
+
+    #[error("{code} log filter is not runtime-tunable", code = self.code())]
+    LogFilterNotTunable,
+
+    #[error("{code} {0}", code = self.code())]
+    LogFilter(#[from] LogFilterError),
+
+    #[error("{code} reloading the configuration is not supported", code = self.code())]
+    ReloadNotSupported,
+
+    #[error("{code} failed to reload the configuration: {0}", code = self.code())]
+    Reload(String),
+
+    #[error("{code} {0}", code = self.code())]
+    Reservation(#[from] ReservationError),
+
+    #[error("{code} {0}", code = self.code())]
+    Persist(#[from] ServerStorageError),
+
+    #[error("{code} failed to render the configuration: {0}", code = self.code())]
+    Config(#[from] serde_json::Error),
+
+    #[error("{code} unknown pool '{0}'", code = self.code())]
+    UnknownPool(String),
+
+    #[error("{code} {0} is not supported by the client", code = self.code())]
+    NotSupportedByClient(String),
+}
+
+error_codes!(ControlRequestError {
+    Protocol => "VULCAN-K101",
+    OutdatedPeer => "VULCAN-K102",
+    LogFilterNotTunable => "VULCAN-K103",
+    LogFilter => "VULCAN-K104",
+    ReloadNotSupported => "VULCAN-K105",
+    Reload => "VULCAN-K106",
+    Reservation => "VULCAN-K107",
+    Persist => "VULCAN-K108",
+    Config => "VULCAN-K109",
+    UnknownPool => "VULCAN-K110",
+    NotSupportedByClient => "VULCAN-K111",
+});
+
+impl From<ControlRequestError> for ControlResponse {
+    fn from(err: ControlRequestError) -> Self {
+        Self::Error(err.to_string())
+    }
+}
+
+/// Request sent to the control socket of the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ControlRequest {
+    /// Lists all leases known to the server.
+    ListLeases,
+
+    /// Returns the utilization of all pools.
+    PoolStats,
+
+    /// Reloads the reservations of the server, answered with their status
+    /// like in [`ControlRequest::Status`].
+    ReloadConfig,
+
+    /// Releases the lease held by the client with the hardware address.
+    ReleaseLease(HardwareAddr),
+
+    /// Returns the effective configuration of the server.
+    EffectiveConfig,
+
+    /// Replaces the log filter, see [`parse_log_filter`](crate::parse_log_filter).
+    /// With `revert_after` (in seconds), the default filter is restored
+    /// once that time elapsed.
+    SetLogFilter {
+        filter: String,
+        revert_after: Option<u32>,
+    },
+
+    /// Returns an overview of the state of the daemon.
+    Status,
+
+    /// Returns the utilization history and lease churn of the pool with the
+    /// name.
+    PoolHistory(String),
+
+    /// Returns all counters in the Prometheus text exposition format, see
+    /// [`ServerStats::to_prometheus`](crate::ServerStats::to_prometheus).
+    Metrics,
+
+    /// Returns the message counters of each interface.
+    InterfaceStats,
+}
+
+/// Response of the server to a [`ControlRequest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ControlResponse {
+    Leases(Vec<LeaseEntry>),
+    PoolStats(Vec<PoolStat>),
+
+    /// Whether a lease was released.
+    Released(bool),
+
+    /// The effective configuration of the server as JSON.
+    Config(String),
+
+    /// The active log filter and the seconds until it reverts to the
+    /// default, if it does.
+    LogFilter {
+        filter: String,
+        revert_in: Option<u32>,
+    },
+
+    /// Status of the daemon as key value pairs, in display order.
+    Status(Vec<(String, String)>),
+
+    PoolHistory(PoolHistory),
+
+    /// All counters in the Prometheus text exposition format.
+    Metrics(String),
+
+    /// Message counters of each interface, sorted by interface name.
+    InterfaceStats(Vec<(String, InterfaceCounters)>),
+
+    /// The request couldn't be served.
+    Error(String),
+}
+
+/// A lease as reported by [`ControlRequest::ListLeases`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaseEntry {
+    pub hardware_addr: HardwareAddr,
+    pub ip_addr: Ipv4Addr,
+
+    /// Remaining lease time in seconds, zero if the lease expired.
+    pub remaining: u32,
+    pub hostname: Option<String>,
+}
+
+/// Utilization of a pool as reported by [`ControlRequest::PoolStats`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolStat {
+    pub name: String,
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+    pub free: u32,
+    pub probation: u32,
+}
+
+/// Answers requests received on a control socket. The server and the
+/// client (on its status socket) share the protocol, but each answers the
+/// requests it supports.
+pub(crate) trait ControlHandler: Send + Sync + 'static {
+    fn dispatch(&self, request: ControlRequest) -> ControlResponse;
+}
+
+/// Binds a control socket at `path`. A stale socket of a previous instance
+/// is removed first.
+pub(crate) fn bind_control_socket(path: &Path) -> io::Result<UnixListener> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    UnixListener::bind(path)
+}
+
+/// Accepts connections on `listener` until the task is aborted. Each
+/// connection is handled in its own task and may send any number of
+/// requests, which are answered in order.
+pub(crate) async fn serve_control<H: ControlHandler>(
+    handler: Arc<H>,
+    listener: UnixListener,
+) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = handle_control_connection(handler.as_ref(), stream).await {
+                warn!(target: DAEMON_TARGET, %err, "control connection failed");
+            }
+        });
+    }
+}
+
+/// Answers the requests received on `stream` until the peer closes the
+/// connection. Peers speaking another protocol version are answered with
+/// an error and disconnected.
+pub(crate) async fn handle_control_connection<H: ControlHandler>(
+    handler: &H,
+    mut stream: UnixStream,
+) -> Result<(), ControlError> {
+    loop {
+        let frame = match read_frame(&mut stream).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(ControlError::InvalidMagic) => {
+                write_unframed_error(&mut stream, ControlRequestError::OutdatedPeer).await?;
+                return Err(ControlError::InvalidMagic);
+            }
+            Err(err @ ControlError::UnsupportedVersion { local, remote }) => {
+                let response = ControlResponse::from(ControlRequestError::Protocol(err));
+                write_frame(&mut stream, &serde_json::to_vec(&response)?).await?;
+                return Err(ControlError::UnsupportedVersion { local, remote });
+            }
+            Err(err) => return Err(err),
+        };
+
+        let response = match serde_json::from_slice::<ControlRequest>(&frame) {
+            Ok(request) => {
+                debug!(target: DAEMON_TARGET, ?request, "received control request");
+                handler.dispatch(request)
+            }
+            Err(err) => ControlRequestError::Protocol(err.into()).into(),
+        };
+
+        write_frame(&mut stream, &serde_json::to_vec(&response)?).await?;
+    }
+
+    Ok(())
+}
+
+/// Answers [`ControlRequest::SetLogFilter`] using `log_control`, which is
+/// [`None`] if the daemon doesn't support changing the filter.
+pub(crate) fn set_log_filter(
+    log_control: Option<&Arc<LogControl>>,
+    filter: &str,
+    revert_after: Option<u32>,
+) -> ControlResponse {
+    let Some(log_control) = log_control else {
+        return ControlRequestError::LogFilterNotTunable.into();
+    };
+
+    let revert_after = revert_after.map(|secs| Duration::from_secs(secs.into()));
+
+    match log_control.set(filter, revert_after) {
+        Ok(status) => ControlResponse::LogFilter {
+            filter: status.filter,
+            revert_in: status.revert_in.map(|d| d.as_secs() as u32),
+        },
+        Err(err) => ControlRequestError::LogFilter(err).into(),
+    }
+}
+
+/// Returns the progress of the startup audit for [`ControlRequest::Status`],
+/// if an audit was started.
+fn audit_status(progress: &AuditProgress) -> Option<(String, String)> {
+    let total = progress.total.load(Ordering::Relaxed);
+    if total == 0 && !progress.is_finished() {
+        return None;
+    }
+
+    let state = if progress.is_finished() {
+        "finished"
+    } else {
+        "running"
+    };
+    let probed = progress.probed.load(Ordering::Relaxed);
+    let occupied = progress.occupied.load(Ordering::Relaxed);
+
+    Some((
+        String::from("audit"),
+        format!("{state}, {probed} of {total} probed, {occupied} occupied"),
+    ))
+}
+
+/// Returns the status entries of the log filter for [`ControlRequest::Status`].
+pub(crate) fn log_filter_status(log_control: Option<&Arc<LogControl>>) -> Vec<(String, String)> {
+    let Some(log_control) = log_control else {
+        return Vec::new();
+    };
+
+    let status = log_control.status();
+    let mut entries = vec![(String::from("log_filter"), status.filter)];
+
+    if let Some(revert_in) = status.revert_in {
+        entries.push((
+            String::from("log_revert_in"),
+            format!("{}s", revert_in.as_secs()),
+        ));
+    }
+
+    entries
+}
+
+/// Serves requests on the control socket of the server.
+pub(crate) struct ControlServer {
+    state: Arc<ServerState>,
+    log_control: Option<Arc<LogControl>>,
+    reservation_reload: Option<ReservationReload>,
+}
+
+impl ControlServer {
+    pub fn new(state: Arc<ServerState>, log_control: Option<Arc<LogControl>>) -> Self {
+        Self {
+            state,
+            log_control,
+            reservation_reload: None,
+        }
+    }
+
+    /// Reloads the reservations with `reload` on
+    /// [`ControlRequest::ReloadConfig`], which is refused without it.
+    pub fn with_reservation_reload(mut self, reload: Option<ReservationReload>) -> Self {
+        self.reservation_reload = reload;
+        self
+    }
+}
+
+impl ControlHandler for ControlServer {
+    fn dispatch(&self, request: ControlRequest) -> ControlResponse {
+        let now = self.state.clock.now_system();
+
+        match request {
+            ControlRequest::ListLeases => ControlResponse::Leases(self.list_leases(now)),
+            ControlRequest::PoolStats => ControlResponse::PoolStats(self.pool_stats()),
+            ControlRequest::ReloadConfig => match self.reload_reservations() {
+                Ok(()) => ControlResponse::Status(self.reservation_status()),
+                Err(err) => err.into(),
+            },
+            ControlRequest::ReleaseLease(addr) => {
+                if !self.state.revoke_lease(&addr) {
+                    return ControlResponse::Released(false);
+                }
+
+                match self.state.persist() {
+                    Ok(()) => ControlResponse::Released(true),
+                    Err(err) => ControlRequestError::Persist(err).into(),
+                }
+            }
+            ControlRequest::EffectiveConfig => {
+                let config = EffectiveConfig::new(&self.state);
+
+                match serde_json::to_string_pretty(&config) {
+                    Ok(json) => ControlResponse::Config(json),
+                    Err(err) => ControlRequestError::Config(err).into(),
+                }
+            }
+            ControlRequest::SetLogFilter {
+                filter,
+                revert_after,
+            } => set_log_filter(self.log_control.as_ref(), &filter, revert_after),
+            ControlRequest::Status => ControlResponse::Status(self.status()),
+            ControlRequest::PoolHistory(name) => match self.pool_history(&name) {
+                Some(history) => ControlResponse::PoolHistory(history),
+                None => ControlRequestError::UnknownPool(name).into(),
+            },
+            ControlRequest::Metrics => ControlResponse::Metrics(self.state.stats.to_prometheus()),
+            ControlRequest::InterfaceStats => {
+                ControlResponse::InterfaceStats(self.state.stats.per_interface())
+            }
+        }
+    }
+}
+
+impl ControlServer {
+    fn status(&self) -> Vec<(String, String)> {
+        let leases = self.state.leases.lock().unwrap().len();
+        let (pools, free) = {
+            let pools = self.state.pools.lock().unwrap();
+            let free: usize = pools.iter().map(|pool| pool.free_addresses().len()).sum();
+            (pools.len(), free)
+        };
+
+        let mut entries = vec![
+            (String::from("leases"), leases.to_string()),
+            (String::from("pools"), pools.to_string()),
+            (String::from("free_addresses"), free.to_string()),
+            (String::from("persistence"), self.persistence_status()),
+            (
+                String::from("under_starvation"),
+                self.state
+                    .under_starvation(self.state.clock.now_instant())
+                    .to_string(),
+            ),
+        ];
+
+        entries.extend(self.reservation_status());
+        entries.extend(audit_status(&self.state.audit));
+        entries.extend(log_filter_status(self.log_control.as_ref()));
+        entries
+    }
+
+    fn persistence_status(&self) -> String {
+        match self.state.persistence.last_error() {
+            Some(err) => format!("degraded ({err})"),
+            None => String::from("ok"),
+        }
+    }
+
+    /// Returns the number of reservations followed by the reservations which
+    /// don't take effect yet, one entry each.
+    fn reservation_status(&self) -> Vec<(String, String)> {
+        let count = self.state.reservations.lock().unwrap().len();
+        let mut entries = vec![(String::from("reservations"), count.to_string())];
+
+        for conflict in self.state.reservation_conflicts() {
+            entries.push((String::from("reservation_conflict"), conflict.to_string()));
+        }
+
+        entries
+    }
+
+    fn reload_reservations(&self) -> Result<(), ControlRequestError> {
+        let Some(reload) = &self.reservation_reload else {
+            return Err(ControlRequestError::ReloadNotSupported);
+        };
+
+        let reservations = reload().map_err(ControlRequestError::Reload)?;
+        self.state.reload_reservations(reservations)?;
+
+        Ok(())
+    }
+
+    fn list_leases(&self, now: SystemTime) -> Vec<LeaseEntry> {
+        let leases = self.state.leases.lock().unwrap();
+
+        let mut entries: Vec<_> = leases
+            .values()
+            .map(|lease| LeaseEntry {
+                hardware_addr: lease.hardware_addr().clone(),
+                ip_addr: lease.ip_addr(),
+                remaining: lease.remaining(now).map_or(0, |d| d.as_secs() as u32),
+                hostname: lease.hostname().cloned(),
+            })
+            .collect();
+
+        entries.sort_by_key(|entry| entry.ip_addr);
+        entries
+    }
+
+    fn pool_stats(&self) -> Vec<PoolStat> {
+        let pools = self.state.pools.lock().unwrap();
+
+        pools
+            .iter()
+            .map(|pool| PoolStat {
+                name: pool.name().to_string(),
+                start: pool.range().start(),
+                end: pool.range().end(),
+                free: pool.free_addresses().len() as u32,
+                probation: pool.probation_count() as u32,
+            })
+            .collect()
+    }
+
+    /// Returns the history of the pool with `name`. Pools which weren't
+    /// sampled yet have an empty history.
+    fn pool_history(&self, name: &str) -> Option<PoolHistory> {
+        let history = self.state.stats.history();
+        if let Some(pool) = history.pool(name) {
+            return Some(pool);
+        }
+
+        let pools = self.state.pools.lock().unwrap();
+        let pool = pools.iter().find(|pool| pool.name() == name)?;
+
+        Some(PoolHistory {
+            name: name.to_string(),
+            size: pool.range().size(),
+            resolution: history.resolution().as_secs() as u32,
+            samples: Vec::new(),
+            churn: Vec::new(),
+        })
+    }
+}
+
+/// Sends `request` on `stream` and waits for the response of the server.
+pub async fn send_control_request<S>(
+    stream: &mut S,
+    request: &ControlRequest,
+) -> Result<ControlResponse, ControlError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    write_frame(stream, &serde_json::to_vec(request)?).await?;
+
+    match read_frame(stream).await? {
+        Some(frame) => Ok(serde_json::from_slice(&frame)?),
+        None => Err(ControlError::Closed),
+    }
+}
+
+/// Header of a frame on a control socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub version: u8,
+
+    /// Length of the payload following the header.
+    pub len: usize,
+}
+
+impl FrameHeader {
+    /// Reads the header at the start of `buf`. The version is not checked,
+    /// lengths exceeding [`MAX_FRAME_SIZE`] are refused.
+    pub fn read(buf: &mut ReadBuffer) -> Result<Self, ControlError> {
+        if buf.len() < FRAME_HEADER_LEN {
+            return Err(ControlError::Truncated);
+        }
+
+        if buf.read_slice(CONTROL_MAGIC.len())? != CONTROL_MAGIC {
+            return Err(ControlError::InvalidMagic);
+        }
+
+        let version = buf.pop()?;
+        let len = u32::read::<BigEndian>(buf)? as usize;
+
+        if len > MAX_FRAME_SIZE {
+            return Err(ControlError::FrameTooLarge(len));
+        }
+
+        Ok(Self { version, len })
+    }
+}
+
+/// Encodes `payload` as a frame of the current protocol version:
+///
+/// ```text
+/// | magic (4) | version (1) | length (4, big endian) | payload (length) |
+/// ```
+pub fn encode_frame(payload: &[u8]) -> Result<Vec<u8>, ControlError> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(ControlError::FrameTooLarge(payload.len()));
+    }
+
+    let mut buf = WriteBuffer::new();
+    buf.write(CONTROL_MAGIC);
+    buf.push(CONTROL_PROTOCOL_VERSION);
+    (payload.len() as u32).write::<BigEndian>(&mut buf)?;
+    buf.write(payload);
+
+    Ok(buf.bytes().to_vec())
+}
+
+/// Decodes the frame at the start of `bytes` and returns its header and
+/// payload. Frames of other protocol versions are refused.
+pub fn decode_frame(bytes: &[u8]) -> Result<(FrameHeader, &[u8]), ControlError> {
+    let mut buf = ReadBuffer::new(bytes);
+    let header = FrameHeader::read(&mut buf)?;
+    check_version(header.version)?;
+
+    if buf.len() < header.len {
+        return Err(ControlError::Truncated);
+    }
+
+    Ok((header, buf.read_slice(header.len)?))
+}
+
+fn check_version(remote: u8) -> Result<(), ControlError> {
+    match remote {
+        CONTROL_PROTOCOL_VERSION => Ok(()),
+        remote => Err(ControlError::UnsupportedVersion {
+            local: CONTROL_PROTOCOL_VERSION,
+            remote,
+        }),
+    }
+}
+
+fn upgrade_hint(local: u8, remote: u8) -> &'static str {
+    if remote > local {
+        "upgrade this side"
+    } else {
+        "upgrade the peer"
+    }
+}
+
+/// Reads a frame and returns its payload. Returns [`None`] if the peer
+/// closed the connection between frames. The payload is only buffered once
+/// the header was validated.
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>, ControlError> {
+    let mut header = [0; FRAME_HEADER_LEN];
+
+    // Peers which don't frame their messages send as few as five bytes, so
+    // check the magic before waiting for the rest of the header
+    let magic_len = CONTROL_MAGIC.len();
+    if !read_exact_or_eof(reader, &mut header[..magic_len]).await? {
+        return Ok(None);
+    }
+    if header[..magic_len] != CONTROL_MAGIC {
+        return Err(ControlError::InvalidMagic);
+    }
+    if !read_exact_or_eof(reader, &mut header[magic_len..]).await? {
+        return Err(ControlError::Truncated);
+    }
+
+    let header = FrameHeader::read(&mut ReadBuffer::new(&header))?;
+    check_version(header.version)?;
+
+    let mut frame = vec![0; header.len];
+    if !read_exact_or_eof(reader, &mut frame).await? {
+        return Err(ControlError::Truncated);
+    }
+
+    Ok(Some(frame))
+}
+
+/// Fills `buf` completely. Returns `false` if the reader reached the end
+/// before the first byte, fails with [`ControlError::Truncated`] if it
+/// reached the end after it.
+async fn read_exact_or_eof<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> Result<bool, ControlError> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await? {
+            0 if filled == 0 => return Ok(false),
+            0 => return Err(ControlError::Truncated),
+            n => filled += n,
+        }
+    }
+
+    Ok(true)
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    payload: &[u8],
+) -> Result<(), ControlError> {
+    writer.write_all(&encode_frame(payload)?).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+/// Answers a peer predating the frame header with `err`, prefixed by
+/// its length only. The error is encoded with binbuf, the only payload
+/// encoding such peers understand.
+async fn write_unframed_error<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    err: ControlRequestError,
+) -> Result<(), ControlError> {
+    let mut buf = WriteBuffer::new();
+    buf.push(LEGACY_ERROR_RESPONSE);
+    write_string::<BigEndian>(&err.to_string(), &mut buf)?;
+
+    writer.write_u32(buf.len() as u32).await?;
+    writer.write_all(buf.bytes()).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+/// Writes `s` prefixed by its length as a 2 byte integer.
+fn write_string<E: Endianness>(s: &str, buf: &mut WriteBuffer) -> Result<usize, ControlError> {
+    let bytes = &s.as_bytes()[..s.len().min(u16::MAX as usize)];
+    let n = (bytes.len() as u16).write::<E>(buf)?;
+
+    Ok(n + buf.write(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::{Rng, RngCore};
+
+    use super::*;
+    use crate::{
+        server::{Reservation, Server, ServerStats},
+        types::Lease,
+        Clock, SeededRng, StepClock,
+    };
+
+    fn hardware_addr(s: &str) -> HardwareAddr {
+        HardwareAddr::try_from(String::from(s)).unwrap()
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let requests = [
+            ControlRequest::ListLeases,
+            ControlRequest::PoolStats,
+            ControlRequest::ReloadConfig,
+            ControlRequest::ReleaseLease(hardware_addr("de:ad:be:ef:12:34")),
+            ControlRequest::EffectiveConfig,
+            ControlRequest::SetLogFilter {
+                filter: String::from("info,dhcp::server=debug"),
+                revert_after: Some(600),
+            },
+            ControlRequest::SetLogFilter {
+                filter: String::from("debug"),
+                revert_after: None,
+            },
+            ControlRequest::Status,
+            ControlRequest::PoolHistory(String::from("default")),
+            ControlRequest::Metrics,
+            ControlRequest::InterfaceStats,
+        ];
+
+        for request in requests {
+            let payload = serde_json::to_vec(&request).unwrap();
+            assert_eq!(
+                serde_json::from_slice::<ControlRequest>(&payload).unwrap(),
+                request
+            );
+        }
+
+        assert_eq!(
+            serde_json::to_string(&ControlRequest::PoolHistory(String::from("default"))).unwrap(),
+            r#"{"PoolHistory":"default"}"#
+        );
+        assert!(serde_json::from_slice::<ControlRequest>(br#""Reboot""#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_leases() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_clock(StepClock::default())
+            .build()
+            .unwrap();
+
+        let now = server.service.state.clock.now_system();
+        let lease = Lease::new_at(
+            hardware_addr("de:ad:be:ef:12:34"),
+            Ipv4Addr::new(192, 168, 1, 100),
+            3600,
+            Some(String::from("printer")),
+            now - Duration::from_secs(600),
+        );
+        server
+            .service
+            .state
+            .leases
+            .lock()
+            .unwrap()
+            .insert(String::from("de:ad:be:ef:12:34"), lease);
+
+        let (mut client, peer) = UnixStream::pair().unwrap();
+        let control = ControlServer::new(server.service.state.clone(), None);
+        let handle = tokio::spawn(async move { handle_control_connection(&control, peer).await });
+
+        let response = send_control_request(&mut client, &ControlRequest::ListLeases)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            ControlResponse::Leases(vec![LeaseEntry {
+                hardware_addr: hardware_addr("de:ad:be:ef:12:34"),
+                ip_addr: Ipv4Addr::new(192, 168, 1, 100),
+                remaining: 3000,
+                hostname: Some(String::from("printer")),
+            }])
+        );
+
+        // Unsupported requests are answered with an error, the connection
+        // stays usable
+        let response = send_control_request(&mut client, &ControlRequest::ReloadConfig)
+            .await
+            .unwrap();
+        assert!(matches!(response, ControlResponse::Error(_)));
+
+        let response = send_control_request(&mut client, &ControlRequest::PoolStats)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::PoolStats(pools) if pools[0].free == 101 && pools[0].name == "default"
+        ));
+
+        let response = send_control_request(&mut client, &ControlRequest::EffectiveConfig)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::Config(json) if json.contains("192.168.1.100")
+        ));
+
+        let request = ControlRequest::PoolHistory(String::from("default"));
+        let response = send_control_request(&mut client, &request).await.unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::PoolHistory(history) if history.size == 101 && history.samples.is_empty()
+        ));
+
+        {
+            let pools = server.service.state.pools.lock().unwrap();
+            server.service.state.stats.history().sample(&pools, now);
+        }
+        let response = send_control_request(&mut client, &request).await.unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::PoolHistory(history)
+                if history.samples.len() == 1 && history.samples[0].allocated == 0
+        ));
+
+        let request = ControlRequest::PoolHistory(String::from("unknown"));
+        let response = send_control_request(&mut client, &request).await.unwrap();
+        assert!(matches!(response, ControlResponse::Error(_)));
+
+        let stats = &server.service.state.stats;
+        ServerStats::incr(&stats.interface("eth0").received);
+        ServerStats::incr(&stats.interface("eth0").replies);
+
+        let response = send_control_request(&mut client, &ControlRequest::Metrics)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::Metrics(metrics)
+                if metrics.contains("vulcan_dhcpd_messages_received_total{interface=\"eth0\"} 1")
+        ));
+
+        let response = send_control_request(&mut client, &ControlRequest::InterfaceStats)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            ControlResponse::InterfaceStats(vec![(
+                String::from("eth0"),
+                InterfaceCounters {
+                    received: 1,
+                    dropped: 0,
+                    replies: 1,
+                }
+            )])
+        );
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_log_filter_and_status() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.109".into())
+            .with_allow_foreign_pools(true)
+            .with_starvation_threshold(10)
+            .build()
+            .unwrap();
+
+        let log_control = Arc::new(LogControl::new(
+            crate::parse_log_filter("info").unwrap(),
+            |_| Ok(()),
+        ));
+
+        let (mut client, peer) = UnixStream::pair().unwrap();
+        let control = ControlServer::new(server.service.state.clone(), Some(log_control));
+        let handle = tokio::spawn(async move { handle_control_connection(&control, peer).await });
+
+        let request = ControlRequest::SetLogFilter {
+            filter: String::from("debug"),
+            revert_after: Some(600),
+        };
+        let response = send_control_request(&mut client, &request).await.unwrap();
+        assert_eq!(
+            response,
+            ControlResponse::LogFilter {
+                filter: String::from("debug"),
+                revert_in: Some(600),
+            }
+        );
+
+        let request = ControlRequest::SetLogFilter {
+            filter: String::from("inof"),
+            revert_after: None,
+        };
+        let response = send_control_request(&mut client, &request).await.unwrap();
+        assert!(matches!(response, ControlResponse::Error(_)));
+
+        let response = send_control_request(&mut client, &ControlRequest::Status)
+            .await
+            .unwrap();
+        let ControlResponse::Status(entries) = response else {
+            panic!("unexpected response {response:?}");
+        };
+
+        let entry = |key: &str| {
+            entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(entry("free_addresses"), Some("10"));
+        assert_eq!(entry("reservations"), Some("0"));
+        assert_eq!(entry("persistence"), Some("ok"));
+        assert_eq!(entry("under_starvation"), Some("false"));
+        assert_eq!(entry("log_filter"), Some("debug"));
+        assert!(entry("log_revert_in").is_some());
+        assert_eq!(entry("audit"), None);
+
+        let audit = &server.service.state.audit;
+        audit.total.store(10, Ordering::Relaxed);
+        audit.probed.store(4, Ordering::Relaxed);
+        audit.occupied.store(1, Ordering::Relaxed);
+
+        let response = send_control_request(&mut client, &ControlRequest::Status)
+            .await
+            .unwrap();
+        let ControlResponse::Status(entries) = response else {
+            panic!("unexpected response {response:?}");
+        };
+        let audit = entries.iter().find(|(k, _)| k == "audit").map(|(_, v)| v);
+        assert_eq!(
+            audit.map(String::as_str),
+            Some("running, 4 of 10 probed, 1 occupied")
+        );
+
+        // Flood with random clients
+        let state = &server.service.state;
+        for i in 0..20 {
+            state.observe_discover(&format!("random-{i}"), state.clock.now_instant());
+        }
+
+        let response = send_control_request(&mut client, &ControlRequest::Status)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::Status(entries)
+                if entries.contains(&(String::from("under_starvation"), String::from("true")))
+        ));
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_reservations() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.109".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let now = server.service.state.clock.now_system();
+        let lease = Lease::new_at(
+            hardware_addr("de:ad:be:ef:12:34"),
+            Ipv4Addr::new(192, 168, 1, 100),
+            3600,
+            None,
+            now,
+        );
+        server
+            .service
+            .state
+            .leases
+            .lock()
+            .unwrap()
+            .insert(String::from("de:ad:be:ef:12:34"), lease);
+
+        let reservations = Arc::new(std::sync::Mutex::new(Ok(vec![Reservation {
+            hardware_addr: hardware_addr("de:ad:be:ef:12:34"),
+            ip_addr: Ipv4Addr::new(192, 168, 1, 105),
+        }])));
+        let source = reservations.clone();
+
+        let (mut client, peer) = UnixStream::pair().unwrap();
+        let control = ControlServer::new(server.service.state.clone(), None)
+            .with_reservation_reload(Some(Arc::new(move || source.lock().unwrap().clone())));
+        let handle = tokio::spawn(async move { handle_control_connection(&control, peer).await });
+
+        // The client keeps its dynamic lease until it ended
+        let response = send_control_request(&mut client, &ControlRequest::ReloadConfig)
+            .await
+            .unwrap();
+        let ControlResponse::Status(entries) = response else {
+            panic!("unexpected response {response:?}");
+        };
+        assert_eq!(
+            entries[0],
+            (String::from("reservations"), String::from("1"))
+        );
+        assert_eq!(entries[1].0, "reservation_conflict");
+        assert!(entries[1].1.contains("192.168.1.105"));
+
+        let response = send_control_request(&mut client, &ControlRequest::Status)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ControlResponse::Status(entries) if entries.iter().any(|(k, _)| k == "reservation_conflict")
+        ));
+
+        // Failed reloads keep the previous reservations
+        *reservations.lock().unwrap() = Err(String::from("invalid config"));
+        let response = send_control_request(&mut client, &ControlRequest::ReloadConfig)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            ControlResponse::Error(String::from(
+                "VULCAN-K106 failed to reload the configuration: invalid config"
+            ))
+        );
+        assert_eq!(server.service.state.reservations.lock().unwrap().len(), 1);
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+
+    fn status_payload() -> Vec<u8> {
+        serde_json::to_vec(&ControlRequest::Status).unwrap()
+    }
+
+    /// Returns a frame of `payload` claiming protocol `version`.
+    fn frame_with_version(payload: &[u8], version: u8) -> Vec<u8> {
+        let mut frame = encode_frame(payload).unwrap();
+        frame[CONTROL_MAGIC.len()] = version;
+        frame
+    }
+
+    #[test]
+    fn test_frame_encoding() {
+        let payload = status_payload();
+        let frame = encode_frame(&payload).unwrap();
+        assert_eq!(&frame[..5], b"VCTL\x02");
+
+        let (header, decoded) = decode_frame(&frame).unwrap();
+        assert_eq!(
+            header,
+            FrameHeader {
+                version: CONTROL_PROTOCOL_VERSION,
+                len: payload.len(),
+            }
+        );
+        assert_eq!(decoded, payload);
+
+        for len in 0..frame.len() {
+            assert!(matches!(
+                decode_frame(&frame[..len]),
+                Err(ControlError::Truncated)
+            ));
+        }
+
+        // Oversize lengths are refused from the header alone
+        let mut oversize = frame[..FRAME_HEADER_LEN].to_vec();
+        oversize[5..].copy_from_slice(&(MAX_FRAME_SIZE as u32 + 1).to_be_bytes());
+        assert!(matches!(
+            decode_frame(&oversize),
+            Err(ControlError::FrameTooLarge(len)) if len == MAX_FRAME_SIZE + 1
+        ));
+        assert!(matches!(
+            encode_frame(&vec![0; MAX_FRAME_SIZE + 1]),
+            Err(ControlError::FrameTooLarge(_))
+        ));
+
+        let mut legacy = (payload.len() as u32).to_be_bytes().to_vec();
+        legacy.extend_from_slice(&payload);
+        legacy.extend_from_slice(&[0; 4]);
+        assert!(matches!(
+            decode_frame(&legacy),
+            Err(ControlError::InvalidMagic)
+        ));
+
+        for version in [1, 3] {
+            assert!(matches!(
+                decode_frame(&frame_with_version(&payload, version)),
+                Err(ControlError::UnsupportedVersion {
+                    local: CONTROL_PROTOCOL_VERSION,
+                    remote,
+                }) if remote == version
+            ));
+        }
+    }
+
+    #[test]
+    fn test_frame_decoder_fuzz() {
+        let valid = encode_frame(&status_payload()).unwrap();
+
+        for seed in 0..20 {
+            let mut rng = SeededRng::new(seed);
+
+            for _ in 0..500 {
+                let mut bytes = match rng.gen_range(0..3) {
+                    // Random bytes
+                    0 => {
+                        let mut bytes = vec![0; rng.gen_range(0..32)];
+                        rng.fill_bytes(&mut bytes);
+                        bytes
+                    }
+                    // A valid header followed by random bytes
+                    1 => {
+                        let mut bytes = valid[..5].to_vec();
+                        let mut rest = vec![0; rng.gen_range(0..32)];
+                        rng.fill_bytes(&mut rest);
+                        bytes.extend(rest);
+                        bytes
+                    }
+                    // A valid frame with a random byte changed
+                    _ => {
+                        let mut bytes = valid.clone();
+                        let i = rng.gen_range(0..bytes.len());
+                        bytes[i] = rng.gen();
+                        bytes
+                    }
+                };
+                bytes.truncate(rng.gen_range(0..=bytes.len()));
+
+                if let Ok((header, payload)) = decode_frame(&bytes) {
+                    assert_eq!(header.version, CONTROL_PROTOCOL_VERSION);
+                    assert_eq!(payload.len(), header.len);
+                    assert!(FRAME_HEADER_LEN + payload.len() <= bytes.len());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cross_version_handshakes() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let control = Arc::new(ControlServer::new(server.service.state.clone(), None));
+
+        let connect = || {
+            let (client, peer) = UnixStream::pair().unwrap();
+            let control = control.clone();
+            let handle =
+                tokio::spawn(
+                    async move { handle_control_connection(control.as_ref(), peer).await },
+                );
+            (client, handle)
+        };
+
+        // A vulcan-ctl predating the frame header is told to upgrade in the
+        // framing it understands
+        let (mut client, handle) = connect();
+        let payload = status_payload();
+        client.write_u32(payload.len() as u32).await.unwrap();
+        client.write_all(&payload).await.unwrap();
+
+        let len = client.read_u32().await.unwrap();
+        let mut response = vec![0; len as usize];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response[0], LEGACY_ERROR_RESPONSE);
+        assert_eq!(
+            usize::from(u16::from_be_bytes([response[1], response[2]])),
+            response.len() - 3
+        );
+        assert!(String::from_utf8_lossy(&response[3..]).contains("upgrade vulcan-ctl"));
+        assert!(matches!(
+            handle.await.unwrap(),
+            Err(ControlError::InvalidMagic)
+        ));
+
+        // Older and newer peers are refused with an error in the current
+        // version, a binbuf peer of version 1 fails on the version alone
+        for version in [1, 3] {
+            let (mut client, handle) = connect();
+            client
+                .write_all(&frame_with_version(&payload, version))
+                .await
+                .unwrap();
+            let response = read_frame(&mut client).await.unwrap().unwrap();
+            assert!(matches!(
+                serde_json::from_slice(&response),
+                Ok(ControlResponse::Error(message))
+                    if message.contains(&format!("version {version}"))
+            ));
+            assert!(matches!(
+                handle.await.unwrap(),
+                Err(ControlError::UnsupportedVersion { remote, .. }) if remote == version
+            ));
+        }
+
+        // Responses of a newer daemon are refused
+        let (mut client, mut daemon) = UnixStream::pair().unwrap();
+        let handle = tokio::spawn(async move {
+            read_frame(&mut daemon).await.unwrap();
+            let payload = serde_json::to_vec(&ControlResponse::Status(Vec::new())).unwrap();
+            daemon
+                .write_all(&frame_with_version(&payload, 3))
+                .await
+                .unwrap();
+        });
+        let err = send_control_request(&mut client, &ControlRequest::Status)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("upgrade this side"));
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oversize_and_truncated_frames() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let control = ControlServer::new(server.service.state.clone(), None);
+
+        // The payload of an oversize frame is never read
+        let (mut client, peer) = UnixStream::pair().unwrap();
+        let mut header = encode_frame(&[]).unwrap();
+        header[5..].copy_from_slice(&u32::MAX.to_be_bytes());
+        client.write_all(&header).await.unwrap();
+        assert!(matches!(
+            handle_control_connection(&control, peer).await,
+            Err(ControlError::FrameTooLarge(_))
+        ));
+
+        let frame = encode_frame(&status_payload()).unwrap();
+        for len in [3, FRAME_HEADER_LEN - 1, frame.len() - 1] {
+            let (mut client, peer) = UnixStream::pair().unwrap();
+            client.write_all(&frame[..len]).await.unwrap();
+            drop(client);
+
+            assert!(matches!(
+                handle_control_connection(&control, peer).await,
+                Err(ControlError::Truncated)
+            ));
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+use serde::Serialize;
+
+use crate::server::{
+    hostname::{HostnameConflictPolicy, HostnameHandling},
+    limits::CacheLimits,
+    listener::ListenInterface,
+    state::ServerState,
+    vendor::VendorProfile,
+};
+
+/// The fully resolved configuration the server runs with. Computed values
+/// like T1/T2 are filled in and every pool lists the lease times and options
+/// it actually hands out. Secrets are never part of it, so it can be dumped
+/// safely.
+///
+/// Scalar fields come before nested tables so that the configuration can be
+/// serialized as TOML.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveConfig {
+    pub server_identifier: Ipv4Addr,
+    pub bind_address: SocketAddr,
+
+    /// Interfaces listened on, `any` if the server listens on all interfaces.
+    pub interfaces: Vec<String>,
+    pub subnet_mask: Option<Ipv4Addr>,
+
+    pub lease_time: u32,
+    pub renew_time: u32,
+    pub rebind_time: u32,
+
+    /// Whether T1 and T2 are sent to clients.
+    pub send_times: bool,
+
+    pub domain_name: Option<String>,
+    pub domain_search: Option<Vec<String>>,
+    pub synthesize_domain_search: bool,
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+    pub hostname_handling: HostnameHandling,
+    pub hostname_conflict_policy: HostnameConflictPolicy,
+
+    pub inform_unknown_subnets: bool,
+    pub nak_unknown_renewals: bool,
+    pub check_renewal_source: bool,
+    pub option_overload: bool,
+    pub relay_max_message_size: u16,
+    pub socket_filter: bool,
+    pub strict: bool,
+    pub trusted_relays: Vec<Ipv4Addr>,
+
+    /// Offer and handler timeouts in seconds.
+    pub offer_timeout: u64,
+    pub defensive_mode: bool,
+    pub defensive_offer_timeout: u64,
+    pub handler_timeout: u64,
+    pub max_concurrent: usize,
+
+    pub audit_sample_size: Option<usize>,
+    pub audit_rate: u32,
+
+    /// Reclaim and retention times in seconds.
+    pub reclaim_grace: u64,
+    pub reclaim_probation: u64,
+    pub lease_retention: u64,
+
+    pub control_socket: Option<PathBuf>,
+    pub cache_limits: CacheLimits,
+    pub storage: Option<EffectiveStorage>,
+    pub pools: Vec<EffectivePool>,
+
+    /// Named like the section of the config file.
+    #[serde(rename = "vendor_profile", skip_serializing_if = "Vec::is_empty")]
+    pub vendor_profiles: Vec<VendorProfile>,
+}
+
+/// Backend the leases are persisted to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveStorage {
+    pub backend: String,
+    pub path: PathBuf,
+    pub require_persistence: bool,
+}
+
+/// A pool with its lease times and options after applying the server-wide
+/// fallbacks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectivePool {
+    pub name: String,
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+    pub lease_time: u32,
+    pub renew_time: u32,
+    pub rebind_time: u32,
+    pub time_servers: Option<Vec<Ipv4Addr>>,
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+}
+
+impl EffectiveConfig {
+    pub(crate) fn new(state: &ServerState) -> Self {
+        let config = &state.config;
+
+        let interfaces = match config.interfaces.is_empty() {
+            true => vec![ListenInterface::any().name],
+            false => config.interfaces.clone(),
+        };
+
+        let defaults = config.default_options();
+        let pools = state
+            .pools
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|pool| {
+                let times = pool.times().unwrap_or_else(|| config.default_times());
+                let options = pool.options().clone().or(&defaults);
+
+                EffectivePool {
+                    name: pool.name().to_string(),
+                    start: pool.range().start(),
+                    end: pool.range().end(),
+                    lease_time: times.lease_time,
+                    renew_time: times.renew_time,
+                    rebind_time: times.rebind_time,
+                    time_servers: options.time_servers,
+                    ntp_servers: options.ntp_servers,
+                }
+            })
+            .collect();
+
+        Self {
+            server_identifier: config.server_identifier,
+            bind_address: config.bind_address,
+            interfaces,
+            subnet_mask: config.subnet_mask,
+            lease_time: config.lease_time,
+            renew_time: config.renew_time,
+            rebind_time: config.rebind_time,
+            send_times: config.send_times,
+            domain_name: config.domain_name.clone(),
+            domain_search: config.domain_search.clone(),
+            synthesize_domain_search: config.synthesize_domain_search,
+            time_servers: config.time_servers.clone(),
+            ntp_servers: config.ntp_servers.clone(),
+            hostname_handling: config.hostname_handling,
+            hostname_conflict_policy: config.hostname_conflict_policy,
+            inform_unknown_subnets: config.inform_unknown_subnets,
+            nak_unknown_renewals: config.nak_unknown_renewals,
+            check_renewal_source: config.check_renewal_source,
+            option_overload: config.option_overload,
+            relay_max_message_size: config.relay_max_message_size,
+            socket_filter: config.socket_filter,
+            strict: config.strict,
+            trusted_relays: config.trusted_relays.clone(),
+            offer_timeout: config.offer_timeout.as_secs(),
+            defensive_mode: config.defensive_mode,
+            defensive_offer_timeout: config.defensive_offer_timeout.as_secs(),
+            handler_timeout: config.handler_timeout.as_secs(),
+            max_concurrent: config.max_concurrent,
+            audit_sample_size: config.audit_sample_size,
+            audit_rate: config.audit_rate,
+            reclaim_grace: config.reclaim_grace.as_secs(),
+            reclaim_probation: config.reclaim_probation.as_secs(),
+            lease_retention: config.lease_retention.as_secs(),
+            control_socket: config.control_socket.clone(),
+            cache_limits: config.cache_limits,
+            storage: config.state_file.clone().map(|path| EffectiveStorage {
+                backend: String::from("file"),
+                path,
+                require_persistence: config.require_persistence,
+            }),
+            pools,
+            vendor_profiles: config.vendor_profiles.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::Server;
+
+    #[test]
+    fn test_effective_config_snapshot() {
+        let server = Server::builder()
+            .with_pool("lan".into(), "192.168.1.100-192.168.1.200".into())
+            .with_pool("guest".into(), "192.168.1.210-192.168.1.250".into())
+            .with_pool_lease_time("guest".into(), 600)
+            .with_pool_ntp_servers("guest".into(), vec!["192.168.1.2".parse().unwrap()])
+            .with_ntp_servers(vec!["192.168.1.1".parse().unwrap()])
+            .with_lease_time(3600)
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let dump = serde_json::to_string_pretty(&server.effective_config()).unwrap();
+        assert_eq!(
+            dump,
+            include_str!("../../tests/fixtures/effective_config.json").trim_end()
+        );
+    }
+}
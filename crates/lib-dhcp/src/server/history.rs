@@ -0,0 +1,342 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::server::pool::Pool;
+
+/// Default interval between two utilization samples of a pool.
+pub const DEFAULT_HISTORY_RESOLUTION: Duration = Duration::from_secs(60);
+
+/// Default time span covered by the utilization history.
+pub const DEFAULT_HISTORY_WINDOW: Duration = Duration::from_secs(24 * 3600);
+
+/// Lease churn is counted per hour, for the last day.
+const CHURN_BUCKET_SECS: u64 = 3600;
+const CHURN_BUCKETS: usize = 24;
+
+/// Windows of the Prometheus gauges, as label and length in seconds.
+const PROMETHEUS_WINDOWS: [(&str, u64); 2] = [("1h", 3600), ("24h", 24 * 3600)];
+
+/// Number of allocated addresses of a pool at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistorySample {
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    pub allocated: u32,
+}
+
+/// Leases of a pool granted, expired and released within an hour.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaseChurn {
+    /// Start of the hour in seconds since the Unix epoch.
+    pub hour: u64,
+    pub granted: u32,
+    pub expired: u32,
+    pub released: u32,
+}
+
+/// Utilization history and lease churn of a single pool, oldest entries
+/// first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolHistory {
+    pub name: String,
+
+    /// Number of addresses in the pool.
+    pub size: u32,
+
+    /// Interval between two samples in seconds.
+    pub resolution: u32,
+    pub samples: Vec<HistorySample>,
+    pub churn: Vec<LeaseChurn>,
+}
+
+/// A change of the leases of a pool, counted in [`LeaseChurn`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ChurnEvent {
+    Granted,
+    Expired,
+    Released,
+}
+
+#[derive(Debug, Default)]
+struct PoolSeries {
+    size: u32,
+    samples: VecDeque<HistorySample>,
+    churn: VecDeque<LeaseChurn>,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    last_sample: Option<u64>,
+    pools: BTreeMap<String, PoolSeries>,
+}
+
+/// Keeps a ring buffer of utilization samples and hourly lease churn
+/// counters per pool. The memory used per pool is bounded by the number of
+/// samples in the window, independent of the size of the pool.
+#[derive(Debug)]
+pub struct UtilizationHistory {
+    resolution: Duration,
+    capacity: usize,
+    series: Mutex<Series>,
+}
+
+impl Default for UtilizationHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_RESOLUTION, DEFAULT_HISTORY_WINDOW)
+    }
+}
+
+impl UtilizationHistory {
+    /// Creates a history keeping one sample per `resolution` for `window`.
+    /// The resolution is at least one second.
+    pub fn new(resolution: Duration, window: Duration) -> Self {
+        let resolution = resolution.max(Duration::from_secs(1));
+        let capacity = (window.as_secs() / resolution.as_secs()).max(1) as usize;
+
+        Self {
+            resolution,
+            capacity,
+            series: Mutex::default(),
+        }
+    }
+
+    pub fn resolution(&self) -> Duration {
+        self.resolution
+    }
+
+    /// Returns the history of the pool with `name`, [`None`] if the pool
+    /// wasn't sampled yet.
+    pub fn pool(&self, name: &str) -> Option<PoolHistory> {
+        let series = self.series.lock().unwrap();
+        let pool = series.pools.get(name)?;
+
+        Some(PoolHistory {
+            name: name.to_string(),
+            size: pool.size,
+            resolution: self.resolution.as_secs() as u32,
+            samples: pool.samples.iter().copied().collect(),
+            churn: pool.churn.iter().copied().collect(),
+        })
+    }
+
+    /// Samples the utilization of `pools` if at least the resolution passed
+    /// since the last sample. Returns whether a sample was taken.
+    pub(crate) fn sample(&self, pools: &[Pool], now: SystemTime) -> bool {
+        let at = unix_secs(now);
+        let mut series = self.series.lock().unwrap();
+
+        if series
+            .last_sample
+            .is_some_and(|last| at < last + self.resolution.as_secs())
+        {
+            return false;
+        }
+        series.last_sample = Some(at);
+
+        for pool in pools {
+            let entry = series.pools.entry(pool.name().to_string()).or_default();
+            entry.size = pool.range().size();
+            entry.samples.push_back(HistorySample {
+                at,
+                allocated: pool.allocated_count() as u32,
+            });
+
+            while entry.samples.len() > self.capacity {
+                entry.samples.pop_front();
+            }
+        }
+
+        true
+    }
+
+    /// Counts `event` for the pool with `name` in the hour of `now`.
+    pub(crate) fn record(&self, name: &str, event: ChurnEvent, now: SystemTime) {
+        let hour = unix_secs(now) / CHURN_BUCKET_SECS * CHURN_BUCKET_SECS;
+        let mut series = self.series.lock().unwrap();
+        let churn = &mut series.pools.entry(name.to_string()).or_default().churn;
+
+        if churn.back().is_none_or(|bucket| bucket.hour < hour) {
+            churn.push_back(LeaseChurn {
+                hour,
+                ..Default::default()
+            });
+
+            while churn.len() > CHURN_BUCKETS {
+                churn.pop_front();
+            }
+        }
+
+        let bucket = churn.back_mut().unwrap();
+        match event {
+            ChurnEvent::Granted => bucket.granted += 1,
+            ChurnEvent::Expired => bucket.expired += 1,
+            ChurnEvent::Released => bucket.released += 1,
+        }
+    }
+
+    /// Appends the utilization and churn gauges of all pools to `out`, one
+    /// per window. Windows end at the last sample.
+    pub(crate) fn write_prometheus(&self, out: &mut String) {
+        let series = self.series.lock().unwrap();
+        let Some(end) = series.last_sample else {
+            return;
+        };
+
+        let gauges = [
+            "pool_utilization_avg",
+            "pool_utilization_max",
+            "pool_leases_granted",
+            "pool_leases_expired",
+            "pool_leases_released",
+        ];
+
+        for gauge in gauges {
+            let _ = writeln!(out, "# TYPE vulcan_dhcpd_{gauge} gauge");
+
+            for (name, pool) in &series.pools {
+                for (window, secs) in PROMETHEUS_WINDOWS {
+                    let start = end.saturating_sub(secs);
+                    let value = pool.gauge(gauge, start);
+                    let _ = writeln!(
+                        out,
+                        "vulcan_dhcpd_{gauge}{{pool=\"{name}\",window=\"{window}\"}} {value}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl PoolSeries {
+    /// Returns the value of `gauge` over the samples and churn buckets after
+    /// `start`.
+    fn gauge(&self, gauge: &str, start: u64) -> f64 {
+        let ratios = self
+            .samples
+            .iter()
+            .filter(|s| s.at > start)
+            .map(|s| s.allocated as f64 / self.size.max(1) as f64);
+        let churn = self
+            .churn
+            .iter()
+            .filter(|b| b.hour + CHURN_BUCKET_SECS > start);
+
+        match gauge {
+            "pool_utilization_avg" => {
+                let (sum, count) = ratios.fold((0.0, 0), |(sum, n), r| (sum + r, n + 1));
+                if count == 0 {
+                    0.0
+                } else {
+                    sum / count as f64
+                }
+            }
+            "pool_utilization_max" => ratios.fold(0.0, f64::max),
+            "pool_leases_granted" => churn.map(|b| b.granted as f64).sum(),
+            "pool_leases_expired" => churn.map(|b| b.expired as f64).sum(),
+            _ => churn.map(|b| b.released as f64).sum(),
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::state::ServerState, Clock, Server, StepClock};
+
+    fn sample(state: &ServerState) -> bool {
+        let pools = state.pools.lock().unwrap();
+        state
+            .stats
+            .history()
+            .sample(&pools, state.clock.now_system())
+    }
+
+    #[test]
+    fn test_history_samples_simulated_allocations() {
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("small".into(), "10.0.0.10-10.0.0.13".into())
+            .with_allow_foreign_pools(true)
+            .with_clock(clock.clone())
+            .with_history_resolution(Duration::from_secs(60))
+            .with_history_window(Duration::from_secs(180))
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+
+        let mut allocated = Vec::new();
+        for i in 0..4 {
+            assert!(sample(state));
+            // Not due again before the resolution passed
+            assert!(!sample(state));
+
+            let key = format!("02:00:00:00:00:{i:02x}");
+            let addr = state.offer_address(&key, clock.now_instant()).unwrap();
+            state.record_offer(
+                &key,
+                key.clone().try_into().unwrap(),
+                0,
+                addr,
+                Duration::from_secs(60),
+                clock.now_system(),
+            );
+            state.commit_offer(&key, addr, clock.now_system()).unwrap();
+            allocated.push((key, addr));
+
+            clock.advance(Duration::from_secs(60));
+        }
+
+        let (key, addr) = &allocated[0];
+        assert!(state.release_lease(key, *addr, clock.now_system()));
+        assert!(sample(state));
+
+        // The window holds three samples, the oldest were dropped
+        let history = state.stats.history().pool("small").unwrap();
+        let series: Vec<_> = history.samples.iter().map(|s| s.allocated).collect();
+        assert_eq!(series, vec![2, 3, 3]);
+        assert_eq!(history.size, 4);
+        assert_eq!(history.resolution, 60);
+
+        let churn: (u32, u32) = history
+            .churn
+            .iter()
+            .fold((0, 0), |(g, r), b| (g + b.granted, r + b.released));
+        assert_eq!(churn, (4, 1));
+
+        let mut out = String::new();
+        state.stats.history().write_prometheus(&mut out);
+        assert!(
+            out.contains("vulcan_dhcpd_pool_utilization_max{pool=\"small\",window=\"1h\"} 0.75\n")
+        );
+        assert!(out.contains("vulcan_dhcpd_pool_leases_granted{pool=\"small\",window=\"24h\"} 4\n"));
+
+        assert!(state.stats.history().pool("unknown").is_none());
+    }
+
+    #[test]
+    fn test_churn_is_bounded() {
+        let history = UtilizationHistory::default();
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        for hour in 0..30 {
+            let now = start + Duration::from_secs(hour * 3600);
+            history.record("pool", ChurnEvent::Granted, now);
+            history.record("pool", ChurnEvent::Expired, now);
+        }
+
+        let series = history.series.lock().unwrap();
+        let churn = &series.pools["pool"].churn;
+        assert_eq!(churn.len(), CHURN_BUCKETS);
+        assert!(churn.iter().all(|b| b.granted == 1 && b.expired == 1));
+    }
+}
@@ -0,0 +1,269 @@
+//! Ingestion of client-supplied hostnames (option 12). Hostnames end up in
+//! the lease storage, in log lines and eventually in DNS, so the raw name
+//! sent by the client is never used as-is.
+use std::{collections::HashMap, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::Lease;
+
+/// Maximum length of a sanitized hostname, the length of a single DNS label
+/// (RFC 1035 Section 2.3.4).
+pub const MAX_HOSTNAME_LEN: usize = 63;
+
+/// Maximum length of the raw hostname kept for reference, the maximum
+/// length of an option.
+const MAX_RAW_HOSTNAME_LEN: usize = 255;
+
+/// How hostnames sent by clients are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostnameHandling {
+    /// Store the sanitized form of every hostname, see [`sanitize_hostname`].
+    #[default]
+    Sanitize,
+
+    /// Only store hostnames which are valid DNS labels already, up to case.
+    /// Leases of clients sending other names don't get a hostname.
+    Reject,
+
+    /// Don't store client hostnames at all.
+    Ignore,
+}
+
+/// What happens if a client claims a hostname another client already holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostnameConflictPolicy {
+    /// Append `-2`, `-3`, and so on until the name is unique.
+    #[default]
+    Suffix,
+
+    /// Keep the lease without a hostname.
+    Reject,
+}
+
+/// Hostname of a client in both the form sent by the client and the
+/// sanitized form used for storage, DNS and logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ClientHostname {
+    /// Name as sent by the client, with non-printable characters escaped.
+    pub raw: String,
+
+    /// Lowercase name which is a valid DNS label, [`None`] if nothing of
+    /// the raw name is usable.
+    pub sanitized: Option<String>,
+}
+
+impl ClientHostname {
+    pub fn new(raw: &str) -> Self {
+        let sanitized = sanitize_hostname(raw);
+
+        Self {
+            raw: escape(raw),
+            sanitized: (!sanitized.is_empty()).then_some(sanitized),
+        }
+    }
+
+    /// Returns the sanitized name if it is allowed by `handling`.
+    pub fn accepted(self, handling: HostnameHandling) -> Option<String> {
+        match handling {
+            HostnameHandling::Sanitize => self.sanitized,
+            HostnameHandling::Reject => self
+                .sanitized
+                .filter(|name| name.eq_ignore_ascii_case(&self.raw)),
+            HostnameHandling::Ignore => None,
+        }
+    }
+}
+
+/// Escapes non-printable characters of `raw` and cuts it off after
+/// [`MAX_RAW_HOSTNAME_LEN`] characters.
+fn escape(raw: &str) -> String {
+    raw.chars()
+        .take(MAX_RAW_HOSTNAME_LEN)
+        .flat_map(|c| match c {
+            c if c.is_control() => c.escape_default().collect::<Vec<_>>(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// Turns the client-supplied `hostname` into a lowercase DNS label following
+/// the rules of RFC 1035 Section 2.3.1: letters, digits and inner hyphens
+/// only, at most [`MAX_HOSTNAME_LEN`] characters. Dots, whitespace and
+/// underscores become hyphens, control and all other characters are
+/// dropped. Returns an empty string if no character is left.
+pub fn sanitize_hostname(hostname: &str) -> String {
+    let mut label = String::new();
+
+    for c in hostname.chars() {
+        let c = match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_lowercase(),
+            '-' | '.' | '_' => '-',
+            c if c.is_whitespace() && !c.is_control() => '-',
+            _ => continue,
+        };
+
+        // Collapse runs of hyphens and drop leading ones
+        if c == '-' && (label.is_empty() || label.ends_with('-')) {
+            continue;
+        }
+
+        label.push(c);
+        if label.len() == MAX_HOSTNAME_LEN {
+            break;
+        }
+    }
+
+    label.trim_end_matches('-').to_string()
+}
+
+/// Returns the hostname the client with `key` gets for the sanitized
+/// `name`, or [`None`] if `policy` rejects it because another client holds
+/// the name already. Names of leases which expired at `now` are free.
+pub(crate) fn resolve_conflict(
+    name: &str,
+    key: &str,
+    leases: &HashMap<String, Lease>,
+    policy: HostnameConflictPolicy,
+    now: SystemTime,
+) -> Option<String> {
+    let taken = |candidate: &str| {
+        leases.iter().any(|(other, lease)| {
+            other != key
+                && lease.hostname().map(|h| h.as_str()) == Some(candidate)
+                && lease.remaining(now).is_some()
+        })
+    };
+
+    if !taken(name) {
+        return Some(name.to_string());
+    }
+
+    if policy == HostnameConflictPolicy::Reject {
+        return None;
+    }
+
+    (2u32..)
+        .map(|n| {
+            let suffix = format!("-{}", n);
+            let base = &name[..name.len().min(MAX_HOSTNAME_LEN - suffix.len())];
+            format!("{}{}", base.trim_end_matches('-'), suffix)
+        })
+        .find(|candidate| !taken(candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::types::HardwareAddr;
+
+    fn lease(hostname: &str) -> Lease {
+        Lease::new(
+            HardwareAddr::from([0xde, 0xad, 0xbe, 0xef, 0x12, 0x34]),
+            Ipv4Addr::new(192, 168, 1, 10),
+            3600,
+            Some(hostname.to_string()),
+        )
+    }
+
+    #[test]
+    fn test_hostile_hostnames() {
+        let name = ClientHostname::new("Printer\0\n.Office");
+        assert_eq!(name.sanitized.as_deref(), Some("printer-office"));
+        assert_eq!(name.raw, "Printer\\u{0}\\n.Office");
+
+        // Over-long names are cut off at the maximum label length
+        let long = "a".repeat(4096);
+        let name = ClientHostname::new(&long);
+        assert_eq!(name.sanitized, Some("a".repeat(MAX_HOSTNAME_LEN)));
+        assert_eq!(name.raw.len(), MAX_RAW_HOSTNAME_LEN);
+
+        // Non-ASCII characters are dropped
+        let name = ClientHostname::new("Käse-Büro");
+        assert_eq!(name.sanitized.as_deref(), Some("kse-bro"));
+        assert_eq!(name.raw, "Käse-Büro");
+
+        assert_eq!(ClientHostname::new("\u{1f600}").sanitized, None);
+        assert_eq!(ClientHostname::new("--.__").sanitized, None);
+        assert_eq!(
+            ClientHostname::new("-lab__pc-").sanitized.as_deref(),
+            Some("lab-pc")
+        );
+    }
+
+    #[test]
+    fn test_sanitize_hostname() {
+        assert_eq!(sanitize_hostname("web\x1b[31m\r\nsrv"), "web31msrv");
+        assert_eq!(
+            sanitize_hostname("db.internal.example.com"),
+            "db-internal-example-com"
+        );
+        assert_eq!(sanitize_hostname(&"x".repeat(300)).len(), MAX_HOSTNAME_LEN);
+        assert_eq!(sanitize_hostname("\0\0\0"), "");
+    }
+
+    #[test]
+    fn test_hostname_handling() {
+        let valid = || ClientHostname::new("Laptop-1");
+        let invalid = || ClientHostname::new("laptop 1");
+
+        assert_eq!(
+            valid().accepted(HostnameHandling::Reject).as_deref(),
+            Some("laptop-1")
+        );
+        assert_eq!(invalid().accepted(HostnameHandling::Reject), None);
+        assert_eq!(
+            invalid().accepted(HostnameHandling::Sanitize).as_deref(),
+            Some("laptop-1")
+        );
+        assert_eq!(valid().accepted(HostnameHandling::Ignore), None);
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        let mut leases = HashMap::new();
+        leases.insert(String::from("a"), lease("printer"));
+        leases.insert(String::from("b"), lease("printer-2"));
+        let now = SystemTime::now();
+
+        // The client holding the name keeps it
+        assert_eq!(
+            resolve_conflict("printer", "a", &leases, HostnameConflictPolicy::Suffix, now),
+            Some(String::from("printer"))
+        );
+
+        assert_eq!(
+            resolve_conflict("printer", "c", &leases, HostnameConflictPolicy::Suffix, now),
+            Some(String::from("printer-3"))
+        );
+        assert_eq!(
+            resolve_conflict("printer", "c", &leases, HostnameConflictPolicy::Reject, now),
+            None
+        );
+
+        // Suffixes still fit into a label
+        let long = "a".repeat(MAX_HOSTNAME_LEN);
+        leases.insert(String::from("d"), lease(&long));
+        let resolved =
+            resolve_conflict(&long, "c", &leases, HostnameConflictPolicy::Suffix, now).unwrap();
+        assert_eq!(resolved.len(), MAX_HOSTNAME_LEN);
+        assert!(resolved.ends_with("a-2"));
+
+        // Expired leases don't hold on to their name
+        let later = now + std::time::Duration::from_secs(7200);
+        assert_eq!(
+            resolve_conflict(
+                "printer",
+                "c",
+                &leases,
+                HostnameConflictPolicy::Reject,
+                later
+            ),
+            Some(String::from("printer"))
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// Default maximum number of entries of each per-client cache.
+pub const DEFAULT_CACHE_LIMIT: usize = 16_384;
+
+/// The per-client state the server keeps outside of leases. Each of these
+/// is bounded by [`CacheLimits`], once full the least recently used entry
+/// is evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransientCache {
+    /// Offers reused for repeated DHCPDISCOVERs within the coalescing
+    /// window.
+    DiscoverCache,
+
+    /// Offers waiting for a DHCPREQUEST. An evicted offer is withdrawn and
+    /// its address returned to the pool.
+    Offers,
+
+    /// Clients seen by the starvation detector. Also bounds the arrivals of
+    /// new clients it counts within its window.
+    KnownClients,
+
+    /// Last replay detection values of authenticated clients.
+    ReplayCounters,
+
+    /// Transactions waiting for the client to follow up, see
+    /// [`TRANSACTION_TARGET`](crate::TRANSACTION_TARGET).
+    Transactions,
+}
+
+impl TransientCache {
+    pub const ALL: [TransientCache; 5] = [
+        Self::DiscoverCache,
+        Self::Offers,
+        Self::KnownClients,
+        Self::ReplayCounters,
+        Self::Transactions,
+    ];
+
+    /// Returns the name of the cache as used in metric labels.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DiscoverCache => "discover_cache",
+            Self::Offers => "offers",
+            Self::KnownClients => "known_clients",
+            Self::ReplayCounters => "replay_counters",
+            Self::Transactions => "transactions",
+        }
+    }
+}
+
+impl Display for TransientCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Maximum number of entries of each [`TransientCache`]. All default to
+/// [`DEFAULT_CACHE_LIMIT`], which is also used for limits missing when
+/// deserializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheLimits {
+    pub discover_cache: usize,
+    pub offers: usize,
+    pub known_clients: usize,
+    pub replay_counters: usize,
+    pub transactions: usize,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        Self {
+            discover_cache: DEFAULT_CACHE_LIMIT,
+            offers: DEFAULT_CACHE_LIMIT,
+            known_clients: DEFAULT_CACHE_LIMIT,
+            replay_counters: DEFAULT_CACHE_LIMIT,
+            transactions: DEFAULT_CACHE_LIMIT,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::{ffi::CString, io, net::SocketAddr};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+use crate::net;
+
+/// Network interface a listener receives messages on. The wildcard listener,
+/// which is used when no interfaces are configured, is called `any` and has
+/// the index 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenInterface {
+    pub name: String,
+    pub index: u32,
+}
+
+impl ListenInterface {
+    pub fn any() -> Self {
+        Self {
+            name: String::from("any"),
+            index: 0,
+        }
+    }
+
+    /// Looks up the index of the interface with `name`.
+    pub fn lookup(name: &str) -> io::Result<Self> {
+        let c_name = CString::new(name)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+
+        let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            index,
+        })
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.index == 0
+    }
+}
+
+/// Binds a UDP socket to `addr` which only receives messages arriving on
+/// `interface`. Multiple sockets can be bound to the same address, one per
+/// interface. With `filter`, datagrams which can't be DHCP messages are
+/// dropped by a socket filter.
+pub(crate) fn bind(
+    interface: &ListenInterface,
+    addr: SocketAddr,
+    filter: bool,
+) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+
+    if !interface.is_any() {
+        socket.bind_device(Some(interface.name.as_bytes()))?;
+    }
+
+    // Attach the filter before binding, so that no unfiltered datagrams get
+    // queued
+    if filter {
+        net::filter_dhcp_datagrams(&socket);
+    }
+    socket.bind(&addr.into())?;
+
+    UdpSocket::from_std(socket.into())
+}
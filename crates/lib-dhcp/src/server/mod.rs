@@ -1,46 +1,197 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
 
 use binbuf::prelude::*;
 use thiserror::Error;
-use tokio::{self, net};
+use tokio::{
+    self,
+    net::UdpSocket,
+    sync::{mpsc, Notify},
+};
+use tracing::{debug, info, info_span, warn, Span};
 
 use crate::{
     constants,
     server::{
-        builder::{ServerBuilder, ServerBuilderError},
-        config::ServerConfig,
+        allocation::OfferAllocation,
+        control::{bind_control_socket, serve_control, ControlServer},
+        options::{select_inform_options, select_reply_options},
+        size::{fit_associated_ips, fit_reply, max_reply_size},
+        state::ServerState,
+        storage::ServerStorageError,
+        tasks::{TaskInfo, TaskManager},
+        vendor::select_vendor_options,
     },
-    types::{options::DhcpMessageType, Message},
+    transaction::{relay_info, TransactionRecord, TransactionStep},
+    types::{
+        options::{ClientIdentifier, DhcpMessageType},
+        DhcpOption, HardwareAddr, HardwareType, Message, MessageView, MessageViewError, OptionData,
+        OptionTag,
+    },
+    utils, LogControl, DAEMON_TARGET, LEASE_TARGET, WIRE_TARGET,
 };
 
+mod allocation;
+mod audit;
+mod auth;
+mod bindings;
 mod builder;
+mod coalesce;
 mod config;
+pub(crate) mod control;
+mod effective;
+mod history;
+mod hostname;
+mod limits;
+mod listener;
+mod offers;
+mod options;
+mod ownership;
+mod persistence;
 mod pool;
+mod probe;
+mod reclaim;
+mod replay;
+mod reservations;
+mod service;
+mod size;
+mod starvation;
+mod state;
+mod stats;
 mod storage;
+mod strict;
+mod tasks;
+mod vendor;
+
+pub use audit::{AuditProgress, AuditSummary, DEFAULT_AUDIT_RATE, MAX_AUDIT_RATE};
+pub use builder::{ServerBuilder, ServerBuilderError};
+pub use control::{
+    decode_frame, encode_frame, send_control_request, ControlError, ControlRequest,
+    ControlRequestError, ControlResponse, FrameHeader, LeaseEntry, PoolStat, CONTROL_MAGIC,
+    CONTROL_PROTOCOL_VERSION, DEFAULT_CONTROL_SOCKET, MAX_FRAME_SIZE,
+};
+pub use effective::{EffectiveConfig, EffectivePool, EffectiveStorage};
+pub use history::{
+    HistorySample, LeaseChurn, PoolHistory, UtilizationHistory, DEFAULT_HISTORY_RESOLUTION,
+    DEFAULT_HISTORY_WINDOW,
+};
+pub use hostname::{sanitize_hostname, HostnameConflictPolicy, HostnameHandling, MAX_HOSTNAME_LEN};
+pub use limits::{CacheLimits, TransientCache, DEFAULT_CACHE_LIMIT};
+pub use listener::ListenInterface;
+pub use offers::PendingOffer;
+pub use persistence::{PERSIST_RETRY_MAX, PERSIST_RETRY_MIN};
+pub use probe::{ConflictProber, IcmpProber, DEFAULT_PROBE_TIMEOUT};
+pub use reclaim::{
+    TransitionError, DEFAULT_LEASE_RETENTION, DEFAULT_RECLAIM_GRACE, DEFAULT_RECLAIM_PROBATION,
+};
+pub use reservations::{Reservation, ReservationConflict, ReservationError, ReservationReload};
+pub use service::{DhcpService, Reply, RequestMeta};
+pub use size::DEFAULT_RELAY_MAX_MESSAGE_SIZE;
+pub use stats::{
+    CacheEvictionCounters, HandlerStats, InterfaceCounters, InterfaceStats, LatencyHistogram,
+    ParseErrorCounters, ServerStats, StrictDropCounters, LATENCY_BUCKETS,
+};
+pub use storage::{PersistedState, ReclaimedAddr, StateStore};
+pub use strict::{StrictViolation, STRICT_MAX_SECS};
+pub use tasks::{DEFAULT_HANDLER_TIMEOUT, DEFAULT_MAX_IN_FLIGHT_HANDLERS, SHUTDOWN_DEADLINE};
+pub use vendor::{VendorPrecedence, VendorProfile};
 
+// Sub-options are configured in vendor profiles
+pub use crate::types::options::VendorSubOption;
+
+/// Context of a single request.
 pub struct Session {
-    socket: Arc<net::UdpSocket>,
+    state: Arc<ServerState>,
     addr: SocketAddr,
+
+    /// Interface the request arrived on.
+    interface: ListenInterface,
+
+    /// Message counters of the interface the request arrived on.
+    stats: Arc<InterfaceStats>,
+
+    /// Maximum size of replies to this request, derived from the client's
+    /// maximum DHCP message size (option 57).
+    max_message_size: u16,
+}
+
+impl Session {
+    fn new(state: Arc<ServerState>, addr: SocketAddr, interface: ListenInterface) -> Self {
+        Self {
+            max_message_size: constants::MINIMUM_LEGAL_MAX_MESSAGE_SIZE,
+            stats: state.stats.interface(&interface.name),
+            interface,
+            state,
+            addr,
+        }
+    }
+
+    /// Returns the span all tracing events emitted while handling the
+    /// request are recorded in.
+    fn span(&self) -> Span {
+        info_span!(
+            "request",
+            interface = %self.interface.name,
+            ifindex = self.interface.index,
+            addr = %self.addr
+        )
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum ServerError {
-    #[error("server is already running, aborting")]
+    #[error("{code} server is already running, aborting", code = self.code())]
     AlreadyRunning,
 
-    #[error("server builder error: {0}")]
+    #[error("{code} server builder error: {0}", code = self.code())]
     ServerBuilderError(#[from] ServerBuilderError),
 
-    #[error("io error: {0}")]
+    #[error("{code} io error: {0}", code = self.code())]
     Io(#[from] std::io::Error),
+
+    #[error("{code} failed to persist state: {0}", code = self.code())]
+    StorageError(#[from] ServerStorageError),
 }
 
+error_codes!(ServerError {
+    AlreadyRunning => "VULCAN-S101",
+    ServerBuilderError => "VULCAN-S102",
+    Io => "VULCAN-S103",
+    StorageError => "VULCAN-S104",
+});
+
 pub struct Server {
-    config: ServerConfig,
+    service: DhcpService,
+    audit_prober: Option<Arc<dyn ConflictProber>>,
+    log_control: Option<Arc<LogControl>>,
+    reservation_reload: Option<ReservationReload>,
+
+    #[cfg(feature = "dhcpv6")]
+    v6_responder: Option<Arc<crate::v6::Responder>>,
+    shutdown: Arc<Notify>,
     is_running: bool,
 }
 
+/// Handle to request a graceful shutdown of a running [`Server`], see
+/// [`Server::shutdown_handle`].
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    notify: Arc<Notify>,
+}
+
+impl ShutdownHandle {
+    /// Requests the server to shut down. The server stops receiving
+    /// messages and persists its state before [`Server::run_async`] returns.
+    pub fn shutdown(&self) {
+        self.notify.notify_one();
+    }
+}
+
 impl Server {
+    /// Builds a server with the defaults of the [`ServerBuilder`]. It has no
+    /// pools, so it only answers DHCPINFORMs and lease queries.
     pub fn new() -> Result<Self, ServerError> {
         Ok(Self::builder().build()?)
     }
@@ -49,105 +200,3332 @@ impl Server {
         ServerBuilder::new()
     }
 
-    #[tokio::main]
-    pub async fn run(&mut self) -> Result<(), ServerError> {
+    pub fn stats(&self) -> &ServerStats {
+        self.service.stats()
+    }
+
+    /// Returns the request handling core of the server. Its clones share
+    /// the leases, pools and stats with the server, so messages can be fed
+    /// to it by other front-ends than [`Server::run_async`].
+    pub fn service(&self) -> DhcpService {
+        self.service.clone()
+    }
+
+    /// Returns if the server currently assumes a DHCP starvation attack,
+    /// see [`ServerBuilder::with_starvation_threshold`].
+    pub fn under_starvation(&self) -> bool {
+        self.service
+            .state
+            .under_starvation(self.service.state.clock.now_instant())
+    }
+
+    /// Returns the progress of the startup address-conflict audit, see
+    /// [`ServerBuilder::with_startup_audit`].
+    pub fn audit_progress(&self) -> &AuditProgress {
+        &self.service.state.audit
+    }
+
+    /// Returns the fully resolved configuration the server runs with.
+    pub fn effective_config(&self) -> EffectiveConfig {
+        EffectiveConfig::new(&self.service.state)
+    }
+
+    /// Returns a handle which can be used to shut down the running server.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            notify: self.shutdown.clone(),
+        }
+    }
+
+    /// Persists the committed leases and pending offers to the configured
+    /// state file. This is a no-op if no state file is configured.
+    pub fn flush(&self) -> Result<(), ServerError> {
+        Ok(self.service.state.persist()?)
+    }
+
+    /// Returns whether the last attempt to persist the state failed. See
+    /// [`ServerBuilder::with_require_persistence`] for the effect on new
+    /// leases.
+    pub fn is_persistence_degraded(&self) -> bool {
+        self.service.state.persistence.is_degraded()
+    }
+
+    /// Forcibly revokes the lease of the client with `mac`. The lease is
+    /// removed, the addresses bound to the client are returned to the pools
+    /// and the change is persisted right away. Returns `false` if the client
+    /// holds no lease.
+    ///
+    /// The client isn't notified. Its renewals go unanswered, so it stops
+    /// using the address once the lease expired.
+    pub fn release_lease(&self, mac: &HardwareAddr) -> Result<bool, ServerError> {
+        if !self.service.state.revoke_lease(mac) {
+            return Ok(false);
+        }
+
+        self.flush()?;
+        Ok(true)
+    }
+
+    /// Replaces the reservations with `reservations` and returns the ones
+    /// which don't take effect yet, because a lease handed out before is
+    /// still running. Nothing is changed if any reservation is invalid.
+    pub fn reload_reservations(
+        &self,
+        reservations: Vec<Reservation>,
+    ) -> Result<Vec<ReservationConflict>, ReservationError> {
+        self.service.state.reload_reservations(reservations)
+    }
+
+    /// Returns the reservations which don't take effect yet, because a
+    /// lease handed out before they were added is still running.
+    pub fn reservation_conflicts(&self) -> Vec<ReservationConflict> {
+        self.service.state.reservation_conflicts()
+    }
+
+    /// Runs the server on a new multi-threaded Tokio runtime and blocks until
+    /// it is shut down. This must not be called from within a runtime, use
+    /// [`Server::run_async`] there instead.
+    pub fn run(&mut self) -> Result<(), ServerError> {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(self.run_async())
+    }
+
+    /// Runs the server on the current Tokio runtime until it is shut down,
+    /// see [`Server::shutdown_handle`].
+    pub async fn run_async(&mut self) -> Result<(), ServerError> {
         if self.is_running {
             return Err(ServerError::AlreadyRunning);
         }
         self.is_running = true;
 
-        let socket = match net::UdpSocket::bind("0.0.0.0:67").await {
-            Ok(socket) => socket,
-            Err(err) => return Err(ServerError::Io(err)),
+        // Listen on all interfaces using a single socket, unless specific
+        // interfaces are configured
+        let interfaces = match self.service.state.config.interfaces.is_empty() {
+            true => vec![ListenInterface::any()],
+            false => self
+                .service
+                .state
+                .config
+                .interfaces
+                .iter()
+                .map(|name| ListenInterface::lookup(name))
+                .collect::<Result<_, _>>()?,
         };
 
-        let socket = Arc::new(socket);
+        // The DHCPv6 responder joins the multicast group on the same
+        // interfaces
+        #[cfg(feature = "dhcpv6")]
+        let v6_responder = match self.v6_responder.clone() {
+            Some(responder) => {
+                let indices: Vec<u32> = interfaces.iter().map(|i| i.index).collect();
+                let socket = crate::v6::Responder::bind(&indices)?;
+
+                Some(tokio::spawn(async move {
+                    if let Err(err) = responder.serve(&socket).await {
+                        warn!(target: DAEMON_TARGET, %err, "DHCPv6 responder failed");
+                    }
+                }))
+            }
+            None => None,
+        };
 
-        loop {
-            // Wait until the socket is readable, this can produce a false positive
-            socket.readable().await?;
+        let (error_tx, mut error_rx) = mpsc::channel(1);
+        let mut listeners = Vec::new();
+        let tasks = Arc::new(TaskManager::new(
+            self.service.state.config.max_concurrent,
+            self.service.state.stats.clone(),
+        ));
 
-            let mut buf = [0u8; constants::MINIMUM_LEGAL_MAX_MESSAGE_SIZE as usize];
-            let (len, addr) = match socket.recv_from(&mut buf).await {
-                Ok(result) => result,
-                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
-                    // Continue when the socket.readable() call procduced a
-                    // false positive
-                    continue;
-                }
-                Err(err) => {
-                    // TODO (Techassi): Log this
-                    println!("{}", err);
-                    continue;
+        for interface in interfaces {
+            let config = &self.service.state.config;
+            let socket = listener::bind(&interface, config.bind_address, config.socket_filter)?;
+            let service = self.service.clone();
+            let tasks = tasks.clone();
+            let error_tx = error_tx.clone();
+
+            listeners.push(tokio::spawn(async move {
+                if let Err(err) = listen(socket, interface, service, tasks).await {
+                    let _ = error_tx.send(err).await;
                 }
-            };
+            }));
+        }
 
-            let session = Session {
-                socket: socket.clone(),
-                addr,
-            };
+        // Serve the control socket alongside the listeners
+        let control = match &self.service.state.config.control_socket {
+            Some(path) => {
+                let listener = bind_control_socket(path)?;
+                let server = Arc::new(
+                    ControlServer::new(self.service.state.clone(), self.log_control.clone())
+                        .with_reservation_reload(self.reservation_reload.clone()),
+                );
+
+                Some(tokio::spawn(async move {
+                    if let Err(err) = serve_control(server, listener).await {
+                        warn!(target: DAEMON_TARGET, %err, "control socket failed");
+                    }
+                }))
+            }
+            None => None,
+        };
+
+        // Reclaim the addresses of expired leases and sample the pool
+        // utilization periodically
+        let maintenance = {
+            let state = self.service.state.clone();
+            tokio::spawn(async move { reclaim::run_maintenance(&state).await })
+        };
+
+        // Retry writing the state while persisting it fails
+        let persistence = {
+            let state = self.service.state.clone();
+            tokio::spawn(async move { persistence::run_persistence_retries(&state).await })
+        };
+
+        // Run the startup audit in the background, the server serves
+        // requests in the meantime
+        if let Some(prober) = self.audit_prober.clone() {
+            let state = self.service.state.clone();
+            tokio::spawn(async move { audit::run_audit(&state, prober.as_ref()).await });
+        }
+
+        // Stop receiving messages when a shutdown is requested or any of the
+        // listeners failed
+        let result = tokio::select! {
+            _ = self.shutdown.notified() => Ok(()),
+            Some(err) = error_rx.recv() => Err(err),
+        };
+
+        for listener in listeners {
+            listener.abort();
+        }
+
+        if let Some(control) = control {
+            control.abort();
+        }
+
+        maintenance.abort();
+        persistence.abort();
 
-            tokio::spawn(async move {
-                handle(&buf[..len], session).await;
-            });
+        #[cfg(feature = "dhcpv6")]
+        if let Some(v6_responder) = v6_responder {
+            v6_responder.abort();
         }
+
+        // Let handlers which are already running finish, so their changes
+        // are persisted below
+        tasks.shutdown(SHUTDOWN_DEADLINE).await;
+
+        self.is_running = false;
+        self.flush()?;
+
+        Ok(result?)
+    }
+}
+
+/// Receives messages on `socket` and hands each of them to `service` in a
+/// separate task spawned by `tasks`. All tracing events emitted while
+/// handling a message carry the interface it arrived on.
+async fn listen(
+    socket: UdpSocket,
+    interface: ListenInterface,
+    service: DhcpService,
+    tasks: Arc<TaskManager>,
+) -> std::io::Result<()> {
+    let socket = Arc::new(socket);
+
+    loop {
+        // Wait until the socket is readable, this can produce a false
+        // positive
+        socket.readable().await?;
+
+        let mut buf = [0u8; constants::MINIMUM_LEGAL_MAX_MESSAGE_SIZE as usize];
+        let (len, addr) = match socket.recv_from(&mut buf).await {
+            Ok(result) => result,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                // Continue when the socket.readable() call procduced a
+                // false positive
+                continue;
+            }
+            Err(err) => {
+                // TODO (Techassi): Log this
+                println!("{}", err);
+                continue;
+            }
+        };
+
+        let meta = RequestMeta::new(addr).with_interface(interface.clone());
+        spawn_handler(&tasks, &service, buf[..len].to_vec(), meta, socket.clone()).await;
     }
 }
 
-async fn handle(buf: &[u8], session: Session) {
-    let mut buf = ReadBuffer::new(buf);
+/// Handles the datagram `buf` with `service` in a task spawned by `tasks`
+/// and sends the reply through `socket`. Waits if the maximum number of
+/// handlers is already running.
+async fn spawn_handler(
+    tasks: &TaskManager,
+    service: &DhcpService,
+    buf: Vec<u8>,
+    meta: RequestMeta,
+    socket: Arc<UdpSocket>,
+) {
+    let info = TaskInfo {
+        peer: meta.source,
+        message_type: MessageView::new(&buf)
+            .ok()
+            .and_then(|view| view.message_type()),
+    };
+    let service = service.clone();
+
+    tasks
+        .spawn(info, async move {
+            let Some(reply) = service.call(&buf, meta).await else {
+                return;
+            };
+
+            if let Err(err) = socket.send_to(&reply.bytes, reply.destination).await {
+                println!("Error while sending DHCP message: {}", err);
+            }
+        })
+        .await
+}
+
+/// Counts and logs a datagram dropped because it isn't a DHCP message.
+fn drop_unreadable(err: &MessageViewError, session: &Session) {
+    println!("Error while reading DHCP message: {}", err);
+    session.state.stats.parse_errors.incr(err.kind());
+    count_strict_drop(StrictViolation::of_view_error(err), session);
+    ServerStats::incr(&session.stats.dropped);
+}
+
+/// Handles the message in `view`, whose type was already looked up by
+/// [`DhcpService::call`]. Messages are routed using the zero-copy view
+/// first, which avoids fully parsing messages the server doesn't handle
+/// anyway. Those that are handled are parsed once.
+async fn handle(
+    view: MessageView<'_>,
+    message_type: Option<DhcpMessageType>,
+    mut session: Session,
+) -> Option<Reply> {
+    // RFC 2131 only allows a DHCPNAK in reply to a DHCPREQUEST, so
+    // messages without a known type are never answered.
+    let Some(message_type) = message_type else {
+        match view.option(OptionTag::DhcpMessageType) {
+            None => {
+                debug!(
+                    target: WIRE_TARGET,
+                    source = %session.addr,
+                    "dropping message without DHCP message type"
+                );
+                ServerStats::incr(&session.state.stats.missing_message_types);
+            }
+            Some(data) => {
+                debug!(
+                    target: WIRE_TARGET,
+                    source = %session.addr,
+                    message_type = ?data,
+                    "dropping message with unknown DHCP message type"
+                );
+                ServerStats::incr(&session.state.stats.unknown_message_types);
+            }
+        }
+
+        ServerStats::incr(&session.stats.dropped);
+        return None;
+    };
+
+    // DHCPOFFER, DHCPACK, DHCPNAK and leasequery replies are only sent by
+    // servers
+    if matches!(
+        message_type,
+        DhcpMessageType::Offer
+            | DhcpMessageType::Ack
+            | DhcpMessageType::Nak
+            | DhcpMessageType::LeaseUnassigned
+            | DhcpMessageType::LeaseUnknown
+            | DhcpMessageType::LeaseActive
+    ) {
+        ServerStats::incr(&session.stats.dropped);
+        return None;
+    }
 
-    let message = match Message::read::<BigEndian>(&mut buf) {
+    let message = match Message::read::<BigEndian>(&mut ReadBuffer::new(view.bytes())) {
         Ok(msg) => msg,
         Err(err) => {
             println!("Error while reading DHCP message: {}", err);
-            return;
+            session.state.stats.parse_errors.incr(err.kind());
+            count_strict_drop(StrictViolation::of_parse_error(&err), &session);
+            ServerStats::incr(&session.stats.dropped);
+            return None;
         }
     };
 
-    let message_type = match message.get_message_type() {
-        Some(ty) => ty,
-        None => {
-            println!("No DHCP message type option");
-            return;
-        }
+    utils::trace_message(&message, session.addr);
+
+    let source = match session.addr.ip() {
+        IpAddr::V4(addr) => addr,
+        IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
     };
+    let config = &session.state.config;
+
+    if let Some(violation) = StrictViolation::of(&message, source, &config.trusted_relays) {
+        if config.strict {
+            count_strict_drop(Some(violation), &session);
+            ServerStats::incr(&session.stats.dropped);
+            return None;
+        }
+
+        debug!(
+            target: WIRE_TARGET,
+            %violation,
+            client = %message.chaddr,
+            "tolerating message outside of strict mode"
+        );
+    }
+
+    // Messages which went through too many relay agents are likely looping.
+    // 'hops' is left alone otherwise, replies always carry zero.
+    if message.header.hops > constants::MAX_HOPS {
+        debug!(
+            target: WIRE_TARGET,
+            hops = message.header.hops,
+            client = %message.chaddr,
+            "dropping message relayed too many times"
+        );
+        ServerStats::incr(&session.state.stats.excessive_hops);
+        ServerStats::incr(&session.stats.dropped);
+        return None;
+    }
+
+    // Addresses are only allocated to Ethernet clients with a unicast
+    // hardware address, others can't be told apart or answered. IPoIB
+    // clients leave 'chaddr' empty (RFC 4390).
+    if matches!(
+        message_type,
+        DhcpMessageType::Discover | DhcpMessageType::Request
+    ) && message.header.htype == HardwareType::Ethernet
+        && !message.chaddr.is_unicast()
+    {
+        debug!(
+            target: WIRE_TARGET,
+            client = %message.chaddr,
+            "dropping message with invalid client hardware address"
+        );
+        ServerStats::incr(&session.state.stats.invalid_chaddrs);
+        ServerStats::incr(&session.stats.dropped);
+        return None;
+    }
+
+    // Drop messages which fail authentication or were seen before. Only
+    // verified messages advance the replay detection counter, so forged
+    // ones can't lock out the client. Without keys, and in DHCPDISCOVERs
+    // merely asking for authentication, the option is ignored.
+    if let Some(auth) = message
+        .get_authentication()
+        .filter(|auth| !auth.information().is_empty() && !config.auth_keys.is_empty())
+    {
+        if let Err(err) = auth::verify(&view, &config.auth_keys) {
+            debug!(
+                target: WIRE_TARGET,
+                client = %message.chaddr,
+                %err,
+                "dropping message failing authentication"
+            );
+            ServerStats::incr(&session.state.stats.auth_failures);
+            ServerStats::incr(&session.stats.dropped);
+            return None;
+        }
+
+        let accepted = session.state.accept_replay_detection(
+            &message.chaddr,
+            auth.rdm(),
+            auth.replay_detection(),
+        );
+
+        if !accepted {
+            debug!(
+                target: WIRE_TARGET,
+                client = %message.chaddr,
+                replay_detection = auth.replay_detection(),
+                "dropping replayed message"
+            );
+            ServerStats::incr(&session.state.stats.replays_rejected);
+            ServerStats::incr(&session.stats.dropped);
+            return None;
+        }
+    }
+
+    session.max_message_size =
+        max_reply_size(&message, session.state.config.relay_max_message_size);
 
     match message_type {
         DhcpMessageType::Discover => handle_discover(message, session).await,
-        DhcpMessageType::Offer => handle_offer(message, session).await,
         DhcpMessageType::Request => handle_request(message, session).await,
         DhcpMessageType::Decline => handle_decline(message, session).await,
-        DhcpMessageType::Ack => handle_ack(message, session).await,
-        DhcpMessageType::Nak => handle_nak(message, session).await,
         DhcpMessageType::Release => handle_release(message, session).await,
+        DhcpMessageType::Inform => handle_inform(message, session).await,
+        DhcpMessageType::LeaseQuery => handle_lease_query(message, session).await,
+        DhcpMessageType::Offer
+        | DhcpMessageType::Ack
+        | DhcpMessageType::Nak
+        | DhcpMessageType::LeaseUnassigned
+        | DhcpMessageType::LeaseUnknown
+        | DhcpMessageType::LeaseActive => None,
     }
 }
 
-async fn handle_discover(message: Message, session: Session) {
-    todo!()
+/// Counts and logs a message dropped because of `violation` if the server
+/// runs in strict mode. Messages failing to parse are dropped in either
+/// mode, but only counted per violation in strict mode.
+fn count_strict_drop(violation: Option<StrictViolation>, session: &Session) {
+    let Some(violation) = violation.filter(|_| session.state.config.strict) else {
+        return;
+    };
+
+    debug!(
+        target: WIRE_TARGET,
+        %violation,
+        source = %session.addr,
+        "dropping message in strict mode"
+    );
+    session.state.stats.strict_drops.incr(violation);
 }
 
-async fn handle_offer(message: Message, session: Session) {
-    todo!()
+async fn handle_discover(message: Message, session: Session) -> Option<Reply> {
+    let state = &session.state;
+    let key = client_key(&message);
+    let now = state.clock.now_instant();
+
+    state.observe_discover(&key, now);
+    state.touch_lease(
+        &key,
+        DhcpMessageType::Discover,
+        state.clock.now_system(),
+        circuit_id(&message),
+    );
+
+    if let Some(params) = message.get_parameter_request_list() {
+        debug!(
+            target: LEASE_TARGET,
+            client = %message.chaddr,
+            xid = %format_args!("{:#010x}", message.header.xid),
+            %params,
+            "client requested parameters"
+        );
+    }
+
+    let allocation = match OfferAllocation::new(state, &key, now) {
+        Some(allocation) => allocation,
+        None => {
+            warn!(
+                target: LEASE_TARGET,
+                decision = "no_address",
+                client = %message.chaddr,
+                xid = %format_args!("{:#010x}", message.header.xid),
+                "no free address available"
+            );
+            return None;
+        }
+    };
+
+    let offered_addr = allocation.addr();
+
+    // Dropping the allocation hands the address back, also if the handler
+    // is aborted while probing
+    if let Some(prober) = state
+        .offer_prober
+        .as_ref()
+        .filter(|_| allocation.is_fresh())
+    {
+        match prober.probe(offered_addr).await {
+            Ok(true) => {
+                drop(allocation);
+                state.put_on_probation(offered_addr);
+                warn!(
+                    target: LEASE_TARGET,
+                    decision = "conflict",
+                    client = %message.chaddr,
+                    xid = %format_args!("{:#010x}", message.header.xid),
+                    addr = %offered_addr,
+                    "address is in use by an unknown host, putting it on probation"
+                );
+                return None;
+            }
+            Ok(false) => {}
+            Err(err) => {
+                warn!(target: LEASE_TARGET, addr = %offered_addr, %err, "failed to probe address")
+            }
+        }
+    }
+
+    allocation.record(
+        message.chaddr.clone(),
+        message.header.xid,
+        state.offer_timeout(now),
+        state.clock.now_system(),
+    );
+
+    let times = state.lease_times(offered_addr);
+    let mut options = select_reply_options(
+        &state.config,
+        &times,
+        &state.pool_options(offered_addr),
+        message.get_parameter_request_list(),
+    );
+    options.extend(select_vendor_options(
+        &state.config.vendor_profiles,
+        &message,
+    ));
+    let offer = match state.builder.make_offer_message(
+        &message,
+        offered_addr,
+        state.config.server_identifier,
+        times.lease_time,
+        options,
+    ) {
+        Ok(offer) => offer,
+        Err(err) => {
+            println!("Error while creating DHCPOFFER: {}", err);
+            return None;
+        }
+    };
+
+    info!(
+        target: LEASE_TARGET,
+        decision = "offer",
+        client = %message.chaddr,
+        xid = %format_args!("{:#010x}", message.header.xid),
+        addr = %offered_addr,
+        lease_time = times.lease_time,
+        "offering address"
+    );
+
+    // The transaction completes with the DHCPREQUEST or when the offer
+    // expires
+    let mut record = transaction_record(&key, &message, &session, now);
+    record.push(TransactionStep::Discover);
+    record.push(TransactionStep::Offer);
+    record.addr = Some(offered_addr);
+    record.lease_time = Some(times.lease_time);
+    record.pool = state.pool_name(offered_addr);
+    state.open_transaction(&key, record);
+
+    encode_reply(offer, &session)
 }
 
-async fn handle_request(message: Message, session: Session) {
-    todo!()
+/// Starts the transaction record of the client with `key`, which sent
+/// `message` at `started`.
+fn transaction_record(
+    key: &str,
+    message: &Message,
+    session: &Session,
+    started: std::time::Instant,
+) -> TransactionRecord {
+    let mut record = TransactionRecord::new(key, started);
+    record.interface = Some(session.interface.name.clone());
+    record.hostname = message.get_host_name().cloned();
+    record.relay = relay_info(message.giaddr, circuit_id(message));
+    record
 }
 
-async fn handle_decline(message: Message, session: Session) {
-    todo!()
+/// Encodes the `reply` for the destination picked by [`reply_destination`].
+fn encode_reply(reply: Message, session: &Session) -> Option<Reply> {
+    let addr = reply_destination(&reply);
+    encode_reply_to(reply, addr, session)
 }
 
-async fn handle_ack(message: Message, session: Session) {
-    todo!()
+/// Returns where `reply` is sent to. The reply carries 'giaddr', 'ciaddr'
+/// and 'flags' of the client's request. The checks are ordered:
+///
+/// 1. Relayed requests ('giaddr' set) are answered via the server port of
+///    the relay agent. The broadcast flag is left to the relay agent.
+/// 2. DHCPNAKs are broadcast, the client may be on the wrong subnet.
+/// 3. Clients with an address ('ciaddr' set) get a unicast reply.
+/// 4. Everything else is broadcast, regardless of the broadcast flag.
+///    Unicasting to 'yiaddr' requires an ARP entry for the client, which
+///    the server can't add.
+///
+/// ### See
+///
+/// RFC 2131 - Section 4.1 - Constructing and sending DHCP messages: https://datatracker.ietf.org/doc/html/rfc2131#section-4.1
+fn reply_destination(reply: &Message) -> SocketAddr {
+    if !reply.giaddr.is_unspecified() {
+        return SocketAddr::from((reply.giaddr, constants::SERVER_PORT));
+    }
+
+    let addr = match reply.get_message_type() {
+        Some(DhcpMessageType::Nak) => Ipv4Addr::BROADCAST,
+        _ if !reply.ciaddr.is_unspecified() => reply.ciaddr,
+        _ => Ipv4Addr::BROADCAST,
+    };
+
+    SocketAddr::from((addr, constants::CLIENT_PORT))
 }
 
-async fn handle_nak(message: Message, session: Session) {
-    todo!()
+/// Encodes the `reply` to be sent to `addr`. Options which don't fit into
+/// the maximum message size of the client are dropped.
+fn encode_reply_to(mut reply: Message, addr: SocketAddr, session: &Session) -> Option<Reply> {
+    let overload = session.state.config.option_overload;
+    match fit_reply(&mut reply, session.max_message_size, overload) {
+        Ok(0) => {}
+        Ok(n) => println!(
+            "Dropped {} option(s) exceeding the maximum message size of {} octets",
+            n, session.max_message_size
+        ),
+        Err(err) => {
+            println!("Error while encoding DHCP message: {}", err);
+            return None;
+        }
+    }
+
+    let mut buf = WriteBuffer::new();
+    if let Err(err) = reply.write_be(&mut buf) {
+        println!("Error while encoding DHCP message: {}", err);
+        return None;
+    }
+
+    ServerStats::incr(&session.stats.replies);
+    Some(Reply {
+        bytes: buf.bytes().to_vec(),
+        destination: addr,
+    })
+}
+
+/// Returns the agent circuit ID of the relay which forwarded `message`, if
+/// any.
+fn circuit_id(message: &Message) -> Option<&[u8]> {
+    message
+        .get_relay_agent_information()
+        .and_then(|info| info.circuit_id())
+}
+
+/// Returns the key identifying the client which sent `message`. This is the
+/// client identifier (option 61) if present, the hardware address otherwise.
+/// Client identifiers carrying a hardware address are keyed by that address,
+/// so the key doesn't change if a client only sends the option sometimes.
+///
+/// If the hardware address fields are inconsistent, 'chaddr' cut off after
+/// the length of the advertised hardware type is used. Identifiers too long
+/// for a hardware address are keyed by their octets if 'chaddr' is empty,
+/// like for IPoIB clients.
+fn client_key(message: &Message) -> String {
+    if let Err(err) = message.validate_hardware_addr() {
+        warn!(
+            target: WIRE_TARGET,
+            %err,
+            "inconsistent hardware address fields, keying client by chaddr"
+        );
+        return message
+            .chaddr
+            .truncated(message.header.htype.addr_len())
+            .to_string();
+    }
+
+    let octets = |ident: &ClientIdentifier| {
+        let bytes: Vec<String> = ident
+            .identifier()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("{:02x}-{}", ident.ty(), bytes.join(":"))
+    };
+
+    match message.get_client_identifier() {
+        // Validated above, this is an address of the advertised type
+        Some(ident) if ident.ty() != 0 => match HardwareAddr::try_from(ident.identifier()) {
+            Ok(addr) => addr.to_string(),
+            Err(_) if message.chaddr.is_zero() => octets(ident),
+            Err(_) => message.chaddr.to_string(),
+        },
+        Some(ident) => octets(ident),
+        None => message.chaddr.to_string(),
+    }
+}
+
+async fn handle_request(message: Message, session: Session) -> Option<Reply> {
+    let state = &session.state;
+    let key = client_key(&message);
+    let now = state.clock.now_system();
+    let started = state.clock.now_instant();
+
+    let requested_addr = message.get_requested_ip_addr().unwrap_or(message.ciaddr);
+
+    let xid = format!("{:#010x}", message.header.xid);
+
+    let lease = match message.get_server_identifier() {
+        // The client selected the offer of another server, our offer is
+        // withdrawn without a reply
+        Some(id) if id != state.config.server_identifier => {
+            let released = state.release_lost_offer(&key, message.header.xid);
+            if released.is_some() {
+                ServerStats::incr(&state.stats.lost_to_other_server);
+            }
+
+            debug!(
+                target: LEASE_TARGET,
+                decision = "ignore",
+                client = %message.chaddr,
+                xid = %xid,
+                server = %id,
+                released = released.map(|addr| addr.to_string()),
+                "client selected another server"
+            );
+            return None;
+        }
+        // SELECTING state, the client requests an offered address
+        Some(_) => match state.commit_offer(&key, requested_addr, now) {
+            Ok(lease) => {
+                persist_change(state);
+                Some(lease)
+            }
+            Err(err) => {
+                debug!(
+                    target: LEASE_TARGET,
+                    client = %message.chaddr,
+                    xid = %xid,
+                    %err,
+                    "offer not committed"
+                );
+                None
+            }
+        },
+        // RENEWING or REBINDING state, the client already uses 'ciaddr'
+        None if is_renewal(&message)
+            && state.config.check_renewal_source
+            && message.giaddr.is_unspecified()
+            && session.addr.ip() != IpAddr::V4(message.ciaddr) =>
+        {
+            debug!(
+                target: LEASE_TARGET,
+                decision = "ignore",
+                client = %message.chaddr,
+                xid = %xid,
+                addr = %message.ciaddr,
+                source = %session.addr,
+                "renewal not sent from client address"
+            );
+            ServerStats::incr(&state.stats.renewal_source_mismatches);
+            return None;
+        }
+        // INIT-REBOOT, RENEWING or REBINDING state. Renewals of addresses on
+        // our subnets are NAKed if the client holds no lease for them,
+        // otherwise stay silent if there is no record of the client.
+        None => match state.renew_lease(&key, requested_addr, now) {
+            Some(lease) => Some(lease),
+            None if is_renewal(&message)
+                && state.config.nak_unknown_renewals
+                && state.serves_subnet(&message.ciaddr) =>
+            {
+                None
+            }
+            None => {
+                debug!(
+                    target: LEASE_TARGET,
+                    decision = "ignore",
+                    client = %message.chaddr,
+                    xid = %xid,
+                    addr = %requested_addr,
+                    "no record of client"
+                );
+                return None;
+            }
+        },
+    };
+
+    if let (Some(_), Some(name)) = (&lease, message.get_host_name()) {
+        state.assign_hostname(&key, name, now);
+    }
+
+    state.touch_lease(&key, DhcpMessageType::Request, now, circuit_id(&message));
+
+    // Renewals and INIT-REBOOT start a new transaction
+    let mut record = state
+        .take_transaction(&key)
+        .unwrap_or_else(|| transaction_record(&key, &message, &session, started));
+    record.push(TransactionStep::Request);
+    if let Some(name) = message.get_host_name() {
+        record.hostname = Some(name.clone());
+    }
+
+    match &lease {
+        Some(lease) => {
+            record.push(TransactionStep::Ack);
+            record.addr = Some(lease.ip_addr());
+            record.lease_time = Some(lease.lease_time());
+            record.pool = state.pool_name(lease.ip_addr());
+        }
+        None => record.push(TransactionStep::Nak),
+    }
+    state.complete_transaction(record);
+
+    let reply = match lease {
+        Some(lease) => {
+            info!(
+                target: LEASE_TARGET,
+                decision = "ack",
+                client = %message.chaddr,
+                xid = %xid,
+                addr = %lease.ip_addr(),
+                lease_time = lease.lease_time(),
+                "acknowledging lease"
+            );
+
+            // Renewals of leases conflicting with a reservation are shorter
+            let times = state
+                .lease_times(lease.ip_addr())
+                .truncated(lease.lease_time());
+            let mut options = select_reply_options(
+                &state.config,
+                &times,
+                &state.pool_options(lease.ip_addr()),
+                message.get_parameter_request_list(),
+            );
+            options.extend(select_vendor_options(
+                &state.config.vendor_profiles,
+                &message,
+            ));
+            state.builder.make_ack_message(
+                &message,
+                lease.ip_addr(),
+                state.config.server_identifier,
+                lease.lease_time(),
+                options,
+            )
+        }
+        None => {
+            info!(
+                target: LEASE_TARGET,
+                decision = "nak",
+                client = %message.chaddr,
+                xid = %xid,
+                addr = %requested_addr,
+                "rejecting requested address"
+            );
+
+            state
+                .builder
+                .make_nak_message(&message, state.config.server_identifier)
+        }
+    };
+
+    match reply {
+        Ok(reply) => encode_reply(reply, &session),
+        Err(err) => {
+            println!("Error while creating DHCP reply: {}", err);
+            None
+        }
+    }
+}
+
+/// Returns if the DHCPREQUEST `message` was sent in the RENEWING or
+/// REBINDING state, that is, with 'ciaddr' set and without the requested IP
+/// address option.
+fn is_renewal(message: &Message) -> bool {
+    !message.ciaddr.is_unspecified() && message.get_requested_ip_addr().is_none()
+}
+
+/// Answers a DHCPINFORM with the configuration parameters for the client's
+/// subnet. The client already has an address, so neither the pools nor the
+/// leases are touched. The DHCPACK is unicast to 'ciaddr'.
+async fn handle_inform(message: Message, session: Session) -> Option<Reply> {
+    let state = &session.state;
+
+    if message.ciaddr.is_unspecified() {
+        println!("Ignoring DHCPINFORM without client address");
+        return None;
+    }
+
+    // Relayed messages are sent from the client's subnet the relay agent
+    // is part of
+    let subnet_addr = match message.giaddr.is_unspecified() {
+        true => message.ciaddr,
+        false => message.giaddr,
+    };
+
+    let known_subnet = state.serves_subnet(&subnet_addr);
+    if !known_subnet && !state.config.inform_unknown_subnets {
+        return None;
+    }
+
+    let mut options = select_inform_options(
+        &state.config,
+        message.get_parameter_request_list(),
+        known_subnet,
+    );
+    options.extend(select_vendor_options(
+        &state.config.vendor_profiles,
+        &message,
+    ));
+
+    match state
+        .builder
+        .make_inform_ack_message(&message, state.config.server_identifier, options)
+    {
+        Ok(reply) => {
+            let addr = SocketAddr::from((message.ciaddr, constants::CLIENT_PORT));
+            encode_reply_to(reply, addr, &session)
+        }
+        Err(err) => {
+            println!("Error while creating DHCPACK: {}", err);
+            None
+        }
+    }
+}
+
+/// Answers a DHCPLEASEQUERY of a relay agent or access concentrator. The
+/// query either names an address ('ciaddr') or a client, by its client
+/// identifier or hardware address. All addresses bound to the client are
+/// returned in a single associated-ip option (92), capped at the maximum
+/// message size of the requester. Queries are only answered if they were
+/// relayed by one of the trusted relay agents, as they expose the bindings
+/// of all clients.
+///
+/// ### See
+///
+/// RFC 4388 - Section 6 - Leasequery Server Behavior: https://datatracker.ietf.org/doc/html/rfc4388#section-6
+async fn handle_lease_query(message: Message, session: Session) -> Option<Reply> {
+    let state = &session.state;
+    let now = state.clock.now_system();
+
+    let trusted = match session.addr.ip() {
+        IpAddr::V4(source) => state.config.trusted_relays.contains(&source),
+        IpAddr::V6(_) => false,
+    };
+    if message.giaddr.is_unspecified() || !trusted {
+        debug!(
+            target: WIRE_TARGET,
+            source = %session.addr,
+            giaddr = %message.giaddr,
+            "dropping leasequery of untrusted source"
+        );
+        ServerStats::incr(&state.stats.untrusted_lease_queries);
+        ServerStats::incr(&session.stats.dropped);
+        return None;
+    }
+
+    let key = match message.ciaddr.is_unspecified() {
+        true => Some(client_key(&message)),
+        false => state
+            .bindings
+            .lock()
+            .unwrap()
+            .key_of(&message.ciaddr)
+            .map(String::from),
+    };
+
+    let addrs = key
+        .as_ref()
+        .map(|key| state.bindings.lock().unwrap().addresses(key))
+        .unwrap_or_default();
+
+    // Report the binding of the queried address, or the most recent one
+    let lease = key
+        .as_ref()
+        .and_then(|key| match message.ciaddr.is_unspecified() {
+            true => state.leases.lock().unwrap().get(key).cloned(),
+            false => state
+                .bindings
+                .lock()
+                .unwrap()
+                .lease(key, &message.ciaddr)
+                .cloned(),
+        });
+
+    let (ty, binding, options) = match lease {
+        Some(lease) if !addrs.is_empty() => {
+            let addr = lease.ip_addr();
+            let remaining = lease.remaining(now).map_or(0, |d| d.as_secs() as u32);
+            let mut options = vec![
+                DhcpOption::new(
+                    OptionTag::IpAddrLeaseTime,
+                    OptionData::IpAddrLeaseTime(remaining),
+                ),
+                DhcpOption::new(OptionTag::AssociatedIp, OptionData::AssociatedIp(addrs)),
+            ];
+
+            if let Some(last_seen) = lease.last_seen() {
+                let elapsed = now.duration_since(last_seen).unwrap_or_default();
+                options.insert(
+                    1,
+                    DhcpOption::new(
+                        OptionTag::ClientLastTransactionTime,
+                        OptionData::ClientLastTransactionTime(elapsed.as_secs() as u32),
+                    ),
+                );
+            }
+
+            (
+                DhcpMessageType::LeaseActive,
+                Some((addr, lease.hardware_addr().clone())),
+                options,
+            )
+        }
+        // Addresses of our pools without a binding are unassigned
+        _ if !message.ciaddr.is_unspecified() && state.serves_subnet(&message.ciaddr) => {
+            (DhcpMessageType::LeaseUnassigned, None, Vec::new())
+        }
+        _ => (DhcpMessageType::LeaseUnknown, None, Vec::new()),
+    };
+
+    let mut reply = match state.builder.make_lease_query_reply(
+        &message,
+        ty,
+        state.config.server_identifier,
+        binding,
+        options,
+    ) {
+        Ok(reply) => reply,
+        Err(err) => {
+            println!("Error while creating leasequery reply: {}", err);
+            return None;
+        }
+    };
+
+    match fit_associated_ips(&mut reply, session.max_message_size) {
+        Ok(true) => warn!(
+            target: WIRE_TARGET,
+            max_message_size = session.max_message_size,
+            "truncated associated-ip list of leasequery reply"
+        ),
+        Ok(false) => {}
+        Err(err) => {
+            println!("Error while creating leasequery reply: {}", err);
+            return None;
+        }
+    }
+
+    // Leasequeries are answered to the relay agent, or the requester itself
+    let addr = match message.giaddr.is_unspecified() {
+        true => session.addr,
+        false => SocketAddr::from((message.giaddr, constants::SERVER_PORT)),
+    };
+
+    encode_reply_to(reply, addr, &session)
 }
 
-async fn handle_release(message: Message, session: Session) {
+async fn handle_decline(message: Message, session: Session) -> Option<Reply> {
     todo!()
 }
+
+/// Returns the address in 'ciaddr' to the pools. The lease is kept as an
+/// expired record. DHCPRELEASEs aren't answered.
+async fn handle_release(message: Message, session: Session) -> Option<Reply> {
+    let state = &session.state;
+
+    if let Some(id) = message.get_server_identifier() {
+        if id != state.config.server_identifier {
+            return None;
+        }
+    }
+
+    let key = client_key(&message);
+    if !state.release_lease(&key, message.ciaddr, state.clock.now_system()) {
+        println!(
+            "Ignoring DHCPRELEASE of {} from client {} without lease",
+            message.ciaddr, key
+        );
+        return None;
+    }
+
+    persist_change(state);
+    None
+}
+
+/// Writes the state after a lease was committed or released. A failing
+/// store degrades persistence right away, so with required persistence no
+/// further leases are granted, instead of only on the next flush.
+fn persist_change(state: &ServerState) {
+    if let Err(err) = state.persist() {
+        debug!(target: DAEMON_TARGET, %err, "persisting lease change failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        server::storage::{PersistedState, StateStore, STATE_VERSION},
+        types::{
+            options::{
+                Authentication, ClientIdentifier, ParameterRequestList, RelayAgentInformation,
+                ALGORITHM_HMAC_MD5, CIRCUIT_ID_SUB_OPTION, PROTOCOL_DELAYED, RDM_MONOTONIC_COUNTER,
+            },
+            ParseErrorKind,
+        },
+        utils::LogCapture,
+        Clock, StepClock,
+    };
+
+    fn discover() -> Vec<u8> {
+        let mut message = Message::new_with_xid(0xdeadbeef);
+        message.set_hardware_address(
+            HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+        );
+        message
+            .add_option_parts(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Discover),
+            )
+            .unwrap();
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    fn inform(ciaddr: Ipv4Addr, requested: Option<Vec<OptionTag>>) -> Vec<u8> {
+        let mut message = Message::new_with_xid(0xcafe);
+        message.ciaddr = ciaddr;
+        message.set_hardware_address(
+            HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+        );
+        message
+            .add_option_parts(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Inform),
+            )
+            .unwrap();
+
+        if let Some(tags) = requested {
+            message
+                .add_option_parts(
+                    OptionTag::ParameterRequestList,
+                    OptionData::ParameterRequestList(ParameterRequestList::new(tags)),
+                )
+                .unwrap();
+        }
+
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    fn inform_server(answer_unknown: bool) -> Server {
+        Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_subnet_mask(Ipv4Addr::new(255, 255, 255, 0))
+            .with_domain_name("lan".into())
+            .with_calculate_times(true)
+            .with_inform_unknown_subnets(answer_unknown)
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap()
+    }
+
+    /// Hands `bytes` received from `source` on `interface` to the service
+    /// of `server`, like the UDP front-end does, and decodes the reply.
+    async fn call_on(
+        server: &Server,
+        source: SocketAddr,
+        interface: ListenInterface,
+        bytes: &[u8],
+    ) -> Option<(Message, SocketAddr)> {
+        let meta = RequestMeta::new(source).with_interface(interface);
+        let reply = server.service().call(bytes, meta).await?;
+
+        Some((reply.message().unwrap(), reply.destination))
+    }
+
+    /// Hands `bytes` received from `source` to the service of `server`.
+    async fn call_from(
+        server: &Server,
+        source: SocketAddr,
+        bytes: &[u8],
+    ) -> Option<(Message, SocketAddr)> {
+        call_on(server, source, ListenInterface::any(), bytes).await
+    }
+
+    /// Hands `bytes` received from a client without an address to the
+    /// service of `server`.
+    async fn call(server: &Server, bytes: &[u8]) -> Option<(Message, SocketAddr)> {
+        let source = SocketAddr::from((Ipv4Addr::UNSPECIFIED, constants::CLIENT_PORT));
+        call_from(server, source, bytes).await
+    }
+
+    async fn send_inform(server: &Server, inform: Vec<u8>) -> Vec<(Message, SocketAddr)> {
+        call(server, &inform).await.into_iter().collect()
+    }
+
+    fn tags(message: &Message) -> Vec<OptionTag> {
+        message
+            .options
+            .iter()
+            .map(|o| o.header().tag.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_run_async_in_runtime() {
+        let mut server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_bind_address(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        // The shutdown is remembered until the server waits for it
+        server.shutdown_handle().shutdown();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server.run_async()).await;
+        assert!(matches!(result, Ok(Ok(()))));
+        assert!(!server.is_running);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_handle_flushes_state() {
+        let path =
+            std::env::temp_dir().join(format!("vulcan-shutdown-{}.json", std::process::id()));
+        let mut server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_bind_address(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .with_allow_foreign_pools(true)
+            .with_state_file(path.clone())
+            .build()
+            .unwrap();
+
+        // A pending offer, which is only persisted by the final flush
+        assert!(call(&server, &discover()).await.is_some());
+        assert!(PersistedState::load(&path).unwrap().is_none());
+
+        let handle = server.shutdown_handle();
+        let run = tokio::spawn(async move { server.run_async().await });
+        handle.shutdown();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), run).await;
+        assert!(matches!(result, Ok(Ok(Ok(())))));
+
+        let persisted = PersistedState::load(&path).unwrap().unwrap();
+        assert_eq!(persisted.pending.len(), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discover_is_answered_with_offer() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let (offer, addr) = call(&server, &discover()).await.unwrap();
+        assert_eq!(offer.get_message_type(), Some(&DhcpMessageType::Offer));
+        assert_eq!(offer.header.xid, 0xdeadbeef);
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(
+            offer.get_server_identifier(),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(addr.ip(), Ipv4Addr::BROADCAST);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_discover_is_coalesced() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let (first, _) = call(&server, &discover()).await.unwrap();
+        let (second, _) = call(&server, &discover()).await.unwrap();
+        assert_eq!(second.get_message_type(), Some(&DhcpMessageType::Offer));
+        assert_eq!(second.yiaddr, first.yiaddr);
+
+        let stats = server.stats();
+        assert_eq!(ServerStats::get(&stats.discover_cache_misses), 1);
+        assert_eq!(ServerStats::get(&stats.discover_cache_hits), 1);
+        assert_eq!(ServerStats::get(&stats.allocations), 1);
+
+        // Other clients get offers of their own
+        let other = from_client("de:ad:be:ef:12:35", discover());
+        let (offer, _) = call(&server, &other).await.unwrap();
+        assert_ne!(offer.yiaddr, first.yiaddr);
+        assert_eq!(ServerStats::get(&stats.discover_cache_misses), 2);
+    }
+
+    #[tokio::test]
+    async fn test_offer_is_logged() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let capture = LogCapture::default();
+        let _guard = tracing::subscriber::set_default(capture.subscriber(tracing::Level::INFO));
+        call(&server, &discover()).await;
+
+        let output = capture.output();
+        let line = output
+            .lines()
+            .find(|line| line.contains("offering address"))
+            .expect("no offer event");
+
+        assert!(line.contains("decision=\"offer\""));
+        assert!(line.contains("client=de:ad:be:ef:12:34"));
+        assert!(line.contains("xid=0xdeadbeef"));
+        assert!(line.contains("addr=192.168.1.100"));
+        assert!(line.contains("lease_time="));
+    }
+
+    #[tokio::test]
+    async fn test_log_targets() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let capture = LogCapture::default();
+        let _guard = tracing::subscriber::set_default(capture.subscriber(tracing::Level::TRACE));
+        call(&server, &discover()).await;
+
+        let output = capture.output();
+        let line_of = |message: &str| {
+            output
+                .lines()
+                .find(|line| line.contains(message))
+                .unwrap_or_else(|| panic!("no '{message}' event"))
+        };
+
+        assert!(line_of("received DHCP message").contains(&format!(" {WIRE_TARGET}: ")));
+        assert!(line_of("offering address").contains(&format!(" {LEASE_TARGET}: ")));
+    }
+
+    #[tokio::test]
+    async fn test_inform_from_served_subnet() {
+        let server = inform_server(false);
+        let client = Ipv4Addr::new(192, 168, 1, 50);
+        let requested = vec![OptionTag::SubnetMask, OptionTag::DomainName];
+
+        let sent = send_inform(&server, inform(client, Some(requested))).await;
+        assert_eq!(sent.len(), 1);
+
+        let (ack, addr) = &sent[0];
+        assert_eq!(ack.get_message_type(), Some(&DhcpMessageType::Ack));
+        assert_eq!(ack.yiaddr, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(ack.ciaddr, client);
+        assert_eq!(*addr, SocketAddr::from((client, constants::CLIENT_PORT)));
+
+        let tags = tags(ack);
+        assert!(tags.contains(&OptionTag::SubnetMask));
+        assert!(tags.contains(&OptionTag::DomainName));
+        assert!(!tags.contains(&OptionTag::IpAddrLeaseTime));
+        assert!(!tags.contains(&OptionTag::RenewalT1Time));
+        assert!(!tags.contains(&OptionTag::RebindingT2Time));
+
+        // Neither the pool nor the leases were touched
+        assert!(server.service.state.leases.lock().unwrap().is_empty());
+        assert!(server.service.state.pools.lock().unwrap()[0]
+            .free_addresses()
+            .contains(&Ipv4Addr::new(192, 168, 1, 100)));
+    }
+
+    #[tokio::test]
+    async fn test_inform_without_prl() {
+        let server = inform_server(false);
+        let sent = send_inform(&server, inform(Ipv4Addr::new(192, 168, 1, 50), None)).await;
+        assert_eq!(sent.len(), 1);
+
+        assert_eq!(
+            tags(&sent[0].0),
+            vec![
+                OptionTag::DhcpMessageType,
+                OptionTag::ServerIdentifier,
+                OptionTag::End
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inform_from_unknown_subnet() {
+        let client = Ipv4Addr::new(10, 0, 0, 50);
+        let requested = vec![OptionTag::SubnetMask, OptionTag::DomainName];
+
+        // Ignored by default
+        let server = inform_server(false);
+        let sent = send_inform(&server, inform(client, Some(requested.clone()))).await;
+        assert!(sent.is_empty());
+
+        // Answered with the global options only
+        let server = inform_server(true);
+        let sent = send_inform(&server, inform(client, Some(requested))).await;
+        assert_eq!(sent.len(), 1);
+
+        let tags = tags(&sent[0].0);
+        assert!(tags.contains(&OptionTag::DomainName));
+        assert!(!tags.contains(&OptionTag::SubnetMask));
+    }
+
+    fn discover_with_max_size(size: u16) -> Vec<u8> {
+        let mut message = Message::new_with_xid(0xdeadbeef);
+        message.set_hardware_address(
+            HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+        );
+        message
+            .add_option_parts(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Discover),
+            )
+            .unwrap();
+        message
+            .add_option_parts(
+                OptionTag::MaxDhcpMessageSize,
+                OptionData::MaxDhcpMessageSize(size),
+            )
+            .unwrap();
+        message
+            .add_option_parts(
+                OptionTag::ParameterRequestList,
+                OptionData::ParameterRequestList(ParameterRequestList::new(vec![
+                    OptionTag::DomainName,
+                    OptionTag::DomainSearch,
+                ])),
+            )
+            .unwrap();
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    /// Returns the options and encoded length of the DHCPOFFER sent to a
+    /// client advertising `size` as its maximum message size. The configured
+    /// domain options don't fit into 576 octets.
+    async fn offer_for_max_size(size: u16) -> (Vec<OptionTag>, usize) {
+        let label = "a".repeat(60);
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_domain_name(format!("{label}.{label}.{label}.example"))
+            .with_domain_search(vec![
+                format!("{label}.one.example"),
+                format!("{label}.two.example"),
+                format!("{label}.three.example"),
+            ])
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let (offer, _) = call(&server, &discover_with_max_size(size)).await.unwrap();
+        let mut buf = WriteBuffer::new();
+        let len = offer.write::<BigEndian>(&mut buf).unwrap();
+
+        (tags(&offer), len)
+    }
+
+    #[tokio::test]
+    async fn test_reply_respects_max_message_size() {
+        // 576 octets minus the IP and UDP headers
+        let (tags, len) = offer_for_max_size(576).await;
+        assert!(len <= 548, "{len}");
+        assert!(tags.contains(&OptionTag::DomainName));
+        assert!(!tags.contains(&OptionTag::DomainSearch));
+        assert!(tags.contains(&OptionTag::IpAddrLeaseTime));
+
+        let (tags, len) = offer_for_max_size(1200).await;
+        assert!(len > 548);
+        assert!(tags.contains(&OptionTag::DomainName));
+        assert!(tags.contains(&OptionTag::DomainSearch));
+
+        // Values below the legal minimum are treated as 576
+        assert_eq!(offer_for_max_size(300).await, offer_for_max_size(576).await);
+    }
+
+    #[tokio::test]
+    async fn test_stats_are_attributed_to_interface() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let client = SocketAddr::from((Ipv4Addr::UNSPECIFIED, constants::CLIENT_PORT));
+        let interface = |name: &str, index: u32| ListenInterface {
+            name: name.into(),
+            index,
+        };
+
+        call_on(&server, client, interface("eth0", 2), &discover()).await;
+        call_on(&server, client, interface("eth0", 2), &discover()).await;
+        call_on(&server, client, interface("eth1", 3), &discover()).await;
+        call_on(&server, client, interface("eth1", 3), &[0u8; 10]).await;
+
+        let stats = server.stats();
+        assert_eq!(
+            stats.per_interface(),
+            vec![
+                (
+                    String::from("eth0"),
+                    InterfaceCounters {
+                        received: 2,
+                        dropped: 0,
+                        replies: 2
+                    }
+                ),
+                (
+                    String::from("eth1"),
+                    InterfaceCounters {
+                        received: 2,
+                        dropped: 1,
+                        replies: 1
+                    }
+                ),
+            ]
+        );
+        assert_eq!(stats.total().received, 4);
+    }
+
+    #[tokio::test]
+    async fn test_replayed_authenticated_message_is_dropped() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_auth_key(7, b"secret".to_vec())
+            .build()
+            .unwrap();
+
+        let send = |replay_detection: u64, key: &'static [u8]| {
+            let mut message = Message::new_with_xid(0xdeadbeef);
+            message.set_hardware_address(
+                HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+            );
+            message
+                .add_option_parts(
+                    OptionTag::DhcpMessageType,
+                    OptionData::DhcpMessageType(DhcpMessageType::Discover),
+                )
+                .unwrap();
+            message
+                .add_option_parts(
+                    OptionTag::Authentication,
+                    OptionData::Authentication(Authentication::new(
+                        PROTOCOL_DELAYED,
+                        ALGORITHM_HMAC_MD5,
+                        RDM_MONOTONIC_COUNTER,
+                        replay_detection,
+                        [&7u32.to_be_bytes()[..], &[0; 16]].concat(),
+                    )),
+                )
+                .unwrap();
+            message.end().unwrap();
+
+            let mut buf = WriteBuffer::new();
+            message.write::<BigEndian>(&mut buf).unwrap();
+            let mut buf = buf.bytes().to_vec();
+            auth::sign(&mut buf, key);
+
+            let server = &server;
+            async move { call(server, &buf).await }
+        };
+
+        assert!(send(1, b"secret").await.is_some());
+
+        // The same replay detection value again
+        assert!(send(1, b"secret").await.is_none());
+        assert_eq!(ServerStats::get(&server.stats().replays_rejected), 1);
+
+        // Forged messages are dropped without advancing the counter
+        assert!(send(100, b"forged").await.is_none());
+        assert_eq!(ServerStats::get(&server.stats().auth_failures), 1);
+
+        assert!(send(2, b"secret").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_parse_errors_are_counted_by_kind() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        // The magic cookie follows the 236 octets of fixed fields
+        let mut cookieless = discover();
+        cookieless[236..240].copy_from_slice(&[0; 4]);
+        call(&server, &cookieless).await;
+
+        let parse_errors = &server.stats().parse_errors;
+        assert_eq!(parse_errors.get(ParseErrorKind::BadCookie), 1);
+        for kind in ParseErrorKind::ALL {
+            if kind != ParseErrorKind::BadCookie {
+                assert_eq!(parse_errors.get(kind), 0, "{kind}");
+            }
+        }
+
+        // A host name claiming more octets than the message has left
+        let mut overrun = discover();
+        assert_eq!(overrun.pop(), Some(255));
+        overrun.extend_from_slice(&[12, 50, b'x']);
+        call(&server, &overrun).await;
+
+        call(&server, &[0u8; 10]).await;
+
+        let parse_errors = &server.stats().parse_errors;
+        assert_eq!(parse_errors.get(ParseErrorKind::BadOptionLength), 1);
+        assert_eq!(parse_errors.get(ParseErrorKind::Truncated), 1);
+        assert_eq!(parse_errors.get(ParseErrorKind::BadCookie), 1);
+    }
+
+    /// DHCPDISCOVER with a client identifier, changed by `edit`.
+    fn identified_discover(edit: impl FnOnce(&mut Message)) -> Vec<u8> {
+        let bytes = discover();
+        let mut message = Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes)).unwrap();
+        message.options.pop();
+        message
+            .add_option_parts(
+                OptionTag::ClientIdentifier,
+                OptionData::ClientIdentifier(ClientIdentifier::new(
+                    1,
+                    vec![0xde, 0xad, 0xbe, 0xef, 0x12, 0x34],
+                )),
+            )
+            .unwrap();
+        message.end().unwrap();
+        edit(&mut message);
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_drops_violations() {
+        let relay = Ipv4Addr::new(10, 0, 0, 254);
+        let client = SocketAddr::from((Ipv4Addr::UNSPECIFIED, constants::CLIENT_PORT));
+        let untrusted = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), constants::SERVER_PORT));
+
+        // The magic cookie follows the 236 octets of fixed fields
+        let mut cookieless = identified_discover(|_| {});
+        cookieless[236..240].copy_from_slice(&[0; 4]);
+
+        // IEEE 802 networks instead of Ethernet
+        let mut token_ring = identified_discover(|_| {});
+        token_ring[1] = 6;
+
+        // Violation, message, source and whether the message is answered
+        // outside of strict mode
+        let cases = [
+            (
+                StrictViolation::MissingMagicCookie,
+                cookieless,
+                client,
+                false,
+            ),
+            (
+                StrictViolation::UnsupportedHardwareType,
+                token_ring,
+                client,
+                false,
+            ),
+            (
+                StrictViolation::MissingClientIdentifier,
+                discover(),
+                client,
+                true,
+            ),
+            (
+                StrictViolation::UntrustedRelay,
+                identified_discover(|m| m.giaddr = Ipv4Addr::new(10, 1, 0, 1)),
+                untrusted,
+                true,
+            ),
+            (
+                StrictViolation::ExcessiveSecs,
+                identified_discover(|m| m.header.secs = STRICT_MAX_SECS + 1),
+                client,
+                true,
+            ),
+        ];
+
+        for (violation, bytes, source, answered) in cases {
+            for strict in [false, true] {
+                let server = Server::builder()
+                    .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+                    .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+                    .with_allow_foreign_pools(true)
+                    .with_strict(strict)
+                    .with_trusted_relays(vec![relay])
+                    .build()
+                    .unwrap();
+
+                let replies = call_from(&server, source, &bytes).await.into_iter().count();
+                let drops = server.stats().strict_drops.get(violation);
+
+                if strict {
+                    assert_eq!((replies, drops), (0, 1), "{violation} in strict mode");
+                } else {
+                    assert_eq!(replies, answered as usize, "{violation} in lenient mode");
+                    assert_eq!(drops, 0, "{violation} in lenient mode");
+                }
+            }
+        }
+
+        // Relayed messages from trusted relays and clean messages pass
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .with_strict(true)
+            .with_trusted_relays(vec![relay])
+            .build()
+            .unwrap();
+
+        for (bytes, source) in [
+            (identified_discover(|_| {}), client),
+            (
+                identified_discover(|m| m.giaddr = Ipv4Addr::new(10, 1, 0, 1)),
+                SocketAddr::from((relay, constants::SERVER_PORT)),
+            ),
+        ] {
+            assert!(call_from(&server, source, &bytes).await.is_some());
+        }
+    }
+
+    fn client_message(ty: DhcpMessageType, ciaddr: Ipv4Addr, options: Vec<OptionData>) -> Vec<u8> {
+        let mut message = Message::new_with_xid(0xabcd);
+        message.ciaddr = ciaddr;
+        message.set_hardware_address(
+            HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+        );
+        message
+            .add_option_parts(OptionTag::DhcpMessageType, OptionData::DhcpMessageType(ty))
+            .unwrap();
+
+        for data in options {
+            let tag = match data {
+                OptionData::RequestedIpAddr(_) => OptionTag::RequestedIpAddr,
+                OptionData::ServerIdentifier(_) => OptionTag::ServerIdentifier,
+                OptionData::RelayAgentInformation(_) => OptionTag::RelayAgentInformation,
+                OptionData::ParameterRequestList(_) => OptionTag::ParameterRequestList,
+                OptionData::ClientIdentifier(_) => OptionTag::ClientIdentifier,
+                _ => unreachable!(),
+            };
+            message.add_option_parts(tag, data).unwrap();
+        }
+
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_lease_metadata_progression() {
+        let clock = StepClock::default();
+        let server_id = Ipv4Addr::new(192, 168, 1, 1);
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(server_id)
+            .with_allow_foreign_pools(true)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let send = |buf: Vec<u8>| {
+            let server = &server;
+            async move { call(server, &buf).await }
+        };
+        let lease = || {
+            let leases = server.service.state.leases.lock().unwrap();
+            leases.get("de:ad:be:ef:12:34").cloned().unwrap()
+        };
+
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+        let relay = RelayAgentInformation::new(vec![(CIRCUIT_ID_SUB_OPTION, b"port7".to_vec())]);
+
+        send(discover()).await;
+        clock.advance(Duration::from_secs(1));
+        send(client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::UNSPECIFIED,
+            vec![
+                OptionData::RequestedIpAddr(addr),
+                OptionData::ServerIdentifier(server_id),
+                OptionData::RelayAgentInformation(relay),
+            ],
+        ))
+        .await;
+
+        let acked = lease();
+        assert_eq!(acked.last_message_type(), Some(&DhcpMessageType::Request));
+        assert_eq!(acked.last_seen(), Some(clock.now_system()));
+        assert_eq!(acked.renew_count(), 0);
+        assert_eq!(acked.circuit_id(), Some(&b"port7"[..]));
+
+        // RENEWING, unicast to the server without relay agent
+        clock.advance(Duration::from_secs(1800));
+        send(client_message(DhcpMessageType::Request, addr, vec![])).await;
+
+        let renewed = lease();
+        assert_eq!(renewed.last_seen(), Some(clock.now_system()));
+        assert_eq!(renewed.renew_count(), 1);
+        assert_eq!(renewed.circuit_id(), Some(&b"port7"[..]));
+        assert!(renewed.leased_until() > acked.leased_until());
+
+        clock.advance(Duration::from_secs(60));
+        send(client_message(
+            DhcpMessageType::Release,
+            addr,
+            vec![OptionData::ServerIdentifier(server_id)],
+        ))
+        .await;
+
+        let released = lease();
+        assert_eq!(
+            released.last_message_type(),
+            Some(&DhcpMessageType::Release)
+        );
+        assert_eq!(released.last_seen(), Some(clock.now_system()));
+        assert_eq!(released.remaining(clock.now_system()), None);
+        assert!(!server.service.state.pools.lock().unwrap()[0].is_allocated(&addr));
+
+        // The metadata survives a restart, the released address stays free
+        let persisted = server.service.state.snapshot(clock.now_system());
+        assert_eq!(persisted.version, STATE_VERSION);
+
+        let json = serde_json::to_string(&persisted).unwrap();
+        let restored: PersistedState = serde_json::from_str(&json).unwrap();
+        let restored_lease = &restored.leases["de:ad:be:ef:12:34"];
+        assert_eq!(restored_lease.renew_count(), 1);
+        assert_eq!(restored_lease.circuit_id(), Some(&b"port7"[..]));
+    }
+
+    /// Replaces the hardware address of the client message `bytes`, which
+    /// starts at offset 28.
+    fn from_client(mac: &str, mut bytes: Vec<u8>) -> Vec<u8> {
+        let mac = HardwareAddr::try_from(String::from(mac)).unwrap();
+        bytes[28..34].copy_from_slice(&mac.as_bytes()[..6]);
+        bytes
+    }
+
+    /// Handles `bytes` as received from a client and returns the replies.
+    async fn exchange(server: &Server, bytes: Vec<u8>) -> Vec<Message> {
+        exchange_from(server, Ipv4Addr::UNSPECIFIED, bytes).await
+    }
+
+    /// Handles `bytes` as received from a client with the address `source`
+    /// and returns the replies.
+    async fn exchange_from(server: &Server, source: Ipv4Addr, bytes: Vec<u8>) -> Vec<Message> {
+        let source = SocketAddr::from((source, constants::CLIENT_PORT));
+
+        call_from(server, source, &bytes)
+            .await
+            .into_iter()
+            .map(|(reply, _)| reply)
+            .collect()
+    }
+
+    fn lease_time_of(reply: &Message) -> u32 {
+        reply
+            .get_option(OptionTag::IpAddrLeaseTime)
+            .and_then(|option| option.data().as_u32())
+            .unwrap()
+    }
+
+    fn reservation_server(clock: &StepClock) -> Server {
+        Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_lease_time(3600)
+            .with_allow_foreign_pools(true)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// Runs DISCOVER and REQUEST of the client with `mac` and returns the
+    /// acknowledged address.
+    async fn acquire(server: &Server, mac: &str) -> Ipv4Addr {
+        let offer = exchange(server, from_client(mac, discover()))
+            .await
+            .remove(0);
+        let request = client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::UNSPECIFIED,
+            vec![
+                OptionData::RequestedIpAddr(offer.yiaddr),
+                OptionData::ServerIdentifier(server.service.state.config.server_identifier),
+            ],
+        );
+
+        let ack = exchange(server, from_client(mac, request)).await.remove(0);
+        assert_eq!(ack.get_message_type(), Some(&DhcpMessageType::Ack));
+        ack.yiaddr
+    }
+
+    /// Counts the saved states, failing the saves while `failing` is set.
+    #[derive(Default)]
+    struct CountingStore {
+        failing: AtomicBool,
+        saves: AtomicUsize,
+    }
+
+    impl StateStore for CountingStore {
+        fn save(&self, _: &PersistedState) -> Result<(), ServerStorageError> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err(std::io::Error::other("no space left on device").into());
+            }
+
+            self.saves.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lease_changes_are_persisted() {
+        let store = Arc::new(CountingStore::default());
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .with_state_store(store.clone())
+            .with_require_persistence(true)
+            .build()
+            .unwrap();
+
+        let addr = acquire(&server, "02:00:00:00:00:01").await;
+        assert_eq!(store.saves.load(Ordering::Relaxed), 1);
+
+        let release = client_message(
+            DhcpMessageType::Release,
+            addr,
+            vec![OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1))],
+        );
+        assert!(exchange(&server, from_client("02:00:00:00:00:01", release))
+            .await
+            .is_empty());
+        assert_eq!(store.saves.load(Ordering::Relaxed), 2);
+
+        // The failed write degrades persistence with the acknowledgement,
+        // so no new client gets an offer
+        store.failing.store(true, Ordering::Relaxed);
+        acquire(&server, "02:00:00:00:00:02").await;
+        assert!(server.is_persistence_degraded());
+        assert!(
+            exchange(&server, from_client("02:00:00:00:00:03", discover()))
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction_records() {
+        let capture = crate::utils::LogCapture::default();
+        let _guard = tracing::subscriber::set_default(capture.subscriber(tracing::Level::INFO));
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let server_id = server.service.state.config.server_identifier;
+
+        acquire(&server, "02:00:00:00:00:01").await;
+
+        clock.advance(Duration::from_secs(1800));
+        let renewal = client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::new(192, 168, 1, 100),
+            Vec::new(),
+        );
+        exchange(&server, from_client("02:00:00:00:00:01", renewal)).await;
+
+        let unknown = client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::UNSPECIFIED,
+            vec![
+                OptionData::RequestedIpAddr(Ipv4Addr::new(192, 168, 1, 150)),
+                OptionData::ServerIdentifier(server_id),
+            ],
+        );
+        exchange(&server, from_client("02:00:00:00:00:02", unknown)).await;
+
+        // The offer expires once the next offer is recorded
+        exchange(&server, from_client("02:00:00:00:00:03", discover())).await;
+        clock.advance(Duration::from_secs(120));
+        acquire(&server, "02:00:00:00:00:04").await;
+
+        let output = capture.output();
+        let records: Vec<_> = output
+            .lines()
+            .filter_map(|line| line.split_once("transaction completed "))
+            .map(|(_, record)| record)
+            .collect();
+
+        assert_eq!(
+            records,
+            [
+                "ts=1600000000 interface=any flow=discover>offer>request>ack \
+                 client=02:00:00:00:00:01 hostname=- addr=192.168.1.100 lease=3600 \
+                 pool=default relay=- duration_ms=0",
+                "ts=1600001800 interface=any flow=request>ack client=02:00:00:00:00:01 \
+                 hostname=- addr=192.168.1.100 lease=3600 pool=default relay=- duration_ms=0",
+                "ts=1600001800 interface=any flow=request>nak client=02:00:00:00:00:02 \
+                 hostname=- addr=- lease=- pool=- relay=- duration_ms=0",
+                "ts=1600001920 interface=any flow=discover>offer>expired \
+                 client=02:00:00:00:00:03 hostname=- addr=192.168.1.101 lease=3600 \
+                 pool=default relay=- duration_ms=120000",
+                "ts=1600001920 interface=any flow=discover>offer>request>ack \
+                 client=02:00:00:00:00:04 hostname=- addr=192.168.1.102 lease=3600 \
+                 pool=default relay=- duration_ms=0",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reservation_hands_off_after_dynamic_lease() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let mac = "de:ad:be:ef:12:34";
+        let dynamic = Ipv4Addr::new(192, 168, 1, 100);
+        let reserved = Ipv4Addr::new(192, 168, 1, 150);
+
+        assert_eq!(acquire(&server, mac).await, dynamic);
+
+        let reservation = Reservation {
+            hardware_addr: HardwareAddr::try_from(String::from(mac)).unwrap(),
+            ip_addr: reserved,
+        };
+        let conflicts = server
+            .reload_reservations(vec![reservation.clone()])
+            .unwrap();
+        assert_eq!(
+            conflicts,
+            vec![ReservationConflict::Handoff {
+                reservation,
+                leased: dynamic,
+            }]
+        );
+
+        // The running lease is kept, a DISCOVER is offered the same address
+        clock.advance(Duration::from_secs(600));
+        let offer = exchange(&server, discover()).await.remove(0);
+        assert_eq!(offer.yiaddr, dynamic);
+
+        // Renewals are answered, but don't extend the lease
+        let leased_until = server.service.state.leases.lock().unwrap()[mac].leased_until();
+        clock.advance(Duration::from_secs(1200));
+        let renew = client_message(DhcpMessageType::Request, dynamic, vec![]);
+        let ack = exchange(&server, renew.clone()).await.remove(0);
+        assert_eq!(ack.get_message_type(), Some(&DhcpMessageType::Ack));
+        assert_eq!(lease_time_of(&ack), 1800);
+        assert_eq!(ack.get_renewal_t1_time(), None);
+        assert_eq!(
+            server.service.state.leases.lock().unwrap()[mac].leased_until(),
+            leased_until
+        );
+
+        // Once the lease ended, it is not renewed anymore and the client
+        // moves to the reserved address
+        clock.advance(Duration::from_secs(1800));
+        let nak = exchange(&server, renew).await.remove(0);
+        assert_eq!(nak.get_message_type(), Some(&DhcpMessageType::Nak));
+
+        assert_eq!(acquire(&server, mac).await, reserved);
+        assert!(server.reservation_conflicts().is_empty());
+        assert!(!server.service.state.pools.lock().unwrap()[0].is_allocated(&dynamic));
+    }
+
+    #[tokio::test]
+    async fn test_pending_reservation_converts_when_lease_revoked() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let (mac, other) = ("de:ad:be:ef:12:34", "02:00:00:00:00:02");
+        let reserved = Ipv4Addr::new(192, 168, 1, 100);
+
+        assert_eq!(acquire(&server, other).await, reserved);
+
+        let reservation = Reservation {
+            hardware_addr: HardwareAddr::try_from(String::from(mac)).unwrap(),
+            ip_addr: reserved,
+        };
+        let conflicts = server
+            .reload_reservations(vec![reservation.clone()])
+            .unwrap();
+        assert_eq!(
+            conflicts,
+            vec![ReservationConflict::Pending {
+                reservation,
+                holder: String::from(other),
+            }]
+        );
+
+        // While pending, the client is offered a dynamic address
+        let offer = exchange(&server, discover()).await.remove(0);
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 101));
+
+        // The holder's renewals don't extend its lease
+        clock.advance(Duration::from_secs(600));
+        let renew = client_message(DhcpMessageType::Request, reserved, vec![]);
+        let ack = exchange(&server, from_client(other, renew)).await.remove(0);
+        assert_eq!(lease_time_of(&ack), 3000);
+
+        // Revoking the lease converts the reservation right away
+        let other = HardwareAddr::try_from(String::from(other)).unwrap();
+        assert!(server.release_lease(&other).unwrap());
+        assert!(server.reservation_conflicts().is_empty());
+
+        assert_eq!(acquire(&server, mac).await, reserved);
+        let pools = server.service.state.pools.lock().unwrap();
+        assert!(!pools[0].is_allocated(&Ipv4Addr::new(192, 168, 1, 101)));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_chaddr_is_rejected() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let invalid = [
+            "ff:ff:ff:ff:ff:ff",
+            "01:00:5e:00:00:fb",
+            "00:00:00:00:00:00",
+        ];
+
+        for mac in invalid {
+            assert!(exchange(&server, from_client(mac, discover()))
+                .await
+                .is_empty());
+
+            let request = client_message(
+                DhcpMessageType::Request,
+                Ipv4Addr::UNSPECIFIED,
+                vec![
+                    OptionData::RequestedIpAddr(Ipv4Addr::new(192, 168, 1, 100)),
+                    OptionData::ServerIdentifier(server.service.state.config.server_identifier),
+                ],
+            );
+            assert!(exchange(&server, from_client(mac, request))
+                .await
+                .is_empty());
+        }
+
+        assert_eq!(ServerStats::get(&server.stats().invalid_chaddrs), 6);
+        assert_eq!(ServerStats::get(&server.stats().allocations), 0);
+        assert!(server.service.state.leases.lock().unwrap().is_empty());
+
+        // Unicast addresses, including locally administered ones, are fine
+        assert_eq!(
+            acquire(&server, "02:00:00:00:00:01").await,
+            Ipv4Addr::new(192, 168, 1, 100)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ipoib_clients_are_served() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+
+        // IPoIB clients send an empty 'chaddr' and identify themselves with
+        // an IAID and a DUID-LL of their 20 octet link-layer address
+        let ipoib = |ty: DhcpMessageType, guid: u8, options: Vec<(OptionTag, OptionData)>| {
+            let mut message = Message::new_with_xid(0xdeadbeef);
+            message.header.htype = HardwareType::InfiniBand;
+            message.set_hardware_address(HardwareAddr::try_from(&[][..]).unwrap());
+            message.set_is_broadcast(true);
+            message
+                .add_option_parts(OptionTag::DhcpMessageType, OptionData::DhcpMessageType(ty))
+                .unwrap();
+
+            let mut duid = vec![0, 0, 0, 1, 0, 3, 0, 32];
+            duid.extend([0x80, 0, 0x02, 0x48, 0xfe, 0x80, 0, 0, 0, 0, 0, 0]);
+            duid.extend([0, 0x02, 0xc9, 0x03, 0, 0x0a, 0xbc, guid]);
+            message
+                .add_option_parts(
+                    OptionTag::ClientIdentifier,
+                    OptionData::ClientIdentifier(ClientIdentifier::new(255, duid)),
+                )
+                .unwrap();
+
+            for (tag, data) in options {
+                message.add_option_parts(tag, data).unwrap();
+            }
+            message.end().unwrap();
+
+            let mut buf = WriteBuffer::new();
+            message.write::<BigEndian>(&mut buf).unwrap();
+            buf.bytes().to_vec()
+        };
+
+        let offer = exchange(&server, ipoib(DhcpMessageType::Discover, 1, vec![]))
+            .await
+            .remove(0);
+        assert_eq!(offer.header.htype, HardwareType::InfiniBand);
+        assert_eq!(offer.header.hlen, 0);
+
+        // Clients are told apart by their DUID, not by the empty 'chaddr'
+        let other = exchange(&server, ipoib(DhcpMessageType::Discover, 2, vec![]))
+            .await
+            .remove(0);
+        assert_ne!(other.yiaddr, offer.yiaddr);
+
+        let request = ipoib(
+            DhcpMessageType::Request,
+            1,
+            vec![
+                (
+                    OptionTag::RequestedIpAddr,
+                    OptionData::RequestedIpAddr(offer.yiaddr),
+                ),
+                (
+                    OptionTag::ServerIdentifier,
+                    OptionData::ServerIdentifier(server.service.state.config.server_identifier),
+                ),
+            ],
+        );
+        let ack = exchange(&server, request).await.remove(0);
+        assert_eq!(ack.get_message_type(), Some(&DhcpMessageType::Ack));
+        assert_eq!(ack.yiaddr, offer.yiaddr);
+        assert_eq!(ServerStats::get(&server.stats().invalid_chaddrs), 0);
+    }
+
+    #[tokio::test]
+    async fn test_excessive_hops_are_dropped() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+
+        let mut looping = from_client("02:00:00:00:00:01", discover());
+        looping[3] = constants::MAX_HOPS + 1;
+        assert!(exchange(&server, looping).await.is_empty());
+        assert_eq!(ServerStats::get(&server.stats().excessive_hops), 1);
+
+        let mut relayed = from_client("02:00:00:00:00:01", discover());
+        relayed[3] = constants::MAX_HOPS;
+        let replies = exchange(&server, relayed).await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].header.hops, 0);
+        assert_eq!(ServerStats::get(&server.stats().excessive_hops), 1);
+    }
+
+    #[tokio::test]
+    async fn test_renewal_of_unknown_lease_is_naked() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let unknown = Ipv4Addr::new(192, 168, 1, 150);
+
+        let renew = client_message(DhcpMessageType::Request, unknown, vec![]);
+        let replies = exchange_from(&server, unknown, renew).await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].get_message_type(), Some(&DhcpMessageType::Nak));
+        assert!(server.service.state.leases.lock().unwrap().is_empty());
+
+        // Addresses on other subnets belong to other servers
+        let foreign = Ipv4Addr::new(10, 0, 0, 5);
+        let renew = client_message(DhcpMessageType::Request, foreign, vec![]);
+        assert!(exchange_from(&server, foreign, renew).await.is_empty());
+
+        // INIT-REBOOT without a record stays silent
+        let reboot = client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::UNSPECIFIED,
+            vec![OptionData::RequestedIpAddr(unknown)],
+        );
+        assert!(exchange(&server, reboot).await.is_empty());
+
+        let lenient = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .with_nak_unknown_renewals(false)
+            .build()
+            .unwrap();
+        let renew = client_message(DhcpMessageType::Request, unknown, vec![]);
+        assert!(exchange_from(&lenient, unknown, renew).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_renewal_source_check() {
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .with_clock(clock.clone())
+            .with_renewal_source_check(true)
+            .build()
+            .unwrap();
+        let addr = acquire(&server, "de:ad:be:ef:12:34").await;
+
+        let renew = client_message(DhcpMessageType::Request, addr, vec![]);
+        let spoofed = Ipv4Addr::new(192, 168, 1, 99);
+        assert!(exchange_from(&server, spoofed, renew.clone())
+            .await
+            .is_empty());
+        assert_eq!(
+            ServerStats::get(&server.stats().renewal_source_mismatches),
+            1
+        );
+
+        let ack = exchange_from(&server, addr, renew).await.remove(0);
+        assert_eq!(ack.get_message_type(), Some(&DhcpMessageType::Ack));
+        assert_eq!(
+            ack.get_server_identifier(),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_for_other_server_releases_offer() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let offer = exchange(&server, discover()).await.remove(0);
+
+        // The client selected the other server in the same transaction
+        let mut request = client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::UNSPECIFIED,
+            vec![
+                OptionData::RequestedIpAddr(Ipv4Addr::new(192, 168, 1, 250)),
+                OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 2)),
+            ],
+        );
+        request[4..8].copy_from_slice(&0xdeadbeefu32.to_be_bytes());
+        assert!(exchange(&server, request).await.is_empty());
+
+        assert_eq!(ServerStats::get(&server.stats().lost_to_other_server), 1);
+        assert!(server
+            .service
+            .state
+            .offers
+            .lock()
+            .unwrap()
+            .pending(clock.now_system())
+            .is_empty());
+        assert_eq!(
+            server.service.state.pools.lock().unwrap()[0].holder_of(&offer.yiaddr),
+            None
+        );
+
+        // The address is offered to the next client right away
+        let other = exchange(&server, from_client("02:00:00:00:00:02", discover()))
+            .await
+            .remove(0);
+        assert_eq!(other.yiaddr, offer.yiaddr);
+
+        // A request for another server in an unrelated transaction is ignored
+        let request = client_message(
+            DhcpMessageType::Request,
+            Ipv4Addr::UNSPECIFIED,
+            vec![OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 2))],
+        );
+        assert!(exchange(&server, from_client("02:00:00:00:00:02", request))
+            .await
+            .is_empty());
+        assert_eq!(ServerStats::get(&server.stats().lost_to_other_server), 1);
+    }
+
+    #[tokio::test]
+    async fn test_message_type_validation() {
+        let clock = StepClock::default();
+        let server = reservation_server(&clock);
+        let options = |buf: &[u8]| {
+            (240..buf.len() - 2)
+                .find(|i| buf[*i..*i + 3] == [53, 1, 1])
+                .unwrap()
+        };
+
+        let mut missing = from_client("02:00:00:00:00:01", discover());
+        let at = options(&missing);
+        missing[at..at + 3].fill(0);
+        assert!(exchange(&server, missing).await.is_empty());
+        assert_eq!(ServerStats::get(&server.stats().missing_message_types), 1);
+        assert_eq!(ServerStats::get(&server.stats().unknown_message_types), 0);
+
+        let mut unknown = from_client("02:00:00:00:00:01", discover());
+        let at = options(&unknown);
+        unknown[at + 2] = 200;
+        assert!(exchange(&server, unknown).await.is_empty());
+        assert_eq!(ServerStats::get(&server.stats().unknown_message_types), 1);
+
+        // The server keeps answering valid messages
+        let replies = exchange(&server, from_client("02:00:00:00:00:01", discover())).await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(ServerStats::get(&server.stats().missing_message_types), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replies_conform() {
+        let server = inform_server(false);
+        let server_id = Ipv4Addr::new(192, 168, 1, 1);
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+
+        let send = |buf: Vec<u8>| {
+            let server = &server;
+            async move { call(server, &buf).await.unwrap().0 }
+        };
+
+        let replies = vec![
+            send(discover()).await,
+            send(client_message(
+                DhcpMessageType::Request,
+                Ipv4Addr::UNSPECIFIED,
+                vec![
+                    OptionData::RequestedIpAddr(addr),
+                    OptionData::ServerIdentifier(server_id),
+                ],
+            ))
+            .await,
+            send(client_message(DhcpMessageType::Request, addr, vec![])).await,
+            send(client_message(
+                DhcpMessageType::Request,
+                Ipv4Addr::UNSPECIFIED,
+                vec![
+                    OptionData::RequestedIpAddr(Ipv4Addr::new(10, 0, 0, 1)),
+                    OptionData::ServerIdentifier(server_id),
+                ],
+            ))
+            .await,
+            send(inform(Ipv4Addr::new(192, 168, 1, 50), None)).await,
+        ];
+
+        let types: Vec<_> = replies
+            .iter()
+            .map(|reply| reply.get_message_type().cloned().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                DhcpMessageType::Offer,
+                DhcpMessageType::Ack,
+                DhcpMessageType::Ack,
+                DhcpMessageType::Nak,
+                DhcpMessageType::Ack,
+            ]
+        );
+
+        for reply in &replies {
+            crate::conformance::assert_conforms(reply);
+            reply.validate().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interop_captures_are_answered() {
+        let captures = crate::interop::captures();
+        let mut servers: HashMap<String, Server> = HashMap::new();
+
+        // Captures of the same client are replayed against the same server,
+        // so its DHCPREQUEST follows up on the offer
+        for capture in &captures {
+            let server = servers
+                .entry(capture.client().to_string())
+                .or_insert_with(|| inform_server(false));
+
+            let (reply, _) = call(server, &capture.bytes)
+                .await
+                .unwrap_or_else(|| panic!("{}: expected a reply", capture.name));
+            let reply = &reply;
+            let expected = match capture.message().get_message_type() {
+                Some(DhcpMessageType::Discover) => DhcpMessageType::Offer,
+                _ => DhcpMessageType::Ack,
+            };
+            assert_eq!(
+                reply.get_message_type(),
+                Some(&expected),
+                "{}",
+                capture.name
+            );
+
+            crate::conformance::assert_conforms(reply);
+            reply.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_client_key_hardware_consistency() {
+        use crate::types::options::ClientIdentifier;
+
+        let mac = vec![0xde, 0xad, 0xbe, 0xef, 0x12, 0x34];
+        let other = vec![0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+        // (hlen, chaddr, client identifier, expected key)
+        let cases: Vec<(u8, Vec<u8>, Option<ClientIdentifier>, &str)> = vec![
+            (6, mac.clone(), None, "de:ad:be:ef:12:34"),
+            // The embedded address is preferred when consistent
+            (
+                6,
+                mac.clone(),
+                Some(ClientIdentifier::new(1, other.clone())),
+                "02:00:00:00:00:01",
+            ),
+            (
+                6,
+                mac.clone(),
+                Some(ClientIdentifier::new(0, b"ab".to_vec())),
+                "00-61:62",
+            ),
+            // Inconsistent identifiers fall back to chaddr
+            (
+                6,
+                mac.clone(),
+                Some(ClientIdentifier::new(1, other[..4].to_vec())),
+                "de:ad:be:ef:12:34",
+            ),
+            (
+                6,
+                mac.clone(),
+                Some(ClientIdentifier::new(6, other.clone())),
+                "de:ad:be:ef:12:34",
+            ),
+            // chaddr is cut off after the Ethernet address length
+            (
+                8,
+                [mac.clone(), vec![0xff, 0xff]].concat(),
+                None,
+                "de:ad:be:ef:12:34",
+            ),
+            (
+                8,
+                [mac.clone(), vec![0xff, 0xff]].concat(),
+                Some(ClientIdentifier::new(1, other.clone())),
+                "de:ad:be:ef:12:34",
+            ),
+        ];
+
+        for (hlen, chaddr, ident, expected) in cases {
+            let mut message = Message::new_with_xid(1);
+            message.set_hardware_address(HardwareAddr::try_from(&chaddr[..]).unwrap());
+            message.header.hlen = hlen;
+
+            if let Some(ident) = ident {
+                message
+                    .add_option_parts(
+                        OptionTag::ClientIdentifier,
+                        OptionData::ClientIdentifier(ident),
+                    )
+                    .unwrap();
+            }
+
+            assert_eq!(client_key(&message), expected, "hlen {hlen}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_version_2_client_identifier_keys_are_migrated() {
+        let path = std::env::temp_dir().join(format!("vulcan-keys-{}.json", std::process::id()));
+        let mac = "02:00:00:00:00:01";
+        let server = || {
+            Server::builder()
+                .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+                .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+                .with_allow_foreign_pools(true)
+                .with_state_file(path.clone())
+                .build()
+                .unwrap()
+        };
+
+        // Write a lease keyed like version 2 did for client identifiers
+        // carrying an Ethernet address
+        let old = server();
+        let state = &old.service.state;
+        let now = state.clock.now_system();
+        let addr = state.offer_address("01-02:00:00:00:00:01", state.clock.now_instant());
+        let addr = addr.unwrap();
+        state.record_offer(
+            "01-02:00:00:00:00:01",
+            HardwareAddr::try_from(String::from(mac)).unwrap(),
+            0,
+            addr,
+            Duration::from_secs(60),
+            now,
+        );
+        state
+            .commit_offer("01-02:00:00:00:00:01", addr, now)
+            .unwrap();
+        old.flush().unwrap();
+        drop(old);
+
+        let mut file: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        file["version"] = 2.into();
+        std::fs::write(&path, file.to_string()).unwrap();
+
+        let persisted = PersistedState::load(&path).unwrap().unwrap();
+        assert_eq!(persisted.leases.keys().collect::<Vec<_>>(), [mac]);
+
+        // The client still renews its lease after the upgrade
+        let server = server();
+        let renewal = client_message(
+            DhcpMessageType::Request,
+            addr,
+            vec![OptionData::ClientIdentifier(ClientIdentifier::new(
+                1,
+                vec![0x02, 0, 0, 0, 0, 0x01],
+            ))],
+        );
+        let replies = exchange_from(&server, addr, from_client(mac, renewal)).await;
+        assert_eq!(replies[0].get_message_type(), Some(&DhcpMessageType::Ack));
+        assert_eq!(replies[0].yiaddr, addr);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_reply_destination() {
+        let relay = Ipv4Addr::new(10, 0, 0, 1);
+        let client = Ipv4Addr::new(192, 168, 1, 100);
+
+        // (message type, giaddr, ciaddr, broadcast flag, destination)
+        let cases = [
+            (
+                DhcpMessageType::Offer,
+                relay,
+                Ipv4Addr::UNSPECIFIED,
+                true,
+                (relay, 67),
+            ),
+            (DhcpMessageType::Ack, relay, client, false, (relay, 67)),
+            (
+                DhcpMessageType::Nak,
+                relay,
+                Ipv4Addr::UNSPECIFIED,
+                true,
+                (relay, 67),
+            ),
+            (
+                DhcpMessageType::Nak,
+                Ipv4Addr::UNSPECIFIED,
+                client,
+                false,
+                (Ipv4Addr::BROADCAST, 68),
+            ),
+            (
+                DhcpMessageType::Ack,
+                Ipv4Addr::UNSPECIFIED,
+                client,
+                true,
+                (client, 68),
+            ),
+            (
+                DhcpMessageType::Offer,
+                Ipv4Addr::UNSPECIFIED,
+                Ipv4Addr::UNSPECIFIED,
+                false,
+                (Ipv4Addr::BROADCAST, 68),
+            ),
+        ];
+
+        for (ty, giaddr, ciaddr, broadcast, expected) in cases {
+            let mut reply = Message::new_with_xid(1);
+            reply.giaddr = giaddr;
+            reply.ciaddr = ciaddr;
+            reply.set_is_broadcast(broadcast);
+            reply
+                .add_option_parts(
+                    OptionTag::DhcpMessageType,
+                    OptionData::DhcpMessageType(ty.clone()),
+                )
+                .unwrap();
+
+            assert_eq!(
+                reply_destination(&reply),
+                SocketAddr::from(expected),
+                "{ty}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relayed_broadcast_discover_is_answered_via_relay() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let relay = Ipv4Addr::new(10, 0, 0, 1);
+        let bytes = discover();
+        let mut message = Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes)).unwrap();
+        message.giaddr = relay;
+        message.set_is_broadcast(true);
+
+        let mut out = WriteBuffer::new();
+        message.write::<BigEndian>(&mut out).unwrap();
+
+        let source = SocketAddr::from((relay, constants::SERVER_PORT));
+        let (offer, addr) = call_from(&server, source, out.bytes()).await.unwrap();
+
+        assert_eq!(offer.giaddr, relay);
+        assert_eq!(addr, SocketAddr::from((relay, constants::SERVER_PORT)));
+    }
+
+    /// Prober which panics on its first probe, used to make a handler panic.
+    #[derive(Default)]
+    struct PanickingProber {
+        panicked: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl ConflictProber for PanickingProber {
+        async fn probe(&self, _: Ipv4Addr) -> std::io::Result<bool> {
+            if !self
+                .panicked
+                .swap(true, std::sync::atomic::Ordering::Relaxed)
+            {
+                panic!("injected prober failure")
+            }
+
+            Ok(false)
+        }
+    }
+
+    /// Returns a UDP socket on the loopback interface for handlers to send
+    /// their replies through.
+    async fn loopback_socket() -> Arc<UdpSocket> {
+        Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_is_counted() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_offer_probe(Arc::new(PanickingProber::default()))
+            .build()
+            .unwrap();
+        let service = server.service();
+        let tasks = TaskManager::new(4, service.state.stats.clone());
+        let socket = loopback_socket().await;
+        let meta = RequestMeta::new(SocketAddr::from((
+            Ipv4Addr::UNSPECIFIED,
+            constants::CLIENT_PORT,
+        )));
+
+        spawn_handler(&tasks, &service, discover(), meta.clone(), socket.clone()).await;
+
+        // The server keeps serving after the panic
+        spawn_handler(&tasks, &service, discover(), meta, socket).await;
+        assert_eq!(tasks.shutdown(Duration::from_secs(1)).await, 0);
+
+        // Panics are reported by the reaper task
+        let stats = server.stats();
+        assert_eq!(stats.total().replies, 1);
+        for _ in 0..100 {
+            if ServerStats::get(&stats.handler_panics) == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(ServerStats::get(&stats.handler_panics), 1);
+        assert_eq!(ServerStats::get(&stats.handlers_in_flight), 0);
+    }
+
+    /// Never answers while `hanging` is set, reports addresses as free
+    /// otherwise.
+    #[derive(Default)]
+    struct HangingProber {
+        hanging: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl ConflictProber for HangingProber {
+        async fn probe(&self, _: Ipv4Addr) -> std::io::Result<bool> {
+            if self.hanging.load(std::sync::atomic::Ordering::Relaxed) {
+                std::future::pending::<()>().await;
+            }
+
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_timeout_rolls_back_allocation() {
+        let prober = Arc::new(HangingProber::default());
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_offer_probe(prober.clone())
+            .with_handler_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let service = server.service();
+        let tasks = TaskManager::new(4, service.state.stats.clone());
+        let socket = loopback_socket().await;
+        let meta = RequestMeta::new(SocketAddr::from((
+            Ipv4Addr::UNSPECIFIED,
+            constants::CLIENT_PORT,
+        )));
+        let stats = server.stats();
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+
+        prober
+            .hanging
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        spawn_handler(&tasks, &service, discover(), meta.clone(), socket.clone()).await;
+        assert_eq!(tasks.shutdown(Duration::from_secs(1)).await, 0);
+
+        assert_eq!(stats.total().replies, 0);
+        assert_eq!(stats.handlers.timeouts(&DhcpMessageType::Discover), 1);
+        assert_eq!(
+            stats.handlers.latency(&DhcpMessageType::Discover).count(),
+            0
+        );
+        assert_eq!(
+            service.state.pools.lock().unwrap()[0].holder_of(&addr),
+            None
+        );
+
+        // The address is offered once the probe answers
+        prober
+            .hanging
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        spawn_handler(&tasks, &service, discover(), meta, socket).await;
+        assert_eq!(tasks.shutdown(Duration::from_secs(1)).await, 0);
+
+        assert_eq!(stats.total().replies, 1);
+        assert!(service.state.pools.lock().unwrap()[0]
+            .holder_of(&addr)
+            .is_some());
+        assert_eq!(stats.handlers.timeouts(&DhcpMessageType::Discover), 1);
+        let latency = stats.handlers.latency(&DhcpMessageType::Discover);
+        assert_eq!(latency.count(), 1);
+        assert!(latency.p99().unwrap() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_ntp_servers_reach_client() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.149".into())
+            .with_pool("lab".into(), "192.168.1.150-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_ntp_servers(vec![Ipv4Addr::new(192, 168, 1, 1)])
+            .with_pool_ntp_servers("lab".into(), vec![Ipv4Addr::new(192, 168, 1, 2)])
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        // A DISCOVER as sent by the client, including its default parameter
+        // request list
+        let mut builder = crate::builder::MessageBuilder::new(
+            HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+            None,
+            1500,
+        );
+        let mut buf = WriteBuffer::new();
+        builder
+            .make_discover_message(1, None, None)
+            .unwrap()
+            .write::<BigEndian>(&mut buf)
+            .unwrap();
+
+        let (offer, _) = call(&server, buf.bytes()).await.unwrap();
+
+        assert_eq!(
+            crate::NtpConfig::from_message(&offer).servers,
+            vec![Ipv4Addr::new(192, 168, 1, 1)]
+        );
+
+        // Pool overrides take precedence over the server-wide servers
+        assert_eq!(
+            server
+                .service
+                .state
+                .pool_options(Ipv4Addr::new(192, 168, 1, 150))
+                .ntp_servers,
+            Some(vec![Ipv4Addr::new(192, 168, 1, 2)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_time_servers_only_sent_when_requested() {
+        let time = vec![Ipv4Addr::new(192, 168, 1, 3)];
+        let ntp = vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)];
+
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_time_servers(time.clone())
+            .with_ntp_servers(ntp.clone())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let offer = |requested: Vec<OptionTag>| {
+            let bytes = client_message(
+                DhcpMessageType::Discover,
+                Ipv4Addr::UNSPECIFIED,
+                vec![OptionData::ParameterRequestList(ParameterRequestList::new(
+                    requested,
+                ))],
+            );
+            let server = &server;
+
+            async move { call(server, &bytes).await.unwrap().0 }
+        };
+
+        let offer_ntp = offer(vec![OptionTag::NetworkTimeProtocolServers]).await;
+        assert_eq!(offer_ntp.get_ntp_servers(), Some(&ntp));
+        assert!(offer_ntp.get_option(OptionTag::TimeServer).is_none());
+
+        let offer_time = offer(vec![OptionTag::TimeServer]).await;
+        assert_eq!(
+            offer_time
+                .get_option(OptionTag::TimeServer)
+                .and_then(|option| option.data().as_ipv4_list()),
+            Some(time.as_slice())
+        );
+        assert!(offer_time.get_ntp_servers().is_none());
+
+        let offer_none = offer(vec![OptionTag::SubnetMask]).await;
+        assert!(offer_none.get_ntp_servers().is_none());
+        assert!(offer_none.get_option(OptionTag::TimeServer).is_none());
+    }
+
+    /// Hands the DHCPLEASEQUERY `query` sent by the relay agent `relay` to
+    /// the service of `server`.
+    async fn send_lease_query(
+        server: &Server,
+        relay: Ipv4Addr,
+        query: Vec<u8>,
+    ) -> Vec<(Message, SocketAddr)> {
+        let source = SocketAddr::from((relay, constants::SERVER_PORT));
+        call_from(server, source, &query)
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lease_query_aggregates_bindings() {
+        const RELAY: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("a".into(), "192.168.1.10-192.168.1.19".into())
+            .with_pool("b".into(), "192.168.1.20-192.168.1.29".into())
+            .with_pool("c".into(), "192.168.1.30-192.168.1.39".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_trusted_relays(vec![RELAY])
+            .with_clock(clock.clone())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let hardware_addr = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let key = hardware_addr.to_string();
+        let now = clock.now_system();
+
+        // The client holds an address in each of the pools
+        let addrs = [
+            Ipv4Addr::new(192, 168, 1, 10),
+            Ipv4Addr::new(192, 168, 1, 20),
+            Ipv4Addr::new(192, 168, 1, 30),
+        ];
+        for addr in addrs {
+            assert!(state
+                .pools
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .any(|p| p.reserve(addr, &key)));
+            state.record_offer(
+                &key,
+                hardware_addr.clone(),
+                0,
+                addr,
+                Duration::from_secs(60),
+                now,
+            );
+            state.commit_offer(&key, addr, now).unwrap();
+        }
+        state.touch_lease(&key, DhcpMessageType::Request, now, None);
+        clock.advance(Duration::from_secs(30));
+
+        let query = |ciaddr: Ipv4Addr| {
+            let mut message = Message::new_with_xid(0x1ea5e);
+            message.ciaddr = ciaddr;
+            message.giaddr = RELAY;
+            message.set_hardware_address(hardware_addr.clone());
+            message
+                .add_option_parts(
+                    OptionTag::DhcpMessageType,
+                    OptionData::DhcpMessageType(DhcpMessageType::LeaseQuery),
+                )
+                .unwrap();
+            message.end().unwrap();
+
+            let mut buf = WriteBuffer::new();
+            message.write::<BigEndian>(&mut buf).unwrap();
+            buf.bytes().to_vec()
+        };
+
+        // Query by hardware address
+        let sent = send_lease_query(&server, RELAY, query(Ipv4Addr::UNSPECIFIED)).await;
+        let (reply, addr) = &sent[0];
+        assert_eq!(*addr, SocketAddr::from((RELAY, 67)));
+        assert_eq!(
+            reply.get_message_type(),
+            Some(&DhcpMessageType::LeaseActive)
+        );
+        assert_eq!(reply.ciaddr, addrs[2]);
+        assert_eq!(
+            reply
+                .get_option(OptionTag::AssociatedIp)
+                .and_then(|o| o.data().as_ipv4_list()),
+            Some(&addrs[..])
+        );
+        assert_eq!(
+            reply
+                .get_option(OptionTag::ClientLastTransactionTime)
+                .and_then(|o| o.data().as_u32()),
+            Some(30)
+        );
+
+        // Query by address, released addresses leave the aggregate
+        assert!(state.release_lease(&key, addrs[2], clock.now_system()));
+        let sent = send_lease_query(&server, RELAY, query(addrs[0])).await;
+        let reply = &sent[0].0;
+        assert_eq!(reply.ciaddr, addrs[0]);
+        assert_eq!(
+            reply
+                .get_option(OptionTag::AssociatedIp)
+                .and_then(|o| o.data().as_ipv4_list()),
+            Some(&addrs[..2])
+        );
+
+        // Free addresses of our pools are unassigned, others unknown
+        let sent = send_lease_query(&server, RELAY, query(addrs[2])).await;
+        assert_eq!(
+            sent[0].0.get_message_type(),
+            Some(&DhcpMessageType::LeaseUnassigned)
+        );
+        let sent = send_lease_query(&server, RELAY, query(Ipv4Addr::new(10, 1, 1, 1))).await;
+        assert_eq!(
+            sent[0].0.get_message_type(),
+            Some(&DhcpMessageType::LeaseUnknown)
+        );
+    }
+
+    /// Returns a DHCPLEASEQUERY relayed by `relay` for `ciaddr`, or for the
+    /// client with `hardware_addr` if `ciaddr` is unspecified.
+    fn lease_query(relay: Ipv4Addr, ciaddr: Ipv4Addr, hardware_addr: &HardwareAddr) -> Vec<u8> {
+        let mut message = Message::new_with_xid(0x1ea5e);
+        message.ciaddr = ciaddr;
+        message.giaddr = relay;
+        message.set_hardware_address(hardware_addr.clone());
+        message
+            .add_option_parts(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::LeaseQuery),
+            )
+            .unwrap();
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    /// Commits `addr` to the client with `hardware_addr` and records a
+    /// DHCPREQUEST of the client.
+    fn bind(state: &ServerState, hardware_addr: &HardwareAddr, addr: Ipv4Addr) {
+        let key = hardware_addr.to_string();
+        let now = state.clock.now_system();
+
+        state.record_offer(
+            &key,
+            hardware_addr.clone(),
+            0,
+            addr,
+            Duration::from_secs(60),
+            now,
+        );
+        state.commit_offer(&key, addr, now).unwrap();
+        state.touch_lease(&key, DhcpMessageType::Request, now, None);
+    }
+
+    #[tokio::test]
+    async fn test_lease_query_by_address_reports_its_binding() {
+        const RELAY: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("a".into(), "192.168.1.10-192.168.1.19".into())
+            .with_pool("b".into(), "192.168.1.20-192.168.1.29".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_lease_time(3600)
+            .with_trusted_relays(vec![RELAY])
+            .with_clock(clock.clone())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let hardware_addr = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let first = Ipv4Addr::new(192, 168, 1, 10);
+        let second = Ipv4Addr::new(192, 168, 1, 20);
+
+        bind(state, &hardware_addr, first);
+        clock.advance(Duration::from_secs(100));
+        bind(state, &hardware_addr, second);
+        clock.advance(Duration::from_secs(30));
+
+        // (queried address, remaining lease time, last transaction)
+        for (addr, remaining, elapsed) in [(first, 3470, 130), (second, 3570, 30)] {
+            let query = lease_query(RELAY, addr, &HardwareAddr::default());
+            let reply = send_lease_query(&server, RELAY, query).await.remove(0).0;
+
+            assert_eq!(reply.ciaddr, addr);
+            assert_eq!(reply.chaddr, hardware_addr);
+            assert_eq!(lease_time_of(&reply), remaining, "{addr}");
+            assert_eq!(
+                reply
+                    .get_option(OptionTag::ClientLastTransactionTime)
+                    .and_then(|o| o.data().as_u32()),
+                Some(elapsed),
+                "{addr}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lease_query_truncates_associated_ips() {
+        const RELAY: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.10-192.168.1.99".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_trusted_relays(vec![RELAY])
+            .with_relay_max_message_size(576)
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let hardware_addr = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+
+        for last in 10..80 {
+            bind(
+                &server.service.state,
+                &hardware_addr,
+                Ipv4Addr::new(192, 168, 1, last),
+            );
+        }
+
+        let query = lease_query(RELAY, Ipv4Addr::UNSPECIFIED, &hardware_addr);
+        let sent = send_lease_query(&server, RELAY, query).await;
+        let reply = &sent[0].0;
+
+        let ips = reply
+            .get_option(OptionTag::AssociatedIp)
+            .and_then(|o| o.data().as_ipv4_list())
+            .unwrap();
+        assert!(ips.len() < 70);
+        assert_eq!(ips[0], Ipv4Addr::new(192, 168, 1, 10));
+        assert!(matches!(
+            reply.get_option(OptionTag::Message).map(|o| o.data()),
+            Some(OptionData::Message(note)) if note == size::ASSOCIATED_IP_TRUNCATED
+        ));
+
+        let mut buf = WriteBuffer::new();
+        reply.write::<BigEndian>(&mut buf).unwrap();
+        assert!(buf.bytes().len() <= 576 - 28);
+    }
+
+    #[tokio::test]
+    async fn test_lease_query_requires_trusted_relay() {
+        const RELAY: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.10-192.168.1.19".into())
+            .with_server_identifier(Ipv4Addr::new(192, 168, 1, 1))
+            .with_trusted_relays(vec![RELAY])
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let query = |giaddr: Ipv4Addr| {
+            let mut message = Message::new_with_xid(0x1ea5e);
+            message.ciaddr = Ipv4Addr::new(192, 168, 1, 10);
+            message.giaddr = giaddr;
+            message
+                .add_option_parts(
+                    OptionTag::DhcpMessageType,
+                    OptionData::DhcpMessageType(DhcpMessageType::LeaseQuery),
+                )
+                .unwrap();
+            message.end().unwrap();
+
+            let mut buf = WriteBuffer::new();
+            message.write::<BigEndian>(&mut buf).unwrap();
+            buf.bytes().to_vec()
+        };
+
+        // Clients can't query the server directly, even from a trusted
+        // address, and neither can untrusted relay agents
+        assert!(send_inform(&server, query(Ipv4Addr::UNSPECIFIED))
+            .await
+            .is_empty());
+        assert!(
+            send_lease_query(&server, RELAY, query(Ipv4Addr::UNSPECIFIED))
+                .await
+                .is_empty()
+        );
+        let untrusted = Ipv4Addr::new(10, 0, 0, 2);
+        assert!(send_lease_query(&server, untrusted, query(untrusted))
+            .await
+            .is_empty());
+        assert_eq!(ServerStats::get(&server.stats().untrusted_lease_queries), 3);
+
+        let sent = send_lease_query(&server, RELAY, query(RELAY)).await;
+        assert_eq!(
+            sent[0].0.get_message_type(),
+            Some(&DhcpMessageType::LeaseUnassigned)
+        );
+    }
+
+    #[test]
+    fn test_release_lease_frees_address() {
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.100".into())
+            .with_clock(clock.clone())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let mac = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+
+        let offered = state.offer_address(&mac.to_string(), clock.now_instant());
+        assert_eq!(offered, Some(addr));
+        state.record_offer(
+            &mac.to_string(),
+            mac.clone(),
+            0,
+            addr,
+            Duration::from_secs(60),
+            clock.now_system(),
+        );
+        state
+            .commit_offer(&mac.to_string(), addr, clock.now_system())
+            .unwrap();
+
+        // The single address of the pool is taken
+        clock.advance(Duration::from_secs(120));
+        assert_eq!(state.offer_address("other", clock.now_instant()), None);
+
+        assert!(server.release_lease(&mac).unwrap());
+        assert!(state.leases.lock().unwrap().is_empty());
+        assert_eq!(
+            state.offer_address("other", clock.now_instant()),
+            Some(addr)
+        );
+
+        // Nothing left to release
+        assert!(!server.release_lease(&mac).unwrap());
+    }
+}
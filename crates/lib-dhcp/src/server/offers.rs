@@ -0,0 +1,244 @@
+use std::{
+    net::Ipv4Addr,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{bounded::BoundedTtlMap, types::HardwareAddr};
+
+/// Default time an offered address stays reserved for the client. If no
+/// matching DHCPREQUEST arrives in this time, the address is returned to the
+/// pool.
+pub const DEFAULT_OFFER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An address offered to a client, which is reserved until the client either
+/// requests it or the offer expires. Pending offers are persisted on
+/// shutdown, so that they survive a restart of the server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PendingOffer {
+    key: String,
+    hardware_addr: HardwareAddr,
+
+    /// Transaction ID of the DHCPDISCOVER the offer answered. Offers
+    /// persisted by older versions default to 0.
+    #[serde(default)]
+    xid: u32,
+    yiaddr: Ipv4Addr,
+    expires: SystemTime,
+}
+
+impl PendingOffer {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn set_key(&mut self, key: String) {
+        self.key = key;
+    }
+
+    pub fn hardware_addr(&self) -> &HardwareAddr {
+        &self.hardware_addr
+    }
+
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    pub fn yiaddr(&self) -> Ipv4Addr {
+        self.yiaddr
+    }
+
+    pub fn expires(&self) -> SystemTime {
+        self.expires
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires <= now
+    }
+}
+
+/// Offers held in memory until the matching DHCPREQUEST arrives. In contrast
+/// to the [`DiscoverCache`](super::coalesce::DiscoverCache), this uses the
+/// wall clock, as expiry times are persisted across restarts.
+#[derive(Debug)]
+pub(crate) struct OfferCache {
+    offers: BoundedTtlMap<String, PendingOffer, SystemTime>,
+}
+
+impl OfferCache {
+    /// Creates a cache of at most `capacity` offers.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            offers: BoundedTtlMap::new(capacity, DEFAULT_OFFER_TIMEOUT),
+        }
+    }
+
+    /// Records an offer of `yiaddr` to the client with `key` in the
+    /// transaction `xid`, which expires at `expires`. A previous offer to the
+    /// same client is replaced. If the cache is full, the least recently made
+    /// offer is evicted and returned.
+    pub fn insert(
+        &mut self,
+        key: String,
+        hardware_addr: HardwareAddr,
+        xid: u32,
+        yiaddr: Ipv4Addr,
+        expires: SystemTime,
+    ) -> Option<PendingOffer> {
+        let offer = PendingOffer {
+            expires,
+            key: key.clone(),
+            hardware_addr,
+            xid,
+            yiaddr,
+        };
+
+        self.offers
+            .insert_until(key, offer, expires)
+            .map(|(_, evicted)| evicted)
+    }
+
+    /// Re-inserts an offer loaded from persisted state. Returns `false` and
+    /// discards the offer if it already expired or the cache is full.
+    pub fn restore(&mut self, offer: PendingOffer, now: SystemTime) -> bool {
+        if offer.is_expired(now) || self.is_full() {
+            return false;
+        }
+
+        let expires = offer.expires;
+        self.offers.insert_until(offer.key.clone(), offer, expires);
+        true
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.offers.is_full()
+    }
+
+    /// Returns the number of offers evicted because the cache was full.
+    pub fn evictions(&self) -> u64 {
+        self.offers.evictions()
+    }
+
+    /// Removes and returns the offer of `yiaddr` to the client with `key`, if
+    /// it didn't expire yet.
+    pub fn take(&mut self, key: &str, yiaddr: Ipv4Addr, now: SystemTime) -> Option<PendingOffer> {
+        match self.offers.peek(key) {
+            Some(offer) if offer.yiaddr == yiaddr && !offer.is_expired(now) => {
+                self.offers.remove(key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes and returns the offer made to the client with `key` in the
+    /// transaction `xid`, even if it expired already.
+    pub fn take_by_xid(&mut self, key: &str, xid: u32) -> Option<PendingOffer> {
+        match self.offers.peek(key) {
+            Some(offer) if offer.xid == xid => self.offers.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Returns the key of the client `yiaddr` is offered to, if the offer
+    /// didn't expire yet.
+    pub fn offeree(&self, yiaddr: Ipv4Addr, now: SystemTime) -> Option<&str> {
+        self.offers
+            .values()
+            .find(|offer| offer.yiaddr == yiaddr && !offer.is_expired(now))
+            .map(|offer| offer.key.as_str())
+    }
+
+    /// Removes and returns all expired offers.
+    pub fn purge_expired(&mut self, now: SystemTime) -> Vec<PendingOffer> {
+        self.offers
+            .purge_expired(now)
+            .into_iter()
+            .map(|(_, offer)| offer)
+            .collect()
+    }
+
+    /// Returns all offers which didn't expire yet.
+    pub fn pending(&self, now: SystemTime) -> Vec<PendingOffer> {
+        self.offers
+            .values()
+            .filter(|offer| !offer.is_expired(now))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::DEFAULT_CACHE_LIMIT;
+
+    fn hardware_addr() -> HardwareAddr {
+        HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap()
+    }
+
+    #[test]
+    fn test_offer_cache_take() {
+        let mut cache = OfferCache::new(DEFAULT_CACHE_LIMIT);
+        let now = SystemTime::now();
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+
+        cache.insert(
+            "a".into(),
+            hardware_addr(),
+            0,
+            addr,
+            now + Duration::from_secs(60),
+        );
+
+        assert!(cache
+            .take("a", Ipv4Addr::new(192, 168, 1, 101), now)
+            .is_none());
+        assert!(cache.take("b", addr, now).is_none());
+        assert!(cache.take("a", addr, now).is_some());
+        assert!(cache.take("a", addr, now).is_none());
+    }
+
+    #[test]
+    fn test_offer_cache_take_by_xid() {
+        let mut cache = OfferCache::new(DEFAULT_CACHE_LIMIT);
+        let now = SystemTime::now();
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+
+        cache.insert(
+            "a".into(),
+            hardware_addr(),
+            0x1234,
+            addr,
+            now + Duration::from_secs(60),
+        );
+
+        assert!(cache.take_by_xid("a", 0x4321).is_none());
+        assert!(cache.take_by_xid("b", 0x1234).is_none());
+        assert_eq!(cache.take_by_xid("a", 0x1234).unwrap().yiaddr(), addr);
+        assert!(cache.take_by_xid("a", 0x1234).is_none());
+    }
+
+    #[test]
+    fn test_offer_cache_expiry() {
+        let mut cache = OfferCache::new(DEFAULT_CACHE_LIMIT);
+        let now = SystemTime::now();
+        let addr = Ipv4Addr::new(192, 168, 1, 100);
+
+        cache.insert(
+            "a".into(),
+            hardware_addr(),
+            0,
+            addr,
+            now + Duration::from_secs(60),
+        );
+
+        let later = now + Duration::from_secs(61);
+        assert!(cache.pending(later).is_empty());
+        assert!(cache.take("a", addr, later).is_none());
+
+        let expired = cache.purge_expired(later);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].yiaddr(), addr);
+    }
+}
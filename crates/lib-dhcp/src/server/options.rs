@@ -0,0 +1,323 @@
+use crate::{
+    server::config::{LeaseTimes, PoolOptions, ServerConfig},
+    types::{
+        options::{DomainSearch, ParameterRequestList},
+        DhcpOption, OptionData, OptionTag,
+    },
+};
+
+/// Selects the configuration options included in a reply (DHCPOFFER or
+/// DHCPACK). Apart from the renewal (T1) and rebinding (T2) times, taken
+/// from `times`, only options the client requested via the parameter request
+/// list are included. Pool specific options are taken from `pool`.
+pub(crate) fn select_reply_options(
+    config: &ServerConfig,
+    times: &LeaseTimes,
+    pool: &PoolOptions,
+    requested: Option<&ParameterRequestList>,
+) -> Vec<DhcpOption> {
+    let mut options = Vec::new();
+
+    if config.send_times {
+        options.push(DhcpOption::new(
+            OptionTag::RenewalT1Time,
+            OptionData::RenewalT1Time(times.renew_time),
+        ));
+        options.push(DhcpOption::new(
+            OptionTag::RebindingT2Time,
+            OptionData::RebindingT2Time(times.rebind_time),
+        ));
+    }
+
+    let requested = match requested {
+        Some(requested) => requested,
+        None => return options,
+    };
+
+    if requested.contains(&OptionTag::SubnetMask) {
+        if let Some(mask) = config.subnet_mask {
+            options.push(DhcpOption::new(
+                OptionTag::SubnetMask,
+                OptionData::SubnetMask(mask),
+            ));
+        }
+    }
+
+    if requested.contains(&OptionTag::DomainName) {
+        if let Some(name) = &config.domain_name {
+            options.push(DhcpOption::new(
+                OptionTag::DomainName,
+                OptionData::DomainName(name.clone()),
+            ));
+        }
+    }
+
+    if requested.contains(&OptionTag::DomainSearch) {
+        if let Some(domains) = domain_search(config) {
+            options.push(DhcpOption::new(
+                OptionTag::DomainSearch,
+                OptionData::DomainSearch(DomainSearch::new(domains)),
+            ));
+        }
+    }
+
+    if requested.contains(&OptionTag::TimeServer) {
+        if let Some(servers) = pool.time_servers.as_ref().filter(|s| !s.is_empty()) {
+            options.push(DhcpOption::new(
+                OptionTag::TimeServer,
+                OptionData::TimeServer(servers.clone()),
+            ));
+        }
+    }
+
+    if requested.contains(&OptionTag::NetworkTimeProtocolServers) {
+        if let Some(servers) = pool.ntp_servers.as_ref().filter(|s| !s.is_empty()) {
+            options.push(DhcpOption::new(
+                OptionTag::NetworkTimeProtocolServers,
+                OptionData::NetworkTimeProtocolServers(servers.clone()),
+            ));
+        }
+    }
+
+    options
+}
+
+/// Selects the configuration options included in the DHCPACK answering a
+/// DHCPINFORM. Only requested options are included, and never lease related
+/// ones like T1 and T2. Subnet specific options are only included if the
+/// client is on a subnet we serve.
+pub(crate) fn select_inform_options(
+    config: &ServerConfig,
+    requested: Option<&ParameterRequestList>,
+    known_subnet: bool,
+) -> Vec<DhcpOption> {
+    let mut options = select_reply_options(
+        config,
+        &config.default_times(),
+        &config.default_options(),
+        requested,
+    );
+
+    options.retain(|option| match option.data() {
+        OptionData::RenewalT1Time(_) | OptionData::RebindingT2Time(_) => false,
+        OptionData::SubnetMask(_) => known_subnet,
+        _ => true,
+    });
+
+    options
+}
+
+/// Returns the domain search list (option 119). An explicitly configured
+/// list always takes precedence. Without one, the list is synthesized from
+/// the domain name (option 15) if enabled, as modern clients expect a search
+/// list to be present.
+fn domain_search(config: &ServerConfig) -> Option<Vec<String>> {
+    match (&config.domain_search, &config.domain_name) {
+        (Some(domains), _) => Some(domains.clone()),
+        (None, Some(name)) if config.synthesize_domain_search => Some(vec![name.clone()]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(domain_name: Option<&str>, domain_search: Option<Vec<&str>>) -> ServerConfig {
+        ServerConfig {
+            server_identifier: std::net::Ipv4Addr::UNSPECIFIED,
+            interfaces: Vec::new(),
+            bind_address: std::net::SocketAddr::from((std::net::Ipv4Addr::UNSPECIFIED, 67)),
+            subnet_mask: None,
+            inform_unknown_subnets: false,
+            nak_unknown_renewals: true,
+            check_renewal_source: false,
+            option_overload: false,
+            socket_filter: true,
+            relay_max_message_size: 1500,
+            strict: false,
+            trusted_relays: Vec::new(),
+            auth_keys: Default::default(),
+            state_file: None,
+            require_persistence: false,
+            audit_sample_size: None,
+            audit_rate: 10,
+            reclaim_grace: std::time::Duration::from_secs(60),
+            reclaim_probation: std::time::Duration::from_secs(300),
+            lease_retention: crate::server::DEFAULT_LEASE_RETENTION,
+            offer_timeout: std::time::Duration::from_secs(60),
+            handler_timeout: crate::server::DEFAULT_HANDLER_TIMEOUT,
+            max_concurrent: crate::server::DEFAULT_MAX_IN_FLIGHT_HANDLERS,
+            cache_limits: crate::server::CacheLimits::default(),
+            defensive_mode: false,
+            defensive_offer_timeout: std::time::Duration::from_secs(5),
+            lease_time: 3600,
+            send_times: false,
+            rebind_time: 0,
+            renew_time: 0,
+            domain_name: domain_name.map(String::from),
+            domain_search: domain_search.map(|d| d.into_iter().map(String::from).collect()),
+            synthesize_domain_search: true,
+            time_servers: None,
+            ntp_servers: None,
+            vendor_profiles: Vec::new(),
+            control_socket: None,
+            hostname_handling: Default::default(),
+            hostname_conflict_policy: Default::default(),
+        }
+    }
+
+    fn times() -> LeaseTimes {
+        LeaseTimes {
+            lease_time: 3600,
+            renew_time: 1800,
+            rebind_time: 3150,
+        }
+    }
+
+    fn requested() -> ParameterRequestList {
+        ParameterRequestList::new(vec![OptionTag::DomainName, OptionTag::DomainSearch])
+    }
+
+    fn search_of(options: &[DhcpOption]) -> Option<Vec<String>> {
+        options.iter().find_map(|o| match o.data() {
+            OptionData::DomainSearch(s) => Some(s.domains().to_vec()),
+            _ => None,
+        })
+    }
+
+    fn name_of(options: &[DhcpOption]) -> Option<String> {
+        options.iter().find_map(|o| match o.data() {
+            OptionData::DomainName(n) => Some(n.clone()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_domain_name_only_synthesizes_search() {
+        let options = select_reply_options(
+            &config(Some("lan"), None),
+            &times(),
+            &PoolOptions::default(),
+            Some(&requested()),
+        );
+
+        assert_eq!(name_of(&options), Some("lan".into()));
+        assert_eq!(search_of(&options), Some(vec!["lan".into()]));
+    }
+
+    #[test]
+    fn test_domain_name_only_without_synthesis() {
+        let mut config = config(Some("lan"), None);
+        config.synthesize_domain_search = false;
+
+        let options = select_reply_options(
+            &config,
+            &times(),
+            &PoolOptions::default(),
+            Some(&requested()),
+        );
+        assert_eq!(name_of(&options), Some("lan".into()));
+        assert_eq!(search_of(&options), None);
+    }
+
+    #[test]
+    fn test_explicit_search_takes_precedence() {
+        let config = config(Some("lan"), Some(vec!["corp.example", "example"]));
+        let options = select_reply_options(
+            &config,
+            &times(),
+            &PoolOptions::default(),
+            Some(&requested()),
+        );
+
+        assert_eq!(name_of(&options), Some("lan".into()));
+        assert_eq!(
+            search_of(&options),
+            Some(vec!["corp.example".into(), "example".into()])
+        );
+    }
+
+    #[test]
+    fn test_search_only() {
+        let config = config(None, Some(vec!["corp.example"]));
+        let options = select_reply_options(
+            &config,
+            &times(),
+            &PoolOptions::default(),
+            Some(&requested()),
+        );
+
+        assert_eq!(name_of(&options), None);
+        assert_eq!(search_of(&options), Some(vec!["corp.example".into()]));
+    }
+
+    #[test]
+    fn test_neither_configured() {
+        let options = select_reply_options(
+            &config(None, None),
+            &times(),
+            &PoolOptions::default(),
+            Some(&requested()),
+        );
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn test_search_not_requested() {
+        let requested = ParameterRequestList::new(vec![OptionTag::DomainName]);
+        let options = select_reply_options(
+            &config(Some("lan"), None),
+            &times(),
+            &PoolOptions::default(),
+            Some(&requested),
+        );
+
+        assert_eq!(name_of(&options), Some("lan".into()));
+        assert_eq!(search_of(&options), None);
+    }
+
+    #[test]
+    fn test_ntp_servers() {
+        let ntp = ParameterRequestList::new(vec![OptionTag::NetworkTimeProtocolServers]);
+        let servers = vec![std::net::Ipv4Addr::new(10, 0, 0, 1)];
+
+        let mut config = config(None, None);
+        config.ntp_servers = Some(servers.clone());
+
+        let options =
+            select_reply_options(&config, &times(), &config.default_options(), Some(&ntp));
+        assert_eq!(
+            options[0].data(),
+            &OptionData::NetworkTimeProtocolServers(servers)
+        );
+
+        // Only sent if requested
+        let options = select_reply_options(
+            &config,
+            &times(),
+            &config.default_options(),
+            Some(&requested()),
+        );
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn test_time_servers() {
+        let time = ParameterRequestList::new(vec![OptionTag::TimeServer]);
+        let servers = vec![std::net::Ipv4Addr::new(10, 0, 0, 1)];
+
+        let mut config = config(None, None);
+        config.time_servers = Some(servers.clone());
+
+        let options =
+            select_reply_options(&config, &times(), &config.default_options(), Some(&time));
+        assert_eq!(options[0].data(), &OptionData::TimeServer(servers));
+
+        // An empty list is never sent
+        config.time_servers = Some(Vec::new());
+        let options =
+            select_reply_options(&config, &times(), &config.default_options(), Some(&time));
+        assert!(options.is_empty());
+    }
+}
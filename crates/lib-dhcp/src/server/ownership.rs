@@ -0,0 +1,109 @@
+use lib_ifs::InterfaceAddr;
+
+use crate::{server::pool::Pool, types::Ipv4Net};
+
+/// Returns the names of all pools for which no interface owns an address in
+/// the pool's subnet. Serving such a pool produces offers clients can't use,
+/// unless the pool is only served via relay agents.
+///
+/// A pool is owned by an interface address if the network of that address
+/// contains the complete pool range. Only addresses of the listen
+/// `interfaces` count, or those of all interfaces if there are none.
+pub(crate) fn foreign_pools(
+    pools: &[Pool],
+    addrs: &[InterfaceAddr],
+    interfaces: &[String],
+) -> Vec<String> {
+    let nets: Vec<Ipv4Net> = addrs
+        .iter()
+        .filter(|a| interfaces.is_empty() || interfaces.contains(&a.name))
+        .filter_map(|a| Ipv4Net::with_netmask(a.addr, a.netmask).ok())
+        .collect();
+
+    pools
+        .iter()
+        .filter(|pool| {
+            !nets
+                .iter()
+                .any(|net| net.contains(&pool.range().start()) && net.contains(&pool.range().end()))
+        })
+        .map(|pool| pool.name().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn pool(name: &str, range: &str) -> Pool {
+        Pool::try_from((name.to_string(), range.to_string())).unwrap()
+    }
+
+    fn addr(name: &str, addr: [u8; 4], netmask: [u8; 4]) -> InterfaceAddr {
+        InterfaceAddr {
+            name: name.to_string(),
+            addr: Ipv4Addr::from(addr),
+            netmask: Ipv4Addr::from(netmask),
+        }
+    }
+
+    #[test]
+    fn test_owned_pool() {
+        let pools = vec![pool("lan", "192.168.1.100-192.168.1.200")];
+        let addrs = vec![
+            addr("lo", [127, 0, 0, 1], [255, 0, 0, 0]),
+            addr("eth0", [192, 168, 1, 1], [255, 255, 255, 0]),
+        ];
+
+        assert!(foreign_pools(&pools, &addrs, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_foreign_pool() {
+        let pools = vec![
+            pool("lan", "192.168.1.100-192.168.1.200"),
+            pool("guest", "192.168.10.100-192.168.10.200"),
+        ];
+        let addrs = vec![addr("eth0", [192, 168, 1, 1], [255, 255, 255, 0])];
+
+        assert_eq!(
+            foreign_pools(&pools, &addrs, &[]),
+            vec![String::from("guest")]
+        );
+    }
+
+    #[test]
+    fn test_only_listen_interfaces_own_pools() {
+        let pools = vec![pool("lan", "192.168.1.100-192.168.1.200")];
+        let addrs = vec![
+            addr("eth0", [10, 0, 0, 1], [255, 255, 255, 0]),
+            addr("eth1", [192, 168, 1, 1], [255, 255, 255, 0]),
+        ];
+
+        let listen = |name: &str| vec![name.to_string()];
+        assert!(foreign_pools(&pools, &addrs, &listen("eth1")).is_empty());
+        assert_eq!(
+            foreign_pools(&pools, &addrs, &listen("eth0")),
+            vec![String::from("lan")]
+        );
+    }
+
+    #[test]
+    fn test_pool_exceeding_subnet_is_foreign() {
+        let pools = vec![pool("lan", "192.168.1.100-192.168.2.10")];
+        let addrs = vec![addr("eth0", [192, 168, 1, 1], [255, 255, 255, 0])];
+
+        assert_eq!(
+            foreign_pools(&pools, &addrs, &[]),
+            vec![String::from("lan")]
+        );
+    }
+
+    #[test]
+    fn test_no_interface_addrs() {
+        let pools = vec![pool("lan", "192.168.1.100-192.168.1.200")];
+        assert_eq!(foreign_pools(&pools, &[], &[]), vec![String::from("lan")]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::time;
+use tracing::warn;
+
+use crate::{server::state::ServerState, DAEMON_TARGET};
+
+/// Delay before a failed write of the state is retried. It doubles with
+/// every further failure, up to [`PERSIST_RETRY_MAX`].
+pub const PERSIST_RETRY_MIN: Duration = Duration::from_secs(1);
+
+/// Longest delay between two attempts to write the state while
+/// persistence is degraded.
+pub const PERSIST_RETRY_MAX: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default)]
+struct Health {
+    /// Number of consecutive failed writes.
+    failures: u32,
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+/// Tracks whether the state could be written on the last attempt. After a
+/// failure, persistence is degraded until a write succeeds again. Leases
+/// are still granted from memory in the meantime, unless the server
+/// requires persistence.
+#[derive(Debug, Default)]
+pub(crate) struct PersistenceHealth {
+    health: Mutex<Health>,
+}
+
+impl PersistenceHealth {
+    pub fn is_degraded(&self) -> bool {
+        self.health.lock().unwrap().failures > 0
+    }
+
+    /// Returns the error of the last failed write while degraded.
+    pub fn last_error(&self) -> Option<String> {
+        self.health.lock().unwrap().last_error.clone()
+    }
+
+    /// Records a failed write at `now` and schedules the next attempt.
+    /// Returns whether persistence was healthy before.
+    pub fn failed(&self, err: String, now: Instant) -> bool {
+        let mut health = self.health.lock().unwrap();
+        let backoff = PERSIST_RETRY_MIN
+            .saturating_mul(2u32.saturating_pow(health.failures))
+            .min(PERSIST_RETRY_MAX);
+
+        health.failures += 1;
+        health.retry_at = Some(now + backoff);
+        health.last_error = Some(err);
+        health.failures == 1
+    }
+
+    /// Records a successful write. Returns whether persistence was degraded
+    /// before.
+    pub fn succeeded(&self) -> bool {
+        let mut health = self.health.lock().unwrap();
+        let degraded = health.failures > 0;
+
+        *health = Health::default();
+        degraded
+    }
+
+    /// Returns whether a failed write is due to be retried at `now`.
+    pub fn retry_due(&self, now: Instant) -> bool {
+        self.health
+            .lock()
+            .unwrap()
+            .retry_at
+            .is_some_and(|at| now >= at)
+    }
+}
+
+/// Retries writing the state while persistence is degraded, see
+/// [`retry_persist`].
+pub(crate) async fn run_persistence_retries(state: &ServerState) {
+    let mut interval = time::interval(PERSIST_RETRY_MIN);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        retry_persist(state);
+    }
+}
+
+/// Writes the full state if a retry is due. Returns whether persistence
+/// recovered.
+pub(crate) fn retry_persist(state: &ServerState) -> bool {
+    if !state.persistence.retry_due(state.clock.now_instant()) {
+        return false;
+    }
+
+    match state.persist() {
+        Ok(()) => true,
+        Err(err) => {
+            warn!(target: DAEMON_TARGET, %err, "retrying to persist state failed");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        net::Ipv4Addr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+    use crate::{
+        server::storage::{PersistedState, ServerStorageError, StateStore},
+        Clock, Server, ServerStats, StepClock,
+    };
+
+    /// Fails to save while `failing` is set, records the saved leases
+    /// otherwise.
+    #[derive(Default)]
+    struct FlakyStore {
+        failing: AtomicBool,
+        saved: Mutex<Vec<String>>,
+    }
+
+    impl StateStore for FlakyStore {
+        fn save(&self, state: &PersistedState) -> Result<(), ServerStorageError> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err(io::Error::other("no space left on device").into());
+            }
+
+            let mut saved: Vec<_> = state.leases.keys().cloned().collect();
+            saved.sort();
+            *self.saved.lock().unwrap() = saved;
+            Ok(())
+        }
+    }
+
+    fn server(require_persistence: bool) -> (Server, Arc<FlakyStore>, StepClock) {
+        let clock = StepClock::default();
+        let store = Arc::new(FlakyStore::default());
+        let server = Server::builder()
+            .with_pool("default".into(), "10.0.0.10-10.0.0.20".into())
+            .with_allow_foreign_pools(true)
+            .with_clock(clock.clone())
+            .with_state_store(store.clone())
+            .with_require_persistence(require_persistence)
+            .build()
+            .unwrap();
+
+        (server, store, clock)
+    }
+
+    /// Offers an address to the client with `key` and commits it.
+    fn grant(state: &ServerState, key: &str) -> Option<Ipv4Addr> {
+        let addr = state.offer_address(key, state.clock.now_instant())?;
+        let now = state.clock.now_system();
+
+        state.record_offer(
+            key,
+            key.to_string().try_into().unwrap(),
+            0,
+            addr,
+            Duration::from_secs(60),
+            now,
+        );
+        state.commit_offer(key, addr, now).unwrap();
+        Some(addr)
+    }
+
+    #[test]
+    fn test_degraded_persistence_grants_from_memory() {
+        let (server, store, clock) = server(false);
+        let state = &server.service.state;
+
+        grant(state, "02:00:00:00:00:01").unwrap();
+        server.flush().unwrap();
+        assert!(!server.is_persistence_degraded());
+
+        store.failing.store(true, Ordering::Relaxed);
+        assert!(server.flush().is_err());
+        assert!(server.is_persistence_degraded());
+        assert_eq!(ServerStats::get(&state.stats.persistence_degraded), 1);
+
+        // Leases are still granted
+        assert!(grant(state, "02:00:00:00:00:02").is_some());
+
+        // Retries back off exponentially
+        assert!(!retry_persist(state));
+        clock.advance(PERSIST_RETRY_MIN);
+        assert!(!retry_persist(state));
+        assert_eq!(ServerStats::get(&state.stats.persist_failures), 2);
+
+        clock.advance(PERSIST_RETRY_MIN);
+        assert!(!state.persistence.retry_due(clock.now_instant()));
+        clock.advance(PERSIST_RETRY_MIN);
+        assert!(state.persistence.retry_due(clock.now_instant()));
+
+        // Recovering writes the leases granted in the meantime
+        store.failing.store(false, Ordering::Relaxed);
+        assert!(retry_persist(state));
+        assert!(!server.is_persistence_degraded());
+        assert_eq!(ServerStats::get(&state.stats.persistence_degraded), 0);
+        assert_eq!(
+            *store.saved.lock().unwrap(),
+            ["02:00:00:00:00:01", "02:00:00:00:00:02"]
+        );
+        assert!(!state.persistence.retry_due(clock.now_instant()));
+    }
+
+    #[test]
+    fn test_require_persistence_serves_existing_bindings_only() {
+        let (server, store, clock) = server(true);
+        let state = &server.service.state;
+
+        let bound = grant(state, "02:00:00:00:00:01").unwrap();
+
+        store.failing.store(true, Ordering::Relaxed);
+        assert!(server.flush().is_err());
+
+        // Past the coalescing window, the allocator is asked again
+        clock.advance(Duration::from_secs(10));
+        let now = clock.now_instant();
+        assert_eq!(state.offer_address("02:00:00:00:00:01", now), Some(bound));
+        assert_eq!(state.offer_address("02:00:00:00:00:02", now), None);
+        assert!(state.persistence.last_error().unwrap().contains("no space"));
+
+        store.failing.store(false, Ordering::Relaxed);
+        assert!(retry_persist(state));
+        assert!(state
+            .offer_address("02:00:00:00:00:02", clock.now_instant())
+            .is_some());
+    }
+}
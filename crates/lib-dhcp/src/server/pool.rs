@@ -1,32 +1,585 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{AddrParseError, Ipv4Addr},
+    time::{Duration, SystemTime},
+};
+
 use thiserror::Error;
 
+use crate::{
+    server::{
+        config::{LeaseTimes, PoolOptions},
+        reclaim::TransitionError,
+    },
+    types::Ipv4AddrRange,
+};
+
 #[derive(Debug, Error)]
-pub enum PoolParseError {}
+pub enum PoolParseError {
+    #[error("pool range error: {0}")]
+    PoolRangeParseError(#[from] PoolRangeParseError),
+}
 
 #[derive(Debug)]
 pub struct Pool {
     range: PoolRange,
     name: String,
+
+    /// Addresses currently handed out (offered or leased) by this pool,
+    /// mapped to the key of the client holding them.
+    allocated: HashMap<Ipv4Addr, String>,
+
+    /// Addresses found to be in use by unknown hosts. These are never
+    /// handed out.
+    probation: HashSet<Ipv4Addr>,
+
+    /// Addresses of reclaimed leases, mapped to the key of their last holder
+    /// and the time they were reclaimed. Only the last holder gets them back
+    /// until the reclaim probation passed.
+    expired: HashMap<Ipv4Addr, (String, SystemTime)>,
+
+    /// Addresses reserved for clients, mapped to their key. Only that client
+    /// is allocated them.
+    reserved: HashMap<Ipv4Addr, String>,
+
+    /// Lease times of addresses from this pool, the server-wide times if
+    /// [`None`].
+    times: Option<LeaseTimes>,
+
+    /// Options overriding the server-wide ones for clients of this pool.
+    options: PoolOptions,
 }
 
 impl TryFrom<(String, String)> for Pool {
     type Error = PoolParseError;
 
     fn try_from(value: (String, String)) -> Result<Self, Self::Error> {
-        todo!()
+        let (name, range) = value;
+
+        Ok(Self {
+            range: PoolRange::try_from(range)?,
+            allocated: HashMap::new(),
+            probation: HashSet::new(),
+            expired: HashMap::new(),
+            reserved: HashMap::new(),
+            times: None,
+            options: PoolOptions::default(),
+            name,
+        })
+    }
+}
+
+impl Pool {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn range(&self) -> &PoolRange {
+        &self.range
+    }
+
+    /// Returns the lease times overriding the server-wide ones.
+    pub fn times(&self) -> Option<LeaseTimes> {
+        self.times
+    }
+
+    pub fn set_times(&mut self, times: LeaseTimes) {
+        self.times = Some(times);
+    }
+
+    /// Returns the options overriding the server-wide ones.
+    pub fn options(&self) -> &PoolOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: PoolOptions) {
+        self.options = options;
+    }
+
+    /// Replaces the reserved addresses, mapped to the key of the client they
+    /// are reserved for.
+    pub fn set_reservations(&mut self, reserved: HashMap<Ipv4Addr, String>) {
+        self.reserved = reserved;
+    }
+
+    /// Returns the key of the client `addr` is reserved for.
+    pub fn reserved_for(&self, addr: &Ipv4Addr) -> Option<&str> {
+        self.reserved.get(addr).map(String::as_str)
+    }
+
+    /// Returns the address reserved for the client with `key`.
+    pub fn reservation_of(&self, key: &str) -> Option<Ipv4Addr> {
+        self.reserved
+            .iter()
+            .find(|(_, k)| k.as_str() == key)
+            .map(|(addr, _)| *addr)
+    }
+
+    /// Returns if `addr` is reserved for a client other than the one with
+    /// `key`.
+    fn reserved_for_other(&self, addr: &Ipv4Addr, key: &str) -> bool {
+        self.reserved_for(addr).is_some_and(|k| k != key)
+    }
+
+    /// Allocates the address reserved for the client with `key`. A
+    /// reclaimed address is taken over from its previous holder right away.
+    /// Returns [`None`] if there is no reservation, or the address is still
+    /// allocated to another client or on probation.
+    pub fn allocate_reserved(&mut self, key: &str) -> Option<Ipv4Addr> {
+        let addr = self.reservation_of(key)?;
+
+        if let Some(holder) = self.allocated.get(&addr) {
+            return (holder == key).then_some(addr);
+        }
+
+        if self.probation.contains(&addr) {
+            return None;
+        }
+
+        self.expired.remove(&addr);
+        self.allocated.insert(addr, key.to_string());
+        Some(addr)
+    }
+
+    /// Allocates an address for the client with `key`. If the client already
+    /// holds an address from this pool, or held one whose lease was
+    /// reclaimed, the same address is returned. Reclaimed addresses of other
+    /// clients are skipped until `probation` passed since they were
+    /// reclaimed, addresses reserved for other clients are always skipped.
+    /// Returns [`None`] if the pool is exhausted.
+    pub fn allocate(
+        &mut self,
+        key: &str,
+        now: SystemTime,
+        probation: Duration,
+    ) -> Option<Ipv4Addr> {
+        if let Some(addr) = self.address_of(key) {
+            return Some(addr);
+        }
+
+        let previous = self
+            .expired
+            .iter()
+            .find(|(addr, (holder, _))| holder == key && !self.reserved_for_other(addr, key))
+            .map(|(addr, _)| *addr);
+
+        if let Some(addr) = previous {
+            self.offer_expired(addr, key, now, probation).ok()?;
+            return Some(addr);
+        }
+
+        for addr in self.range.iter() {
+            if self.allocated.contains_key(&addr)
+                || self.probation.contains(&addr)
+                || self.reserved_for_other(&addr, key)
+            {
+                continue;
+            }
+
+            if self.expired.contains_key(&addr) {
+                if self.offer_expired(addr, key, now, probation).is_ok() {
+                    return Some(addr);
+                }
+                continue;
+            }
+
+            self.allocated.insert(addr, key.to_string());
+            return Some(addr);
+        }
+
+        None
+    }
+
+    /// Marks `addr` as allocated to the client with `key`, for example when
+    /// restoring persisted state or renewing a lease. Returns `false` if the
+    /// address is not part of this pool, already allocated to another
+    /// client or was reclaimed from another client.
+    pub fn reserve(&mut self, addr: Ipv4Addr, key: &str) -> bool {
+        if !self.range.contains(&addr) {
+            return false;
+        }
+
+        if let Some((holder, _)) = self.expired.get(&addr) {
+            if holder != key {
+                return false;
+            }
+            self.expired.remove(&addr);
+        }
+
+        match self.allocated.get(&addr) {
+            Some(k) => k == key,
+            None => {
+                self.allocated.insert(addr, key.to_string());
+                true
+            }
+        }
+    }
+
+    /// Returns the address to the pool. Returns `false` if the address
+    /// wasn't allocated.
+    pub fn release(&mut self, addr: &Ipv4Addr) -> bool {
+        self.expired.remove(addr);
+        self.allocated.remove(addr).is_some()
+    }
+
+    /// Returns all addresses allocated to the client with `key` to the pool,
+    /// except `keep`. Returns the released addresses.
+    pub fn release_others(&mut self, key: &str, keep: Ipv4Addr) -> Vec<Ipv4Addr> {
+        let others: Vec<_> = self
+            .allocated
+            .iter()
+            .filter(|(addr, k)| k.as_str() == key && **addr != keep)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in &others {
+            self.allocated.remove(addr);
+        }
+
+        others
+    }
+
+    /// Takes `addr` back from the client with `key` after its lease expired
+    /// (Active to Expired). The address is on probation afterwards, see
+    /// [`Pool::offer_expired`].
+    pub fn reclaim(
+        &mut self,
+        addr: Ipv4Addr,
+        key: &str,
+        now: SystemTime,
+    ) -> Result<(), TransitionError> {
+        match self.allocated.get(&addr) {
+            Some(holder) if holder == key => {}
+            Some(_) => return Err(TransitionError::HeldByOther(addr)),
+            None => return Err(TransitionError::NotHeld(addr)),
+        }
+
+        self.allocated.remove(&addr);
+        self.expired.insert(addr, (key.to_string(), now));
+        Ok(())
+    }
+
+    /// Offers the reclaimed `addr` to the client with `key` (Expired to
+    /// Offered). This fails while the address is on probation, unless the
+    /// client is the one the address was reclaimed from.
+    pub fn offer_expired(
+        &mut self,
+        addr: Ipv4Addr,
+        key: &str,
+        now: SystemTime,
+        probation: Duration,
+    ) -> Result<(), TransitionError> {
+        let (holder, reclaimed) = self
+            .expired
+            .get(&addr)
+            .ok_or(TransitionError::NotHeld(addr))?;
+
+        if holder != key && now < *reclaimed + probation {
+            return Err(TransitionError::OnProbation(addr));
+        }
+
+        self.expired.remove(&addr);
+        self.allocated.insert(addr, key.to_string());
+        Ok(())
+    }
+
+    /// Returns the reclaimed addresses with the key of their last holder and
+    /// the time they were reclaimed, see [`Pool::reclaim`].
+    pub fn reclaimed(&self) -> impl Iterator<Item = (Ipv4Addr, &str, SystemTime)> {
+        self.expired
+            .iter()
+            .map(|(addr, (key, reclaimed))| (*addr, key.as_str(), *reclaimed))
+    }
+
+    /// Restores `addr` as reclaimed from the client with `key` at
+    /// `reclaimed`, for example from persisted state. Returns `false` if the
+    /// address is not part of this pool, allocated or on probation.
+    pub fn restore_reclaimed(&mut self, addr: Ipv4Addr, key: &str, reclaimed: SystemTime) -> bool {
+        if !self.range.contains(&addr)
+            || self.allocated.contains_key(&addr)
+            || self.probation.contains(&addr)
+        {
+            return false;
+        }
+
+        self.expired.insert(addr, (key.to_string(), reclaimed));
+        true
+    }
+
+    /// Returns the client key an address is allocated to.
+    pub fn holder_of(&self, addr: &Ipv4Addr) -> Option<&str> {
+        self.allocated.get(addr).map(String::as_str)
+    }
+
+    /// Returns the address currently allocated to the client with `key`.
+    pub fn address_of(&self, key: &str) -> Option<Ipv4Addr> {
+        self.allocated
+            .iter()
+            .find(|(_, k)| k.as_str() == key)
+            .map(|(addr, _)| *addr)
+    }
+
+    pub fn is_allocated(&self, addr: &Ipv4Addr) -> bool {
+        self.allocated.contains_key(addr)
+    }
+
+    /// Returns the number of allocated addresses.
+    pub fn allocated_count(&self) -> usize {
+        self.allocated.len()
+    }
+
+    /// Returns all addresses which are neither allocated, on probation nor
+    /// reclaimed.
+    pub fn free_addresses(&self) -> Vec<Ipv4Addr> {
+        self.range
+            .iter()
+            .filter(|addr| {
+                !self.allocated.contains_key(addr)
+                    && !self.probation.contains(addr)
+                    && !self.expired.contains_key(addr)
+            })
+            .collect()
+    }
+
+    /// Puts `addr` on probation, so that it isn't handed out anymore.
+    /// Returns `false` if the address is not part of this pool or currently
+    /// allocated.
+    pub fn put_on_probation(&mut self, addr: Ipv4Addr) -> bool {
+        if !self.range.contains(&addr) || self.allocated.contains_key(&addr) {
+            return false;
+        }
+
+        self.expired.remove(&addr);
+
+        self.probation.insert(addr)
+    }
+
+    pub fn is_on_probation(&self, addr: &Ipv4Addr) -> bool {
+        self.probation.contains(addr)
+    }
+
+    /// Returns the number of addresses on probation.
+    pub fn probation_count(&self) -> usize {
+        self.probation.len()
     }
 }
 
 #[derive(Debug, Error)]
-pub enum PoolRangeParseError {}
+pub enum PoolRangeParseError {
+    #[error("invalid pool range, expected '<start>-<end>'")]
+    InvalidFormat,
+
+    #[error("invalid IP address: {0}")]
+    AddrParseError(#[from] AddrParseError),
 
+    #[error("start address must not be greater than end address")]
+    InvalidOrder,
+}
+
+/// Inclusive range of IPv4 addresses, like `192.168.1.100-192.168.1.200`.
 #[derive(Debug)]
-pub struct PoolRange {}
+pub struct PoolRange {
+    start: Ipv4Addr,
+    end: Ipv4Addr,
+}
 
 impl TryFrom<String> for PoolRange {
     type Error = PoolRangeParseError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        todo!()
+        let (start, end) = match value.split_once('-') {
+            Some(parts) => parts,
+            None => return Err(PoolRangeParseError::InvalidFormat),
+        };
+
+        let start: Ipv4Addr = start.trim().parse()?;
+        let end: Ipv4Addr = end.trim().parse()?;
+
+        if start > end {
+            return Err(PoolRangeParseError::InvalidOrder);
+        }
+
+        Ok(Self { start, end })
+    }
+}
+
+impl PoolRange {
+    pub fn start(&self) -> Ipv4Addr {
+        self.start
+    }
+
+    pub fn end(&self) -> Ipv4Addr {
+        self.end
+    }
+
+    pub fn contains(&self, addr: &Ipv4Addr) -> bool {
+        self.start <= *addr && *addr <= self.end
+    }
+
+    /// Returns the number of addresses in the range.
+    pub fn size(&self) -> u32 {
+        u32::from(self.end) - u32::from(self.start) + 1
+    }
+
+    /// Returns an iterator over all addresses of the range.
+    pub fn iter(&self) -> Ipv4AddrRange {
+        Ipv4AddrRange::new(self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_range_from_string() {
+        match PoolRange::try_from(String::from("192.168.1.100 - 192.168.1.200")) {
+            Ok(range) => {
+                assert_eq!(range.start(), Ipv4Addr::new(192, 168, 1, 100));
+                assert_eq!(range.end(), Ipv4Addr::new(192, 168, 1, 200));
+            }
+            Err(err) => panic!("{}", err),
+        }
+
+        assert!(PoolRange::try_from(String::from("192.168.1.100")).is_err());
+        assert!(PoolRange::try_from(String::from("192.168.1.200-192.168.1.100")).is_err());
+    }
+
+    #[test]
+    fn test_pool_allocate_exhausted() {
+        let mut pool =
+            Pool::try_from((String::from("small"), String::from("10.0.0.1-10.0.0.2"))).unwrap();
+
+        assert_eq!(
+            pool.allocate("a", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            pool.allocate("b", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 2))
+        );
+        assert_eq!(
+            pool.allocate("a", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            pool.allocate("c", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            None
+        );
+
+        assert!(pool.release(&Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(
+            pool.allocate("c", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 2))
+        );
+    }
+
+    #[test]
+    fn test_pool_probation() {
+        let mut pool =
+            Pool::try_from((String::from("small"), String::from("10.0.0.1-10.0.0.3"))).unwrap();
+
+        assert_eq!(
+            pool.allocate("a", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert!(!pool.put_on_probation(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(pool.put_on_probation(Ipv4Addr::new(10, 0, 0, 2)));
+
+        assert_eq!(pool.free_addresses(), vec![Ipv4Addr::new(10, 0, 0, 3)]);
+        assert_eq!(
+            pool.allocate("b", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 3))
+        );
+        assert_eq!(
+            pool.allocate("c", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pool_reserve() {
+        let mut pool =
+            Pool::try_from((String::from("small"), String::from("10.0.0.1-10.0.0.2"))).unwrap();
+
+        assert!(pool.reserve(Ipv4Addr::new(10, 0, 0, 2), "a"));
+        assert!(pool.reserve(Ipv4Addr::new(10, 0, 0, 2), "a"));
+        assert!(!pool.reserve(Ipv4Addr::new(10, 0, 0, 2), "b"));
+        assert!(!pool.reserve(Ipv4Addr::new(10, 0, 0, 3), "b"));
+
+        assert_eq!(
+            pool.allocate("b", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+    }
+
+    #[test]
+    fn test_pool_reserved_addresses() {
+        let mut pool =
+            Pool::try_from((String::from("small"), String::from("10.0.0.1-10.0.0.3"))).unwrap();
+        let reserved = Ipv4Addr::new(10, 0, 0, 1);
+        pool.set_reservations(HashMap::from([(reserved, String::from("a"))]));
+
+        // Other clients never get the reserved address
+        assert_eq!(
+            pool.allocate("b", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 2))
+        );
+        assert_eq!(pool.allocate_reserved("b"), None);
+        assert_eq!(pool.allocate_reserved("a"), Some(reserved));
+        assert_eq!(pool.allocate_reserved("a"), Some(reserved));
+
+        // A reclaimed reserved address goes to the reservation, not back to
+        // its previous holder
+        pool.release(&reserved);
+        assert!(pool.reserve(reserved, "c"));
+        pool.reclaim(reserved, "c", SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(
+            pool.allocate("c", SystemTime::UNIX_EPOCH, Duration::ZERO),
+            Some(Ipv4Addr::new(10, 0, 0, 3))
+        );
+        assert_eq!(pool.allocate_reserved("a"), Some(reserved));
+    }
+
+    #[test]
+    fn test_pool_reclaim_transitions() {
+        let mut pool =
+            Pool::try_from((String::from("small"), String::from("10.0.0.1-10.0.0.2"))).unwrap();
+        let probation = Duration::from_secs(300);
+        let start = SystemTime::UNIX_EPOCH;
+        let addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        assert_eq!(pool.allocate("a", start, probation), Some(addr));
+        assert_eq!(
+            pool.reclaim(addr, "b", start),
+            Err(TransitionError::HeldByOther(addr))
+        );
+        assert_eq!(pool.reclaim(addr, "a", start), Ok(()));
+        assert_eq!(
+            pool.reclaim(addr, "a", start),
+            Err(TransitionError::NotHeld(addr))
+        );
+
+        // The reclaimed address is skipped for other clients while on
+        // probation
+        assert_eq!(
+            pool.offer_expired(addr, "b", start, probation),
+            Err(TransitionError::OnProbation(addr))
+        );
+        assert!(!pool.reserve(addr, "b"));
+        assert_eq!(
+            pool.allocate("b", start, probation),
+            Some(Ipv4Addr::new(10, 0, 0, 2))
+        );
+        assert_eq!(pool.allocate("c", start, probation), None);
+
+        // Its previous holder gets it back right away
+        assert_eq!(pool.allocate("a", start, probation), Some(addr));
+        assert_eq!(pool.reclaim(addr, "a", start), Ok(()));
+
+        let later = start + probation;
+        assert_eq!(pool.allocate("c", later, probation), Some(addr));
+        assert_eq!(pool.holder_of(&addr), Some("c"));
+        assert!(!pool.reserve(addr, "a"));
     }
 }
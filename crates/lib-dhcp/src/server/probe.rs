@@ -0,0 +1,211 @@
+use std::{
+    io::{self, Read},
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::atomic::{AtomicU16, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use tokio::{io::unix::AsyncFd, time};
+
+/// Time [`IcmpProber`] waits for an echo reply by default.
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const IP_PROTO_ICMP: u8 = 1;
+const ICMP_HEADER_LEN: usize = 8;
+
+/// A [`ConflictProber`] checks if an address is in use on the network, for
+/// example by sending an ARP request or an ICMP echo request (RFC 2131
+/// Section 2.2). Implementations decide how long to wait for a response.
+#[async_trait]
+pub trait ConflictProber: Send + Sync {
+    /// Returns `true` if a host responded for `addr`.
+    async fn probe(&self, addr: Ipv4Addr) -> std::io::Result<bool>;
+}
+
+/// [`ConflictProber`] which sends an ICMP echo request and reports the
+/// address as in use if a reply arrives within the timeout. Opening the raw
+/// socket requires `CAP_NET_RAW`. Hosts which drop echo requests are not
+/// detected.
+#[derive(Debug)]
+pub struct IcmpProber {
+    timeout: Duration,
+    identifier: u16,
+    sequence: AtomicU16,
+}
+
+impl IcmpProber {
+    /// Creates a prober which waits `timeout` for each reply.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            identifier: rand::random(),
+            sequence: AtomicU16::new(0),
+        }
+    }
+}
+
+impl Default for IcmpProber {
+    fn default() -> Self {
+        Self::new(DEFAULT_PROBE_TIMEOUT)
+    }
+}
+
+#[async_trait]
+impl ConflictProber for IcmpProber {
+    async fn probe(&self, addr: Ipv4Addr) -> io::Result<bool> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+        let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?;
+        socket.set_nonblocking(true)?;
+
+        // Only deliver packets from the probed address to this socket
+        let target = SockAddr::from(SocketAddrV4::new(addr, 0));
+        socket.connect(&target)?;
+        socket.send(&echo_request(self.identifier, sequence))?;
+
+        let socket = AsyncFd::new(socket)?;
+        let wait_for_reply = async {
+            let mut packet = [0u8; 1500];
+
+            loop {
+                let mut guard = socket.readable().await?;
+
+                let len = match guard.try_io(|fd| fd.get_ref().read(&mut packet)) {
+                    Ok(result) => result?,
+                    Err(_would_block) => continue,
+                };
+
+                if is_echo_reply(&packet[..len], addr, self.identifier, sequence) {
+                    return Ok::<_, io::Error>(());
+                }
+            }
+        };
+
+        match time::timeout(self.timeout, wait_for_reply).await {
+            Ok(result) => result.map(|()| true),
+            Err(_elapsed) => Ok(false),
+        }
+    }
+}
+
+/// Builds an ICMP echo request without payload.
+fn echo_request(identifier: u16, sequence: u16) -> [u8; ICMP_HEADER_LEN] {
+    let mut packet = [0u8; ICMP_HEADER_LEN];
+    packet[0] = ICMP_ECHO_REQUEST;
+    packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+
+    let checksum = internet_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+/// Returns if `packet`, an IPv4 packet as received on a raw socket, is the
+/// echo reply from `addr` to the request with `identifier` and `sequence`.
+fn is_echo_reply(packet: &[u8], addr: Ipv4Addr, identifier: u16, sequence: u16) -> bool {
+    let Some(&version_ihl) = packet.first() else {
+        return false;
+    };
+
+    let header_len = usize::from(version_ihl & 0x0f) * 4;
+    if version_ihl >> 4 != 4 || packet.len() < header_len + ICMP_HEADER_LEN {
+        return false;
+    }
+
+    let source = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    if packet[9] != IP_PROTO_ICMP || source != addr {
+        return false;
+    }
+
+    let icmp = &packet[header_len..];
+    icmp[0] == ICMP_ECHO_REPLY
+        && icmp[4..6] == identifier.to_be_bytes()
+        && icmp[6..8] == sequence.to_be_bytes()
+}
+
+/// Computes the Internet checksum (RFC 1071) of `data`.
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|chunk| u32::from(u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)])))
+        .sum();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wraps `icmp` into an IPv4 header from `source`.
+    fn ipv4_packet(source: Ipv4Addr, icmp: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, IP_PROTO_ICMP, 0, 0];
+        packet.extend(source.octets());
+        packet.extend([127, 0, 0, 1]);
+        packet.extend(icmp);
+        packet
+    }
+
+    #[test]
+    fn test_echo_request() {
+        let packet = echo_request(0x1234, 7);
+
+        assert_eq!(packet[0], ICMP_ECHO_REQUEST);
+        assert_eq!(packet[4..8], [0x12, 0x34, 0, 7]);
+
+        // The checksum over a packet including its checksum is zero
+        assert_eq!(internet_checksum(&packet), 0);
+    }
+
+    #[test]
+    fn test_internet_checksum() {
+        // Example from RFC 1071 Section 3
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(internet_checksum(&data), !0xddf2);
+
+        // Odd lengths are padded with a zero byte
+        assert_eq!(internet_checksum(&[0x01]), !0x0100);
+    }
+
+    #[test]
+    fn test_is_echo_reply() {
+        let addr = Ipv4Addr::new(10, 0, 0, 2);
+        let mut reply = echo_request(0x1234, 7);
+        reply[0] = ICMP_ECHO_REPLY;
+
+        assert!(is_echo_reply(&ipv4_packet(addr, &reply), addr, 0x1234, 7));
+
+        // Replies to other probes, from other hosts and other messages
+        assert!(!is_echo_reply(&ipv4_packet(addr, &reply), addr, 0x1234, 8));
+        assert!(!is_echo_reply(&ipv4_packet(addr, &reply), addr, 0x4321, 7));
+        assert!(!is_echo_reply(
+            &ipv4_packet(Ipv4Addr::new(10, 0, 0, 3), &reply),
+            addr,
+            0x1234,
+            7
+        ));
+        assert!(!is_echo_reply(
+            &ipv4_packet(addr, &echo_request(0x1234, 7)),
+            addr,
+            0x1234,
+            7
+        ));
+
+        // Truncated packets
+        assert!(!is_echo_reply(
+            &ipv4_packet(addr, &reply[..4]),
+            addr,
+            0x1234,
+            7
+        ));
+        assert!(!is_echo_reply(&[], addr, 0x1234, 7));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use thiserror::Error;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::{server::state::ServerState, DAEMON_TARGET, LEASE_TARGET};
+
+/// Default time after the end of a lease before its address is reclaimed.
+pub const DEFAULT_RECLAIM_GRACE: Duration = Duration::from_secs(60);
+
+/// Default time a reclaimed address is only handed out to its previous
+/// holder.
+pub const DEFAULT_RECLAIM_PROBATION: Duration = Duration::from_secs(300);
+
+/// Default time ended leases are kept as records of their binding.
+pub const DEFAULT_LEASE_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+/// Interval at which expired leases are reclaimed.
+const RECLAIM_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A lease state transition which was refused by its guard. Leases move
+/// from Offered to Active on commit, from Active to Expired when reclaimed
+/// and from Expired to Offered when the address is handed out again.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TransitionError {
+    #[error("client {0} holds no lease")]
+    NoLease(String),
+
+    #[error("lease of {0} didn't expire yet")]
+    NotExpired(Ipv4Addr),
+
+    #[error("client of {0} was seen within the reclaim grace")]
+    RenewedWithinGrace(Ipv4Addr),
+
+    #[error("{0} is on probation after being reclaimed")]
+    OnProbation(Ipv4Addr),
+
+    #[error("{0} is held by another client")]
+    HeldByOther(Ipv4Addr),
+
+    #[error("{0} is not held by any client")]
+    NotHeld(Ipv4Addr),
+
+    #[error("{0} was not offered")]
+    NoOffer(Ipv4Addr),
+
+    #[error("{0} was offered to another client")]
+    NotOfferee(Ipv4Addr),
+}
+
+/// Periodically runs the maintenance of the server state, see [`maintain`].
+/// Ticks often enough to honor the resolution of the utilization history.
+pub(crate) async fn run_maintenance(state: &ServerState) {
+    let period = RECLAIM_INTERVAL.min(state.stats.history().resolution());
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        maintain(state);
+    }
+}
+
+/// Returns the addresses of expired leases to the pools, see
+/// [`ServerState::reclaim_expired`], purges the records of leases which
+/// ended before the lease retention, and samples the utilization of the
+/// pools when due.
+fn maintain(state: &ServerState) {
+    let now = state.clock.now_system();
+    let reclaimed = state.reclaim_expired(now);
+    let purged = state.purge_ended_leases(now);
+
+    state
+        .stats
+        .history()
+        .sample(&state.pools.lock().unwrap(), now);
+
+    if reclaimed.is_empty() && purged == 0 {
+        return;
+    }
+
+    if !reclaimed.is_empty() {
+        info!(
+            target: LEASE_TARGET,
+            count = reclaimed.len(),
+            addrs = ?reclaimed,
+            "reclaimed expired leases"
+        );
+    }
+
+    if purged > 0 {
+        info!(target: LEASE_TARGET, count = purged, "purged ended leases");
+    }
+
+    if let Err(err) = state.persist() {
+        warn!(target: DAEMON_TARGET, %err, "failed to persist state after reclaiming leases");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::{
+        types::{options::DhcpMessageType, HardwareAddr},
+        Clock, SeededRng, Server, StepClock,
+    };
+
+    const LEASE_TIME: u32 = 120;
+
+    /// Asserts that no address is Active for two clients and that every
+    /// Active lease is backed by the pool.
+    fn assert_consistent(state: &ServerState) {
+        let now = state.clock.now_system();
+        let leases = state.leases.lock().unwrap();
+        let pools = state.pools.lock().unwrap();
+        let mut active: HashMap<Ipv4Addr, &str> = HashMap::new();
+
+        for (key, lease) in leases.iter() {
+            if lease.remaining(now).is_none() {
+                continue;
+            }
+
+            let addr = lease.ip_addr();
+            if let Some(other) = active.insert(addr, key) {
+                panic!("{addr} is active for {other} and {key}");
+            }
+
+            let holder = pools.iter().find_map(|pool| pool.holder_of(&addr));
+            assert_eq!(holder, Some(key.as_str()), "{addr} of {key}");
+        }
+    }
+
+    #[test]
+    fn test_renewal_and_reclamation_interleaved() {
+        for seed in 0..20 {
+            let clock = StepClock::default();
+            let server = Server::builder()
+                .with_pool("small".into(), "10.0.0.10-10.0.0.13".into())
+                .with_allow_foreign_pools(true)
+                .with_lease_time(LEASE_TIME)
+                .with_clock(clock.clone())
+                .build()
+                .unwrap();
+            let state = &server.service.state;
+            let mut rng = SeededRng::new(seed);
+
+            let keys: Vec<String> = (0..8).map(|i| format!("02:00:00:00:00:{i:02x}")).collect();
+
+            for _ in 0..500 {
+                let key = &keys[rng.gen_range(0..keys.len())];
+                let now = clock.now_system();
+
+                match rng.gen_range(0..4) {
+                    // DISCOVER followed by a REQUEST for the offered address
+                    0 => {
+                        let addr = match state.offer_address(key, clock.now_instant()) {
+                            Some(addr) => addr,
+                            None => continue,
+                        };
+
+                        let hardware_addr = HardwareAddr::try_from(key.clone()).unwrap();
+                        state.record_offer(
+                            key,
+                            hardware_addr,
+                            0,
+                            addr,
+                            Duration::from_secs(60),
+                            now,
+                        );
+
+                        if rng.gen_bool(0.8) {
+                            let _ = state.commit_offer(key, addr, now);
+                        }
+                    }
+                    // REQUEST renewing the current lease, possibly too late
+                    1 => {
+                        let addr = state.leases.lock().unwrap().get(key).map(|l| l.ip_addr());
+                        if let Some(addr) = addr {
+                            state.touch_lease(key, DhcpMessageType::Request, now, None);
+                            state.renew_lease(key, addr, now);
+                        }
+                    }
+                    2 => clock.advance(Duration::from_secs(rng.gen_range(1..=LEASE_TIME as u64))),
+                    _ => {
+                        state.reclaim_expired(now);
+                    }
+                }
+
+                assert_consistent(state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_expire_lease_guards() {
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("small".into(), "10.0.0.10-10.0.0.13".into())
+            .with_allow_foreign_pools(true)
+            .with_lease_time(LEASE_TIME)
+            .with_clock(clock.clone())
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let key = "02:00:00:00:00:01";
+
+        assert_eq!(
+            state.expire_lease(key, clock.now_system()),
+            Err(TransitionError::NoLease(key.to_string()))
+        );
+
+        let addr = state.offer_address(key, clock.now_instant()).unwrap();
+        let hardware_addr = HardwareAddr::try_from(key.to_string()).unwrap();
+        state.record_offer(
+            key,
+            hardware_addr,
+            0,
+            addr,
+            Duration::from_secs(60),
+            clock.now_system(),
+        );
+
+        assert!(matches!(
+            state.commit_offer("02:00:00:00:00:02", addr, clock.now_system()),
+            Err(TransitionError::NotOfferee(a)) if a == addr
+        ));
+        state.commit_offer(key, addr, clock.now_system()).unwrap();
+
+        // Within the grace after the end of the lease
+        clock.advance(Duration::from_secs(LEASE_TIME as u64));
+        assert_eq!(
+            state.expire_lease(key, clock.now_system()),
+            Err(TransitionError::NotExpired(addr))
+        );
+
+        // The client showed up late, but within the grace
+        clock.advance(Duration::from_secs(30));
+        state.touch_lease(key, DhcpMessageType::Request, clock.now_system(), None);
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(
+            state.expire_lease(key, clock.now_system()),
+            Err(TransitionError::RenewedWithinGrace(addr))
+        );
+
+        clock.advance(DEFAULT_RECLAIM_GRACE);
+        assert_eq!(state.reclaim_expired(clock.now_system()), vec![addr]);
+        assert_eq!(
+            state.expire_lease(key, clock.now_system()),
+            Err(TransitionError::NotHeld(addr))
+        );
+    }
+}
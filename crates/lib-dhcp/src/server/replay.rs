@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    bounded::BoundedTtlMap,
+    types::{options::RDM_MONOTONIC_COUNTER, HardwareAddr},
+};
+
+/// Time after which the last replay detection value of a client which
+/// didn't send authenticated messages anymore is forgotten.
+const REPLAY_COUNTER_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Tracks the replay detection values of the Authentication option (RFC
+/// 3118) per client. With the monotonic counter method, every message of a
+/// client has to carry a higher value than the previous one, so captured
+/// messages can't be sent again.
+///
+/// At most `capacity` clients are tracked. Once full, the counter of the
+/// least recently seen client is forgotten.
+#[derive(Debug)]
+pub(crate) struct ReplayDetector {
+    last: BoundedTtlMap<HardwareAddr, u64>,
+}
+
+impl ReplayDetector {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            last: BoundedTtlMap::new(capacity, REPLAY_COUNTER_RETENTION),
+        }
+    }
+
+    /// Checks the replay detection `value` of a message from the client with
+    /// `hardware_addr`, received at `now`, and remembers it. Returns `false`
+    /// if the value isn't strictly greater than the last accepted value of
+    /// this client. Values of replay detection methods other than the
+    /// monotonic counter are not checked.
+    pub fn accept(
+        &mut self,
+        hardware_addr: &HardwareAddr,
+        rdm: u8,
+        value: u64,
+        now: Instant,
+    ) -> bool {
+        if rdm != RDM_MONOTONIC_COUNTER {
+            return true;
+        }
+
+        self.last.purge_expired(now);
+
+        if self
+            .last
+            .get(hardware_addr, now)
+            .is_some_and(|last| value <= *last)
+        {
+            return false;
+        }
+
+        self.last.insert(hardware_addr.clone(), value, now);
+        true
+    }
+
+    /// Returns the number of clients forgotten because the detector tracked
+    /// too many.
+    pub fn evictions(&self) -> u64 {
+        self.last.evictions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_detector() {
+        let a = HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap();
+        let b = HardwareAddr::try_from(String::from("de:ad:be:ef:12:35")).unwrap();
+        let mut detector = ReplayDetector::new(16);
+        let now = Instant::now();
+
+        assert!(detector.accept(&a, RDM_MONOTONIC_COUNTER, 5, now));
+        assert!(!detector.accept(&a, RDM_MONOTONIC_COUNTER, 5, now));
+        assert!(!detector.accept(&a, RDM_MONOTONIC_COUNTER, 4, now));
+        assert!(detector.accept(&a, RDM_MONOTONIC_COUNTER, 6, now));
+
+        // Counters are tracked per client
+        assert!(detector.accept(&b, RDM_MONOTONIC_COUNTER, 1, now));
+
+        // Unknown methods aren't checked
+        assert!(detector.accept(&a, 7, 0, now));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    net::Ipv4Addr,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use thiserror::Error;
+
+use crate::{
+    server::pool::Pool,
+    types::{HardwareAddr, Lease},
+};
+
+/// Returns the reservations to apply when the configuration is reloaded,
+/// see [`ServerBuilder::with_reservation_reload`](crate::server::ServerBuilder::with_reservation_reload).
+pub type ReservationReload = Arc<dyn Fn() -> Result<Vec<Reservation>, String> + Send + Sync>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ReservationError {
+    #[error("reserved address {0} is not part of any pool")]
+    OutsidePools(Ipv4Addr),
+
+    #[error("address {0} is reserved more than once")]
+    DuplicateAddress(Ipv4Addr),
+
+    #[error("client {0} has more than one reservation")]
+    DuplicateClient(HardwareAddr),
+}
+
+/// An address reserved for the client with a hardware address. Only this
+/// client is allocated the address, and it is always allocated this address
+/// instead of a dynamic one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    pub hardware_addr: HardwareAddr,
+    pub ip_addr: Ipv4Addr,
+}
+
+/// A reservation which doesn't take effect yet, because a lease handed out
+/// before the reservation was added is still running.
+///
+/// Leases are never cut short by a reservation. The client holding such a
+/// lease is still answered, but renewals don't extend the lease beyond the
+/// time it would have ended anyway. Once it ended, the reservation takes
+/// effect. Revoking the lease, for example with `vulcan-ctl leases revoke`,
+/// resolves the conflict right away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReservationConflict {
+    /// The client holds a dynamic lease on another address. The client is
+    /// allocated the reserved address once that lease ended.
+    Handoff {
+        reservation: Reservation,
+        leased: Ipv4Addr,
+    },
+
+    /// The reserved address is held by another client. The reservation is
+    /// pending until the lease of that client ended.
+    Pending {
+        reservation: Reservation,
+
+        /// Key of the client holding the address, its hardware address
+        /// unless it sent a client identifier.
+        holder: String,
+    },
+}
+
+impl ReservationConflict {
+    pub fn reservation(&self) -> &Reservation {
+        match self {
+            Self::Handoff { reservation, .. } | Self::Pending { reservation, .. } => reservation,
+        }
+    }
+}
+
+impl Display for ReservationConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Handoff {
+                reservation,
+                leased,
+            } => write!(
+                f,
+                "{} holds {} and moves to its reserved address {} once the lease ended",
+                reservation.hardware_addr, leased, reservation.ip_addr
+            ),
+            Self::Pending {
+                reservation,
+                holder,
+            } => write!(
+                f,
+                "{} reserved for {} is held by {}, pending until the lease ended",
+                reservation.ip_addr, reservation.hardware_addr, holder
+            ),
+        }
+    }
+}
+
+/// Replaces the reservations of `pools` with `reservations`. Nothing is
+/// changed if any reservation is invalid.
+pub(crate) fn assign(
+    pools: &mut [Pool],
+    reservations: &[Reservation],
+) -> Result<(), ReservationError> {
+    let mut per_pool = vec![HashMap::new(); pools.len()];
+    let mut clients = HashSet::new();
+
+    for reservation in reservations {
+        let addr = reservation.ip_addr;
+        let index = pools
+            .iter()
+            .position(|pool| pool.range().contains(&addr))
+            .ok_or(ReservationError::OutsidePools(addr))?;
+
+        if !clients.insert(&reservation.hardware_addr) {
+            return Err(ReservationError::DuplicateClient(
+                reservation.hardware_addr.clone(),
+            ));
+        }
+
+        if per_pool[index]
+            .insert(addr, reservation.hardware_addr.to_string())
+            .is_some()
+        {
+            return Err(ReservationError::DuplicateAddress(addr));
+        }
+    }
+
+    for (pool, reserved) in pools.iter_mut().zip(per_pool) {
+        pool.set_reservations(reserved);
+    }
+
+    Ok(())
+}
+
+/// Returns the reservations which don't take effect yet because of leases
+/// handed out before they were added.
+pub(crate) fn conflicts(
+    reservations: &[Reservation],
+    leases: &HashMap<String, Lease>,
+    pools: &[Pool],
+    now: SystemTime,
+) -> Vec<ReservationConflict> {
+    reservations
+        .iter()
+        .filter_map(|reservation| {
+            let key = reservation.hardware_addr.to_string();
+
+            let leased = leases
+                .get(&key)
+                .filter(|lease| lease.remaining(now).is_some())
+                .map(Lease::ip_addr)
+                .filter(|addr| *addr != reservation.ip_addr);
+
+            if let Some(leased) = leased {
+                return Some(ReservationConflict::Handoff {
+                    reservation: reservation.clone(),
+                    leased,
+                });
+            }
+
+            let holder = pools
+                .iter()
+                .find_map(|pool| pool.holder_of(&reservation.ip_addr))
+                .filter(|holder| *holder != key)?;
+
+            Some(ReservationConflict::Pending {
+                reservation: reservation.clone(),
+                holder: holder.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reservation(mac: &str, addr: [u8; 4]) -> Reservation {
+        Reservation {
+            hardware_addr: mac.to_string().try_into().unwrap(),
+            ip_addr: Ipv4Addr::from(addr),
+        }
+    }
+
+    #[test]
+    fn test_assign_rejects_invalid_reservations() {
+        let mut pools = vec![
+            Pool::try_from(("a".to_string(), "10.0.0.1-10.0.0.9".to_string())).unwrap(),
+            Pool::try_from(("b".to_string(), "10.0.1.1-10.0.1.9".to_string())).unwrap(),
+        ];
+
+        let valid = [
+            reservation("02:00:00:00:00:01", [10, 0, 0, 5]),
+            reservation("02:00:00:00:00:02", [10, 0, 1, 5]),
+        ];
+        assert_eq!(assign(&mut pools, &valid), Ok(()));
+        assert_eq!(
+            pools[0].reserved_for(&Ipv4Addr::new(10, 0, 0, 5)),
+            Some("02:00:00:00:00:01")
+        );
+        assert_eq!(
+            pools[1].reserved_for(&Ipv4Addr::new(10, 0, 1, 5)),
+            Some("02:00:00:00:00:02")
+        );
+
+        let outside = [reservation("02:00:00:00:00:03", [10, 0, 2, 5])];
+        assert_eq!(
+            assign(&mut pools, &outside),
+            Err(ReservationError::OutsidePools(Ipv4Addr::new(10, 0, 2, 5)))
+        );
+
+        let same_addr = [
+            reservation("02:00:00:00:00:01", [10, 0, 0, 6]),
+            reservation("02:00:00:00:00:02", [10, 0, 0, 6]),
+        ];
+        assert_eq!(
+            assign(&mut pools, &same_addr),
+            Err(ReservationError::DuplicateAddress(Ipv4Addr::new(
+                10, 0, 0, 6
+            )))
+        );
+
+        let same_client = [
+            reservation("02:00:00:00:00:01", [10, 0, 0, 6]),
+            reservation("02:00:00:00:00:01", [10, 0, 0, 7]),
+        ];
+        assert!(matches!(
+            assign(&mut pools, &same_client),
+            Err(ReservationError::DuplicateClient(_))
+        ));
+
+        // Failed assignments leave the previous reservations in place
+        assert_eq!(
+            pools[0].reserved_for(&Ipv4Addr::new(10, 0, 0, 5)),
+            Some("02:00:00:00:00:01")
+        );
+        assert_eq!(pools[0].reserved_for(&Ipv4Addr::new(10, 0, 0, 6)), None);
+    }
+}
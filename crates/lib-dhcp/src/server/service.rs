@@ -0,0 +1,128 @@
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+use tracing::{warn, Instrument};
+
+use crate::{
+    server::{drop_unreadable, handle, state::ServerState, ListenInterface, ServerStats, Session},
+    types::{Message, MessageError, MessageView},
+    DAEMON_TARGET,
+};
+
+/// Where a datagram passed to [`DhcpService::call`] came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestMeta {
+    /// Source address and port of the datagram.
+    pub source: SocketAddr,
+
+    /// Interface the datagram arrived on, used for the per-interface stats
+    /// and logs.
+    pub interface: ListenInterface,
+}
+
+impl RequestMeta {
+    /// Creates the metadata of a datagram from `source`, received on the
+    /// wildcard interface `any`.
+    pub fn new(source: SocketAddr) -> Self {
+        Self {
+            source,
+            interface: ListenInterface::any(),
+        }
+    }
+
+    pub fn with_interface(mut self, interface: ListenInterface) -> Self {
+        self.interface = interface;
+        self
+    }
+}
+
+/// Encoded reply to a datagram and the address it has to be sent to. The
+/// destination is picked following RFC 2131, replies go to the relay agent,
+/// the client address or are broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub bytes: Vec<u8>,
+    pub destination: SocketAddr,
+}
+
+impl Reply {
+    /// Parses the reply back into a [`Message`].
+    pub fn message(&self) -> Result<Message, MessageError> {
+        Message::from_bytes(&self.bytes)
+    }
+}
+
+/// The request handling core of the [`Server`](crate::Server), without any
+/// sockets. It owns the pools, leases, caches and stats, so front-ends like
+/// the UDP listeners of [`Server::run_async`](crate::Server::run_async), a
+/// daemon embedding the server or tests can share a single instance by
+/// cloning it.
+#[derive(Clone)]
+pub struct DhcpService {
+    pub(crate) state: Arc<ServerState>,
+}
+
+impl DhcpService {
+    pub(crate) fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    pub fn stats(&self) -> &ServerStats {
+        &self.state.stats
+    }
+
+    /// Handles the `datagram` received from `meta` and returns the reply to
+    /// send, if any. Sending it is up to the caller.
+    ///
+    /// Handlers exceeding the handler timeout are aborted. Dropping the
+    /// handler rolls back addresses it allocated but didn't offer yet. The
+    /// latency of handlers finishing in time is recorded per message type.
+    pub async fn call(&self, datagram: &[u8], meta: RequestMeta) -> Option<Reply> {
+        let state = &self.state;
+        let peer = meta.source;
+        let session = Session::new(state.clone(), meta.source, meta.interface);
+        let span = session.span();
+        let started = Instant::now();
+        ServerStats::incr(&session.stats.received);
+
+        // The view and the message type located with it are passed along,
+        // so the datagram is only looked at once before the full parse
+        let view = match MessageView::new(datagram) {
+            Ok(view) => view,
+            Err(err) => {
+                span.in_scope(|| drop_unreadable(&err, &session));
+                return None;
+            }
+        };
+        let message_type = view.message_type();
+
+        let handled = tokio::time::timeout(
+            state.config.handler_timeout,
+            handle(view, message_type.clone(), session),
+        )
+        .instrument(span)
+        .await;
+
+        match (handled, message_type) {
+            (Ok(reply), Some(ty)) => {
+                state.stats.handlers.observe(&ty, started.elapsed());
+                reply
+            }
+            (Ok(reply), None) => reply,
+            (Err(_), ty) => {
+                warn!(
+                    target: DAEMON_TARGET,
+                    %peer,
+                    message_type = ty.as_ref().map(ToString::to_string),
+                    xid = %format_args!("{:#010x}", view.xid()),
+                    timeout = ?state.config.handler_timeout,
+                    "handler timed out, aborting it"
+                );
+
+                if let Some(ty) = ty {
+                    state.stats.handlers.timed_out(&ty);
+                }
+                None
+            }
+        }
+    }
+}
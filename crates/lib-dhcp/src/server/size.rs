@@ -0,0 +1,267 @@
+use binbuf::prelude::*;
+
+use crate::{
+    types::{DhcpOption, Message, MessageError, OptionData, OptionTag},
+    MINIMUM_LEGAL_MAX_MESSAGE_SIZE,
+};
+
+/// Default maximum reply size for relayed requests without option 57. Relay
+/// agents forward replies over the regular network, which usually supports
+/// full Ethernet frames.
+pub const DEFAULT_RELAY_MAX_MESSAGE_SIZE: u16 = 1500;
+
+/// Combined length of the IP and UDP headers. The maximum DHCP message size
+/// (option 57) covers the complete IP datagram, not only the DHCP message.
+const IP_UDP_HEADER_LEN: u16 = 28;
+
+/// Combined length of the fixed fields and the magic cookie, which precede
+/// the options field.
+const FIXED_FIELDS_LEN: usize = 236 + 4;
+
+/// Maximum number of addresses in a single associated-ip option (92), the
+/// option length is limited to 255 octets.
+const MAX_ASSOCIATED_IPS: usize = 255 / 4;
+
+/// Text of the message option (56) added to leasequery replies whose
+/// associated-ip list was truncated.
+pub(crate) const ASSOCIATED_IP_TRUNCATED: &str = "associated-ip list truncated";
+
+/// Options which are never removed when trimming a reply.
+const ESSENTIAL_OPTIONS: [OptionTag; 4] = [
+    OptionTag::DhcpMessageType,
+    OptionTag::ServerIdentifier,
+    OptionTag::IpAddrLeaseTime,
+    OptionTag::End,
+];
+
+/// Returns the maximum size of the reply to `request`. This is the size the
+/// client advertised in option 57, but at least 576 octets, the minimum every
+/// client must accept. Without the option, 576 octets are assumed, unless the
+/// request was relayed, in which case `relay_default` is used.
+///
+/// ### See
+///
+/// RFC 2132 - Section 9.10 - Maximum DHCP Message Size: https://datatracker.ietf.org/doc/html/rfc2132#section-9.10
+pub(crate) fn max_reply_size(request: &Message, relay_default: u16) -> u16 {
+    match request.get_max_message_size() {
+        Some(size) => size.max(MINIMUM_LEGAL_MAX_MESSAGE_SIZE),
+        None if !request.giaddr.is_unspecified() => relay_default,
+        None => MINIMUM_LEGAL_MAX_MESSAGE_SIZE,
+    }
+}
+
+/// Removes options from the end of `reply` until it fits into a datagram of
+/// `max_size` octets. Essential options like the message type are kept.
+/// With `overload`, options are moved into the 'file' and 'sname' fields
+/// first, if they are unused. Returns the number of removed options.
+pub(crate) fn fit_reply(
+    reply: &mut Message,
+    max_size: u16,
+    overload: bool,
+) -> Result<usize, MessageError> {
+    let limit = max_size.saturating_sub(IP_UDP_HEADER_LEN) as usize;
+    let mut removed = 0;
+
+    if overload && encoded_len(reply)? > limit {
+        match reply.overload_options(limit.saturating_sub(FIXED_FIELDS_LEN)) {
+            Ok(_) | Err(MessageError::OptionsTooLarge(..)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    while encoded_len(reply)? > limit {
+        let index = match reply
+            .options
+            .iter()
+            .rposition(|o| !ESSENTIAL_OPTIONS.contains(&o.header().tag))
+        {
+            Some(index) => index,
+            None => break,
+        };
+
+        reply.options.remove(index);
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Drops addresses from the end of the associated-ip option (92) of `reply`
+/// until the reply fits into a datagram of `max_size` octets. A truncation
+/// is signaled by a message option (56). Returns `true` if the list was
+/// truncated.
+pub(crate) fn fit_associated_ips(reply: &mut Message, max_size: u16) -> Result<bool, MessageError> {
+    let limit = max_size.saturating_sub(IP_UDP_HEADER_LEN) as usize;
+
+    let index = match reply
+        .options
+        .iter()
+        .position(|o| o.header().tag == OptionTag::AssociatedIp)
+    {
+        Some(index) => index,
+        None => return Ok(false),
+    };
+
+    let mut ips = match reply.options[index].data() {
+        OptionData::AssociatedIp(ips) => ips.clone(),
+        _ => return Ok(false),
+    };
+
+    if ips.len() <= MAX_ASSOCIATED_IPS && encoded_len(reply)? <= limit {
+        return Ok(false);
+    }
+
+    // The note takes up space as well, add it before trimming the list
+    let end = reply
+        .options
+        .iter()
+        .position(|o| o.header().tag == OptionTag::End)
+        .unwrap_or(reply.options.len());
+    reply.options.insert(
+        end,
+        DhcpOption::new(
+            OptionTag::Message,
+            OptionData::Message(String::from(ASSOCIATED_IP_TRUNCATED)),
+        ),
+    );
+
+    ips.truncate(MAX_ASSOCIATED_IPS);
+
+    loop {
+        reply.options[index] = DhcpOption::new(
+            OptionTag::AssociatedIp,
+            OptionData::AssociatedIp(ips.clone()),
+        );
+
+        if ips.len() <= 1 || encoded_len(reply)? <= limit {
+            break;
+        }
+
+        ips.pop();
+    }
+
+    Ok(true)
+}
+
+fn encoded_len(message: &Message) -> Result<usize, MessageError> {
+    let mut buf = WriteBuffer::new();
+    message.write::<BigEndian>(&mut buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::types::{options::OptionOverload, OptionData};
+
+    fn request(max_size: Option<u16>) -> Message {
+        let mut message = Message::new_with_xid(1);
+
+        if let Some(size) = max_size {
+            message
+                .add_option_parts(
+                    OptionTag::MaxDhcpMessageSize,
+                    OptionData::MaxDhcpMessageSize(size),
+                )
+                .unwrap();
+        }
+
+        message.end().unwrap();
+        message
+    }
+
+    #[test]
+    fn test_max_reply_size() {
+        assert_eq!(max_reply_size(&request(Some(1200)), 1500), 1200);
+        assert_eq!(max_reply_size(&request(Some(576)), 1500), 576);
+        assert_eq!(max_reply_size(&request(Some(300)), 1500), 576);
+        assert_eq!(max_reply_size(&request(None), 1500), 576);
+
+        let mut relayed = request(None);
+        relayed.giaddr = Ipv4Addr::new(10, 0, 0, 1);
+        assert_eq!(max_reply_size(&relayed, 1500), 1500);
+        assert_eq!(max_reply_size(&relayed, 1000), 1000);
+    }
+
+    #[test]
+    fn test_fit_reply_overloads_options() {
+        let servers: Vec<_> = (1..=40).map(|i| Ipv4Addr::new(10, 0, 0, i)).collect();
+
+        let mut reply = Message::new_with_xid(1);
+        for (tag, data) in [
+            (
+                OptionTag::DomainNameServer,
+                OptionData::DomainNameServer(servers.clone()),
+            ),
+            (
+                OptionTag::Router,
+                OptionData::Router(servers[..30].to_vec()),
+            ),
+            (
+                OptionTag::NetworkTimeProtocolServers,
+                OptionData::NetworkTimeProtocolServers(servers[..30].to_vec()),
+            ),
+        ] {
+            reply.add_option_parts(tag, data).unwrap();
+        }
+        reply.end().unwrap();
+
+        let mut trimmed = reply.clone();
+        assert_eq!(fit_reply(&mut trimmed, 576, false).unwrap(), 1);
+
+        // The last option moves into 'file' instead of being dropped
+        let mut overloaded = reply.clone();
+        assert_eq!(fit_reply(&mut overloaded, 576, true).unwrap(), 0);
+        assert!(encoded_len(&overloaded).unwrap() <= 548);
+        assert_eq!(
+            overloaded
+                .get_option(OptionTag::OptionOverload)
+                .map(|o| o.data()),
+            Some(&OptionData::OptionOverload(OptionOverload::File))
+        );
+
+        let mut buf = WriteBuffer::new();
+        overloaded.write::<BigEndian>(&mut buf).unwrap();
+        let parsed = Message::from_bytes(buf.bytes()).unwrap();
+        assert_eq!(parsed.options_map().len(), reply.options_map().len() + 1);
+    }
+
+    #[test]
+    fn test_fit_associated_ips() {
+        let ips: Vec<_> = (1..=20).map(|i| Ipv4Addr::new(192, 168, 1, i)).collect();
+
+        let mut reply = Message::new_with_xid(1);
+        reply
+            .add_option_parts(
+                OptionTag::AssociatedIp,
+                OptionData::AssociatedIp(ips.clone()),
+            )
+            .unwrap();
+        reply.end().unwrap();
+
+        // Fits as is
+        let len = encoded_len(&reply).unwrap() as u16;
+        let mut fitting = reply.clone();
+        assert!(!fit_associated_ips(&mut fitting, len + IP_UDP_HEADER_LEN).unwrap());
+        assert_eq!(fitting, reply);
+
+        // One octet too many, the list has to make room for the note as well
+        let max_size = len + IP_UDP_HEADER_LEN - 1;
+        assert!(fit_associated_ips(&mut reply, max_size).unwrap());
+
+        let note_len = 2 + ASSOCIATED_IP_TRUNCATED.len();
+        let kept = ips.len() - (note_len + 1 + 3) / 4;
+        assert_eq!(
+            reply
+                .get_option(OptionTag::AssociatedIp)
+                .and_then(|o| o.data().as_ipv4_list()),
+            Some(&ips[..kept])
+        );
+        assert_eq!(
+            reply.get_message_text().map(String::as_str),
+            Some(ASSOCIATED_IP_TRUNCATED)
+        );
+        assert!(encoded_len(&reply).unwrap() <= (max_size - IP_UDP_HEADER_LEN) as usize);
+    }
+}
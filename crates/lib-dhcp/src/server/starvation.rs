@@ -0,0 +1,215 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::bounded::BoundedTtlMap;
+
+/// Default sliding window in which new clients are counted.
+pub const DEFAULT_STARVATION_WINDOW: Duration = Duration::from_secs(10);
+
+/// Default number of new clients within the window above which a starvation
+/// attack is assumed.
+pub const DEFAULT_STARVATION_THRESHOLD: usize = 50;
+
+/// Default offer timeout while in defensive mode.
+pub const DEFAULT_DEFENSIVE_OFFER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time after which a client which wasn't seen again is treated as new.
+const KNOWN_CLIENT_RETENTION: Duration = Duration::from_secs(600);
+
+/// Transitions of the [`StarvationDetector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StarvationEvent {
+    /// The rate of new clients exceeded the threshold. Contains the number of
+    /// new clients within the window.
+    Detected(usize),
+
+    /// The rate of new clients dropped to half the threshold or below.
+    Subsided,
+}
+
+/// Detects DHCP starvation attacks, where a flood of DHCPDISCOVERs with
+/// random hardware addresses empties the pools. The detector tracks the
+/// arrival rate of never-seen client keys over a sliding window. It becomes
+/// active when more than `threshold` new clients arrive within the window
+/// and inactive again when the rate drops to half the threshold, so that it
+/// doesn't flap around the threshold.
+///
+/// At most `capacity` known clients are remembered. Once full, the least
+/// recently seen client is forgotten and counted as new when it returns.
+/// The arrivals within the window are bounded the same way, above the
+/// threshold, so a flood saturates the rate instead of growing the window.
+#[derive(Debug)]
+pub(crate) struct StarvationDetector {
+    arrivals: VecDeque<Instant>,
+    arrival_limit: usize,
+    known: BoundedTtlMap<String, ()>,
+    window: Duration,
+    threshold: usize,
+    active: bool,
+}
+
+impl StarvationDetector {
+    pub fn new(window: Duration, threshold: usize, capacity: usize) -> Self {
+        Self {
+            arrivals: VecDeque::new(),
+            arrival_limit: capacity.max(threshold + 1),
+            known: BoundedTtlMap::new(capacity, KNOWN_CLIENT_RETENTION),
+            active: false,
+            threshold,
+            window,
+        }
+    }
+
+    /// Returns if a starvation attack is currently assumed.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Records a DHCPDISCOVER of the client with `key` arriving at `now`.
+    /// Returns an event if the detector changed its state.
+    pub fn observe(&mut self, key: &str, now: Instant) -> Option<StarvationEvent> {
+        self.known.purge_expired(now);
+
+        if self.known.get(key, now).is_none() {
+            if self.arrivals.len() == self.arrival_limit {
+                self.arrivals.pop_front();
+            }
+            self.arrivals.push_back(now);
+        }
+
+        self.known.insert(key.to_string(), (), now);
+        self.poll(now)
+    }
+
+    /// Re-evaluates the state of the detector at `now`, without recording an
+    /// arrival. Returns an event if the detector changed its state.
+    pub fn poll(&mut self, now: Instant) -> Option<StarvationEvent> {
+        while let Some(arrival) = self.arrivals.front() {
+            if now.saturating_duration_since(*arrival) < self.window {
+                break;
+            }
+            self.arrivals.pop_front();
+        }
+
+        let rate = self.arrivals.len();
+
+        if !self.active && rate > self.threshold {
+            self.active = true;
+            return Some(StarvationEvent::Detected(rate));
+        }
+
+        if self.active && rate <= self.threshold / 2 {
+            self.active = false;
+            return Some(StarvationEvent::Subsided);
+        }
+
+        None
+    }
+
+    /// Returns the number of known clients forgotten because the detector
+    /// remembered too many.
+    pub fn evictions(&self) -> u64 {
+        self.known.evictions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> StarvationDetector {
+        StarvationDetector::new(Duration::from_secs(10), 10, 1000)
+    }
+
+    #[test]
+    fn test_steady_clients_dont_trigger() {
+        let mut detector = detector();
+        let start = Instant::now();
+
+        // One new client per second stays well below 10 per 10 seconds
+        for i in 0..100 {
+            let event = detector.observe(&format!("client-{i}"), start + Duration::from_secs(i));
+            assert_eq!(event, None);
+        }
+
+        assert!(!detector.is_active());
+    }
+
+    #[test]
+    fn test_flood_triggers_and_subsides() {
+        let mut detector = detector();
+        let start = Instant::now();
+
+        // 20 random clients within 200 ms
+        let events: Vec<_> = (0..20)
+            .filter_map(|i| {
+                detector.observe(
+                    &format!("random-{i}"),
+                    start + Duration::from_millis(i * 10),
+                )
+            })
+            .collect();
+
+        assert_eq!(events, vec![StarvationEvent::Detected(11)]);
+        assert!(detector.is_active());
+
+        // The flood stops, the window slides past the arrivals
+        let later = start + Duration::from_secs(11);
+        assert_eq!(detector.poll(later), Some(StarvationEvent::Subsided));
+        assert!(!detector.is_active());
+    }
+
+    #[test]
+    fn test_arrivals_are_bounded() {
+        let mut detector = StarvationDetector::new(Duration::from_secs(10), 10, 100);
+        let start = Instant::now();
+
+        for i in 0..10_000 {
+            detector.observe(&format!("random-{i}"), start);
+        }
+        assert_eq!(detector.arrivals.len(), 100);
+        assert!(detector.is_active());
+
+        let later = start + Duration::from_secs(11);
+        assert_eq!(detector.poll(later), Some(StarvationEvent::Subsided));
+    }
+
+    #[test]
+    fn test_known_clients_are_not_counted() {
+        let mut detector = detector();
+        let start = Instant::now();
+
+        // A single client sending a burst of DHCPDISCOVERs
+        for i in 0..100 {
+            let event = detector.observe("de:ad:be:ef:12:34", start + Duration::from_millis(i));
+            assert_eq!(event, None);
+        }
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut detector = detector();
+        let start = Instant::now();
+
+        for i in 0..11 {
+            detector.observe(&format!("a-{i}"), start);
+        }
+        for i in 0..6 {
+            detector.observe(&format!("b-{i}"), start + Duration::from_secs(9));
+        }
+        assert!(detector.is_active());
+
+        // The first burst left the window, but the rate is still above half
+        // the threshold
+        let later = start + Duration::from_secs(15);
+        assert_eq!(detector.observe("c", later), None);
+        assert!(detector.is_active());
+
+        // Once the rate drops to half the threshold, the detector resets
+        let later = start + Duration::from_secs(20);
+        assert_eq!(detector.poll(later), Some(StarvationEvent::Subsided));
+        assert!(!detector.is_active());
+    }
+}
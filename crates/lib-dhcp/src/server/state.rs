@@ -0,0 +1,1345 @@
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    bounded::BoundedTtlMap,
+    builder::MessageBuilder,
+    server::{
+        audit::AuditProgress,
+        bindings::BindingIndex,
+        coalesce::DiscoverCache,
+        config::{LeaseTimes, PoolOptions, ServerConfig},
+        history::ChurnEvent,
+        hostname::{resolve_conflict, ClientHostname, HostnameHandling},
+        limits::TransientCache,
+        offers::{OfferCache, PendingOffer},
+        ownership::foreign_pools,
+        persistence::PersistenceHealth,
+        pool::Pool,
+        probe::ConflictProber,
+        reclaim::TransitionError,
+        replay::ReplayDetector,
+        reservations::{self, Reservation, ReservationConflict, ReservationError},
+        starvation::{StarvationDetector, StarvationEvent},
+        stats::ServerStats,
+        storage::{PersistedState, ReclaimedAddr, ServerStorageError, StateStore, STATE_VERSION},
+    },
+    transaction::{TransactionRecord, TransactionStep},
+    types::{options::DhcpMessageType, HardwareAddr, Ipv4Net, Lease},
+    Clock, DAEMON_TARGET, LEASE_TARGET,
+};
+
+/// State shared between all sessions (handlers) of the server.
+pub(crate) struct ServerState {
+    pub config: ServerConfig,
+    pub pools: Mutex<Vec<Pool>>,
+    pub discover_cache: Mutex<DiscoverCache>,
+    pub offers: Mutex<OfferCache>,
+    pub leases: Mutex<HashMap<String, Lease>>,
+    pub bindings: Mutex<BindingIndex>,
+    pub starvation: Mutex<StarvationDetector>,
+    pub replay: Mutex<ReplayDetector>,
+
+    /// Transactions waiting for the client to follow up, keyed by client.
+    /// They are completed by the DHCPACK or DHCPNAK, or when the offer
+    /// expires.
+    pub transactions: Mutex<BoundedTtlMap<String, TransactionRecord>>,
+
+    /// Reservations as configured, the pools hold them by address.
+    pub reservations: Mutex<Vec<Reservation>>,
+
+    /// Destination of the persisted state, [`None`] if the state isn't
+    /// persisted.
+    pub store: Option<Arc<dyn StateStore>>,
+    pub persistence: PersistenceHealth,
+    pub audit: AuditProgress,
+
+    /// Probes newly allocated addresses before they are offered.
+    pub offer_prober: Option<Arc<dyn ConflictProber>>,
+    pub builder: MessageBuilder,
+    pub stats: Arc<ServerStats>,
+    pub clock: Arc<dyn Clock>,
+}
+
+impl ServerState {
+    /// Returns the address offered to the client with `key`. Repeated
+    /// DHCPDISCOVERs within the coalescing window reuse the previously
+    /// computed offer and skip the allocator.
+    pub fn offer_address(&self, key: &str, now: Instant) -> Option<Ipv4Addr> {
+        let mut cache = self.discover_cache.lock().unwrap();
+
+        if let Some(addr) = cache.get(key, now) {
+            ServerStats::incr(&self.stats.discover_cache_hits);
+            return Some(addr);
+        }
+
+        ServerStats::incr(&self.stats.discover_cache_misses);
+
+        let addr = self.allocate(key)?;
+        cache.insert(key.to_string(), addr, now);
+        self.stats
+            .cache_evictions
+            .set(TransientCache::DiscoverCache, cache.evictions());
+
+        Some(addr)
+    }
+
+    /// Feeds the DHCPDISCOVER of the client with `key` into the starvation
+    /// detector. Clients holding a lease are never counted as new.
+    pub fn observe_discover(&self, key: &str, now: Instant) {
+        if self.leases.lock().unwrap().contains_key(key) {
+            return;
+        }
+
+        let event = {
+            let mut detector = self.starvation.lock().unwrap();
+            let event = detector.observe(key, now);
+            self.stats
+                .cache_evictions
+                .set(TransientCache::KnownClients, detector.evictions());
+            event
+        };
+
+        match event {
+            Some(StarvationEvent::Detected(rate)) => {
+                ServerStats::incr(&self.stats.starvation_alerts);
+                warn!(
+                    target: LEASE_TARGET,
+                    new_clients = rate,
+                    defensive_mode = self.config.defensive_mode,
+                    "possible DHCP starvation attack detected"
+                );
+            }
+            Some(StarvationEvent::Subsided) => {
+                info!(
+                    target: LEASE_TARGET,
+                    "rate of new clients subsided, leaving starvation alert"
+                )
+            }
+            None => {}
+        }
+    }
+
+    /// Checks the replay detection `value` of an authenticated message, see
+    /// [`ReplayDetector::accept`]. Returns `false` for replayed messages.
+    pub fn accept_replay_detection(
+        &self,
+        hardware_addr: &HardwareAddr,
+        rdm: u8,
+        value: u64,
+    ) -> bool {
+        let mut detector = self.replay.lock().unwrap();
+        let accepted = detector.accept(hardware_addr, rdm, value, self.clock.now_instant());
+
+        self.stats
+            .cache_evictions
+            .set(TransientCache::ReplayCounters, detector.evictions());
+        accepted
+    }
+
+    /// Returns if a starvation attack is currently assumed.
+    pub fn under_starvation(&self, now: Instant) -> bool {
+        let mut detector = self.starvation.lock().unwrap();
+        detector.poll(now);
+        detector.is_active()
+    }
+
+    /// Returns the time an offer stays reserved. In defensive mode, offers
+    /// expire quickly so that addresses only count as consumed when the
+    /// client follows up with a DHCPREQUEST.
+    pub fn offer_timeout(&self, now: Instant) -> Duration {
+        if self.config.defensive_mode && self.under_starvation(now) {
+            return self.config.defensive_offer_timeout;
+        }
+
+        self.config.offer_timeout
+    }
+
+    /// Returns if `addr` is on a subnet we serve, that is, the subnet of
+    /// any pool. Without a configured subnet mask, the pool ranges are used.
+    pub fn serves_subnet(&self, addr: &Ipv4Addr) -> bool {
+        let pools = self.pools.lock().unwrap();
+
+        pools.iter().any(|pool| match self.config.subnet_mask {
+            Some(mask) => Ipv4Net::with_netmask(pool.range().start(), mask)
+                .map(|net| net.contains(addr))
+                .unwrap_or(false),
+            None => pool.range().contains(addr),
+        })
+    }
+
+    /// Returns the lease times of `addr`. These are the times of the pool
+    /// containing the address, falling back to the server-wide times.
+    pub fn lease_times(&self, addr: Ipv4Addr) -> LeaseTimes {
+        let pools = self.pools.lock().unwrap();
+
+        pools
+            .iter()
+            .find(|pool| pool.range().contains(&addr))
+            .and_then(|pool| pool.times())
+            .unwrap_or_else(|| self.config.default_times())
+    }
+
+    /// Returns the configuration options of `addr`. These are the options of
+    /// the pool containing the address, with unset ones taken from the
+    /// server-wide options.
+    pub fn pool_options(&self, addr: Ipv4Addr) -> PoolOptions {
+        let pools = self.pools.lock().unwrap();
+        let defaults = self.config.default_options();
+
+        match pools.iter().find(|pool| pool.range().contains(&addr)) {
+            Some(pool) => pool.options().clone().or(&defaults),
+            None => defaults,
+        }
+    }
+
+    /// Returns the name of the pool containing `addr`.
+    pub fn pool_name(&self, addr: Ipv4Addr) -> Option<String> {
+        let pools = self.pools.lock().unwrap();
+        pools
+            .iter()
+            .find(|pool| pool.range().contains(&addr))
+            .map(|pool| pool.name().to_string())
+    }
+
+    /// Keeps the transaction of the client with `key` open until the client
+    /// follows up. A previously open transaction of the client is replaced.
+    /// Transactions open for too long, or evicted because too many are
+    /// open, are dropped without being emitted.
+    pub fn open_transaction(&self, key: &str, record: TransactionRecord) {
+        let now = self.clock.now_instant();
+        let mut transactions = self.transactions.lock().unwrap();
+
+        transactions.purge_expired(now);
+        transactions.insert(key.to_string(), record, now);
+        self.stats
+            .cache_evictions
+            .set(TransientCache::Transactions, transactions.evictions());
+    }
+
+    /// Takes the open transaction of the client with `key`.
+    pub fn take_transaction(&self, key: &str) -> Option<TransactionRecord> {
+        self.transactions.lock().unwrap().remove(key)
+    }
+
+    /// Completes `record` now and emits it.
+    pub fn complete_transaction(&self, mut record: TransactionRecord) {
+        record.complete(self.clock.now_system(), self.clock.now_instant());
+        record.emit();
+    }
+
+    /// Records that `yiaddr` was offered to the client with `key` in the
+    /// transaction `xid`. The address stays reserved until the client
+    /// requests it or the offer expires after `timeout`.
+    pub fn record_offer(
+        &self,
+        key: &str,
+        hardware_addr: HardwareAddr,
+        xid: u32,
+        yiaddr: Ipv4Addr,
+        timeout: Duration,
+        now: SystemTime,
+    ) {
+        self.expire_offers(now);
+
+        let evicted = {
+            let mut offers = self.offers.lock().unwrap();
+            let evicted = offers.insert(key.to_string(), hardware_addr, xid, yiaddr, now + timeout);
+
+            self.stats
+                .cache_evictions
+                .set(TransientCache::Offers, offers.evictions());
+            evicted
+        };
+
+        // The offer cache is full, the client of the oldest offer is
+        // treated as if it never followed up
+        if let Some(offer) = evicted {
+            warn!(
+                target: LEASE_TARGET,
+                client = offer.key(),
+                addr = %offer.yiaddr(),
+                "offer cache full, withdrawing offer"
+            );
+            self.withdraw_offers(&[offer], &[TransactionStep::Expired]);
+        }
+    }
+
+    /// Turns the pending offer of `yiaddr` to the client with `key` into a
+    /// lease (Offered to Active). Fails if there is no such offer, it
+    /// expired, or the address was offered to another client.
+    pub fn commit_offer(
+        &self,
+        key: &str,
+        yiaddr: Ipv4Addr,
+        now: SystemTime,
+    ) -> Result<Lease, TransitionError> {
+        let offer = {
+            let mut offers = self.offers.lock().unwrap();
+
+            match offers.take(key, yiaddr, now) {
+                Some(offer) => offer,
+                None if offers.offeree(yiaddr, now).is_some() => {
+                    return Err(TransitionError::NotOfferee(yiaddr))
+                }
+                None => return Err(TransitionError::NoOffer(yiaddr)),
+            }
+        };
+
+        let lease = Lease::new_at(
+            offer.hardware_addr().clone(),
+            yiaddr,
+            self.lease_times(yiaddr).lease_time,
+            None,
+            now,
+        );
+
+        let mut leases = self.leases.lock().unwrap();
+        {
+            let mut pools = self.pools.lock().unwrap();
+            if !reserve(&mut pools, yiaddr, key) {
+                return Err(TransitionError::HeldByOther(yiaddr));
+            }
+            self.record_churn(&pools, yiaddr, ChurnEvent::Granted, now);
+        }
+
+        leases.insert(key.to_string(), lease.clone());
+        self.bindings.lock().unwrap().insert(key, &lease);
+
+        Ok(lease)
+    }
+
+    /// Extends the lease of `addr` held by the client with `key`. Returns
+    /// [`None`] if the client holds no lease for this address or the address
+    /// was handed out to another client after the lease was released.
+    ///
+    /// Leases conflicting with a reservation are renewed for their remaining
+    /// time only, so they end when they would have without the renewal. An
+    /// ended lease of this kind is released, see [`ReservationConflict`].
+    pub fn renew_lease(&self, key: &str, addr: Ipv4Addr, now: SystemTime) -> Option<Lease> {
+        let lease_time = self.lease_times(addr).lease_time;
+
+        let mut leases = self.leases.lock().unwrap();
+        let lease = leases.get_mut(key).filter(|l| l.ip_addr() == addr)?;
+
+        let mut pools = self.pools.lock().unwrap();
+        if !reserve(&mut pools, addr, key) {
+            return None;
+        }
+
+        let conflicting = pools.iter().any(|pool| {
+            pool.reserved_for(&addr).is_some_and(|k| k != key)
+                || pool
+                    .reservation_of(key)
+                    .is_some_and(|reserved| reserved != addr)
+        });
+
+        if conflicting {
+            let remaining = lease.remaining(now).map_or(0, |d| d.as_secs() as u32);
+
+            if remaining == 0 {
+                for pool in pools.iter_mut() {
+                    pool.release(&addr);
+                }
+                self.record_churn(&pools, addr, ChurnEvent::Expired, now);
+                drop(pools);
+
+                lease.expire(now);
+                self.bindings.lock().unwrap().remove(key, &addr);
+                info!(target: LEASE_TARGET, client = key, %addr, "lease ended, handing over to reservation");
+                return None;
+            }
+
+            lease.renew(remaining, now);
+        } else {
+            lease.renew(lease_time, now);
+        }
+
+        drop(pools);
+        self.bindings.lock().unwrap().insert(key, lease);
+
+        Some(lease.clone())
+    }
+
+    /// Replaces the reservations with `reservations` and returns the ones
+    /// which don't take effect yet. Nothing is changed if any reservation is
+    /// invalid.
+    pub fn reload_reservations(
+        &self,
+        reservations: Vec<Reservation>,
+    ) -> Result<Vec<ReservationConflict>, ReservationError> {
+        let now = self.clock.now_system();
+        let leases = self.leases.lock().unwrap();
+        let mut pools = self.pools.lock().unwrap();
+
+        reservations::assign(&mut pools, &reservations)?;
+        let conflicts = reservations::conflicts(&reservations, &leases, &pools, now);
+        drop(pools);
+        drop(leases);
+
+        // Cached offers may point at addresses which are reserved now
+        self.discover_cache.lock().unwrap().clear();
+
+        info!(
+            target: LEASE_TARGET,
+            reservations = reservations.len(),
+            conflicts = conflicts.len(),
+            "reloaded reservations"
+        );
+        for conflict in &conflicts {
+            info!(target: LEASE_TARGET, "{conflict}");
+        }
+
+        *self.reservations.lock().unwrap() = reservations;
+
+        // Interface addresses may have changed since the server was built
+        self.warn_foreign_pools();
+        Ok(conflicts)
+    }
+
+    /// Warns about pools for which none of the listen interfaces owns an
+    /// address in the pool's subnet, see [`foreign_pools`].
+    pub(crate) fn warn_foreign_pools(&self) {
+        let addrs = match lib_ifs::if_addrs() {
+            Ok(addrs) => addrs,
+            Err(err) => {
+                warn!(target: DAEMON_TARGET, %err, "failed to check the pools' subnets");
+                return;
+            }
+        };
+
+        let pools = self.pools.lock().unwrap();
+        let foreign = foreign_pools(&pools, &addrs, &self.config.interfaces);
+        if !foreign.is_empty() {
+            warn!(
+                target: DAEMON_TARGET,
+                pools = ?foreign,
+                "no interface owns an address in the subnet of pools"
+            );
+        }
+    }
+
+    /// Returns the reservations which don't take effect yet because of
+    /// leases handed out before they were added.
+    pub fn reservation_conflicts(&self) -> Vec<ReservationConflict> {
+        let now = self.clock.now_system();
+        let leases = self.leases.lock().unwrap();
+        let pools = self.pools.lock().unwrap();
+        let reservations = self.reservations.lock().unwrap();
+
+        reservations::conflicts(&reservations, &leases, &pools, now)
+    }
+
+    /// Sets the hostname of the lease held by the client with `key` to the
+    /// sanitized form of `raw`, as far as the configured handling allows it.
+    /// Conflicts with the hostnames of other clients are resolved according
+    /// to the configured policy. Does nothing if the client holds no lease.
+    pub fn assign_hostname(&self, key: &str, raw: &str, now: SystemTime) {
+        if self.config.hostname_handling == HostnameHandling::Ignore {
+            return;
+        }
+
+        let hostname = ClientHostname::new(raw);
+        let raw = hostname.raw.clone();
+        let accepted = hostname.accepted(self.config.hostname_handling);
+        let mut leases = self.leases.lock().unwrap();
+
+        let sanitized = accepted.as_deref().and_then(|name| {
+            let resolved = resolve_conflict(
+                name,
+                key,
+                &leases,
+                self.config.hostname_conflict_policy,
+                now,
+            );
+
+            match &resolved {
+                Some(resolved) if resolved != name => {
+                    info!(
+                        target: LEASE_TARGET,
+                        client = key,
+                        hostname = %resolved,
+                        "hostname already taken, renamed"
+                    )
+                }
+                None => warn!(
+                    target: LEASE_TARGET,
+                    client = key,
+                    hostname = name,
+                    "hostname already taken, rejected"
+                ),
+                Some(_) => {}
+            }
+
+            resolved
+        });
+
+        if let Some(lease) = leases.get_mut(key) {
+            lease.set_hostname(raw, sanitized);
+        }
+    }
+
+    /// Records that the client with `key` sent a message of type
+    /// `message_type`. Does nothing if the client holds no lease.
+    pub fn touch_lease(
+        &self,
+        key: &str,
+        message_type: DhcpMessageType,
+        now: SystemTime,
+        circuit_id: Option<&[u8]>,
+    ) {
+        if let Some(lease) = self.leases.lock().unwrap().get_mut(key) {
+            lease.touch(message_type, now, circuit_id);
+            self.bindings.lock().unwrap().update(key, lease);
+        }
+    }
+
+    /// Releases the lease of `addr` held by the client with `key` and
+    /// returns the address to the pools. The lease itself is kept as an
+    /// expired record of the binding for the lease retention. Returns `false` if the client holds
+    /// no lease for this address.
+    pub fn release_lease(&self, key: &str, addr: Ipv4Addr, now: SystemTime) -> bool {
+        let mut leases = self.leases.lock().unwrap();
+        let lease = match leases.get_mut(key).filter(|l| l.ip_addr() == addr) {
+            Some(lease) => lease,
+            None => return false,
+        };
+
+        lease.touch(DhcpMessageType::Release, now, None);
+        lease.expire(now);
+
+        {
+            let mut pools = self.pools.lock().unwrap();
+            for pool in pools.iter_mut() {
+                pool.release(&addr);
+            }
+            self.record_churn(&pools, addr, ChurnEvent::Released, now);
+        }
+
+        self.bindings.lock().unwrap().remove(key, &addr);
+        true
+    }
+
+    /// Revokes the lease of the client with `hardware_addr`, for example on
+    /// request of an operator. Unlike a release by the client, the lease is
+    /// removed completely and all addresses bound to the client are returned
+    /// to the pools. Returns `false` if the client holds no lease.
+    pub fn revoke_lease(&self, hardware_addr: &HardwareAddr) -> bool {
+        let mut leases = self.leases.lock().unwrap();
+
+        let key = match leases
+            .iter()
+            .find(|(_, lease)| lease.hardware_addr() == hardware_addr)
+        {
+            Some((key, _)) => key.clone(),
+            None => return false,
+        };
+
+        let lease = leases.remove(&key).unwrap();
+        let mut bindings = self.bindings.lock().unwrap();
+        let mut pools = self.pools.lock().unwrap();
+
+        let mut addrs = bindings.addresses(&key);
+        addrs.push(lease.ip_addr());
+
+        let now = self.clock.now_system();
+        for addr in addrs {
+            bindings.remove(&key, &addr);
+
+            for pool in pools.iter_mut() {
+                if pool.address_of(&key) == Some(addr) {
+                    pool.release(&addr);
+                    self.stats
+                        .history()
+                        .record(pool.name(), ChurnEvent::Released, now);
+                }
+            }
+        }
+
+        info!(target: LEASE_TARGET, %hardware_addr, addr = %lease.ip_addr(), "revoked lease");
+        true
+    }
+
+    /// Returns the address of the expired lease held by the client with
+    /// `key` to the pools (Active to Expired). Fails unless the lease ended
+    /// more than the reclaim grace before `now` and the client wasn't seen
+    /// within the grace either. The lease is kept as an expired record of
+    /// the binding for the lease retention.
+    pub fn expire_lease(&self, key: &str, now: SystemTime) -> Result<Ipv4Addr, TransitionError> {
+        let grace = self.config.reclaim_grace;
+        let leases = self.leases.lock().unwrap();
+
+        let lease = leases
+            .get(key)
+            .ok_or_else(|| TransitionError::NoLease(key.to_string()))?;
+        let addr = lease.ip_addr();
+
+        if lease.leased_until() + grace > now {
+            return Err(TransitionError::NotExpired(addr));
+        }
+
+        if lease.last_seen().is_some_and(|seen| seen + grace > now) {
+            return Err(TransitionError::RenewedWithinGrace(addr));
+        }
+
+        let mut pools = self.pools.lock().unwrap();
+        let pool = pools
+            .iter_mut()
+            .find(|pool| pool.range().contains(&addr))
+            .ok_or(TransitionError::NotHeld(addr))?;
+        pool.reclaim(addr, key, now)?;
+
+        self.stats
+            .history()
+            .record(pool.name(), ChurnEvent::Expired, now);
+        drop(pools);
+
+        self.bindings.lock().unwrap().remove(key, &addr);
+        Ok(addr)
+    }
+
+    /// Removes the records of released and reclaimed leases which ended more
+    /// than the lease retention before `now`. Leases whose address is still
+    /// held by their client are kept. Returns the number of purged leases.
+    pub fn purge_ended_leases(&self, now: SystemTime) -> usize {
+        let retention = self.config.lease_retention;
+        let mut leases = self.leases.lock().unwrap();
+        let pools = self.pools.lock().unwrap();
+        let before = leases.len();
+
+        leases.retain(|key, lease| {
+            lease.leased_until() + retention > now
+                || pools
+                    .iter()
+                    .any(|pool| pool.holder_of(&lease.ip_addr()) == Some(key.as_str()))
+        });
+
+        before - leases.len()
+    }
+
+    /// Reclaims the addresses of all leases which expired more than the
+    /// reclaim grace before `now`. Returns the reclaimed addresses.
+    pub fn reclaim_expired(&self, now: SystemTime) -> Vec<Ipv4Addr> {
+        let candidates: Vec<String> = {
+            let leases = self.leases.lock().unwrap();
+            let pools = self.pools.lock().unwrap();
+
+            leases
+                .iter()
+                .filter(|(key, lease)| {
+                    lease.remaining(now).is_none()
+                        && pools
+                            .iter()
+                            .any(|pool| pool.holder_of(&lease.ip_addr()) == Some(key.as_str()))
+                })
+                .map(|(key, _)| key.clone())
+                .collect()
+        };
+
+        candidates
+            .iter()
+            .filter_map(|key| self.expire_lease(key, now).ok())
+            .collect()
+    }
+
+    /// Persists the committed leases and pending offers to the configured
+    /// store. This is a no-op if no store is configured.
+    ///
+    /// A failed write degrades persistence until a later write succeeds,
+    /// see [`PersistenceHealth`]. Every write covers the full state, so the
+    /// first successful one catches up on everything missed before.
+    pub fn persist(&self) -> Result<(), ServerStorageError> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+
+        let result = store.save(&self.snapshot(self.clock.now_system()));
+
+        match &result {
+            Ok(()) => {
+                if self.persistence.succeeded() {
+                    info!(target: DAEMON_TARGET, "persisting state recovered");
+                }
+            }
+            Err(err) => {
+                ServerStats::incr(&self.stats.persist_failures);
+
+                if self
+                    .persistence
+                    .failed(err.to_string(), self.clock.now_instant())
+                {
+                    warn!(
+                        target: DAEMON_TARGET,
+                        %err,
+                        require_persistence = self.config.require_persistence,
+                        "failed to persist state, persistence is degraded"
+                    );
+                }
+            }
+        }
+
+        let degraded = self.persistence.is_degraded();
+        self.stats
+            .persistence_degraded
+            .store(degraded.into(), Ordering::Relaxed);
+
+        result
+    }
+
+    /// Returns the state which needs to survive a restart: committed leases,
+    /// offers which didn't expire yet and reclaimed addresses.
+    pub fn snapshot(&self, now: SystemTime) -> PersistedState {
+        let mut reclaimed: Vec<_> = self
+            .pools
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|pool| pool.reclaimed())
+            .map(|(addr, key, reclaimed_at)| ReclaimedAddr {
+                addr,
+                key: key.to_string(),
+                reclaimed_at,
+            })
+            .collect();
+        reclaimed.sort_unstable_by_key(|reclaimed| reclaimed.addr);
+
+        PersistedState {
+            version: STATE_VERSION,
+            leases: self.leases.lock().unwrap().clone(),
+            pending: self.offers.lock().unwrap().pending(now),
+            reclaimed,
+        }
+    }
+
+    /// Restores previously persisted state. Addresses of active leases and
+    /// pending offers are reserved in the pools again, expired offers are
+    /// discarded. Expired leases are kept as records without reserving their
+    /// address. Reclaimed addresses not taken by one of these stay with their
+    /// last holder until the reclaim probation passed. Returns the number of
+    /// restored pending offers.
+    pub fn restore(&self, state: PersistedState, now: SystemTime) -> usize {
+        let mut leases = self.leases.lock().unwrap();
+        let mut pools = self.pools.lock().unwrap();
+        let mut offers = self.offers.lock().unwrap();
+        let mut bindings = self.bindings.lock().unwrap();
+
+        for (key, lease) in state.leases {
+            if lease.remaining(now).is_none() {
+                leases.insert(key, lease);
+                continue;
+            }
+
+            if reserve(&mut pools, lease.ip_addr(), &key) {
+                bindings.insert(&key, &lease);
+                leases.insert(key, lease);
+            }
+        }
+
+        let mut restored = 0;
+        for offer in state.pending {
+            if offer.is_expired(now)
+                || offers.is_full()
+                || !reserve(&mut pools, offer.yiaddr(), offer.key())
+            {
+                continue;
+            }
+
+            if offers.restore(offer, now) {
+                restored += 1;
+            }
+        }
+
+        for reclaimed in state.reclaimed {
+            if let Some(pool) = pools
+                .iter_mut()
+                .find(|pool| pool.range().contains(&reclaimed.addr))
+            {
+                pool.restore_reclaimed(reclaimed.addr, &reclaimed.key, reclaimed.reclaimed_at);
+            }
+        }
+
+        restored
+    }
+
+    /// Withdraws the offer made to the client with `key` in the transaction
+    /// `xid`, after the client requested the offer of another server. The
+    /// address goes straight back to the pool, without the reclaim
+    /// probation, unless the client holds a lease for it or it is still
+    /// bound to it. Returns the address of the withdrawn offer.
+    pub fn release_lost_offer(&self, key: &str, xid: u32) -> Option<Ipv4Addr> {
+        let offer = self.offers.lock().unwrap().take_by_xid(key, xid)?;
+        self.discover_cache.lock().unwrap().remove(key);
+
+        let yiaddr = offer.yiaddr();
+        self.withdraw_offers(&[offer], &[TransactionStep::Request, TransactionStep::Lost]);
+        Some(yiaddr)
+    }
+
+    /// Hands `addr`, allocated to the client with `key` but never offered,
+    /// back to the pool.
+    pub fn roll_back_allocation(&self, key: &str, addr: Ipv4Addr) {
+        self.discover_cache.lock().unwrap().remove(key);
+
+        let mut pools = self.pools.lock().unwrap();
+        if let Some(pool) = pools
+            .iter_mut()
+            .find(|pool| pool.holder_of(&addr) == Some(key))
+        {
+            pool.release(&addr);
+        }
+    }
+
+    /// Puts `addr` on probation, see [`Pool::put_on_probation`]. Returns
+    /// `false` if the address is allocated or not part of any pool.
+    pub fn put_on_probation(&self, addr: Ipv4Addr) -> bool {
+        let mut pools = self.pools.lock().unwrap();
+        pools.iter_mut().any(|pool| pool.put_on_probation(addr))
+    }
+
+    /// Returns the addresses of expired offers to the pools, unless the
+    /// client holds a lease for the address in the meantime or the address
+    /// is still bound to it.
+    fn expire_offers(&self, now: SystemTime) {
+        let expired = self.offers.lock().unwrap().purge_expired(now);
+        if !expired.is_empty() {
+            self.withdraw_offers(&expired, &[TransactionStep::Expired]);
+        }
+    }
+
+    /// Returns the addresses of the withdrawn `offers` to the pools, see
+    /// [`ServerState::expire_offers`], and completes the transactions of
+    /// their clients with `steps`.
+    fn withdraw_offers(&self, offers: &[PendingOffer], steps: &[TransactionStep]) {
+        {
+            let leases = self.leases.lock().unwrap();
+            let bindings = self.bindings.lock().unwrap();
+            let mut pools = self.pools.lock().unwrap();
+
+            for offer in offers {
+                if leases
+                    .get(offer.key())
+                    .is_some_and(|l| l.ip_addr() == offer.yiaddr())
+                    || bindings.is_bound(offer.key(), &offer.yiaddr())
+                {
+                    continue;
+                }
+
+                for pool in pools.iter_mut() {
+                    pool.release(&offer.yiaddr());
+                }
+            }
+        }
+
+        for offer in offers {
+            if let Some(mut record) = self.take_transaction(offer.key()) {
+                for step in steps {
+                    record.push(*step);
+                }
+                self.complete_transaction(record);
+            }
+        }
+    }
+
+    /// Allocates an address from the first pool with free addresses. A
+    /// client with a reservation gets the reserved address, unless it still
+    /// holds a lease on another address or the reserved address is held by
+    /// another client.
+    fn allocate(&self, key: &str) -> Option<Ipv4Addr> {
+        // New leases would be lost on a restart, only the addresses already
+        // bound to the client are handed out
+        if self.config.require_persistence && self.persistence.is_degraded() {
+            let pools = self.pools.lock().unwrap();
+            return pools.iter().find_map(|pool| pool.address_of(key));
+        }
+
+        let now = self.clock.now_system();
+        let leases = self.leases.lock().unwrap();
+        let mut pools = self.pools.lock().unwrap();
+
+        let active = leases
+            .get(key)
+            .is_some_and(|lease| lease.remaining(now).is_some());
+
+        if !active {
+            if let Some(addr) = pools
+                .iter_mut()
+                .find_map(|pool| pool.allocate_reserved(key))
+            {
+                // Addresses of ended leases and stale offers are handed back
+                let previous: Vec<_> = pools
+                    .iter_mut()
+                    .flat_map(|pool| pool.release_others(key, addr))
+                    .collect();
+                drop(pools);
+
+                let mut bindings = self.bindings.lock().unwrap();
+                for addr in previous {
+                    bindings.remove(key, &addr);
+                }
+
+                ServerStats::incr(&self.stats.allocations);
+                return Some(addr);
+            }
+        }
+        drop(leases);
+
+        for pool in pools.iter_mut() {
+            if let Some(addr) = pool.allocate(key, now, self.config.reclaim_probation) {
+                ServerStats::incr(&self.stats.allocations);
+                return Some(addr);
+            }
+        }
+
+        None
+    }
+
+    /// Counts `event` in the churn of the pool containing `addr`.
+    fn record_churn(&self, pools: &[Pool], addr: Ipv4Addr, event: ChurnEvent, now: SystemTime) {
+        if let Some(pool) = pools.iter().find(|pool| pool.range().contains(&addr)) {
+            self.stats.history().record(pool.name(), event, now);
+        }
+    }
+}
+
+/// Reserves `addr` for the client with `key` in the pool containing it.
+fn reserve(pools: &mut [Pool], addr: Ipv4Addr, key: &str) -> bool {
+    pools
+        .iter_mut()
+        .find(|pool| pool.range().contains(&addr))
+        .is_some_and(|pool| pool.reserve(addr, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{
+        types::HardwareAddr, utils::LogCapture, CacheLimits, Clock, PersistedState, Server,
+        ServerStats, StepClock, TransientCache, TransitionError, DEFAULT_RECLAIM_GRACE,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn server() -> Server {
+        Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_discover_burst_allocates_once() {
+        let server = server();
+        let state = &server.service.state;
+        let start = std::time::Instant::now();
+
+        // Simulate a burst of 20 DHCPDISCOVERs, one every 50 ms
+        let offers: Vec<_> = (0..20)
+            .map(|i| {
+                state.offer_address("de:ad:be:ef:12:34", start + Duration::from_millis(i * 50))
+            })
+            .collect();
+
+        assert!(offers.iter().all(|o| *o == offers[0]));
+        assert_eq!(ServerStats::get(&state.stats.allocations), 1);
+        assert_eq!(ServerStats::get(&state.stats.discover_cache_misses), 1);
+        assert_eq!(ServerStats::get(&state.stats.discover_cache_hits), 19);
+    }
+
+    #[test]
+    fn test_discover_after_window_recomputes_offer() {
+        let server = server();
+        let state = &server.service.state;
+        let start = std::time::Instant::now();
+
+        let first = state.offer_address("de:ad:be:ef:12:34", start);
+        let second = state.offer_address("de:ad:be:ef:12:34", start + Duration::from_secs(3));
+
+        // The client keeps its address, but the allocator was consulted twice
+        assert_eq!(first, second);
+        assert_eq!(ServerStats::get(&state.stats.discover_cache_misses), 2);
+    }
+
+    #[test]
+    fn test_different_clients_are_not_coalesced() {
+        let server = server();
+        let state = &server.service.state;
+        let now = std::time::Instant::now();
+
+        let first = state.offer_address("de:ad:be:ef:12:34", now);
+        let second = state.offer_address("de:ad:be:ef:12:35", now);
+
+        assert_ne!(first, second);
+        assert_eq!(ServerStats::get(&state.stats.allocations), 2);
+    }
+
+    fn hardware_addr() -> HardwareAddr {
+        HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap()
+    }
+
+    fn state_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vulcan-{}-{}.json", name, std::process::id()))
+    }
+
+    fn server_with_state_file(path: &PathBuf) -> Server {
+        Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_state_file(path.clone())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pending_offer_survives_restart() {
+        let path = state_file("restart");
+        let key = "de:ad:be:ef:12:34";
+
+        // Offer an address and shut down before the DHCPREQUEST arrives
+        let server = server_with_state_file(&path);
+        let addr = server
+            .service
+            .state
+            .offer_address(key, std::time::Instant::now())
+            .unwrap();
+        server.service.state.record_offer(
+            key,
+            hardware_addr(),
+            0,
+            addr,
+            TIMEOUT,
+            SystemTime::now(),
+        );
+        server.flush().unwrap();
+        drop(server);
+
+        // The restarted server still reserves the address for the client
+        let server = server_with_state_file(&path);
+        let state = &server.service.state;
+
+        assert_eq!(
+            state.offer_address("de:ad:be:ef:12:35", std::time::Instant::now()),
+            Some(Ipv4Addr::new(192, 168, 1, 101))
+        );
+
+        let lease = state.commit_offer(key, addr, SystemTime::now()).unwrap();
+        assert_eq!(lease.ip_addr(), addr);
+
+        // The committed lease is persisted as well
+        server.flush().unwrap();
+        let persisted = PersistedState::load(&path).unwrap().unwrap();
+        assert!(persisted.leases.contains_key(key));
+        assert!(persisted.pending.is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_expired_pending_offer_is_discarded_on_restart() {
+        let server = server();
+        let key = "de:ad:be:ef:12:34";
+        let now = SystemTime::now();
+
+        let addr = server
+            .service
+            .state
+            .offer_address(key, std::time::Instant::now())
+            .unwrap();
+        server
+            .service
+            .state
+            .record_offer(key, hardware_addr(), 0, addr, TIMEOUT, now);
+        let persisted = server.service.state.snapshot(now);
+        assert_eq!(persisted.pending.len(), 1);
+
+        // Restart after the offer expired
+        let restarted = self::server();
+        let later = now + Duration::from_secs(61);
+
+        assert_eq!(restarted.service.state.restore(persisted, later), 0);
+        assert!(restarted
+            .service
+            .state
+            .commit_offer(key, addr, later)
+            .is_err());
+        assert_eq!(
+            restarted
+                .service
+                .state
+                .offer_address("de:ad:be:ef:12:35", std::time::Instant::now()),
+            Some(addr)
+        );
+    }
+
+    #[test]
+    fn test_reclaimed_address_survives_restart() {
+        let clock = StepClock::default();
+        let server = || {
+            Server::builder()
+                .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+                .with_allow_foreign_pools(true)
+                .with_lease_time(120)
+                .with_clock(clock.clone())
+                .build()
+                .unwrap()
+        };
+        let key = "de:ad:be:ef:12:34";
+
+        let before = server();
+        let state = &before.service.state;
+        let addr = state.offer_address(key, clock.now_instant()).unwrap();
+        state.record_offer(key, hardware_addr(), 0, addr, TIMEOUT, clock.now_system());
+        state.commit_offer(key, addr, clock.now_system()).unwrap();
+
+        clock.advance(Duration::from_secs(120) + DEFAULT_RECLAIM_GRACE);
+        assert_eq!(state.reclaim_expired(clock.now_system()), vec![addr]);
+
+        let persisted = state.snapshot(clock.now_system());
+        assert_eq!(persisted.reclaimed.len(), 1);
+        assert_eq!(persisted.reclaimed[0].addr, addr);
+        assert_eq!(persisted.reclaimed[0].key, key);
+
+        // After the restart, the address is still kept for its last holder
+        let after = server();
+        let state = &after.service.state;
+        state.restore(persisted, clock.now_system());
+
+        assert_eq!(
+            state.offer_address("de:ad:be:ef:12:35", clock.now_instant()),
+            Some(Ipv4Addr::new(192, 168, 1, 101))
+        );
+        assert_eq!(state.offer_address(key, clock.now_instant()), Some(addr));
+    }
+
+    #[test]
+    fn test_ended_leases_are_purged_after_retention() {
+        let clock = StepClock::default();
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_lease_time(120)
+            .with_lease_retention(Duration::from_secs(3600))
+            .with_clock(clock.clone())
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+
+        let mut addrs = Vec::new();
+        for key in ["released", "expired", "active"] {
+            let addr = state.offer_address(key, clock.now_instant()).unwrap();
+            state.record_offer(key, hardware_addr(), 0, addr, TIMEOUT, clock.now_system());
+            state.commit_offer(key, addr, clock.now_system()).unwrap();
+            addrs.push(addr);
+        }
+        assert!(state.release_lease("released", addrs[0], clock.now_system()));
+
+        // Ended leases are kept as records within the retention
+        clock.advance(Duration::from_secs(120) + DEFAULT_RECLAIM_GRACE);
+        assert!(state
+            .renew_lease("active", addrs[2], clock.now_system())
+            .is_some());
+        assert_eq!(state.reclaim_expired(clock.now_system()), vec![addrs[1]]);
+        assert_eq!(state.purge_ended_leases(clock.now_system()), 0);
+
+        clock.advance(Duration::from_secs(3600));
+        assert!(state
+            .renew_lease("active", addrs[2], clock.now_system())
+            .is_some());
+        assert_eq!(state.purge_ended_leases(clock.now_system()), 2);
+
+        let leases = state.leases.lock().unwrap();
+        assert!(!leases.contains_key("released"));
+        assert!(!leases.contains_key("expired"));
+        assert!(leases.contains_key("active"));
+    }
+
+    #[test]
+    fn test_expired_offer_returns_address_to_pool() {
+        let server = server();
+        let state = &server.service.state;
+        let now = SystemTime::now();
+
+        let addr = state.offer_address("a", std::time::Instant::now()).unwrap();
+        state.record_offer("a", hardware_addr(), 0, addr, TIMEOUT, now);
+
+        // Expired offers are purged when the next offer is recorded
+        let later = now + Duration::from_secs(61);
+        let other = Ipv4Addr::new(192, 168, 1, 101);
+        state.record_offer("b", hardware_addr(), 0, other, TIMEOUT, later);
+
+        assert!(!state.pools.lock().unwrap()[0].is_allocated(&addr));
+    }
+
+    #[test]
+    fn test_full_offer_cache_withdraws_oldest_offer() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_cache_limits(CacheLimits {
+                offers: 2,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let now = SystemTime::now();
+
+        let offered: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|key| {
+                let addr = state.offer_address(key, std::time::Instant::now()).unwrap();
+                state.record_offer(key, hardware_addr(), 0, addr, TIMEOUT, now);
+                addr
+            })
+            .collect();
+
+        // The offer to "a" was withdrawn and its address returned
+        assert!(!state.pools.lock().unwrap()[0].is_allocated(&offered[0]));
+        assert!(state.pools.lock().unwrap()[0].is_allocated(&offered[2]));
+        assert!(matches!(
+            state.commit_offer("a", offered[0], now),
+            Err(TransitionError::NoOffer(_))
+        ));
+        assert!(state.commit_offer("c", offered[2], now).is_ok());
+        assert_eq!(state.stats.cache_evictions.get(TransientCache::Offers), 1);
+    }
+
+    #[test]
+    fn test_defensive_mode_is_reversible() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_allow_foreign_pools(true)
+            .with_starvation_window(Duration::from_secs(10))
+            .with_starvation_threshold(10)
+            .with_defensive_mode(true)
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let start = std::time::Instant::now();
+
+        assert_eq!(state.offer_timeout(start), Duration::from_secs(60));
+
+        // Flood with random clients
+        for i in 0..20 {
+            state.observe_discover(&format!("random-{i}"), start);
+        }
+
+        assert!(state.under_starvation(start));
+        assert_eq!(state.offer_timeout(start), Duration::from_secs(5));
+        assert_eq!(ServerStats::get(&state.stats.starvation_alerts), 1);
+
+        // The flood subsides
+        let later = start + Duration::from_secs(10);
+        assert!(!state.under_starvation(later));
+        assert_eq!(state.offer_timeout(later), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_pool_lease_time_override() {
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_pool("guest".into(), "192.168.2.100-192.168.2.200".into())
+            .with_pool_lease_time("guest".into(), 600)
+            .with_lease_time(7200)
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+        let state = &server.service.state;
+        let now = SystemTime::now();
+
+        let default_addr = Ipv4Addr::new(192, 168, 1, 100);
+        let guest_addr = Ipv4Addr::new(192, 168, 2, 100);
+
+        for (key, addr) in [("a", default_addr), ("b", guest_addr)] {
+            assert!(state
+                .pools
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .any(|p| p.reserve(addr, key)));
+            state.record_offer(key, hardware_addr(), 0, addr, TIMEOUT, now);
+        }
+
+        let lease = state.commit_offer("a", default_addr, now).unwrap();
+        assert_eq!(lease.lease_time(), 7200);
+
+        let lease = state.commit_offer("b", guest_addr, now).unwrap();
+        assert_eq!(lease.lease_time(), 600);
+
+        // T1 and T2 are derived from the pool's lease time
+        let times = state.lease_times(guest_addr);
+        assert_eq!((times.renew_time, times.rebind_time), (300, 525));
+        assert_eq!(state.lease_times(default_addr).lease_time, 7200);
+
+        // Renewals keep the pool's lease time
+        let renewed = state.renew_lease("b", guest_addr, now).unwrap();
+        assert_eq!(renewed.lease_time(), 600);
+    }
+
+    #[test]
+    fn test_assign_hostname() {
+        let server = server();
+        let state = &server.service.state;
+        let now = SystemTime::now();
+
+        for (key, addr) in [
+            ("a", Ipv4Addr::new(192, 168, 1, 100)),
+            ("b", Ipv4Addr::new(192, 168, 1, 101)),
+        ] {
+            assert!(state
+                .pools
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .any(|p| p.reserve(addr, key)));
+            state.record_offer(key, hardware_addr(), 0, addr, TIMEOUT, now);
+            state.commit_offer(key, addr, now).unwrap();
+        }
+
+        state.assign_hostname("a", "Laptop\0", now);
+        state.assign_hostname("b", "LAPTOP", now);
+
+        let leases = state.leases.lock().unwrap();
+        assert_eq!(leases["a"].hostname().unwrap(), "laptop");
+        assert_eq!(leases["a"].raw_hostname().unwrap(), "Laptop\\u{0}");
+        assert_eq!(leases["b"].hostname().unwrap(), "laptop-2");
+        assert_eq!(leases["b"].raw_hostname().unwrap(), "LAPTOP");
+    }
+
+    #[test]
+    fn test_reload_checks_pool_ownership() {
+        // Only the loopback interface counts, which owns no address in the
+        // pool's subnet
+        let server = Server::builder()
+            .with_pool("default".into(), "192.168.1.100-192.168.1.200".into())
+            .with_interface("lo".into())
+            .with_allow_foreign_pools(true)
+            .build()
+            .unwrap();
+
+        let capture = LogCapture::default();
+        tracing::subscriber::with_default(capture.subscriber(tracing::Level::WARN), || {
+            server
+                .service
+                .state
+                .reload_reservations(Vec::new())
+                .unwrap();
+        });
+
+        assert!(capture.output().lines().any(|line| {
+            line.contains("no interface owns an address") && line.contains("\"default\"")
+        }));
+    }
+}
@@ -0,0 +1,573 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server::{history::UtilizationHistory, limits::TransientCache, strict::StrictViolation},
+    types::{options::DhcpMessageType, ParseErrorKind},
+};
+
+/// Upper bounds of the handler latency buckets.
+pub const LATENCY_BUCKETS: [Duration; 13] = [
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(10),
+];
+
+/// Number of [`DhcpMessageType`] variants.
+const MESSAGE_TYPES: usize = 12;
+
+/// Message types handled by the server, in the order they are exported.
+const HANDLED_TYPES: [DhcpMessageType; 6] = [
+    DhcpMessageType::Discover,
+    DhcpMessageType::Request,
+    DhcpMessageType::Decline,
+    DhcpMessageType::Release,
+    DhcpMessageType::Inform,
+    DhcpMessageType::LeaseQuery,
+];
+
+/// Runtime counters of the server. All counters except the gauges
+/// [`ServerStats::handlers_in_flight`] and
+/// [`ServerStats::persistence_degraded`] are monotonically increasing.
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    /// Number of addresses handed out by the pool allocator.
+    pub allocations: AtomicU64,
+
+    /// Number of DHCPDISCOVERs answered from the coalescing cache.
+    pub discover_cache_hits: AtomicU64,
+
+    /// Number of DHCPDISCOVERs which required a fresh offer.
+    pub discover_cache_misses: AtomicU64,
+
+    /// Number of detected starvation attacks.
+    pub starvation_alerts: AtomicU64,
+
+    /// Number of message handlers which panicked.
+    pub handler_panics: AtomicU64,
+
+    /// Number of authenticated messages dropped as replays.
+    pub replays_rejected: AtomicU64,
+
+    /// Number of authenticated messages dropped because their HMAC couldn't
+    /// be verified.
+    pub auth_failures: AtomicU64,
+
+    /// Number of DHCPDISCOVERs and DHCPREQUESTs dropped because 'chaddr' is
+    /// a broadcast, multicast or all-zero address.
+    pub invalid_chaddrs: AtomicU64,
+
+    /// Number of messages dropped because they were relayed more than
+    /// [`MAX_HOPS`](crate::MAX_HOPS) times.
+    pub excessive_hops: AtomicU64,
+
+    /// Number of DHCPLEASEQUERYs dropped because they weren't relayed by one
+    /// of the trusted relay agents, see
+    /// [`ServerBuilder::with_trusted_relays`](crate::ServerBuilder::with_trusted_relays).
+    pub untrusted_lease_queries: AtomicU64,
+
+    /// Number of renewals dropped because they weren't sent from 'ciaddr',
+    /// see [`ServerBuilder::with_renewal_source_check`](crate::ServerBuilder::with_renewal_source_check).
+    pub renewal_source_mismatches: AtomicU64,
+
+    /// Number of offers withdrawn because the client requested the offer of
+    /// another server.
+    pub lost_to_other_server: AtomicU64,
+
+    /// Number of messages dropped because they carry no DHCP message type
+    /// option (53). These are malformed, or BOOTP requests.
+    pub missing_message_types: AtomicU64,
+
+    /// Number of messages dropped because their DHCP message type is not
+    /// known to the server.
+    pub unknown_message_types: AtomicU64,
+
+    /// Number of message handlers currently running. This is a gauge, not a
+    /// counter.
+    pub handlers_in_flight: AtomicU64,
+
+    /// Number of messages which had to wait for a free handler slot, see
+    /// [`ServerBuilder::with_max_concurrent`](crate::ServerBuilder::with_max_concurrent).
+    pub handlers_deferred: AtomicU64,
+
+    /// Number of failed writes of the persisted state.
+    pub persist_failures: AtomicU64,
+
+    /// 1 while writing the state fails, 0 otherwise. This is a gauge, not a
+    /// counter.
+    pub persistence_degraded: AtomicU64,
+
+    /// Number of received messages which failed to parse, by kind of error.
+    pub parse_errors: ParseErrorCounters,
+
+    /// Number of messages dropped in strict mode, by violation.
+    pub strict_drops: StrictDropCounters,
+
+    /// Timeouts and latencies of the message handlers, by message type.
+    pub handlers: HandlerStats,
+
+    /// Number of entries evicted from the per-client caches because they
+    /// were full, by cache.
+    pub cache_evictions: CacheEvictionCounters,
+
+    /// Message counters of each interface the server listens on, keyed by
+    /// interface name.
+    interfaces: Mutex<BTreeMap<String, Arc<InterfaceStats>>>,
+
+    history: UtilizationHistory,
+}
+
+impl ServerStats {
+    pub(crate) fn with_history(history: UtilizationHistory) -> Self {
+        Self {
+            history,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the utilization history and lease churn of the pools.
+    pub fn history(&self) -> &UtilizationHistory {
+        &self.history
+    }
+
+    pub(crate) fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current value of `counter`.
+    pub fn get(counter: &AtomicU64) -> u64 {
+        counter.load(Ordering::Relaxed)
+    }
+
+    /// Returns the counters of the interface with `name`, creating them on
+    /// first use.
+    pub(crate) fn interface(&self, name: &str) -> Arc<InterfaceStats> {
+        let mut interfaces = self.interfaces.lock().unwrap();
+        interfaces.entry(name.to_string()).or_default().clone()
+    }
+
+    /// Returns the message counters of each interface, sorted by interface
+    /// name.
+    pub fn per_interface(&self) -> Vec<(String, InterfaceCounters)> {
+        let interfaces = self.interfaces.lock().unwrap();
+        interfaces
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.counters()))
+            .collect()
+    }
+
+    /// Returns the message counters summed up over all interfaces.
+    pub fn total(&self) -> InterfaceCounters {
+        self.per_interface()
+            .into_iter()
+            .fold(InterfaceCounters::default(), |total, (_, c)| {
+                InterfaceCounters {
+                    received: total.received + c.received,
+                    dropped: total.dropped + c.dropped,
+                    replies: total.replies + c.replies,
+                }
+            })
+    }
+
+    /// Renders all counters in the Prometheus text exposition format.
+    /// Message counters are labeled with the interface they belong to, the
+    /// pool history gauges with the pool and window they cover.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        let global = [
+            ("allocations", &self.allocations),
+            ("discover_cache_hits", &self.discover_cache_hits),
+            ("discover_cache_misses", &self.discover_cache_misses),
+            ("starvation_alerts", &self.starvation_alerts),
+            ("handler_panics", &self.handler_panics),
+            ("replays_rejected", &self.replays_rejected),
+            ("auth_failures", &self.auth_failures),
+            ("invalid_chaddrs", &self.invalid_chaddrs),
+            ("excessive_hops", &self.excessive_hops),
+            ("renewal_source_mismatches", &self.renewal_source_mismatches),
+            ("untrusted_lease_queries", &self.untrusted_lease_queries),
+            ("missing_message_types", &self.missing_message_types),
+            ("unknown_message_types", &self.unknown_message_types),
+            ("lost_to_other_server", &self.lost_to_other_server),
+            ("handlers_deferred", &self.handlers_deferred),
+            ("persist_failures", &self.persist_failures),
+        ];
+
+        for (name, counter) in global {
+            let _ = writeln!(out, "# TYPE vulcan_dhcpd_{name}_total counter");
+            let _ = writeln!(out, "vulcan_dhcpd_{name}_total {}", Self::get(counter));
+        }
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_handlers_in_flight gauge");
+        let _ = writeln!(
+            out,
+            "vulcan_dhcpd_handlers_in_flight {}",
+            Self::get(&self.handlers_in_flight)
+        );
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_persistence_degraded gauge");
+        let _ = writeln!(
+            out,
+            "vulcan_dhcpd_persistence_degraded {}",
+            Self::get(&self.persistence_degraded)
+        );
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_parse_errors_total counter");
+        for kind in ParseErrorKind::ALL {
+            let _ = writeln!(
+                out,
+                "vulcan_dhcpd_parse_errors_total{{kind=\"{kind}\"}} {}",
+                self.parse_errors.get(kind)
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_strict_drops_total counter");
+        for violation in StrictViolation::ALL {
+            let _ = writeln!(
+                out,
+                "vulcan_dhcpd_strict_drops_total{{reason=\"{violation}\"}} {}",
+                self.strict_drops.get(violation)
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_cache_evictions_total counter");
+        for cache in TransientCache::ALL {
+            let _ = writeln!(
+                out,
+                "vulcan_dhcpd_cache_evictions_total{{cache=\"{cache}\"}} {}",
+                self.cache_evictions.get(cache)
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_handler_timeouts_total counter");
+        for ty in &HANDLED_TYPES {
+            let _ = writeln!(
+                out,
+                "vulcan_dhcpd_handler_timeouts_total{{type=\"{ty}\"}} {}",
+                self.handlers.timeouts(ty)
+            );
+        }
+
+        let _ = writeln!(out, "# TYPE vulcan_dhcpd_handler_latency_seconds histogram");
+        for ty in &HANDLED_TYPES {
+            self.handlers.latency(ty).write_prometheus(&mut out, ty);
+        }
+
+        let per_interface = self.per_interface();
+        let counters: [(&str, InterfaceCounterFn); 3] = [
+            ("messages_received", |c| c.received),
+            ("messages_dropped", |c| c.dropped),
+            ("replies_sent", |c| c.replies),
+        ];
+
+        for (name, value) in counters {
+            let _ = writeln!(out, "# TYPE vulcan_dhcpd_{name}_total counter");
+
+            for (interface, c) in &per_interface {
+                let _ = writeln!(
+                    out,
+                    "vulcan_dhcpd_{name}_total{{interface=\"{interface}\"}} {}",
+                    value(c)
+                );
+            }
+        }
+
+        self.history.write_prometheus(&mut out);
+        out
+    }
+}
+
+/// Parse failure counters, one per [`ParseErrorKind`].
+#[derive(Debug, Default)]
+pub struct ParseErrorCounters {
+    counters: [AtomicU64; ParseErrorKind::ALL.len()],
+}
+
+impl ParseErrorCounters {
+    pub(crate) fn incr(&self, kind: ParseErrorKind) {
+        ServerStats::incr(&self.counters[kind as usize]);
+    }
+
+    /// Returns the number of parse failures of `kind`.
+    pub fn get(&self, kind: ParseErrorKind) -> u64 {
+        ServerStats::get(&self.counters[kind as usize])
+    }
+}
+
+/// Strict mode drop counters, one per [`StrictViolation`].
+#[derive(Debug, Default)]
+pub struct StrictDropCounters {
+    counters: [AtomicU64; StrictViolation::ALL.len()],
+}
+
+impl StrictDropCounters {
+    pub(crate) fn incr(&self, violation: StrictViolation) {
+        ServerStats::incr(&self.counters[violation as usize]);
+    }
+
+    /// Returns the number of messages dropped because of `violation`.
+    pub fn get(&self, violation: StrictViolation) -> u64 {
+        ServerStats::get(&self.counters[violation as usize])
+    }
+}
+
+/// Eviction counters, one per [`TransientCache`].
+#[derive(Debug, Default)]
+pub struct CacheEvictionCounters {
+    counters: [AtomicU64; TransientCache::ALL.len()],
+}
+
+impl CacheEvictionCounters {
+    /// Updates the counter of `cache` to the `evictions` counted by the
+    /// cache itself.
+    pub(crate) fn set(&self, cache: TransientCache, evictions: u64) {
+        self.counters[cache as usize].store(evictions, Ordering::Relaxed);
+    }
+
+    /// Returns the number of entries evicted from `cache`.
+    pub fn get(&self, cache: TransientCache) -> u64 {
+        ServerStats::get(&self.counters[cache as usize])
+    }
+}
+
+/// Timeout counters and latency histograms of the message handlers, one
+/// each per [`DhcpMessageType`].
+#[derive(Debug, Default)]
+pub struct HandlerStats {
+    timeouts: [AtomicU64; MESSAGE_TYPES],
+    latencies: [LatencyHistogram; MESSAGE_TYPES],
+}
+
+impl HandlerStats {
+    pub(crate) fn timed_out(&self, ty: &DhcpMessageType) {
+        ServerStats::incr(&self.timeouts[ty.clone() as usize]);
+    }
+
+    pub(crate) fn observe(&self, ty: &DhcpMessageType, latency: Duration) {
+        self.latencies[ty.clone() as usize].observe(latency);
+    }
+
+    /// Returns the number of handlers of messages of type `ty` which were
+    /// aborted because they exceeded the handler timeout.
+    pub fn timeouts(&self, ty: &DhcpMessageType) -> u64 {
+        ServerStats::get(&self.timeouts[ty.clone() as usize])
+    }
+
+    /// Returns the latencies of the handlers of messages of type `ty` which
+    /// finished in time.
+    pub fn latency(&self, ty: &DhcpMessageType) -> &LatencyHistogram {
+        &self.latencies[ty.clone() as usize]
+    }
+}
+
+/// Histogram of latencies, bucketed by [`LATENCY_BUCKETS`]. Latencies
+/// above the largest bucket are counted, but quantiles falling into them
+/// are reported as the largest bucket.
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    /// Observations per bucket, the last one counts the latencies above
+    /// all buckets.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub(crate) fn observe(&self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        ServerStats::incr(&self.buckets[bucket]);
+        self.sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the number of observed latencies.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(ServerStats::get).sum()
+    }
+
+    /// Returns the upper bound of the bucket holding the `q` quantile, for
+    /// example 0.99 for the 99th percentile. Returns [`None`] if nothing was
+    /// observed yet.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            seen += ServerStats::get(bucket);
+            if seen >= rank {
+                return Some(bound);
+            }
+        }
+
+        LATENCY_BUCKETS.last().copied()
+    }
+
+    pub fn p50(&self) -> Option<Duration> {
+        self.quantile(0.5)
+    }
+
+    pub fn p99(&self) -> Option<Duration> {
+        self.quantile(0.99)
+    }
+
+    fn write_prometheus(&self, out: &mut String, ty: &DhcpMessageType) {
+        let mut cumulative = 0;
+
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            cumulative += ServerStats::get(bucket);
+            let _ = writeln!(
+                out,
+                "vulcan_dhcpd_handler_latency_seconds_bucket{{type=\"{ty}\",le=\"{}\"}} {cumulative}",
+                bound.as_secs_f64()
+            );
+        }
+
+        let count = self.count();
+        let _ = writeln!(
+            out,
+            "vulcan_dhcpd_handler_latency_seconds_bucket{{type=\"{ty}\",le=\"+Inf\"}} {count}"
+        );
+        let _ = writeln!(
+            out,
+            "vulcan_dhcpd_handler_latency_seconds_sum{{type=\"{ty}\"}} {}",
+            ServerStats::get(&self.sum_micros) as f64 / 1e6
+        );
+        let _ = writeln!(
+            out,
+            "vulcan_dhcpd_handler_latency_seconds_count{{type=\"{ty}\"}} {count}"
+        );
+    }
+}
+
+/// Message counters of a single interface.
+#[derive(Debug, Default)]
+pub struct InterfaceStats {
+    /// Number of received messages.
+    pub received: AtomicU64,
+
+    /// Number of received messages which were malformed or not meant for a
+    /// server.
+    pub dropped: AtomicU64,
+
+    /// Number of replies handed to the front-end for sending.
+    pub replies: AtomicU64,
+}
+
+impl InterfaceStats {
+    pub fn counters(&self) -> InterfaceCounters {
+        InterfaceCounters {
+            received: ServerStats::get(&self.received),
+            dropped: ServerStats::get(&self.dropped),
+            replies: ServerStats::get(&self.replies),
+        }
+    }
+}
+
+/// Point-in-time copy of [`InterfaceStats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceCounters {
+    pub received: u64,
+    pub dropped: u64,
+    pub replies: u64,
+}
+
+/// Reads a single counter of [`InterfaceCounters`].
+type InterfaceCounterFn = fn(&InterfaceCounters) -> u64;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll_up_and_prometheus() {
+        let stats = ServerStats::default();
+
+        ServerStats::incr(&stats.interface("eth0").received);
+        ServerStats::incr(&stats.interface("eth0").replies);
+        ServerStats::incr(&stats.interface("eth1").received);
+        ServerStats::incr(&stats.interface("eth1").dropped);
+
+        assert_eq!(
+            stats.total(),
+            InterfaceCounters {
+                received: 2,
+                dropped: 1,
+                replies: 1
+            }
+        );
+
+        let out = stats.to_prometheus();
+        assert!(out.contains("vulcan_dhcpd_messages_received_total{interface=\"eth0\"} 1\n"));
+        assert!(out.contains("vulcan_dhcpd_messages_dropped_total{interface=\"eth1\"} 1\n"));
+        assert!(out.contains("vulcan_dhcpd_allocations_total 0\n"));
+        assert!(out.contains("vulcan_dhcpd_handlers_in_flight 0\n"));
+        assert!(out.contains("vulcan_dhcpd_persistence_degraded 0\n"));
+        assert!(out.contains("vulcan_dhcpd_parse_errors_total{kind=\"bad_cookie\"} 0\n"));
+        assert!(out.contains("vulcan_dhcpd_strict_drops_total{reason=\"untrusted_relay\"} 0\n"));
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let stats = ServerStats::default();
+        let latency = stats.handlers.latency(&DhcpMessageType::Discover);
+        assert_eq!(latency.p50(), None);
+
+        for _ in 0..98 {
+            stats
+                .handlers
+                .observe(&DhcpMessageType::Discover, Duration::from_micros(300));
+        }
+        stats
+            .handlers
+            .observe(&DhcpMessageType::Discover, Duration::from_millis(40));
+        stats
+            .handlers
+            .observe(&DhcpMessageType::Discover, Duration::from_secs(30));
+
+        assert_eq!(latency.count(), 100);
+        assert_eq!(latency.p50(), Some(Duration::from_micros(500)));
+        assert_eq!(latency.p99(), Some(Duration::from_millis(50)));
+        assert_eq!(latency.quantile(1.0), Some(Duration::from_secs(10)));
+        assert_eq!(stats.handlers.latency(&DhcpMessageType::Request).count(), 0);
+
+        let out = stats.to_prometheus();
+        assert!(out.contains(
+            "vulcan_dhcpd_handler_latency_seconds_bucket{type=\"DHCPDISCOVER\",le=\"0.05\"} 99\n"
+        ));
+        assert!(out.contains(
+            "vulcan_dhcpd_handler_latency_seconds_bucket{type=\"DHCPDISCOVER\",le=\"10\"} 99\n"
+        ));
+        assert!(out.contains(
+            "vulcan_dhcpd_handler_latency_seconds_bucket{type=\"DHCPDISCOVER\",le=\"+Inf\"} 100\n"
+        ));
+        assert!(out.contains("vulcan_dhcpd_handler_timeouts_total{type=\"DHCPREQUEST\"} 0\n"));
+    }
+}
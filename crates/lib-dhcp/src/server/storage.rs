@@ -1,166 +1,216 @@
 use std::{
     collections::HashMap,
-    fmt::Display,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Duration,
+    fs,
+    io::ErrorKind,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use async_trait::async_trait;
-use serde_json;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::{
-    self,
-    fs::File,
-    io::{AsyncWriteExt, BufWriter},
-    task::JoinError,
-    time,
-};
+use tokio::task::JoinError;
 
 use crate::{
+    server::offers::PendingOffer,
     types::{HardwareAddr, Lease},
-    IntoLease, Storage, StorageError,
+    StorageError,
 };
 
-pub struct ServerStorage {
-    leases: Arc<Mutex<HashMap<String, Lease>>>,
-
-    leases_file_path: PathBuf,
-    flush_interval: u64,
-    changed: bool,
-}
-
-#[derive(Debug, Hash)]
-pub struct StorageKey {
-    hardware_addr: HardwareAddr,
-    hostname: Option<String>,
+/// Version of the persisted state format written by this build. Version 2
+/// added the transaction metadata of leases, version 3 keys clients whose
+/// identifier carries a hardware address by that address, version 4 added
+/// the reclaimed addresses.
+pub const STATE_VERSION: u32 = 4;
+
+/// Schema of the persisted server state. Committed leases and offers which
+/// are still waiting for the matching DHCPREQUEST are kept in separate
+/// sections.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PersistedState {
+    /// Format version, see [`STATE_VERSION`]. Files written before the
+    /// version was recorded are version 1.
+    #[serde(default = "initial_state_version")]
+    pub version: u32,
+
+    pub leases: HashMap<String, Lease>,
+
+    #[serde(default)]
+    pub pending: Vec<PendingOffer>,
+
+    #[serde(default)]
+    pub reclaimed: Vec<ReclaimedAddr>,
 }
 
-impl Display for StorageKey {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.hostname {
-            Some(_) => write!(
-                f,
-                "{}_{}",
-                self.hostname.as_ref().unwrap(),
-                self.hardware_addr
-            ),
-            None => write!(f, "{}", self.hardware_addr),
+impl Default for PersistedState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            leases: HashMap::new(),
+            pending: Vec::new(),
+            reclaimed: Vec::new(),
         }
     }
 }
 
-#[derive(Debug, Error)]
-pub enum ServerStorageError {
-    #[error("io error: {0}")]
-    IoError(#[from] std::io::Error),
-
-    #[error("join error: {0}")]
-    JoinError(#[from] JoinError),
-
-    #[error("failed to deserialize/serialize from/into JSON: {0}")]
-    JsonError(#[from] serde_json::Error),
+/// Address taken back from the client with `key` after its lease expired.
+/// Until the reclaim probation passed, only that client gets it back.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReclaimedAddr {
+    pub addr: Ipv4Addr,
+    pub key: String,
+    pub reclaimed_at: SystemTime,
+}
 
-    #[error("storage error: {0}")]
-    StorageError(#[from] StorageError),
+fn initial_state_version() -> u32 {
+    1
 }
 
-#[async_trait]
-impl Storage for ServerStorage {
-    type Error = ServerStorageError;
-    type Key = StorageKey;
+impl PersistedState {
+    /// Loads the state from the file at `path`. Returns [`None`] if the file
+    /// doesn't exist (yet).
+    pub fn load(path: &Path) -> Result<Option<Self>, ServerStorageError> {
+        let b = match fs::read_to_string(path) {
+            Ok(b) => b,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut state: Self = serde_json::from_str(&b)?;
+
+        // Older formats are forward compatible, newer ones may carry data
+        // we would silently drop on the next save
+        if state.version > STATE_VERSION {
+            return Err(ServerStorageError::UnsupportedVersion(state.version));
+        }
 
-    async fn retrieve_lease(&self, key: Self::Key) -> Option<Lease> {
-        let key = key.to_string();
-        let leases = self.leases.lock().unwrap();
+        if state.version < 3 {
+            state.migrate_client_keys();
+        }
 
-        // leases.get(&key)
-        None
+        Ok(Some(state))
     }
 
-    async fn store_lease<L: IntoLease>(
-        &mut self,
-        key: Self::Key,
-        lease: L,
-    ) -> Result<(), Self::Error> {
-        // self.changed = true;
-
-        let lease = lease.into_lease();
-        let key = key.to_string();
+    /// Rewrites the keys of clients identified by a client identifier
+    /// carrying a hardware address, which versions before 3 stored as
+    /// `<type>-<identifier>`, to that address. A lease already keyed by the
+    /// address is kept over the migrated one.
+    fn migrate_client_keys(&mut self) {
+        let mut leases = HashMap::with_capacity(self.leases.len());
+        let mut migrated = Vec::new();
+
+        for (key, lease) in self.leases.drain() {
+            match hardware_addr_key(&key) {
+                Some(key) => migrated.push((key, lease)),
+                None => {
+                    leases.insert(key, lease);
+                }
+            }
+        }
 
-        let mut leases = self.leases.lock().unwrap();
-        leases.insert(key, lease);
+        for (key, lease) in migrated {
+            leases.entry(key).or_insert(lease);
+        }
+        self.leases = leases;
 
-        Ok(())
+        for offer in &mut self.pending {
+            if let Some(key) = hardware_addr_key(offer.key()) {
+                offer.set_key(key);
+            }
+        }
     }
 
-    async fn run_flush(&self) -> Result<(), Self::Error> {
-        let leases_file_path = self.leases_file_path.clone();
-        let leases = self.leases.clone();
+    /// Writes the state to the file at `path`. The state is written to a
+    /// temporary file first, which then replaces the previous state, so a
+    /// crash while writing doesn't corrupt it.
+    pub fn save(&self, path: &Path) -> Result<(), ServerStorageError> {
+        let output = serde_json::to_string_pretty(self)?;
 
-        let interval = self.flush_interval;
-        let changed = self.changed;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-        tokio::spawn(
-            async move { handle_flush(interval, changed, leases_file_path, leases).await },
-        );
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, output)?;
+        fs::rename(tmp_path, path)?;
 
         Ok(())
     }
+}
 
-    fn len(&self) -> usize {
-        let guard = self.leases.lock().unwrap();
-        guard.len()
+/// Returns the hardware address key of the version 2 client identifier key
+/// `key`, if the identifier has a hardware type.
+fn hardware_addr_key(key: &str) -> Option<String> {
+    let (ty, identifier) = key.split_once('-')?;
+    if ty.len() != 2 || u8::from_str_radix(ty, 16).ok()? == 0 {
+        return None;
     }
+
+    let bytes = identifier
+        .split(':')
+        .map(|b| u8::from_str_radix(b, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    HardwareAddr::try_from(bytes.as_slice())
+        .ok()
+        .map(|addr| addr.to_string())
 }
 
-impl ServerStorage {
-    pub fn new(leases_file_path: PathBuf, flush_interval: u64) -> Self {
-        Self {
-            leases: Arc::new(Mutex::new(HashMap::new())),
-            changed: false,
-            leases_file_path,
-            flush_interval,
-        }
+/// Destination the state of the server is written to. By default, the
+/// state is written to the state file, see
+/// [`ServerBuilder::with_state_store`](crate::server::ServerBuilder::with_state_store)
+/// for others.
+pub trait StateStore: Send + Sync {
+    fn save(&self, state: &PersistedState) -> Result<(), ServerStorageError>;
+}
+
+/// Writes the state to a file, see [`PersistedState::save`].
+pub(crate) struct FileStore(pub PathBuf);
+
+impl StateStore for FileStore {
+    fn save(&self, state: &PersistedState) -> Result<(), ServerStorageError> {
+        state.save(&self.0)
     }
 }
 
-async fn handle_flush(
-    flush_interval: u64,
-    changed: bool,
-    leases_file_path: PathBuf,
-    leases: Arc<Mutex<HashMap<String, Lease>>>,
-) -> Result<(), ServerStorageError> {
-    let mut interval = time::interval(Duration::from_secs(flush_interval));
-    interval.tick().await;
-
-    loop {
-        // Await next interval tick
-        interval.tick().await;
-
-        // Check if there are any new leases added since we last flushed.
-        // If not, we skip flushing and wait for the next interval tick.
-        if !changed {
-            continue;
-        }
+#[derive(Debug, Error)]
+pub enum ServerStorageError {
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
 
-        // Open the leases file
-        // FIXME (Techassi): This will overwrite the file everytime. We
-        // should diff here to only write the changes.
-        let leases_file = File::create(leases_file_path.clone()).await?;
+    #[error("join error: {0}")]
+    JoinError(#[from] JoinError),
 
-        // Create a buffered writer on the file to write lease by lease
-        let mut writer = BufWriter::new(leases_file);
+    #[error("failed to deserialize/serialize from/into JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
 
-        // Serialize list of leases into JSON string
-        let mut output = String::new();
-        {
-            let guard = leases.lock().unwrap();
-            output = serde_json::to_string_pretty(&*guard)?;
-        }
+    #[error("storage error: {0}")]
+    StorageError(#[from] StorageError),
+
+    #[error("unsupported state format version {0}")]
+    UnsupportedVersion(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_version() {
+        let dir = std::env::temp_dir().join(format!("vulcan-version-{}", std::process::id()));
+        let path = dir.join("state.json");
+
+        // Files written before the version was recorded are still readable
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, r#"{"leases":{}}"#).unwrap();
+        assert_eq!(PersistedState::load(&path).unwrap().unwrap().version, 1);
+
+        fs::write(&path, r#"{"version":99,"leases":{}}"#).unwrap();
+        assert!(matches!(
+            PersistedState::load(&path),
+            Err(ServerStorageError::UnsupportedVersion(99))
+        ));
 
-        // Write JSON string to file using the buffered writer
-        writer.write(output.as_bytes()).await?;
-        writer.flush().await?
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,159 @@
+use std::{fmt::Display, net::Ipv4Addr};
+
+use crate::types::{HardwareTypeError, HeaderError, Message, MessageError, MessageViewError};
+
+/// Largest 'secs' value accepted in strict mode. Clients retrying for more
+/// than an hour are more likely crafted than real.
+pub const STRICT_MAX_SECS: u16 = 3600;
+
+/// A deviation from clean RFC 2131 DHCP which is tolerated by default, but
+/// causes the message to be dropped in strict mode. See
+/// [`ServerBuilder::with_strict`](crate::server::ServerBuilder::with_strict).
+///
+/// Messages without magic cookie or with a hardware type other than
+/// Ethernet fail to parse and are dropped in either mode, strict mode
+/// additionally counts them by their violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StrictViolation {
+    /// The message has no magic cookie, it is a BOOTP message.
+    MissingMagicCookie,
+
+    /// The client didn't send a client identifier (option 61).
+    MissingClientIdentifier,
+
+    /// The hardware type is not Ethernet.
+    UnsupportedHardwareType,
+
+    /// 'giaddr' is set, but the message wasn't received from a trusted
+    /// relay agent.
+    UntrustedRelay,
+
+    /// 'secs' exceeds [`STRICT_MAX_SECS`].
+    ExcessiveSecs,
+}
+
+impl StrictViolation {
+    pub const ALL: [StrictViolation; 5] = [
+        Self::MissingMagicCookie,
+        Self::MissingClientIdentifier,
+        Self::UnsupportedHardwareType,
+        Self::UntrustedRelay,
+        Self::ExcessiveSecs,
+    ];
+
+    /// Returns the name of the violation as used in metric labels and logs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MissingMagicCookie => "missing_magic_cookie",
+            Self::MissingClientIdentifier => "missing_client_identifier",
+            Self::UnsupportedHardwareType => "unsupported_hardware_type",
+            Self::UntrustedRelay => "untrusted_relay",
+            Self::ExcessiveSecs => "excessive_secs",
+        }
+    }
+
+    /// Returns the first violation of `message`, received from `source`.
+    /// Relayed messages are only accepted from `trusted_relays`.
+    pub(crate) fn of(
+        message: &Message,
+        source: Ipv4Addr,
+        trusted_relays: &[Ipv4Addr],
+    ) -> Option<Self> {
+        if message.vendor.is_some() {
+            return Some(Self::MissingMagicCookie);
+        }
+
+        if message.get_client_identifier().is_none() {
+            return Some(Self::MissingClientIdentifier);
+        }
+
+        if !message.giaddr.is_unspecified() && !trusted_relays.contains(&source) {
+            return Some(Self::UntrustedRelay);
+        }
+
+        if message.header.secs > STRICT_MAX_SECS {
+            return Some(Self::ExcessiveSecs);
+        }
+
+        None
+    }
+
+    /// Returns the violation behind a message which failed the zero-copy
+    /// routing checks.
+    pub(crate) fn of_view_error(err: &MessageViewError) -> Option<Self> {
+        match err {
+            MessageViewError::NoMagicCookie => Some(Self::MissingMagicCookie),
+            MessageViewError::BufTooShort => None,
+        }
+    }
+
+    /// Returns the violation behind a message which failed to parse.
+    pub(crate) fn of_parse_error(err: &MessageError) -> Option<Self> {
+        match err {
+            MessageError::HeaderError(HeaderError::HardwareTypeError(
+                HardwareTypeError::InvalidType(_),
+            )) => Some(Self::UnsupportedHardwareType),
+            _ => None,
+        }
+    }
+}
+
+impl Display for StrictViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{options::ClientIdentifier, OptionData, OptionTag};
+
+    fn message() -> Message {
+        let mut message = Message::new_with_xid(1);
+        message.set_hardware_address("02:00:00:00:00:01".to_string().try_into().unwrap());
+        message
+            .add_option_parts(
+                OptionTag::ClientIdentifier,
+                OptionData::ClientIdentifier(ClientIdentifier::new(1, vec![2, 0, 0, 0, 0, 1])),
+            )
+            .unwrap();
+        message
+    }
+
+    #[test]
+    fn test_violations() {
+        let source = Ipv4Addr::new(10, 0, 0, 1);
+        let relay = Ipv4Addr::new(10, 0, 0, 254);
+        assert_eq!(StrictViolation::of(&message(), source, &[]), None);
+
+        let mut bootp = message();
+        bootp.vendor = Some(Vec::new());
+        assert_eq!(
+            StrictViolation::of(&bootp, source, &[]),
+            Some(StrictViolation::MissingMagicCookie)
+        );
+
+        let mut anonymous = Message::new_with_xid(1);
+        anonymous.set_hardware_address("02:00:00:00:00:01".to_string().try_into().unwrap());
+        assert_eq!(
+            StrictViolation::of(&anonymous, source, &[]),
+            Some(StrictViolation::MissingClientIdentifier)
+        );
+
+        let mut relayed = message();
+        relayed.giaddr = Ipv4Addr::new(10, 1, 0, 1);
+        assert_eq!(
+            StrictViolation::of(&relayed, source, &[relay]),
+            Some(StrictViolation::UntrustedRelay)
+        );
+        assert_eq!(StrictViolation::of(&relayed, relay, &[relay]), None);
+
+        let mut slow = message();
+        slow.header.secs = STRICT_MAX_SECS + 1;
+        assert_eq!(
+            StrictViolation::of(&slow, source, &[]),
+            Some(StrictViolation::ExcessiveSecs)
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    future::Future,
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore, TryAcquireError},
+    task::{JoinError, JoinHandle, JoinSet},
+    time::timeout,
+};
+use tracing::{debug, error, warn};
+
+use crate::{server::stats::ServerStats, types::options::DhcpMessageType, DAEMON_TARGET};
+
+/// Default number of handlers which may run at the same time.
+pub const DEFAULT_MAX_IN_FLIGHT_HANDLERS: usize = 1024;
+
+/// Default time a handler may take before it is aborted.
+pub const DEFAULT_HANDLER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time outstanding handlers get to finish during shutdown.
+pub const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
+/// Describes the request a handler task was spawned for. Used to attribute
+/// panics.
+#[derive(Debug, Clone)]
+pub(crate) struct TaskInfo {
+    pub peer: SocketAddr,
+    pub message_type: Option<DhcpMessageType>,
+}
+
+/// Spawns the handler tasks of the server. At most `limit` handlers run at
+/// the same time, further spawns wait for a free slot, which stops the
+/// listeners from receiving more messages in the meantime.
+///
+/// Finished tasks are collected by a reaper task, which logs and counts
+/// handlers which panicked.
+pub(crate) struct TaskManager {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    stats: Arc<ServerStats>,
+    reaper_tx: mpsc::UnboundedSender<(JoinHandle<()>, TaskInfo)>,
+}
+
+impl TaskManager {
+    /// Creates a new task manager and spawns its reaper task. This must be
+    /// called from within a tokio runtime.
+    pub fn new(limit: usize, stats: Arc<ServerStats>) -> Self {
+        let (reaper_tx, reaper_rx) = mpsc::unbounded_channel();
+        tokio::spawn(reap(reaper_rx, stats.clone()));
+
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            stats,
+            reaper_tx,
+        }
+    }
+
+    /// Spawns `task` as soon as fewer than `limit` handlers are running.
+    /// Waiting for a free slot is counted and logged.
+    pub async fn spawn<F>(&self, info: TaskInfo, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // The semaphore is never closed
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(TryAcquireError::Closed) => return,
+            Err(TryAcquireError::NoPermits) => {
+                ServerStats::incr(&self.stats.handlers_deferred);
+                debug!(
+                    target: DAEMON_TARGET,
+                    peer = %info.peer,
+                    limit = self.limit,
+                    "handler limit reached, deferring message"
+                );
+
+                match self.permits.clone().acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => return,
+                }
+            }
+        };
+
+        let guard = InFlightGuard::new(permit, self.stats.clone());
+        let handle = tokio::spawn(async move {
+            // Dropped when the task finishes or unwinds
+            let _guard = guard;
+            task.await
+        });
+
+        let _ = self.reaper_tx.send((handle, info));
+    }
+
+    /// Returns the number of currently running handlers.
+    pub fn in_flight(&self) -> usize {
+        self.limit - self.permits.available_permits()
+    }
+
+    /// Waits until all running handlers finished, at most for `deadline`.
+    /// Returns the number of handlers still running afterwards.
+    pub async fn shutdown(&self, deadline: Duration) -> usize {
+        let all = self.permits.acquire_many(self.limit as u32);
+
+        match timeout(deadline, all).await {
+            Ok(_) => 0,
+            Err(_) => {
+                let remaining = self.in_flight();
+                warn!(
+                    target: DAEMON_TARGET,
+                    remaining,
+                    "handlers didn't finish before the shutdown deadline"
+                );
+                remaining
+            }
+        }
+    }
+}
+
+/// Keeps the in-flight gauge up to date for the lifetime of a handler task.
+struct InFlightGuard {
+    _permit: OwnedSemaphorePermit,
+    stats: Arc<ServerStats>,
+}
+
+impl InFlightGuard {
+    fn new(permit: OwnedSemaphorePermit, stats: Arc<ServerStats>) -> Self {
+        stats.handlers_in_flight.fetch_add(1, Ordering::Relaxed);
+        Self {
+            _permit: permit,
+            stats,
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.stats
+            .handlers_in_flight
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Awaits the handles of all spawned tasks and reports the ones which
+/// panicked. Runs until all senders are gone.
+async fn reap(
+    mut rx: mpsc::UnboundedReceiver<(JoinHandle<()>, TaskInfo)>,
+    stats: Arc<ServerStats>,
+) {
+    let mut watchers: JoinSet<(TaskInfo, Result<(), JoinError>)> = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            received = rx.recv() => match received {
+                Some((handle, info)) => {
+                    watchers.spawn(async move { (info, handle.await) });
+                }
+                None => break,
+            },
+            Some(Ok((info, result))) = watchers.join_next() => report(&info, result, &stats),
+        }
+    }
+
+    while let Some(joined) = watchers.join_next().await {
+        if let Ok((info, result)) = joined {
+            report(&info, result, &stats);
+        }
+    }
+}
+
+fn report(info: &TaskInfo, result: Result<(), JoinError>, stats: &ServerStats) {
+    let err = match result {
+        Err(err) if err.is_panic() => err,
+        _ => return,
+    };
+
+    ServerStats::incr(&stats.handler_panics);
+
+    let message_type = info
+        .message_type
+        .as_ref()
+        .map_or_else(|| String::from("unknown"), |ty| ty.to_string());
+
+    error!(
+        target: DAEMON_TARGET,
+        peer = %info.peer,
+        message_type,
+        %err,
+        "handler panicked"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn info() -> TaskInfo {
+        TaskInfo {
+            peer: SocketAddr::from((Ipv4Addr::LOCALHOST, 68)),
+            message_type: Some(DhcpMessageType::Discover),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spawn_is_bounded() {
+        let stats = Arc::new(ServerStats::default());
+        let tasks = Arc::new(TaskManager::new(2, stats.clone()));
+        let release = Arc::new(Semaphore::new(0));
+
+        for _ in 0..2 {
+            let release = release.clone();
+            tasks
+                .spawn(info(), async move {
+                    let _ = release.acquire().await;
+                })
+                .await;
+        }
+
+        assert_eq!(tasks.in_flight(), 2);
+        assert_eq!(ServerStats::get(&stats.handlers_in_flight), 2);
+
+        // The third spawn waits for a free slot
+        let third = {
+            let tasks = tasks.clone();
+            tokio::spawn(async move { tasks.spawn(info(), async {}).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!third.is_finished());
+        assert_eq!(ServerStats::get(&stats.handlers_deferred), 1);
+
+        release.add_permits(2);
+        third.await.unwrap();
+
+        assert_eq!(tasks.shutdown(Duration::from_secs(1)).await, 0);
+        assert_eq!(ServerStats::get(&stats.handlers_in_flight), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_deadline() {
+        let stats = Arc::new(ServerStats::default());
+        let tasks = TaskManager::new(4, stats);
+
+        tasks.spawn(info(), std::future::pending()).await;
+        assert_eq!(tasks.shutdown(Duration::from_millis(10)).await, 1);
+    }
+}
@@ -0,0 +1,374 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{
+    options::{encode_vendor_sub_options, VendorIdentifying, VendorSubOption},
+    DhcpOption, Message, OptionData, OptionTag,
+};
+
+/// Which form of vendor options a client gets if it supports both the
+/// vendor specific information option (43) and the vendor-identifying
+/// vendor-specific information option (125).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VendorPrecedence {
+    /// Only send option 125.
+    #[default]
+    PreferIdentifying,
+
+    /// Only send option 43.
+    PreferLegacy,
+
+    /// Send both options.
+    Both,
+}
+
+/// Vendor options of a single vendor, served as the vendor specific
+/// information option (43), the vendor-identifying vendor-specific
+/// information option (125) or both, depending on what the client supports.
+///
+/// A client matches the profile if its vendor class identifier (option 60)
+/// starts with `class`, or if it sends the vendor-identifying vendor class
+/// option (124) for `enterprise_number`. Clients sending option 124 or
+/// requesting option 125 support option 125, clients matching by option 60
+/// support option 43. If a client supports both forms, `precedence` decides.
+/// Forms without any sub-options aren't sent.
+///
+/// Nested sub-options come last, so that the profile can be serialized as
+/// TOML.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VendorProfile {
+    /// IANA enterprise number of the vendor.
+    pub enterprise_number: u32,
+
+    /// Prefix of the vendor class identifier (option 60) of matching
+    /// clients.
+    #[serde(default)]
+    pub class: Option<String>,
+
+    #[serde(default)]
+    pub precedence: VendorPrecedence,
+
+    /// Sub-options sent in option 43.
+    #[serde(default)]
+    pub sub_options: Vec<VendorSubOption>,
+
+    /// Sub-options sent in option 125, the ones of option 43 if unset. This
+    /// allows migrating devices from option 43 without duplicating the
+    /// configuration.
+    #[serde(default)]
+    pub identifying_sub_options: Option<Vec<VendorSubOption>>,
+}
+
+impl VendorProfile {
+    pub fn new(enterprise_number: u32) -> Self {
+        Self {
+            enterprise_number,
+            class: None,
+            precedence: VendorPrecedence::default(),
+            sub_options: Vec::new(),
+            identifying_sub_options: None,
+        }
+    }
+
+    pub fn with_class(mut self, class: String) -> Self {
+        self.class = Some(class);
+        self
+    }
+
+    pub fn with_sub_option(mut self, code: u8, data: Vec<u8>) -> Self {
+        self.sub_options.push(VendorSubOption::new(code, data));
+        self
+    }
+
+    pub fn with_identifying_sub_options(mut self, sub_options: Vec<VendorSubOption>) -> Self {
+        self.identifying_sub_options = Some(sub_options);
+        self
+    }
+
+    /// Sets which form a client supporting both gets. Defaults to
+    /// [`VendorPrecedence::PreferIdentifying`].
+    pub fn with_precedence(mut self, precedence: VendorPrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Returns the encoded sub-options of option 43.
+    fn legacy_data(&self) -> Vec<u8> {
+        encode_vendor_sub_options(&self.sub_options)
+    }
+
+    /// Returns the encoded sub-options of option 125.
+    fn identifying_data(&self) -> Vec<u8> {
+        encode_vendor_sub_options(
+            self.identifying_sub_options
+                .as_ref()
+                .unwrap_or(&self.sub_options),
+        )
+    }
+
+    /// Returns if the encoded sub-options fit into options 43 and 125,
+    /// which carry the enterprise number and data length in addition.
+    pub(crate) fn fits(&self) -> bool {
+        self.legacy_data().len() <= u8::MAX as usize
+            && self.identifying_data().len() + 5 <= u8::MAX as usize
+    }
+
+    fn matches_class(&self, message: &Message) -> bool {
+        match (&self.class, message.get_class_identifier()) {
+            (Some(prefix), Some(class)) => class.as_str().starts_with(prefix.as_str()),
+            _ => false,
+        }
+    }
+
+    fn matches_enterprise(&self, message: &Message) -> bool {
+        message
+            .get_vendor_identifying_vendor_class()
+            .is_some_and(|class| class.get(self.enterprise_number).is_some())
+    }
+}
+
+/// Selects the vendor options of the first profile `message` matches, see
+/// [`VendorProfile`].
+pub(crate) fn select_vendor_options(
+    profiles: &[VendorProfile],
+    message: &Message,
+) -> Vec<DhcpOption> {
+    let mut options = Vec::new();
+
+    let Some((profile, legacy, identifying)) = profiles.iter().find_map(|profile| {
+        let legacy = profile.matches_class(message);
+        let enterprise = profile.matches_enterprise(message);
+
+        let requested = message
+            .get_parameter_request_list()
+            .is_some_and(|list| list.contains(&OptionTag::VendorIdentifyingVendorSpecific));
+
+        (legacy || enterprise).then_some((profile, legacy, enterprise || requested))
+    }) else {
+        return options;
+    };
+
+    let (legacy, identifying) = match profile.precedence {
+        VendorPrecedence::PreferIdentifying => (legacy && !identifying, identifying),
+        VendorPrecedence::PreferLegacy => (legacy, identifying && !legacy),
+        VendorPrecedence::Both => (legacy, identifying),
+    };
+
+    // Profiles without sub-options for a form send nothing in that form,
+    // an empty option 43 is invalid and an empty option 125 useless
+    let legacy_data = profile.legacy_data();
+    if legacy && !legacy_data.is_empty() {
+        options.push(DhcpOption::new(
+            OptionTag::VendorSpecificInformation,
+            OptionData::VendorSpecificInformation(legacy_data),
+        ));
+    }
+
+    let identifying_data = profile.identifying_data();
+    if identifying && !identifying_data.is_empty() {
+        options.push(DhcpOption::new(
+            OptionTag::VendorIdentifyingVendorSpecific,
+            OptionData::VendorIdentifyingVendorSpecific(VendorIdentifying::new(vec![(
+                profile.enterprise_number,
+                identifying_data,
+            )])),
+        ));
+    }
+
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::options::{decode_vendor_sub_options, ClassIdentifier, ParameterRequestList};
+
+    const ENTERPRISE: u32 = 3561;
+
+    fn profile() -> VendorProfile {
+        VendorProfile::new(ENTERPRISE)
+            .with_class(String::from("acme-phone"))
+            .with_sub_option(1, b"http://prov".to_vec())
+    }
+
+    fn message(class: Option<&str>, enterprise: Option<u32>, requested: Vec<OptionTag>) -> Message {
+        let mut message = Message::new_with_xid(0xcafe);
+
+        if let Some(class) = class {
+            message
+                .add_option_parts(
+                    OptionTag::ClassIdentifier,
+                    OptionData::ClassIdentifier(ClassIdentifier::new(class.to_string())),
+                )
+                .unwrap();
+        }
+
+        if let Some(enterprise) = enterprise {
+            message
+                .add_option_parts(
+                    OptionTag::VendorIdentifyingVendorClass,
+                    OptionData::VendorIdentifyingVendorClass(VendorIdentifying::new(vec![(
+                        enterprise,
+                        b"acme".to_vec(),
+                    )])),
+                )
+                .unwrap();
+        }
+
+        message
+            .add_option_parts(
+                OptionTag::ParameterRequestList,
+                OptionData::ParameterRequestList(ParameterRequestList::new(requested)),
+            )
+            .unwrap();
+
+        message
+    }
+
+    fn tags(options: &[DhcpOption]) -> Vec<OptionTag> {
+        options.iter().map(|o| o.header().tag.clone()).collect()
+    }
+
+    #[test]
+    fn test_class_only_client() {
+        let request = message(
+            Some("acme-phone-v2"),
+            None,
+            vec![OptionTag::VendorSpecificInformation],
+        );
+
+        let options = select_vendor_options(&[profile()], &request);
+        assert_eq!(options.len(), 1);
+        assert_eq!(
+            options[0].data(),
+            &OptionData::VendorSpecificInformation(b"\x01\x0bhttp://prov".to_vec())
+        );
+
+        // Clients decode the sub-options of the profile
+        let OptionData::VendorSpecificInformation(data) = options[0].data() else {
+            unreachable!()
+        };
+        assert_eq!(
+            decode_vendor_sub_options(data).unwrap(),
+            profile().sub_options
+        );
+
+        // Other vendor classes don't match
+        let request = message(
+            Some("other"),
+            None,
+            vec![OptionTag::VendorSpecificInformation],
+        );
+        assert!(select_vendor_options(&[profile()], &request).is_empty());
+    }
+
+    #[test]
+    fn test_identifying_client() {
+        let request = message(None, Some(ENTERPRISE), Vec::new());
+
+        let options = select_vendor_options(&[profile()], &request);
+        assert_eq!(
+            tags(&options),
+            vec![OptionTag::VendorIdentifyingVendorSpecific]
+        );
+        assert_eq!(
+            options[0].data(),
+            &OptionData::VendorIdentifyingVendorSpecific(VendorIdentifying::new(vec![(
+                ENTERPRISE,
+                b"\x01\x0bhttp://prov".to_vec()
+            )]))
+        );
+
+        // Other enterprises don't match
+        let request = message(None, Some(9), Vec::new());
+        assert!(select_vendor_options(&[profile()], &request).is_empty());
+    }
+
+    #[test]
+    fn test_client_supporting_both() {
+        let request = message(
+            Some("acme-phone"),
+            Some(ENTERPRISE),
+            vec![OptionTag::VendorSpecificInformation],
+        );
+
+        let options = select_vendor_options(&[profile()], &request);
+        assert_eq!(
+            tags(&options),
+            vec![OptionTag::VendorIdentifyingVendorSpecific]
+        );
+
+        let legacy = profile().with_precedence(VendorPrecedence::PreferLegacy);
+        let options = select_vendor_options(&[legacy], &request);
+        assert_eq!(tags(&options), vec![OptionTag::VendorSpecificInformation]);
+
+        let both = profile().with_precedence(VendorPrecedence::Both);
+        let options = select_vendor_options(&[both], &request);
+        assert_eq!(
+            tags(&options),
+            vec![
+                OptionTag::VendorSpecificInformation,
+                OptionTag::VendorIdentifyingVendorSpecific
+            ]
+        );
+
+        // Requesting option 125 also counts as support for it
+        let request = message(
+            Some("acme-phone"),
+            None,
+            vec![OptionTag::VendorIdentifyingVendorSpecific],
+        );
+        let options = select_vendor_options(&[profile()], &request);
+        assert_eq!(
+            tags(&options),
+            vec![OptionTag::VendorIdentifyingVendorSpecific]
+        );
+    }
+
+    #[test]
+    fn test_identifying_sub_options() {
+        let profile = profile().with_identifying_sub_options(vec![VendorSubOption {
+            code: 2,
+            data: vec![0xff],
+        }]);
+        assert!(profile.fits());
+
+        let request = message(None, Some(ENTERPRISE), Vec::new());
+        let options = select_vendor_options(&[profile], &request);
+        assert_eq!(
+            options[0].data(),
+            &OptionData::VendorIdentifyingVendorSpecific(VendorIdentifying::new(vec![(
+                ENTERPRISE,
+                vec![2, 1, 0xff]
+            )]))
+        );
+
+        let profile = VendorProfile::new(ENTERPRISE).with_sub_option(1, vec![0; 250]);
+        assert!(!profile.fits());
+    }
+
+    #[test]
+    fn test_empty_sub_options_send_nothing() {
+        let request = message(
+            Some("acme-phone"),
+            Some(ENTERPRISE),
+            vec![OptionTag::VendorSpecificInformation],
+        );
+
+        let empty = VendorProfile::new(ENTERPRISE)
+            .with_class(String::from("acme-phone"))
+            .with_precedence(VendorPrecedence::Both);
+        assert!(select_vendor_options(&[empty.clone()], &request).is_empty());
+
+        // Only option 125 has sub-options
+        let identifying = empty.with_identifying_sub_options(vec![VendorSubOption {
+            code: 2,
+            data: vec![0xff],
+        }]);
+        let options = select_vendor_options(&[identifying], &request);
+        assert_eq!(
+            tags(&options),
+            vec![OptionTag::VendorIdentifyingVendorSpecific]
+        );
+    }
+}
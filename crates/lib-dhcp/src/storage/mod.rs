@@ -9,19 +9,25 @@ use crate::types::Lease;
 pub enum StorageError {
     /// This error indicates that the storage provider failed to retrieve the
     /// requested lease.
-    #[error("failed to retrieve DHCP lease")]
+    #[error("{code} failed to retrieve DHCP lease", code = self.code())]
     RetrieveError,
 
     /// This error indicates that the storage provider failed to save the
     /// provided lease.
-    #[error("failed to store DHCP lease")]
+    #[error("{code} failed to store DHCP lease", code = self.code())]
     StoreError,
 
     /// This indicates some other unknown error occured.
-    #[error("storage error: {0}")]
+    #[error("{code} storage error: {0}", code = self.code())]
     Unknown(String),
 }
 
+error_codes!(StorageError {
+    RetrieveError => "VULCAN-D101",
+    StoreError => "VULCAN-D102",
+    Unknown => "VULCAN-D103",
+});
+
 #[async_trait]
 pub trait Storage {
     type Error: Display + std::error::Error + From<StorageError>;
@@ -0,0 +1,253 @@
+//! Canonical log line per completed DHCP transaction. Server and client
+//! accumulate a [`TransactionRecord`] while the messages of a transaction
+//! are exchanged and emit it as a single event under
+//! [`TRANSACTION_TARGET`] once it completed, similar to the access log of a
+//! web server. The fields are always emitted in the same order, missing
+//! values are rendered as `-`:
+//!
+//! ```text
+//! ts=1700000000 interface=eth0 flow=discover>offer>request>ack client=02:00:00:00:00:01 hostname=laptop addr=192.168.1.100 lease=3600 pool=default relay=- duration_ms=12
+//! ```
+use std::{
+    fmt::Display,
+    net::Ipv4Addr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tracing::info;
+
+use crate::TRANSACTION_TARGET;
+
+/// A message or event which is part of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TransactionStep {
+    Discover,
+    Offer,
+    Request,
+    Ack,
+    Nak,
+
+    /// The offer expired without a DHCPREQUEST.
+    Expired,
+
+    /// The client requested the offer of another server.
+    Lost,
+}
+
+impl TransactionStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Discover => "discover",
+            Self::Offer => "offer",
+            Self::Request => "request",
+            Self::Ack => "ack",
+            Self::Nak => "nak",
+            Self::Expired => "expired",
+            Self::Lost => "lost",
+        }
+    }
+}
+
+/// Summary of a single DHCP transaction, like a DORA exchange or a renewal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TransactionRecord {
+    /// Time the transaction completed.
+    pub timestamp: SystemTime,
+    pub interface: Option<String>,
+    pub flow: Vec<TransactionStep>,
+
+    /// Key of the client, its hardware address unless it sent a client
+    /// identifier.
+    pub client: String,
+    pub hostname: Option<String>,
+    pub addr: Option<Ipv4Addr>,
+    pub lease_time: Option<u32>,
+    pub pool: Option<String>,
+
+    /// Relay agent address ('giaddr'), followed by the agent circuit ID if
+    /// the relay sent one.
+    pub relay: Option<String>,
+
+    /// Time between the first message of the transaction and its
+    /// completion.
+    pub duration: Duration,
+
+    started: Instant,
+}
+
+impl TransactionRecord {
+    /// Starts recording a transaction of the client with `client` at
+    /// `started`.
+    pub fn new(client: impl Into<String>, started: Instant) -> Self {
+        Self {
+            timestamp: UNIX_EPOCH,
+            interface: None,
+            flow: Vec::new(),
+            client: client.into(),
+            hostname: None,
+            addr: None,
+            lease_time: None,
+            pool: None,
+            relay: None,
+            duration: Duration::ZERO,
+            started,
+        }
+    }
+
+    pub fn push(&mut self, step: TransactionStep) {
+        self.flow.push(step);
+    }
+
+    /// Returns the steps of the transaction, like
+    /// `discover>offer>request>ack`.
+    pub fn flow(&self) -> String {
+        self.flow
+            .iter()
+            .map(TransactionStep::as_str)
+            .collect::<Vec<_>>()
+            .join(">")
+    }
+
+    /// Marks the transaction as completed at `now` and `at`.
+    pub fn complete(&mut self, now: SystemTime, at: Instant) {
+        self.timestamp = now;
+        self.duration = at.saturating_duration_since(self.started);
+    }
+
+    /// Emits the record as a single info event. The fields match the
+    /// canonical line the record is displayed as.
+    pub fn emit(&self) {
+        let [ts, interface, flow, client, hostname, addr, lease, pool, relay, duration_ms] =
+            self.fields().map(|(_, value)| value);
+
+        info!(
+            target: TRANSACTION_TARGET,
+            %ts,
+            %interface,
+            %flow,
+            %client,
+            %hostname,
+            %addr,
+            %lease,
+            %pool,
+            %relay,
+            %duration_ms,
+            "transaction completed"
+        );
+    }
+
+    fn fields(&self) -> [(&'static str, String); 10] {
+        fn or_dash<T: Display>(value: Option<T>) -> String {
+            value.map_or_else(|| String::from("-"), |value| value.to_string())
+        }
+
+        let ts = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        [
+            ("ts", ts.to_string()),
+            ("interface", or_dash(self.interface.as_ref())),
+            ("flow", self.flow()),
+            ("client", self.client.clone()),
+            ("hostname", or_dash(self.hostname.as_ref())),
+            ("addr", or_dash(self.addr)),
+            ("lease", or_dash(self.lease_time)),
+            ("pool", or_dash(self.pool.as_ref())),
+            ("relay", or_dash(self.relay.as_ref())),
+            ("duration_ms", self.duration.as_millis().to_string()),
+        ]
+    }
+}
+
+/// Renders the record as canonical log line.
+impl Display for TransactionRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, value)) in self.fields().iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{name}={value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats the relay agent `giaddr` and its agent circuit ID for
+/// [`TransactionRecord::relay`]. Returns [`None`] for messages which weren't
+/// relayed.
+pub(crate) fn relay_info(giaddr: Ipv4Addr, circuit_id: Option<&[u8]>) -> Option<String> {
+    if giaddr.is_unspecified() {
+        return None;
+    }
+
+    match circuit_id {
+        Some(id) => {
+            let hex: String = id.iter().map(|b| format!("{b:02x}")).collect();
+            Some(format!("{giaddr}/{hex}"))
+        }
+        None => Some(giaddr.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(flow: &[TransactionStep]) -> TransactionRecord {
+        let started = Instant::now();
+        let mut record = TransactionRecord::new("02:00:00:00:00:01", started);
+        flow.iter().for_each(|step| record.push(*step));
+        record.complete(
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            started + Duration::from_millis(12),
+        );
+        record
+    }
+
+    #[test]
+    fn test_render_flows() {
+        use TransactionStep::*;
+
+        let mut dora = record(&[Discover, Offer, Request, Ack]);
+        dora.interface = Some("eth0".into());
+        dora.hostname = Some("laptop".into());
+        dora.addr = Some(Ipv4Addr::new(192, 168, 1, 100));
+        dora.lease_time = Some(3600);
+        dora.pool = Some("default".into());
+        assert_eq!(
+            dora.to_string(),
+            "ts=1700000000 interface=eth0 flow=discover>offer>request>ack \
+             client=02:00:00:00:00:01 hostname=laptop addr=192.168.1.100 lease=3600 \
+             pool=default relay=- duration_ms=12"
+        );
+
+        let mut renewal = record(&[Request, Nak]);
+        renewal.relay = relay_info(Ipv4Addr::new(10, 1, 0, 1), Some(&[0x0a, 0x2f]));
+        assert_eq!(
+            renewal.to_string(),
+            "ts=1700000000 interface=- flow=request>nak client=02:00:00:00:00:01 hostname=- \
+             addr=- lease=- pool=- relay=10.1.0.1/0a2f duration_ms=12"
+        );
+
+        let abandoned = record(&[Discover, Offer, Expired]);
+        assert_eq!(abandoned.flow(), "discover>offer>expired");
+        assert_eq!(relay_info(Ipv4Addr::UNSPECIFIED, None), None);
+    }
+
+    #[test]
+    fn test_emit_matches_render() {
+        let capture = crate::utils::LogCapture::default();
+        let record = record(&[TransactionStep::Request, TransactionStep::Ack]);
+
+        tracing::subscriber::with_default(capture.subscriber(tracing::Level::INFO), || {
+            record.emit()
+        });
+
+        let output = capture.output();
+        assert!(output.contains(" vulcan::transaction: transaction completed "));
+        assert!(output.contains(&record.to_string()));
+    }
+}
@@ -0,0 +1,93 @@
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use binbuf::prelude::*;
+use thiserror::Error;
+use tokio::net::UdpSocket;
+
+use crate::types::{Message, MessageError};
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Message error: {0}")]
+    MessageError(#[from] MessageError),
+}
+
+/// A [`Transport`] puts DHCP messages on the wire. Both the client and the
+/// server send messages through this trait, which allows tests to replace
+/// the socket, see [`RecordingTransport`].
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Sends `message` to `addr`.
+    async fn send(&self, message: Message, addr: SocketAddr) -> Result<(), TransportError>;
+}
+
+#[async_trait]
+impl Transport for UdpSocket {
+    async fn send(&self, message: Message, addr: SocketAddr) -> Result<(), TransportError> {
+        let mut buf = WriteBuffer::new();
+        message.write_be(&mut buf)?;
+
+        self.send_to(buf.bytes(), addr).await?;
+        Ok(())
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+pub use recording::RecordingTransport;
+
+#[cfg(any(test, feature = "testing"))]
+mod recording {
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+
+    use crate::{
+        transport::{Transport, TransportError},
+        types::Message,
+    };
+
+    /// Test-only [`Transport`] which doesn't send anything, but records every
+    /// message together with its destination. Clones share the recorded
+    /// messages, so a clone can be handed to the code under test while the
+    /// test keeps the other one for inspection.
+    #[derive(Debug, Clone, Default)]
+    pub struct RecordingTransport {
+        sent: Arc<Mutex<Vec<(Message, SocketAddr)>>>,
+    }
+
+    impl RecordingTransport {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Returns the number of recorded messages.
+        pub fn len(&self) -> usize {
+            self.sent.lock().unwrap().len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Removes and returns all recorded messages in the order they were
+        /// sent.
+        pub fn take(&self) -> Vec<(Message, SocketAddr)> {
+            std::mem::take(&mut *self.sent.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl Transport for RecordingTransport {
+        async fn send(&self, message: Message, addr: SocketAddr) -> Result<(), TransportError> {
+            self.sent.lock().unwrap().push((message, addr));
+            Ok(())
+        }
+    }
+}
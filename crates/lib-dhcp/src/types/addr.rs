@@ -6,8 +6,13 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ParseHardwareAddrError {
-    #[error("Invalid byte: {0}")]
-    InvalidByte(#[from] ParseIntError),
+    /// The octet at the zero-based `position` isn't a hexadecimal byte.
+    #[error("Invalid byte '{byte}' at position {position}: {source}")]
+    InvalidByte {
+        position: usize,
+        byte: String,
+        source: ParseIntError,
+    },
 
     #[error("Invalid separator, expected ':'")]
     InvalidSeparator,
@@ -16,7 +21,7 @@ pub enum ParseHardwareAddrError {
     InvalidLength(usize),
 }
 
-#[derive(Debug, Clone, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct HardwareAddr {
     padding: Vec<u8>,
     addr: Vec<u8>,
@@ -24,7 +29,8 @@ pub struct HardwareAddr {
 
 impl Display for HardwareAddr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}, Padding: {:?}", self.addr, self.padding)
+        let addr: Vec<String> = self.addr.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(f, "{}", addr.join(":"))
     }
 }
 
@@ -71,8 +77,16 @@ impl TryFrom<String> for HardwareAddr {
 
         let mut addr: Vec<u8> = Vec::new();
 
-        for byte in bytes {
-            addr.push(u8::from_str_radix(byte, 16)?);
+        for (position, byte) in bytes.into_iter().enumerate() {
+            let byte = u8::from_str_radix(byte, 16).map_err(|source| {
+                ParseHardwareAddrError::InvalidByte {
+                    position,
+                    byte: byte.to_string(),
+                    source,
+                }
+            })?;
+
+            addr.push(byte);
         }
 
         Ok(Self {
@@ -90,6 +104,30 @@ impl TryFrom<&String> for HardwareAddr {
     }
 }
 
+impl TryFrom<&[u8]> for HardwareAddr {
+    type Error = ParseHardwareAddrError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() > 16 {
+            return Err(ParseHardwareAddrError::InvalidLength(bytes.len()));
+        }
+
+        Ok(Self {
+            padding: vec![0; 16 - bytes.len()],
+            addr: bytes.to_vec(),
+        })
+    }
+}
+
+impl From<[u8; 6]> for HardwareAddr {
+    fn from(bytes: [u8; 6]) -> Self {
+        Self {
+            padding: vec![0; 10],
+            addr: bytes.to_vec(),
+        }
+    }
+}
+
 impl HardwareAddr {
     pub fn read<E: Endianness>(buf: &mut ReadBuffer, hlen: u8) -> Result<Self, BufferError> {
         // The client hardware address can only be 16 bytes long at max
@@ -119,6 +157,40 @@ impl HardwareAddr {
     pub fn as_bytes(&self) -> Vec<u8> {
         self.addr.to_owned()
     }
+
+    /// Returns whether all bits of the address are set, like in
+    /// `ff:ff:ff:ff:ff:ff`.
+    pub fn is_broadcast(&self) -> bool {
+        !self.addr.is_empty() && self.addr.iter().all(|b| *b == 0xff)
+    }
+
+    /// Returns whether the group bit, the least significant bit of the first
+    /// octet, is set. This includes the broadcast address.
+    pub fn is_multicast(&self) -> bool {
+        self.addr.first().is_some_and(|b| b & 0x01 != 0)
+    }
+
+    /// Returns whether all bits of the address are cleared.
+    pub fn is_zero(&self) -> bool {
+        self.addr.iter().all(|b| *b == 0)
+    }
+
+    /// Returns whether the address can identify a single client. Broadcast,
+    /// multicast and all-zero addresses can't.
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast() && !self.is_zero()
+    }
+
+    /// Returns this address cut off after `len` octets. Shorter addresses
+    /// are returned unchanged.
+    pub fn truncated(&self, len: usize) -> Self {
+        let len = len.min(self.addr.len());
+
+        Self {
+            padding: vec![0; 16 - len],
+            addr: self.addr[..len].to_vec(),
+        }
+    }
 }
 
 #[test]
@@ -132,3 +204,50 @@ fn test_hardware_address_from_string() {
         Err(err) => panic!("{}", err),
     };
 }
+
+#[test]
+fn test_hardware_address_from_array() {
+    let addr = HardwareAddr::from([0xde, 0xad, 0xbe, 0xef, 0x12, 0x34]);
+
+    assert_eq!(
+        addr,
+        HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap()
+    );
+    assert_eq!(addr.len(), 6);
+}
+
+#[test]
+fn test_hardware_address_invalid_byte_position() {
+    match HardwareAddr::try_from(String::from("de:ad:be:zz:12:34")) {
+        Err(err @ ParseHardwareAddrError::InvalidByte { position: 3, .. }) => {
+            assert!(err.to_string().contains("'zz' at position 3"));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_hardware_address_kinds() {
+    let addr = |s: &str| HardwareAddr::try_from(String::from(s)).unwrap();
+
+    let broadcast = addr("ff:ff:ff:ff:ff:ff");
+    assert!(broadcast.is_broadcast());
+    assert!(broadcast.is_multicast());
+    assert!(!broadcast.is_unicast());
+
+    let multicast = addr("01:00:5e:00:00:fb");
+    assert!(!multicast.is_broadcast());
+    assert!(multicast.is_multicast());
+    assert!(!multicast.is_unicast());
+
+    let zero = addr("00:00:00:00:00:00");
+    assert!(zero.is_zero());
+    assert!(!zero.is_unicast());
+    assert!(HardwareAddr::default().is_zero());
+
+    let unicast = addr("02:00:5e:00:00:fb");
+    assert!(!unicast.is_broadcast());
+    assert!(!unicast.is_multicast());
+    assert!(!unicast.is_zero());
+    assert!(unicast.is_unicast());
+}
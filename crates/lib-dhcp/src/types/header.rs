@@ -18,7 +18,7 @@ pub enum HeaderError {
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Header {
     /// Packet op code / message type (1 for BOOTREQUEST and 2 for BOOTREPLY).
     pub opcode: OpCode,
@@ -45,11 +45,12 @@ pub struct Header {
     pub flags: u16,
 }
 
+// Not derived, 'hlen' has to match the default hardware type
 impl Default for Header {
     fn default() -> Self {
         Self {
-            opcode: OpCode::BootRequest,
-            htype: HardwareType::Ethernet,
+            opcode: OpCode::default(),
+            htype: HardwareType::default(),
             hlen: constants::HARDWARE_ADDR_LEN_ETHERNET,
             hops: 0,
             xid: 0,
@@ -63,12 +64,15 @@ impl Readable for Header {
     type Error = HeaderError;
 
     fn read<E: Endianness>(buf: &mut ReadBuffer) -> Result<Self, Self::Error> {
-        let opcode = OpCode::read::<E>(buf)?;
-        let htype = HardwareType::read::<E>(buf)?;
-        let [hlen, hops] = u8::read_multi::<E, 2>(buf)?;
-        let xid = u32::read::<E>(buf)?;
-        let secs = u16::read::<E>(buf)?;
-        let flags = u16::read::<E>(buf)?;
+        read_fields!(buf, E =>
+            opcode: OpCode,
+            htype: HardwareType,
+            hlen: u8,
+            hops: u8,
+            xid: u32,
+            secs: u16,
+            flags: u16,
+        );
 
         Ok(Self {
             opcode,
@@ -86,32 +90,32 @@ impl Writeable for Header {
     type Error = HeaderError;
 
     fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
-        let n = bytes_written! {
-            self.opcode.write::<E>(buf)?;
-            self.htype.write::<E>(buf)?;
-            self.hlen.write::<E>(buf)?;
-            self.hops.write::<E>(buf)?;
-            self.xid.write::<E>(buf)?;
-            self.secs.write::<E>(buf)?;
-            self.flags.write::<E>(buf)?
-        };
+        let n = write_fields!(buf, E =>
+            self.opcode,
+            self.htype,
+            self.hlen,
+            self.hops,
+            self.xid,
+            self.secs,
+            self.flags,
+        );
 
         Ok(n)
     }
 }
 
 impl Header {
+    /// Creates a default header with a random transaction ID.
+    #[deprecated(note = "use `Header::new_with_xid(rand::random())` instead")]
     pub fn new() -> Self {
-        let mut header = Self::default();
-        header.xid = rand::random();
-
-        header
+        Self::new_with_xid(rand::random())
     }
 
+    /// Creates a default header with the transaction ID `xid`.
     pub fn new_with_xid(xid: u32) -> Self {
-        let mut header = Self::default();
-        header.xid = xid;
-
-        header
+        Self {
+            xid,
+            ..Default::default()
+        }
     }
 }
@@ -12,9 +12,14 @@ pub enum HardwareTypeError {
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum HardwareType {
+    #[default]
     Ethernet,
+
+    /// IP over InfiniBand (RFC 4390). 'chaddr' is left empty, clients
+    /// identify themselves with a client identifier.
+    InfiniBand,
 }
 
 impl TryFrom<u8> for HardwareType {
@@ -23,6 +28,7 @@ impl TryFrom<u8> for HardwareType {
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             1 => Ok(Self::Ethernet),
+            32 => Ok(Self::InfiniBand),
             _ => Err(HardwareTypeError::InvalidType(value)),
         }
     }
@@ -32,6 +38,7 @@ impl From<HardwareType> for u8 {
     fn from(value: HardwareType) -> Self {
         match value {
             HardwareType::Ethernet => 1,
+            HardwareType::InfiniBand => 32,
         }
     }
 }
@@ -40,6 +47,17 @@ impl From<&HardwareType> for u8 {
     fn from(value: &HardwareType) -> Self {
         match value {
             HardwareType::Ethernet => 1,
+            HardwareType::InfiniBand => 32,
+        }
+    }
+}
+
+impl HardwareType {
+    /// Returns the length of hardware addresses of this type ('hlen').
+    pub fn addr_len(&self) -> usize {
+        match self {
+            HardwareType::Ethernet => 6,
+            HardwareType::InfiniBand => 0,
         }
     }
 }
@@ -48,6 +66,7 @@ impl Display for HardwareType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             HardwareType::Ethernet => write!(f, "Ethernet (1)"),
+            HardwareType::InfiniBand => write!(f, "InfiniBand (32)"),
         }
     }
 }
@@ -1,14 +1,311 @@
-use std::{net::Ipv4Addr, time::Instant};
+use std::{
+    fmt::Display,
+    net::Ipv4Addr,
+    time::{Duration, SystemTime},
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::types::HardwareAddr;
+use crate::types::{options::DhcpMessageType, HardwareAddr};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Lease {
     hardware_addr: HardwareAddr,
-    // FIXME (Techassi): I guess we should switch to chrono
-    // leased_until: Instant,
+    leased_until: SystemTime,
+
+    /// Sanitized hostname, unique among the leases of the server.
+    hostname: Option<String>,
+
+    /// Hostname as sent by the client, with non-printable characters
+    /// escaped.
+    #[serde(default)]
+    raw_hostname: Option<String>,
     ip_addr: Ipv4Addr,
     lease_time: u32,
+
+    /// Type of the last client message which touched this lease.
+    #[serde(default)]
+    last_message_type: Option<DhcpMessageType>,
+
+    /// Time the last client message touching this lease was received.
+    #[serde(default)]
+    last_seen: Option<SystemTime>,
+
+    /// Number of times the lease was extended by the client.
+    #[serde(default)]
+    renew_count: u32,
+
+    /// Agent circuit ID of the relay the client was last seen behind.
+    #[serde(default)]
+    circuit_id: Option<Vec<u8>>,
+}
+
+impl Display for Lease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.display_at(SystemTime::now()).fmt(f)
+    }
+}
+
+impl Lease {
+    /// Create a new [`Lease`] which starts now and expires after
+    /// `lease_time` seconds.
+    pub fn new(
+        hardware_addr: HardwareAddr,
+        ip_addr: Ipv4Addr,
+        lease_time: u32,
+        hostname: Option<String>,
+    ) -> Self {
+        Self::new_at(
+            hardware_addr,
+            ip_addr,
+            lease_time,
+            hostname,
+            SystemTime::now(),
+        )
+    }
+
+    /// Create a new [`Lease`] which starts at `now` and expires after
+    /// `lease_time` seconds.
+    pub fn new_at(
+        hardware_addr: HardwareAddr,
+        ip_addr: Ipv4Addr,
+        lease_time: u32,
+        hostname: Option<String>,
+        now: SystemTime,
+    ) -> Self {
+        Self {
+            leased_until: now + Duration::from_secs(lease_time as u64),
+            hardware_addr,
+            hostname,
+            raw_hostname: None,
+            ip_addr,
+            lease_time,
+            last_message_type: None,
+            last_seen: None,
+            renew_count: 0,
+            circuit_id: None,
+        }
+    }
+
+    pub fn hardware_addr(&self) -> &HardwareAddr {
+        &self.hardware_addr
+    }
+
+    pub fn ip_addr(&self) -> Ipv4Addr {
+        self.ip_addr
+    }
+
+    pub fn hostname(&self) -> Option<&String> {
+        self.hostname.as_ref()
+    }
+
+    pub fn raw_hostname(&self) -> Option<&String> {
+        self.raw_hostname.as_ref()
+    }
+
+    /// Sets the hostname sent by the client, `raw`, and the `sanitized`
+    /// form the lease is known by.
+    pub fn set_hostname(&mut self, raw: String, sanitized: Option<String>) {
+        self.raw_hostname = Some(raw);
+        self.hostname = sanitized;
+    }
+
+    pub fn lease_time(&self) -> u32 {
+        self.lease_time
+    }
+
+    pub fn leased_until(&self) -> SystemTime {
+        self.leased_until
+    }
+
+    pub fn last_message_type(&self) -> Option<&DhcpMessageType> {
+        self.last_message_type.as_ref()
+    }
+
+    pub fn last_seen(&self) -> Option<SystemTime> {
+        self.last_seen
+    }
+
+    pub fn renew_count(&self) -> u32 {
+        self.renew_count
+    }
+
+    pub fn circuit_id(&self) -> Option<&[u8]> {
+        self.circuit_id.as_deref()
+    }
+
+    /// Records that the client sent a message of type `message_type` at
+    /// `now` which touched this lease. The circuit ID is only replaced if
+    /// the message was relayed with one.
+    pub fn touch(
+        &mut self,
+        message_type: DhcpMessageType,
+        now: SystemTime,
+        circuit_id: Option<&[u8]>,
+    ) {
+        self.last_message_type = Some(message_type);
+        self.last_seen = Some(now);
+
+        if let Some(circuit_id) = circuit_id {
+            self.circuit_id = Some(circuit_id.to_vec());
+        }
+    }
+
+    /// Extends the lease to expire `lease_time` seconds after `now`.
+    pub fn renew(&mut self, lease_time: u32, now: SystemTime) {
+        self.leased_until = now + Duration::from_secs(lease_time as u64);
+        self.lease_time = lease_time;
+        self.renew_count += 1;
+    }
+
+    /// Lets the lease expire at `now`, for example because the client
+    /// released it.
+    pub fn expire(&mut self, now: SystemTime) {
+        self.leased_until = self.leased_until.min(now);
+    }
+
+    /// Returns the remaining lease duration relative to `now`. Returns
+    /// [`None`] if the lease already expired.
+    pub fn remaining(&self, now: SystemTime) -> Option<Duration> {
+        self.leased_until
+            .duration_since(now)
+            .ok()
+            .filter(|d| !d.is_zero())
+    }
+
+    /// Returns a displayable version of this lease where the remaining time
+    /// is computed against `now` instead of the current system time.
+    pub fn display_at(&self, now: SystemTime) -> LeaseDisplay<'_> {
+        LeaseDisplay { lease: self, now }
+    }
+}
+
+/// Operator facing representation of a [`Lease`], see [`Lease::display_at`].
+pub struct LeaseDisplay<'a> {
+    lease: &'a Lease,
+    now: SystemTime,
+}
+
+impl<'a> Display for LeaseDisplay<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let remaining = match self.lease.remaining(self.now) {
+            Some(remaining) => format_duration(remaining),
+            None => String::from("expired"),
+        };
+
+        write!(
+            f,
+            "{} {} {}",
+            self.lease.hardware_addr, self.lease.ip_addr, remaining
+        )?;
+
+        if let Some(hostname) = &self.lease.hostname {
+            write!(f, " {}", hostname)?;
+        }
+
+        if let (Some(ty), Some(last_seen)) = (&self.lease.last_message_type, self.lease.last_seen) {
+            let ago = self.now.duration_since(last_seen).unwrap_or_default();
+            write!(f, " last {} {} ago", ty, format_duration(ago))?;
+        }
+
+        if self.lease.renew_count > 0 {
+            write!(f, " renewed {}x", self.lease.renew_count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a duration in a human-readable way, like `1h 2m 3s`. Leading zero
+/// units are omitted.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, mins, secs) = (
+        secs / 86400,
+        (secs % 86400) / 3600,
+        (secs % 3600) / 60,
+        secs % 60,
+    );
+
+    let mut parts = Vec::new();
+
+    if days > 0 {
+        parts.push(format!("{}d", days));
+    }
+
+    if days > 0 || hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+
+    if days > 0 || hours > 0 || mins > 0 {
+        parts.push(format!("{}m", mins));
+    }
+
+    parts.push(format!("{}s", secs));
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_display() {
+        let hardware_addr = HardwareAddr::try_from(String::from("DE:AD:BE:EF:12:34")).unwrap();
+        let lease = Lease::new(
+            hardware_addr,
+            Ipv4Addr::new(192, 168, 1, 10),
+            3600,
+            Some(String::from("printer")),
+        );
+
+        let now = lease.leased_until() - Duration::from_secs(3723);
+        let output = lease.display_at(now).to_string();
+
+        assert!(output.contains("de:ad:be:ef:12:34"));
+        assert!(output.contains("192.168.1.10"));
+        assert!(output.contains("1h 2m 3s"));
+        assert!(output.contains("printer"));
+    }
+
+    #[test]
+    fn test_expired_lease_display() {
+        let hardware_addr = HardwareAddr::try_from(String::from("DE:AD:BE:EF:12:34")).unwrap();
+        let lease = Lease::new(hardware_addr, Ipv4Addr::new(192, 168, 1, 10), 60, None);
+
+        let now = lease.leased_until() + Duration::from_secs(1);
+        assert!(lease.display_at(now).to_string().ends_with("expired"));
+    }
+
+    #[test]
+    fn test_lease_metadata_display() {
+        let hardware_addr = HardwareAddr::try_from(String::from("DE:AD:BE:EF:12:34")).unwrap();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mut lease = Lease::new_at(
+            hardware_addr,
+            Ipv4Addr::new(192, 168, 1, 10),
+            3600,
+            None,
+            start,
+        );
+
+        lease.touch(DhcpMessageType::Request, start, Some(b"eth1/1"));
+        lease.renew(3600, start + Duration::from_secs(1800));
+        lease.touch(
+            DhcpMessageType::Request,
+            start + Duration::from_secs(1800),
+            None,
+        );
+
+        // The circuit ID of earlier relayed messages is kept
+        assert_eq!(lease.circuit_id(), Some(&b"eth1/1"[..]));
+
+        let output = lease
+            .display_at(start + Duration::from_secs(1830))
+            .to_string();
+        assert!(
+            output.ends_with("last DHCPREQUEST 30s ago renewed 1x"),
+            "{output}"
+        );
+    }
 }
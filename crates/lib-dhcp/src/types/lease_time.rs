@@ -0,0 +1,80 @@
+use std::{fmt::Display, time::Duration};
+
+/// Lease time of an address as sent in the 'IP address lease time' option
+/// (51). The value `0xffffffff` represents an infinite lease.
+///
+/// ### See
+///
+/// RFC 2131 - Section 3.3 - Interpretation and representation of time values: https://datatracker.ietf.org/doc/html/rfc2131#section-3.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseTime {
+    Finite(u32),
+    Infinite,
+}
+
+impl LeaseTime {
+    /// Value of option 51 representing an infinite lease.
+    pub const INFINITE_SECS: u32 = 0xffffffff;
+
+    pub fn is_infinite(&self) -> bool {
+        matches!(self, Self::Infinite)
+    }
+
+    /// Returns the lease time as duration, [`None`] if the lease is infinite.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            Self::Finite(secs) => Some(Duration::from_secs(*secs as u64)),
+            Self::Infinite => None,
+        }
+    }
+
+    /// Returns the lease time as encoded in option 51.
+    pub fn as_secs(&self) -> u32 {
+        match self {
+            Self::Finite(secs) => *secs,
+            Self::Infinite => Self::INFINITE_SECS,
+        }
+    }
+}
+
+impl From<u32> for LeaseTime {
+    fn from(secs: u32) -> Self {
+        match secs {
+            Self::INFINITE_SECS => Self::Infinite,
+            secs => Self::Finite(secs),
+        }
+    }
+}
+
+impl From<LeaseTime> for u32 {
+    fn from(time: LeaseTime) -> Self {
+        time.as_secs()
+    }
+}
+
+impl Display for LeaseTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Finite(secs) => write!(f, "{}s", secs),
+            Self::Infinite => write!(f, "infinite"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infinite_lease_time() {
+        let time = LeaseTime::from(0xffffffff);
+
+        assert!(time.is_infinite());
+        assert_eq!(time.as_duration(), None);
+        assert_eq!(u32::from(time), 0xffffffff);
+
+        let time = LeaseTime::from(0xfffffffe);
+        assert!(!time.is_infinite());
+        assert_eq!(time.as_duration(), Some(Duration::from_secs(0xfffffffe)));
+    }
+}
@@ -1,32 +1,156 @@
-use std::{fmt::Display, net::Ipv4Addr};
+use std::{collections::HashMap, fmt::Display, net::Ipv4Addr};
 
 use binbuf::prelude::*;
 use thiserror::Error;
 
 use crate::{
-    constants,
+    constants::{self, FILE_LEN, SNAME_LEN},
     types::{
-        options::DhcpMessageType, DhcpOption, HardwareAddr, Header, HeaderError, OptionData,
+        options::{
+            Authentication, ClassIdentifier, ClientFqdn, ClientIdentifier, DhcpMessageType,
+            DomainSearch, OptionOverload, ParameterRequestList, RelayAgentInformation,
+            VendorIdentifying,
+        },
+        DhcpOption, HardwareAddr, HardwareType, Header, HeaderError, OpCode, OptionData,
         OptionError, OptionTag,
     },
 };
 
 #[derive(Debug, Error)]
 pub enum MessageError {
-    #[error("Header error: {0}")]
+    #[error("{code} Header error: {0}", code = self.code())]
     HeaderError(#[from] HeaderError),
 
-    #[error("Option error: {0}")]
+    #[error("{code} Option error: {0}", code = self.code())]
     OptionError(#[from] OptionError),
 
-    #[error("Buffer error: {0}")]
+    #[error("{code} Buffer error: {0}", code = self.code())]
     BufferError(#[from] BufferError),
 
-    #[error("Option with tag {0} already present, duplicates are not allowed")]
+    #[error("{code} Option with tag {0} already present, duplicates are not allowed", code = self.code())]
     DuplicateOptionError(OptionTag),
 
-    #[error("No DHCP magic cookie found at the start of OPTIONS field")]
-    NoMagicCookie,
+    #[error("{code} Hardware address length {1} doesn't match hardware type {0}", code = self.code())]
+    HardwareAddrLength(HardwareType, u8),
+
+    #[error("{code} Client identifier of hardware type {0} with {1} octet address doesn't match hardware type {2}", code = self.code())]
+    InconsistentClientIdentifier(u8, usize, HardwareType),
+
+    #[error("{code} {0} message MUST include option {1}", code = self.code())]
+    MissingOption(DhcpMessageType, OptionTag),
+
+    #[error("{code} {0} message MUST NOT include option {1}", code = self.code())]
+    ForbiddenOption(DhcpMessageType, OptionTag),
+
+    #[error("{code} Field '{1}' of {0} message has an invalid value", code = self.code())]
+    InvalidField(DhcpMessageType, &'static str),
+
+    #[error("{code} Option with tag {0} claims {1} octets, but only {2} are left", code = self.code())]
+    OptionLength(u8, u8, usize),
+
+    #[error("{code} Option with tag {0} is truncated, the message ends before its length", code = self.code())]
+    TruncatedOption(u8),
+
+    #[error("{code} Options need {0} octets, but only {1} fit into the message, including the 'file' and 'sname' fields", code = self.code())]
+    OptionsTooLarge(usize, usize),
+
+    #[error("{code} Invalid hex dump: {0}", code = self.code())]
+    InvalidHex(String),
+}
+
+// VULCAN-P105 stays unused, it was the missing magic cookie. Messages
+// without the cookie are read as BOOTP since.
+error_codes!(MessageError {
+    HeaderError => "VULCAN-P101",
+    OptionError => "VULCAN-P102",
+    BufferError => "VULCAN-P103",
+    DuplicateOptionError => "VULCAN-P104",
+    HardwareAddrLength => "VULCAN-P106",
+    InconsistentClientIdentifier => "VULCAN-P107",
+    MissingOption => "VULCAN-P108",
+    ForbiddenOption => "VULCAN-P109",
+    InvalidField => "VULCAN-P110",
+    OptionLength => "VULCAN-P111",
+    TruncatedOption => "VULCAN-P112",
+    OptionsTooLarge => "VULCAN-P113",
+    InvalidHex => "VULCAN-P114",
+});
+
+/// Coarse category of a parse error, used to break down parse failures in
+/// metrics. See [`MessageError::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ParseErrorKind {
+    /// The magic cookie is missing where options are expected.
+    BadCookie,
+
+    /// The message ends before a fixed field or the options end.
+    Truncated,
+
+    /// The length of an option exceeds the message or doesn't fit its data.
+    BadOptionLength,
+
+    /// An option tag the parser doesn't know.
+    UnknownOption,
+
+    /// Any other malformed or inconsistent content.
+    Invalid,
+}
+
+impl ParseErrorKind {
+    pub const ALL: [ParseErrorKind; 5] = [
+        Self::BadCookie,
+        Self::Truncated,
+        Self::BadOptionLength,
+        Self::UnknownOption,
+        Self::Invalid,
+    ];
+
+    /// Returns the name of the kind as used in metric labels.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BadCookie => "bad_cookie",
+            Self::Truncated => "truncated",
+            Self::BadOptionLength => "bad_option_length",
+            Self::UnknownOption => "unknown_option",
+            Self::Invalid => "invalid",
+        }
+    }
+
+    pub(crate) fn of_buffer_error(err: &BufferError) -> Self {
+        match err {
+            BufferError::BufTooShort => Self::Truncated,
+            _ => Self::Invalid,
+        }
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl MessageError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ParseErrorKind {
+        match self {
+            Self::HeaderError(HeaderError::BufferError(err)) | Self::BufferError(err) => {
+                ParseErrorKind::of_buffer_error(err)
+            }
+            Self::OptionError(err) => err.kind(),
+            Self::OptionLength(..) => ParseErrorKind::BadOptionLength,
+            Self::TruncatedOption(_) => ParseErrorKind::Truncated,
+            Self::HeaderError(_)
+            | Self::DuplicateOptionError(_)
+            | Self::HardwareAddrLength(..)
+            | Self::InconsistentClientIdentifier(..)
+            | Self::MissingOption(..)
+            | Self::ForbiddenOption(..)
+            | Self::InvalidField(..)
+            | Self::OptionsTooLarge(..)
+            | Self::InvalidHex(_) => ParseErrorKind::Invalid,
+        }
+    }
 }
 
 /// [`Message`] describes a complete DHCP message. The same packet field
@@ -35,7 +159,7 @@ pub enum MessageError {
 /// ### See
 ///
 /// RFC 2131 - Section 2 - Protocol Summary: https://datatracker.ietf.org/doc/html/rfc2131#section-2
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Message {
     /// Header fields like the opcode, transaction id and additional flags.
     pub header: Header,
@@ -74,15 +198,28 @@ pub struct Message {
     ///
     /// The DHCP RFC renames this filed to 'options'.
     pub options: Vec<DhcpOption>,
+
+    /// Raw 'vendor extensions' of a BOOTP message, which doesn't start with
+    /// the DHCP magic cookie. The bytes are kept as they are and written back
+    /// unchanged instead of the cookie and [`Message::options`], so BOOTP
+    /// messages can be relayed transparently. [`None`] for DHCP messages.
+    ///
+    /// ### See
+    ///
+    /// RFC 951 - Section 3 - Packet Format: https://datatracker.ietf.org/doc/html/rfc951#section-3
+    pub vendor: Option<Vec<u8>>,
 }
 
 impl Display for Message {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let options: String = self
-            .options
-            .iter()
-            .map(|o| format!(";; {:?}\n", o))
-            .collect();
+        let options: String = match &self.vendor {
+            Some(vendor) => format!(";; BOOTP vendor extensions: {:02x?}\n", vendor),
+            None => self
+                .options
+                .iter()
+                .map(|o| format!(";; {:?}\n", o))
+                .collect(),
+        };
 
         write!(
             f,
@@ -112,6 +249,7 @@ impl Display for Message {
     }
 }
 
+// Not derived, 'sname' and 'file' are fixed-length fields
 impl Default for Message {
     fn default() -> Self {
         Self {
@@ -121,9 +259,10 @@ impl Default for Message {
             siaddr: Ipv4Addr::new(0, 0, 0, 0),
             giaddr: Ipv4Addr::new(0, 0, 0, 0),
             chaddr: Default::default(),
-            sname: vec![0; 64],
-            file: vec![0; 128],
+            sname: vec![0; SNAME_LEN],
+            file: vec![0; FILE_LEN],
             options: vec![],
+            vendor: None,
         }
     }
 }
@@ -134,23 +273,29 @@ impl Readable for Message {
     fn read<E: Endianness>(buf: &mut ReadBuffer) -> Result<Self, Self::Error> {
         let header = Header::read::<E>(buf)?;
 
-        let ciaddr = Ipv4Addr::read::<E>(buf)?;
-        let yiaddr = Ipv4Addr::read::<E>(buf)?;
-        let siaddr = Ipv4Addr::read::<E>(buf)?;
-        let giaddr = Ipv4Addr::read::<E>(buf)?;
+        read_fields!(buf, E =>
+            ciaddr: Ipv4Addr,
+            yiaddr: Ipv4Addr,
+            siaddr: Ipv4Addr,
+            giaddr: Ipv4Addr,
+        );
         let chaddr = HardwareAddr::read::<E>(buf, header.hlen)?;
 
-        let sname = buf.read_vec(64)?;
-        let file = buf.read_vec(128)?;
+        let sname = buf.read_vec(SNAME_LEN)?;
+        let file = buf.read_vec(FILE_LEN)?;
 
-        match buf.peekn::<4>() {
-            Some(m) if m == constants::MAGIC_COOKIE_ARR => buf.skipn(4)?,
-            Some(_) => return Err(MessageError::NoMagicCookie),
-            None => return Err(BufferError::BufTooShort.into()),
+        // Without magic cookie, this is a BOOTP message. Keep the vendor
+        // extensions as they are.
+        let (options, vendor) = match buf.peekn::<4>() {
+            Some(m) if m == constants::MAGIC_COOKIE_ARR => {
+                buf.skipn(4)?;
+                let options = read_options::<E>(buf)?;
+                (read_overloaded::<E>(options, &file, &sname)?, None)
+            }
+            _ if buf.is_empty() => return Err(BufferError::BufTooShort.into()),
+            _ => (Vec::new(), Some(read_vendor(buf)?)),
         };
 
-        let options = read_options::<E>(buf)?;
-
         Ok(Self {
             header,
             ciaddr,
@@ -161,66 +306,357 @@ impl Readable for Message {
             sname,
             file,
             options,
+            vendor,
         })
     }
 }
 
-fn read_options<E: Endianness>(buf: &mut ReadBuffer) -> Result<Vec<DhcpOption>, MessageError> {
-    if buf.is_empty() {
-        return Err(MessageError::BufferError(BufferError::BufTooShort));
+/// Reads the remaining bytes of `buf` as raw BOOTP vendor extensions.
+fn read_vendor(buf: &mut ReadBuffer) -> Result<Vec<u8>, MessageError> {
+    let mut vendor = Vec::new();
+
+    while !buf.is_empty() {
+        vendor.push(buf.pop()?);
     }
 
-    let mut options = vec![];
+    Ok(vendor)
+}
+
+/// Reads the options following the magic cookie. The options field may be
+/// empty, like in minimal BOOTP-style messages, which have no options.
+fn read_options<E: Endianness>(buf: &mut ReadBuffer) -> Result<Vec<DhcpOption>, MessageError> {
+    let mut slots = vec![];
+    let mut segments: HashMap<u8, Vec<&[u8]>> = HashMap::new();
 
     while !buf.is_empty() {
+        // Only the fixed length pad and end options fit in a single octet
+        if let (1, Some([tag @ 1..=254])) = (buf.len(), buf.peekn::<1>()) {
+            return Err(MessageError::TruncatedOption(tag));
+        }
+
+        // Report options overrunning the message as such, instead of as a
+        // truncated read of their data
+        if let Some([tag, len]) = buf.peekn::<2>() {
+            if tag != 0 && tag != 255 && usize::from(len) > buf.len() - 2 {
+                return Err(MessageError::OptionLength(tag, len, buf.len() - 2));
+            }
+
+            // Collect the data of concatenatable options, see RFC 3396. They
+            // are parsed once all instances were seen, at the position of
+            // the first one.
+            if OptionTag::try_from(tag).is_ok_and(|t| t.is_concatenatable()) {
+                buf.skipn(2)?;
+                let data = buf.read_slice(len.into())?;
+
+                let instances = segments.entry(tag).or_default();
+                if instances.is_empty() {
+                    slots.push(OptionSlot::Concatenated(tag));
+                }
+                instances.push(data);
+                continue;
+            }
+        }
+
         let option = match DhcpOption::read::<E>(buf) {
             Ok(option) => option,
             Err(err) => return Err(MessageError::OptionError(err)),
         };
-        options.push(option);
+        slots.push(OptionSlot::Read(option));
+    }
+
+    let mut options = vec![];
+    for slot in slots {
+        match slot {
+            OptionSlot::Read(option) => options.push(option),
+            OptionSlot::Concatenated(tag) => {
+                options.extend(read_concatenated::<E>(tag, &segments[&tag])?)
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+/// Adds the options held in the 'file' and 'sname' fields to `options`, if
+/// the option overload option (52) says so. The fields are read as if they
+/// followed each other, 'file' first, so options split into multiple
+/// instances across both of them are concatenated (RFC 3396). The options
+/// are inserted before the End option of the options field.
+///
+/// ### See
+///
+/// RFC 2131 - Section 4.1 - Constructing and sending DHCP messages: https://datatracker.ietf.org/doc/html/rfc2131#section-4.1
+fn read_overloaded<E: Endianness>(
+    mut options: Vec<DhcpOption>,
+    file: &[u8],
+    sname: &[u8],
+) -> Result<Vec<DhcpOption>, MessageError> {
+    let overload = options.iter().find_map(|option| match option.data() {
+        OptionData::OptionOverload(overload) => Some(*overload),
+        _ => None,
+    });
+
+    let Some(overload) = overload else {
+        return Ok(options);
+    };
+
+    let mut bytes = Vec::new();
+    if overload.uses_file() {
+        bytes.extend_from_slice(overloaded_field(file)?);
+    }
+    if overload.uses_sname() {
+        bytes.extend_from_slice(overloaded_field(sname)?);
     }
 
+    let end = options
+        .iter()
+        .position(|option| option.header().tag == OptionTag::End)
+        .unwrap_or(options.len());
+    let overflow = read_options::<E>(&mut ReadBuffer::new(&bytes))?
+        .into_iter()
+        .filter(|option| !matches!(option.header().tag, OptionTag::Pad | OptionTag::End));
+
+    options.splice(end..end, overflow);
     Ok(options)
 }
 
+/// Returns the options of an overloaded 'file' or 'sname' `field`, up to
+/// its End option.
+fn overloaded_field(field: &[u8]) -> Result<&[u8], MessageError> {
+    let mut pos = 0;
+
+    while let Some(&tag) = field.get(pos) {
+        match tag {
+            0 => pos += 1,
+            255 => return Ok(&field[..pos]),
+            _ => {
+                let len = *field
+                    .get(pos + 1)
+                    .ok_or(MessageError::TruncatedOption(tag))?;
+                let left = field.len() - pos - 2;
+
+                if usize::from(len) > left {
+                    return Err(MessageError::OptionLength(tag, len, left));
+                }
+
+                pos += 2 + usize::from(len);
+            }
+        }
+    }
+
+    Ok(field)
+}
+
+/// Field of a message options are placed in when overloading, see
+/// [`Message::overload_options`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OptionField {
+    Options,
+    File,
+    Sname,
+}
+
+/// Position of an option in the options field while reading them.
+enum OptionSlot {
+    Read(DhcpOption),
+    Concatenated(u8),
+}
+
+/// Reads the concatenated data of all `instances` of the option with `tag`
+/// as a single option. Option data is limited to 255 octets, so longer
+/// concatenations are read as separate options instead.
+fn read_concatenated<E: Endianness>(
+    tag: u8,
+    instances: &[&[u8]],
+) -> Result<Vec<DhcpOption>, MessageError> {
+    let data = instances.concat();
+
+    let parts = match u8::try_from(data.len()) {
+        Ok(len) => vec![[&[tag, len], data.as_slice()].concat()],
+        Err(_) => instances
+            .iter()
+            .map(|data| [&[tag, data.len() as u8], *data].concat())
+            .collect(),
+    };
+
+    parts
+        .iter()
+        .map(|bytes| DhcpOption::read::<E>(&mut ReadBuffer::new(bytes)).map_err(MessageError::from))
+        .collect()
+}
+
+/// Messages are written in a canonical form, so that two equal messages
+/// built the same way always produce identical bytes:
+///
+/// - 'sname' and 'file' are padded with zeros (or truncated) to exactly 64
+///   and 128 octets.
+/// - Options are written in the order of [`Message::options`]. The builder
+///   determines this order, either by the default order or the configured
+///   one, see [`MessageBuilder::with_option_order`](crate::MessageBuilder::with_option_order).
+/// - Pad options are never written, there is no alignment.
+/// - Exactly one End option terminates the options, regardless of whether
+///   (and where) the options contain one.
+///
+/// BOOTP messages with [`Message::vendor`] set are the exception: the vendor
+/// extensions are written byte for byte instead of the cookie and options.
 impl Writeable for Message {
     type Error = MessageError;
 
     fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
-        // let n = bytes_written! {};
-
-        let mut n = 0;
+        let mut n = write_fields!(buf, E =>
+            self.header,
+            self.ciaddr,
+            self.yiaddr,
+            self.siaddr,
+            self.giaddr,
+            self.chaddr,
+        );
+        n += write_fixed(&self.sname, SNAME_LEN, buf);
+        n += write_fixed(&self.file, FILE_LEN, buf);
 
-        n += self.header.write::<E>(buf)?;
-        n += self.ciaddr.write::<E>(buf)?;
-        n += self.yiaddr.write::<E>(buf)?;
-        n += self.siaddr.write::<E>(buf)?;
-        n += self.giaddr.write::<E>(buf)?;
-        n += self.chaddr.write::<E>(buf)?;
-        n += self.sname.write::<E>(buf)?;
-        n += self.file.write::<E>(buf)?;
+        if let Some(vendor) = &self.vendor {
+            return Ok(n + buf.write(vendor.as_slice()));
+        }
 
         // Write magic cookie
         n += buf.write(constants::MAGIC_COOKIE_ARR);
 
-        n += self.options.write::<E>(buf)?;
+        for option in &self.options {
+            match option.header().tag {
+                OptionTag::Pad | OptionTag::End => continue,
+                _ => n += option.write::<E>(buf)?,
+            }
+        }
+
+        n += DhcpOption::new(OptionTag::End, OptionData::End).write::<E>(buf)?;
 
         Ok(n)
     }
 }
 
+/// Writes `field` zero-padded or truncated to exactly `len` octets.
+fn write_fixed(field: &[u8], len: usize, buf: &mut WriteBuffer) -> usize {
+    let field = &field[..field.len().min(len)];
+
+    let mut n = buf.write(field);
+    for _ in field.len()..len {
+        buf.push(0);
+        n += 1;
+    }
+
+    n
+}
+
+/// Messages are compared field by field. Options are compared
+/// order-insensitively by tag and payload, as the order of options carries no
+/// meaning (RFC 2131 Section 4.1).
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header
+            && self.ciaddr == other.ciaddr
+            && self.yiaddr == other.yiaddr
+            && self.siaddr == other.siaddr
+            && self.giaddr == other.giaddr
+            && self.chaddr == other.chaddr
+            && self.sname == other.sname
+            && self.file == other.file
+            && self.vendor == other.vendor
+            && unmatched_options(&self.options, &other.options).is_empty()
+            && unmatched_options(&other.options, &self.options).is_empty()
+    }
+}
+
+impl Message {
+    /// Returns a human-readable list of the differences between this and
+    /// the `other` message, one entry per differing field or option. The
+    /// list is empty if the messages are equal, see the [`PartialEq`]
+    /// implementation.
+    pub fn differences(&self, other: &Self) -> Vec<String> {
+        let mut diffs = Vec::new();
+
+        macro_rules! diff_field {
+            ($($field:ident).+) => {
+                if self.$($field).+ != other.$($field).+ {
+                    diffs.push(format!(
+                        "{}: {:?} != {:?}",
+                        stringify!($($field).+),
+                        self.$($field).+,
+                        other.$($field).+
+                    ));
+                }
+            };
+        }
+
+        diff_field!(header.opcode);
+        diff_field!(header.htype);
+        diff_field!(header.hlen);
+        diff_field!(header.hops);
+        diff_field!(header.xid);
+        diff_field!(header.secs);
+        diff_field!(header.flags);
+        diff_field!(ciaddr);
+        diff_field!(yiaddr);
+        diff_field!(siaddr);
+        diff_field!(giaddr);
+        diff_field!(chaddr);
+        diff_field!(sname);
+        diff_field!(file);
+        diff_field!(vendor);
+
+        for option in unmatched_options(&self.options, &other.options) {
+            diffs.push(format!("option only in left: {:?}", option));
+        }
+
+        for option in unmatched_options(&other.options, &self.options) {
+            diffs.push(format!("option only in right: {:?}", option));
+        }
+
+        diffs
+    }
+}
+
+/// Returns the options of `left` which have no equal counterpart in `right`.
+/// Each option of `right` can only be matched once, so that duplicates are
+/// accounted for.
+fn unmatched_options<'a>(left: &'a [DhcpOption], right: &[DhcpOption]) -> Vec<&'a DhcpOption> {
+    let mut matched = vec![false; right.len()];
+    let mut unmatched = Vec::new();
+
+    for option in left {
+        match (0..right.len()).find(|&i| !matched[i] && right[i] == *option) {
+            Some(i) => matched[i] = true,
+            None => unmatched.push(option),
+        }
+    }
+
+    unmatched
+}
+
+/// Asserts that two [`Message`]s are equal (see the [`PartialEq`]
+/// implementation of [`Message`]). On mismatch, the panic message lists
+/// every differing field and option instead of dumping both messages.
+#[macro_export]
+macro_rules! assert_messages_equivalent {
+    ($left:expr, $right:expr $(,)?) => {{
+        let diffs = $crate::types::Message::differences(&$left, &$right);
+        if !diffs.is_empty() {
+            panic!("messages are not equivalent:\n  {}", diffs.join("\n  "));
+        }
+    }};
+}
+
 impl Message {
-    /// Create a new DHCP [`Message`]. Internally this creates a default header
-    /// with a random transaction ID and then calls [`Self::new_with_header`].
+    /// Create a new DHCP [`Message`] with a random transaction ID, see
+    /// [`Self::new_with_xid`].
+    #[deprecated(note = "use `Message::new_with_xid(rand::random())` instead")]
     pub fn new() -> Self {
-        let header = Header::new();
-        Self::new_with_header(header)
+        Self::new_with_xid(rand::random())
     }
 
-    /// Create a new DHCP [`Message`] with the provided transaction id (xid).
+    /// Create a new DHCP [`Message`] with the provided transaction id (xid)
+    /// and a default header, see [`Self::new_with_header`].
     pub fn new_with_xid(xid: u32) -> Self {
-        let header = Header::new_with_xid(xid);
-        Self::new_with_header(header)
+        Self::new_with_header(Header::new_with_xid(xid))
     }
 
     /// Create a new DHCP [`Message`] with the provided header. All other
@@ -232,6 +668,65 @@ impl Message {
         }
     }
 
+    /// Parses a [`Message`] from `bytes` as received on the wire.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MessageError> {
+        Self::read::<BigEndian>(&mut ReadBuffer::new(bytes))
+    }
+
+    /// Parses a [`Message`] from a hex dump of its bytes, like the one
+    /// returned by [`Message::to_hex`]. Whitespace between the octets is
+    /// ignored.
+    pub fn from_hex(hex: &str) -> Result<Self, MessageError> {
+        let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+
+        if let Some(invalid) = digits.iter().find(|b| !b.is_ascii_hexdigit()) {
+            return Err(MessageError::InvalidHex(format!(
+                "unexpected character '{}'",
+                char::from(*invalid)
+            )));
+        }
+
+        let pairs = digits.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return Err(MessageError::InvalidHex(String::from(
+                "odd number of hex digits",
+            )));
+        }
+
+        let bytes: Vec<u8> = pairs
+            .map(|pair| {
+                let pair = std::str::from_utf8(pair).unwrap();
+                u8::from_str_radix(pair, 16).unwrap()
+            })
+            .collect();
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Encodes the message as it is sent on the wire.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MessageError> {
+        let mut buf = WriteBuffer::new();
+        self.write::<BigEndian>(&mut buf)?;
+        Ok(buf.bytes().to_vec())
+    }
+
+    /// Returns a hex dump of the encoded message, 16 octets per line. It
+    /// can be parsed back with [`Message::from_hex`].
+    pub fn to_hex(&self) -> Result<String, MessageError> {
+        let lines: Vec<String> = self
+            .to_bytes()?
+            .chunks(16)
+            .map(|line| {
+                line.iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+
+        Ok(lines.join("\n"))
+    }
+
     pub fn valid_xid(&self, xid: u32) -> bool {
         self.header.xid == xid
     }
@@ -253,6 +748,17 @@ impl Message {
         None
     }
 
+    /// Returns the data of all options by tag, without Pad and End. Options
+    /// split across multiple instances (RFC 3396) are concatenated when the
+    /// message is read, so each tag maps to the full data of its option.
+    pub fn options_map(&self) -> HashMap<OptionTag, &OptionData> {
+        self.options
+            .iter()
+            .filter(|option| !matches!(option.header().tag, OptionTag::Pad | OptionTag::End))
+            .map(|option| (option.header().tag.clone(), option.data()))
+            .collect()
+    }
+
     /// Get DHCP message type
     pub fn get_message_type(&self) -> Option<&DhcpMessageType> {
         let option = self.get_option(OptionTag::DhcpMessageType)?;
@@ -271,6 +777,24 @@ impl Message {
         }
     }
 
+    /// Get interface MTU option
+    pub fn get_interface_mtu(&self) -> Option<u16> {
+        let option = self.get_option(OptionTag::InterfaceMtu)?;
+        match option.data() {
+            OptionData::InterfaceMtu(mtu) => Some(*mtu),
+            _ => None,
+        }
+    }
+
+    /// Get maximum DHCP message size option
+    pub fn get_max_message_size(&self) -> Option<u16> {
+        let option = self.get_option(OptionTag::MaxDhcpMessageSize)?;
+        match option.data() {
+            OptionData::MaxDhcpMessageSize(size) => Some(*size),
+            _ => None,
+        }
+    }
+
     /// Get rebinding T2 time option
     pub fn get_rebinding_t2_time(&self) -> Option<u32> {
         let option = self.get_option(OptionTag::RebindingT2Time)?;
@@ -280,6 +804,378 @@ impl Message {
         }
     }
 
+    /// Get domain name server option
+    pub fn get_domain_name_servers(&self) -> Option<&Vec<Ipv4Addr>> {
+        let option = self.get_option(OptionTag::DomainNameServer)?;
+        match option.data() {
+            OptionData::DomainNameServer(ips) => Some(ips),
+            _ => None,
+        }
+    }
+
+    /// Get network time protocol servers option (42)
+    pub fn get_ntp_servers(&self) -> Option<&Vec<Ipv4Addr>> {
+        let option = self.get_option(OptionTag::NetworkTimeProtocolServers)?;
+        match option.data() {
+            OptionData::NetworkTimeProtocolServers(ips) => Some(ips),
+            _ => None,
+        }
+    }
+
+    /// Get host name option (12)
+    pub fn get_host_name(&self) -> Option<&String> {
+        let option = self.get_option(OptionTag::HostName)?;
+        match option.data() {
+            OptionData::HostName(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get domain name option
+    pub fn get_domain_name(&self) -> Option<&String> {
+        let option = self.get_option(OptionTag::DomainName)?;
+        match option.data() {
+            OptionData::DomainName(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get domain search option
+    pub fn get_domain_search(&self) -> Option<&DomainSearch> {
+        let option = self.get_option(OptionTag::DomainSearch)?;
+        match option.data() {
+            OptionData::DomainSearch(search) => Some(search),
+            _ => None,
+        }
+    }
+
+    /// Get message option (56), the explanation of a DHCPNAK or
+    /// DHCPDECLINE
+    pub fn get_message_text(&self) -> Option<&String> {
+        let option = self.get_option(OptionTag::Message)?;
+        match option.data() {
+            OptionData::Message(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Get relay agent information option (82)
+    pub fn get_relay_agent_information(&self) -> Option<&RelayAgentInformation> {
+        let option = self.get_option(OptionTag::RelayAgentInformation)?;
+        match option.data() {
+            OptionData::RelayAgentInformation(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Get vendor specific information option (43)
+    pub fn get_vendor_specific_information(&self) -> Option<&[u8]> {
+        let option = self.get_option(OptionTag::VendorSpecificInformation)?;
+        match option.data() {
+            OptionData::VendorSpecificInformation(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Get vendor-identifying vendor class option (124)
+    pub fn get_vendor_identifying_vendor_class(&self) -> Option<&VendorIdentifying> {
+        let option = self.get_option(OptionTag::VendorIdentifyingVendorClass)?;
+        match option.data() {
+            OptionData::VendorIdentifyingVendorClass(class) => Some(class),
+            _ => None,
+        }
+    }
+
+    /// Get client identifier option
+    pub fn get_client_identifier(&self) -> Option<&ClientIdentifier> {
+        let option = self.get_option(OptionTag::ClientIdentifier)?;
+        match option.data() {
+            OptionData::ClientIdentifier(ident) => Some(ident),
+            _ => None,
+        }
+    }
+
+    /// Get server identifier option
+    pub fn get_server_identifier(&self) -> Option<Ipv4Addr> {
+        let option = self.get_option(OptionTag::ServerIdentifier)?;
+        match option.data() {
+            OptionData::ServerIdentifier(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Get requested IP address option
+    pub fn get_requested_ip_addr(&self) -> Option<Ipv4Addr> {
+        let option = self.get_option(OptionTag::RequestedIpAddr)?;
+        match option.data() {
+            OptionData::RequestedIpAddr(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    pub fn get_authentication(&self) -> Option<&Authentication> {
+        let option = self.get_option(OptionTag::Authentication)?;
+        match option.data() {
+            OptionData::Authentication(auth) => Some(auth),
+            _ => None,
+        }
+    }
+
+    pub fn get_class_identifier(&self) -> Option<&ClassIdentifier> {
+        let option = self.get_option(OptionTag::ClassIdentifier)?;
+        match option.data() {
+            OptionData::ClassIdentifier(class) => Some(class),
+            _ => None,
+        }
+    }
+
+    pub fn get_client_fqdn(&self) -> Option<&ClientFqdn> {
+        let option = self.get_option(OptionTag::ClientFqdn)?;
+        match option.data() {
+            OptionData::ClientFqdn(fqdn) => Some(fqdn),
+            _ => None,
+        }
+    }
+
+    /// Get parameter request list option
+    pub fn get_parameter_request_list(&self) -> Option<&ParameterRequestList> {
+        let option = self.get_option(OptionTag::ParameterRequestList)?;
+        match option.data() {
+            OptionData::ParameterRequestList(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Checks that the message is valid: the hardware address fields have to
+    /// be consistent (see [`Message::validate_hardware_addr`]) and DHCP
+    /// messages have to follow the field and option rules of RFC 2131
+    /// Tables 3 to 5. Messages without a DHCP message type are BOOTP
+    /// messages, the tables don't apply to them.
+    ///
+    /// Rules depending on state the message doesn't carry are checked as far
+    /// as the message allows: a DHCPACK with 'yiaddr' set answers a
+    /// DHCPREQUEST, otherwise a DHCPINFORM, and a DHCPREQUEST with 'ciaddr'
+    /// set is sent in RENEWING or REBINDING (Table 4).
+    ///
+    /// ### See
+    ///
+    /// RFC 2131 - Section 4.3.1 - Table 3: https://datatracker.ietf.org/doc/html/rfc2131#section-4.3.1
+    /// RFC 2131 - Section 4.4.1 - Table 5: https://datatracker.ietf.org/doc/html/rfc2131#section-4.4.1
+    pub fn validate(&self) -> Result<(), MessageError> {
+        self.validate_hardware_addr()?;
+
+        let ty = match self.get_message_type() {
+            Some(ty) => ty.clone(),
+            None => return Ok(()),
+        };
+
+        self.validate_fields(&ty)?;
+        self.validate_options(ty)
+    }
+
+    /// Checks that the hardware address fields are consistent: 'hlen' must
+    /// match the length of addresses of type 'htype' and a client identifier
+    /// carrying a hardware address (type other than 0 and 255) must be of
+    /// the same hardware type and length. Type 255 identifiers consist of an
+    /// IAID and a DUID (RFC 4361), as sent by systemd-networkd.
+    pub fn validate_hardware_addr(&self) -> Result<(), MessageError> {
+        let htype = &self.header.htype;
+
+        if self.header.hlen as usize != htype.addr_len() {
+            return Err(MessageError::HardwareAddrLength(
+                htype.clone(),
+                self.header.hlen,
+            ));
+        }
+
+        if let Some(ident) = self.get_client_identifier() {
+            let consistent =
+                ident.ty() == u8::from(htype) && ident.identifier().len() == htype.addr_len();
+
+            if !matches!(ident.ty(), 0 | 255) && !consistent {
+                return Err(MessageError::InconsistentClientIdentifier(
+                    ident.ty(),
+                    ident.identifier().len(),
+                    htype.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the fixed fields of a message of type `ty`. 'hops' and
+    /// 'giaddr' of client messages are left alone, relay agents update them
+    /// on the way to the server.
+    fn validate_fields(&self, ty: &DhcpMessageType) -> Result<(), MessageError> {
+        let zero = Ipv4Addr::UNSPECIFIED;
+        let unused = |field: &[u8]| field.iter().all(|b| *b == 0);
+
+        let mut checks: Vec<(&'static str, bool)> = Vec::new();
+
+        match ty {
+            DhcpMessageType::Offer | DhcpMessageType::Ack | DhcpMessageType::Nak => {
+                checks.push(("op", self.header.opcode == OpCode::BootReply));
+                checks.push(("hops", self.header.hops == 0));
+                checks.push(("secs", self.header.secs == 0));
+            }
+            DhcpMessageType::Discover
+            | DhcpMessageType::Request
+            | DhcpMessageType::Decline
+            | DhcpMessageType::Release
+            | DhcpMessageType::Inform => {
+                checks.push(("op", self.header.opcode == OpCode::BootRequest));
+                checks.push(("yiaddr", self.yiaddr == zero));
+                checks.push(("siaddr", self.siaddr == zero));
+            }
+            _ => {}
+        }
+
+        match ty {
+            DhcpMessageType::Offer => {
+                checks.push(("ciaddr", self.ciaddr == zero));
+                checks.push(("yiaddr", self.yiaddr != zero));
+            }
+            DhcpMessageType::Nak => {
+                checks.push(("ciaddr", self.ciaddr == zero));
+                checks.push(("yiaddr", self.yiaddr == zero));
+                checks.push(("siaddr", self.siaddr == zero));
+                checks.push(("sname", unused(&self.sname)));
+                checks.push(("file", unused(&self.file)));
+            }
+            DhcpMessageType::Discover => checks.push(("ciaddr", self.ciaddr == zero)),
+            DhcpMessageType::Decline | DhcpMessageType::Release => {
+                let ciaddr = match ty {
+                    DhcpMessageType::Decline => self.ciaddr == zero,
+                    _ => self.ciaddr != zero,
+                };
+
+                checks.push(("ciaddr", ciaddr));
+                checks.push(("secs", self.header.secs == 0));
+                checks.push(("flags", self.header.flags == 0));
+                checks.push(("sname", unused(&self.sname)));
+                checks.push(("file", unused(&self.file)));
+            }
+            DhcpMessageType::Inform => checks.push(("ciaddr", self.ciaddr != zero)),
+            _ => {}
+        }
+
+        match checks.into_iter().find(|(_, valid)| !valid) {
+            Some((field, _)) => Err(MessageError::InvalidField(ty.clone(), field)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the options of a message of type `ty`. Options a relay agent
+    /// adds (RFC 3046) are allowed in every message.
+    fn validate_options(&self, ty: DhcpMessageType) -> Result<(), MessageError> {
+        let zero = Ipv4Addr::UNSPECIFIED;
+
+        // Options which MUST be present, options which MUST NOT be present
+        // and, if all other options are forbidden, the allowed ones
+        let (required, forbidden, allowed): (&[OptionTag], &[OptionTag], Option<&[OptionTag]>) =
+            match ty {
+                DhcpMessageType::Discover => (&[], &[OptionTag::ServerIdentifier], None),
+                DhcpMessageType::Request if self.ciaddr == zero => {
+                    (&[OptionTag::RequestedIpAddr], &[], None)
+                }
+                DhcpMessageType::Request => (
+                    &[],
+                    &[OptionTag::RequestedIpAddr, OptionTag::ServerIdentifier],
+                    None,
+                ),
+                DhcpMessageType::Decline => (
+                    &[OptionTag::RequestedIpAddr, OptionTag::ServerIdentifier],
+                    &[],
+                    Some(&[OptionTag::ClientIdentifier, OptionTag::Message]),
+                ),
+                DhcpMessageType::Release => (
+                    &[OptionTag::ServerIdentifier],
+                    &[],
+                    Some(&[OptionTag::ClientIdentifier, OptionTag::Message]),
+                ),
+                DhcpMessageType::Inform => (
+                    &[],
+                    &[
+                        OptionTag::RequestedIpAddr,
+                        OptionTag::IpAddrLeaseTime,
+                        OptionTag::ServerIdentifier,
+                    ],
+                    None,
+                ),
+                // Servers echo the client identifier, see RFC 6842
+                DhcpMessageType::Offer => (
+                    &[OptionTag::IpAddrLeaseTime, OptionTag::ServerIdentifier],
+                    &[
+                        OptionTag::RequestedIpAddr,
+                        OptionTag::ParameterRequestList,
+                        OptionTag::MaxDhcpMessageSize,
+                    ],
+                    None,
+                ),
+                DhcpMessageType::Ack if self.yiaddr != zero => (
+                    &[OptionTag::IpAddrLeaseTime, OptionTag::ServerIdentifier],
+                    &[
+                        OptionTag::RequestedIpAddr,
+                        OptionTag::ParameterRequestList,
+                        OptionTag::MaxDhcpMessageSize,
+                    ],
+                    None,
+                ),
+                DhcpMessageType::Ack => (
+                    &[OptionTag::ServerIdentifier],
+                    &[
+                        OptionTag::RequestedIpAddr,
+                        OptionTag::IpAddrLeaseTime,
+                        OptionTag::ParameterRequestList,
+                        OptionTag::MaxDhcpMessageSize,
+                    ],
+                    None,
+                ),
+                DhcpMessageType::Nak => (
+                    &[OptionTag::ServerIdentifier],
+                    &[],
+                    Some(&[
+                        OptionTag::Message,
+                        OptionTag::ClientIdentifier,
+                        OptionTag::ClassIdentifier,
+                    ]),
+                ),
+                _ => (&[], &[], None),
+            };
+
+        if let Some(tag) = required
+            .iter()
+            .find(|tag| self.get_option((*tag).clone()).is_none())
+        {
+            return Err(MessageError::MissingOption(ty, tag.clone()));
+        }
+
+        for option in &self.options {
+            let tag = &option.header().tag;
+
+            let always_allowed = matches!(
+                tag,
+                OptionTag::Pad
+                    | OptionTag::End
+                    | OptionTag::DhcpMessageType
+                    | OptionTag::RelayAgentInformation
+            );
+
+            let is_allowed = always_allowed
+                || match allowed {
+                    Some(allowed) => required.contains(tag) || allowed.contains(tag),
+                    None => !forbidden.contains(tag),
+                };
+
+            if !is_allowed {
+                return Err(MessageError::ForbiddenOption(ty, tag.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn set_hardware_address(&mut self, haddr: HardwareAddr) {
         // TODO (Techassi): We should return a u8. This would make the len call falliable tho
         self.header.hlen = haddr.len() as u8;
@@ -317,4 +1213,577 @@ impl Message {
     pub fn end(&mut self) -> Result<(), MessageError> {
         self.add_option(DhcpOption::new(OptionTag::End, OptionData::End))
     }
+
+    /// Moves the options which don't fit into an options field of
+    /// `max_len` octets into the 'file' and then the 'sname' field, and
+    /// adds the option overload option (52) announcing it. `max_len`
+    /// excludes the magic cookie and includes the End option. Only fields
+    /// which are unused, that is all zeros, are filled.
+    ///
+    /// Options keep their order across the fields, so options split into
+    /// multiple instances (RFC 3396) are concatenated correctly by the
+    /// receiver. Returns the overload value, or [`None`] if all options fit
+    /// into the options field already.
+    ///
+    /// ### See
+    ///
+    /// RFC 2131 - Section 4.1 - Constructing and sending DHCP messages: https://datatracker.ietf.org/doc/html/rfc2131#section-4.1
+    pub fn overload_options(
+        &mut self,
+        max_len: usize,
+    ) -> Result<Option<OptionOverload>, MessageError> {
+        let mut encoded = Vec::new();
+        for option in &self.options {
+            if matches!(
+                option.header().tag,
+                OptionTag::Pad | OptionTag::End | OptionTag::OptionOverload
+            ) {
+                continue;
+            }
+
+            let mut buf = WriteBuffer::new();
+            option.write::<BigEndian>(&mut buf)?;
+            encoded.push((option.clone(), buf.bytes().to_vec()));
+        }
+
+        let needed = encoded.iter().map(|(_, bytes)| bytes.len()).sum::<usize>();
+        if needed < max_len {
+            return Ok(None);
+        }
+
+        let unused = |field: &[u8]| field.iter().all(|b| *b == 0);
+
+        // Fields options can be placed in and their capacity. Each field
+        // ends with an End option, the options field also holds the
+        // overload option.
+        let mut fields = vec![(OptionField::Options, max_len.saturating_sub(3 + 1))];
+        if unused(&self.file) {
+            fields.push((OptionField::File, FILE_LEN - 1));
+        }
+        if unused(&self.sname) {
+            fields.push((OptionField::Sname, SNAME_LEN - 1));
+        }
+
+        // Fill the fields in order, an option which doesn't fit moves on to
+        // the next field together with all options after it
+        let mut placed: Vec<Vec<(DhcpOption, Vec<u8>)>> = vec![Vec::new(); fields.len()];
+        let mut current = 0;
+        let mut used = 0;
+
+        for (option, bytes) in encoded {
+            while used + bytes.len() > fields[current].1 {
+                current += 1;
+                used = 0;
+
+                if current == fields.len() {
+                    let available = fields.iter().map(|(_, capacity)| capacity).sum();
+                    return Err(MessageError::OptionsTooLarge(needed, available));
+                }
+            }
+
+            used += bytes.len();
+            placed[current].push((option, bytes));
+        }
+
+        let mut options = Vec::new();
+        let (mut uses_file, mut uses_sname) = (false, false);
+
+        for ((field, _), placed) in fields.into_iter().zip(placed) {
+            if field == OptionField::Options {
+                options.extend(placed.into_iter().map(|(option, _)| option));
+                continue;
+            }
+
+            if placed.is_empty() {
+                continue;
+            }
+
+            let mut bytes: Vec<u8> = placed.into_iter().flat_map(|(_, bytes)| bytes).collect();
+            bytes.push(255);
+
+            if field == OptionField::File {
+                bytes.resize(FILE_LEN, 0);
+                self.file = bytes;
+                uses_file = true;
+            } else {
+                bytes.resize(SNAME_LEN, 0);
+                self.sname = bytes;
+                uses_sname = true;
+            }
+        }
+
+        let overload = OptionOverload::of(uses_file, uses_sname);
+        if let Some(overload) = overload {
+            options.push(DhcpOption::new(
+                OptionTag::OptionOverload,
+                OptionData::OptionOverload(overload),
+            ));
+        }
+        options.push(DhcpOption::new(OptionTag::End, OptionData::End));
+
+        self.options = options;
+        Ok(overload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn message(options: Vec<(OptionTag, OptionData)>) -> Message {
+        let mut message = Message::new_with_xid(0xdeadbeef);
+        for (tag, data) in options {
+            message.add_option_parts(tag, data).unwrap();
+        }
+        message
+    }
+
+    #[test]
+    fn test_message_eq_ignores_option_order() {
+        let left = message(vec![
+            (
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Offer),
+            ),
+            (
+                OptionTag::ServerIdentifier,
+                OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)),
+            ),
+        ]);
+        let right = message(vec![
+            (
+                OptionTag::ServerIdentifier,
+                OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)),
+            ),
+            (
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Offer),
+            ),
+        ]);
+
+        assert_eq!(left, right);
+        assert_eq!(left.clone(), left);
+        crate::assert_messages_equivalent!(left, right);
+    }
+
+    #[test]
+    fn test_message_differences() {
+        let left = message(vec![(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)),
+        )]);
+        let mut right = message(vec![(
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 2)),
+        )]);
+        right.yiaddr = Ipv4Addr::new(192, 168, 1, 100);
+
+        assert_ne!(left, right);
+
+        let diffs = left.differences(&right);
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[0], "yiaddr: 0.0.0.0 != 192.168.1.100");
+        assert!(diffs[1].starts_with("option only in left"));
+        assert!(diffs[2].starts_with("option only in right"));
+    }
+
+    #[test]
+    fn test_message_eq_counts_duplicates() {
+        // Pad options may appear multiple times
+        let pad = || DhcpOption::new(OptionTag::Pad, OptionData::Pad);
+
+        let mut left = message(vec![]);
+        left.options = vec![pad(), pad()];
+
+        let mut right = message(vec![]);
+        right.options = vec![pad()];
+
+        assert_ne!(left, right);
+        assert_ne!(right, left);
+    }
+
+    #[test]
+    fn test_canonical_encoding() {
+        let pad = || DhcpOption::new(OptionTag::Pad, OptionData::Pad);
+        let end = || DhcpOption::new(OptionTag::End, OptionData::End);
+        let message_type = || {
+            DhcpOption::new(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Discover),
+            )
+        };
+
+        let encode = |message: &Message| {
+            let mut buf = WriteBuffer::new();
+            message.write::<BigEndian>(&mut buf).unwrap();
+            buf.bytes().to_vec()
+        };
+
+        let mut canonical = message(vec![]);
+        canonical.options = vec![message_type(), end()];
+
+        // Stray pads, a missing or misplaced End and short 'sname' and 'file'
+        // fields don't change the encoding
+        let mut padded = message(vec![]);
+        padded.options = vec![pad(), message_type(), pad(), end(), pad()];
+
+        let mut unterminated = message(vec![]);
+        unterminated.options = vec![message_type()];
+        unterminated.sname = vec![];
+        unterminated.file = vec![0; 4];
+
+        let mut misplaced = message(vec![]);
+        misplaced.options = vec![end(), message_type()];
+
+        let bytes = encode(&canonical);
+        assert_eq!(bytes.len(), 240 + 3 + 1);
+        assert_eq!(bytes[240..], [53, 1, 1, 255]);
+
+        for other in [padded, unterminated, misplaced] {
+            assert_eq!(encode(&other), bytes);
+        }
+    }
+
+    #[test]
+    fn test_bootp_vendor_round_trip() {
+        let mut message = Message::new_with_xid(0x1234);
+        message.set_hardware_address(
+            HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap(),
+        );
+        message.file[..8].copy_from_slice(b"vmunix\0\0");
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+
+        // BOOTP request with a 64 octet vendor area of the RFC 951 style
+        // "vend" magic instead of the DHCP cookie
+        let mut bytes = buf.bytes()[..236].to_vec();
+        let mut vendor = b"vend".to_vec();
+        vendor.extend((0..60).map(|i| i as u8));
+        bytes.extend(&vendor);
+
+        let read = Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes)).unwrap();
+        assert_eq!(read.vendor.as_ref(), Some(&vendor));
+        assert!(read.options.is_empty());
+        assert_eq!(read.header.xid, 0x1234);
+
+        let mut buf = WriteBuffer::new();
+        let n = read.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(n, bytes.len());
+        assert_eq!(buf.bytes(), bytes);
+
+        // DHCP messages don't have raw vendor extensions
+        let mut buf = WriteBuffer::new();
+        message.end().unwrap();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        let read = Message::read::<BigEndian>(&mut ReadBuffer::new(buf.bytes())).unwrap();
+        assert_eq!(read.vendor, None);
+    }
+
+    #[test]
+    fn test_parse_error_kind() {
+        let mut buf = WriteBuffer::new();
+        message(vec![(OptionTag::End, OptionData::End)])
+            .write::<BigEndian>(&mut buf)
+            .unwrap();
+        let fixed = &buf.bytes()[..240];
+
+        let kind = |options: &[u8]| {
+            let bytes = [fixed, options].concat();
+            Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes))
+                .unwrap_err()
+                .kind()
+        };
+
+        assert_eq!(kind(&[53, 1, 1, 12]), ParseErrorKind::Truncated);
+        assert_eq!(kind(&[200, 1, 0, 255]), ParseErrorKind::UnknownOption);
+        assert_eq!(kind(&[12, 8, b'h', 255]), ParseErrorKind::BadOptionLength);
+        assert_eq!(
+            kind(&[3, 3, 10, 0, 0, 255]),
+            ParseErrorKind::BadOptionLength
+        );
+        assert_eq!(kind(&[53, 1, 99, 255]), ParseErrorKind::Invalid);
+
+        let truncated = &fixed[..100];
+        assert_eq!(
+            Message::read::<BigEndian>(&mut ReadBuffer::new(truncated))
+                .unwrap_err()
+                .kind(),
+            ParseErrorKind::Truncated
+        );
+    }
+
+    #[test]
+    fn test_read_without_options() {
+        let mut buf = WriteBuffer::new();
+        message(vec![(OptionTag::End, OptionData::End)])
+            .write::<BigEndian>(&mut buf)
+            .unwrap();
+
+        // The message ends right after the magic cookie
+        let cookie_only = &buf.bytes()[..240];
+        let message = Message::read::<BigEndian>(&mut ReadBuffer::new(cookie_only)).unwrap();
+        assert!(message.options.is_empty());
+        assert_eq!(message.vendor, None);
+
+        // A tag without length is truncated, not a message without options
+        let bytes = [cookie_only, &[12]].concat();
+        assert!(matches!(
+            Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes)),
+            Err(MessageError::TruncatedOption(12))
+        ));
+
+        // Without cookie, the message ends before the options field
+        assert!(matches!(
+            Message::read::<BigEndian>(&mut ReadBuffer::new(&cookie_only[..236])),
+            Err(MessageError::BufferError(BufferError::BufTooShort))
+        ));
+    }
+
+    #[test]
+    fn test_read_concatenated_options() {
+        let mut buf = WriteBuffer::new();
+        message(vec![(OptionTag::End, OptionData::End)])
+            .write::<BigEndian>(&mut buf)
+            .unwrap();
+        let fixed = &buf.bytes()[..240];
+
+        // The parameter request list is split around the message type
+        let options = [55, 2, 1, 3, 53, 1, 1, 55, 1, 6, 255];
+        let bytes = [fixed, &options].concat();
+        let message = Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes)).unwrap();
+
+        assert_eq!(
+            message.get_parameter_request_list().unwrap().tags(),
+            [
+                OptionTag::SubnetMask,
+                OptionTag::Router,
+                OptionTag::DomainNameServer
+            ]
+        );
+        assert_eq!(
+            message.options[0].header().tag,
+            OptionTag::ParameterRequestList
+        );
+        assert_eq!(message.options[1].header().tag, OptionTag::DhcpMessageType);
+        assert_eq!(message.options.len(), 3);
+
+        let options = message.options_map();
+        assert_eq!(options.len(), 2);
+        assert!(matches!(
+            options[&OptionTag::ParameterRequestList],
+            OptionData::ParameterRequestList(list) if list.tags().len() == 3
+        ));
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        let message = message(vec![
+            (
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Discover),
+            ),
+            (OptionTag::End, OptionData::End),
+        ]);
+
+        let hex = message.to_hex().unwrap();
+        let lines: Vec<_> = hex.lines().collect();
+        assert!(lines[..lines.len() - 1]
+            .iter()
+            .all(|line| line.split(' ').count() == 16));
+        assert!(lines[0].starts_with("01 01 06 00"));
+
+        let parsed = Message::from_hex(&hex).unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), message.to_bytes().unwrap());
+        assert_eq!(
+            Message::from_hex(&hex.replace([' ', '\n'], ""))
+                .unwrap()
+                .header,
+            message.header
+        );
+
+        assert!(matches!(
+            Message::from_hex("01 0"),
+            Err(MessageError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Message::from_hex("01 +1"),
+            Err(MessageError::InvalidHex(_))
+        ));
+    }
+
+    #[test]
+    fn test_overloaded_options_round_trip() {
+        let servers: Vec<_> = (1..=20).map(|i| Ipv4Addr::new(10, 0, 0, i)).collect();
+        let mut overloaded = message(vec![
+            (
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Offer),
+            ),
+            (
+                OptionTag::ServerIdentifier,
+                OptionData::ServerIdentifier(Ipv4Addr::new(10, 0, 0, 1)),
+            ),
+            (
+                OptionTag::DomainNameServer,
+                OptionData::DomainNameServer(servers.clone()),
+            ),
+            (
+                OptionTag::Router,
+                OptionData::Router(servers[..10].to_vec()),
+            ),
+            (OptionTag::HostName, OptionData::HostName("x".repeat(40))),
+            (OptionTag::End, OptionData::End),
+        ]);
+        let original = overloaded.clone();
+
+        // Everything fits, nothing changes
+        assert_eq!(overloaded.overload_options(312).unwrap(), None);
+        assert_eq!(overloaded, original);
+
+        // The message type and server identifier stay in the options field,
+        // the servers go to 'file' and the host name to 'sname'
+        assert_eq!(
+            overloaded.overload_options(20).unwrap(),
+            Some(OptionOverload::Both)
+        );
+        assert_eq!(overloaded.options.len(), 4);
+        assert_eq!(overloaded.file[0], 6);
+        assert_eq!(overloaded.sname[0], 12);
+
+        let mut buf = WriteBuffer::new();
+        let len = overloaded.write::<BigEndian>(&mut buf).unwrap();
+        assert!(len - 240 <= 20, "{len}");
+
+        // The fields are kept as they were on the wire
+        let parsed = Message::from_bytes(buf.bytes()).unwrap();
+        assert_eq!(parsed.file, overloaded.file);
+        assert_eq!(parsed.sname, overloaded.sname);
+
+        let mut options = parsed.options_map();
+        assert!(matches!(
+            options.remove(&OptionTag::OptionOverload),
+            Some(OptionData::OptionOverload(OptionOverload::Both))
+        ));
+        assert_eq!(options, original.options_map());
+
+        // Fields with content are left alone
+        let mut named = original.clone();
+        named.sname[..4].copy_from_slice(b"boot");
+        assert_eq!(
+            named.overload_options(100).unwrap(),
+            Some(OptionOverload::File)
+        );
+        assert_eq!(&named.sname[..4], b"boot");
+
+        let mut buf = WriteBuffer::new();
+        named.write::<BigEndian>(&mut buf).unwrap();
+        let parsed = Message::from_bytes(buf.bytes()).unwrap();
+        assert_eq!(parsed.options_map().len(), original.options_map().len() + 1);
+
+        // No room left for the servers
+        let mut full = original.clone();
+        full.file[0] = b'x';
+        full.sname[0] = b'x';
+        assert!(matches!(
+            full.overload_options(20),
+            Err(MessageError::OptionsTooLarge(..))
+        ));
+        assert_eq!(full.options, original.options);
+    }
+
+    #[test]
+    #[should_panic(expected = "xid")]
+    fn test_assert_messages_equivalent_panics() {
+        let left = Message::new_with_xid(1);
+        let right = Message::new_with_xid(2);
+
+        crate::assert_messages_equivalent!(left, right);
+    }
+
+    #[test]
+    fn test_validate_hardware_fields() {
+        let mac = vec![0xde, 0xad, 0xbe, 0xef, 0x12, 0x34];
+
+        // (hlen, client identifier, valid)
+        let cases: Vec<(u8, Option<ClientIdentifier>, bool)> = vec![
+            (6, None, true),
+            (6, Some(ClientIdentifier::new(1, mac.clone())), true),
+            (
+                6,
+                Some(ClientIdentifier::new(0, b"opaque-id".to_vec())),
+                true,
+            ),
+            (
+                6,
+                Some(ClientIdentifier::new(255, b"iaid-and-duid".to_vec())),
+                true,
+            ),
+            (16, None, false),
+            (4, None, false),
+            (6, Some(ClientIdentifier::new(1, mac[..4].to_vec())), false),
+            (6, Some(ClientIdentifier::new(6, mac.clone())), false),
+        ];
+
+        for (hlen, ident, valid) in cases {
+            let mut message = Message::new_with_xid(1);
+            message.set_hardware_address(HardwareAddr::try_from(&mac[..]).unwrap());
+            message.header.hlen = hlen;
+
+            if let Some(ident) = ident.clone() {
+                message
+                    .add_option_parts(
+                        OptionTag::ClientIdentifier,
+                        OptionData::ClientIdentifier(ident),
+                    )
+                    .unwrap();
+            }
+
+            assert_eq!(message.validate().is_ok(), valid, "hlen {hlen}, {ident:?}");
+        }
+    }
+
+    #[test]
+    fn test_validate_echoed_client_identifier() {
+        let mac = vec![0xde, 0xad, 0xbe, 0xef, 0x12, 0x34];
+
+        // (message type, yiaddr)
+        let cases = [
+            (DhcpMessageType::Offer, Ipv4Addr::new(192, 168, 1, 100)),
+            (DhcpMessageType::Ack, Ipv4Addr::new(192, 168, 1, 100)),
+            (DhcpMessageType::Ack, Ipv4Addr::UNSPECIFIED),
+            (DhcpMessageType::Nak, Ipv4Addr::UNSPECIFIED),
+        ];
+
+        for (ty, yiaddr) in cases {
+            let mut message = Message::new_with_xid(1);
+            message.header.opcode = OpCode::BootReply;
+            message.set_hardware_address(HardwareAddr::try_from(&mac[..]).unwrap());
+            message.yiaddr = yiaddr;
+
+            let mut options = vec![
+                OptionData::DhcpMessageType(ty.clone()),
+                OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)),
+                OptionData::ClientIdentifier(ClientIdentifier::new(1, mac.clone())),
+            ];
+            if yiaddr != Ipv4Addr::UNSPECIFIED {
+                options.push(OptionData::IpAddrLeaseTime(3600));
+            }
+
+            for data in options {
+                let tag = match data {
+                    OptionData::DhcpMessageType(_) => OptionTag::DhcpMessageType,
+                    OptionData::ServerIdentifier(_) => OptionTag::ServerIdentifier,
+                    OptionData::ClientIdentifier(_) => OptionTag::ClientIdentifier,
+                    _ => OptionTag::IpAddrLeaseTime,
+                };
+                message.add_option_parts(tag, data).unwrap();
+            }
+            message.end().unwrap();
+
+            assert!(message.validate().is_ok(), "{ty:?}, {yiaddr}");
+        }
+    }
 }
@@ -4,14 +4,20 @@ mod addr;
 mod header;
 mod htype;
 mod lease;
+mod lease_time;
 mod message;
+mod net;
 mod opcode;
 mod option;
+mod view;
 
 pub use addr::*;
 pub use header::*;
 pub use htype::*;
 pub use lease::*;
+pub use lease_time::*;
 pub use message::*;
+pub use net::*;
 pub use opcode::*;
 pub use option::*;
+pub use view::*;
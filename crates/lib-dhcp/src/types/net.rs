@@ -0,0 +1,220 @@
+use std::{
+    fmt::Display,
+    net::{AddrParseError, Ipv4Addr},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Ipv4NetError {
+    #[error("invalid network, expected '<addr>/<prefix len>'")]
+    InvalidFormat,
+
+    #[error("invalid IP address: {0}")]
+    AddrParseError(#[from] AddrParseError),
+
+    #[error("invalid prefix length: {0}")]
+    ParseIntError(#[from] ParseIntError),
+
+    #[error("invalid prefix length - expected <= 32, got {0}")]
+    InvalidPrefixLen(u8),
+
+    #[error("invalid netmask {0}, netmasks must be contiguous")]
+    InvalidNetmask(Ipv4Addr),
+}
+
+/// An IPv4 network, like `192.168.1.0/24`. The address doesn't need to be
+/// the network address, `192.168.1.10/24` describes the same network as an
+/// interface address usually does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ipv4Net {
+    addr: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Display for Ipv4Net {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for Ipv4Net {
+    type Err = Ipv4NetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some(parts) => parts,
+            None => return Err(Ipv4NetError::InvalidFormat),
+        };
+
+        Self::new(addr.parse()?, prefix_len.parse()?)
+    }
+}
+
+impl Ipv4Net {
+    pub fn new(addr: Ipv4Addr, prefix_len: u8) -> Result<Self, Ipv4NetError> {
+        if prefix_len > 32 {
+            return Err(Ipv4NetError::InvalidPrefixLen(prefix_len));
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Creates a network from an address and a netmask, like
+    /// `255.255.255.0`.
+    pub fn with_netmask(addr: Ipv4Addr, netmask: Ipv4Addr) -> Result<Self, Ipv4NetError> {
+        let mask = u32::from(netmask);
+        let prefix_len = mask.leading_ones();
+
+        if mask.checked_shl(prefix_len).unwrap_or(0) != 0 {
+            return Err(Ipv4NetError::InvalidNetmask(netmask));
+        }
+
+        Self::new(addr, prefix_len as u8)
+    }
+
+    pub fn addr(&self) -> Ipv4Addr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.mask())
+    }
+
+    /// Returns the network address, all host bits set to zero.
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) & self.mask())
+    }
+
+    /// Returns the broadcast address, all host bits set to one.
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) | !self.mask())
+    }
+
+    pub fn contains(&self, addr: &Ipv4Addr) -> bool {
+        u32::from(*addr) & self.mask() == u32::from(self.network())
+    }
+
+    fn mask(&self) -> u32 {
+        u32::MAX
+            .checked_shl(32 - self.prefix_len as u32)
+            .unwrap_or(0)
+    }
+}
+
+/// Iterator over an inclusive range of IPv4 addresses. The range is empty
+/// if `start` is greater than `end`, iterating stops at `end` even if it is
+/// `255.255.255.255`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv4AddrRange {
+    next: Option<u32>,
+    end: u32,
+}
+
+impl Ipv4AddrRange {
+    pub fn new(start: Ipv4Addr, end: Ipv4Addr) -> Self {
+        let (start, end) = (u32::from(start), u32::from(end));
+
+        Self {
+            next: (start <= end).then_some(start),
+            end,
+        }
+    }
+}
+
+impl Iterator for Ipv4AddrRange {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let addr = self.next?;
+
+        // Checked, the end might be the last address there is
+        self.next = addr.checked_add(1).filter(|next| *next <= self.end);
+        Some(Ipv4Addr::from(addr))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.next.map_or(0, |next| (self.end - next) as usize + 1);
+        (len, Some(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipv4_net_from_str() {
+        match Ipv4Net::from_str("192.168.1.10/24") {
+            Ok(net) => {
+                assert_eq!(net.network(), Ipv4Addr::new(192, 168, 1, 0));
+                assert_eq!(net.broadcast(), Ipv4Addr::new(192, 168, 1, 255));
+                assert_eq!(net.netmask(), Ipv4Addr::new(255, 255, 255, 0));
+                assert!(net.contains(&Ipv4Addr::new(192, 168, 1, 200)));
+                assert!(!net.contains(&Ipv4Addr::new(192, 168, 2, 1)));
+            }
+            Err(err) => panic!("{}", err),
+        }
+
+        assert!(Ipv4Net::from_str("192.168.1.10").is_err());
+        assert!(Ipv4Net::from_str("192.168.1.10/33").is_err());
+    }
+
+    #[test]
+    fn test_ipv4_net_with_netmask() {
+        let net = Ipv4Net::with_netmask(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 0, 0, 0));
+        assert_eq!(net.unwrap().prefix_len(), 8);
+
+        let net = Ipv4Net::with_netmask(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(0, 0, 0, 0));
+        assert_eq!(net.unwrap().prefix_len(), 0);
+
+        let net = Ipv4Net::with_netmask(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(255, 0, 255, 0));
+        assert!(net.is_err());
+    }
+
+    #[test]
+    fn test_ipv4_addr_range() {
+        let range = Ipv4AddrRange::new(
+            Ipv4Addr::new(192, 168, 1, 254),
+            Ipv4Addr::new(192, 168, 2, 1),
+        );
+        assert_eq!(range.size_hint(), (4, Some(4)));
+        assert_eq!(
+            range.collect::<Vec<_>>(),
+            vec![
+                Ipv4Addr::new(192, 168, 1, 254),
+                Ipv4Addr::new(192, 168, 1, 255),
+                Ipv4Addr::new(192, 168, 2, 0),
+                Ipv4Addr::new(192, 168, 2, 1),
+            ]
+        );
+
+        // A single address and an empty range
+        let addr = Ipv4Addr::new(10, 0, 0, 1);
+        assert_eq!(Ipv4AddrRange::new(addr, addr).count(), 1);
+        assert_eq!(
+            Ipv4AddrRange::new(addr, Ipv4Addr::new(10, 0, 0, 0)).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_ipv4_addr_range_ends_at_broadcast() {
+        let mut range = Ipv4AddrRange::new(Ipv4Addr::new(255, 255, 255, 254), Ipv4Addr::BROADCAST);
+        assert_eq!(range.next(), Some(Ipv4Addr::new(255, 255, 255, 254)));
+        assert_eq!(range.next(), Some(Ipv4Addr::BROADCAST));
+        assert_eq!(range.next(), None);
+        assert_eq!(range.next(), None);
+
+        assert_eq!(
+            Ipv4AddrRange::new(Ipv4Addr::BROADCAST, Ipv4Addr::BROADCAST).count(),
+            1
+        );
+    }
+}
@@ -12,8 +12,9 @@ pub enum OpCodeError {
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum OpCode {
+    #[default]
     BootRequest,
     BootReply,
 }
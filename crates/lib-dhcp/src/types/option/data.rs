@@ -3,17 +3,18 @@ use std::net::Ipv4Addr;
 use binbuf::prelude::*;
 use thiserror::Error;
 
-use crate::{
-    types::{
-        options::{
-            ClassIdentifier, ClientIdentifier, DhcpMessageType, ParameterRequestList,
-            ParameterRequestListError,
-        },
-        OptionHeader, OptionTag,
+use crate::types::{
+    options::{
+        Authentication, ClassIdentifier, ClientFqdn, ClientIdentifier, DhcpMessageType,
+        DomainSearch, DomainSearchError, OptionOverload, ParameterRequestList,
+        ParameterRequestListError, RelayAgentInformation, VendorIdentifying,
     },
-    MINIMUM_LEGAL_MAX_MESSAGE_SIZE,
+    OptionHeader, OptionTag,
 };
 
+/// Minimum MTU of the interface MTU option (26).
+const MIN_INTERFACE_MTU: u16 = 68;
+
 #[derive(Debug, Error)]
 pub enum OptionDataError {
     #[error("Invalid DHCP message size")]
@@ -22,14 +23,20 @@ pub enum OptionDataError {
     #[error("Invalid option data")]
     InvalidData,
 
+    #[error("Invalid option length {0}")]
+    InvalidLength(u8),
+
     #[error("Parameter request list error: {0}")]
     ParameterRequestListError(#[from] ParameterRequestListError),
 
+    #[error("Domain search error: {0}")]
+    DomainSearchError(#[from] DomainSearchError),
+
     #[error("Buffer error: {0}")]
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OptionData {
     Pad,
     End,
@@ -47,7 +54,18 @@ pub enum OptionData {
     HostName(String),
     BootFileSize(u16),
     MeritDumpFile,
-    DomainName,
+
+    /// #### Domain Name
+    ///
+    /// The code for this option is 15. Its minimum length is 1.
+    ///
+    /// ```text
+    ///  Code   Len        Domain Name
+    /// +-----+-----+-----+-----+-----+-----+--
+    /// |  15 |  n  |  d1 |  d2 |  d3 |  d4 |  ...
+    /// +-----+-----+-----+-----+-----+-----+--
+    /// ```
+    DomainName(String),
     SwapServer,
     RootPath,
     ExtensionsPath,
@@ -55,17 +73,59 @@ pub enum OptionData {
     NonLocalSourceRouting,
     PolicyFilter,
     MaxDatagramReassemblySize,
-    DefaultIpTtl,
+
+    /// #### Default IP Time-to-live
+    ///
+    /// The code for this option is 23. The length is 1, and the value is
+    /// between 1 and 255.
+    ///
+    /// ```text
+    ///  Code   Len   TTL
+    /// +-----+-----+-----+
+    /// |  23 |  1  | ttl |
+    /// +-----+-----+-----+
+    /// ```
+    DefaultIpTtl(u8),
     PathMtuAgingTimeout,
     PathMtuPlateauTable,
-    InterfaceMtu,
+
+    /// #### Interface MTU Option
+    ///
+    /// The code for this option is 26. The length is 2, and the minimum
+    /// value of the MTU is 68.
+    ///
+    /// ```text
+    ///  Code   Len      MTU
+    /// +-----+-----+-----+-----+
+    /// |  26 |  2  |  m1 |  m2 |
+    /// +-----+-----+-----+-----+
+    /// ```
+    InterfaceMtu(u16),
     AllSubnetsLocal,
     BroadcastAddr,
     PerformMaskDiscovery,
     MaskSupplier,
     PerformRouterDiscovery,
     RouterSolicitationAddr,
-    StaticRoute,
+
+    /// #### Static Route
+    ///
+    /// The code for this option is 33. The minimum length is 8, and the
+    /// length must be a multiple of 8. Each route is a pair of destination
+    /// and router address. The default route (0.0.0.0) is an illegal
+    /// destination.
+    ///
+    /// ```text
+    ///  Code   Len         Destination 1           Router 1
+    /// +-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+
+    /// |  33 |  n  |  d1 |  d2 |  d3 |  d4 |  r1 |  r2 |  r3 |  r4 |
+    /// +-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+
+    ///         Destination 2           Router 2
+    /// +-----+-----+-----+-----+-----+-----+-----+-----+---
+    /// |  d1 |  d2 |  d3 |  d4 |  r1 |  r2 |  r3 |  r4 | ...
+    /// +-----+-----+-----+-----+-----+-----+-----+-----+---
+    /// ```
+    StaticRoute(Vec<(Ipv4Addr, Ipv4Addr)>),
     TrailerEncapsulation,
     ArpCacheTimeout,
     EthernetEncapsulation,
@@ -74,8 +134,21 @@ pub enum OptionData {
     TcpKeepaliveGarbage,
     NetworkInformationServiceDomain,
     NetworkInformationServers,
-    NetworkTimeProtocolServers,
-    VendorSpecificInformation,
+    NetworkTimeProtocolServers(Vec<Ipv4Addr>),
+
+    /// #### Vendor Specific Information
+    ///
+    /// The code for this option is 43 and its minimum length is 1. The
+    /// content is defined by the vendor and kept as is. Most vendors
+    /// encapsulate sub-options, see [`decode_vendor_sub_options`](crate::types::options::decode_vendor_sub_options).
+    ///
+    /// ```text
+    /// Code   Len   Vendor-specific information
+    /// +-----+-----+-----+-----+---
+    /// |  43 |  n  |  i1 |  i2 | ...
+    /// +-----+-----+-----+-----+---
+    /// ```
+    VendorSpecificInformation(Vec<u8>),
     NetbiosNameServer,
     NetbiosDatagramDistributionServer,
     NetbiosNodeType,
@@ -95,7 +168,20 @@ pub enum OptionData {
     /// ```
     RequestedIpAddr(Ipv4Addr),
     IpAddrLeaseTime(u32),
-    OptionOverload,
+
+    /// #### Option Overload
+    ///
+    /// The code for this option is 52, and its length is 1. The value tells
+    /// if the 'file' (1), the 'sname' (2) or both fields (3) hold options.
+    ///
+    /// ```text
+    ///  Code   Len  Value
+    /// +-----+-----+-----+
+    /// |  52 |  1  |1/2/3|
+    /// +-----+-----+-----+
+    /// ```
+    OptionOverload(OptionOverload),
+
     /// #### DHCP Message Type
     ///
     /// ```text
@@ -118,7 +204,19 @@ pub enum OptionData {
     /// +-----+-----+-----+-----+---
     /// ```
     ParameterRequestList(ParameterRequestList),
-    Message,
+
+    /// #### Message
+    ///
+    /// The code for this option is 56 and its minimum length is 1. Servers
+    /// use it to explain a DHCPNAK, clients to explain a DHCPDECLINE.
+    ///
+    /// ```text
+    ///  Code   Len     Text
+    /// +-----+-----+-----+-----+---
+    /// |  56 |  n  |  c1 |  c2 | ...
+    /// +-----+-----+-----+-----+---
+    /// ```
+    Message(String),
 
     /// #### Maximum DHCP Message Size
     ///
@@ -156,6 +254,33 @@ pub enum OptionData {
     /// +-----+-----+-----+-----+-----+---
     /// ```
     ClientIdentifier(ClientIdentifier),
+
+    /// #### Domain Search
+    ///
+    /// See [`DomainSearch`] for the encoding.
+    DomainSearch(DomainSearch),
+
+    /// See [`RelayAgentInformation`] for the encoding.
+    RelayAgentInformation(RelayAgentInformation),
+
+    /// See [`ClientFqdn`] for the encoding.
+    ClientFqdn(ClientFqdn),
+
+    /// See [`Authentication`] for the encoding.
+    Authentication(Authentication),
+
+    /// Seconds since the client last sent a message to the server, see
+    /// RFC 4388.
+    ClientLastTransactionTime(u32),
+
+    /// All addresses bound to the queried client, see RFC 4388.
+    AssociatedIp(Vec<Ipv4Addr>),
+
+    /// See [`VendorIdentifying`] for the encoding.
+    VendorIdentifyingVendorClass(VendorIdentifying),
+
+    /// See [`VendorIdentifying`] for the encoding.
+    VendorIdentifyingVendorSpecific(VendorIdentifying),
 }
 
 impl Writeable for OptionData {
@@ -163,8 +288,9 @@ impl Writeable for OptionData {
 
     fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
         let n = match self {
-            OptionData::Pad => 0u8.write::<E>(buf)?,
-            OptionData::End => 255u8.write::<E>(buf)?,
+            // The tag is the only octet of fixed length options and is
+            // already written by the option header
+            OptionData::Pad | OptionData::End => 0,
             OptionData::SubnetMask(mask) => mask.write::<E>(buf)?,
             OptionData::TimeOffset(off) => off.write::<E>(buf)?,
             OptionData::Router(ips) => ips.write::<E>(buf)?,
@@ -179,7 +305,7 @@ impl Writeable for OptionData {
             OptionData::HostName(name) => name.write::<E>(buf)?,
             OptionData::BootFileSize(size) => size.write::<E>(buf)?,
             OptionData::MeritDumpFile => todo!(),
-            OptionData::DomainName => todo!(),
+            OptionData::DomainName(name) => name.write::<E>(buf)?,
             OptionData::SwapServer => todo!(),
             OptionData::RootPath => todo!(),
             OptionData::ExtensionsPath => todo!(),
@@ -187,17 +313,27 @@ impl Writeable for OptionData {
             OptionData::NonLocalSourceRouting => todo!(),
             OptionData::PolicyFilter => todo!(),
             OptionData::MaxDatagramReassemblySize => todo!(),
-            OptionData::DefaultIpTtl => todo!(),
+            OptionData::DefaultIpTtl(ttl) => {
+                buf.push(*ttl);
+                1
+            }
             OptionData::PathMtuAgingTimeout => todo!(),
             OptionData::PathMtuPlateauTable => todo!(),
-            OptionData::InterfaceMtu => todo!(),
+            OptionData::InterfaceMtu(mtu) => mtu.write::<E>(buf)?,
             OptionData::AllSubnetsLocal => todo!(),
             OptionData::BroadcastAddr => todo!(),
             OptionData::PerformMaskDiscovery => todo!(),
             OptionData::MaskSupplier => todo!(),
             OptionData::PerformRouterDiscovery => todo!(),
             OptionData::RouterSolicitationAddr => todo!(),
-            OptionData::StaticRoute => todo!(),
+            OptionData::StaticRoute(routes) => {
+                let mut n = 0;
+                for (destination, router) in routes {
+                    n += destination.write::<E>(buf)?;
+                    n += router.write::<E>(buf)?;
+                }
+                n
+            }
             OptionData::TrailerEncapsulation => todo!(),
             OptionData::ArpCacheTimeout => todo!(),
             OptionData::EthernetEncapsulation => todo!(),
@@ -206,8 +342,8 @@ impl Writeable for OptionData {
             OptionData::TcpKeepaliveGarbage => todo!(),
             OptionData::NetworkInformationServiceDomain => todo!(),
             OptionData::NetworkInformationServers => todo!(),
-            OptionData::NetworkTimeProtocolServers => todo!(),
-            OptionData::VendorSpecificInformation => todo!(),
+            OptionData::NetworkTimeProtocolServers(ips) => ips.write::<E>(buf)?,
+            OptionData::VendorSpecificInformation(info) => buf.write(info.as_slice()),
             OptionData::NetbiosNameServer => todo!(),
             OptionData::NetbiosDatagramDistributionServer => todo!(),
             OptionData::NetbiosNodeType => todo!(),
@@ -216,16 +352,24 @@ impl Writeable for OptionData {
             OptionData::XWindowSystemDisplayManager => todo!(),
             OptionData::RequestedIpAddr(ip) => ip.write::<E>(buf)?,
             OptionData::IpAddrLeaseTime(time) => time.write::<E>(buf)?,
-            OptionData::OptionOverload => todo!(),
+            OptionData::OptionOverload(overload) => overload.write::<E>(buf)?,
             OptionData::DhcpMessageType(ty) => ty.write::<E>(buf)?,
             OptionData::ServerIdentifier(ip) => ip.write::<E>(buf)?,
             OptionData::ParameterRequestList(list) => list.write::<E>(buf)?,
-            OptionData::Message => todo!(),
+            OptionData::Message(text) => text.write::<E>(buf)?,
             OptionData::MaxDhcpMessageSize(size) => size.write::<E>(buf)?,
             OptionData::RenewalT1Time(time) => time.write::<E>(buf)?,
             OptionData::RebindingT2Time(time) => time.write::<E>(buf)?,
-            OptionData::ClassIdentifier(_) => todo!(),
+            OptionData::ClassIdentifier(c) => c.write::<E>(buf)?,
             OptionData::ClientIdentifier(c) => c.write::<E>(buf)?,
+            OptionData::DomainSearch(s) => s.write::<E>(buf)?,
+            OptionData::RelayAgentInformation(info) => info.write::<E>(buf)?,
+            OptionData::ClientFqdn(fqdn) => fqdn.write::<E>(buf)?,
+            OptionData::Authentication(auth) => auth.write::<E>(buf)?,
+            OptionData::ClientLastTransactionTime(time) => time.write::<E>(buf)?,
+            OptionData::AssociatedIp(ips) => ips.write::<E>(buf)?,
+            OptionData::VendorIdentifyingVendorClass(v) => v.write::<E>(buf)?,
+            OptionData::VendorIdentifyingVendorSpecific(v) => v.write::<E>(buf)?,
         };
 
         Ok(n)
@@ -274,14 +418,32 @@ impl OptionData {
                 let ips = read_ip_addrs_set::<E>(buf, header.len)?;
                 Self::ImpressServer(ips)
             }
-            OptionTag::ResourceLocationServer => todo!(),
+            OptionTag::ResourceLocationServer => {
+                let ips = read_ip_addrs_set::<E>(buf, header.len)?;
+                Self::ResourceLocationServer(ips)
+            }
             OptionTag::HostName => {
                 let b = buf.read_vec(header.len as usize)?;
-                Self::HostName(String::from_utf8(b).unwrap())
+                // Clients send all kinds of garbage, sanitizing is up to the
+                // consumer
+                Self::HostName(String::from_utf8_lossy(&b).into_owned())
+            }
+            OptionTag::BootFileSize => {
+                // The size of the boot file in 512-octet blocks
+                if header.len != 2 {
+                    return Err(OptionDataError::InvalidData);
+                }
+
+                Self::BootFileSize(u16::read::<E>(buf)?)
             }
-            OptionTag::BootFileSize => todo!(),
             OptionTag::MeritDumpFile => todo!(),
-            OptionTag::DomainName => todo!(),
+            OptionTag::DomainName => {
+                let b = buf.read_vec(header.len as usize)?;
+                match String::from_utf8(b) {
+                    Ok(name) => Self::DomainName(name),
+                    Err(_) => return Err(OptionDataError::InvalidData),
+                }
+            }
             OptionTag::SwapServer => todo!(),
             OptionTag::RootPath => todo!(),
             OptionTag::ExtensionsPath => todo!(),
@@ -289,17 +451,29 @@ impl OptionData {
             OptionTag::NonLocalSourceRouting => todo!(),
             OptionTag::PolicyFilter => todo!(),
             OptionTag::MaxDatagramReassemblySize => todo!(),
-            OptionTag::DefaultIpTtl => todo!(),
+            OptionTag::DefaultIpTtl => {
+                if header.len != 1 {
+                    return Err(OptionDataError::InvalidData);
+                }
+
+                match buf.pop()? {
+                    0 => return Err(OptionDataError::InvalidData),
+                    ttl => Self::DefaultIpTtl(ttl),
+                }
+            }
             OptionTag::PathMtuAgingTimeout => todo!(),
             OptionTag::PathMtuPlateauTable => todo!(),
-            OptionTag::InterfaceMtu => todo!(),
+            OptionTag::InterfaceMtu => match u16::read::<E>(buf)? {
+                ..MIN_INTERFACE_MTU => return Err(OptionDataError::InvalidData),
+                mtu => Self::InterfaceMtu(mtu),
+            },
             OptionTag::AllSubnetsLocal => todo!(),
             OptionTag::BroadcastAddr => todo!(),
             OptionTag::PerformMaskDiscovery => todo!(),
             OptionTag::MaskSupplier => todo!(),
             OptionTag::PerformRouterDiscovery => todo!(),
             OptionTag::RouterSolicitationAddr => todo!(),
-            OptionTag::StaticRoute => todo!(),
+            OptionTag::StaticRoute => Self::StaticRoute(read_static_routes::<E>(buf, header.len)?),
             OptionTag::TrailerEncapsulation => todo!(),
             OptionTag::ArpCacheTimeout => todo!(),
             OptionTag::EthernetEncapsulation => todo!(),
@@ -308,8 +482,13 @@ impl OptionData {
             OptionTag::TcpKeepaliveGarbage => todo!(),
             OptionTag::NetworkInformationServiceDomain => todo!(),
             OptionTag::NetworkInformationServers => todo!(),
-            OptionTag::NetworkTimeProtocolServers => todo!(),
-            OptionTag::VendorSpecificInformation => todo!(),
+            OptionTag::NetworkTimeProtocolServers => {
+                let ips = read_ip_addrs_set::<E>(buf, header.len)?;
+                Self::NetworkTimeProtocolServers(ips)
+            }
+            OptionTag::VendorSpecificInformation => {
+                Self::VendorSpecificInformation(buf.read_vec(header.len as usize)?)
+            }
             OptionTag::NetbiosNameServer => todo!(),
             OptionTag::NetbiosDatagramDistributionServer => todo!(),
             OptionTag::NetbiosNodeType => todo!(),
@@ -318,22 +497,23 @@ impl OptionData {
             OptionTag::XWindowSystemDisplayManager => todo!(),
             OptionTag::RequestedIpAddr => Self::RequestedIpAddr(Ipv4Addr::read::<E>(buf)?),
             OptionTag::IpAddrLeaseTime => Self::IpAddrLeaseTime(u32::read::<E>(buf)?),
-            OptionTag::OptionOverload => todo!(),
+            OptionTag::OptionOverload => Self::OptionOverload(OptionOverload::read::<E>(buf)?),
             OptionTag::DhcpMessageType => Self::DhcpMessageType(DhcpMessageType::read::<E>(buf)?),
             OptionTag::ServerIdentifier => Self::ServerIdentifier(Ipv4Addr::read::<E>(buf)?),
             OptionTag::ParameterRequestList => {
                 Self::ParameterRequestList(ParameterRequestList::read::<E>(buf, header.len)?)
             }
-            OptionTag::Message => todo!(),
-            OptionTag::MaxDhcpMessageSize => {
-                let size = u16::read::<E>(buf)?;
-
-                if size < MINIMUM_LEGAL_MAX_MESSAGE_SIZE {
-                    return Err(OptionDataError::InvalidDhcpMessageSize);
-                }
-
-                Self::MaxDhcpMessageSize(size)
+            OptionTag::Message => {
+                // The text is only shown to humans, don't reject the whole
+                // message because of a stray byte
+                let b = buf.read_vec(header.len as usize)?;
+                Self::Message(String::from_utf8_lossy(&b).into_owned())
             }
+            // Values below the legal minimum of 576 are kept as is and must
+            // be treated as 576 by the receiver (RFC 2132 Section 9.10).
+            // Rejecting the complete message would leave the client without
+            // a reply.
+            OptionTag::MaxDhcpMessageSize => Self::MaxDhcpMessageSize(u16::read::<E>(buf)?),
             OptionTag::RenewalT1Time => Self::RenewalT1Time(u32::read::<E>(buf)?),
             OptionTag::RebindingT2Time => Self::RebindingT2Time(u32::read::<E>(buf)?),
             OptionTag::ClassIdentifier => {
@@ -343,7 +523,31 @@ impl OptionData {
                 Self::ClientIdentifier(ClientIdentifier::read::<E>(buf, header.len)?)
             }
             OptionTag::DhcpCaptivePortal => todo!(),
+            OptionTag::DomainSearch => {
+                Self::DomainSearch(DomainSearch::read::<E>(buf, header.len)?)
+            }
+            OptionTag::RelayAgentInformation => {
+                Self::RelayAgentInformation(RelayAgentInformation::read::<E>(buf, header.len)?)
+            }
+            OptionTag::ClientFqdn => Self::ClientFqdn(ClientFqdn::read::<E>(buf, header.len)?),
+            OptionTag::Authentication => {
+                Self::Authentication(Authentication::read::<E>(buf, header.len)?)
+            }
+            OptionTag::ClientLastTransactionTime => {
+                Self::ClientLastTransactionTime(u32::read::<E>(buf)?)
+            }
+            OptionTag::AssociatedIp => {
+                let ips = read_ip_addrs_set::<E>(buf, header.len)?;
+                Self::AssociatedIp(ips)
+            }
+            OptionTag::VendorIdentifyingVendorClass => {
+                Self::VendorIdentifyingVendorClass(VendorIdentifying::read::<E>(buf, header.len)?)
+            }
+            OptionTag::VendorIdentifyingVendorSpecific => Self::VendorIdentifyingVendorSpecific(
+                VendorIdentifying::read::<E>(buf, header.len)?,
+            ),
             OptionTag::UnassignedOrRemoved(_) => todo!(),
+            OptionTag::Unknown(_) => return Err(OptionDataError::InvalidData),
         };
 
         Ok(option_data)
@@ -367,7 +571,7 @@ impl OptionData {
             OptionData::HostName(h) => h.len() as u8,
             OptionData::BootFileSize(_) => 2,
             OptionData::MeritDumpFile => todo!(),
-            OptionData::DomainName => todo!(),
+            OptionData::DomainName(name) => name.len() as u8,
             OptionData::SwapServer => todo!(),
             OptionData::RootPath => todo!(),
             OptionData::ExtensionsPath => todo!(),
@@ -375,17 +579,17 @@ impl OptionData {
             OptionData::NonLocalSourceRouting => 1,
             OptionData::PolicyFilter => todo!(),
             OptionData::MaxDatagramReassemblySize => 2,
-            OptionData::DefaultIpTtl => 1,
+            OptionData::DefaultIpTtl(_) => 1,
             OptionData::PathMtuAgingTimeout => 4,
             OptionData::PathMtuPlateauTable => todo!(),
-            OptionData::InterfaceMtu => 2,
+            OptionData::InterfaceMtu(_) => 2,
             OptionData::AllSubnetsLocal => 1,
             OptionData::BroadcastAddr => 4,
             OptionData::PerformMaskDiscovery => 1,
             OptionData::MaskSupplier => 1,
             OptionData::PerformRouterDiscovery => 1,
             OptionData::RouterSolicitationAddr => 4,
-            OptionData::StaticRoute => todo!(),
+            OptionData::StaticRoute(routes) => (routes.len() * 8) as u8,
             OptionData::TrailerEncapsulation => 1,
             OptionData::ArpCacheTimeout => 4,
             OptionData::EthernetEncapsulation => 1,
@@ -394,8 +598,8 @@ impl OptionData {
             OptionData::TcpKeepaliveGarbage => 1,
             OptionData::NetworkInformationServiceDomain => todo!(),
             OptionData::NetworkInformationServers => todo!(),
-            OptionData::NetworkTimeProtocolServers => todo!(),
-            OptionData::VendorSpecificInformation => todo!(),
+            OptionData::NetworkTimeProtocolServers(ips) => (ips.len() * 4) as u8,
+            OptionData::VendorSpecificInformation(info) => info.len() as u8,
             OptionData::NetbiosNameServer => todo!(),
             OptionData::NetbiosDatagramDistributionServer => todo!(),
             OptionData::NetbiosNodeType => 1,
@@ -404,16 +608,76 @@ impl OptionData {
             OptionData::XWindowSystemDisplayManager => todo!(),
             OptionData::RequestedIpAddr(_) => 4,
             OptionData::IpAddrLeaseTime(_) => 4,
-            OptionData::OptionOverload => 1,
+            OptionData::OptionOverload(_) => 1,
             OptionData::DhcpMessageType(_) => 1,
             OptionData::ServerIdentifier(_) => 4,
             OptionData::ParameterRequestList(l) => l.len() as u8,
-            OptionData::Message => todo!(),
+            OptionData::Message(text) => text.len() as u8,
             OptionData::MaxDhcpMessageSize(_) => 2,
             OptionData::RenewalT1Time(_) => 4,
             OptionData::RebindingT2Time(_) => 4,
-            OptionData::ClassIdentifier(_) => todo!(),
+            OptionData::ClassIdentifier(c) => c.len() as u8,
             OptionData::ClientIdentifier(c) => c.len() as u8,
+            OptionData::DomainSearch(s) => s.len() as u8,
+            OptionData::RelayAgentInformation(info) => info.len() as u8,
+            OptionData::ClientFqdn(fqdn) => fqdn.len() as u8,
+            OptionData::Authentication(auth) => auth.len() as u8,
+            OptionData::ClientLastTransactionTime(_) => 4,
+            OptionData::AssociatedIp(ips) => (ips.len() * 4) as u8,
+            OptionData::VendorIdentifyingVendorClass(v) => v.len() as u8,
+            OptionData::VendorIdentifyingVendorSpecific(v) => v.len() as u8,
+        }
+    }
+
+    /// Returns the address list of options carrying a list of IPv4
+    /// addresses, like [`OptionData::Router`] or [`OptionData::TimeServer`].
+    pub fn as_ipv4_list(&self) -> Option<&[Ipv4Addr]> {
+        match self {
+            OptionData::Router(ips)
+            | OptionData::TimeServer(ips)
+            | OptionData::NameServer(ips)
+            | OptionData::DomainNameServer(ips)
+            | OptionData::LogServer(ips)
+            | OptionData::CookieServer(ips)
+            | OptionData::LprServer(ips)
+            | OptionData::ImpressServer(ips)
+            | OptionData::ResourceLocationServer(ips)
+            | OptionData::NetworkTimeProtocolServers(ips)
+            | OptionData::AssociatedIp(ips) => Some(ips),
+            _ => None,
+        }
+    }
+
+    /// Returns the address of options carrying a single IPv4 address, like
+    /// [`OptionData::SubnetMask`] or [`OptionData::ServerIdentifier`].
+    pub fn as_ipv4(&self) -> Option<Ipv4Addr> {
+        match self {
+            OptionData::SubnetMask(addr)
+            | OptionData::RequestedIpAddr(addr)
+            | OptionData::ServerIdentifier(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Returns the text of string options, like [`OptionData::HostName`] or
+    /// [`OptionData::DomainName`].
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            OptionData::HostName(s) | OptionData::DomainName(s) | OptionData::Message(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of 32 bit integer options, like
+    /// [`OptionData::IpAddrLeaseTime`] or [`OptionData::TimeOffset`].
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            OptionData::TimeOffset(n)
+            | OptionData::IpAddrLeaseTime(n)
+            | OptionData::RenewalT1Time(n)
+            | OptionData::RebindingT2Time(n)
+            | OptionData::ClientLastTransactionTime(n) => Some(*n),
+            _ => None,
         }
     }
 }
@@ -425,7 +689,7 @@ fn read_ip_addrs_set<E: Endianness>(
     len: u8,
 ) -> Result<Vec<Ipv4Addr>, OptionDataError> {
     if len < 4 || len % 4 != 0 {
-        return Err(OptionDataError::InvalidData);
+        return Err(OptionDataError::InvalidLength(len));
     }
 
     let mut ips = Vec::new();
@@ -436,3 +700,286 @@ fn read_ip_addrs_set<E: Endianness>(
 
     Ok(ips)
 }
+
+/// Reads the destination and router pairs of the static route option. The
+/// length must be a non-zero multiple of 8 and no destination may be the
+/// default route.
+fn read_static_routes<E: Endianness>(
+    buf: &mut ReadBuffer,
+    len: u8,
+) -> Result<Vec<(Ipv4Addr, Ipv4Addr)>, OptionDataError> {
+    if len == 0 || !len.is_multiple_of(8) {
+        return Err(OptionDataError::InvalidData);
+    }
+
+    let mut routes = Vec::new();
+
+    for _ in 0..len / 8 {
+        let destination = Ipv4Addr::read::<E>(buf)?;
+        let router = Ipv4Addr::read::<E>(buf)?;
+
+        if destination.is_unspecified() {
+            return Err(OptionDataError::InvalidData);
+        }
+
+        routes.push((destination, router));
+    }
+
+    Ok(routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DhcpOption;
+
+    #[test]
+    fn test_as_ipv4_list() {
+        let ips = vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)];
+
+        assert_eq!(
+            OptionData::TimeServer(ips.clone()).as_ipv4_list(),
+            Some(ips.as_slice())
+        );
+        assert_eq!(
+            OptionData::Router(ips.clone()).as_ipv4_list(),
+            Some(ips.as_slice())
+        );
+        assert_eq!(OptionData::HostName("host".into()).as_ipv4_list(), None);
+        assert_eq!(
+            OptionData::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)).as_ipv4_list(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_as_ipv4() {
+        let addr = Ipv4Addr::new(10, 0, 0, 1);
+
+        assert_eq!(OptionData::ServerIdentifier(addr).as_ipv4(), Some(addr));
+        assert_eq!(OptionData::Router(vec![addr]).as_ipv4(), None);
+    }
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!(
+            OptionData::HostName("host".into()).as_string(),
+            Some("host")
+        );
+        assert_eq!(
+            OptionData::DomainName("example.com".into()).as_string(),
+            Some("example.com")
+        );
+        assert_eq!(OptionData::IpAddrLeaseTime(3600).as_string(), None);
+    }
+
+    #[test]
+    fn test_as_u32() {
+        assert_eq!(OptionData::IpAddrLeaseTime(3600).as_u32(), Some(3600));
+        assert_eq!(OptionData::RenewalT1Time(1800).as_u32(), Some(1800));
+        assert_eq!(OptionData::BootFileSize(12).as_u32(), None);
+        assert_eq!(OptionData::HostName("host".into()).as_u32(), None);
+    }
+
+    #[test]
+    fn test_fixed_length_options() {
+        // Pad and End are a single tag octet, without length or data
+        for (tag, data, encoded) in [
+            (OptionTag::Pad, OptionData::Pad, 0),
+            (OptionTag::End, OptionData::End, 255),
+        ] {
+            let mut buf = WriteBuffer::new();
+            let n = DhcpOption::new(tag, data)
+                .write::<BigEndian>(&mut buf)
+                .unwrap();
+            assert_eq!(n, 1);
+            assert_eq!(buf.bytes(), &[encoded]);
+        }
+    }
+
+    #[test]
+    fn test_message_option() {
+        let option = DhcpOption::new(
+            OptionTag::Message,
+            OptionData::Message(String::from("address in use")),
+        );
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(&buf.bytes()[..2], &[56, 14]);
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+
+        // Invalid UTF-8 is replaced instead of rejecting the option
+        let data = [56, 3, b'n', 0xff, b'k'];
+        let mut buf = ReadBuffer::new(&data);
+        let option = DhcpOption::read::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(option.data().as_string(), Some("n\u{fffd}k"));
+    }
+
+    #[test]
+    fn test_ntp_servers_option() {
+        let option = DhcpOption::new(
+            OptionTag::NetworkTimeProtocolServers,
+            OptionData::NetworkTimeProtocolServers(vec![
+                Ipv4Addr::new(10, 0, 0, 1),
+                Ipv4Addr::new(10, 0, 0, 2),
+            ]),
+        );
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &[42, 8, 10, 0, 0, 1, 10, 0, 0, 2]);
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+
+        // The length must be a non-zero multiple of 4
+        for data in [&[42, 0][..], &[42, 5, 10, 0, 0, 1, 10]] {
+            let mut buf = ReadBuffer::new(data);
+            assert!(DhcpOption::read::<BigEndian>(&mut buf).is_err());
+        }
+    }
+
+    #[test]
+    fn test_resource_location_server_option() {
+        let option = DhcpOption::new(
+            OptionTag::ResourceLocationServer,
+            OptionData::ResourceLocationServer(vec![
+                Ipv4Addr::new(10, 0, 0, 1),
+                Ipv4Addr::new(10, 0, 0, 2),
+            ]),
+        );
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &[11, 8, 10, 0, 0, 1, 10, 0, 0, 2]);
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+    }
+
+    #[test]
+    fn test_boot_file_size_option() {
+        let option = DhcpOption::new(OptionTag::BootFileSize, OptionData::BootFileSize(0x1234));
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &[13, 2, 0x12, 0x34]);
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+
+        // The length must be exactly 2
+        for data in [&[13, 1, 0x12][..], &[13, 3, 0x12, 0x34, 0x56]] {
+            let mut buf = ReadBuffer::new(data);
+            assert!(DhcpOption::read::<BigEndian>(&mut buf).is_err());
+        }
+    }
+
+    #[test]
+    fn test_default_ip_ttl_option() {
+        let option = DhcpOption::new(OptionTag::DefaultIpTtl, OptionData::DefaultIpTtl(64));
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &[23, 1, 64]);
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+
+        // A TTL of 0 is illegal
+        let mut buf = ReadBuffer::new(&[0]);
+        let header = OptionHeader {
+            tag: OptionTag::DefaultIpTtl,
+            len: 1,
+        };
+        assert!(matches!(
+            OptionData::read::<BigEndian>(&mut buf, &header),
+            Err(OptionDataError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn test_interface_mtu_option() {
+        let option = DhcpOption::new(OptionTag::InterfaceMtu, OptionData::InterfaceMtu(1400));
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &[26, 2, 0x05, 0x78]);
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+
+        // MTUs below 68 are illegal
+        let mut buf = ReadBuffer::new(&[0, 67]);
+        let header = OptionHeader {
+            tag: OptionTag::InterfaceMtu,
+            len: 2,
+        };
+        assert!(matches!(
+            OptionData::read::<BigEndian>(&mut buf, &header),
+            Err(OptionDataError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn test_static_route_option() {
+        let option = DhcpOption::new(
+            OptionTag::StaticRoute,
+            OptionData::StaticRoute(vec![
+                (Ipv4Addr::new(10, 1, 0, 0), Ipv4Addr::new(192, 168, 1, 1)),
+                (Ipv4Addr::new(10, 2, 0, 0), Ipv4Addr::new(192, 168, 1, 2)),
+            ]),
+        );
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(
+            buf.bytes(),
+            &[33, 16, 10, 1, 0, 0, 192, 168, 1, 1, 10, 2, 0, 0, 192, 168, 1, 2]
+        );
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        assert_eq!(DhcpOption::read::<BigEndian>(&mut buf).unwrap(), option);
+
+        // Odd and empty payloads and the default route are rejected
+        for data in [
+            &[7, 10, 1, 0, 0, 192, 168, 1][..],
+            &[0],
+            &[8, 0, 0, 0, 0, 192, 168, 1, 1],
+        ] {
+            let header = OptionHeader {
+                tag: OptionTag::StaticRoute,
+                len: data[0],
+            };
+
+            let mut buf = ReadBuffer::new(&data[1..]);
+            assert!(matches!(
+                OptionData::read::<BigEndian>(&mut buf, &header),
+                Err(OptionDataError::InvalidData)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_domain_name_option() {
+        // Option 15 of the ISC dhcpd DHCPOFFER in tests/corpus
+        let data = [
+            0x0f, 0x0b, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x6c, 0x61, 0x6e,
+        ];
+        let mut buf = ReadBuffer::new(&data);
+        let option = DhcpOption::read::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(option.data().as_string(), Some("example.lan"));
+
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &data);
+
+        // Invalid UTF-8 is rejected instead of panicking
+        let data = [0x0f, 0x03, b'l', 0xff, b'n'];
+        let mut buf = ReadBuffer::new(&data);
+        assert!(DhcpOption::read::<BigEndian>(&mut buf).is_err());
+    }
+}
@@ -12,7 +12,7 @@ pub enum OptionHeaderError {
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct OptionHeader {
     pub(crate) tag: OptionTag,
     pub(crate) len: u8,
@@ -40,6 +40,12 @@ impl Writeable for OptionHeader {
 
     fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
         let mut n = self.tag.write::<E>(buf)?;
+
+        // Fixed length options consist of the tag only
+        if self.tag == OptionTag::Pad || self.tag == OptionTag::End {
+            return Ok(n);
+        }
+
         n += self.len.write::<E>(buf)?;
 
         Ok(n)
@@ -1,6 +1,8 @@
 use binbuf::prelude::*;
 use thiserror::Error;
 
+use crate::types::ParseErrorKind;
+
 mod data;
 mod header;
 mod tag;
@@ -11,17 +13,44 @@ pub use tag::*;
 
 #[derive(Debug, Error)]
 pub enum OptionError {
-    #[error("Option header error: {0}")]
+    #[error("{code} Option header error: {0}", code = self.code())]
     OptionHeaderError(#[from] OptionHeaderError),
 
-    #[error("Option data error: {0}")]
+    #[error("{code} Option data error: {0}", code = self.code())]
     OptionDataError(#[from] OptionDataError),
 
-    #[error("Buffer error: {0}")]
+    #[error("{code} Buffer error: {0}", code = self.code())]
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+error_codes!(OptionError {
+    OptionHeaderError => "VULCAN-P201",
+    OptionDataError => "VULCAN-P202",
+    BufferError => "VULCAN-P203",
+});
+
+impl OptionError {
+    /// Returns the category of the error, see [`MessageError::kind`](crate::types::MessageError::kind).
+    pub fn kind(&self) -> ParseErrorKind {
+        match self {
+            Self::OptionHeaderError(OptionHeaderError::OptionTagError(
+                OptionTagError::InvalidTag(_),
+            )) => ParseErrorKind::UnknownOption,
+            Self::OptionHeaderError(OptionHeaderError::OptionTagError(
+                OptionTagError::BufferError(err),
+            ))
+            | Self::OptionHeaderError(OptionHeaderError::BufferError(err))
+            | Self::OptionDataError(OptionDataError::BufferError(err))
+            | Self::BufferError(err) => ParseErrorKind::of_buffer_error(err),
+            Self::OptionDataError(OptionDataError::InvalidLength(_)) => {
+                ParseErrorKind::BadOptionLength
+            }
+            Self::OptionDataError(_) => ParseErrorKind::Invalid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct DhcpOption {
     header: OptionHeader,
     data: OptionData,
@@ -12,7 +12,7 @@ pub enum OptionTagError {
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum OptionTag {
     /// See [3.1. Pad Option](https://datatracker.ietf.org/doc/html/rfc1533#section-3.1)
     Pad,
@@ -226,10 +226,169 @@ pub enum OptionTag {
     /// See [9.12. Client-identifier][1]
     ClientIdentifier,
 
+    /// See [RFC 4702 - The DHCP Client FQDN Option](https://datatracker.ietf.org/doc/html/rfc4702)
+    ClientFqdn,
+
+    /// See [RFC 3046 - DHCP Relay Agent Information Option](https://datatracker.ietf.org/doc/html/rfc3046)
+    RelayAgentInformation,
+
+    /// See [RFC 3118 - Authentication for DHCP Messages](https://datatracker.ietf.org/doc/html/rfc3118)
+    Authentication,
+
+    /// See [RFC 4388 - Section 6.1. Client-last-transaction-time](https://datatracker.ietf.org/doc/html/rfc4388#section-6.1)
+    ClientLastTransactionTime,
+
+    /// See [RFC 4388 - Section 6.1. Associated-ip](https://datatracker.ietf.org/doc/html/rfc4388#section-6.1)
+    AssociatedIp,
+
     /// See [Captive-Portal Identification in DHCP and Router Advertisements (RAs)][2]
     DhcpCaptivePortal,
 
+    /// See [RFC 3397 - DHCP Domain Search Option](https://datatracker.ietf.org/doc/html/rfc3397)
+    DomainSearch,
+
+    /// See [RFC 3925 - Section 3. Vendor-Identifying Vendor Class Option](https://datatracker.ietf.org/doc/html/rfc3925#section-3)
+    VendorIdentifyingVendorClass,
+
+    /// See [RFC 3925 - Section 4. Vendor-Identifying Vendor-Specific Information Option](https://datatracker.ietf.org/doc/html/rfc3925#section-4)
+    VendorIdentifyingVendorSpecific,
+
     UnassignedOrRemoved(u8),
+
+    /// A code this crate doesn't implement. Clients request such options in
+    /// their parameter request list, options carrying one are rejected.
+    Unknown(u8),
+}
+
+impl OptionTag {
+    /// Returns whether the data of multiple instances of this option can be
+    /// concatenated into a single option, see [RFC 3396](https://datatracker.ietf.org/doc/html/rfc3396).
+    /// This holds for options carrying lists, strings or opaque data.
+    /// Fixed-length options like the message type, where a second instance
+    /// can't be a continuation of the first, are not concatenatable.
+    pub fn is_concatenatable(&self) -> bool {
+        matches!(
+            self,
+            Self::Router
+                | Self::TimeServer
+                | Self::NameServer
+                | Self::DomainNameServer
+                | Self::LogServer
+                | Self::CookieServer
+                | Self::LprServer
+                | Self::ImpressServer
+                | Self::ResourceLocationServer
+                | Self::HostName
+                | Self::MeritDumpFile
+                | Self::DomainName
+                | Self::RootPath
+                | Self::ExtensionsPath
+                | Self::PolicyFilter
+                | Self::PathMtuPlateauTable
+                | Self::StaticRoute
+                | Self::NetworkInformationServiceDomain
+                | Self::NetworkInformationServers
+                | Self::NetworkTimeProtocolServers
+                | Self::VendorSpecificInformation
+                | Self::NetbiosNameServer
+                | Self::NetbiosDatagramDistributionServer
+                | Self::NetbiosScope
+                | Self::XWindowSystemFontServer
+                | Self::XWindowSystemDisplayManager
+                | Self::ParameterRequestList
+                | Self::Message
+                | Self::ClassIdentifier
+                | Self::ClientIdentifier
+                | Self::RelayAgentInformation
+                | Self::AssociatedIp
+                | Self::DhcpCaptivePortal
+                | Self::DomainSearch
+                | Self::VendorIdentifyingVendorClass
+                | Self::VendorIdentifyingVendorSpecific
+        )
+    }
+
+    /// Returns the human-readable name of the option, like `router` or
+    /// `domain-name-server`. Unknown and unassigned tags have no name.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self {
+            Self::Pad => "pad",
+            Self::End => "end",
+            Self::SubnetMask => "subnet-mask",
+            Self::TimeOffset => "time-offset",
+            Self::Router => "router",
+            Self::TimeServer => "time-server",
+            Self::NameServer => "name-server",
+            Self::DomainNameServer => "domain-name-server",
+            Self::LogServer => "log-server",
+            Self::CookieServer => "cookie-server",
+            Self::LprServer => "lpr-server",
+            Self::ImpressServer => "impress-server",
+            Self::ResourceLocationServer => "resource-location-server",
+            Self::HostName => "host-name",
+            Self::BootFileSize => "boot-file-size",
+            Self::MeritDumpFile => "merit-dump-file",
+            Self::DomainName => "domain-name",
+            Self::SwapServer => "swap-server",
+            Self::RootPath => "root-path",
+            Self::ExtensionsPath => "extensions-path",
+            Self::IpForwarding => "ip-forwarding",
+            Self::NonLocalSourceRouting => "non-local-source-routing",
+            Self::PolicyFilter => "policy-filter",
+            Self::MaxDatagramReassemblySize => "max-datagram-reassembly-size",
+            Self::DefaultIpTtl => "default-ip-ttl",
+            Self::PathMtuAgingTimeout => "path-mtu-aging-timeout",
+            Self::PathMtuPlateauTable => "path-mtu-plateau-table",
+            Self::InterfaceMtu => "interface-mtu",
+            Self::AllSubnetsLocal => "all-subnets-local",
+            Self::BroadcastAddr => "broadcast-addr",
+            Self::PerformMaskDiscovery => "perform-mask-discovery",
+            Self::MaskSupplier => "mask-supplier",
+            Self::PerformRouterDiscovery => "perform-router-discovery",
+            Self::RouterSolicitationAddr => "router-solicitation-addr",
+            Self::StaticRoute => "static-route",
+            Self::TrailerEncapsulation => "trailer-encapsulation",
+            Self::ArpCacheTimeout => "arp-cache-timeout",
+            Self::EthernetEncapsulation => "ethernet-encapsulation",
+            Self::TcpDefaultTtl => "tcp-default-ttl",
+            Self::TcpKeepaliveInterval => "tcp-keepalive-interval",
+            Self::TcpKeepaliveGarbage => "tcp-keepalive-garbage",
+            Self::NetworkInformationServiceDomain => "network-information-service-domain",
+            Self::NetworkInformationServers => "network-information-servers",
+            Self::NetworkTimeProtocolServers => "network-time-protocol-servers",
+            Self::VendorSpecificInformation => "vendor-specific-information",
+            Self::NetbiosNameServer => "netbios-name-server",
+            Self::NetbiosDatagramDistributionServer => "netbios-datagram-distribution-server",
+            Self::NetbiosNodeType => "netbios-node-type",
+            Self::NetbiosScope => "netbios-scope",
+            Self::XWindowSystemFontServer => "x-window-system-font-server",
+            Self::XWindowSystemDisplayManager => "x-window-system-display-manager",
+            Self::RequestedIpAddr => "requested-ip-addr",
+            Self::IpAddrLeaseTime => "ip-addr-lease-time",
+            Self::OptionOverload => "option-overload",
+            Self::DhcpMessageType => "dhcp-message-type",
+            Self::ServerIdentifier => "server-identifier",
+            Self::ParameterRequestList => "parameter-request-list",
+            Self::Message => "message",
+            Self::MaxDhcpMessageSize => "max-dhcp-message-size",
+            Self::RenewalT1Time => "renewal-t1-time",
+            Self::RebindingT2Time => "rebinding-t2-time",
+            Self::ClassIdentifier => "class-identifier",
+            Self::ClientIdentifier => "client-identifier",
+            Self::ClientFqdn => "client-fqdn",
+            Self::RelayAgentInformation => "relay-agent-information",
+            Self::Authentication => "authentication",
+            Self::ClientLastTransactionTime => "client-last-transaction-time",
+            Self::AssociatedIp => "associated-ip",
+            Self::DhcpCaptivePortal => "dhcp-captive-portal",
+            Self::DomainSearch => "domain-search",
+            Self::VendorIdentifyingVendorClass => "vendor-identifying-vendor-class",
+            Self::VendorIdentifyingVendorSpecific => "vendor-identifying-vendor-specific",
+            Self::UnassignedOrRemoved(_) | Self::Unknown(_) => return None,
+        };
+
+        Some(name)
+    }
 }
 
 impl Display for OptionTag {
@@ -306,7 +465,15 @@ impl TryFrom<u8> for OptionTag {
             59 => Ok(Self::RebindingT2Time),
             60 => Ok(Self::ClassIdentifier),
             61 => Ok(Self::ClientIdentifier),
+            81 => Ok(Self::ClientFqdn),
+            82 => Ok(Self::RelayAgentInformation),
+            90 => Ok(Self::Authentication),
+            91 => Ok(Self::ClientLastTransactionTime),
+            92 => Ok(Self::AssociatedIp),
             114 => Ok(Self::DhcpCaptivePortal),
+            119 => Ok(Self::DomainSearch),
+            124 => Ok(Self::VendorIdentifyingVendorClass),
+            125 => Ok(Self::VendorIdentifyingVendorSpecific),
             255 => Ok(Self::End),
             108 => Ok(Self::UnassignedOrRemoved(value)),
             _ => Err(OptionTagError::InvalidTag(value)),
@@ -379,9 +546,18 @@ impl From<OptionTag> for u8 {
             OptionTag::RebindingT2Time => 59,
             OptionTag::ClassIdentifier => 60,
             OptionTag::ClientIdentifier => 61,
+            OptionTag::ClientFqdn => 81,
+            OptionTag::RelayAgentInformation => 82,
+            OptionTag::Authentication => 90,
+            OptionTag::ClientLastTransactionTime => 91,
+            OptionTag::AssociatedIp => 92,
             OptionTag::DhcpCaptivePortal => 114,
+            OptionTag::DomainSearch => 119,
+            OptionTag::VendorIdentifyingVendorClass => 124,
+            OptionTag::VendorIdentifyingVendorSpecific => 125,
             OptionTag::End => 255,
             OptionTag::UnassignedOrRemoved(v) => v,
+            OptionTag::Unknown(v) => v,
         }
     }
 }
@@ -408,3 +584,18 @@ impl Writeable for OptionTag {
         Ok(1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_concatenatable() {
+        assert!(OptionTag::ParameterRequestList.is_concatenatable());
+        assert!(OptionTag::DomainSearch.is_concatenatable());
+
+        assert!(!OptionTag::DhcpMessageType.is_concatenatable());
+        assert!(!OptionTag::SubnetMask.is_concatenatable());
+        assert!(!OptionTag::UnassignedOrRemoved(224).is_concatenatable());
+    }
+}
@@ -1,17 +1,33 @@
 use binbuf::prelude::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClassIdentifier(String);
 
 impl ClassIdentifier {
+    pub fn new(ident: String) -> Self {
+        Self(ident)
+    }
+
     pub fn read<E: Endianness>(buf: &mut ReadBuffer, len: u8) -> Result<Self, BufferError> {
         if len == 0 {
             return Err(BufferError::InvalidData);
         }
 
+        // Vendors put arbitrary bytes in here, don't panic on them
         let ident = buf.read_vec(len as usize)?;
-        let ident = String::from_utf8(ident).unwrap();
-        Ok(Self(ident))
+        Ok(Self(String::from_utf8_lossy(&ident).into_owned()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
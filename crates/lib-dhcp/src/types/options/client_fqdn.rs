@@ -0,0 +1,180 @@
+use binbuf::prelude::*;
+
+/// Flag bit indicating that the domain name is in canonical wire format
+/// (RFC 1035 Section 3.1) instead of deprecated ASCII encoding.
+pub const FQDN_FLAG_E: u8 = 0b0000_0100;
+
+/// #### Client Fully Qualified Domain Name
+///
+/// See [RFC 4702](https://datatracker.ietf.org/doc/html/rfc4702#section-2).
+/// The code for this option is 81, and its minimum length is 3.
+///
+/// ```text
+///  Code   Len    Flags  RCODE1 RCODE2   Domain Name
+/// +------+------+------+------+------+------+--
+/// |  81  |   n  |      |      |      |       ...
+/// +------+------+------+------+------+------+--
+/// ```
+///
+/// Clients still send the name in ASCII encoding (the E flag is not set),
+/// Windows for example does. Both encodings are read, the name is kept in
+/// its dotted form and written back in the encoding the flags indicate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientFqdn {
+    flags: u8,
+    rcode1: u8,
+    rcode2: u8,
+    name: String,
+}
+
+impl Writeable for ClientFqdn {
+    type Error = BufferError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        buf.push(self.flags);
+        buf.push(self.rcode1);
+        buf.push(self.rcode2);
+        buf.write(self.encoded_name()?);
+
+        Ok(self.len())
+    }
+}
+
+impl ClientFqdn {
+    /// Length of the fields preceding the domain name.
+    const FIXED_LEN: u8 = 3;
+
+    pub fn new(flags: u8, name: String) -> Self {
+        Self {
+            flags,
+            rcode1: 0,
+            rcode2: 0,
+            name,
+        }
+    }
+
+    pub fn read<E: Endianness>(buf: &mut ReadBuffer, len: u8) -> Result<Self, BufferError> {
+        if len < Self::FIXED_LEN {
+            return Err(BufferError::InvalidData);
+        }
+
+        let flags = buf.pop()?;
+        let rcode1 = buf.pop()?;
+        let rcode2 = buf.pop()?;
+        let data = buf.read_vec((len - Self::FIXED_LEN) as usize)?;
+
+        let name = match flags & FQDN_FLAG_E {
+            0 => String::from_utf8_lossy(&data).into_owned(),
+            _ => read_canonical(&data)?,
+        };
+
+        Ok(Self {
+            flags,
+            rcode1,
+            rcode2,
+            name,
+        })
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns the name in its dotted form. A fully qualified name ends with
+    /// a dot, a partial name doesn't.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns if the name is in canonical wire format on the wire.
+    pub fn is_canonical(&self) -> bool {
+        self.flags & FQDN_FLAG_E != 0
+    }
+
+    pub fn len(&self) -> usize {
+        Self::FIXED_LEN as usize + self.encoded_name().map_or(0, |name| name.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    fn encoded_name(&self) -> Result<Vec<u8>, BufferError> {
+        if !self.is_canonical() {
+            return Ok(self.name.as_bytes().to_vec());
+        }
+
+        let mut data = Vec::new();
+        for label in self.name.split('.').filter(|l| !l.is_empty()) {
+            let len = u8::try_from(label.len()).map_err(|_| BufferError::InvalidData)?;
+            data.push(len);
+            data.extend_from_slice(label.as_bytes());
+        }
+
+        // Only fully qualified names are terminated with the root label
+        if self.name.ends_with('.') {
+            data.push(0);
+        }
+
+        Ok(data)
+    }
+}
+
+/// Reads a name in canonical wire format. Compression is not allowed in
+/// this option (RFC 4702 Section 2.3.1).
+fn read_canonical(data: &[u8]) -> Result<String, BufferError> {
+    let mut name = String::new();
+    let mut position = 0;
+
+    while position < data.len() {
+        let len = data[position] as usize;
+
+        if len == 0 {
+            name.push('.');
+            break;
+        }
+
+        let label = data
+            .get(position + 1..position + 1 + len)
+            .ok_or(BufferError::BufTooShort)?;
+
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(&String::from_utf8_lossy(label));
+        position += 1 + len;
+    }
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_fqdn_encodings() {
+        // ASCII encoding as sent by Windows
+        let ascii = [0, 0, 0, b'p', b'c', b'.', b'l', b'a', b'n'];
+        let fqdn = ClientFqdn::read::<BigEndian>(&mut ReadBuffer::new(&ascii), 9).unwrap();
+        assert_eq!(fqdn.name(), "pc.lan");
+        assert!(!fqdn.is_canonical());
+
+        // Canonical encoding of a fully qualified name
+        let canonical = [FQDN_FLAG_E, 0, 0, 2, b'p', b'c', 3, b'l', b'a', b'n', 0];
+        let fqdn = ClientFqdn::read::<BigEndian>(&mut ReadBuffer::new(&canonical), 11).unwrap();
+        assert_eq!(fqdn.name(), "pc.lan.");
+
+        let mut buf = WriteBuffer::new();
+        assert_eq!(fqdn.write::<BigEndian>(&mut buf).unwrap(), 11);
+        assert_eq!(buf.bytes(), &canonical);
+
+        // Partial names lack the root label
+        let partial = [FQDN_FLAG_E, 0, 0, 2, b'p', b'c'];
+        let fqdn = ClientFqdn::read::<BigEndian>(&mut ReadBuffer::new(&partial), 6).unwrap();
+        assert_eq!(fqdn.name(), "pc");
+
+        let truncated = [FQDN_FLAG_E, 0, 0, 5, b'p', b'c'];
+        assert!(ClientFqdn::read::<BigEndian>(&mut ReadBuffer::new(&truncated), 6).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use binbuf::prelude::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClientIdentifier {
     identifier: Vec<u8>,
     ty: u8,
@@ -32,8 +32,18 @@ impl Writeable for ClientIdentifier {
 }
 
 impl ClientIdentifier {
+    /// Create a new client identifier with an explicit type octet. A type of
+    /// 0 indicates an opaque identifier, other values are hardware types as
+    /// defined in the "Assigned Numbers" RFC.
+    pub fn new(ty: u8, identifier: Vec<u8>) -> Self {
+        Self { identifier, ty }
+    }
+
+    /// Reads the client identifier. The first octet is the type (`t1`), the
+    /// remaining `len - 1` octets are the identifier itself.
     pub fn read<E: Endianness>(buf: &mut ReadBuffer, len: u8) -> Result<Self, BufferError> {
-        // The RFC states the minimum length is 2
+        // The RFC states the minimum length is 2. This also rejects empty
+        // identifiers (len == 0).
         if len < 2 {
             return Err(BufferError::InvalidData);
         }
@@ -44,7 +54,61 @@ impl ClientIdentifier {
         Ok(Self { identifier, ty })
     }
 
+    /// Returns the type octet of this client identifier.
+    pub fn ty(&self) -> u8 {
+        self.ty
+    }
+
+    /// Returns the identifier bytes without the leading type octet.
+    pub fn identifier(&self) -> &[u8] {
+        &self.identifier
+    }
+
     pub fn len(&self) -> usize {
         self.identifier.len() + 1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_ethernet_client_identifier() {
+        let data = [1, 0xDE, 0xAD, 0xBE, 0xEF, 0x12, 0x34];
+        let mut buf = ReadBuffer::new(&data);
+
+        match ClientIdentifier::read::<BigEndian>(&mut buf, data.len() as u8) {
+            Ok(ident) => {
+                assert_eq!(ident.ty(), 1);
+                assert_eq!(ident.identifier(), &[0xDE, 0xAD, 0xBE, 0xEF, 0x12, 0x34]);
+                assert_eq!(ident.len(), data.len());
+            }
+            Err(err) => panic!("{}", err),
+        }
+
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_read_opaque_client_identifier() {
+        let data = [0, b'v', b'u', b'l', b'c', b'a', b'n'];
+        let mut buf = ReadBuffer::new(&data);
+
+        match ClientIdentifier::read::<BigEndian>(&mut buf, data.len() as u8) {
+            Ok(ident) => {
+                assert_eq!(ident.ty(), 0);
+                assert_eq!(ident.identifier(), b"vulcan");
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_read_empty_client_identifier() {
+        let data = [1, 2, 3];
+        let mut buf = ReadBuffer::new(&data);
+
+        assert!(ClientIdentifier::read::<BigEndian>(&mut buf, 0).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use binbuf::prelude::*;
+use thiserror::Error;
+
+/// DNS compression pointers are indicated by the two most significant bits
+/// of the length octet being set.
+const POINTER_MASK: u8 = 0b1100_0000;
+
+/// Maximum length of a single label, see RFC 1035 Section 2.3.4.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Maximum length of an encoded domain name, including the length octets
+/// and the root label, see RFC 1035 Section 2.3.4.
+const MAX_NAME_LEN: usize = 255;
+
+/// Maximum number of compression pointers followed while reading a single
+/// domain name.
+const MAX_POINTER_JUMPS: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum DomainSearchError {
+    #[error("Invalid domain name label")]
+    InvalidLabel,
+
+    #[error("Invalid or looping compression pointer")]
+    InvalidPointer,
+
+    #[error("Domain name exceeds {MAX_NAME_LEN} octets")]
+    NameTooLong,
+
+    #[error("Buffer error: {0}")]
+    BufferError(#[from] BufferError),
+}
+
+/// #### Domain Search
+///
+/// The code for this option is 119. The data is a list of domain names
+/// encoded as described in RFC 1035 Section 3.1, optionally using the
+/// compression scheme described in RFC 1035 Section 4.1.4.
+///
+/// ```text
+///  Code   Len         Searchstring
+/// +-----+-----+-----+-----+-----+-----+-----+---
+/// | 119 |  n  |  s1 |  s2 |  s3 |  s4 |  s5 | ...
+/// +-----+-----+-----+-----+-----+-----+-----+---
+/// ```
+///
+/// ### See
+///
+/// RFC 3397 - Dynamic Host Configuration Protocol (DHCP) Domain Search Option: https://datatracker.ietf.org/doc/html/rfc3397
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainSearch(Vec<String>);
+
+impl DomainSearch {
+    pub fn new(domains: Vec<String>) -> Self {
+        Self(domains)
+    }
+
+    pub fn read<E: Endianness>(buf: &mut ReadBuffer, len: u8) -> Result<Self, DomainSearchError> {
+        // Compression pointers are relative to the start of the option
+        // data, which is why we read the complete data first.
+        let data = buf.read_vec(len as usize)?;
+        let mut domains = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let (domain, next) = read_domain(&data, offset)?;
+            domains.push(domain);
+            offset = next;
+        }
+
+        Ok(Self(domains))
+    }
+
+    /// Returns the list of domains.
+    pub fn domains(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Returns the length of the encoded (uncompressed) domain list.
+    pub fn len(&self) -> usize {
+        self.0
+            .iter()
+            .map(|d| labels(d).map(|l| l.len() + 1).sum::<usize>() + 1)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Writeable for DomainSearch {
+    type Error = DomainSearchError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        let mut n = 0;
+
+        for domain in &self.0 {
+            for label in labels(domain) {
+                if label.len() > MAX_LABEL_LEN {
+                    return Err(DomainSearchError::InvalidLabel);
+                }
+
+                buf.push(label.len() as u8);
+                n += buf.write(label.as_bytes()) + 1;
+            }
+
+            // Terminate the domain with the root label
+            buf.push(0);
+            n += 1;
+        }
+
+        Ok(n)
+    }
+}
+
+/// Returns the non-empty labels of a domain. A trailing dot (fully qualified
+/// domain) is ignored.
+fn labels(domain: &str) -> impl Iterator<Item = &str> {
+    domain.split('.').filter(|l| !l.is_empty())
+}
+
+/// Reads a single domain starting at `offset` and returns it together with
+/// the offset of the next domain. Compression pointers are followed, but
+/// only to data before the name or before the previous pointer target, so
+/// every jump moves strictly backwards and loops are impossible.
+fn read_domain(data: &[u8], offset: usize) -> Result<(String, usize), DomainSearchError> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = offset;
+    let mut limit = offset;
+    let mut jumps = 0;
+    let mut name_len = 1;
+    let mut next = None;
+
+    loop {
+        let len = *data.get(position).ok_or(BufferError::BufTooShort)?;
+
+        if len & POINTER_MASK == POINTER_MASK {
+            let low = *data.get(position + 1).ok_or(BufferError::BufTooShort)?;
+            let target = (((len & !POINTER_MASK) as usize) << 8) | low as usize;
+
+            jumps += 1;
+            if target >= limit || jumps > MAX_POINTER_JUMPS {
+                return Err(DomainSearchError::InvalidPointer);
+            }
+
+            next.get_or_insert(position + 2);
+            position = target;
+            limit = target;
+            continue;
+        }
+
+        if len == 0 {
+            next.get_or_insert(position + 1);
+            break;
+        }
+
+        if len as usize > MAX_LABEL_LEN {
+            return Err(DomainSearchError::InvalidLabel);
+        }
+
+        name_len += len as usize + 1;
+        if name_len > MAX_NAME_LEN {
+            return Err(DomainSearchError::NameTooLong);
+        }
+
+        let start = position + 1;
+        let end = start + len as usize;
+        let label = data.get(start..end).ok_or(BufferError::BufTooShort)?;
+        let label =
+            String::from_utf8(label.to_vec()).map_err(|_| DomainSearchError::InvalidLabel)?;
+
+        labels.push(label);
+        position = end;
+    }
+
+    Ok((labels.join("."), next.unwrap_or(position)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_compressed_domain_search() {
+        // Example taken from RFC 3397 Section 3, containing "eng.apple.com."
+        // and "marketing.apple.com." with the second one using compression.
+        let data = [
+            3, b'e', b'n', b'g', 5, b'a', b'p', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, 9, b'm',
+            b'a', b'r', b'k', b'e', b't', b'i', b'n', b'g', 0xC0, 0x04,
+        ];
+        let mut buf = ReadBuffer::new(&data);
+
+        match DomainSearch::read::<BigEndian>(&mut buf, data.len() as u8) {
+            Ok(search) => assert_eq!(
+                search.domains(),
+                &[
+                    "eng.apple.com".to_string(),
+                    "marketing.apple.com".to_string()
+                ]
+            ),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_domain_search_round_trip() {
+        let search = DomainSearch::new(vec!["example.com".into(), "lan".into()]);
+
+        let mut buf = WriteBuffer::new();
+        let n = search.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(n, search.len());
+
+        let mut buf = ReadBuffer::new(buf.bytes());
+        let read = DomainSearch::read::<BigEndian>(&mut buf, n as u8).unwrap();
+        assert_eq!(read.domains(), search.domains());
+    }
+
+    #[test]
+    fn test_read_looping_pointer() {
+        let data = [0xC0, 0x00];
+        let mut buf = ReadBuffer::new(&data);
+
+        assert!(DomainSearch::read::<BigEndian>(&mut buf, data.len() as u8).is_err());
+
+        // Points back to the start of its own name, which would repeat the
+        // "a" label forever
+        let data = [1, b'a', 0xC0, 0x00];
+        let mut buf = ReadBuffer::new(&data);
+
+        assert!(matches!(
+            DomainSearch::read::<BigEndian>(&mut buf, data.len() as u8),
+            Err(DomainSearchError::InvalidPointer)
+        ));
+    }
+
+    #[test]
+    fn test_read_name_too_long() {
+        // Five labels of 63 octets exceed the 255 octets of a domain name
+        let mut data = Vec::new();
+        for _ in 0..5 {
+            data.push(63);
+            data.extend_from_slice(&[b'a'; 63]);
+        }
+        data.push(0);
+
+        assert!(matches!(
+            read_domain(&data, 0),
+            Err(DomainSearchError::NameTooLong)
+        ));
+    }
+}
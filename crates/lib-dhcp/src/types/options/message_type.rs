@@ -1,6 +1,9 @@
+use std::fmt::Display;
+
 use binbuf::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum DhcpMessageType {
     Discover,
     Offer,
@@ -9,6 +12,34 @@ pub enum DhcpMessageType {
     Ack,
     Nak,
     Release,
+    Inform,
+
+    // RFC 4388 leasequery messages
+    LeaseQuery,
+    LeaseUnassigned,
+    LeaseUnknown,
+    LeaseActive,
+}
+
+impl Display for DhcpMessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Discover => "DHCPDISCOVER",
+            Self::Offer => "DHCPOFFER",
+            Self::Request => "DHCPREQUEST",
+            Self::Decline => "DHCPDECLINE",
+            Self::Ack => "DHCPACK",
+            Self::Nak => "DHCPNAK",
+            Self::Release => "DHCPRELEASE",
+            Self::Inform => "DHCPINFORM",
+            Self::LeaseQuery => "DHCPLEASEQUERY",
+            Self::LeaseUnassigned => "DHCPLEASEUNASSIGNED",
+            Self::LeaseUnknown => "DHCPLEASEUNKNOWN",
+            Self::LeaseActive => "DHCPLEASEACTIVE",
+        };
+
+        f.write_str(name)
+    }
 }
 
 impl Readable for DhcpMessageType {
@@ -25,6 +56,11 @@ impl Readable for DhcpMessageType {
             5 => Ok(Self::Ack),
             6 => Ok(Self::Nak),
             7 => Ok(Self::Release),
+            8 => Ok(Self::Inform),
+            10 => Ok(Self::LeaseQuery),
+            11 => Ok(Self::LeaseUnassigned),
+            12 => Ok(Self::LeaseUnknown),
+            13 => Ok(Self::LeaseActive),
             _ => Err(BufferError::InvalidData),
         }
     }
@@ -42,6 +78,11 @@ impl Writeable for DhcpMessageType {
             Self::Ack => buf.push(5),
             Self::Nak => buf.push(6),
             Self::Release => buf.push(7),
+            Self::Inform => buf.push(8),
+            Self::LeaseQuery => buf.push(10),
+            Self::LeaseUnassigned => buf.push(11),
+            Self::LeaseUnknown => buf.push(12),
+            Self::LeaseActive => buf.push(13),
         };
 
         Ok(1)
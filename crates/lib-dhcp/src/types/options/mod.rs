@@ -1,11 +1,23 @@
+mod authentication;
 mod class_identifier;
+mod client_fqdn;
 mod client_identifier;
+mod domain_search;
 mod message_type;
 mod option_overload;
 mod param_req_list;
+mod relay_agent;
+mod vendor_identifying;
+mod vendor_specific;
 
+pub use authentication::*;
 pub use class_identifier::*;
+pub use client_fqdn::*;
 pub use client_identifier::*;
+pub use domain_search::*;
 pub use message_type::*;
 pub use option_overload::*;
 pub use param_req_list::*;
+pub use relay_agent::*;
+pub use vendor_identifying::*;
+pub use vendor_specific::*;
@@ -0,0 +1,67 @@
+use binbuf::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Value of the option overload option (52), which tells which of the
+/// 'file' and 'sname' fields carry options instead of their usual content.
+///
+/// ### See
+///
+/// RFC 2132 - Section 9.3 - Option Overload: https://datatracker.ietf.org/doc/html/rfc2132#section-9.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OptionOverload {
+    /// The 'file' field is used to hold options.
+    File,
+
+    /// The 'sname' field is used to hold options.
+    Sname,
+
+    /// Both fields are used to hold options.
+    Both,
+}
+
+impl OptionOverload {
+    /// Returns the overload value for the fields in use, if any is.
+    pub fn of(file: bool, sname: bool) -> Option<Self> {
+        match (file, sname) {
+            (true, true) => Some(Self::Both),
+            (true, false) => Some(Self::File),
+            (false, true) => Some(Self::Sname),
+            (false, false) => None,
+        }
+    }
+
+    pub fn uses_file(&self) -> bool {
+        matches!(self, Self::File | Self::Both)
+    }
+
+    pub fn uses_sname(&self) -> bool {
+        matches!(self, Self::Sname | Self::Both)
+    }
+}
+
+impl Readable for OptionOverload {
+    type Error = BufferError;
+
+    fn read<E: Endianness>(buf: &mut ReadBuffer) -> Result<Self, Self::Error> {
+        match buf.pop()? {
+            1 => Ok(Self::File),
+            2 => Ok(Self::Sname),
+            3 => Ok(Self::Both),
+            _ => Err(BufferError::InvalidData),
+        }
+    }
+}
+
+impl Writeable for OptionOverload {
+    type Error = BufferError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        match self {
+            Self::File => buf.push(1),
+            Self::Sname => buf.push(2),
+            Self::Both => buf.push(3),
+        };
+
+        Ok(1)
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use binbuf::prelude::*;
 use thiserror::Error;
 
@@ -15,7 +17,7 @@ pub enum ParameterRequestListError {
     BufferError(#[from] BufferError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParameterRequestList(Vec<OptionTag>);
 
 impl ParameterRequestList {
@@ -29,9 +31,12 @@ impl ParameterRequestList {
 
         let mut params = Vec::new();
 
+        // Clients request plenty of options this crate doesn't implement,
+        // like classless static routes (121). They are kept, the server just
+        // never supplies them.
         for _i in 0..len {
-            let tag = OptionTag::read::<E>(buf)?;
-            params.push(tag);
+            let code = buf.pop()?;
+            params.push(OptionTag::try_from(code).unwrap_or(OptionTag::Unknown(code)));
         }
 
         Ok(Self(params))
@@ -55,7 +60,57 @@ impl ParameterRequestList {
         Self(tags)
     }
 
+    /// Returns if the client requested the option with `tag`.
+    pub fn contains(&self, tag: &OptionTag) -> bool {
+        self.0.contains(tag)
+    }
+
+    /// Returns the requested option tags in the order the client sent them.
+    pub fn tags(&self) -> &[OptionTag] {
+        &self.0
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
 }
+
+/// Renders the requested options by name, like `router, domain-name-server`.
+/// Options without a name are rendered by their code.
+impl Display for ParameterRequestList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, tag) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            match tag.name() {
+                Some(name) => write!(f, "{}", name)?,
+                None => write!(f, "{}", tag)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_names() {
+        let list = ParameterRequestList::new(vec![
+            OptionTag::Router,
+            OptionTag::DomainNameServer,
+            OptionTag::RenewalT1Time,
+        ]);
+        assert_eq!(
+            list.to_string(),
+            "router, domain-name-server, renewal-t1-time"
+        );
+
+        let list = ParameterRequestList::new(vec![OptionTag::SubnetMask, OptionTag::Unknown(121)]);
+        assert_eq!(list.to_string(), "subnet-mask, 121");
+    }
+}
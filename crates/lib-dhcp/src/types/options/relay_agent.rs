@@ -0,0 +1,125 @@
+use binbuf::prelude::*;
+
+/// Sub-option code of the agent circuit ID.
+pub const CIRCUIT_ID_SUB_OPTION: u8 = 1;
+
+/// Sub-option code of the agent remote ID.
+pub const REMOTE_ID_SUB_OPTION: u8 = 2;
+
+/// #### Relay Agent Information
+///
+/// The code for this option is 82. It is added by relay agents and carries
+/// a list of sub-options, each encoded as code, length and value.
+///
+/// ```text
+///  Code   Len     Agent Information Field
+/// +-----+-----+-----+-----+-----+-----+-----+-----+--
+/// |  82 |  N  |  i1 |  i2 |  i3 |  i4 |     |  iN |
+/// +-----+-----+-----+-----+-----+-----+-----+-----+--
+/// ```
+///
+/// ### See
+///
+/// RFC 3046 - DHCP Relay Agent Information Option: https://datatracker.ietf.org/doc/html/rfc3046
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayAgentInformation {
+    sub_options: Vec<(u8, Vec<u8>)>,
+}
+
+impl Writeable for RelayAgentInformation {
+    type Error = BufferError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        for (code, value) in &self.sub_options {
+            buf.push(*code);
+            buf.push(value.len() as u8);
+            buf.write(value.clone());
+        }
+
+        Ok(self.len())
+    }
+}
+
+impl RelayAgentInformation {
+    pub fn new(sub_options: Vec<(u8, Vec<u8>)>) -> Self {
+        Self { sub_options }
+    }
+
+    /// Reads `len` octets of sub-options.
+    pub fn read<E: Endianness>(buf: &mut ReadBuffer, len: u8) -> Result<Self, BufferError> {
+        let mut remaining = len as usize;
+        let mut sub_options = Vec::new();
+
+        while remaining > 0 {
+            if remaining < 2 {
+                return Err(BufferError::InvalidData);
+            }
+
+            let code = buf.pop()?;
+            let sub_len = buf.pop()? as usize;
+
+            if sub_len > remaining - 2 {
+                return Err(BufferError::InvalidData);
+            }
+
+            sub_options.push((code, buf.read_vec(sub_len)?));
+            remaining -= sub_len + 2;
+        }
+
+        Ok(Self { sub_options })
+    }
+
+    /// Returns the value of the sub-option with `code`.
+    pub fn get(&self, code: u8) -> Option<&[u8]> {
+        self.sub_options
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Returns the agent circuit ID (sub-option 1), which identifies the
+    /// circuit (like the switch port) the request arrived on.
+    pub fn circuit_id(&self) -> Option<&[u8]> {
+        self.get(CIRCUIT_ID_SUB_OPTION)
+    }
+
+    /// Returns the agent remote ID (sub-option 2).
+    pub fn remote_id(&self) -> Option<&[u8]> {
+        self.get(REMOTE_ID_SUB_OPTION)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sub_options
+            .iter()
+            .map(|(_, value)| value.len() + 2)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_sub_options() {
+        let data = [1, 4, b'p', b'o', b'r', b't', 2, 2, 0xab, 0xcd];
+        let mut buf = ReadBuffer::new(&data);
+
+        let info = RelayAgentInformation::read::<BigEndian>(&mut buf, data.len() as u8).unwrap();
+        assert_eq!(info.circuit_id(), Some(&b"port"[..]));
+        assert_eq!(info.remote_id(), Some(&[0xab, 0xcd][..]));
+        assert_eq!(info.len(), data.len());
+
+        let mut out = WriteBuffer::new();
+        info.write::<BigEndian>(&mut out).unwrap();
+        assert_eq!(out.bytes(), &data);
+    }
+
+    #[test]
+    fn test_read_truncated_sub_option() {
+        let data = [1, 6, b'p', b'o'];
+        let mut buf = ReadBuffer::new(&data);
+
+        assert!(RelayAgentInformation::read::<BigEndian>(&mut buf, data.len() as u8).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use binbuf::prelude::*;
+
+/// #### Vendor-Identifying Vendor Class and Vendor-Specific Information
+///
+/// The codes for these options are 124 (vendor class) and 125 (vendor
+/// specific information). Both carry data for one or more vendors, each
+/// identified by its IANA enterprise number.
+///
+/// ```text
+///  Code   Len          enterprise-number1          data-len1
+/// +-----+-----+-----+-----+-----+-----+-----+-----+-----+
+/// | 125 |  N  |  n1 |  n2 |  n3 |  n4 |     |  d1 |     |
+/// +-----+-----+-----+-----+-----+-----+-----+-----+-----+--
+///       data1       |  enterprise-number2   | data-len2 | ...
+/// +-----+-----+-----+-----+-----+-----+-----+-----+-----+--
+/// ```
+///
+/// The data is kept as is. For option 125 it holds the vendor's
+/// sub-options, encoded as code, length and value.
+///
+/// ### See
+///
+/// RFC 3925 - Vendor-Identifying Vendor Options: https://datatracker.ietf.org/doc/html/rfc3925
+#[derive(Debug, Clone, PartialEq)]
+pub struct VendorIdentifying {
+    vendors: Vec<(u32, Vec<u8>)>,
+}
+
+impl Writeable for VendorIdentifying {
+    type Error = BufferError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        for (enterprise_number, data) in &self.vendors {
+            enterprise_number.write::<E>(buf)?;
+            buf.push(data.len() as u8);
+            buf.write(data.clone());
+        }
+
+        Ok(self.len())
+    }
+}
+
+impl VendorIdentifying {
+    pub fn new(vendors: Vec<(u32, Vec<u8>)>) -> Self {
+        Self { vendors }
+    }
+
+    /// Reads `len` octets of vendor data.
+    pub fn read<E: Endianness>(buf: &mut ReadBuffer, len: u8) -> Result<Self, BufferError> {
+        let mut remaining = len as usize;
+        let mut vendors = Vec::new();
+
+        if remaining == 0 {
+            return Err(BufferError::InvalidData);
+        }
+
+        while remaining > 0 {
+            if remaining < 5 {
+                return Err(BufferError::InvalidData);
+            }
+
+            let enterprise_number = u32::read::<E>(buf)?;
+            let data_len = buf.pop()? as usize;
+
+            if data_len > remaining - 5 {
+                return Err(BufferError::InvalidData);
+            }
+
+            vendors.push((enterprise_number, buf.read_vec(data_len)?));
+            remaining -= data_len + 5;
+        }
+
+        Ok(Self { vendors })
+    }
+
+    /// Returns the data of the vendor with `enterprise_number`.
+    pub fn get(&self, enterprise_number: u32) -> Option<&[u8]> {
+        self.vendors
+            .iter()
+            .find(|(n, _)| *n == enterprise_number)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    /// Returns the enterprise numbers of all vendors, in order.
+    pub fn enterprise_numbers(&self) -> impl Iterator<Item = u32> + '_ {
+        self.vendors.iter().map(|(n, _)| *n)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vendors.iter().map(|(_, data)| data.len() + 5).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vendors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_vendors() {
+        let data = [0, 0, 0x0d, 0xe9, 3, b'a', b'b', b'c', 0, 0, 0, 9, 0];
+        let mut buf = ReadBuffer::new(&data);
+
+        let vendors = VendorIdentifying::read::<BigEndian>(&mut buf, data.len() as u8).unwrap();
+        assert_eq!(vendors.get(3561), Some(&b"abc"[..]));
+        assert_eq!(vendors.get(9), Some(&[][..]));
+        assert_eq!(
+            vendors.enterprise_numbers().collect::<Vec<_>>(),
+            vec![3561, 9]
+        );
+        assert_eq!(vendors.len(), data.len());
+
+        let mut out = WriteBuffer::new();
+        vendors.write::<BigEndian>(&mut out).unwrap();
+        assert_eq!(out.bytes(), &data);
+    }
+
+    #[test]
+    fn test_read_truncated_vendor() {
+        for data in [&[0, 0, 0x0d, 0xe9, 4, b'a'][..], &[0, 0, 0x0d]] {
+            let mut buf = ReadBuffer::new(data);
+            assert!(VendorIdentifying::read::<BigEndian>(&mut buf, data.len() as u8).is_err());
+        }
+    }
+}
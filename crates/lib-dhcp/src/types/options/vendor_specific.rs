@@ -0,0 +1,146 @@
+use binbuf::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Code of the pad sub-option, which has no length octet.
+const PAD_SUB_OPTION: u8 = 0;
+
+/// Code of the end sub-option, which terminates the sub-options.
+const END_SUB_OPTION: u8 = 255;
+
+/// A sub-option of the vendor options, encoded as code, length and value.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VendorSubOption {
+    pub code: u8,
+    pub data: Vec<u8>,
+}
+
+impl VendorSubOption {
+    pub fn new(code: u8, data: Vec<u8>) -> Self {
+        Self { code, data }
+    }
+}
+
+/// Encodes `sub_options` as code, length and value, without an end
+/// sub-option. This is the inverse of [`decode_vendor_sub_options`]. The
+/// data of a sub-option must not exceed 255 bytes.
+pub fn encode_vendor_sub_options(sub_options: &[VendorSubOption]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    for sub_option in sub_options {
+        data.push(sub_option.code);
+        data.push(sub_option.data.len() as u8);
+        data.extend_from_slice(&sub_option.data);
+    }
+
+    data
+}
+
+/// Decodes the sub-options encapsulated in the vendor specific information
+/// option (43). They are encoded like options, as code, length and value,
+/// without a magic cookie. Pad sub-options are skipped and the end
+/// sub-option (255) terminates the list, but it is optional.
+///
+/// ### See
+///
+/// RFC 2132 - Section 8.4 - Vendor Specific Information: https://datatracker.ietf.org/doc/html/rfc2132#section-8.4
+pub fn decode_vendor_sub_options(data: &[u8]) -> Result<Vec<VendorSubOption>, BufferError> {
+    let mut sub_options = Vec::new();
+    let mut rest = data;
+
+    while let Some((&code, tail)) = rest.split_first() {
+        rest = tail;
+
+        match code {
+            PAD_SUB_OPTION => continue,
+            END_SUB_OPTION => break,
+            _ => {}
+        }
+
+        let Some((&len, tail)) = rest.split_first() else {
+            return Err(BufferError::InvalidData);
+        };
+
+        if tail.len() < len as usize {
+            return Err(BufferError::InvalidData);
+        }
+
+        let (value, tail) = tail.split_at(len as usize);
+        sub_options.push(VendorSubOption::new(code, value.to_vec()));
+        rest = tail;
+    }
+
+    Ok(sub_options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DhcpOption, OptionData};
+
+    #[test]
+    fn test_decode_cisco_sub_options() {
+        // Option 43 of a Cisco access point: the controller addresses
+        // (241) and a TFTP server name (150)
+        let data = [
+            43, 14, 0xf1, 0x04, 0x0a, 0x00, 0x00, 0x05, 0x96, 0x05, b't', b'f', b't', b'p', b'1',
+            0xff,
+        ];
+        let mut buf = ReadBuffer::new(&data);
+        let option = DhcpOption::read::<BigEndian>(&mut buf).unwrap();
+
+        let OptionData::VendorSpecificInformation(info) = option.data() else {
+            panic!(
+                "expected vendor specific information, got {:?}",
+                option.data()
+            );
+        };
+
+        assert_eq!(
+            decode_vendor_sub_options(info).unwrap(),
+            vec![
+                VendorSubOption::new(0xf1, vec![10, 0, 0, 5]),
+                VendorSubOption::new(0x96, b"tftp1".to_vec())
+            ]
+        );
+
+        // The raw bytes are written back unchanged
+        let mut buf = WriteBuffer::new();
+        option.write::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(buf.bytes(), &data);
+    }
+
+    #[test]
+    fn test_decode_without_end() {
+        let data = [0x01, 0x02, 0xab, 0xcd, 0x00, 0x02, 0x00];
+        assert_eq!(
+            decode_vendor_sub_options(&data).unwrap(),
+            vec![
+                VendorSubOption::new(1, vec![0xab, 0xcd]),
+                VendorSubOption::new(2, vec![])
+            ]
+        );
+
+        // Everything after the end sub-option is ignored
+        assert_eq!(
+            decode_vendor_sub_options(&[0x01, 0x00, 0xff, 0x07]).unwrap(),
+            vec![VendorSubOption::new(1, vec![])]
+        );
+
+        for data in [&[0x01][..], &[0x01, 0x03, 0xab]] {
+            assert!(decode_vendor_sub_options(data).is_err());
+        }
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let sub_options = vec![
+            VendorSubOption::new(1, vec![0xab, 0xcd]),
+            VendorSubOption::new(2, vec![]),
+            VendorSubOption::new(254, vec![0; 255]),
+        ];
+
+        let data = encode_vendor_sub_options(&sub_options);
+        assert_eq!(data[..6], [1, 2, 0xab, 0xcd, 2, 0]);
+        assert_eq!(decode_vendor_sub_options(&data).unwrap(), sub_options);
+    }
+}
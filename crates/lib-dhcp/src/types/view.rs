@@ -0,0 +1,190 @@
+use std::ops::Range;
+
+use binbuf::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    constants,
+    types::{options::DhcpMessageType, OptionTag, ParseErrorKind},
+};
+
+/// Offset of the 'xid' field.
+const XID_OFFSET: usize = 4;
+
+/// Offset of the magic cookie, directly following the fixed length fields.
+const MAGIC_COOKIE_OFFSET: usize = 236;
+
+/// Offset of the options field, directly following the magic cookie.
+const OPTIONS_OFFSET: usize = MAGIC_COOKIE_OFFSET + 4;
+
+#[derive(Debug, Error)]
+pub enum MessageViewError {
+    #[error("Buffer too short to contain a DHCP message")]
+    BufTooShort,
+
+    #[error("No DHCP magic cookie found at the start of OPTIONS field")]
+    NoMagicCookie,
+}
+
+impl MessageViewError {
+    /// Returns the category of the error, see [`MessageError::kind`](crate::types::MessageError::kind).
+    pub fn kind(&self) -> ParseErrorKind {
+        match self {
+            Self::BufTooShort => ParseErrorKind::Truncated,
+            Self::NoMagicCookie => ParseErrorKind::BadCookie,
+        }
+    }
+}
+
+/// [`MessageView`] is a zero-copy view over a raw DHCP message. In contrast
+/// to [`Message`](crate::types::Message), it doesn't parse (and own) every
+/// option up front, but lazily locates options by tag without allocating.
+/// This allows routing messages cheaply before doing a full parse.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageView<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> MessageView<'a> {
+    /// Creates a new view over `buf`. This only validates the length of the
+    /// fixed fields and the presence of the magic cookie. Malformed options
+    /// are detected when looking them up.
+    pub fn new(buf: &'a [u8]) -> Result<Self, MessageViewError> {
+        if buf.len() < OPTIONS_OFFSET {
+            return Err(MessageViewError::BufTooShort);
+        }
+
+        if buf[MAGIC_COOKIE_OFFSET..OPTIONS_OFFSET] != constants::MAGIC_COOKIE_ARR {
+            return Err(MessageViewError::NoMagicCookie);
+        }
+
+        Ok(Self { buf })
+    }
+
+    /// Returns the raw message.
+    pub(crate) fn bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Returns the transaction ID.
+    pub fn xid(&self) -> u32 {
+        let mut xid = [0u8; 4];
+        xid.copy_from_slice(&self.buf[XID_OFFSET..XID_OFFSET + 4]);
+        u32::from_be_bytes(xid)
+    }
+
+    /// Returns the DHCP message type (option 53), if present and valid.
+    pub fn message_type(&self) -> Option<DhcpMessageType> {
+        let data = self.option(OptionTag::DhcpMessageType)?;
+        let mut buf = ReadBuffer::new(data);
+
+        DhcpMessageType::read::<BigEndian>(&mut buf).ok()
+    }
+
+    /// Returns the data of the first option with `tag`, without the tag and
+    /// length octets. Returns [`None`] if the option is not present or the
+    /// options field is truncated before the option is found.
+    pub fn option(&self, tag: OptionTag) -> Option<&'a [u8]> {
+        self.option_range(tag).map(|range| &self.buf[range])
+    }
+
+    /// Returns the range of the data of the first option with `tag` within
+    /// the whole message, like [`MessageView::option`].
+    pub(crate) fn option_range(&self, tag: OptionTag) -> Option<Range<usize>> {
+        let tag = u8::from(tag);
+        let options = &self.buf[OPTIONS_OFFSET..];
+        let mut offset = 0;
+
+        while offset < options.len() {
+            match options[offset] {
+                // Pad
+                0 => offset += 1,
+                // End
+                255 => return None,
+                t => {
+                    let len = *options.get(offset + 1)? as usize;
+                    let start = offset + 2;
+
+                    if start + len > options.len() {
+                        return None;
+                    }
+
+                    if t == tag {
+                        return Some(OPTIONS_OFFSET + start..OPTIONS_OFFSET + start + len);
+                    }
+
+                    offset = start + len;
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Message, OptionData};
+
+    fn discover() -> Vec<u8> {
+        let mut message = Message::new_with_xid(0xdeadbeef);
+        message
+            .add_option_parts(
+                OptionTag::MaxDhcpMessageSize,
+                OptionData::MaxDhcpMessageSize(1500),
+            )
+            .unwrap();
+        message
+            .add_option_parts(
+                OptionTag::DhcpMessageType,
+                OptionData::DhcpMessageType(DhcpMessageType::Discover),
+            )
+            .unwrap();
+        message.end().unwrap();
+
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    #[test]
+    fn test_message_view() {
+        let bytes = discover();
+        let view = MessageView::new(&bytes).unwrap();
+
+        assert_eq!(view.xid(), 0xdeadbeef);
+        assert_eq!(view.message_type(), Some(DhcpMessageType::Discover));
+        assert_eq!(
+            view.option(OptionTag::MaxDhcpMessageSize),
+            Some(&[0x05, 0xdc][..])
+        );
+        assert_eq!(view.option(OptionTag::Router), None);
+    }
+
+    #[test]
+    fn test_message_view_truncated_option() {
+        let mut bytes = discover();
+
+        // Cut off the End option and the message type data
+        bytes.truncate(bytes.len() - 2);
+        let view = MessageView::new(&bytes).unwrap();
+
+        assert_eq!(view.message_type(), None);
+    }
+
+    #[test]
+    fn test_message_view_no_magic_cookie() {
+        let mut bytes = discover();
+        bytes[MAGIC_COOKIE_OFFSET] = 0;
+
+        assert!(matches!(
+            MessageView::new(&bytes),
+            Err(MessageViewError::NoMagicCookie)
+        ));
+        assert!(matches!(
+            MessageView::new(&bytes[..100]),
+            Err(MessageViewError::BufTooShort)
+        ));
+    }
+}
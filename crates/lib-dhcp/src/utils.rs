@@ -1,49 +1,9 @@
-use std::{future::Future, time::Duration};
+use std::net::SocketAddr;
 
 use network_interface::{Error as InterfaceError, NetworkInterface, NetworkInterfaceConfig};
-use tokio::time::timeout as to;
+use tracing::{enabled, trace, Level};
 
-pub enum TimeoutResult<O, E> {
-    Timeout,
-    Error(E),
-    Ok(O),
-}
-
-/// Requires a future to complete before the specified duration has elapsed.
-/// This type returns one of three distinct variants:
-///
-/// - [`TimeoutResult::Timeout`] indicates that the specified duration has
-///   elapsed.
-/// - [`TimeoutResult::Error`] indicates that the future resulted in an error.
-/// - [`TimeoutResult::Ok`] indicates the future resolved correctly and has
-///   returned a success value.
-///
-/// ### Example
-///
-/// ```ignore
-/// use std::time::Duration;
-///
-/// use portal::utils::{timeout, TimeoutResult};
-/// use tokio::net::UdpSocket;
-///
-/// let socket = match timeout(Duration::from_secs(2), UdpSocket::bind("127.0.0.1:0")).await {
-///     TimeoutResult::Timeout => panic!("Binding UDP socket timed out"),
-///     TimeoutResult::Error(err) => panic!("An error occurred: {}", err),
-///     TimeoutResult::Ok(socket) => socket,
-/// };
-/// ```
-pub async fn timeout<T: Future<Output = Result<O, E>>, O, E>(
-    d: Duration,
-    f: T,
-) -> TimeoutResult<O, E> {
-    match to(d, f).await {
-        Ok(res) => match res {
-            Ok(o) => TimeoutResult::Ok(o),
-            Err(err) => TimeoutResult::Error(err),
-        },
-        Err(_) => TimeoutResult::Timeout,
-    }
-}
+use crate::{types::Message, WIRE_TARGET};
 
 pub fn select_network_interface(
     name: &String,
@@ -88,3 +48,75 @@ pub fn select_network_interface(
 
     Ok(None)
 }
+
+/// Logs the full decoded `message` received from `addr` at trace level.
+/// Formatting the whole message is expensive, so it is only done when the
+/// trace level is enabled.
+pub(crate) fn trace_message(message: &Message, addr: SocketAddr) {
+    if enabled!(target: WIRE_TARGET, Level::TRACE) {
+        trace!(target: WIRE_TARGET, %addr, "received DHCP message\n{}", message);
+    }
+}
+
+/// Writer which captures the log output of a test.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct LogCapture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl LogCapture {
+    /// Returns a subscriber writing all events up to `level` into the
+    /// capture, without ANSI colors.
+    pub(crate) fn subscriber(&self, level: Level) -> impl tracing::Subscriber {
+        let writer = self.clone();
+
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish()
+    }
+
+    /// Returns the output captured so far.
+    pub(crate) fn output(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_at(level: Level) -> String {
+        let capture = LogCapture::default();
+
+        let message = Message::new_with_xid(0xdeadbeef);
+        let addr = SocketAddr::from(([192, 168, 1, 1], 67));
+
+        tracing::subscriber::with_default(capture.subscriber(level), || {
+            trace_message(&message, addr)
+        });
+
+        capture.output()
+    }
+
+    #[test]
+    fn test_trace_message() {
+        let output = log_at(Level::TRACE);
+        assert!(output.contains("received DHCP message"));
+        assert!(output.contains("XID: 0xDEADBEEF"));
+
+        assert!(log_at(Level::INFO).is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    io,
+    net::{SocketAddr, SocketAddrV6},
+    time::Duration,
+};
+
+use binbuf::prelude::*;
+use lib_ifs::InterfacesError;
+use thiserror::Error;
+use tokio::{
+    net::UdpSocket,
+    time::{timeout_at, Instant},
+};
+use tracing::debug;
+
+use crate::{
+    v6::{
+        DhcpOption, Duid, Message, MessageError, MessageType, OptionCode,
+        ALL_DHCP_RELAY_AGENTS_AND_SERVERS, CLIENT_PORT, SERVER_PORT,
+    },
+    WIRE_TARGET,
+};
+
+/// Time the client waits for a REPLY to its INFORMATION-REQUEST.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("{code} IO error: {0}", code = self.code())]
+    Io(#[from] io::Error),
+
+    #[error("{code} Message error: {0}", code = self.code())]
+    MessageError(#[from] MessageError),
+
+    #[error("{code} Interface error: {0}", code = self.code())]
+    InterfacesError(#[from] InterfacesError),
+
+    #[error("{code} No link-local address on interface {0}", code = self.code())]
+    NoLinkLocalAddr(String),
+
+    #[error("{code} No DHCPv6 reply received within {0:?}", code = self.code())]
+    Timeout(Duration),
+}
+
+error_codes!(ClientError {
+    Io => "VULCAN-C201",
+    MessageError => "VULCAN-C202",
+    InterfacesError => "VULCAN-C203",
+    NoLinkLocalAddr => "VULCAN-C204",
+    Timeout => "VULCAN-C205",
+});
+
+/// Stateless DHCPv6 client, which retrieves the DNS configuration with a
+/// single INFORMATION-REQUEST/REPLY exchange.
+#[derive(Debug, Clone)]
+pub struct Client {
+    duid: Duid,
+    timeout: Duration,
+    requested_options: Vec<OptionCode>,
+}
+
+impl Client {
+    pub fn new(duid: Duid) -> Self {
+        Self {
+            duid,
+            timeout: DEFAULT_TIMEOUT,
+            requested_options: vec![OptionCode::DnsServers, OptionCode::DomainList],
+        }
+    }
+
+    /// Sets the time to wait for a REPLY.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the options requested from the server, by default the DNS
+    /// servers and the domain list.
+    pub fn with_requested_options(mut self, codes: Vec<OptionCode>) -> Self {
+        self.requested_options = codes;
+        self
+    }
+
+    /// Multicasts an INFORMATION-REQUEST to all servers on the link of
+    /// `interface`, sent from the link-local address of the interface.
+    /// Returns the first REPLY received.
+    pub async fn request_information(&self, interface: &str) -> Result<Message, ClientError> {
+        let addr = lib_ifs::if_addrs_v6()?
+            .into_iter()
+            .find(|addr| addr.name == interface && is_unicast_link_local(&addr.addr.segments()))
+            .ok_or_else(|| ClientError::NoLinkLocalAddr(interface.to_string()))?;
+
+        let local = SocketAddrV6::new(addr.addr, CLIENT_PORT, 0, addr.scope_id);
+        let server = SocketAddrV6::new(
+            ALL_DHCP_RELAY_AGENTS_AND_SERVERS,
+            SERVER_PORT,
+            0,
+            addr.scope_id,
+        );
+
+        let socket = UdpSocket::bind(local).await?;
+        self.exchange(&socket, server.into()).await
+    }
+
+    /// Sends an INFORMATION-REQUEST on `socket` to `server` and waits for
+    /// the matching REPLY. Replies to other transactions or other clients
+    /// are skipped.
+    pub async fn exchange(
+        &self,
+        socket: &UdpSocket,
+        server: SocketAddr,
+    ) -> Result<Message, ClientError> {
+        let request = self.make_information_request(rand::random());
+
+        let mut buf = WriteBuffer::new();
+        request.write_be(&mut buf)?;
+        socket.send_to(buf.bytes(), server).await?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0u8; 1500];
+
+        loop {
+            let (len, addr) = match timeout_at(deadline, socket.recv_from(&mut buf)).await {
+                Ok(result) => result?,
+                Err(_) => return Err(ClientError::Timeout(self.timeout)),
+            };
+
+            let reply = match Message::read::<BigEndian>(&mut ReadBuffer::new(&buf[..len])) {
+                Ok(reply) => reply,
+                Err(err) => {
+                    debug!(target: WIRE_TARGET, %err, %addr, "failed to parse DHCPv6 message");
+                    continue;
+                }
+            };
+
+            if reply.msg_type == MessageType::Reply
+                && reply.transaction_id == request.transaction_id
+                && reply.client_id() == Some(&self.duid)
+            {
+                return Ok(reply);
+            }
+        }
+    }
+
+    /// Creates an INFORMATION-REQUEST with the transaction ID `xid`.
+    ///
+    /// ### See
+    ///
+    /// RFC 8415 - Section 18.2.6 - Creation and Transmission of Information-request Messages: https://datatracker.ietf.org/doc/html/rfc8415#section-18.2.6
+    pub fn make_information_request(&self, xid: u32) -> Message {
+        let mut request = Message::new(MessageType::InformationRequest, xid);
+        request.add_option(DhcpOption::ClientId(self.duid.clone()));
+        request.add_option(DhcpOption::ElapsedTime(0));
+        request.add_option(DhcpOption::OptionRequest(self.requested_options.clone()));
+
+        request
+    }
+}
+
+/// Returns if the address with `segments` is in fe80::/10.
+fn is_unicast_link_local(segments: &[u16; 8]) -> bool {
+    segments[0] & 0xffc0 == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::*;
+    use crate::v6::Responder;
+
+    async fn loopback_socket() -> UdpSocket {
+        UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_information_request_loopback() {
+        let responder = Responder::new(Duid::from(vec![0, 3, 0, 1, 1, 2, 3, 4, 5, 6]))
+            .with_dns_servers(vec!["2001:db8::53".parse().unwrap()])
+            .with_domain_list(vec![String::from("example.com")]);
+
+        let server_socket = loopback_socket().await;
+        let server_addr = server_socket.local_addr().unwrap();
+        let server = tokio::spawn(async move { responder.serve(&server_socket).await });
+
+        let client = Client::new(Duid::link_layer(1, &[0xde, 0xad, 0xbe, 0xef, 0x12, 0x34]))
+            .with_timeout(Duration::from_secs(2));
+        let socket = loopback_socket().await;
+
+        let reply = client.exchange(&socket, server_addr).await.unwrap();
+        assert_eq!(
+            reply.dns_servers(),
+            Some(&vec!["2001:db8::53".parse().unwrap()])
+        );
+        assert_eq!(
+            reply.domain_list(),
+            Some(&vec![String::from("example.com")])
+        );
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_information_request_timeout() {
+        // Nobody answers on the silent socket
+        let silent = loopback_socket().await;
+        let client = Client::new(Duid::from(vec![1])).with_timeout(Duration::from_millis(50));
+
+        let result = client
+            .exchange(&loopback_socket().await, silent.local_addr().unwrap())
+            .await;
+        assert!(matches!(result, Err(ClientError::Timeout(_))));
+    }
+
+    #[test]
+    fn test_link_local() {
+        let addr: Ipv6Addr = "fe80::1".parse().unwrap();
+        assert!(is_unicast_link_local(&addr.segments()));
+
+        let addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert!(!is_unicast_link_local(&addr.segments()));
+    }
+}
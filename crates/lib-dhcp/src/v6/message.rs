@@ -0,0 +1,290 @@
+use std::{fmt::Display, net::Ipv6Addr};
+
+use binbuf::prelude::*;
+use thiserror::Error;
+
+use crate::v6::{DhcpOption, Duid, OptionCode};
+
+#[derive(Debug, Error)]
+pub enum MessageError {
+    #[error("{code} Buffer error: {0}", code = self.code())]
+    BufferError(#[from] BufferError),
+
+    #[error("{code} Unknown or unsupported DHCPv6 message type {0}", code = self.code())]
+    UnknownMessageType(u8),
+
+    #[error("{code} DHCPv6 option {0} has an invalid length of {1} octets", code = self.code())]
+    InvalidOptionLength(u16, usize),
+
+    #[error("{code} Invalid domain name in DHCPv6 option {0}", code = self.code())]
+    InvalidDomainName(u16),
+}
+
+error_codes!(MessageError {
+    BufferError => "VULCAN-P301",
+    UnknownMessageType => "VULCAN-P302",
+    InvalidOptionLength => "VULCAN-P303",
+    InvalidDomainName => "VULCAN-P304",
+});
+
+/// Type of a client/server message. Relay messages use a different format
+/// and are not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Solicit,
+    Advertise,
+    Request,
+    Confirm,
+    Renew,
+    Rebind,
+    Reply,
+    Release,
+    Decline,
+    Reconfigure,
+    InformationRequest,
+}
+
+impl Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Solicit => "SOLICIT",
+            Self::Advertise => "ADVERTISE",
+            Self::Request => "REQUEST",
+            Self::Confirm => "CONFIRM",
+            Self::Renew => "RENEW",
+            Self::Rebind => "REBIND",
+            Self::Reply => "REPLY",
+            Self::Release => "RELEASE",
+            Self::Decline => "DECLINE",
+            Self::Reconfigure => "RECONFIGURE",
+            Self::InformationRequest => "INFORMATION-REQUEST",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = MessageError;
+
+    fn try_from(ty: u8) -> Result<Self, Self::Error> {
+        let ty = match ty {
+            1 => Self::Solicit,
+            2 => Self::Advertise,
+            3 => Self::Request,
+            4 => Self::Confirm,
+            5 => Self::Renew,
+            6 => Self::Rebind,
+            7 => Self::Reply,
+            8 => Self::Release,
+            9 => Self::Decline,
+            10 => Self::Reconfigure,
+            11 => Self::InformationRequest,
+            ty => return Err(MessageError::UnknownMessageType(ty)),
+        };
+
+        Ok(ty)
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(ty: MessageType) -> Self {
+        match ty {
+            MessageType::Solicit => 1,
+            MessageType::Advertise => 2,
+            MessageType::Request => 3,
+            MessageType::Confirm => 4,
+            MessageType::Renew => 5,
+            MessageType::Rebind => 6,
+            MessageType::Reply => 7,
+            MessageType::Release => 8,
+            MessageType::Decline => 9,
+            MessageType::Reconfigure => 10,
+            MessageType::InformationRequest => 11,
+        }
+    }
+}
+
+/// A DHCPv6 client/server message.
+///
+/// ### See
+///
+/// RFC 8415 - Section 8 - Client/Server Message Formats: https://datatracker.ietf.org/doc/html/rfc8415#section-8
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub msg_type: MessageType,
+
+    /// Transaction ID, only the lower 24 bits are used.
+    pub transaction_id: u32,
+    pub options: Vec<DhcpOption>,
+}
+
+impl Readable for Message {
+    type Error = MessageError;
+
+    fn read<E: Endianness>(buf: &mut ReadBuffer) -> Result<Self, Self::Error> {
+        let msg_type = MessageType::try_from(buf.pop()?)?;
+
+        let xid = buf.read_slice(3)?;
+        let transaction_id = u32::from_be_bytes([0, xid[0], xid[1], xid[2]]);
+
+        let mut options = Vec::new();
+        while !buf.is_empty() {
+            options.push(DhcpOption::read::<E>(buf)?);
+        }
+
+        Ok(Self {
+            msg_type,
+            transaction_id,
+            options,
+        })
+    }
+}
+
+impl Writeable for Message {
+    type Error = MessageError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        buf.push(self.msg_type.into());
+        let mut n = 1 + buf.write(&self.transaction_id.to_be_bytes()[1..]);
+
+        for option in &self.options {
+            n += option.write::<E>(buf)?;
+        }
+
+        Ok(n)
+    }
+}
+
+impl Message {
+    pub fn new(msg_type: MessageType, transaction_id: u32) -> Self {
+        Self {
+            msg_type,
+            transaction_id: transaction_id & 0x00ff_ffff,
+            options: Vec::new(),
+        }
+    }
+
+    pub fn add_option(&mut self, option: DhcpOption) {
+        self.options.push(option);
+    }
+
+    pub fn get_option(&self, code: OptionCode) -> Option<&DhcpOption> {
+        self.options.iter().find(|option| option.code() == code)
+    }
+
+    pub fn client_id(&self) -> Option<&Duid> {
+        match self.get_option(OptionCode::ClientId) {
+            Some(DhcpOption::ClientId(duid)) => Some(duid),
+            _ => None,
+        }
+    }
+
+    pub fn server_id(&self) -> Option<&Duid> {
+        match self.get_option(OptionCode::ServerId) {
+            Some(DhcpOption::ServerId(duid)) => Some(duid),
+            _ => None,
+        }
+    }
+
+    /// Returns the codes of the option request option (6), which is empty if
+    /// the option is missing.
+    pub fn requested_options(&self) -> &[OptionCode] {
+        match self.get_option(OptionCode::OptionRequest) {
+            Some(DhcpOption::OptionRequest(codes)) => codes,
+            _ => &[],
+        }
+    }
+
+    pub fn dns_servers(&self) -> Option<&Vec<Ipv6Addr>> {
+        match self.get_option(OptionCode::DnsServers) {
+            Some(DhcpOption::DnsServers(servers)) => Some(servers),
+            _ => None,
+        }
+    }
+
+    pub fn domain_list(&self) -> Option<&Vec<String>> {
+        match self.get_option(OptionCode::DomainList) {
+            Some(DhcpOption::DomainList(domains)) => Some(domains),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(message: &Message) -> Vec<u8> {
+        let mut buf = WriteBuffer::new();
+        message.write::<BigEndian>(&mut buf).unwrap();
+        buf.bytes().to_vec()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut message = Message::new(MessageType::Reply, 0xdead_beef);
+        message.add_option(DhcpOption::ClientId(Duid::link_layer(
+            1,
+            &[0xde, 0xad, 0xbe, 0xef, 0x12, 0x34],
+        )));
+        message.add_option(DhcpOption::ServerId(Duid::from(vec![0, 1, 2, 3])));
+        message.add_option(DhcpOption::OptionRequest(vec![
+            OptionCode::DnsServers,
+            OptionCode::DomainList,
+            OptionCode::Unknown(82),
+        ]));
+        message.add_option(DhcpOption::ElapsedTime(100));
+        message.add_option(DhcpOption::DnsServers(vec![
+            "2001:db8::53".parse().unwrap(),
+            "2001:db8::54".parse().unwrap(),
+        ]));
+        message.add_option(DhcpOption::DomainList(vec![
+            String::from("example.com"),
+            String::from("lab.example.com"),
+        ]));
+        message.add_option(DhcpOption::Unknown(39, vec![1, 2, 3]));
+
+        let bytes = encode(&message);
+        assert_eq!(bytes[..4], [7, 0xad, 0xbe, 0xef]);
+
+        let read = Message::read::<BigEndian>(&mut ReadBuffer::new(&bytes)).unwrap();
+        assert_eq!(read, message);
+        assert_eq!(read.transaction_id, 0x00ad_beef);
+    }
+
+    #[test]
+    fn test_domain_list_encoding() {
+        let mut message = Message::new(MessageType::Reply, 1);
+        message.add_option(DhcpOption::DomainList(vec![String::from("example.com.")]));
+
+        assert_eq!(
+            encode(&message)[4..],
+            [0, 24, 0, 13, 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0]
+        );
+
+        message.options = vec![DhcpOption::DomainList(vec![String::from("a..b")])];
+        let mut buf = WriteBuffer::new();
+        assert!(matches!(
+            message.write::<BigEndian>(&mut buf),
+            Err(MessageError::InvalidDomainName(24))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        // Relay-forward messages are not supported
+        let mut buf = ReadBuffer::new(&[12, 0, 0, 1]);
+        assert!(matches!(
+            Message::read::<BigEndian>(&mut buf),
+            Err(MessageError::UnknownMessageType(12))
+        ));
+
+        // DNS servers option with a truncated address
+        let mut buf = ReadBuffer::new(&[7, 0, 0, 1, 0, 23, 0, 4, 0x20, 0x01, 0x0d, 0xb8]);
+        assert!(matches!(
+            Message::read::<BigEndian>(&mut buf),
+            Err(MessageError::InvalidOptionLength(23, 4))
+        ));
+    }
+}
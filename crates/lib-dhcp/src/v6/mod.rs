@@ -0,0 +1,26 @@
+//! Minimal stateless DHCPv6 (RFC 8415 Section 6.1). Dual-stack hosts often
+//! only need their DNS configuration via DHCPv6, while addresses come from
+//! SLAAC. This module supports exactly that: the INFORMATION-REQUEST/REPLY
+//! exchange with the client and server identifier, option request, elapsed
+//! time, DNS servers and domain list options.
+//!
+//! The types are independent of the DHCPv4 ones. Relay messages and address
+//! assignment (IA_NA, IA_PD) are not supported.
+use std::net::Ipv6Addr;
+
+mod client;
+mod message;
+mod option;
+mod responder;
+
+pub use client::{Client, ClientError, DEFAULT_TIMEOUT};
+pub use message::{Message, MessageError, MessageType};
+pub use option::{DhcpOption, Duid, OptionCode};
+pub use responder::Responder;
+
+pub const CLIENT_PORT: u16 = 546;
+pub const SERVER_PORT: u16 = 547;
+
+/// Link-scoped multicast address of all DHCPv6 servers and relay agents
+/// (ff02::1:2).
+pub const ALL_DHCP_RELAY_AGENTS_AND_SERVERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
@@ -0,0 +1,273 @@
+use std::{fmt::Display, net::Ipv6Addr};
+
+use binbuf::prelude::*;
+
+use crate::v6::MessageError;
+
+/// Code of a DHCPv6 option.
+///
+/// ### See
+///
+/// RFC 8415 - Section 21 - DHCP Options: https://datatracker.ietf.org/doc/html/rfc8415#section-21
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionCode {
+    ClientId,
+    ServerId,
+    OptionRequest,
+    ElapsedTime,
+    DnsServers,
+    DomainList,
+    Unknown(u16),
+}
+
+impl From<u16> for OptionCode {
+    fn from(code: u16) -> Self {
+        match code {
+            1 => Self::ClientId,
+            2 => Self::ServerId,
+            6 => Self::OptionRequest,
+            8 => Self::ElapsedTime,
+            23 => Self::DnsServers,
+            24 => Self::DomainList,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<OptionCode> for u16 {
+    fn from(code: OptionCode) -> Self {
+        match code {
+            OptionCode::ClientId => 1,
+            OptionCode::ServerId => 2,
+            OptionCode::OptionRequest => 6,
+            OptionCode::ElapsedTime => 8,
+            OptionCode::DnsServers => 23,
+            OptionCode::DomainList => 24,
+            OptionCode::Unknown(code) => code,
+        }
+    }
+}
+
+impl Display for OptionCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", u16::from(*self))
+    }
+}
+
+/// DHCP unique identifier of a client or server. The content is opaque,
+/// DUIDs are only compared for equality.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duid(Vec<u8>);
+
+impl Duid {
+    /// Type of DUIDs based on the link-layer address (DUID-LL).
+    pub const LINK_LAYER: u16 = 3;
+
+    /// Creates a DUID-LL from the hardware type (1 for Ethernet) and the
+    /// link-layer address of an interface.
+    pub fn link_layer(hardware_type: u16, addr: &[u8]) -> Self {
+        let mut duid = Vec::with_capacity(4 + addr.len());
+        duid.extend_from_slice(&Self::LINK_LAYER.to_be_bytes());
+        duid.extend_from_slice(&hardware_type.to_be_bytes());
+        duid.extend_from_slice(addr);
+
+        Self(duid)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Duid {
+    fn from(duid: Vec<u8>) -> Self {
+        Self(duid)
+    }
+}
+
+impl Display for Duid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex: Vec<String> = self.0.iter().map(|b| format!("{:02x}", b)).collect();
+        f.write_str(&hex.join(":"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DhcpOption {
+    ClientId(Duid),
+    ServerId(Duid),
+
+    /// Codes of the options the client is interested in.
+    OptionRequest(Vec<OptionCode>),
+
+    /// Time since the client began the exchange in hundredths of a second.
+    ElapsedTime(u16),
+
+    /// Recursive DNS servers (RFC 3646).
+    DnsServers(Vec<Ipv6Addr>),
+
+    /// Domain search list (RFC 3646).
+    DomainList(Vec<String>),
+    Unknown(u16, Vec<u8>),
+}
+
+impl Readable for DhcpOption {
+    type Error = MessageError;
+
+    fn read<E: Endianness>(buf: &mut ReadBuffer) -> Result<Self, Self::Error> {
+        let code = OptionCode::from(u16::read::<E>(buf)?);
+        let len = u16::read::<E>(buf)? as usize;
+        let data = buf.read_slice(len)?;
+        let mut data_buf = ReadBuffer::new(data);
+
+        let option = match code {
+            OptionCode::ClientId => Self::ClientId(Duid(data.to_vec())),
+            OptionCode::ServerId => Self::ServerId(Duid(data.to_vec())),
+            OptionCode::OptionRequest => {
+                check_multiple_of(code, len, 2)?;
+
+                let mut codes = Vec::new();
+                while !data_buf.is_empty() {
+                    codes.push(OptionCode::from(u16::read::<E>(&mut data_buf)?));
+                }
+                Self::OptionRequest(codes)
+            }
+            OptionCode::ElapsedTime => {
+                if len != 2 {
+                    return Err(MessageError::InvalidOptionLength(code.into(), len));
+                }
+                Self::ElapsedTime(u16::read::<E>(&mut data_buf)?)
+            }
+            OptionCode::DnsServers => {
+                check_multiple_of(code, len, 16)?;
+
+                let mut servers = Vec::new();
+                while !data_buf.is_empty() {
+                    servers.push(Ipv6Addr::read::<E>(&mut data_buf)?);
+                }
+                Self::DnsServers(servers)
+            }
+            OptionCode::DomainList => {
+                let mut domains = Vec::new();
+                while !data_buf.is_empty() {
+                    domains.push(read_domain_name(&mut data_buf, code)?);
+                }
+                Self::DomainList(domains)
+            }
+            OptionCode::Unknown(code) => Self::Unknown(code, data.to_vec()),
+        };
+
+        Ok(option)
+    }
+}
+
+impl Writeable for DhcpOption {
+    type Error = MessageError;
+
+    fn write<E: Endianness>(&self, buf: &mut WriteBuffer) -> Result<usize, Self::Error> {
+        // The length precedes the data, write the data first
+        let mut data = WriteBuffer::new();
+
+        match self {
+            Self::ClientId(duid) | Self::ServerId(duid) => {
+                data.write(duid.as_bytes());
+            }
+            Self::OptionRequest(codes) => {
+                for code in codes {
+                    u16::from(*code).write::<E>(&mut data)?;
+                }
+            }
+            Self::ElapsedTime(time) => {
+                time.write::<E>(&mut data)?;
+            }
+            Self::DnsServers(servers) => {
+                servers.write::<E>(&mut data)?;
+            }
+            Self::DomainList(domains) => {
+                for domain in domains {
+                    write_domain_name(domain, &mut data, self.code())?;
+                }
+            }
+            Self::Unknown(_, bytes) => {
+                data.write(bytes);
+            }
+        }
+
+        if data.len() > u16::MAX as usize {
+            return Err(MessageError::InvalidOptionLength(
+                self.code().into(),
+                data.len(),
+            ));
+        }
+
+        let n = bytes_written! {
+            u16::from(self.code()).write::<E>(buf)?;
+            (data.len() as u16).write::<E>(buf)?;
+            buf.write(data.bytes())
+        };
+
+        Ok(n)
+    }
+}
+
+impl DhcpOption {
+    pub fn code(&self) -> OptionCode {
+        match self {
+            Self::ClientId(_) => OptionCode::ClientId,
+            Self::ServerId(_) => OptionCode::ServerId,
+            Self::OptionRequest(_) => OptionCode::OptionRequest,
+            Self::ElapsedTime(_) => OptionCode::ElapsedTime,
+            Self::DnsServers(_) => OptionCode::DnsServers,
+            Self::DomainList(_) => OptionCode::DomainList,
+            Self::Unknown(code, _) => OptionCode::Unknown(*code),
+        }
+    }
+}
+
+fn check_multiple_of(code: OptionCode, len: usize, size: usize) -> Result<(), MessageError> {
+    match len % size {
+        0 => Ok(()),
+        _ => Err(MessageError::InvalidOptionLength(code.into(), len)),
+    }
+}
+
+/// Reads a domain name in the uncompressed wire format of RFC 1035 Section
+/// 3.1. DHCPv6 never uses name compression (RFC 8415 Section 10).
+fn read_domain_name(buf: &mut ReadBuffer, code: OptionCode) -> Result<String, MessageError> {
+    let mut labels = Vec::new();
+
+    loop {
+        let len = buf.pop()? as usize;
+        if len == 0 {
+            break;
+        }
+
+        if len > 63 {
+            return Err(MessageError::InvalidDomainName(code.into()));
+        }
+
+        labels.push(String::from_utf8_lossy(buf.read_slice(len)?).into_owned());
+    }
+
+    Ok(labels.join("."))
+}
+
+fn write_domain_name(
+    domain: &str,
+    buf: &mut WriteBuffer,
+    code: OptionCode,
+) -> Result<usize, MessageError> {
+    let mut n = 0;
+
+    for label in domain.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(MessageError::InvalidDomainName(code.into()));
+        }
+
+        buf.push(label.len() as u8);
+        n += 1 + buf.write(label.as_bytes());
+    }
+
+    buf.push(0);
+    Ok(n + 1)
+}
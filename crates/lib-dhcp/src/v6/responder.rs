@@ -0,0 +1,195 @@
+use std::{
+    io,
+    net::{Ipv6Addr, SocketAddr, SocketAddrV6},
+};
+
+use binbuf::prelude::*;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+
+use crate::{
+    v6::{
+        DhcpOption, Duid, Message, MessageType, OptionCode, ALL_DHCP_RELAY_AGENTS_AND_SERVERS,
+        SERVER_PORT,
+    },
+    WIRE_TARGET,
+};
+
+/// Stateless DHCPv6 server. It answers INFORMATION-REQUESTs with the
+/// configured DNS servers and domain list and ignores all other messages, as
+/// it doesn't assign addresses.
+#[derive(Debug, Clone)]
+pub struct Responder {
+    duid: Duid,
+    dns_servers: Vec<Ipv6Addr>,
+    domain_list: Vec<String>,
+}
+
+impl Responder {
+    pub fn new(duid: Duid) -> Self {
+        Self {
+            duid,
+            dns_servers: Vec::new(),
+            domain_list: Vec::new(),
+        }
+    }
+
+    /// Sets the DNS servers (option 23) handed out to clients.
+    pub fn with_dns_servers(mut self, servers: Vec<Ipv6Addr>) -> Self {
+        self.dns_servers = servers;
+        self
+    }
+
+    /// Sets the domain search list (option 24) handed out to clients.
+    pub fn with_domain_list(mut self, domains: Vec<String>) -> Self {
+        self.domain_list = domains;
+        self
+    }
+
+    pub fn duid(&self) -> &Duid {
+        &self.duid
+    }
+
+    /// Returns the REPLY to `request`, or [`None`] if the request has to be
+    /// ignored. Requests addressed to another server are ignored as well.
+    /// Only options requested in the option request option are included.
+    ///
+    /// ### See
+    ///
+    /// RFC 8415 - Section 18.3.6 - Receipt of Information-request Messages: https://datatracker.ietf.org/doc/html/rfc8415#section-18.3.6
+    pub fn reply(&self, request: &Message) -> Option<Message> {
+        if request.msg_type != MessageType::InformationRequest {
+            return None;
+        }
+
+        if matches!(request.server_id(), Some(id) if id != &self.duid) {
+            return None;
+        }
+
+        let mut reply = Message::new(MessageType::Reply, request.transaction_id);
+
+        if let Some(client_id) = request.client_id() {
+            reply.add_option(DhcpOption::ClientId(client_id.clone()));
+        }
+
+        reply.add_option(DhcpOption::ServerId(self.duid.clone()));
+
+        for code in request.requested_options() {
+            match code {
+                OptionCode::DnsServers if !self.dns_servers.is_empty() => {
+                    reply.add_option(DhcpOption::DnsServers(self.dns_servers.clone()))
+                }
+                OptionCode::DomainList if !self.domain_list.is_empty() => {
+                    reply.add_option(DhcpOption::DomainList(self.domain_list.clone()))
+                }
+                _ => {}
+            }
+        }
+
+        Some(reply)
+    }
+
+    /// Binds the server port on all addresses and joins the
+    /// All_DHCP_Relay_Agents_and_Servers group on the interfaces with
+    /// `interface_indices`. The index 0 lets the kernel choose the interface.
+    pub fn bind(interface_indices: &[u32]) -> io::Result<UdpSocket> {
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_only_v6(true)?;
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+
+        let addr = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, SERVER_PORT, 0, 0);
+        socket.bind(&SocketAddr::from(addr).into())?;
+
+        for index in interface_indices {
+            socket.join_multicast_v6(&ALL_DHCP_RELAY_AGENTS_AND_SERVERS, *index)?;
+        }
+
+        UdpSocket::from_std(socket.into())
+    }
+
+    /// Answers the requests received on `socket` until an IO error occurs.
+    /// Replies are sent to the source address of the request, which is the
+    /// link-local address of the client.
+    pub async fn serve(&self, socket: &UdpSocket) -> io::Result<()> {
+        let mut buf = [0u8; 1500];
+
+        loop {
+            let (len, addr) = socket.recv_from(&mut buf).await?;
+
+            let request = match Message::read::<BigEndian>(&mut ReadBuffer::new(&buf[..len])) {
+                Ok(request) => request,
+                Err(err) => {
+                    debug!(target: WIRE_TARGET, %err, %addr, "failed to parse DHCPv6 message");
+                    continue;
+                }
+            };
+
+            let reply = match self.reply(&request) {
+                Some(reply) => reply,
+                None => continue,
+            };
+
+            let mut out = WriteBuffer::new();
+            if let Err(err) = reply.write_be(&mut out) {
+                warn!(target: WIRE_TARGET, %err, "failed to encode DHCPv6 reply");
+                continue;
+            }
+
+            socket.send_to(out.bytes(), addr).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder() -> Responder {
+        Responder::new(Duid::from(vec![0, 3, 0, 1, 1, 2, 3, 4, 5, 6]))
+            .with_dns_servers(vec!["2001:db8::53".parse().unwrap()])
+    }
+
+    fn request(requested: Vec<OptionCode>) -> Message {
+        let mut request = Message::new(MessageType::InformationRequest, 42);
+        request.add_option(DhcpOption::ClientId(Duid::from(vec![9, 9])));
+        request.add_option(DhcpOption::OptionRequest(requested));
+        request
+    }
+
+    #[test]
+    fn test_reply_contains_requested_options() {
+        let reply = responder()
+            .reply(&request(vec![
+                OptionCode::DnsServers,
+                OptionCode::DomainList,
+            ]))
+            .unwrap();
+
+        assert_eq!(reply.msg_type, MessageType::Reply);
+        assert_eq!(reply.transaction_id, 42);
+        assert_eq!(reply.client_id(), Some(&Duid::from(vec![9, 9])));
+        assert_eq!(reply.server_id(), Some(responder().duid()));
+        assert_eq!(
+            reply.dns_servers(),
+            Some(&vec!["2001:db8::53".parse().unwrap()])
+        );
+
+        // No domain list is configured
+        assert_eq!(reply.domain_list(), None);
+
+        let reply = responder().reply(&request(vec![])).unwrap();
+        assert_eq!(reply.dns_servers(), None);
+    }
+
+    #[test]
+    fn test_ignored_requests() {
+        let mut other_server = request(vec![OptionCode::DnsServers]);
+        other_server.add_option(DhcpOption::ServerId(Duid::from(vec![1])));
+        assert!(responder().reply(&other_server).is_none());
+
+        let solicit = Message::new(MessageType::Solicit, 1);
+        assert!(responder().reply(&solicit).is_none());
+    }
+}
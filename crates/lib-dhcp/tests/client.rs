@@ -1,6 +1,6 @@
 use std::net::Ipv4Addr;
 
-use dhcp::Client;
+use dhcp::prelude::Client;
 
 #[tokio::test]
 async fn test_client() {
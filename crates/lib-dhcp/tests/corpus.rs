@@ -0,0 +1,157 @@
+//! Parses the replies of common DHCP servers in `tests/corpus/`. Each file
+//! holds a single frame as hex dump, preceded by `key: value` lines listing
+//! what the message must contain. Lines starting with `#` are comments, the
+//! format matches the client captures in `tests/interop/`.
+//!
+//! Every capture has to parse without panicking, which guards the option
+//! parsers against regressions and catches options they don't support yet.
+
+use std::{fs, net::Ipv4Addr, panic};
+
+use dhcp::types::{options::DhcpMessageType, Message, OptionData, OptionTag};
+
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+
+struct Capture {
+    name: String,
+    bytes: Vec<u8>,
+    expectations: Vec<(String, String)>,
+}
+
+fn captures() -> Vec<Capture> {
+    let mut paths: Vec<_> = fs::read_dir(CORPUS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let mut bytes = Vec::new();
+            let mut expectations = Vec::new();
+
+            for line in fs::read_to_string(path).unwrap().lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                if let Some((key, value)) = line.split_once(':') {
+                    expectations.push((key.trim().to_string(), value.trim().to_string()));
+                    continue;
+                }
+
+                for byte in line.split_whitespace() {
+                    let byte = u8::from_str_radix(byte, 16)
+                        .unwrap_or_else(|_| panic!("{name}: invalid hex '{byte}'"));
+                    bytes.push(byte);
+                }
+            }
+
+            Capture {
+                name,
+                bytes,
+                expectations,
+            }
+        })
+        .collect()
+}
+
+/// Returns the value of `key` in `message` in the notation of the corpus
+/// files.
+fn actual(message: &Message, key: &str) -> String {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(":")
+    };
+    let addrs = |addrs: &[Ipv4Addr]| {
+        addrs
+            .iter()
+            .map(Ipv4Addr::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let data = |tag: OptionTag| message.get_option(tag).map(|option| option.data());
+    let none = || String::from("none");
+
+    match key {
+        "type" => message.get_message_type().map_or_else(none, |ty| {
+            let name = match ty {
+                DhcpMessageType::Offer => "offer",
+                DhcpMessageType::Ack => "ack",
+                DhcpMessageType::Nak => "nak",
+                _ => "other",
+            };
+            name.to_string()
+        }),
+        "yiaddr" => message.yiaddr.to_string(),
+        "giaddr" => message.giaddr.to_string(),
+        "server-id" => message
+            .get_server_identifier()
+            .map_or_else(none, |ip| ip.to_string()),
+        "lease-time" => data(OptionTag::IpAddrLeaseTime)
+            .and_then(OptionData::as_u32)
+            .map_or_else(none, |secs| secs.to_string()),
+        "t1" => message
+            .get_renewal_t1_time()
+            .map_or_else(none, |secs| secs.to_string()),
+        "t2" => message
+            .get_rebinding_t2_time()
+            .map_or_else(none, |secs| secs.to_string()),
+        "subnet-mask" => data(OptionTag::SubnetMask)
+            .and_then(OptionData::as_ipv4)
+            .map_or_else(none, |mask| mask.to_string()),
+        "router" => data(OptionTag::Router)
+            .and_then(OptionData::as_ipv4_list)
+            .map_or_else(none, addrs),
+        "dns" => message
+            .get_domain_name_servers()
+            .map_or_else(none, |ips| addrs(ips)),
+        "ntp" => message
+            .get_ntp_servers()
+            .map_or_else(none, |ips| addrs(ips)),
+        "domain" => message.get_domain_name().cloned().unwrap_or_else(none),
+        "hostname" => message.get_host_name().cloned().unwrap_or_else(none),
+        "message" => message.get_message_text().cloned().unwrap_or_else(none),
+        "circuit-id" => message
+            .get_relay_agent_information()
+            .and_then(|info| info.circuit_id())
+            .map_or_else(none, hex),
+        "remote-id" => message
+            .get_relay_agent_information()
+            .and_then(|info| info.remote_id())
+            .map_or_else(none, hex),
+        "vendor" => message
+            .get_vendor_specific_information()
+            .map_or_else(none, hex),
+        _ => panic!("unknown expectation '{key}'"),
+    }
+}
+
+#[test]
+fn test_corpus_captures() {
+    let captures = captures();
+    assert!(!captures.is_empty());
+
+    for capture in &captures {
+        let result = panic::catch_unwind(|| Message::from_bytes(&capture.bytes))
+            .unwrap_or_else(|_| panic!("{}: parser panicked", capture.name));
+        let message =
+            result.unwrap_or_else(|err| panic!("{}: failed to parse: {}", capture.name, err));
+
+        for (key, expected) in &capture.expectations {
+            assert_eq!(
+                &actual(&message, key),
+                expected,
+                "{}: unexpected {}",
+                capture.name,
+                key
+            );
+        }
+    }
+}
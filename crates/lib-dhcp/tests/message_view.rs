@@ -0,0 +1,107 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    net::Ipv4Addr,
+};
+
+use binbuf::prelude::*;
+use dhcp::types::{
+    options::{DhcpMessageType, ParameterRequestList},
+    HardwareAddr, Message, MessageView, OptionData, OptionTag,
+};
+
+/// Global allocator which counts the allocations of the current thread.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|a| a.get());
+    f();
+    ALLOCATIONS.with(|a| a.get()) - before
+}
+
+/// A typical DHCPREQUEST as sent by the client.
+fn request() -> Vec<u8> {
+    let mut message = Message::new_with_xid(0x3903f326);
+    message
+        .set_hardware_address(HardwareAddr::try_from(String::from("de:ad:be:ef:12:34")).unwrap());
+
+    let options = vec![
+        (
+            OptionTag::MaxDhcpMessageSize,
+            OptionData::MaxDhcpMessageSize(1500),
+        ),
+        (
+            OptionTag::DhcpMessageType,
+            OptionData::DhcpMessageType(DhcpMessageType::Request),
+        ),
+        (
+            OptionTag::ServerIdentifier,
+            OptionData::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)),
+        ),
+        (
+            OptionTag::RequestedIpAddr,
+            OptionData::RequestedIpAddr(Ipv4Addr::new(192, 168, 1, 100)),
+        ),
+        (
+            OptionTag::IpAddrLeaseTime,
+            OptionData::IpAddrLeaseTime(3600),
+        ),
+        (
+            OptionTag::ParameterRequestList,
+            OptionData::ParameterRequestList(ParameterRequestList::new(vec![
+                OptionTag::Router,
+                OptionTag::DomainNameServer,
+                OptionTag::DomainName,
+            ])),
+        ),
+    ];
+
+    for (tag, data) in options {
+        message.add_option_parts(tag, data).unwrap();
+    }
+    message.end().unwrap();
+
+    let mut buf = WriteBuffer::new();
+    message.write::<BigEndian>(&mut buf).unwrap();
+    buf.bytes().to_vec()
+}
+
+#[test]
+fn test_message_view_allocations() {
+    let bytes = request();
+
+    let view_allocations = count_allocations(|| {
+        let view = MessageView::new(&bytes).unwrap();
+        assert_eq!(view.message_type(), Some(DhcpMessageType::Request));
+        assert!(view.option(OptionTag::ServerIdentifier).is_some());
+    });
+
+    let read_allocations = count_allocations(|| {
+        let mut buf = ReadBuffer::new(&bytes);
+        let message = Message::read::<BigEndian>(&mut buf).unwrap();
+        assert_eq!(message.get_message_type(), Some(&DhcpMessageType::Request));
+    });
+
+    println!("MessageView: {view_allocations} allocations, Message::read: {read_allocations} allocations");
+
+    assert_eq!(view_allocations, 0);
+    assert!(read_allocations > 0);
+}
@@ -0,0 +1,176 @@
+//! Guards the public surface of the crate. Every item re-exported by
+//! `src/lib.rs`, and every public item of the public modules like `types`
+//! and `v6`, has to be listed in `tests/public-api.txt`, so additions and
+//! removals show up in review. Regenerate the snapshot with
+//! `UPDATE_PUBLIC_API=1 cargo test --test public_api`.
+
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT: &str = include_str!("public-api.txt");
+
+/// Keywords of the items collected from the modules, longest first so
+/// `const fn` isn't taken for a `const`.
+const ITEM_KEYWORDS: [&str; 10] = [
+    "const fn ",
+    "async fn ",
+    "unsafe fn ",
+    "struct ",
+    "enum ",
+    "trait ",
+    "type ",
+    "const ",
+    "static ",
+    "fn ",
+];
+
+/// Returns the file of the module `name` declared in `file`.
+fn module_file(file: &Path, name: &str) -> PathBuf {
+    let dir = match file.file_name().and_then(|name| name.to_str()) {
+        Some("lib.rs" | "mod.rs") => file.parent().unwrap().to_path_buf(),
+        _ => file.with_extension(""),
+    };
+
+    let flat = dir.join(format!("{name}.rs"));
+    match flat.exists() {
+        true => flat,
+        false => dir.join(name).join("mod.rs"),
+    }
+}
+
+/// Returns the name of the item declared by `line`, like `Message` for
+/// `pub struct Message {`.
+fn item_name(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("pub ")?;
+    let rest = ITEM_KEYWORDS
+        .iter()
+        .find_map(|keyword| rest.strip_prefix(keyword))?;
+
+    let end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    Some(&rest[..end])
+}
+
+/// Collects the public items of `module`, which is declared in `file`, one
+/// path per entry. These are the public modules and their items, the
+/// re-exported items and, for glob re-exports, the public items of the
+/// re-exported module. Items behind `cfg` attributes, or in gated modules,
+/// are suffixed with ` (cfg)`.
+fn collect(module: &str, file: &Path, suffix: &str, items: &mut Vec<String>) {
+    let src = std::fs::read_to_string(file).unwrap();
+    let mut current = module.to_string();
+    let mut indent = 0;
+    let mut gated = false;
+    let mut lines = src.lines();
+
+    while let Some(raw) = lines.next() {
+        let line = raw.trim();
+
+        // End of an inline module
+        if indent > 0 && raw == "}" {
+            current = module.to_string();
+            indent = 0;
+            continue;
+        }
+
+        // Only items of the module itself count, not those in function
+        // bodies, impl blocks or private inline modules
+        if raw.len() - raw.trim_start().len() != indent {
+            continue;
+        }
+
+        if line.starts_with("#[cfg(") {
+            gated = true;
+            continue;
+        }
+
+        // Other attributes and docs between a cfg and its item
+        if line.starts_with("#[") || line.starts_with("//") {
+            continue;
+        }
+
+        let suffix = if std::mem::take(&mut gated) {
+            " (cfg)"
+        } else {
+            suffix
+        };
+
+        if let Some(name) = line.strip_prefix("pub mod ") {
+            let name = name.trim_end_matches([';', '{', ' ']);
+            items.push(format!("{current}::{name}{suffix}"));
+
+            match line.ends_with('{') {
+                true => {
+                    current = format!("{current}::{name}");
+                    indent = 4;
+                }
+                false => collect(
+                    &format!("{current}::{name}"),
+                    &module_file(file, name),
+                    suffix,
+                    items,
+                ),
+            }
+            continue;
+        }
+
+        let Some(stmt) = line.strip_prefix("pub use ") else {
+            if let Some(name) = item_name(line) {
+                items.push(format!("{current}::{name}{suffix}"));
+            }
+            continue;
+        };
+
+        let mut stmt = stmt.to_string();
+        while !stmt.ends_with(';') {
+            match lines.next() {
+                Some(next) => stmt.push_str(next.trim()),
+                None => break,
+            }
+        }
+
+        let stmt = stmt.trim_end_matches(';');
+
+        // Glob re-exports of child modules pull in all of their items
+        if let Some(child) = stmt.strip_suffix("::*") {
+            collect(&current, &module_file(file, child), suffix, items);
+            continue;
+        }
+
+        let names = match stmt.split_once("::{") {
+            Some((_, names)) => names.trim_end_matches('}').split(',').collect(),
+            None => vec![stmt.rsplit("::").next().unwrap()],
+        };
+
+        for name in names.into_iter().map(str::trim).filter(|n| !n.is_empty()) {
+            let name = name.rsplit(" as ").next().unwrap();
+            items.push(format!("{current}::{name}{suffix}"));
+        }
+    }
+}
+
+fn public_items() -> Vec<String> {
+    let lib = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs");
+    let mut items = Vec::new();
+    collect("dhcp", &lib, "", &mut items);
+
+    items.sort();
+    items.dedup();
+    items
+}
+
+#[test]
+fn test_public_api_snapshot() {
+    let actual = public_items().join("\n") + "\n";
+
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/public-api.txt");
+        std::fs::write(path, &actual).unwrap();
+        return;
+    }
+
+    assert_eq!(
+        actual, SNAPSHOT,
+        "the public API changed, update tests/public-api.txt if this is intended"
+    );
+}
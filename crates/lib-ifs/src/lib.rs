@@ -1,5 +1,10 @@
 use std::{
-    error::Error, fmt::Display, marker::PhantomData, ptr::NonNull, slice::from_raw_parts,
+    error::Error,
+    fmt::Display,
+    marker::PhantomData,
+    net::{Ipv4Addr, Ipv6Addr},
+    ptr::NonNull,
+    slice::from_raw_parts,
     string::FromUtf8Error,
 };
 
@@ -93,6 +98,139 @@ pub fn if_nameindex() -> Result<Interfaces, InterfacesError> {
     }
 }
 
+/// IPv4 address assigned to a network interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddr {
+    pub name: String,
+    pub addr: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+/// Returns all IPv4 addresses assigned to the network interfaces of this
+/// host. Interfaces can appear multiple times if they have multiple
+/// addresses assigned.
+pub fn if_addrs() -> Result<Vec<InterfaceAddr>, InterfacesError> {
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+
+    if unsafe { libc::getifaddrs(&mut ifap) } != 0 {
+        return Err(InterfacesError(
+            "failed to retrieve network interface addresses".into(),
+        ));
+    }
+
+    let mut addrs = Vec::new();
+    let mut ptr = ifap;
+
+    while !ptr.is_null() {
+        let ifa = unsafe { &*ptr };
+        ptr = ifa.ifa_next;
+
+        if ifa.ifa_addr.is_null() || ifa.ifa_netmask.is_null() {
+            continue;
+        }
+
+        if unsafe { (*ifa.ifa_addr).sa_family } as i32 != libc::AF_INET {
+            continue;
+        }
+
+        let addr = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+        let netmask = unsafe { &*(ifa.ifa_netmask as *const libc::sockaddr_in) };
+        let name = if_name_to_slice(ifa.ifa_name);
+
+        addrs.push(InterfaceAddr {
+            name: String::from_utf8_lossy(name).into_owned(),
+            addr: Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+            netmask: Ipv4Addr::from(u32::from_be(netmask.sin_addr.s_addr)),
+        });
+    }
+
+    unsafe { libc::freeifaddrs(ifap) };
+    Ok(addrs)
+}
+
+/// IPv6 address assigned to a network interface. The scope ID is the index
+/// of the interface for link-local addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddrV6 {
+    pub name: String,
+    pub addr: Ipv6Addr,
+    pub scope_id: u32,
+}
+
+/// Returns all IPv6 addresses assigned to the network interfaces of this
+/// host.
+pub fn if_addrs_v6() -> Result<Vec<InterfaceAddrV6>, InterfacesError> {
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+
+    if unsafe { libc::getifaddrs(&mut ifap) } != 0 {
+        return Err(InterfacesError(
+            "failed to retrieve network interface addresses".into(),
+        ));
+    }
+
+    let mut addrs = Vec::new();
+    let mut ptr = ifap;
+
+    while !ptr.is_null() {
+        let ifa = unsafe { &*ptr };
+        ptr = ifa.ifa_next;
+
+        if ifa.ifa_addr.is_null() {
+            continue;
+        }
+
+        if unsafe { (*ifa.ifa_addr).sa_family } as i32 != libc::AF_INET6 {
+            continue;
+        }
+
+        let addr = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in6) };
+        let name = if_name_to_slice(ifa.ifa_name);
+
+        addrs.push(InterfaceAddrV6 {
+            name: String::from_utf8_lossy(name).into_owned(),
+            addr: Ipv6Addr::from(addr.sin6_addr.s6_addr),
+            scope_id: addr.sin6_scope_id,
+        });
+    }
+
+    unsafe { libc::freeifaddrs(ifap) };
+    Ok(addrs)
+}
+
+/// Returns the MTU of the network interface named `name`.
+pub fn if_mtu(name: &str) -> Result<u32, InterfacesError> {
+    let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+
+    // The name has to leave room for the terminating NUL byte
+    if name.is_empty() || name.len() >= req.ifr_name.len() {
+        return Err(InterfacesError(format!("invalid interface name '{name}'")));
+    }
+
+    for (dst, src) in req.ifr_name.iter_mut().zip(name.as_bytes()) {
+        *dst = *src as libc::c_char;
+    }
+
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(InterfacesError(format!(
+            "failed to retrieve MTU of interface '{name}': {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    let res = unsafe { libc::ioctl(fd, libc::SIOCGIFMTU as _, &mut req) };
+    let err = std::io::Error::last_os_error();
+    unsafe { libc::close(fd) };
+
+    if res < 0 {
+        return Err(InterfacesError(format!(
+            "failed to retrieve MTU of interface '{name}': {err}"
+        )));
+    }
+
+    Ok(unsafe { req.ifr_ifru.ifru_mtu } as u32)
+}
+
 fn if_name_to_slice<'a>(if_name: *mut i8) -> &'a [u8] {
     let data = if_name as *const libc::c_char;
     let len = unsafe { libc::strlen(data) };
@@ -114,4 +252,40 @@ mod tests {
             println!("{}", ifa.name())
         }
     }
+
+    #[test]
+    fn retrieve_interface_addrs_v6() {
+        let addrs = match if_addrs_v6() {
+            Ok(addrs) => addrs,
+            Err(err) => panic!("{err}"),
+        };
+
+        for addr in addrs {
+            println!("{}: {}%{}", addr.name, addr.addr, addr.scope_id)
+        }
+    }
+
+    #[test]
+    fn retrieve_interface_mtu() {
+        let mtu = match if_mtu("lo") {
+            Ok(mtu) => mtu,
+            Err(err) => panic!("{err}"),
+        };
+
+        assert!(mtu > 0);
+        assert!(if_mtu("does-not-exist0").is_err());
+        assert!(if_mtu("").is_err());
+    }
+
+    #[test]
+    fn retrieve_interface_addrs() {
+        let addrs = match if_addrs() {
+            Ok(addrs) => addrs,
+            Err(err) => panic!("{err}"),
+        };
+
+        for addr in addrs {
+            println!("{}: {}/{}", addr.name, addr.addr, addr.netmask)
+        }
+    }
 }